@@ -1,17 +1,23 @@
 use criterion::{Criterion, black_box, criterion_group, criterion_main};
-use screenshot_tool::{
-    snapshot::capture::capture_display_screenshot, snapshot::types::SnapshotConfig,
-};
+use cli_vision::snapshot::{CaptureBackend, MockFramebuffer, Vt100Parser};
 
 fn benchmark_screenshot(c: &mut Criterion) {
-    let config = SnapshotConfig::default();
-
-    c.bench_function("screenshot_capture", |b| {
+    c.bench_function("mock_capture", |b| {
+        let mut fb = MockFramebuffer::with_color(800, 600, [50, 50, 50]);
+        fb.draw_text(10, 10, "Benchmark", [255, 255, 255], [50, 50, 50]);
         b.iter(|| {
-            let result = unsafe { capture_display_screenshot(black_box(&config)) };
+            let result = fb.capture();
             assert!(result.is_ok());
         })
     });
+
+    c.bench_function("terminal_render", |b| {
+        let mut parser = Vt100Parser::new(120, 40);
+        for byte in b"\x1b[1;32mHello\x1b[0m \x1b[7mWorld\x1b[0m\r\n".repeat(40) {
+            parser.process_byte(byte);
+        }
+        b.iter(|| black_box(parser.terminal().render_to_image()))
+    });
 }
 
 criterion_group!(benches, benchmark_screenshot);
@@ -48,5 +48,6 @@ fn create_cli_harness_config() -> HarnessConfig {
                 expected_description: Some("Button action executed.".to_string()),
             },
         ],
+        vlm: None,
     }
 }
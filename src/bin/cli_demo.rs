@@ -545,23 +545,19 @@ fn main() -> Result<(), Box<dyn Error>> {
                 if let Event::Key(key) = event::read()? {
                     match key.code {
                         KeyCode::Char('q') | KeyCode::Char('Q') => break,
-                        KeyCode::Left => {
-                            if app.selected_button > 0 {
-                                app.selected_button -= 1;
-                                status_bar.update(format!(
-                                    "Navigated to {}",
-                                    buttons[app.selected_button].label
-                                ));
-                            }
+                        KeyCode::Left if app.selected_button > 0 => {
+                            app.selected_button -= 1;
+                            status_bar.update(format!(
+                                "Navigated to {}",
+                                buttons[app.selected_button].label
+                            ));
                         }
-                        KeyCode::Right => {
-                            if app.selected_button < buttons.len() - 1 {
-                                app.selected_button += 1;
-                                status_bar.update(format!(
-                                    "Navigated to {}",
-                                    buttons[app.selected_button].label
-                                ));
-                            }
+                        KeyCode::Right if app.selected_button < buttons.len() - 1 => {
+                            app.selected_button += 1;
+                            status_bar.update(format!(
+                                "Navigated to {}",
+                                buttons[app.selected_button].label
+                            ));
                         }
                         KeyCode::Enter => {
                             match app.selected_button {
@@ -1,4 +1,5 @@
 use cli_vision::harness::{HarnessConfig, StateConfig};
+use cli_vision::vlm::VlmConfig;
 use std::path::PathBuf;
 
 fn main() {
@@ -15,13 +16,17 @@ fn main() {
                 "Status bar shows uptime, progress bar at 0%, Increment button selected.".to_string(),
             ),
         }],
+        vlm: std::env::var("CLI_VISION_VLM_ENDPOINT").ok().map(VlmConfig::new),
     };
 
     match cli_vision::harness::run_harness(&config) {
         Ok(results) => {
             println!("Harness completed successfully");
-            for (name, snapshot) in results {
-                println!("State: {} -> {}", name, snapshot.image_path.display());
+            for result in results {
+                println!("State: {} -> {}", result.name, result.snapshot.image_path.display());
+                if let Some(verdict) = result.verdict {
+                    println!("  {}: {}", if verdict.pass { "PASS" } else { "FAIL" }, verdict.reasoning);
+                }
             }
         }
         Err(e) => {
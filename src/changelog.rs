@@ -0,0 +1,348 @@
+//! Changelogs of visual changes between releases.
+//!
+//! Given the captures of two releases (a `run` session directory, or a
+//! `suite` output directory with one subdirectory per scenario), the states
+//! of each scenario are aligned by perceptual hash as in [`crate::compare`]
+//! and every screen that changed, appeared or disappeared is listed in a
+//! Markdown changelog with before/after thumbnails, ready to paste into
+//! release notes. A VLM can write a one-sentence summary of each change.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::changelog::{diff_releases, write_changelog};
+//! use cli_vision::compare::DEFAULT_MATCH_THRESHOLD;
+//!
+//! let changes = diff_releases("v1/".as_ref(), "v2/".as_ref(), DEFAULT_MATCH_THRESHOLD, |_, _| None).unwrap();
+//! write_changelog(&changes, "CHANGES.md".as_ref()).unwrap();
+//! ```
+
+use image::{GenericImage, ImageFormat, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use crate::compare::{align_states, hash_distance, list_state_images, perceptual_hash, StateAlignment};
+use crate::snapshot::{SnapshotError, SnapshotResult};
+use crate::vlm::{analyze_image, VlmConfig, VlmError, VlmResult};
+
+/// Width of the thumbnails linked from the changelog
+pub const THUMBNAIL_WIDTH: u32 = 480;
+
+/// Prompt asking the VLM to summarize a before/after pair
+const CHANGE_PROMPT: &str = "This image shows two versions of the same terminal screen side by side: \
+     the previous release on the left and the new release on the right, separated by a white bar. \
+     In one sentence written for release notes, describe what changed for the user. \
+     Do not mention the image layout.";
+
+/// What happened to a screen between releases
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// The screen exists in both releases but looks different
+    Changed,
+    /// The screen only exists in the new release
+    Added,
+    /// The screen only exists in the old release
+    Removed,
+}
+
+/// One screen that differs between releases
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScreenChange {
+    /// What happened to the screen
+    pub kind: ChangeKind,
+    /// Screenshot in the old release
+    pub old: Option<PathBuf>,
+    /// Screenshot in the new release
+    pub new: Option<PathBuf>,
+    /// Normalized perceptual distance between the two screenshots (changed screens only)
+    pub distance: Option<f64>,
+    /// Summary of the change, if one was written
+    pub summary: Option<String>,
+}
+
+impl ScreenChange {
+    /// Label such as `state 3 (enter)`, from the newest screenshot's file name
+    pub fn label(&self) -> String {
+        let path = self.new.as_ref().or(self.old.as_ref());
+        let stem = path.and_then(|path| path.file_stem()).map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let rest = stem.strip_prefix("state_").unwrap_or(&stem);
+        match rest.split_once('_') {
+            Some((step, input)) => format!("state {} ({})", step, input),
+            None => format!("state {}", rest),
+        }
+    }
+}
+
+/// Changed screens of one scenario
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScenarioChanges {
+    /// Scenario name, from its directory name
+    pub name: String,
+    /// Changed, added and removed screens in order
+    pub changes: Vec<ScreenChange>,
+}
+
+/// Scenarios of a release directory as `(name, dir)` pairs.
+///
+/// A directory holding state screenshots is a single scenario named after
+/// it; otherwise each subdirectory holding them is one, with the `NN_`
+/// index prefix of suite output directories dropped from its name.
+pub fn find_scenarios(dir: &Path) -> std::io::Result<Vec<(String, PathBuf)>> {
+    let dir_name = |dir: &Path| dir.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    if !list_state_images(dir)?.is_empty() {
+        return Ok(vec![(dir_name(dir), dir.to_path_buf())]);
+    }
+    let mut scenarios = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() && !list_state_images(&path)?.is_empty() {
+            let name = dir_name(&path);
+            let name = match name.split_once('_') {
+                Some((index, rest)) if !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()) => rest.to_string(),
+                _ => name,
+            };
+            scenarios.push((name, path));
+        }
+    }
+    scenarios.sort();
+    Ok(scenarios)
+}
+
+/// Compare the captures of two releases, scenario by scenario.
+///
+/// `summarize` is called with the old and new PNG of every changed screen and
+/// may return a summary of the change. Scenarios only present in one release
+/// list all of their screens as added or removed.
+pub fn diff_releases<F>(old_dir: &Path, new_dir: &Path, threshold: f64, mut summarize: F) -> SnapshotResult<Vec<ScenarioChanges>>
+where
+    F: FnMut(&[u8], &[u8]) -> Option<String>,
+{
+    let old = find_scenarios(old_dir)?;
+    let new = find_scenarios(new_dir)?;
+    // A single session on each side is the same scenario whatever the directories are called
+    let single = old.len() == 1 && new.len() == 1;
+
+    let mut names: Vec<&String> = match single {
+        true => vec![&new[0].0],
+        false => old.iter().chain(&new).map(|(name, _)| name).collect(),
+    };
+    names.sort();
+    names.dedup();
+
+    let mut result = Vec::new();
+    for name in names {
+        let find = |scenarios: &[(String, PathBuf)]| {
+            scenarios.iter().find(|(other, _)| single || other == name).map(|(_, dir)| dir.clone())
+        };
+        let old_images = find(&old).map(|dir| list_state_images(&dir)).transpose()?.unwrap_or_default();
+        let new_images = find(&new).map(|dir| list_state_images(&dir)).transpose()?.unwrap_or_default();
+        let changes = diff_states(&old_images, &new_images, threshold, &mut summarize)?;
+        result.push(ScenarioChanges { name: name.clone(), changes });
+    }
+    Ok(result)
+}
+
+fn diff_states<F>(old: &[PathBuf], new: &[PathBuf], threshold: f64, summarize: &mut F) -> SnapshotResult<Vec<ScreenChange>>
+where
+    F: FnMut(&[u8], &[u8]) -> Option<String>,
+{
+    let read = |paths: &[PathBuf]| paths.iter().map(fs::read).collect::<std::io::Result<Vec<_>>>();
+    let (old_pngs, new_pngs) = (read(old)?, read(new)?);
+    let hash = |pngs: &[Vec<u8>]| pngs.iter().map(|png| perceptual_hash(png)).collect::<SnapshotResult<Vec<_>>>();
+    let (old_hashes, new_hashes) = (hash(&old_pngs)?, hash(&new_pngs)?);
+
+    let mut changes = Vec::new();
+    for alignment in align_states(&old_hashes, &new_hashes, threshold, |a, b| hash_distance(*a, *b)) {
+        let change = match alignment {
+            // The renderer is deterministic, so equal screens have equal bytes
+            StateAlignment::Matched { baseline, current, .. } if old_pngs[baseline] == new_pngs[current] => continue,
+            StateAlignment::Matched { baseline, current, distance } => ScreenChange {
+                kind: ChangeKind::Changed,
+                old: Some(old[baseline].clone()),
+                new: Some(new[current].clone()),
+                distance: Some(distance),
+                summary: summarize(&old_pngs[baseline], &new_pngs[current]),
+            },
+            StateAlignment::Inserted { current } => ScreenChange {
+                kind: ChangeKind::Added,
+                old: None,
+                new: Some(new[current].clone()),
+                distance: None,
+                summary: None,
+            },
+            StateAlignment::Removed { baseline } => ScreenChange {
+                kind: ChangeKind::Removed,
+                old: Some(old[baseline].clone()),
+                new: None,
+                distance: None,
+                summary: None,
+            },
+        };
+        changes.push(change);
+    }
+    Ok(changes)
+}
+
+/// Place two screenshots side by side, separated by a white bar
+pub fn side_by_side(left_png: &[u8], right_png: &[u8]) -> SnapshotResult<Vec<u8>> {
+    const GAP: u32 = 8;
+    let load = |png: &[u8]| {
+        image::load_from_memory(png)
+            .map(|img| img.to_rgb8())
+            .map_err(|e| SnapshotError::Capture(format!("Failed to load PNG: {}", e)))
+    };
+    let (left, right) = (load(left_png)?, load(right_png)?);
+    let height = left.height().max(right.height());
+    let mut combined = RgbImage::from_pixel(left.width() + GAP + right.width(), height, image::Rgb([255, 255, 255]));
+    combined.copy_from(&left, 0, 0).and_then(|_| combined.copy_from(&right, left.width() + GAP, 0)).map_err(|e| SnapshotError::Capture(e.to_string()))?;
+    encode_png(&combined)
+}
+
+/// Ask the VLM for a one-sentence summary of how a screen changed
+pub fn summarize_change(config: &VlmConfig, old_png: &[u8], new_png: &[u8]) -> VlmResult<String> {
+    let pair = side_by_side(old_png, new_png).map_err(|e| VlmError::InvalidResponse(e.to_string()))?;
+    Ok(analyze_image(config, &pair, CHANGE_PROMPT)?.trim().to_string())
+}
+
+/// Render the changelog as Markdown; thumbnails are linked from `images_dir` (relative to the file)
+pub fn render_changelog(scenarios: &[ScenarioChanges], images_dir: &str) -> String {
+    let count = |kind| scenarios.iter().flat_map(|s| &s.changes).filter(|c| c.kind == kind).count();
+    let mut out = String::from("# UI changes\n\n");
+    out.push_str(&format!(
+        "{} changed, {} new and {} removed screens across {} scenarios.\n",
+        count(ChangeKind::Changed),
+        count(ChangeKind::Added),
+        count(ChangeKind::Removed),
+        scenarios.len()
+    ));
+
+    let mut unchanged = Vec::new();
+    for scenario in scenarios {
+        if scenario.changes.is_empty() {
+            unchanged.push(scenario.name.as_str());
+            continue;
+        }
+        out.push_str(&format!("\n## {}\n", scenario.name));
+        for (index, change) in scenario.changes.iter().enumerate() {
+            let thumbnail = |side: &str| format!("![{}]({}/{})", side, images_dir, thumbnail_name(&scenario.name, index, side));
+            let heading = match change.kind {
+                ChangeKind::Changed => "changed",
+                ChangeKind::Added => "new",
+                ChangeKind::Removed => "removed",
+            };
+            out.push_str(&format!("\n### {} ({})\n\n", capitalize(&change.label()), heading));
+            if let Some(summary) = &change.summary {
+                out.push_str(&format!("{}\n\n", summary));
+            }
+            match change.kind {
+                ChangeKind::Changed => out.push_str(&format!(
+                    "| Before | After |\n|--------|-------|\n| {} | {} |\n",
+                    thumbnail("before"),
+                    thumbnail("after")
+                )),
+                ChangeKind::Added => out.push_str(&format!("{}\n", thumbnail("after"))),
+                ChangeKind::Removed => out.push_str(&format!("{}\n", thumbnail("before"))),
+            }
+        }
+    }
+    if !unchanged.is_empty() {
+        out.push_str(&format!("\nUnchanged: {}.\n", unchanged.join(", ")));
+    }
+    out
+}
+
+/// Write the Markdown changelog to `output` and its thumbnails to `<output stem>_images/` beside it
+pub fn write_changelog(scenarios: &[ScenarioChanges], output: &Path) -> SnapshotResult<()> {
+    let stem = output.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_else(|| "CHANGES".to_string());
+    let images_dir_name = format!("{}_images", stem);
+    let images_dir = output.parent().unwrap_or(Path::new("")).join(&images_dir_name);
+    fs::create_dir_all(&images_dir)?;
+
+    for scenario in scenarios {
+        for (index, change) in scenario.changes.iter().enumerate() {
+            for (side, path) in [("before", &change.old), ("after", &change.new)] {
+                if let Some(path) = path {
+                    let thumbnail = thumbnail(&fs::read(path)?, THUMBNAIL_WIDTH)?;
+                    fs::write(images_dir.join(thumbnail_name(&scenario.name, index, side)), thumbnail)?;
+                }
+            }
+        }
+    }
+    fs::write(output, render_changelog(scenarios, &images_dir_name))?;
+    Ok(())
+}
+
+/// Scale a screenshot down to at most `max_width` pixels wide
+pub fn thumbnail(png: &[u8], max_width: u32) -> SnapshotResult<Vec<u8>> {
+    let img = image::load_from_memory(png).map_err(|e| SnapshotError::Capture(format!("Failed to load PNG: {}", e)))?;
+    if img.width() <= max_width {
+        return Ok(png.to_vec());
+    }
+    encode_png(&img.thumbnail(max_width, u32::MAX).to_rgb8())
+}
+
+fn encode_png(img: &RgbImage) -> SnapshotResult<Vec<u8>> {
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| SnapshotError::Capture(format!("Failed to encode PNG: {}", e)))?;
+    Ok(png)
+}
+
+fn thumbnail_name(scenario: &str, index: usize, side: &str) -> String {
+    let scenario: String = scenario.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect();
+    format!("{}_{}_{}.png", scenario, index + 1, side)
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid_png(width: u32, color: [u8; 3]) -> Vec<u8> {
+        encode_png(&RgbImage::from_pixel(width, 8, image::Rgb(color))).unwrap()
+    }
+
+    #[test]
+    fn test_release_diff_lists_changed_and_new_screens() {
+        let (old, new) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        for dir in [old.path(), new.path()] {
+            fs::create_dir_all(dir.join("01_settings")).unwrap();
+            fs::write(dir.join("01_settings/state_0_initial.png"), solid_png(16, [0, 0, 0])).unwrap();
+        }
+        fs::write(old.path().join("01_settings/state_1_s.png"), solid_png(16, [10, 10, 10])).unwrap();
+        fs::write(new.path().join("01_settings/state_1_s.png"), solid_png(16, [12, 10, 10])).unwrap();
+        fs::create_dir_all(new.path().join("02_help")).unwrap();
+        fs::write(new.path().join("02_help/state_0_initial.png"), solid_png(16, [0, 0, 0])).unwrap();
+
+        let changes = diff_releases(old.path(), new.path(), 0.25, |_, _| Some("The title is brighter.".to_string())).unwrap();
+        assert_eq!(changes.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), ["help", "settings"]);
+        assert_eq!(changes[0].changes[0].kind, ChangeKind::Added);
+        assert_eq!(changes[1].changes.len(), 1);
+        assert_eq!(changes[1].changes[0].kind, ChangeKind::Changed);
+        assert_eq!(changes[1].changes[0].label(), "state 1 (s)");
+
+        let markdown = render_changelog(&changes, "CHANGES_images");
+        assert!(markdown.contains("1 changed, 1 new and 0 removed screens across 2 scenarios."));
+        assert!(markdown.contains("### State 1 (s) (changed)\n\nThe title is brighter.\n"));
+        assert!(markdown.contains("![after](CHANGES_images/help_1_after.png)"));
+    }
+
+    #[test]
+    fn test_thumbnail_and_side_by_side_sizes() {
+        let wide = solid_png(960, [1, 2, 3]);
+        let small = image::load_from_memory(&thumbnail(&wide, 480).unwrap()).unwrap();
+        assert_eq!((small.width(), small.height()), (480, 4));
+        let pair = image::load_from_memory(&side_by_side(&wide, &solid_png(16, [0, 0, 0])).unwrap()).unwrap();
+        assert_eq!(pair.width(), 960 + 8 + 16);
+    }
+}
@@ -0,0 +1,109 @@
+//! Follow-up questions about a captured state.
+//!
+//! A [`ChatSession`] holds a conversation with the VLM about one state
+//! screenshot of a session directory. Every answered question is saved to
+//! `chat_state_<N>.json` next to the screenshot, so reopening the same step
+//! continues where the last conversation stopped and the transcript stays
+//! with the captures it is about.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::chat::ChatSession;
+//! use cli_vision::vlm::VlmConfig;
+//!
+//! let mut chat = ChatSession::open("/tmp/cli-vision/run-1".as_ref(), 3).unwrap();
+//! let config = VlmConfig::new("http://127.0.0.1:8080/v1/chat/completions");
+//! let answer = chat.ask(&config, "Why is the footer cut off?", |_| {}).unwrap();
+//! println!("{}", answer);
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::compare::{list_state_images, state_step};
+use crate::vlm::{chat_about_image, ChatMessage, VlmConfig, VlmProgress, VlmResult};
+
+/// A saved conversation about one state screenshot
+#[derive(Debug, Clone)]
+pub struct ChatSession {
+    /// State number the conversation is about
+    pub step: usize,
+    /// The state's screenshot
+    pub screenshot: PathBuf,
+    /// Where the conversation is saved
+    pub transcript_path: PathBuf,
+    /// Questions and answers so far
+    pub messages: Vec<ChatMessage>,
+}
+
+/// Screenshot of state `step` in a session directory
+pub fn find_state_image(dir: &Path, step: usize) -> io::Result<Option<PathBuf>> {
+    Ok(list_state_images(dir)?.into_iter().find(|path| state_step(path) == Some(step)))
+}
+
+impl ChatSession {
+    /// Open the conversation about state `step` of a session, loading any saved turns
+    pub fn open(dir: &Path, step: usize) -> io::Result<Self> {
+        let screenshot = find_state_image(dir, step)?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no screenshot for state {} in {}", step, dir.display()))
+        })?;
+        let transcript_path = dir.join(format!("chat_state_{}.json", step));
+        let messages = match fs::read(&transcript_path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            step,
+            screenshot,
+            transcript_path,
+            messages,
+        })
+    }
+
+    /// Ask a question with the conversation so far and save both it and the answer
+    pub fn ask<F>(&mut self, config: &VlmConfig, question: &str, on_progress: F) -> VlmResult<String>
+    where
+        F: FnMut(VlmProgress),
+    {
+        let image = fs::read(&self.screenshot)?;
+        let mut turns = self.messages.clone();
+        turns.push(ChatMessage::user(question));
+        let answer = chat_about_image(config, &image, &turns, on_progress)?;
+        turns.push(ChatMessage::assistant(answer.clone()));
+        self.messages = turns;
+        self.save()?;
+        Ok(answer)
+    }
+
+    /// Write the conversation to [`ChatSession::transcript_path`]
+    pub fn save(&self) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.messages).map_err(io::Error::other)?;
+        fs::write(&self.transcript_path, json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_finds_state_and_resumes_transcript() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("state_0_initial.png"), b"png").unwrap();
+        fs::write(dir.path().join("state_3_enter.png"), b"png").unwrap();
+
+        let mut chat = ChatSession::open(dir.path(), 3).unwrap();
+        assert!(chat.screenshot.ends_with("state_3_enter.png"));
+        assert!(chat.messages.is_empty());
+        assert!(ChatSession::open(dir.path(), 2).is_err());
+
+        chat.messages = vec![ChatMessage::user("What is selected?"), ChatMessage::assistant("The second row.")];
+        chat.save().unwrap();
+        let resumed = ChatSession::open(dir.path(), 3).unwrap();
+        assert_eq!(resumed.messages, chat.messages);
+        assert!(resumed.transcript_path.ends_with("chat_state_3.json"));
+    }
+}
@@ -0,0 +1,293 @@
+//! Comparison of captured sessions against a baseline.
+//!
+//! Sessions rarely line up index-for-index: a new confirmation dialog adds a
+//! step, a removed splash screen drops one. This module aligns the states of
+//! two sessions by visual or textual similarity instead of strict step
+//! numbers, reporting inserted and removed states explicitly.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::compare::align_session_dirs;
+//!
+//! let alignment = align_session_dirs("baseline/".as_ref(), "current/".as_ref(), 0.25).unwrap();
+//! for entry in &alignment {
+//!     println!("{:?}", entry);
+//! }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::snapshot::{SnapshotError, SnapshotResult};
+
+/// Default maximum normalized distance for two states to be considered the same screen
+pub const DEFAULT_MATCH_THRESHOLD: f64 = 0.25;
+
+/// One entry of an alignment between a baseline and a current session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StateAlignment {
+    /// A baseline state paired with its closest current state
+    Matched {
+        /// Index into the baseline states
+        baseline: usize,
+        /// Index into the current states
+        current: usize,
+        /// Normalized distance between the two states (0.0 = identical)
+        distance: f64,
+    },
+    /// A state only present in the current session (e.g. a new dialog)
+    Inserted {
+        /// Index into the current states
+        current: usize,
+    },
+    /// A state only present in the baseline session
+    Removed {
+        /// Index into the baseline states
+        baseline: usize,
+    },
+}
+
+/// Compute a 64-bit average hash of a PNG image.
+///
+/// The image is reduced to 8x8 grayscale and each bit records whether the
+/// pixel is brighter than the mean, so small rendering differences map to
+/// nearby hashes.
+pub fn perceptual_hash(png_data: &[u8]) -> SnapshotResult<u64> {
+    let img = image::load_from_memory(png_data)
+        .map_err(|e| SnapshotError::Capture(format!("Failed to load PNG: {}", e)))?;
+    let small = img
+        .resize_exact(8, 8, image::imageops::FilterType::Triangle)
+        .to_luma8();
+
+    let pixels: Vec<u32> = small.pixels().map(|p| u32::from(p.0[0])).collect();
+    let mean = pixels.iter().sum::<u32>() / pixels.len() as u32;
+
+    let mut hash = 0u64;
+    for (i, value) in pixels.iter().enumerate() {
+        if *value > mean {
+            hash |= 1 << i;
+        }
+    }
+    Ok(hash)
+}
+
+/// Normalized distance between two perceptual hashes (0.0 = identical, 1.0 = inverted)
+pub fn hash_distance(a: u64, b: u64) -> f64 {
+    f64::from((a ^ b).count_ones()) / 64.0
+}
+
+/// Normalized distance between two text dumps (0.0 = identical, 1.0 = nothing in common).
+///
+/// Compares the screens character by character, line by line, so it is
+/// cheap and stable for fixed-size terminal grids.
+pub fn text_distance(a: &str, b: &str) -> f64 {
+    let a_lines: Vec<&str> = a.lines().collect();
+    let b_lines: Vec<&str> = b.lines().collect();
+    let rows = a_lines.len().max(b_lines.len());
+
+    let mut total = 0usize;
+    let mut differing = 0usize;
+    for row in 0..rows {
+        let a_chars: Vec<char> = a_lines.get(row).map(|l| l.chars().collect()).unwrap_or_default();
+        let b_chars: Vec<char> = b_lines.get(row).map(|l| l.chars().collect()).unwrap_or_default();
+        let cols = a_chars.len().max(b_chars.len());
+        for col in 0..cols {
+            let ca = a_chars.get(col).copied().unwrap_or(' ');
+            let cb = b_chars.get(col).copied().unwrap_or(' ');
+            // Blank-vs-blank cells carry no signal
+            if ca == ' ' && cb == ' ' {
+                continue;
+            }
+            total += 1;
+            if ca != cb {
+                differing += 1;
+            }
+        }
+    }
+
+    if total == 0 {
+        0.0
+    } else {
+        differing as f64 / total as f64
+    }
+}
+
+/// Align two sequences of states using a distance function.
+///
+/// Performs a global (Needleman-Wunsch style) alignment where pairing two
+/// states costs their distance and skipping a state costs `threshold`, so
+/// states further apart than the threshold are reported as inserted/removed
+/// rather than force-matched. Order is preserved on both sides.
+pub fn align_states<T, F>(baseline: &[T], current: &[T], threshold: f64, distance: F) -> Vec<StateAlignment>
+where
+    F: Fn(&T, &T) -> f64,
+{
+    let n = baseline.len();
+    let m = current.len();
+    let gap = threshold;
+
+    // cost[i][j] = best cost aligning baseline[..i] with current[..j]
+    let mut cost = vec![vec![0.0f64; m + 1]; n + 1];
+    for (i, row) in cost.iter_mut().enumerate() {
+        row[0] = i as f64 * gap;
+    }
+    for (j, cell) in cost[0].iter_mut().enumerate() {
+        *cell = j as f64 * gap;
+    }
+
+    let mut distances = vec![vec![0.0f64; m]; n];
+    for i in 0..n {
+        for j in 0..m {
+            distances[i][j] = distance(&baseline[i], &current[j]);
+        }
+    }
+
+    for i in 1..=n {
+        for j in 1..=m {
+            let d = distances[i - 1][j - 1];
+            let matched = if d <= threshold { cost[i - 1][j - 1] + d } else { f64::INFINITY };
+            let removed = cost[i - 1][j] + gap;
+            let inserted = cost[i][j - 1] + gap;
+            cost[i][j] = matched.min(removed).min(inserted);
+        }
+    }
+
+    // Trace back from the bottom-right corner
+    let mut result = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 {
+            let d = distances[i - 1][j - 1];
+            if d <= threshold && (cost[i][j] - (cost[i - 1][j - 1] + d)).abs() < 1e-9 {
+                result.push(StateAlignment::Matched {
+                    baseline: i - 1,
+                    current: j - 1,
+                    distance: d,
+                });
+                i -= 1;
+                j -= 1;
+                continue;
+            }
+        }
+        if i > 0 && (j == 0 || (cost[i][j] - (cost[i - 1][j] + gap)).abs() < 1e-9) {
+            result.push(StateAlignment::Removed { baseline: i - 1 });
+            i -= 1;
+        } else {
+            result.push(StateAlignment::Inserted { current: j - 1 });
+            j -= 1;
+        }
+    }
+    result.reverse();
+    result
+}
+
+/// List state screenshots in a session directory, ordered by step number.
+///
+/// Recognizes the `state_<N>_<input>.png` naming used by the `run` command.
+pub fn list_state_images(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut states: Vec<(usize, PathBuf)> = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        // Annotated copies (`state_1_down.findings.png`, `.diff.png`) sit beside the states
+        let name = path.to_string_lossy();
+        if path.extension().map(|e| e == "png").unwrap_or(false)
+            && !name.ends_with(".findings.png")
+            && !name.ends_with(".diff.png")
+            && let Some(step) = state_step(&path)
+        {
+            states.push((step, path));
+        }
+    }
+    states.sort();
+    Ok(states.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Extract the step number from a `state_<N>_...png` file name
+pub(crate) fn state_step(path: &Path) -> Option<usize> {
+    let stem = path.file_stem()?.to_str()?;
+    stem.strip_prefix("state_")?.split('_').next()?.parse().ok()
+}
+
+/// The input part of a `state_<N>_<input>.png` file name (`down`, empty for `state_0.png`)
+fn state_label(path: &Path) -> String {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    stem.strip_prefix("state_")
+        .map(|rest| rest.trim_start_matches(|c: char| c.is_ascii_digit()).trim_start_matches('_'))
+        .unwrap_or("")
+        .to_string()
+}
+
+/// Align the state screenshots of two session directories by perceptual hash.
+///
+/// Indices refer to [`list_state_images`] of each directory. States reached
+/// by the same input always count as close enough to pair, so a state whose
+/// screen changed a lot is still matched with its counterpart; between
+/// equally similar screens, the one reached by the same input wins.
+pub fn align_session_dirs(
+    baseline_dir: &Path,
+    current_dir: &Path,
+    threshold: f64,
+) -> SnapshotResult<Vec<StateAlignment>> {
+    let hash_dir = |dir: &Path| -> SnapshotResult<Vec<(String, u64)>> {
+        list_state_images(dir)?
+            .iter()
+            .map(|path| Ok((state_label(path), perceptual_hash(&fs::read(path)?)?)))
+            .collect()
+    };
+    let baseline = hash_dir(baseline_dir)?;
+    let current = hash_dir(current_dir)?;
+    Ok(align_states(&baseline, &current, threshold, |(label_a, a), (label_b, b)| {
+        let distance = hash_distance(*a, *b);
+        // A different input costs as much as one differing hash bit
+        if label_a == label_b { distance.min(threshold) } else { distance + 1.0 / 64.0 }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_align_identical_sequences() {
+        let states = ["menu", "list", "detail"];
+        let alignment = align_states(&states, &states, 0.25, |a, b| text_distance(a, b));
+        assert_eq!(alignment.len(), 3);
+        assert!(alignment.iter().all(|a| matches!(a, StateAlignment::Matched { distance, .. } if *distance == 0.0)));
+    }
+
+    #[test]
+    fn test_align_reports_inserted_state() {
+        let baseline = ["Main menu", "Settings"];
+        let current = ["Main menu", "Are you sure? [y/n]", "Settings"];
+        let alignment = align_states(&baseline, &current, 0.25, |a, b| text_distance(a, b));
+        assert_eq!(
+            alignment,
+            vec![
+                StateAlignment::Matched { baseline: 0, current: 0, distance: 0.0 },
+                StateAlignment::Inserted { current: 1 },
+                StateAlignment::Matched { baseline: 1, current: 2, distance: 0.0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_align_reports_removed_state() {
+        let baseline = ["Splash", "Main menu"];
+        let current = ["Main menu"];
+        let alignment = align_states(&baseline, &current, 0.25, |a, b| text_distance(a, b));
+        assert_eq!(alignment[0], StateAlignment::Removed { baseline: 0 });
+        assert!(matches!(alignment[1], StateAlignment::Matched { baseline: 1, current: 0, .. }));
+    }
+
+    #[test]
+    fn test_state_step_parsing() {
+        assert_eq!(state_step(Path::new("state_0_initial.png")), Some(0));
+        assert_eq!(state_step(Path::new("state_12_down.png")), Some(12));
+        assert_eq!(state_step(Path::new("capture.png")), None);
+        assert_eq!(state_label(Path::new("state_12_ctrl+c.png")), "ctrl+c");
+        assert_eq!(state_label(Path::new("state_0.png")), "");
+    }
+}
@@ -11,12 +11,23 @@
 //! |----------|-------------|---------|
 //! | `CLI_VISION_VLM_ENDPOINT` | VLM API endpoint URL | `http://127.0.0.1:8080/v1/chat/completions` |
 //! | `CLI_VISION_VLM_MODEL` | Model name for VLM | `qwen3` |
+//! | `CLI_VISION_VLM_PROVIDER` | VLM API format: `openai`, `ollama`, `anthropic` or `gemini` | `openai` |
+//! | `CLI_VISION_VLM_API_KEY` | API key for hosted VLM endpoints | - |
+//! | `CLI_VISION_VLM_HEADERS` | Extra request headers, `Name: value` separated by `;` | - |
 //! | `CLI_VISION_VLM_MAX_TOKENS` | Maximum tokens in VLM response | `400` |
 //! | `CLI_VISION_VLM_TIMEOUT` | VLM activity timeout in seconds | `60` |
 //! | `CLI_VISION_VLM_CONNECT_TIMEOUT` | VLM connection timeout in seconds | `10` |
+//! | `CLI_VISION_VLM_SAMPLES` | VLM samples per verification (majority vote) | `1` |
+//! | `CLI_VISION_VLM_MAX_ATTEMPTS` | Tries per VLM request when it fails transiently (429, 5xx, connection errors) | `3` |
+//! | `CLI_VISION_VLM_RPM` | Most VLM requests sent per minute | (unlimited) |
+//! | `CLI_VISION_VLM_CACHE_DIR` | Cache directory for VLM answers | `~/.cache/cli-vision/vlm` |
+//! | `CLI_VISION_VLM_CACHE_TTL` | Seconds a cached VLM answer stays valid (0: forever) | `604800` |
+//! | `CLI_VISION_LOCAL_MODEL` | Local CLIP model directory (`local-vision` feature) | - |
 //! | `CLI_VISION_SESSION_DIR` | Base directory for sessions | `/tmp/cli-vision` |
 //! | `CLI_VISION_DEFAULT_DELAY` | Default delay between inputs (ms) | `100` |
 //! | `CLI_VISION_DEFAULT_SIZE` | Default terminal size | `standard` |
+//! | `CLI_VISION_THEME` | Renderer color theme (built-in name or JSON file) | `default` |
+//! | `CLI_VISION_FIXTURE_DIR` | Cache directory for downloaded fixtures | `~/.cache/cli-vision/fixtures` |
 //!
 //! # Example
 //!
@@ -30,6 +41,7 @@
 //! ```
 
 use std::env;
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 
 // ============================================================================
@@ -42,6 +54,9 @@ pub const DEFAULT_VLM_ENDPOINT: &str = "http://127.0.0.1:8080/v1/chat/completion
 /// Default VLM model name
 pub const DEFAULT_VLM_MODEL: &str = "qwen3";
 
+/// Default VLM API format
+pub const DEFAULT_VLM_PROVIDER: &str = "openai";
+
 /// Default max tokens for VLM responses
 pub const DEFAULT_VLM_MAX_TOKENS: u32 = 400;
 
@@ -51,6 +66,21 @@ pub const DEFAULT_VLM_CONNECT_TIMEOUT: u64 = 10;
 /// Default VLM activity timeout (seconds)
 pub const DEFAULT_VLM_ACTIVITY_TIMEOUT: u64 = 60;
 
+/// Default VLM samples per verification
+pub const DEFAULT_VLM_SAMPLES: u32 = 1;
+
+/// Default tries per VLM request, including the first
+pub const DEFAULT_VLM_MAX_ATTEMPTS: u32 = 3;
+
+/// Default format screenshots are sent to the VLM in
+pub const DEFAULT_VLM_IMAGE_FORMAT: &str = "png";
+
+/// Default JPEG quality of screenshots sent to the VLM
+pub const DEFAULT_VLM_IMAGE_QUALITY: u8 = 85;
+
+/// Default lifetime of cached VLM answers (seconds; one week)
+pub const DEFAULT_VLM_CACHE_TTL: u64 = 7 * 24 * 60 * 60;
+
 /// Default session base directory
 pub const DEFAULT_SESSION_DIR: &str = "/tmp/cli-vision";
 
@@ -66,6 +96,9 @@ pub const DEFAULT_TERMINAL_WIDTH: u16 = 120;
 /// Default terminal height (rows)
 pub const DEFAULT_TERMINAL_HEIGHT: u16 = 40;
 
+/// Default renderer color theme
+pub const DEFAULT_THEME: &str = "default";
+
 /// Default mock screenshot width (pixels)
 pub const DEFAULT_MOCK_WIDTH: u32 = 800;
 
@@ -82,6 +115,15 @@ pub const ENV_VLM_ENDPOINT: &str = "CLI_VISION_VLM_ENDPOINT";
 /// Environment variable for VLM model
 pub const ENV_VLM_MODEL: &str = "CLI_VISION_VLM_MODEL";
 
+/// Environment variable for the VLM API format
+pub const ENV_VLM_PROVIDER: &str = "CLI_VISION_VLM_PROVIDER";
+
+/// Environment variable for the VLM API key
+pub const ENV_VLM_API_KEY: &str = "CLI_VISION_VLM_API_KEY";
+
+/// Environment variable for extra VLM request headers
+pub const ENV_VLM_HEADERS: &str = "CLI_VISION_VLM_HEADERS";
+
 /// Environment variable for VLM max tokens
 pub const ENV_VLM_MAX_TOKENS: &str = "CLI_VISION_VLM_MAX_TOKENS";
 
@@ -91,6 +133,33 @@ pub const ENV_VLM_CONNECT_TIMEOUT: &str = "CLI_VISION_VLM_CONNECT_TIMEOUT";
 /// Environment variable for VLM activity timeout
 pub const ENV_VLM_ACTIVITY_TIMEOUT: &str = "CLI_VISION_VLM_TIMEOUT";
 
+/// Environment variable for VLM samples per verification
+pub const ENV_VLM_SAMPLES: &str = "CLI_VISION_VLM_SAMPLES";
+
+/// Environment variable for tries per VLM request
+pub const ENV_VLM_MAX_ATTEMPTS: &str = "CLI_VISION_VLM_MAX_ATTEMPTS";
+
+/// Environment variable for the longest side of screenshots sent to the VLM
+pub const ENV_VLM_IMAGE_MAX_SIZE: &str = "CLI_VISION_VLM_IMAGE_MAX_SIZE";
+
+/// Environment variable for the format screenshots are sent to the VLM in
+pub const ENV_VLM_IMAGE_FORMAT: &str = "CLI_VISION_VLM_IMAGE_FORMAT";
+
+/// Environment variable for the JPEG quality of screenshots sent to the VLM
+pub const ENV_VLM_IMAGE_QUALITY: &str = "CLI_VISION_VLM_IMAGE_QUALITY";
+
+/// Environment variable for the most VLM requests sent per minute
+pub const ENV_VLM_RPM: &str = "CLI_VISION_VLM_RPM";
+
+/// Environment variable for the VLM answer cache directory
+pub const ENV_VLM_CACHE_DIR: &str = "CLI_VISION_VLM_CACHE_DIR";
+
+/// Environment variable for the lifetime of cached VLM answers
+pub const ENV_VLM_CACHE_TTL: &str = "CLI_VISION_VLM_CACHE_TTL";
+
+/// Environment variable for the local vision model directory
+pub const ENV_LOCAL_MODEL: &str = "CLI_VISION_LOCAL_MODEL";
+
 /// Environment variable for session directory
 pub const ENV_SESSION_DIR: &str = "CLI_VISION_SESSION_DIR";
 
@@ -100,6 +169,12 @@ pub const ENV_DEFAULT_DELAY: &str = "CLI_VISION_DEFAULT_DELAY";
 /// Environment variable for default terminal size
 pub const ENV_DEFAULT_SIZE: &str = "CLI_VISION_DEFAULT_SIZE";
 
+/// Environment variable for the renderer color theme
+pub const ENV_THEME: &str = "CLI_VISION_THEME";
+
+/// Environment variable for the fixture cache directory
+pub const ENV_FIXTURE_DIR: &str = "CLI_VISION_FIXTURE_DIR";
+
 // ============================================================================
 // Legacy Environment Variable Support (for backwards compatibility)
 // ============================================================================
@@ -139,12 +214,32 @@ pub struct VlmSettings {
     pub endpoint: String,
     /// Model name
     pub model: String,
+    /// API format (see [`crate::vlm_provider`])
+    pub provider: String,
+    /// API key for hosted endpoints
+    pub api_key: Option<Secret>,
+    /// Extra headers sent with every request
+    pub extra_headers: Vec<(String, Secret)>,
     /// Maximum tokens in response
     pub max_tokens: u32,
     /// Connection timeout (seconds)
     pub connect_timeout: u64,
     /// Activity timeout during streaming (seconds)
     pub activity_timeout: u64,
+    /// Samples per verification (majority vote)
+    pub samples: u32,
+    /// Tries per request when it fails transiently
+    pub max_attempts: u32,
+    /// Longest side of screenshots sent, in pixels (see [`crate::vlm_image`])
+    pub image_max_size: Option<u32>,
+    /// Format screenshots are sent in: png, jpeg or webp
+    pub image_format: String,
+    /// JPEG quality of screenshots sent
+    pub image_quality: u8,
+    /// Most requests sent per minute (see [`crate::vlm_rate`])
+    pub requests_per_minute: Option<u32>,
+    /// Directory of the local vision model, if configured
+    pub local_model: Option<String>,
 }
 
 /// Session-related settings
@@ -165,6 +260,8 @@ pub struct DefaultSettings {
     pub terminal_width: u16,
     /// Default terminal height
     pub terminal_height: u16,
+    /// Default color theme (built-in name or theme file path)
+    pub theme: String,
     /// Default mock width
     pub mock_width: u32,
     /// Default mock height
@@ -206,6 +303,12 @@ impl VlmSettings {
                 .unwrap_or_else(|_| DEFAULT_VLM_ENDPOINT.to_string()),
             model: env::var(ENV_VLM_MODEL)
                 .unwrap_or_else(|_| DEFAULT_VLM_MODEL.to_string()),
+            provider: env::var(ENV_VLM_PROVIDER)
+                .unwrap_or_else(|_| DEFAULT_VLM_PROVIDER.to_string()),
+            api_key: env::var(ENV_VLM_API_KEY).ok().filter(|s| !s.is_empty()).map(Secret::new),
+            extra_headers: env::var(ENV_VLM_HEADERS)
+                .map(|s| parse_headers(&s))
+                .unwrap_or_default(),
             max_tokens: env::var(ENV_VLM_MAX_TOKENS)
                 .ok()
                 .and_then(|s| s.parse().ok())
@@ -218,6 +321,31 @@ impl VlmSettings {
                 .ok()
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(DEFAULT_VLM_ACTIVITY_TIMEOUT),
+            samples: env::var(ENV_VLM_SAMPLES)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|&n| n > 0)
+                .unwrap_or(DEFAULT_VLM_SAMPLES),
+            max_attempts: env::var(ENV_VLM_MAX_ATTEMPTS)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|&n| n > 0)
+                .unwrap_or(DEFAULT_VLM_MAX_ATTEMPTS),
+            image_max_size: env::var(ENV_VLM_IMAGE_MAX_SIZE)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|&n| n > 0),
+            image_format: env::var(ENV_VLM_IMAGE_FORMAT).unwrap_or_else(|_| DEFAULT_VLM_IMAGE_FORMAT.to_string()),
+            image_quality: env::var(ENV_VLM_IMAGE_QUALITY)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|n| (1..=100).contains(n))
+                .unwrap_or(DEFAULT_VLM_IMAGE_QUALITY),
+            requests_per_minute: env::var(ENV_VLM_RPM)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|&n| n > 0),
+            local_model: env::var(ENV_LOCAL_MODEL).ok().filter(|s| !s.is_empty()),
         }
     }
 
@@ -226,9 +354,19 @@ impl VlmSettings {
         Self {
             endpoint: DEFAULT_VLM_ENDPOINT.to_string(),
             model: DEFAULT_VLM_MODEL.to_string(),
+            provider: DEFAULT_VLM_PROVIDER.to_string(),
+            api_key: None,
+            extra_headers: Vec::new(),
             max_tokens: DEFAULT_VLM_MAX_TOKENS,
             connect_timeout: DEFAULT_VLM_CONNECT_TIMEOUT,
             activity_timeout: DEFAULT_VLM_ACTIVITY_TIMEOUT,
+            samples: DEFAULT_VLM_SAMPLES,
+            max_attempts: DEFAULT_VLM_MAX_ATTEMPTS,
+            image_max_size: None,
+            image_format: DEFAULT_VLM_IMAGE_FORMAT.to_string(),
+            image_quality: DEFAULT_VLM_IMAGE_QUALITY,
+            requests_per_minute: None,
+            local_model: None,
         }
     }
 }
@@ -268,6 +406,7 @@ impl DefaultSettings {
             terminal_size,
             terminal_width: width,
             terminal_height: height,
+            theme: env::var(ENV_THEME).unwrap_or_else(|_| DEFAULT_THEME.to_string()),
             mock_width: DEFAULT_MOCK_WIDTH,
             mock_height: DEFAULT_MOCK_HEIGHT,
         }
@@ -280,16 +419,50 @@ impl DefaultSettings {
             terminal_size: DEFAULT_TERMINAL_SIZE.to_string(),
             terminal_width: DEFAULT_TERMINAL_WIDTH,
             terminal_height: DEFAULT_TERMINAL_HEIGHT,
+            theme: DEFAULT_THEME.to_string(),
             mock_width: DEFAULT_MOCK_WIDTH,
             mock_height: DEFAULT_MOCK_HEIGHT,
         }
     }
 }
 
+/// A value kept out of `Debug` output, such as an API key
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// The value itself, for sending it
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("\"[redacted]\"")
+    }
+}
+
 // ============================================================================
 // Helper Functions
 // ============================================================================
 
+/// Parse `Name: value` headers separated by `;`, skipping entries without a name
+pub fn parse_headers(headers: &str) -> Vec<(String, Secret)> {
+    headers
+        .split(';')
+        .filter_map(|header| {
+            let (name, value) = header.split_once(':')?;
+            let name = name.trim();
+            (!name.is_empty()).then(|| (name.to_string(), Secret::new(value.trim())))
+        })
+        .collect()
+}
+
 /// Parse a terminal size string into (width, height)
 /// Supports: "compact" (80x24), "standard" (120x40), "large" (160x50), "xl" (200x60), or "WxH"
 fn parse_terminal_size(size: &str) -> Option<(u16, u16)> {
@@ -311,6 +484,15 @@ fn parse_terminal_size(size: &str) -> Option<(u16, u16)> {
     }
 }
 
+/// Base directory for caches: `$XDG_CACHE_HOME/cli-vision`, `~/.cache/cli-vision`, or the temp dir
+pub fn cache_base_dir() -> PathBuf {
+    env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .unwrap_or_else(env::temp_dir)
+        .join("cli-vision")
+}
+
 /// Get VLM endpoint from environment (convenience function)
 pub fn vlm_endpoint() -> String {
     get().vlm.endpoint.clone()
@@ -367,4 +549,14 @@ mod tests {
         assert_eq!(config.vlm.model, DEFAULT_VLM_MODEL);
         assert_eq!(config.session.base_dir, DEFAULT_SESSION_DIR);
     }
+
+    #[test]
+    fn test_parse_headers_redacts_values() {
+        let headers = parse_headers("HTTP-Referer: https://example.com; X-Title: CI ;broken");
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[0].0, "HTTP-Referer");
+        assert_eq!(headers[0].1.expose(), "https://example.com");
+        assert_eq!(headers[1].1.expose(), "CI");
+        assert!(!format!("{:?}", headers).contains("example.com"));
+    }
 }
@@ -0,0 +1,422 @@
+//! JSON-RPC interface for editor and IDE plugins (`cli-vision serve --editor`).
+//!
+//! Messages are JSON-RPC 2.0 framed with `Content-Length` headers, as in the
+//! Language Server Protocol, so existing LSP client libraries can talk to
+//! the server over stdio. Methods:
+//!
+//! | Method | Params | Result |
+//! |--------|--------|--------|
+//! | `initialize` | - | server name, version and methods |
+//! | `scenario/validate` | `{ "path" }` or `{ "text" }` | `{ "diagnostics": [...] }` |
+//! | `suite/validate` | `{ "path" }` or `{ "text" }` | `{ "diagnostics": [...] }` |
+//! | `schema/get` | `{ "kind": "scenario" \| "suite" }` | the JSON Schema |
+//! | `scenario/runStep` | scenario, `"step"` | the state after that step |
+//! | `capture/latest` | - | `{ "step", "width", "height", "png_base64" }` |
+//! | `events/subscribe` | - | `true`; `run/event` notifications follow |
+//! | `events/unsubscribe` | - | `true` |
+//! | `shutdown` | - | `null` |
+//!
+//! `scenario/runStep` replays the scenario from a fresh launch up to and
+//! including the requested (0-based) step. Subscribers receive `run/event`
+//! notifications with `kind` set to `started`, `captured` (once per state,
+//! as it is captured), `finished` or `failed`. An `exit` notification stops
+//! the server.
+
+use base64::Engine;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::scenario::{validate_scenario, Diagnostic, Scenario, Severity};
+use crate::schema::{scenario_json_schema, suite_json_schema, validate_json};
+use crate::snapshot::{run_with_options, RunOptions, StateCaptureResult};
+
+/// Methods answered by the server
+pub const METHODS: &[&str] = &[
+    "initialize",
+    "scenario/validate",
+    "suite/validate",
+    "schema/get",
+    "scenario/runStep",
+    "capture/latest",
+    "events/subscribe",
+    "events/unsubscribe",
+    "shutdown",
+];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Server-defined error: the run failed or there is nothing to return
+const RUN_FAILED: i64 = -32000;
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+type Output = Arc<Mutex<Box<dyn Write + Send>>>;
+
+/// Read one `Content-Length` framed message body; `None` at end of input
+pub fn read_message(reader: &mut dyn BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            length = Some(value.trim().parse::<usize>().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("invalid Content-Length '{}'", value.trim()))
+            })?);
+        }
+    }
+    let mut body = vec![0u8; length.unwrap_or(0)];
+    reader.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Write one `Content-Length` framed message
+pub fn write_message(writer: &mut dyn Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// Editor protocol server
+pub struct EditorServer {
+    out: Output,
+    subscribed: Arc<AtomicBool>,
+    latest: Arc<Mutex<Option<StateCaptureResult>>>,
+    options: RunOptions,
+    shutdown: bool,
+}
+
+impl EditorServer {
+    /// Server writing responses and notifications to `out`
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        Self {
+            out: Arc::new(Mutex::new(Box::new(out))),
+            subscribed: Arc::new(AtomicBool::new(false)),
+            latest: Arc::new(Mutex::new(None)),
+            options: RunOptions::default(),
+            shutdown: false,
+        }
+    }
+
+    /// Set the capture options used for scenario runs (theme, limits, ...)
+    pub fn options(mut self, options: RunOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Answer messages from `input` until it ends or an `exit` notification arrives
+    pub fn serve(&mut self, input: &mut dyn BufRead) -> io::Result<()> {
+        while let Some(body) = read_message(input)? {
+            let message = match serde_json::from_str::<Value>(&body) {
+                Ok(message) => message,
+                Err(e) => {
+                    send(&self.out, &error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string())))?;
+                    continue;
+                }
+            };
+            if message.get("method").and_then(Value::as_str) == Some("exit") {
+                break;
+            }
+            if let Some(response) = self.handle(&message) {
+                send(&self.out, &response)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle one message, returning the response for requests (`None` for notifications)
+    pub fn handle(&mut self, message: &Value) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            return Some(error_response(
+                id.unwrap_or(Value::Null),
+                RpcError::new(INVALID_REQUEST, "missing method"),
+            ));
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = self.dispatch(method, &params);
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(err) => error_response(id, err),
+        })
+    }
+
+    fn dispatch(&mut self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        if self.shutdown && method != "shutdown" {
+            return Err(RpcError::new(INVALID_REQUEST, "server is shutting down"));
+        }
+        match method {
+            "initialize" => Ok(json!({
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+                "methods": METHODS,
+            })),
+            "scenario/validate" => {
+                let (_, diagnostics) = validate_scenario(&scenario_source(params)?);
+                Ok(json!({ "diagnostics": diagnostics }))
+            }
+            "suite/validate" => {
+                let diagnostics = validate_json(&scenario_source(params)?, &suite_json_schema());
+                Ok(json!({ "diagnostics": diagnostics }))
+            }
+            "schema/get" => match params.get("kind").and_then(Value::as_str).unwrap_or("scenario") {
+                "scenario" => Ok(scenario_json_schema()),
+                "suite" => Ok(suite_json_schema()),
+                other => Err(RpcError::new(INVALID_PARAMS, format!("unknown schema kind '{}'", other))),
+            },
+            "scenario/runStep" => self.run_step(params),
+            "capture/latest" => {
+                let latest = self.latest.lock().unwrap_or_else(|p| p.into_inner());
+                let capture = latest
+                    .as_ref()
+                    .ok_or_else(|| RpcError::new(RUN_FAILED, "no capture yet"))?;
+                Ok(json!({
+                    "step": capture.step,
+                    "width": capture.width,
+                    "height": capture.height,
+                    "png_base64": base64::engine::general_purpose::STANDARD.encode(&capture.image_data),
+                }))
+            }
+            "events/subscribe" => {
+                self.subscribed.store(true, Ordering::SeqCst);
+                Ok(json!(true))
+            }
+            "events/unsubscribe" => {
+                self.subscribed.store(false, Ordering::SeqCst);
+                Ok(json!(true))
+            }
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            other => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method '{}'", other))),
+        }
+    }
+
+    /// Replay a scenario up to and including the requested step
+    fn run_step(&mut self, params: &Value) -> Result<Value, RpcError> {
+        let source = scenario_source(params)?;
+        let (scenario, diagnostics) = validate_scenario(&source);
+        let errors: Vec<&Diagnostic> = diagnostics.iter().filter(|d| d.severity == Severity::Error).collect();
+        let scenario: Scenario = match scenario {
+            Some(scenario) if errors.is_empty() => scenario,
+            _ => {
+                let summary: Vec<String> = errors.iter().map(|d| d.to_string()).collect();
+                return Err(RpcError::new(INVALID_PARAMS, format!("invalid scenario: {}", summary.join("; "))));
+            }
+        };
+        let step = params
+            .get("step")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing step"))? as usize;
+        if step >= scenario.steps.len() {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("step {} out of range (scenario has {} steps)", step, scenario.steps.len()),
+            ));
+        }
+
+        let size = scenario.terminal_size().unwrap_or_default();
+        let mut options = self.options.clone().layout(scenario.keyboard_layout().unwrap_or_default());
+        options.env.extend(scenario.env_pairs());
+        let (out, subscribed, latest) = (self.out.clone(), self.subscribed.clone(), self.latest.clone());
+        let options = options.on_capture(move |capture| {
+            *latest.lock().unwrap_or_else(|p| p.into_inner()) = Some(capture.clone());
+            if subscribed.load(Ordering::SeqCst) {
+                let _ = send(&out, &event("captured", capture_summary(capture)));
+            }
+        });
+
+        // Run every input up to and including the expansion of the requested step
+        let inputs: Vec<String> = scenario
+            .expanded_inputs()
+            .map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid scenario: {}", e)))?
+            .into_iter()
+            .filter(|expanded| expanded.origin <= step)
+            .map(|expanded| expanded.input)
+            .collect();
+
+        self.notify(event("started", json!({ "step": step, "binary": scenario.binary })));
+        let result = run_with_options(
+            &scenario.binary.to_string_lossy(),
+            &scenario.args,
+            &inputs,
+            scenario.delay_ms,
+            size,
+            &options,
+        );
+        match result {
+            Ok(captures) => {
+                let last = captures.last().map(capture_summary).unwrap_or(Value::Null);
+                let expected = scenario.steps[step].expect_text.as_deref();
+                let summary = json!({
+                    "state": last,
+                    "expect_text_found": expected.map(|text| last["text"].as_str().unwrap_or("").contains(text)),
+                });
+                self.notify(event("finished", summary.clone()));
+                Ok(summary)
+            }
+            Err(e) => {
+                self.notify(event("failed", json!({ "message": e.to_string() })));
+                Err(RpcError::new(RUN_FAILED, e.to_string()))
+            }
+        }
+    }
+
+    /// Send a notification to subscribers
+    fn notify(&self, notification: Value) {
+        if self.subscribed.load(Ordering::SeqCst) {
+            let _ = send(&self.out, &notification);
+        }
+    }
+}
+
+/// Scenario JSON from `text`, or read from `path`
+fn scenario_source(params: &Value) -> Result<String, RpcError> {
+    if let Some(text) = params.get("text").and_then(Value::as_str) {
+        return Ok(text.to_string());
+    }
+    let path = params
+        .get("path")
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, "expected 'path' or 'text'"))?;
+    std::fs::read_to_string(Path::new(path))
+        .map_err(|e| RpcError::new(INVALID_PARAMS, format!("{}: {}", path, e)))
+}
+
+fn capture_summary(capture: &StateCaptureResult) -> Value {
+    json!({
+        "step": capture.step,
+        "input": capture.input,
+        "width": capture.width,
+        "height": capture.height,
+        "bell_count": capture.bell_count,
+        "text": capture.text,
+    })
+}
+
+fn event(kind: &str, data: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": "run/event", "params": { "kind": kind, "data": data } })
+}
+
+fn error_response(id: Value, err: RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": err.code, "message": err.message } })
+}
+
+fn send(out: &Output, message: &Value) -> io::Result<()> {
+    let mut out = out.lock().unwrap_or_else(|p| p.into_inner());
+    write_message(&mut **out, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writer whose contents stay readable after being handed to the server
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn messages(buffer: &SharedBuffer) -> Vec<Value> {
+        let data = buffer.0.lock().unwrap().clone();
+        let mut reader = io::Cursor::new(data);
+        std::iter::from_fn(|| read_message(&mut reader).unwrap())
+            .map(|body| serde_json::from_str(&body).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_framing_round_trip() {
+        let mut out = Vec::new();
+        write_message(&mut out, &json!({ "id": 1 })).unwrap();
+        write_message(&mut out, &json!({ "id": "ü" })).unwrap();
+        let mut reader = io::Cursor::new(out);
+        assert_eq!(read_message(&mut reader).unwrap().unwrap(), r#"{"id":1}"#);
+        assert_eq!(read_message(&mut reader).unwrap().unwrap(), r#"{"id":"ü"}"#);
+        assert!(read_message(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_validate_and_unknown_method() {
+        let mut server = EditorServer::new(io::sink());
+        let response = server
+            .handle(&json!({ "jsonrpc": "2.0", "id": 1, "method": "scenario/validate", "params": { "text": "{\n  \"steps\": 3\n}" } }))
+            .unwrap();
+        let diagnostics = response["result"]["diagnostics"].as_array().unwrap();
+        let lines: Vec<_> = diagnostics.iter().map(|d| d["line"].as_u64().unwrap()).collect();
+        // The missing binary is reported at the object, the wrong type at the value
+        assert_eq!(lines, [1, 2]);
+        assert_eq!(diagnostics[1]["severity"], "error");
+
+        let response = server.handle(&json!({ "jsonrpc": "2.0", "id": 2, "method": "frobnicate" })).unwrap();
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        assert!(server.handle(&json!({ "jsonrpc": "2.0", "method": "events/subscribe" })).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_step_streams_events_and_keeps_latest_capture() {
+        let buffer = SharedBuffer::default();
+        let mut server = EditorServer::new(buffer.clone());
+        let scenario = r#"{ "binary": "/bin/sh", "args": ["-c", "read a; echo got-$a; read b"], "size": "compact", "delay_ms": 0,
+            "steps": [{ "input": "hi" }, { "input": "enter", "expect_text": "got-hi" }, { "input": "enter" }] }"#;
+        let mut input = Vec::new();
+        for (id, method, params) in [
+            (1, "events/subscribe", Value::Null),
+            (2, "scenario/runStep", json!({ "text": scenario, "step": 1 })),
+            (3, "capture/latest", Value::Null),
+        ] {
+            write_message(&mut input, &json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })).unwrap();
+        }
+        write_message(&mut input, &json!({ "jsonrpc": "2.0", "method": "exit" })).unwrap();
+        server.serve(&mut io::Cursor::new(input)).unwrap();
+
+        let messages = messages(&buffer);
+        let kinds: Vec<&str> = messages.iter().filter_map(|m| m["params"]["kind"].as_str()).collect();
+        assert_eq!(kinds, ["started", "captured", "captured", "captured", "finished"]);
+        let run = messages.iter().find(|m| m["id"] == 2).unwrap();
+        assert_eq!(run["result"]["expect_text_found"], true);
+        let latest = messages.iter().find(|m| m["id"] == 3).unwrap();
+        assert_eq!(latest["result"]["step"], 2);
+        assert!(latest["result"]["png_base64"].as_str().unwrap().starts_with("iVBORw0KGgo"));
+    }
+}
@@ -0,0 +1,706 @@
+//! Deterministic checks on a captured screen.
+//!
+//! An [`Expectation`] is a one-line statement about a screen, written the
+//! same way in scenario `expect` entries and on the `assert` command line:
+//!
+//! | Expectation | Passes when |
+//! |-------------|-------------|
+//! | `contains TEXT` | `TEXT` is on screen |
+//! | `not-contains TEXT` | `TEXT` is nowhere on screen |
+//! | `regex PATTERN` | a line matches `PATTERN` (`^` and `$` match at line ends) |
+//! | `cell-color ROW,COL #rrggbb` | the cell's foreground has that color |
+//! | `cursor-at ROW,COL` | the cursor is at that cell |
+//! | `in REGION EXPECTATION` | `EXPECTATION` holds within `REGION` alone |
+//!
+//! Rows and columns are 0-based. Color checks need the cell grid of the
+//! capture ([`RunOptions::capture_grid`](crate::snapshot::RunOptions::capture_grid),
+//! or a `.grid.json` file from `run --format grid-json`); text checks work
+//! on plain screen text too.
+//!
+//! A region is a name given to a rectangle of cells ([`Screen::with_region`],
+//! or `regions` in a scenario), or a literal `X,Y,COLS,ROWS` or
+//! `COLSxROWS+X+Y` rectangle. Checks in a region ignore the rest of the
+//! screen, and their positions count from the region's top-left cell.
+//!
+//! In `cargo test`, [`ScreenAssert`] runs the same checks fluently and
+//! panics with the part of the screen that failed:
+//!
+//! ```rust,no_run
+//! use cli_vision::snapshot::{run_with_options, RunOptions, TerminalSize};
+//!
+//! let options = RunOptions::default().capture_grid(true);
+//! let states = run_with_options("./my-tui", &[], &["enter".to_string()], 100, TerminalSize::default(), &options).unwrap();
+//! states[1].assert().contains_text("OK").cell(3, 10).has_fg([255, 0, 0]).cursor_at(5, 0);
+//! ```
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+use crate::snapshot::{CaptureResult, CellRect, GridAttribute, GridCell, GridCursor, StateCaptureResult, TerminalGrid};
+
+/// Rows shown above and below the failing cell in assertion messages
+const EXCERPT_CONTEXT: u32 = 2;
+
+/// A check on a captured screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expectation {
+    /// Text that must be on screen
+    Contains(String),
+    /// Text that must not be on screen
+    NotContains(String),
+    /// Pattern a line must match
+    Regex(String),
+    /// Foreground color of a cell
+    CellColor { row: u32, col: u32, color: [u8; 3] },
+    /// Cursor position
+    CursorAt { row: u32, col: u32 },
+    /// An expectation checked within a region only
+    Within { region: String, expectation: Box<Expectation> },
+}
+
+impl Expectation {
+    /// Parse an expectation such as `contains Save` or `cell-color 0,4 #cd3131`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        let (keyword, argument) = spec.split_once(char::is_whitespace).unwrap_or((spec, ""));
+        let argument = argument.trim();
+        if argument.is_empty() {
+            return Err(format!("expectation '{}' needs an argument", spec));
+        }
+        match keyword.to_lowercase().as_str() {
+            "contains" => Ok(Expectation::Contains(unquote(argument).to_string())),
+            "not-contains" => Ok(Expectation::NotContains(unquote(argument).to_string())),
+            "regex" => {
+                let pattern = unquote(argument);
+                Regex::new(pattern).map_err(|e| format!("invalid regex '{}': {}", pattern, e))?;
+                Ok(Expectation::Regex(pattern.to_string()))
+            }
+            "cell-color" => {
+                let (position, color) = argument
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| format!("malformed cell-color '{}' (expected cell-color ROW,COL #rrggbb)", argument))?;
+                let (row, col) = parse_position(position)?;
+                Ok(Expectation::CellColor { row, col, color: parse_color(color.trim())? })
+            }
+            "cursor-at" => {
+                let (row, col) = parse_position(argument)?;
+                Ok(Expectation::CursorAt { row, col })
+            }
+            "in" => {
+                let (region, rest) = argument
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| format!("malformed region check '{}' (expected in REGION EXPECTATION)", argument))?;
+                Ok(Expectation::Within {
+                    region: region.to_string(),
+                    expectation: Box::new(Expectation::parse(rest)?),
+                })
+            }
+            _ => Err(format!(
+                "unknown expectation '{}' (expected contains, not-contains, regex, cell-color, cursor-at or in)",
+                keyword
+            )),
+        }
+    }
+
+    /// The region the expectation is limited to, if any
+    pub fn region(&self) -> Option<&str> {
+        match self {
+            Expectation::Within { region, .. } => Some(region),
+            _ => None,
+        }
+    }
+
+    /// Check the expectation against a screen
+    pub fn check(&self, screen: &Screen) -> ExpectationResult {
+        let result = |pass: bool, message: String| ExpectationResult {
+            expectation: self.to_string(),
+            pass,
+            message,
+        };
+        match self {
+            Expectation::Contains(text) => match screen.find(text) {
+                Some((row, col)) => result(true, format!("found at {},{}", row, col)),
+                None => result(false, format!("'{}' is not on screen", text)),
+            },
+            Expectation::NotContains(text) => match screen.find(text) {
+                Some((row, col)) => result(false, format!("'{}' is on screen at {},{}", text, row, col)),
+                None => result(true, "not on screen".to_string()),
+            },
+            Expectation::Regex(pattern) => {
+                let regex = Regex::new(&format!("(?m){}", pattern)).expect("validated when parsed");
+                match regex.find(&screen.text) {
+                    Some(found) => result(true, format!("matched '{}'", found.as_str())),
+                    None => result(false, format!("no line matches /{}/", pattern)),
+                }
+            }
+            Expectation::CellColor { row, col, color } => {
+                let Some(grid) = &screen.grid else {
+                    return result(false, "cell colors need a cell grid capture".to_string());
+                };
+                match grid.cell(*row, *col) {
+                    Some(cell) if cell.fg == *color => result(true, format!("'{}' is {}", cell.ch, hex(cell.fg))),
+                    Some(cell) => result(false, format!("'{}' at {},{} is {}", cell.ch, row, col, hex(cell.fg))),
+                    None => result(false, format!("{},{} is off screen ({}x{})", row, col, grid.width, grid.height)),
+                }
+            }
+            Expectation::CursorAt { row, col } => match screen.cursor {
+                Some(cursor) if cursor == (*row, *col) => result(true, format!("cursor at {},{}", row, col)),
+                Some((actual_row, actual_col)) => result(false, format!("cursor is at {},{}", actual_row, actual_col)),
+                None => result(false, "the capture has no cursor position".to_string()),
+            },
+            Expectation::Within { region, expectation } => match screen.resolve_region(region) {
+                Ok(rect) => ExpectationResult {
+                    expectation: self.to_string(),
+                    ..expectation.check(&screen.crop(rect))
+                },
+                Err(problem) => result(false, problem),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expectation::Contains(text) => write!(f, "contains {}", text),
+            Expectation::NotContains(text) => write!(f, "not-contains {}", text),
+            Expectation::Regex(pattern) => write!(f, "regex {}", pattern),
+            Expectation::CellColor { row, col, color } => write!(f, "cell-color {},{} {}", row, col, hex(*color)),
+            Expectation::CursorAt { row, col } => write!(f, "cursor-at {},{}", row, col),
+            Expectation::Within { region, expectation } => write!(f, "in {} {}", region, expectation),
+        }
+    }
+}
+
+/// Outcome of one expectation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpectationResult {
+    /// The expectation, as written
+    pub expectation: String,
+    pub pass: bool,
+    /// What was found on screen
+    pub message: String,
+}
+
+/// Outcome of checking several expectations against one screen
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssertReport {
+    /// Whether every expectation passed
+    pub pass: bool,
+    pub results: Vec<ExpectationResult>,
+}
+
+/// Check every expectation against a screen
+pub fn check_all(expectations: &[Expectation], screen: &Screen) -> AssertReport {
+    let results: Vec<_> = expectations.iter().map(|expectation| expectation.check(screen)).collect();
+    AssertReport {
+        pass: results.iter().all(|result| result.pass),
+        results,
+    }
+}
+
+/// What expectations are checked against: screen text, and the cursor and
+/// cell grid when the capture has them
+#[derive(Debug, Clone, Default)]
+pub struct Screen {
+    /// Screen text, one line per row
+    pub text: String,
+    /// Cursor position as (row, column)
+    pub cursor: Option<(u32, u32)>,
+    /// Cell grid
+    pub grid: Option<TerminalGrid>,
+    /// Named regions that `in REGION` expectations can refer to
+    pub regions: BTreeMap<String, CellRect>,
+}
+
+impl Screen {
+    /// A screen known only by its text
+    pub fn from_text(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Self::default()
+        }
+    }
+
+    /// A screen with its cell grid
+    pub fn from_grid(grid: TerminalGrid) -> Self {
+        Self {
+            text: (0..grid.height).map(|row| grid.row_text(row)).collect::<Vec<_>>().join("\n"),
+            cursor: Some((grid.cursor.row, grid.cursor.col)),
+            grid: Some(grid),
+            regions: BTreeMap::new(),
+        }
+    }
+
+    /// The screen of a captured state
+    pub fn from_state(state: &StateCaptureResult) -> Self {
+        Self {
+            text: state.text.clone(),
+            cursor: Some(state.cursor_position),
+            grid: state.grid.clone(),
+            regions: BTreeMap::new(),
+        }
+    }
+
+    /// Name a region for `in REGION` expectations
+    pub fn with_region(mut self, name: impl Into<String>, rect: CellRect) -> Self {
+        self.regions.insert(name.into(), rect);
+        self
+    }
+
+    /// The rectangle of a named region, or of a literal `X,Y,COLS,ROWS` or `COLSxROWS+X+Y`
+    pub fn resolve_region(&self, region: &str) -> Result<CellRect, String> {
+        match self.regions.get(region) {
+            Some(rect) => Ok(*rect),
+            None => CellRect::parse(region).map_err(|_| format!("unknown region '{}'", region)),
+        }
+    }
+
+    /// The part of the screen inside `rect`, clamped to the screen; positions
+    /// count from its top-left cell and the cursor is dropped if outside
+    pub fn crop(&self, rect: CellRect) -> Screen {
+        let lines: Vec<&str> = self.text.lines().collect();
+        let (width, height) = match &self.grid {
+            Some(grid) => (grid.width, grid.height),
+            None => (lines.iter().map(|line| line.chars().count()).max().unwrap_or(0) as u32, lines.len() as u32),
+        };
+        let (rows, cols) = (rect.row_range(height), rect.col_range(width));
+        let text = rows
+            .clone()
+            .map(|row| match lines.get(row as usize) {
+                Some(line) => line.chars().skip(cols.start as usize).take(cols.len()).collect(),
+                None => String::new(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let cursor = self
+            .cursor
+            .filter(|(row, col)| rows.contains(row) && cols.contains(col))
+            .map(|(row, col)| (row - rows.start, col - cols.start));
+        let grid = self.grid.as_ref().map(|grid| TerminalGrid {
+            width: cols.len() as u32,
+            height: rows.len() as u32,
+            cursor: GridCursor {
+                row: cursor.map_or(0, |(row, _)| row),
+                col: cursor.map_or(0, |(_, col)| col),
+                state: grid.cursor.state,
+            },
+            rows: rows
+                .clone()
+                .map(|row| {
+                    let cells = grid.rows.get(row as usize).map(Vec::as_slice).unwrap_or_default();
+                    cells.get(cols.start as usize..(cols.end as usize).min(cells.len())).unwrap_or_default().to_vec()
+                })
+                .collect(),
+            ..grid.clone()
+        });
+        Screen {
+            text,
+            cursor,
+            grid,
+            regions: BTreeMap::new(),
+        }
+    }
+
+    /// Load a saved capture: a `.grid.json` file, a text file, or a
+    /// screenshot with a `.grid.json` or `.txt` file beside it
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let name = path.to_string_lossy();
+        if name.ends_with(".grid.json") {
+            return Ok(Self::from_grid(serde_json::from_slice(&std::fs::read(path)?)?));
+        }
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")) {
+            let grid = path.with_extension("grid.json");
+            let text = path.with_extension("txt");
+            return match (grid.exists(), text.exists()) {
+                (true, _) => Self::load(&grid),
+                (false, true) => Self::load(&text),
+                (false, false) => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} has no .grid.json or .txt beside it (run with --format grid-json or --save-text)", path.display()),
+                )),
+            };
+        }
+        Ok(Self::from_text(std::fs::read_to_string(path)?))
+    }
+
+    /// Position (row, column) of the first occurrence of `text`
+    fn find(&self, text: &str) -> Option<(u32, u32)> {
+        if let Some(grid) = &self.grid {
+            return grid.find(text);
+        }
+        self.text.lines().enumerate().find_map(|(row, line)| {
+            line.find(text).map(|byte| (row as u32, line[..byte].chars().count() as u32))
+        })
+    }
+}
+
+/// Fluent assertions on a screen, panicking with the rendered text around
+/// the failure. Each check returns the assertion, so they chain.
+#[derive(Debug, Clone)]
+pub struct ScreenAssert {
+    screen: Screen,
+    /// The region being checked, for failure messages
+    region: Option<String>,
+}
+
+impl ScreenAssert {
+    pub fn new(screen: Screen) -> Self {
+        Self { screen, region: None }
+    }
+
+    /// The screen being checked
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    /// Name a region for [`region`](Self::region) and `in REGION` expectations
+    pub fn with_region(mut self, name: impl Into<String>, rect: CellRect) -> Self {
+        self.screen.regions.insert(name.into(), rect);
+        self
+    }
+
+    /// Assertions on a rectangle of the screen only; positions count from its top-left cell
+    pub fn within(&self, rect: CellRect) -> ScreenAssert {
+        ScreenAssert {
+            screen: self.screen.crop(rect),
+            region: Some(format!("region {}", rect)),
+        }
+    }
+
+    /// Assertions on a named region only
+    #[track_caller]
+    pub fn region(&self, name: &str) -> ScreenAssert {
+        match self.screen.regions.get(name) {
+            Some(rect) => ScreenAssert {
+                screen: self.screen.crop(*rect),
+                region: Some(format!("region {} ({})", name, rect)),
+            },
+            None => panic!("unknown region '{}' (name it with with_region)", name),
+        }
+    }
+
+    /// `text` is on screen
+    #[track_caller]
+    pub fn contains_text(&self, text: &str) -> &Self {
+        self.check(&Expectation::Contains(text.to_string()), None)
+    }
+
+    /// `text` is nowhere on screen
+    #[track_caller]
+    pub fn not_contains_text(&self, text: &str) -> &Self {
+        self.check(&Expectation::NotContains(text.to_string()), self.screen.find(text))
+    }
+
+    /// A line matches `pattern`
+    #[track_caller]
+    pub fn matches_regex(&self, pattern: &str) -> &Self {
+        match Expectation::parse(&format!("regex {}", pattern)) {
+            Ok(expectation) => self.check(&expectation, None),
+            Err(problem) => panic!("{}", problem),
+        }
+    }
+
+    /// `text` starts at a cell
+    #[track_caller]
+    pub fn text_at(&self, row: u32, col: u32, text: &str) -> &Self {
+        let line = self.screen.text.lines().nth(row as usize).unwrap_or_default();
+        let found: String = line.chars().skip(col as usize).take(text.chars().count()).collect();
+        if found != text {
+            self.fail(&format!("text at {},{} is {:?}", row, col, text), &format!("found {:?}", found), Some((row, col)));
+        }
+        self
+    }
+
+    /// The cursor is at a cell
+    #[track_caller]
+    pub fn cursor_at(&self, row: u32, col: u32) -> &Self {
+        self.check(&Expectation::CursorAt { row, col }, self.screen.cursor)
+    }
+
+    /// An expectation written as in scenario `expect` entries, e.g. `cell-color 0,4 #cd3131`
+    #[track_caller]
+    pub fn expect(&self, spec: &str) -> &Self {
+        match Expectation::parse(spec) {
+            Ok(expectation) => {
+                let focus = match expectation {
+                    Expectation::CellColor { row, col, .. } => Some((row, col)),
+                    _ => None,
+                };
+                self.check(&expectation, focus)
+            }
+            Err(problem) => panic!("{}", problem),
+        }
+    }
+
+    /// Checks on one cell (need the cell grid)
+    pub fn cell(&self, row: u32, col: u32) -> CellAssert<'_> {
+        CellAssert { parent: self, row, col }
+    }
+
+    #[track_caller]
+    fn check(&self, expectation: &Expectation, focus: Option<(u32, u32)>) -> &Self {
+        let result = expectation.check(&self.screen);
+        if !result.pass {
+            self.fail(&result.expectation, &result.message, focus);
+        }
+        self
+    }
+
+    #[track_caller]
+    fn fail(&self, expected: &str, message: &str, focus: Option<(u32, u32)>) -> ! {
+        let within = self.region.as_ref().map(|region| format!(" in {}", region)).unwrap_or_default();
+        panic!("expected {}{}\n  {}\n{}", expected, within, message, self.excerpt(focus))
+    }
+
+    /// Screen rows around `focus` with a marker under its column, or the whole screen
+    fn excerpt(&self, focus: Option<(u32, u32)>) -> String {
+        let lines: Vec<&str> = self.screen.text.lines().collect();
+        let (first, last) = match focus {
+            Some((row, _)) => (row.saturating_sub(EXCERPT_CONTEXT), row + EXCERPT_CONTEXT),
+            None => (0, lines.iter().rposition(|line| !line.trim().is_empty()).unwrap_or(0) as u32),
+        };
+        let mut out = String::from("screen:\n");
+        for (row, line) in (0u32..).zip(&lines).filter(|(row, _)| (first..=last).contains(row)) {
+            out.push_str(&format!("{:>4} | {}\n", row, line.trim_end()));
+            if let Some((focus_row, col)) = focus
+                && focus_row == row
+            {
+                out.push_str(&format!("     | {}^\n", " ".repeat(col as usize)));
+            }
+        }
+        out
+    }
+}
+
+/// Checks on one cell, from [`ScreenAssert::cell`]. Screen checks stay
+/// available, so `.cell(3, 10).has_fg(red).cursor_at(5, 0)` chains.
+#[derive(Debug, Clone, Copy)]
+pub struct CellAssert<'a> {
+    parent: &'a ScreenAssert,
+    row: u32,
+    col: u32,
+}
+
+impl CellAssert<'_> {
+    /// The cell holds `ch`
+    #[track_caller]
+    pub fn has_char(&self, ch: char) -> &Self {
+        let cell = self.cell();
+        if cell.ch != ch {
+            self.fail(&format!("{:?}", ch), &format!("found {:?}", cell.ch));
+        }
+        self
+    }
+
+    /// The cell's foreground color
+    #[track_caller]
+    pub fn has_fg(&self, color: [u8; 3]) -> &Self {
+        let cell = self.cell();
+        if cell.fg != color {
+            self.fail(&format!("foreground {}", hex(color)), &format!("{:?} has foreground {}", cell.ch, hex(cell.fg)));
+        }
+        self
+    }
+
+    /// The cell's background color
+    #[track_caller]
+    pub fn has_bg(&self, color: [u8; 3]) -> &Self {
+        let cell = self.cell();
+        if cell.bg != color {
+            self.fail(&format!("background {}", hex(color)), &format!("{:?} has background {}", cell.ch, hex(cell.bg)));
+        }
+        self
+    }
+
+    /// The cell has an attribute, e.g. [`GridAttribute::Bold`]
+    #[track_caller]
+    pub fn has_attr(&self, attr: GridAttribute) -> &Self {
+        let cell = self.cell();
+        if !cell.has(attr) {
+            self.fail(&format!("{:?}", attr), &format!("{:?} has attributes {:?}", cell.ch, cell.attrs));
+        }
+        self
+    }
+
+    /// The cell lacks an attribute
+    #[track_caller]
+    pub fn lacks_attr(&self, attr: GridAttribute) -> &Self {
+        let cell = self.cell();
+        if cell.has(attr) {
+            self.fail(&format!("no {:?}", attr), &format!("{:?} has attributes {:?}", cell.ch, cell.attrs));
+        }
+        self
+    }
+
+    #[track_caller]
+    fn cell(&self) -> &GridCell {
+        let Some(grid) = &self.parent.screen.grid else {
+            panic!("cell {},{}: the capture has no cell grid (enable RunOptions::capture_grid)", self.row, self.col)
+        };
+        match grid.cell(self.row, self.col) {
+            Some(cell) => cell,
+            None => panic!("cell {},{} is off screen ({}x{})", self.row, self.col, grid.width, grid.height),
+        }
+    }
+
+    #[track_caller]
+    fn fail(&self, expected: &str, message: &str) -> ! {
+        self.parent
+            .fail(&format!("cell {},{} to be {}", self.row, self.col, expected), message, Some((self.row, self.col)))
+    }
+}
+
+impl Deref for CellAssert<'_> {
+    type Target = ScreenAssert;
+
+    fn deref(&self) -> &ScreenAssert {
+        self.parent
+    }
+}
+
+impl StateCaptureResult {
+    /// Fluent assertions on this state's screen
+    pub fn assert(&self) -> ScreenAssert {
+        ScreenAssert::new(Screen::from_state(self))
+    }
+}
+
+impl CaptureResult {
+    /// Fluent assertions on this capture's screen (cell checks need the PTY backend's grid)
+    pub fn assert(&self) -> ScreenAssert {
+        match &self.grid {
+            Some(grid) => ScreenAssert::new(Screen::from_grid(grid.clone())),
+            None => ScreenAssert::new(Screen::from_text(self.text.clone().unwrap_or_default())),
+        }
+    }
+}
+
+impl TerminalGrid {
+    /// Fluent assertions on this grid
+    pub fn assert(&self) -> ScreenAssert {
+        ScreenAssert::new(Screen::from_grid(self.clone()))
+    }
+}
+
+fn unquote(text: &str) -> &str {
+    text.strip_prefix('"').and_then(|t| t.strip_suffix('"')).unwrap_or(text)
+}
+
+fn parse_position(text: &str) -> Result<(u32, u32), String> {
+    text.split_once(',')
+        .and_then(|(row, col)| Some((row.trim().parse().ok()?, col.trim().parse().ok()?)))
+        .ok_or_else(|| format!("malformed position '{}' (expected ROW,COL)", text))
+}
+
+fn parse_color(text: &str) -> Result<[u8; 3], String> {
+    let digits = text.strip_prefix('#').unwrap_or(text);
+    let channel = |i: usize| digits.get(i..i + 2).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+    match (digits.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok([r, g, b]),
+        _ => Err(format!("malformed color '{}' (expected #rrggbb)", text)),
+    }
+}
+
+fn hex(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::Vt100Parser;
+
+    #[test]
+    fn test_expectations_parse_and_print() {
+        let parsed = Expectation::parse("cell-color 3,10 #FF0000").unwrap();
+        assert_eq!(parsed, Expectation::CellColor { row: 3, col: 10, color: [255, 0, 0] });
+        assert_eq!(parsed.to_string(), "cell-color 3,10 #ff0000");
+        assert_eq!(Expectation::parse("contains \"Save as\"").unwrap(), Expectation::Contains("Save as".to_string()));
+        assert_eq!(Expectation::parse("cursor-at 5, 0").unwrap(), Expectation::CursorAt { row: 5, col: 0 });
+        assert!(Expectation::parse("regex (").unwrap_err().starts_with("invalid regex"));
+        assert!(Expectation::parse("cursor-at 5").is_err());
+        assert!(Expectation::parse("cell-color 1,1 red").is_err());
+        assert!(Expectation::parse("shows OK").unwrap_err().starts_with("unknown expectation"));
+        assert!(Expectation::parse("contains").is_err());
+    }
+
+    #[test]
+    fn test_expectations_check_text_colors_and_cursor() {
+        let mut parser = Vt100Parser::new(20, 3);
+        parser.process(b"v1.2 ready\r\n\x1b[31mError\x1b[0m: disk");
+        let screen = Screen::from_grid(parser.terminal().to_grid());
+        let expectations: Vec<_> = ["contains ready", "not-contains Error", "regex ^v\\d+\\.\\d+", "cell-color 1,0 #cd3131", "cursor-at 1,11"]
+            .iter()
+            .map(|spec| Expectation::parse(spec).unwrap())
+            .collect();
+        let report = check_all(&expectations, &screen);
+        let passes: Vec<_> = report.results.iter().map(|result| result.pass).collect();
+        assert_eq!(passes, [true, false, true, true, true]);
+        assert!(!report.pass);
+        assert_eq!(report.results[1].message, "'Error' is on screen at 1,0");
+
+        let text_only = Screen::from_text("v1.2 ready");
+        assert!(Expectation::parse("contains ready").unwrap().check(&text_only).pass);
+        assert_eq!(expectations[3].check(&text_only).message, "cell colors need a cell grid capture");
+    }
+
+    #[test]
+    fn test_fluent_assertions_chain_and_show_the_failing_region() {
+        let mut parser = Vt100Parser::new(20, 6);
+        parser.process(b"Menu\r\n\r\n\r\n  \x1b[1;31mQuit\x1b[0m\r\n");
+        let grid = parser.terminal().to_grid();
+        grid.assert()
+            .contains_text("Menu")
+            .not_contains_text("Error")
+            .cell(3, 2)
+            .has_char('Q')
+            .has_fg([0xcd, 0x31, 0x31])
+            .has_attr(GridAttribute::Bold)
+            .cursor_at(4, 0)
+            .text_at(3, 2, "Quit")
+            .expect("regex ^\\s+Quit$");
+
+        let failure = std::panic::catch_unwind(|| {
+            grid.assert().cell(3, 3).has_bg([255, 255, 255]);
+        })
+        .unwrap_err();
+        let message = failure.downcast_ref::<String>().unwrap();
+        assert_eq!(
+            message,
+            "expected cell 3,3 to be background #ffffff\n  'u' has background #000000\nscreen:\n   1 | \n   2 | \n   3 |   Quit\n     |    ^\n   4 | \n"
+        );
+    }
+
+    #[test]
+    fn test_regions_limit_checks() {
+        let mut parser = Vt100Parser::new(20, 4);
+        parser.process(b"Error log\r\n\r\n\r\nReady \x1b[32mok\x1b[0m");
+        let statusbar = CellRect::parse("0,3,20,1").unwrap();
+        let screen = Screen::from_grid(parser.terminal().to_grid()).with_region("statusbar", statusbar);
+        let check = |spec: &str| Expectation::parse(spec).unwrap().check(&screen);
+        assert!(check("in statusbar not-contains Error").pass);
+        assert!(check("in statusbar cell-color 0,6 #0dbc79").pass);
+        assert!(check("in 20x1+0+3 regex ^Ready").pass);
+        assert_eq!(check("in statusbar contains log").message, "'log' is not on screen");
+        assert_eq!(check("in footer contains log").message, "unknown region 'footer'");
+        assert_eq!(Expectation::parse("in statusbar contains ok").unwrap().region(), Some("statusbar"));
+
+        let cropped = screen.crop(statusbar);
+        assert_eq!(cropped.text.trim_end(), "Ready ok");
+        assert_eq!(cropped.cursor, Some((0, 8)));
+
+        let fluent = ScreenAssert::new(screen.clone());
+        fluent.region("statusbar").contains_text("Ready").cell(0, 6).has_fg([0x0d, 0xbc, 0x79]);
+        let failure = std::panic::catch_unwind(|| {
+            fluent.within(statusbar).contains_text("Error");
+        })
+        .unwrap_err();
+        assert!(failure.downcast_ref::<String>().unwrap().starts_with("expected contains Error in region 20x1+0+3\n"));
+    }
+}
@@ -0,0 +1,385 @@
+//! Exploratory runs where the VLM chooses the keys.
+//!
+//! A fuzz run (see [`crate::snapshot::fuzz`]) presses random keys; an
+//! exploration lets the model decide. After each state the model sees the
+//! screenshot, the screen's text and the keys pressed so far, and picks the
+//! next key from a fixed list, saying why. The whole trajectory is kept in
+//! an [`ExploreReport`]: each state's screenshot, what the model saw there,
+//! the key it chose and its reason.
+//!
+//! The run ends after the step budget, when the model has nothing left to
+//! try, or when the application exits. Exits and screens the model calls
+//! dead ends (errors, hangs, no way forward or back) are what a reviewer
+//! should look at. The model's choices are not reproducible, but the keys
+//! are recorded and can be replayed with `run`.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::explore::{run_explore, ExploreConfig};
+//! use cli_vision::vlm::VlmConfig;
+//!
+//! let config = ExploreConfig::new(30).goal("Open the settings and change a value");
+//! let report = run_explore("./my-tui", &[], &config, &VlmConfig::default(), |step| {
+//!     println!("{:?} -> {:?}", step.observation, step.next_key);
+//! })
+//! .unwrap();
+//! assert!(report.died.is_none(), "died after {:?}", report.inputs());
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::snapshot::{run_driven, RunOptions, SnapshotResult, StateCaptureResult, TerminalSize};
+use crate::vlm::{analyze_image_structured, PromptContext, VlmConfig, VlmError};
+
+/// Keys the model chooses from by default: navigation, confirming and
+/// cancelling, and the usual help, search and vi-style keys
+pub const DEFAULT_EXPLORE_KEYS: &[&str] = &[
+    "up", "down", "left", "right", "enter", "escape", "tab", "shift+tab", "space", "backspace", "pageup", "pagedown",
+    "home", "end", "?", "/", "h", "j", "k", "l", "f1",
+];
+
+/// What the model answers instead of a key when there is nothing left to try
+pub const STOP: &str = "stop";
+
+/// Earlier steps shown to the model with each state
+const HISTORY_STEPS: usize = 12;
+
+/// Configuration for an exploration
+#[derive(Debug, Clone)]
+pub struct ExploreConfig {
+    /// Most keys to press
+    pub steps: usize,
+    /// Keys the model may choose from
+    pub keys: Vec<String>,
+    /// What to try to reach or do, if anything in particular
+    pub goal: Option<String>,
+    /// Delay before each key (milliseconds)
+    pub input_delay_ms: u64,
+    /// Terminal size
+    pub size: TerminalSize,
+    /// Capture options (theme, environment, limits, ...)
+    pub options: RunOptions,
+}
+
+impl ExploreConfig {
+    /// Press up to `steps` keys from [`DEFAULT_EXPLORE_KEYS`]
+    pub fn new(steps: usize) -> Self {
+        Self {
+            steps,
+            keys: DEFAULT_EXPLORE_KEYS.iter().map(|key| key.to_string()).collect(),
+            goal: None,
+            input_delay_ms: 100,
+            size: TerminalSize::default(),
+            options: RunOptions::default(),
+        }
+    }
+
+    /// Choose from these keys instead of the default list
+    pub fn keys(mut self, keys: Vec<String>) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    /// Leave keys out of the list, e.g. the application's quit key
+    pub fn exclude(mut self, keys: &[&str]) -> Self {
+        self.keys.retain(|key| !keys.iter().any(|excluded| excluded.eq_ignore_ascii_case(key)));
+        self
+    }
+
+    /// Steer the exploration towards a goal
+    pub fn goal(mut self, goal: impl Into<String>) -> Self {
+        self.goal = Some(goal.into());
+        self
+    }
+
+    /// Set the delay before each key
+    pub fn input_delay(mut self, delay_ms: u64) -> Self {
+        self.input_delay_ms = delay_ms;
+        self
+    }
+
+    /// Set the terminal size
+    pub fn size(mut self, size: TerminalSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Set capture options
+    pub fn options(mut self, options: RunOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+/// The model's answer about one state
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExploreDecision {
+    /// What is on the screen
+    pub observation: String,
+    /// Whether the screen is a dead end
+    pub dead_end: bool,
+    /// Key to press next, or [`STOP`]
+    pub key: String,
+    /// Why that key
+    pub rationale: String,
+}
+
+/// One state of the trajectory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExploreStep {
+    /// State number (0 = initial)
+    pub step: usize,
+    /// Key that led to the state
+    pub input: Option<String>,
+    /// What the model saw on the screen
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub observation: Option<String>,
+    /// Whether the model called the screen a dead end
+    #[serde(default)]
+    pub dead_end: bool,
+    /// Whether the screen's text is the same as before the key
+    #[serde(default)]
+    pub unchanged: bool,
+    /// Key pressed next, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_key: Option<String>,
+    /// Why the model chose the next key (or to stop)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rationale: Option<String>,
+    /// Exit code, if the application had exited in this state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<u32>,
+    /// PNG screenshot
+    #[serde(skip)]
+    pub image_data: Vec<u8>,
+}
+
+/// Result of an exploration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExploreReport {
+    /// What the exploration was steered towards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub goal: Option<String>,
+    /// Every state, in order
+    pub steps: Vec<ExploreStep>,
+    /// Distinct screens seen, by text
+    pub screens: usize,
+    /// Why the application is considered dead, if it died
+    pub died: Option<String>,
+    /// Why the exploration ended before the step budget, if it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stopped: Option<String>,
+}
+
+impl ExploreReport {
+    /// The keys pressed, in order
+    pub fn inputs(&self) -> Vec<String> {
+        self.steps.iter().filter_map(|step| step.input.clone()).collect()
+    }
+
+    /// States the model called dead ends
+    pub fn dead_ends(&self) -> impl Iterator<Item = &ExploreStep> {
+        self.steps.iter().filter(|step| step.dead_end)
+    }
+}
+
+/// Explore an application, letting the VLM choose each key.
+///
+/// `on_step` is called with each state once the model has decided what to
+/// do there, so progress can be shown as the exploration goes. A failed VLM
+/// request ends the exploration; the states up to it are still reported.
+pub fn run_explore(
+    command: &str,
+    args: &[String],
+    config: &ExploreConfig,
+    vlm: &VlmConfig,
+    mut on_step: impl FnMut(&ExploreStep),
+) -> SnapshotResult<ExploreReport> {
+    let schema = decision_schema(&config.keys);
+    let binary = Path::new(command).file_name().map(|name| name.to_string_lossy().to_string());
+    let mut steps: Vec<ExploreStep> = Vec::new();
+    let mut stopped = None;
+
+    // Captures are collected as they happen, so they survive the run failing
+    let captures: Arc<Mutex<Vec<StateCaptureResult>>> = Arc::default();
+    let sink = captures.clone();
+    let options = config
+        .options
+        .clone()
+        .stop_on_exit(true)
+        .on_capture(move |capture| sink.lock().unwrap_or_else(|p| p.into_inner()).push(capture.clone()));
+    let result = run_driven(command, args, config.input_delay_ms, config.size, &options, |captures| {
+        let current = captures.last()?;
+        let mut step = explore_step(captures);
+        let context = PromptContext {
+            step: current.step,
+            input: current.input.clone(),
+            prior_inputs: steps.iter().filter_map(|step| step.input.clone()).collect(),
+            terminal_size: Some(config.size.dimensions().into()),
+            binary: binary.clone(),
+            scenario: None,
+            screen_text: Some(current.text.clone()),
+        };
+        let prompt = vlm.contextual_prompt(&build_explore_prompt(config.goal.as_deref(), &config.keys, &steps), &context);
+        match decide(vlm, &current.image_data, &prompt, &schema) {
+            Ok(decision) => {
+                step.observation = Some(decision.observation);
+                step.dead_end = decision.dead_end;
+                step.rationale = Some(decision.rationale);
+                if decision.key == STOP {
+                    stopped = Some("the model found nothing left to try".to_string());
+                } else if current.step < config.steps {
+                    step.next_key = Some(decision.key);
+                }
+            }
+            Err(e) => stopped = Some(format!("VLM request failed: {}", e)),
+        }
+        on_step(&step);
+        let next = step.next_key.clone();
+        steps.push(step);
+        next
+    });
+    let captures = std::mem::take(&mut *captures.lock().unwrap_or_else(|p| p.into_inner()));
+
+    let died = match result {
+        Ok(_) => captures.last().and_then(|last| last.exit_code).map(|code| format!("exited with code {}", code)),
+        // Nothing was captured, so the application never ran
+        Err(e) if captures.is_empty() => return Err(e),
+        Err(e) => Some(e.to_string()),
+    };
+    // The state the application died in was never shown to the model
+    for end in steps.len()..captures.len() {
+        let step = explore_step(&captures[..=end]);
+        on_step(&step);
+        steps.push(step);
+    }
+
+    Ok(ExploreReport {
+        goal: config.goal.clone(),
+        screens: captures.iter().map(|capture| capture.text.as_str()).collect::<BTreeSet<_>>().len(),
+        steps,
+        died,
+        stopped,
+    })
+}
+
+/// The last of `captures` as a step, before the model has seen it
+fn explore_step(captures: &[StateCaptureResult]) -> ExploreStep {
+    let current = &captures[captures.len() - 1];
+    ExploreStep {
+        step: current.step,
+        input: current.input.clone(),
+        observation: None,
+        dead_end: false,
+        unchanged: captures.len() > 1 && captures[captures.len() - 2].text == current.text,
+        next_key: None,
+        rationale: None,
+        exit_code: current.exit_code,
+        image_data: current.image_data.clone(),
+    }
+}
+
+/// Ask the model about a state
+fn decide(vlm: &VlmConfig, image_data: &[u8], prompt: &str, schema: &serde_json::Value) -> Result<ExploreDecision, VlmError> {
+    let answer = analyze_image_structured(vlm, image_data, prompt, schema)?;
+    serde_json::from_value(answer).map_err(|e| VlmError::InvalidResponse(format!("exploration answer: {}", e)))
+}
+
+/// JSON Schema of an [`ExploreDecision`] choosing from `keys` or [`STOP`]
+pub fn decision_schema(keys: &[String]) -> serde_json::Value {
+    let choices: Vec<&str> = keys.iter().map(String::as_str).chain([STOP]).collect();
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "observation": { "type": "string", "description": "What is on the screen, in one or two sentences" },
+            "dead_end": { "type": "boolean", "description": "Whether the screen is an error, looks stuck, or offers no way forward or back" },
+            "key": { "enum": choices, "description": "Key to press next" },
+            "rationale": { "type": "string", "description": "Why that key" }
+        },
+        "required": ["observation", "dead_end", "key", "rationale"],
+        "additionalProperties": false
+    })
+}
+
+/// The prompt for choosing the next key, given the steps taken so far
+pub fn build_explore_prompt(goal: Option<&str>, keys: &[String], history: &[ExploreStep]) -> String {
+    let mut prompt = String::from(
+        "You are exploring this terminal application to find crashes, error messages and dead ends: \
+         screens that are stuck or offer no way forward or back.",
+    );
+    if let Some(goal) = goal {
+        prompt.push_str(&format!(" Your goal: {}", goal));
+    }
+    if !history.is_empty() {
+        prompt.push_str("\n\nRecent steps (screen, then the key pressed there):");
+        for step in &history[history.len().saturating_sub(HISTORY_STEPS)..] {
+            let observation = step.observation.as_deref().unwrap_or("(not described)");
+            let key = step.next_key.as_deref().unwrap_or("-");
+            let unchanged = if step.unchanged { " (the previous key did not change the screen)" } else { "" };
+            prompt.push_str(&format!("\n{}. {}{} -> {}", step.step, observation, unchanged, key));
+        }
+    }
+    prompt.push_str(&format!(
+        "\n\nChoose the next key from: {}. Prefer keys that lead to screens you have not seen, and do not repeat \
+         keys that left the screen unchanged. Answer \"{}\" as the key when there is nothing left to try. \
+         Set dead_end if this screen is an error, looks stuck, or offers no way forward or back.",
+        keys.join(", "),
+        STOP
+    ));
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prompt_offers_keys_and_history() {
+        let keys: Vec<String> = ["down", "enter"].iter().map(|key| key.to_string()).collect();
+        let schema = decision_schema(&keys);
+        assert_eq!(schema["properties"]["key"]["enum"], serde_json::json!(["down", "enter", "stop"]));
+        let answer = r#"{"observation":"A menu","dead_end":false,"key":"down","rationale":"Try the next item"}"#;
+        let problems = crate::schema::validate_json(answer, &schema);
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert!(!crate::schema::validate_json(&answer.replace("\"down\"", "\"q\""), &schema).is_empty());
+
+        let history = [ExploreStep {
+            step: 0,
+            input: None,
+            observation: Some("A menu".to_string()),
+            dead_end: false,
+            unchanged: false,
+            next_key: Some("down".to_string()),
+            rationale: Some("Try the next item".to_string()),
+            exit_code: None,
+            image_data: Vec::new(),
+        }];
+        let prompt = build_explore_prompt(Some("Open the settings"), &keys, &history);
+        assert!(prompt.contains("Your goal: Open the settings"));
+        assert!(prompt.contains("0. A menu -> down"));
+        assert!(prompt.contains("Choose the next key from: down, enter."));
+        assert!(!build_explore_prompt(None, &keys, &[]).contains("Recent steps"));
+        let config = ExploreConfig::new(5).exclude(&["ESCAPE"]);
+        assert!(!config.keys.iter().any(|key| key == "escape"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failed_decision_ends_exploration() {
+        // The local model cannot answer in JSON, so the first decision fails
+        let config = ExploreConfig::new(5).input_delay(0);
+        let mut seen = Vec::new();
+        let report = run_explore("/bin/sh", &["-c".to_string(), "read a".to_string()], &config, &VlmConfig::new("local"), |step| {
+            seen.push(step.step)
+        })
+        .unwrap();
+        assert_eq!(seen, [0]);
+        assert_eq!(report.steps.len(), 1);
+        assert!(report.stopped.as_deref().is_some_and(|reason| reason.starts_with("VLM request failed")));
+        assert!(report.inputs().is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+//! Pinned third-party TUI binaries (htop, vim, ...) for reproducible suites.
+//!
+//! Suites declare targets by name and version in a fixture manifest; the
+//! [`FixtureCache`] downloads the pinned static build on first use, verifies
+//! its SHA-256 checksum and reuses the cached copy afterwards.
+//!
+//! # Manifest
+//!
+//! ```json
+//! {
+//!   "fixtures": [
+//!     {
+//!       "name": "htop",
+//!       "version": "3.3.0",
+//!       "platform": "x86_64-linux",
+//!       "url": "https://example.com/htop-3.3.0-x86_64-linux",
+//!       "sha256": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::fixtures::{FixtureCache, FixtureManifest};
+//!
+//! let manifest = FixtureManifest::from_file("fixtures.json").unwrap();
+//! let htop = FixtureCache::from_env().fetch_named(&manifest, "htop", "3.3.0").unwrap();
+//! println!("htop binary: {}", htop.display());
+//! ```
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::{self, ENV_FIXTURE_DIR};
+
+/// Errors from fixture lookup and download
+#[derive(Debug)]
+pub enum FixtureError {
+    /// No manifest entry for the name/version on this platform
+    NotFound {
+        name: String,
+        version: String,
+    },
+    /// The download failed
+    Download(String),
+    /// The downloaded file does not match the pinned checksum
+    ChecksumMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+    /// The manifest could not be parsed
+    Manifest(serde_json::Error),
+    /// IO error
+    Io(io::Error),
+}
+
+impl std::fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FixtureError::NotFound { name, version } => {
+                write!(f, "No fixture '{}@{}' for platform {}", name, version, current_platform())
+            }
+            FixtureError::Download(msg) => write!(f, "Download failed: {}", msg),
+            FixtureError::ChecksumMismatch { name, expected, actual } => write!(
+                f,
+                "Checksum mismatch for '{}': expected {}, got {}",
+                name, expected, actual
+            ),
+            FixtureError::Manifest(e) => write!(f, "Invalid fixture manifest: {}", e),
+            FixtureError::Io(e) => write!(f, "IO error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FixtureError {}
+
+impl From<io::Error> for FixtureError {
+    fn from(e: io::Error) -> Self {
+        FixtureError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for FixtureError {
+    fn from(e: serde_json::Error) -> Self {
+        FixtureError::Manifest(e)
+    }
+}
+
+/// A pinned binary build
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fixture {
+    /// Fixture name (e.g. `htop`); also the cached file name
+    pub name: String,
+    /// Pinned version
+    pub version: String,
+    /// Platform the build runs on (`<arch>-<os>`, e.g. `x86_64-linux`); any if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+    /// Download URL: `http://`, `https://` or `file://`
+    pub url: String,
+    /// Expected SHA-256 of the file, hex encoded
+    pub sha256: String,
+}
+
+/// A set of fixtures, usually loaded from a JSON manifest
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FixtureManifest {
+    pub fixtures: Vec<Fixture>,
+}
+
+impl FixtureManifest {
+    /// Load a manifest from a JSON file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, FixtureError> {
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Find a fixture for the current platform, preferring platform-specific entries
+    pub fn get(&self, name: &str, version: &str) -> Option<&Fixture> {
+        let platform = current_platform();
+        let candidates = || self.fixtures.iter().filter(|f| f.name == name && f.version == version);
+        candidates()
+            .find(|f| f.platform.as_deref() == Some(platform.as_str()))
+            .or_else(|| candidates().find(|f| f.platform.is_none()))
+    }
+}
+
+/// The current platform as `<arch>-<os>` (e.g. `x86_64-linux`)
+pub fn current_platform() -> String {
+    format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
+
+/// Hex-encoded SHA-256 of a file
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let digest = Sha256::digest(fs::read(path)?);
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Local cache of downloaded fixtures
+#[derive(Debug, Clone)]
+pub struct FixtureCache {
+    /// Cache directory; fixtures live at `<dir>/<name>/<version>/<name>`
+    pub dir: PathBuf,
+}
+
+impl FixtureCache {
+    /// Create a cache in the given directory
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Create a cache in `CLI_VISION_FIXTURE_DIR`, or `~/.cache/cli-vision/fixtures`
+    pub fn from_env() -> Self {
+        if let Ok(dir) = std::env::var(ENV_FIXTURE_DIR) {
+            return Self::new(dir);
+        }
+        Self::new(config::cache_base_dir().join("fixtures"))
+    }
+
+    /// Path a fixture is cached at
+    pub fn path_for(&self, fixture: &Fixture) -> PathBuf {
+        self.dir.join(&fixture.name).join(&fixture.version).join(&fixture.name)
+    }
+
+    /// Return the cached binary, downloading and verifying it first if needed
+    pub fn fetch(&self, fixture: &Fixture) -> Result<PathBuf, FixtureError> {
+        let path = self.path_for(fixture);
+        if path.exists() && sha256_file(&path)?.eq_ignore_ascii_case(&fixture.sha256) {
+            return Ok(path);
+        }
+
+        let parent = path.parent().expect("fixture path has a parent");
+        fs::create_dir_all(parent)?;
+        let partial = path.with_extension("part");
+        if let Err(e) = download(&fixture.url, &partial) {
+            let _ = fs::remove_file(&partial);
+            return Err(FixtureError::Download(format!("{}: {}", fixture.url, e)));
+        }
+
+        let actual = sha256_file(&partial)?;
+        if !actual.eq_ignore_ascii_case(&fixture.sha256) {
+            let _ = fs::remove_file(&partial);
+            return Err(FixtureError::ChecksumMismatch {
+                name: fixture.name.clone(),
+                expected: fixture.sha256.clone(),
+                actual,
+            });
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&partial, fs::Permissions::from_mode(0o755))?;
+        }
+        fs::rename(&partial, &path)?;
+        Ok(path)
+    }
+
+    /// Look up a fixture in a manifest and fetch it
+    pub fn fetch_named(&self, manifest: &FixtureManifest, name: &str, version: &str) -> Result<PathBuf, FixtureError> {
+        let fixture = manifest.get(name, version).ok_or_else(|| FixtureError::NotFound {
+            name: name.to_string(),
+            version: version.to_string(),
+        })?;
+        self.fetch(fixture)
+    }
+}
+
+/// Longest a fixture download may take
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Copy `url` to `dest`, reading `file://` URLs from disk and following HTTP redirects
+fn download(url: &str, dest: &Path) -> Result<(), String> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return fs::copy(path, dest).map(drop).map_err(|e| e.to_string());
+    }
+    let agent = ureq::AgentBuilder::new().timeout(DOWNLOAD_TIMEOUT).build();
+    let response = agent.get(url).call().map_err(|e| e.to_string())?;
+    let mut file = fs::File::create(dest).map_err(|e| e.to_string())?;
+    io::copy(&mut response.into_reader(), &mut file).map(drop).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_fixture(dir: &Path, contents: &str, sha256: &str) -> Fixture {
+        let source = dir.join("source-bin");
+        fs::write(&source, contents).unwrap();
+        Fixture {
+            name: "tool".to_string(),
+            version: "1.0".to_string(),
+            platform: None,
+            url: format!("file://{}", source.display()),
+            sha256: sha256.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_fetch_verifies_and_caches() {
+        let dir = tempfile::tempdir().unwrap();
+        // sha256("hello\n")
+        let sha = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+        let fixture = local_fixture(dir.path(), "hello\n", sha);
+        let cache = FixtureCache::new(dir.path().join("cache"));
+
+        let path = cache.fetch(&fixture).unwrap();
+        assert_eq!(path, dir.path().join("cache/tool/1.0/tool"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello\n");
+
+        // Served from the cache once the source is gone
+        fs::remove_file(dir.path().join("source-bin")).unwrap();
+        assert_eq!(cache.fetch(&fixture).unwrap(), path);
+    }
+
+    #[test]
+    fn test_checksum_mismatch_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = local_fixture(dir.path(), "tampered\n", &"0".repeat(64));
+        let cache = FixtureCache::new(dir.path().join("cache"));
+
+        let err = cache.fetch(&fixture).unwrap_err();
+        assert!(matches!(err, FixtureError::ChecksumMismatch { .. }));
+        assert!(!cache.path_for(&fixture).exists());
+    }
+
+    #[test]
+    fn test_manifest_prefers_platform_specific_entries() {
+        let generic = Fixture {
+            name: "vim".to_string(),
+            version: "9.1".to_string(),
+            platform: None,
+            url: "https://example.com/vim".to_string(),
+            sha256: String::new(),
+        };
+        let native = Fixture {
+            platform: Some(current_platform()),
+            url: "https://example.com/vim-native".to_string(),
+            ..generic.clone()
+        };
+        let manifest = FixtureManifest {
+            fixtures: vec![generic, native],
+        };
+        assert_eq!(manifest.get("vim", "9.1").unwrap().url, "https://example.com/vim-native");
+        assert!(manifest.get("vim", "9.0").is_none());
+    }
+}
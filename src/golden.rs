@@ -0,0 +1,374 @@
+//! Golden captures: record a state once, then fail when it changes.
+//!
+//! The first run of [`assert_matches_golden`] records the capture under
+//! `goldens/<name>` and passes: the screen text as `<name>.txt`, the cell
+//! grid as `<name>.grid.json` when the capture carries one (see
+//! [`RunOptions::capture_grid`](crate::snapshot::RunOptions::capture_grid)),
+//! and with [`Golden::images`] the screenshot as `<name>.png`. Later runs
+//! compare against those files and fail with a text, cell and pixel diff.
+//!
+//! Set `CLI_VISION_UPDATE=1` to accept changed captures as the new goldens.
+//! On CI (`CI` set) missing goldens fail instead of being recorded, so a
+//! forgotten golden does not pass silently. `CLI_VISION_GOLDEN_DIR` moves
+//! the goldens out of `<crate>/goldens`.
+//!
+//! Unlike [`expect_snapshot!`](crate::expect_snapshot), goldens have no
+//! review step: `run --golden-dir DIR [--update]` checks every state of a
+//! run the same way.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::golden::assert_matches_golden;
+//! use cli_vision::snapshot::{run_with_options, RunOptions, TerminalSize};
+//!
+//! let options = RunOptions::default().capture_grid(true);
+//! let states = run_with_options("./my-tui", &[], &["down".to_string()], 100, TerminalSize::default(), &options).unwrap();
+//! assert_matches_golden("menu_after_down", &states[1]);
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::snapshot::{diff_images, CaptureResult, StateCaptureResult, TerminalGrid};
+use crate::testing::{line_diff, normalize_text, read_if_exists};
+
+/// Environment variable selecting the golden directory
+pub const GOLDEN_DIR_ENV: &str = "CLI_VISION_GOLDEN_DIR";
+
+/// Environment variable that accepts changed captures as goldens when set to `1`
+pub const UPDATE_ENV: &str = "CLI_VISION_UPDATE";
+
+/// Changed cells listed in a grid mismatch before the rest are summarized
+const MAX_LISTED_CELLS: usize = 10;
+
+/// Values that can be checked against a golden
+pub trait GoldenCapture {
+    /// Screen text, one line per row
+    fn golden_text(&self) -> String;
+
+    /// Cell grid, if captured
+    fn golden_grid(&self) -> Option<&TerminalGrid> {
+        None
+    }
+
+    /// PNG screenshot, if any
+    fn golden_png(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+impl GoldenCapture for StateCaptureResult {
+    fn golden_text(&self) -> String {
+        self.text.clone()
+    }
+
+    fn golden_grid(&self) -> Option<&TerminalGrid> {
+        self.grid.as_ref()
+    }
+
+    fn golden_png(&self) -> Option<&[u8]> {
+        Some(&self.image_data)
+    }
+}
+
+impl GoldenCapture for CaptureResult {
+    fn golden_text(&self) -> String {
+        self.text.clone().unwrap_or_default()
+    }
+
+    fn golden_grid(&self) -> Option<&TerminalGrid> {
+        self.grid.as_ref()
+    }
+
+    fn golden_png(&self) -> Option<&[u8]> {
+        Some(&self.image_data)
+    }
+}
+
+impl GoldenCapture for TerminalGrid {
+    fn golden_text(&self) -> String {
+        (0..self.height).map(|row| self.row_text(row)).collect::<Vec<_>>().join("\n")
+    }
+
+    fn golden_grid(&self) -> Option<&TerminalGrid> {
+        Some(self)
+    }
+}
+
+/// Outcome of checking a capture against its golden
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GoldenOutcome {
+    /// Every stored file matches
+    Matched,
+    /// Goldens did not exist yet and were written
+    Recorded(Vec<PathBuf>),
+    /// Changed goldens were overwritten because updates are on
+    Updated(Vec<PathBuf>),
+    /// No golden exists and recording is off (on CI)
+    Missing(Vec<PathBuf>),
+    /// The capture differs from its golden
+    Mismatch(String),
+}
+
+impl GoldenOutcome {
+    /// Whether the check passes
+    pub fn passed(&self) -> bool {
+        matches!(self, GoldenOutcome::Matched | GoldenOutcome::Recorded(_) | GoldenOutcome::Updated(_))
+    }
+}
+
+/// Where goldens live and how new and changed captures are treated
+#[derive(Debug, Clone)]
+pub struct Golden {
+    /// Directory holding the golden files
+    pub dir: PathBuf,
+    /// Overwrite goldens that differ
+    pub update: bool,
+    /// Write goldens that do not exist yet
+    pub record_new: bool,
+    /// Also keep and compare the PNG screenshot
+    pub images: bool,
+}
+
+impl Golden {
+    /// Goldens in `dir`, recording missing ones and failing on changes
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            update: false,
+            record_new: true,
+            images: false,
+        }
+    }
+
+    /// Goldens configured by the environment.
+    ///
+    /// The directory is `CLI_VISION_GOLDEN_DIR` or `<crate>/goldens`;
+    /// `CLI_VISION_UPDATE=1` turns updates on, and on CI (`CI` set and not
+    /// `false`) missing goldens are not recorded.
+    pub fn from_env() -> Self {
+        let dir = std::env::var(GOLDEN_DIR_ENV).map(PathBuf::from).unwrap_or_else(|_| {
+            let base = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+            Path::new(&base).join("goldens")
+        });
+        let on_ci = std::env::var("CI").is_ok_and(|v| !matches!(v.trim(), "" | "0" | "false"));
+        Self::new(dir).update(update_from_env()).record_new(!on_ci)
+    }
+
+    /// Overwrite goldens that differ
+    pub fn update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    /// Write goldens that do not exist yet
+    pub fn record_new(mut self, record_new: bool) -> Self {
+        self.record_new = record_new;
+        self
+    }
+
+    /// Also keep and compare the PNG screenshot
+    pub fn images(mut self, images: bool) -> Self {
+        self.images = images;
+        self
+    }
+
+    /// Check a capture against golden `name`, writing files as configured.
+    ///
+    /// `name` may contain `/` to group goldens in subdirectories.
+    pub fn check(&self, name: &str, capture: &(impl GoldenCapture + ?Sized)) -> io::Result<GoldenOutcome> {
+        let mut files = vec![(GoldenFile::Text, normalize_text(&capture.golden_text()).into_bytes())];
+        if let Some(grid) = capture.golden_grid() {
+            files.push((GoldenFile::Grid, serde_json::to_vec(grid)?));
+        }
+        if self.images
+            && let Some(png) = capture.golden_png()
+        {
+            files.push((GoldenFile::Image, png.to_vec()));
+        }
+
+        let mut missing = Vec::new();
+        let mut changed = Vec::new();
+        let mut diff = String::new();
+        for (kind, contents) in files {
+            let path = self.dir.join(format!("{}{}", name, kind.suffix()));
+            match read_if_exists(&path)? {
+                None => missing.push((path, contents)),
+                Some(stored) => {
+                    if let Some(file_diff) = kind.diff(&stored, &contents) {
+                        diff.push_str(&file_diff);
+                        changed.push((path, contents));
+                    }
+                }
+            }
+        }
+
+        if !changed.is_empty() && !self.update {
+            return Ok(GoldenOutcome::Mismatch(diff));
+        }
+        if !missing.is_empty() && !self.record_new && !self.update {
+            return Ok(GoldenOutcome::Missing(missing.into_iter().map(|(path, _)| path).collect()));
+        }
+        let updated = !changed.is_empty();
+        let written = write_all(changed.into_iter().chain(missing))?;
+        Ok(match written {
+            _ if written.is_empty() => GoldenOutcome::Matched,
+            _ if updated => GoldenOutcome::Updated(written),
+            _ => GoldenOutcome::Recorded(written),
+        })
+    }
+
+    /// Assert that a capture matches golden `name`, panicking with a diff otherwise
+    #[track_caller]
+    pub fn assert_matches(&self, name: &str, capture: &(impl GoldenCapture + ?Sized)) {
+        let location = std::panic::Location::caller();
+        let outcome = self
+            .check(name, capture)
+            .unwrap_or_else(|e| panic!("golden '{}' ({}): failed to access golden files: {}", name, location, e));
+        match outcome {
+            GoldenOutcome::Matched | GoldenOutcome::Recorded(_) | GoldenOutcome::Updated(_) => {}
+            GoldenOutcome::Missing(paths) => panic!(
+                "golden '{}' ({}) does not exist ({}) and recording is off on CI; record it locally or set {}=1",
+                name,
+                location,
+                paths[0].display(),
+                UPDATE_ENV
+            ),
+            GoldenOutcome::Mismatch(diff) => panic!(
+                "golden '{}' ({}) does not match:\n{}set {}=1 to accept the new capture",
+                name, location, diff, UPDATE_ENV
+            ),
+        }
+    }
+}
+
+/// Assert that a capture matches golden `name` in the directory and mode
+/// chosen by the environment (see [`Golden::from_env`]).
+///
+/// Goldens are recorded on the first run; afterwards a changed screen fails
+/// with a diff until it is accepted with `CLI_VISION_UPDATE=1`.
+#[track_caller]
+pub fn assert_matches_golden(name: &str, capture: &(impl GoldenCapture + ?Sized)) {
+    Golden::from_env().assert_matches(name, capture);
+}
+
+/// Whether `CLI_VISION_UPDATE` asks for goldens to be updated
+pub fn update_from_env() -> bool {
+    std::env::var(UPDATE_ENV).is_ok_and(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes" | "always"))
+}
+
+/// Files a golden is made of
+#[derive(Debug, Clone, Copy)]
+enum GoldenFile {
+    Text,
+    Grid,
+    Image,
+}
+
+impl GoldenFile {
+    fn suffix(self) -> &'static str {
+        match self {
+            GoldenFile::Text => ".txt",
+            GoldenFile::Grid => ".grid.json",
+            GoldenFile::Image => ".png",
+        }
+    }
+
+    /// Description of how `new` differs from the stored file, or `None` if they match
+    fn diff(self, stored: &[u8], new: &[u8]) -> Option<String> {
+        match self {
+            GoldenFile::Text => {
+                (stored != new).then(|| format!("text:\n{}", line_diff(&String::from_utf8_lossy(stored), &String::from_utf8_lossy(new))))
+            }
+            GoldenFile::Grid => {
+                let (Ok(old), Ok(current)) = (serde_json::from_slice::<TerminalGrid>(stored), serde_json::from_slice(new)) else {
+                    return Some("grid: stored golden is not a readable grid\n".to_string());
+                };
+                let diff = old.diff(&current);
+                if diff.is_empty() {
+                    return None;
+                }
+                let mut out = format!("grid: {}\n", diff.summary());
+                for change in diff.changes.iter().take(MAX_LISTED_CELLS) {
+                    let ch = |cell: &Option<crate::snapshot::GridCell>| cell.as_ref().map_or(' ', |cell| cell.ch);
+                    out.push_str(&format!(
+                        "  row {} col {}: {:?} -> {:?} ({:?})\n",
+                        change.row,
+                        change.col,
+                        ch(&change.before),
+                        ch(&change.after),
+                        change.fields
+                    ));
+                }
+                if diff.changes.len() > MAX_LISTED_CELLS {
+                    out.push_str(&format!("  ... and {} more\n", diff.changes.len() - MAX_LISTED_CELLS));
+                }
+                Some(out)
+            }
+            GoldenFile::Image => match diff_images(stored, new, 0) {
+                Ok(diff) if diff.is_identical() => None,
+                Ok(diff) => Some(format!("image: {}\n", diff.summary())),
+                Err(e) => Some(format!("image: {}\n", e)),
+            },
+        }
+    }
+}
+
+fn write_all(files: impl Iterator<Item = (PathBuf, Vec<u8>)>) -> io::Result<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    for (path, contents) in files {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, contents)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::Vt100Parser;
+
+    fn grid(bytes: &[u8]) -> TerminalGrid {
+        let mut parser = Vt100Parser::new(10, 2);
+        parser.process(bytes);
+        parser.terminal().to_grid()
+    }
+
+    #[test]
+    fn test_goldens_are_recorded_compared_and_updated() {
+        let dir = tempfile::tempdir().unwrap();
+        let golden = Golden::new(dir.path());
+        let menu = grid(b"Open\r\nQuit");
+
+        let GoldenOutcome::Recorded(files) = golden.check("menu/main", &menu).unwrap() else {
+            panic!("first run should record");
+        };
+        assert_eq!(files, vec![dir.path().join("menu/main.txt"), dir.path().join("menu/main.grid.json")]);
+        assert_eq!(fs::read_to_string(&files[0]).unwrap(), "Open\nQuit\n");
+        assert_eq!(golden.check("menu/main", &menu).unwrap(), GoldenOutcome::Matched);
+
+        // Same text, different style: only the grid catches it
+        let GoldenOutcome::Mismatch(diff) = golden.check("menu/main", &grid(b"\x1b[1mOpen\x1b[0m\r\nQuit")).unwrap() else {
+            panic!("restyled menu should not match");
+        };
+        assert!(diff.starts_with("grid: 0 added, 0 removed, 4 changed cells in 1 rows\n  row 0 col 0: 'O' -> 'O' ([Attrs])\n"));
+
+        let renamed = grid(b"Open\r\nExit");
+        assert!(matches!(golden.check("menu/main", &renamed).unwrap(), GoldenOutcome::Mismatch(diff) if diff.contains("-Quit\n+Exit")));
+        assert!(matches!(golden.clone().update(true).check("menu/main", &renamed).unwrap(), GoldenOutcome::Updated(_)));
+        assert_eq!(golden.check("menu/main", &renamed).unwrap(), GoldenOutcome::Matched);
+    }
+
+    #[test]
+    fn test_missing_goldens_fail_without_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let golden = Golden::new(dir.path()).record_new(false);
+        assert!(matches!(golden.check("new", &grid(b"hi")).unwrap(), GoldenOutcome::Missing(_)));
+        assert!(!dir.path().join("new.txt").exists());
+    }
+}
@@ -1,11 +1,14 @@
 use chrono::Utc;
 
-use crate::harness::types::{HarnessConfig, HarnessResult, InputAction};
-use crate::snapshot::{Snapshot, SnapshotConfig};
+use crate::harness::types::{HarnessConfig, HarnessResult, InputAction, StateResult};
+use crate::snapshot::utils::write_manifest;
+use crate::snapshot::{Snapshot, SnapshotConfig, SnapshotError};
+use crate::vlm::{judge_image, JudgeVerdict, VlmConfig};
 
 /// Runs the CLI harness using PTY-based VT100 rendering.
-/// Returns a list of (state_name, snapshot) pairs.
-pub fn run_harness(config: &HarnessConfig) -> HarnessResult<Vec<(String, Snapshot)>> {
+/// Returns the captured states in order; with [`HarnessConfig::vlm`] set,
+/// each state's `expected_description` is judged by the VLM.
+pub fn run_harness(config: &HarnessConfig) -> HarnessResult<Vec<StateResult>> {
     let run_id = format!("run_{}", i64::MAX - Utc::now().timestamp_millis());
     let run_dir = config.output_dir.join(&run_id);
 
@@ -14,6 +17,7 @@ pub fn run_harness(config: &HarnessConfig) -> HarnessResult<Vec<(String, Snapsho
         include_metadata: true,
         include_manifest: true,
         allow_mock_captures: false,
+        include_text: false,
     };
 
     std::fs::create_dir_all(&config.output_dir)?;
@@ -38,7 +42,7 @@ pub fn run_harness(config: &HarnessConfig) -> HarnessResult<Vec<(String, Snapsho
                 );
             }
 
-            let snapshot = capture_cli_snapshot_pty(
+            let mut snapshot = capture_cli_snapshot_pty(
                 &snapshot_config,
                 config.binary_path.to_str().unwrap(),
                 &config.args,
@@ -46,13 +50,39 @@ pub fn run_harness(config: &HarnessConfig) -> HarnessResult<Vec<(String, Snapsho
                 Some(serde_json::Value::Object(metadata)),
             )?;
 
-            results.push((state_config.name.clone(), snapshot));
+            let verdict = match (&state_config.expected_description, &config.vlm) {
+                (Some(expected), Some(vlm)) => Some(judge_snapshot(vlm, &snapshot, expected)?),
+                _ => None,
+            };
+            if let Some(verdict) = &verdict
+                && let Some(serde_json::Value::Object(map)) = snapshot.metadata.as_mut()
+            {
+                map.insert("verdict".to_string(), serde_json::to_value(verdict).map_err(SnapshotError::from)?);
+            }
+            // The manifest was written before the harness metadata was added
+            write_manifest(&snapshot, &snapshot_config)?;
+
+            results.push(StateResult {
+                name: state_config.name.clone(),
+                snapshot,
+                verdict,
+            });
         }
     }
 
     Ok(results)
 }
 
+/// Judge a snapshot's screenshot against the expected description
+fn judge_snapshot(vlm: &VlmConfig, snapshot: &Snapshot, expected: &str) -> HarnessResult<JudgeVerdict> {
+    let image_data = std::fs::read(&snapshot.image_path)?;
+    Ok(judge_image(vlm, &image_data, expected).unwrap_or_else(|e| JudgeVerdict {
+        pass: false,
+        reasoning: format!("VLM request failed: {}", e),
+        response: String::new(),
+    }))
+}
+
 /// Captures a screenshot for CLI testing using PTY-based VT100 rendering
 fn capture_cli_snapshot_pty(
     config: &SnapshotConfig,
@@ -65,18 +95,16 @@ fn capture_cli_snapshot_pty(
 
     let mut snapshot = capture_cli_screenshot_pty(config, binary_path, args, inputs)?;
 
-    if let Some(meta) = snapshot.metadata.as_mut() {
-        if let serde_json::Value::Object(map) = meta {
-            map.insert(
-                "source".to_string(),
-                serde_json::Value::String("cli".to_string()),
-            );
-            if let Some(extra) = extra_metadata {
-                if let serde_json::Value::Object(extra_map) = extra {
-                    for (k, v) in extra_map {
-                        map.insert(k, v);
-                    }
-                }
+    if let Some(meta) = snapshot.metadata.as_mut()
+        && let serde_json::Value::Object(map) = meta
+    {
+        map.insert(
+            "source".to_string(),
+            serde_json::Value::String("cli".to_string()),
+        );
+        if let Some(serde_json::Value::Object(extra_map)) = extra_metadata {
+            for (k, v) in extra_map {
+                map.insert(k, v);
             }
         }
     }
@@ -2,4 +2,4 @@ pub mod cli;
 pub mod types;
 
 pub use cli::run_harness;
-pub use types::{HarnessConfig, HarnessError, HarnessResult, InputAction, StateConfig};
+pub use types::{HarnessConfig, HarnessError, HarnessResult, InputAction, StateConfig, StateResult};
@@ -1,5 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::snapshot::{MouseButton, MouseInput, ScrollDirection, Sig, Snapshot, WaitCondition};
+use crate::vlm::{JudgeVerdict, VlmConfig};
 
 /// Configuration for a specific application state
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,10 +20,24 @@ pub struct StateConfig {
     /// Whether to capture a snapshot at this state
     pub capture_snapshot: bool,
 
-    /// Optional textual expectation for this state (for VLM comparison)
+    /// Optional textual expectation for this state, judged by [`HarnessConfig::vlm`]
     pub expected_description: Option<String>,
 }
 
+/// A captured state and the VLM's verdict on it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateResult {
+    /// Name of the state
+    pub name: String,
+
+    /// The captured snapshot; its metadata and manifest include the verdict
+    pub snapshot: Snapshot,
+
+    /// Whether the capture matches `expected_description`, when the state has
+    /// one and a VLM is configured; a failed request is a failing verdict
+    pub verdict: Option<JudgeVerdict>,
+}
+
 /// Configuration for the harness execution
 #[derive(Debug)]
 pub struct HarnessConfig {
@@ -34,6 +52,9 @@ pub struct HarnessConfig {
 
     /// Sequence of states to navigate through
     pub states: Vec<StateConfig>,
+
+    /// VLM that judges each state's `expected_description`; `None` skips the judging
+    pub vlm: Option<VlmConfig>,
 }
 
 impl Default for HarnessConfig {
@@ -43,6 +64,7 @@ impl Default for HarnessConfig {
             args: vec!["--headless".to_string()],
             output_dir: PathBuf::from("./harness_snapshots"),
             states: vec![],
+            vlm: None,
         }
     }
 }
@@ -55,6 +77,77 @@ pub enum InputAction {
 
     /// Send a special key (e.g., "enter", "up", "ctrl+c")
     SendKey(String),
+
+    /// Send exact bytes, e.g. a bracketed paste start `\x1b[200~`
+    Raw(Vec<u8>),
+
+    /// Resize the terminal to (cols, rows); the application receives SIGWINCH
+    Resize(u16, u16),
+
+    /// Click a cell (0-based); reported only if the application enabled mouse tracking
+    MouseClick { col: u16, row: u16, button: MouseButton },
+
+    /// Turn the mouse wheel one step over a cell (0-based)
+    MouseScroll { col: u16, row: u16, direction: ScrollDirection },
+
+    /// Pause for a number of milliseconds (animations, network), sending nothing
+    Sleep(u64),
+
+    /// Wait until the text is on screen; fails the capture if it does not appear in time
+    WaitForText(String),
+
+    /// Wait until the screen text is unchanged for `window_ms`, for apps that keep
+    /// redrawing after input; fails the capture after `max_wait_ms` (default 10s)
+    WaitStable { window_ms: u64, max_wait_ms: Option<u64> },
+
+    /// Deliver a signal to the application's process group (Unix only)
+    Signal(Sig),
+
+    /// Close the application's stdin; later states are still captured
+    Eof,
+
+    /// Pause for `delay_ms` milliseconds, then perform `action`
+    Delayed { delay_ms: u64, action: Box<InputAction> },
+}
+
+impl InputAction {
+    /// Perform this action after a pause of `delay_ms` milliseconds
+    pub fn with_delay(self, delay_ms: u64) -> Self {
+        InputAction::Delayed { delay_ms, action: Box::new(self) }
+    }
+
+    /// The pause before this action (including [`InputAction::Sleep`]) and the action to perform after it
+    pub fn delay_and_action(&self) -> (Duration, &InputAction) {
+        match self {
+            InputAction::Sleep(ms) => (Duration::from_millis(*ms), self),
+            InputAction::Delayed { delay_ms, action } => {
+                let (inner, action) = action.delay_and_action();
+                (Duration::from_millis(*delay_ms) + inner, action)
+            }
+            _ => (Duration::ZERO, self),
+        }
+    }
+
+    /// The screen condition wait actions wait for
+    pub fn wait_condition(&self) -> Option<WaitCondition> {
+        match self {
+            InputAction::WaitForText(text) => Some(WaitCondition::Text(text.clone())),
+            InputAction::WaitStable { window_ms, max_wait_ms } => Some(WaitCondition::Stable {
+                window: Duration::from_millis(*window_ms),
+                max_wait: max_wait_ms.map(Duration::from_millis),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The mouse input for mouse actions
+    pub fn mouse_input(&self) -> Option<MouseInput> {
+        match *self {
+            InputAction::MouseClick { col, row, button } => Some(MouseInput::Click { col, row, button }),
+            InputAction::MouseScroll { col, row, direction } => Some(MouseInput::Scroll { col, row, direction }),
+            _ => None,
+        }
+    }
 }
 
 /// Result type for harness operations
@@ -104,3 +197,26 @@ impl From<crate::snapshot::SnapshotError> for HarnessError {
         HarnessError::Snapshot(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delays_are_unwrapped() {
+        let action = InputAction::SendKey("enter".to_string()).with_delay(1000).with_delay(500);
+        let (delay, inner) = action.delay_and_action();
+        assert_eq!(delay, Duration::from_millis(1500));
+        assert!(matches!(inner, InputAction::SendKey(key) if key == "enter"));
+        assert_eq!(InputAction::Sleep(250).delay_and_action().0, Duration::from_millis(250));
+        assert_eq!(InputAction::Resize(80, 24).delay_and_action().0, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_wait_conditions() {
+        let stable = InputAction::WaitStable { window_ms: 500, max_wait_ms: None };
+        assert_eq!(stable.wait_condition(), crate::snapshot::parse_wait_input("wait-stable:500").and_then(Result::ok));
+        assert_eq!(InputAction::WaitForText("Ready".to_string()).wait_condition(), Some(WaitCondition::Text("Ready".to_string())));
+        assert_eq!(InputAction::SendKey("enter".to_string()).wait_condition(), None);
+    }
+}
@@ -6,6 +6,19 @@
 //! - Multi-state capture with input sequences
 //! - Vision model integration for UI analysis
 //! - Session management for organized temp files
+//! - Fuzzy alignment of sessions against a baseline
+//! - Markdown changelogs of visual changes between releases (`release-diff`)
+//! - Text QA checks (spelling, banned content, localization, layout) over captured screens
+//! - Pinned third-party TUI fixtures downloaded into a checksum-verified cache
+//! - JSON scenario files with a published JSON Schema, positioned diagnostics, and a JSON-RPC server for editor plugins
+//! - Suite runs with VLM checks, aggregated into an issue heatmap by screen region and tag
+//! - Offline coarse checks and state similarity with a local CLIP model (`local-vision` feature)
+//! - Embedding search for states across sessions (`search`)
+//! - Follow-up questions about a captured state, saved with the session (`chat`)
+//! - Golden text snapshots for `cargo test` (`expect_snapshot!`)
+//! - Text, regex, cell color and cursor expectations on captures (`expect`, `assert`)
+//! - Golden captures recorded on the first run and checked afterwards (`golden`, `run --golden-dir`)
+//! - Emulator building blocks (parser performer, palettes, glyphs) for custom renderers (`snapshot::emulator`)
 //! - Configurable via environment variables
 //!
 //! # Configuration
@@ -33,18 +46,40 @@
 //! std::fs::write("screenshot.png", &result.image_data).unwrap();
 //! ```
 
+pub mod changelog;
+pub mod chat;
+pub mod compare;
 pub mod config;
+pub mod editor;
+pub mod expect;
+pub mod explore;
+pub mod fixtures;
+pub mod golden;
 pub mod harness;
+#[cfg(feature = "local-vision")]
+pub mod local_vision;
+pub mod qa;
+pub mod report;
 pub mod runner;
+pub mod scenario;
+pub mod schema;
+pub mod search;
 pub mod session;
 pub mod snapshot;
+pub mod testing;
 pub mod vlm;
+pub mod vlm_cache;
+pub mod vlm_grounding;
+pub mod vlm_image;
+pub mod vlm_log;
+pub mod vlm_provider;
+pub mod vlm_rate;
 
 // Re-export runner types
 pub use runner::{RunResult, StateCapture};
 
 // Re-export harness types
-pub use harness::{HarnessConfig, HarnessError, HarnessResult, InputAction, StateConfig, run_harness};
+pub use harness::{HarnessConfig, HarnessError, HarnessResult, InputAction, StateConfig, StateResult, run_harness};
 
 // Re-export snapshot types and backends
 pub use snapshot::{
@@ -56,7 +91,10 @@ pub use snapshot::{
 pub use session::{Session, cleanup_old_sessions, list_sessions};
 
 // Re-export VLM client
-pub use vlm::{VlmConfig, VlmError, VlmProgress, VlmResult, analyze_image, analyze_image_with_progress, check_health, build_analysis_prompt};
+pub use vlm::{AnalysisMode, VlmConfig, VlmError, VlmProgress, VlmResult, analyze_image, analyze_image_structured, analyze_image_with_progress, check_health, check_model, complete_text, build_analysis_prompt, judge_image, JudgeVerdict, PromptContext, ServerInfo, VlmConversation};
+
+// Re-export session comparison
+pub use compare::{StateAlignment, align_session_dirs, align_states};
 
 // Re-export configuration
 pub use config::{Config, VlmSettings, SessionSettings, DefaultSettings};
@@ -0,0 +1,265 @@
+//! Offline vision checks with a local CLIP model (`local-vision` feature).
+//!
+//! When the VLM endpoint is set to `local` (or left empty), checks and
+//! descriptions are answered by a CLIP ViT-B/32 model run with candle instead
+//! of a remote VLM. The model is loaded from the directory named by
+//! `CLI_VISION_LOCAL_MODEL`, which must hold `model.safetensors` and
+//! `tokenizer.json` (as published for `openai/clip-vit-base-patch32`).
+//!
+//! CLIP cannot read text or describe a screen in detail. It can tell which of
+//! a few captions fits a screenshot best, which is enough for coarse checks
+//! such as "a dialog is visible", and its image embeddings measure how
+//! similar two states look.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::clip::{ClipConfig, ClipModel};
+use tokenizers::Tokenizer;
+
+use crate::config;
+use crate::search::cosine_similarity;
+use crate::vlm::{VlmIssue, VlmVerdict};
+
+/// Side length of the square CLIP input image
+const IMAGE_SIZE: u32 = 224;
+/// Per-channel mean used to normalize CLIP input
+const MEAN: [f32; 3] = [0.481_454_66, 0.457_827_5, 0.408_210_73];
+/// Per-channel standard deviation used to normalize CLIP input
+const STD: [f32; 3] = [0.268_629_54, 0.261_302_6, 0.275_777_1];
+/// Captions ranked by [`LocalVisionModel::describe`]
+const SCREEN_CAPTIONS: &[&str] = &[
+    "a dialog box",
+    "a menu",
+    "a list of items",
+    "a table",
+    "an error message",
+    "a text editor",
+    "a progress bar",
+    "a form with input fields",
+    "a help screen",
+    "an empty screen",
+];
+
+/// Errors from the local vision model
+#[derive(Debug)]
+pub enum LocalVisionError {
+    /// `CLI_VISION_LOCAL_MODEL` is not set
+    NotConfigured,
+    /// The model or tokenizer could not be loaded or run
+    Model(String),
+    /// The screenshot could not be decoded
+    Image(String),
+}
+
+impl std::fmt::Display for LocalVisionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LocalVisionError::NotConfigured => {
+                write!(f, "no local model configured (set {})", config::ENV_LOCAL_MODEL)
+            }
+            LocalVisionError::Model(msg) => write!(f, "Local model error: {}", msg),
+            LocalVisionError::Image(msg) => write!(f, "Invalid image: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for LocalVisionError {}
+
+impl From<candle_core::Error> for LocalVisionError {
+    fn from(e: candle_core::Error) -> Self {
+        LocalVisionError::Model(e.to_string())
+    }
+}
+
+/// A CLIP model with its tokenizer, run on the CPU
+pub struct LocalVisionModel {
+    model: ClipModel,
+    tokenizer: Tokenizer,
+    device: Device,
+    pad_id: u32,
+    max_len: usize,
+}
+
+impl std::fmt::Debug for LocalVisionModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("LocalVisionModel")
+    }
+}
+
+impl LocalVisionModel {
+    /// Load `model.safetensors` and `tokenizer.json` from a directory
+    pub fn load(dir: &Path) -> Result<Self, LocalVisionError> {
+        let device = Device::Cpu;
+        let config = ClipConfig::vit_base_patch32();
+        let weights = dir.join("model.safetensors");
+        // SAFETY: the weights file is memory-mapped read-only and must not be modified while loaded
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[&weights], DType::F32, &device)? };
+        let model = ClipModel::new(vb, &config)?;
+        let tokenizer = Tokenizer::from_file(dir.join("tokenizer.json"))
+            .map_err(|e| LocalVisionError::Model(format!("{}: {}", dir.join("tokenizer.json").display(), e)))?;
+        let pad_id = tokenizer
+            .token_to_id("<|endoftext|>")
+            .ok_or_else(|| LocalVisionError::Model("tokenizer has no <|endoftext|> token".to_string()))?;
+        Ok(Self {
+            model,
+            tokenizer,
+            device,
+            pad_id,
+            max_len: config.text_config.max_position_embeddings,
+        })
+    }
+
+    /// Normalized embedding of a PNG screenshot
+    pub fn embed_image(&self, png: &[u8]) -> Result<Vec<f32>, LocalVisionError> {
+        let pixels = self.pixel_values(png)?;
+        let features = self.model.get_image_features(&pixels)?;
+        Ok(l2_normalize(features.squeeze(0)?.to_vec1()?))
+    }
+
+    /// Normalized embeddings of captions
+    pub fn embed_texts(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LocalVisionError> {
+        let mut ids = Vec::with_capacity(texts.len() * self.max_len);
+        for text in texts {
+            let encoding = self
+                .tokenizer
+                .encode(text.as_str(), true)
+                .map_err(|e| LocalVisionError::Model(e.to_string()))?;
+            let mut tokens = encoding.get_ids().to_vec();
+            tokens.resize(self.max_len, self.pad_id);
+            ids.extend(tokens);
+        }
+        let input_ids = Tensor::from_vec(ids, (texts.len(), self.max_len), &self.device)?;
+        let features = self.model.get_text_features(&input_ids)?;
+        let rows: Vec<Vec<f32>> = features.to_vec2()?;
+        Ok(rows.into_iter().map(l2_normalize).collect())
+    }
+
+    /// Probability of each caption describing the screenshot, most likely first
+    pub fn rank(&self, png: &[u8], captions: &[String]) -> Result<Vec<(String, f32)>, LocalVisionError> {
+        let image = self.embed_image(png)?;
+        let texts = self.embed_texts(captions)?;
+        // CLIP's learned logit scale, exp(4.6052)
+        let logits: Vec<f32> = texts.iter().map(|text| 100.0 * cosine_similarity(&image, text)).collect();
+        let mut ranked: Vec<(String, f32)> = captions.iter().cloned().zip(softmax(&logits)).collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(ranked)
+    }
+
+    /// Coarse verdict on a check: does the statement fit better than its negation?
+    pub fn check(&self, png: &[u8], statement: &str) -> Result<VlmVerdict, LocalVisionError> {
+        let captions = check_captions(statement);
+        let ranked = self.rank(png, &captions)?;
+        let probability = ranked.iter().find(|(caption, _)| *caption == captions[0]).map_or(0.0, |(_, p)| *p);
+        let pass = probability >= 0.5;
+        let response = format!("local CLIP model: {:.0}% that {}", probability * 100.0, statement);
+        Ok(VlmVerdict {
+            pass,
+            issues: if pass {
+                Vec::new()
+            } else {
+                vec![VlmIssue {
+                    category: String::new(),
+                    area: String::new(),
+                    description: response.clone(),
+                }]
+            },
+            response,
+            samples: 1,
+            agreement: 1.0,
+            retries: 0,
+        })
+    }
+
+    /// Short description listing the best-matching kinds of screen
+    pub fn describe(&self, png: &[u8]) -> Result<String, LocalVisionError> {
+        let captions: Vec<String> = SCREEN_CAPTIONS.iter().map(|c| format!("a terminal screenshot showing {}", c)).collect();
+        let ranked = self.rank(png, &captions)?;
+        let best: Vec<String> = ranked
+            .iter()
+            .take(3)
+            .map(|(caption, p)| format!("{} ({:.0}%)", caption.trim_start_matches("a terminal screenshot showing "), p * 100.0))
+            .collect();
+        Ok(format!("Closest matches (local CLIP model): {}", best.join(", ")))
+    }
+
+    /// Cosine similarity of two screenshots' embeddings (1.0 for identical looks)
+    pub fn state_similarity(&self, a: &[u8], b: &[u8]) -> Result<f32, LocalVisionError> {
+        Ok(cosine_similarity(&self.embed_image(a)?, &self.embed_image(b)?))
+    }
+
+    /// Resize, normalize and lay out a screenshot as a 1x3x224x224 tensor
+    fn pixel_values(&self, png: &[u8]) -> Result<Tensor, LocalVisionError> {
+        let image = image::load_from_memory(png).map_err(|e| LocalVisionError::Image(e.to_string()))?;
+        let image = image
+            .resize_exact(IMAGE_SIZE, IMAGE_SIZE, image::imageops::FilterType::Triangle)
+            .to_rgb8();
+        let plane = (IMAGE_SIZE * IMAGE_SIZE) as usize;
+        let mut data = vec![0f32; 3 * plane];
+        for (idx, pixel) in image.pixels().enumerate() {
+            for channel in 0..3 {
+                data[channel * plane + idx] = (f32::from(pixel[channel]) / 255.0 - MEAN[channel]) / STD[channel];
+            }
+        }
+        let size = IMAGE_SIZE as usize;
+        Ok(Tensor::from_vec(data, (1, 3, size, size), &self.device)?)
+    }
+}
+
+/// Captions compared for a check: the statement first, then its negation
+fn check_captions(statement: &str) -> Vec<String> {
+    let statement = statement.trim().trim_end_matches('.');
+    vec![
+        format!("a terminal screenshot where {}", statement),
+        format!("a terminal screenshot where it is not true that {}", statement),
+    ]
+}
+
+fn l2_normalize(v: Vec<f32>) -> Vec<f32> {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 { v } else { v.into_iter().map(|x| x / norm).collect() }
+}
+
+fn softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exps: Vec<f32> = logits.iter().map(|l| (l - max).exp()).collect();
+    let sum: f32 = exps.iter().sum();
+    exps.into_iter().map(|e| e / sum).collect()
+}
+
+/// Directory of the configured local model, if any
+pub fn model_dir() -> Option<PathBuf> {
+    config::get().vlm.local_model.as_ref().map(PathBuf::from)
+}
+
+/// The configured local model, loaded on first use and shared afterwards
+pub fn shared_model() -> Result<&'static LocalVisionModel, LocalVisionError> {
+    static MODEL: OnceLock<Result<LocalVisionModel, String>> = OnceLock::new();
+    let dir = model_dir().ok_or(LocalVisionError::NotConfigured)?;
+    MODEL
+        .get_or_init(|| LocalVisionModel::load(&dir).map_err(|e| e.to_string()))
+        .as_ref()
+        .map_err(|e| LocalVisionError::Model(e.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_captions_pair_statement_with_negation() {
+        let captions = check_captions("a dialog is visible.");
+        assert_eq!(captions[0], "a terminal screenshot where a dialog is visible");
+        assert!(captions[1].contains("not true that a dialog is visible"));
+    }
+
+    #[test]
+    fn test_softmax_and_normalization() {
+        assert_eq!(l2_normalize(vec![3.0, 4.0]), vec![0.6, 0.8]);
+        assert_eq!(l2_normalize(vec![0.0, 0.0]), vec![0.0, 0.0]);
+        let probs = softmax(&[1.0, 1.0, 1.0]);
+        assert!(probs.iter().all(|p| (p - 1.0 / 3.0).abs() < 1e-6));
+    }
+}
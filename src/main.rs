@@ -1,13 +1,39 @@
 use clap::{Parser, Subcommand};
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
+use cli_vision::changelog::{diff_releases, summarize_change, write_changelog};
+use cli_vision::chat::ChatSession;
+use cli_vision::compare::DEFAULT_MATCH_THRESHOLD;
+use cli_vision::editor::EditorServer;
+use cli_vision::expect::{check_all, Expectation, Screen};
+use cli_vision::explore::{run_explore, ExploreConfig};
+use cli_vision::fixtures::{current_platform, FixtureCache, FixtureManifest};
+use cli_vision::golden::{update_from_env, Golden, GoldenOutcome};
+use cli_vision::qa::{check_color_vision, check_layout, locale_coverage, BannedContentScanner, ColorDeficiency, ColorVisionIssueKind, SpellChecker, SYSTEM_DICTIONARY};
+use cli_vision::report::run_suite;
 use cli_vision::runner::{RunResult, StateCapture};
+use cli_vision::scenario::{Scenario, Suite};
+use cli_vision::schema::{scenario_json_schema, suite_json_schema};
+use cli_vision::search::{search_sessions, EmbeddingSource};
 use cli_vision::session::Session;
+use cli_vision::snapshot::export::{check_video_support, encode_video, html_page, to_gif, StateFormat, VideoFormat, DEFAULT_VIDEO_FPS};
+use cli_vision::snapshot::fuzz::{run_fuzz, FuzzConfig, FuzzEventKind, DEFAULT_CHANGE_THRESHOLD};
+use cli_vision::snapshot::glyphs::{codepoint_label, parse_fallback_chain};
+use cli_vision::snapshot::replay::{replay_cast, replay_plan, Cast, CastEventKind, CastRecorder, ReplayAction, ReplayCapture, ReplayConfig, ReplayStep};
+use cli_vision::snapshot::record::{record, RecordConfig, RecordedEvent, DEFAULT_CAPTURE_KEY, DEFAULT_STOP_KEY};
 use cli_vision::snapshot::{
-    run_with_inputs_sized, CaptureBackend, MockFramebuffer, PtyBackend, PtyBackendConfig, TerminalSize,
+    annotated_diff, diff_directories, diff_images, run_with_options, validate_hyperlink, CaptureBackend, Crop, expand_inputs, expanded_inputs, load_macros, ExpandedInput, FrameFormat, GlyphFallback, HexFont, KeyboardLayout, KeyProtocol, MockFramebuffer, PtyBackend, PtyBackendConfig, PtyPool, ResourceLimits, RunOptions, SoakConfig, SoakFailure, StateDiffStatus, TerminalSize, Theme, run_soak, write_png_frame,
 };
-use cli_vision::vlm::{VlmConfig, analyze_image, build_analysis_prompt, check_health};
+use cli_vision::testing::{accept_snapshot, image_diff, line_diff, pending_snapshots, reject_snapshot};
+use cli_vision::vlm::{AnalysisMode, ChatRole, PriceTable, PromptContext, VlmConfig, VlmConversation, VlmProgress, VlmUsage, build_analysis_prompt, load_step_prompts, parse_step_prompts, build_comparison_prompt, check_health, check_model, compare_images_with_progress, embeddings_endpoint, is_local_endpoint};
+use cli_vision::vlm_cache::VlmCache;
+use cli_vision::vlm_grounding::{self, analyze_image_grounded};
+use cli_vision::vlm_image::{ImageEncoding, ImagePrep};
+use cli_vision::vlm_log::VlmTranscript;
 
 /// CLI Vision - Terminal UI testing with vision model analysis
 #[derive(Parser, Debug)]
@@ -17,9 +43,16 @@ use cli_vision::vlm::{VlmConfig, analyze_image, build_analysis_prompt, check_hea
     after_help = "ENVIRONMENT VARIABLES:\n\
         CLI_VISION_VLM_ENDPOINT    VLM API endpoint URL\n\
         CLI_VISION_VLM_MODEL       VLM model name\n\
+        CLI_VISION_VLM_PROVIDER    VLM API format (openai, ollama, anthropic, gemini)\n\
+        CLI_VISION_VLM_API_KEY     API key for hosted VLM endpoints\n\
+        CLI_VISION_VLM_HEADERS     Extra VLM headers (Name: value; ...)\n\
         CLI_VISION_SESSION_DIR     Base directory for sessions\n\
         CLI_VISION_DEFAULT_DELAY   Default delay between inputs (ms)\n\
-        CLI_VISION_DEFAULT_SIZE    Default terminal size"
+        CLI_VISION_DEFAULT_SIZE    Default terminal size\n\
+        CLI_VISION_THEME           Renderer color theme\n\
+        CLI_VISION_FIXTURE_DIR     Fixture cache directory\n\
+        CLI_VISION_VLM_CACHE_DIR   VLM answer cache directory\n\
+        CLI_VISION_VLM_CACHE_TTL   Seconds a cached VLM answer stays valid"
 )]
 struct Args {
     #[command(subcommand)]
@@ -27,376 +60,2885 @@ struct Args {
 }
 
 #[derive(Subcommand, Debug)]
+#[allow(clippy::large_enum_variant)] // parsed once at startup
 enum Commands {
     /// Capture a CLI application screenshot using PTY emulation
-    Cli {
-        /// Path to the binary to capture
-        #[arg(short, long)]
-        binary: PathBuf,
-
-        /// Output directory for screenshots (default: auto-generated in session dir)
-        #[arg(short, long)]
-        output: Option<PathBuf>,
-
-        /// Keep screenshots after completion (default: cleanup unless --output is specified)
-        #[arg(long, short = 'k')]
-        keep: bool,
-
-        /// Terminal size: compact (80x24), standard (120x40), large (160x50), xl (200x60), or WxH
-        #[arg(long, short = 's', env = "CLI_VISION_DEFAULT_SIZE", default_value = "standard")]
-        size: String,
-
-        /// Arguments to pass to the binary
-        #[arg(last = true)]
-        args: Vec<String>,
+    Cli(CliArgs),
+
+    /// Run a CLI application with inputs, capturing state after each
+    Run(RunArgs),
+
+    /// Drive an application from this terminal and record the keystrokes as a replayable scenario
+    Record(RecordArgs),
+
+    /// Loop inputs for a fixed wall time, failing on crash, memory growth or layout drift
+    Soak(SoakArgs),
+
+    /// Send a seeded random key stream, keeping screenshots of dramatic changes and crashes
+    Fuzz(FuzzArgs),
+
+    /// Let the VLM explore an application, choosing each key from a list, to find crashes and dead ends
+    Explore(ExploreArgs),
+
+    /// Create a mock framebuffer screenshot for testing
+    Mock(MockArgs),
+
+    /// Download pinned fixture binaries into the local cache
+    Fixtures {
+        #[command(subcommand)]
+        action: FixturesAction,
+    },
+
+    /// Manage golden text snapshots written by `expect_snapshot!`
+    Snapshots {
+        #[command(subcommand)]
+        action: SnapshotsAction,
+    },
+
+    /// Write a Markdown changelog of the screens that changed between two releases' captures
+    ReleaseDiff(ReleaseDiffArgs),
+
+    /// Compare two screenshots, or every state of two capture directories, pixel by pixel and cell by cell
+    Diff(DiffArgs),
+
+    /// Check a saved capture against expectations, e.g. -e "contains Saved" -e "cursor-at 5,0"
+    Assert(AssertArgs),
+
+    /// Ask the VLM follow-up questions about a captured state, saving the conversation in the session
+    Chat(ChatArgs),
+
+    /// Find the captured states most similar to a description across all sessions
+    Search(SearchArgs),
+
+    /// Run every scenario in a suite file and write a report with an issue heatmap
+    Suite(SuiteArgs),
+
+    /// Print the JSON Schema for scenario (or suite) files
+    Schema(SchemaArgs),
+
+    /// Serve a protocol over stdio for other tools to drive cli-vision
+    Serve(ServeArgs),
+}
+
+/// Arguments of the `cli` command
+#[derive(clap::Args, Debug)]
+struct CliArgs {
+    /// Path to the binary to capture
+    #[arg(short, long)]
+    binary: PathBuf,
+
+    /// Output directory for screenshots (default: auto-generated in session dir)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Keep screenshots after completion (default: cleanup unless --output is specified)
+    #[arg(long, short = 'k')]
+    keep: bool,
+
+    /// Terminal size: compact (80x24), standard (120x40), large (160x50), xl (200x60), or WxH
+    #[arg(long, short = 's', env = "CLI_VISION_DEFAULT_SIZE", default_value = "standard")]
+    size: String,
+
+    /// Draw the terminal cursor in the screenshot
+    #[arg(long)]
+    show_cursor: bool,
+
+    /// Capture only part of the screen: top:N, bottom:N, center[:N], rows:A-B, or a COLSxROWS+X+Y cell rectangle
+    #[arg(long, value_name = "PRESET", value_parser = Crop::parse)]
+    crop: Option<Crop>,
+
+    /// Glyph fallbacks for characters without a built-in glyph, in order: font, hex, tofu, or none
+    #[arg(long, value_name = "CHAIN")]
+    glyph_fallback: Option<String>,
+
+    /// GNU Unifont .hex font used by the `font` glyph fallback (default chain: font)
+    #[arg(long, value_name = "FILE")]
+    glyph_font: Option<PathBuf>,
+
+    /// Color theme: default, vscode-dark, solarized, light, or a theme JSON file
+    #[arg(long, env = "CLI_VISION_THEME", default_value = "default")]
+    theme: String,
+
+    /// Scrub the environment to TERM, LANG and --env vars; requires an absolute binary path
+    #[arg(long)]
+    hermetic: bool,
+
+    /// Environment variable for the application as KEY=VALUE (repeatable)
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    env_vars: Vec<(String, String)>,
+
+    /// Kill the application after this much CPU time (seconds, Linux only)
+    #[arg(long, value_name = "SECS")]
+    cpu_limit: Option<u64>,
+
+    /// Limit the application's address space (MiB, Linux only)
+    #[arg(long, value_name = "MB")]
+    memory_limit: Option<u64>,
+
+    /// Limit the application's open file descriptors (Linux only)
+    #[arg(long, value_name = "N")]
+    fd_limit: Option<u64>,
+
+    /// Also write the screen text next to the screenshot (.txt)
+    #[arg(long)]
+    save_text: bool,
+
+    /// Arguments to pass to the binary
+    #[arg(last = true)]
+    args: Vec<String>,
+}
+
+/// Arguments of the `run` command
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    /// Path to the binary to execute
+    #[arg(short, long, required_unless_present = "scenario")]
+    binary: Option<PathBuf>,
+
+    /// Arguments to pass to the binary (comma-separated, e.g., "--headless,--config,foo.yaml")
+    #[arg(short, long, value_delimiter = ',', allow_hyphen_values = true)]
+    args: Vec<String>,
+
+    /// Comma-separated list of inputs (e.g., "down,down,enter,escape", "down*40", "@login")
+    #[arg(short, long, required_unless_present_any = ["scenario", "replay"])]
+    inputs: Option<String>,
+
+    /// Scenario file supplying the binary, arguments, inputs, size, delay, environment, layout and crops (e.g., from `record`)
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["binary", "args", "inputs", "macros", "replay"])]
+    scenario: Option<PathBuf>,
+
+    /// Replay the input recorded in an asciinema .cast file (recorded with `asciinema rec --stdin`) at the cast's size
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["inputs", "macros"])]
+    replay: Option<PathBuf>,
+
+    /// Playback speed for --replay (e.g., 2 replays twice as fast)
+    #[arg(long, value_name = "FACTOR", default_value = "1", requires = "replay")]
+    replay_speed: f64,
+
+    /// Cut pauses in the --replay recording to at most this long (e.g., "2s")
+    #[arg(long, value_name = "TIME", value_parser = parse_duration, requires = "replay")]
+    replay_idle_limit: Option<Duration>,
+
+    /// When --replay captures the screen: markers, inputs, or an interval like 500ms
+    #[arg(long, value_name = "WHEN", default_value = "markers", value_parser = ReplayCapture::parse, requires = "replay")]
+    capture_at: ReplayCapture,
+
+    /// JSON file of named input sequences, used in inputs as @NAME
+    #[arg(long, value_name = "FILE")]
+    macros: Option<PathBuf>,
+
+    /// Keyboard layout for `keycode:` inputs: en-US, en-GB, de-DE, fr-FR, es-ES
+    #[arg(long, value_name = "LAYOUT", default_value = "en-US")]
+    keyboard_layout: KeyboardLayout,
+
+    /// Key encoding: auto (kitty CSI-u once the app enables it), legacy, or kitty
+    #[arg(long, value_name = "PROTOCOL", default_value = "auto")]
+    key_protocol: KeyProtocol,
+
+    /// Delay in milliseconds between inputs
+    #[arg(short, long, env = "CLI_VISION_DEFAULT_DELAY", default_value = "100")]
+    delay: u64,
+
+    /// Delay in milliseconds between characters of `type:` inputs
+    #[arg(long, value_name = "MS", default_value = "30")]
+    type_delay: u64,
+
+    /// Milliseconds a wait input (wait-for:, wait-regex:, wait-gone:) waits before the run fails
+    #[arg(long, value_name = "MS", default_value = "10000")]
+    wait_timeout: u64,
+
+    /// Output directory for screenshots (default: auto-generated in session dir)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Keep screenshots after completion (default: cleanup unless --output is specified)
+    #[arg(long, short = 'k')]
+    keep: bool,
+
+    /// Analyze screenshots with VLM and include descriptions
+    #[arg(long)]
+    analyze: bool,
+
+    /// VLM endpoint URL
+    #[arg(long, env = "CLI_VISION_VLM_ENDPOINT", default_value = "http://127.0.0.1:8080/v1/chat/completions")]
+    vlm_endpoint: String,
+
+    /// VLM model name
+    #[arg(long, env = "CLI_VISION_VLM_MODEL", default_value = "qwen3")]
+    vlm_model: String,
+
+    /// Custom analysis prompt (use {input} and {step} as placeholders)
+    #[arg(long)]
+    prompt: Option<String>,
+
+    /// Read the custom analysis prompt from a file
+    #[arg(long, value_name = "PATH", conflicts_with = "prompt")]
+    prompt_file: Option<PathBuf>,
+
+    /// Per-step prompts as JSON: {"1": "check if button is blue", "3": "verify dialog opened"}
+    #[arg(long)]
+    step_prompts: Option<String>,
+
+    /// Read per-step prompts from a JSON file (an object keyed by state number; YAML is not accepted)
+    #[arg(long, value_name = "PATH", conflicts_with = "step_prompts")]
+    step_prompts_file: Option<PathBuf>,
+
+    /// Template for the context preamble before VLM prompts ({context}, {step}, {input}, {inputs}, {cols}, {rows}, {binary}, {scenario})
+    #[arg(long, env = "CLI_VISION_VLM_PREAMBLE", value_name = "TEMPLATE")]
+    prompt_preamble: Option<String>,
+
+    /// Send VLM prompts without the run context preamble
+    #[arg(long, conflicts_with = "prompt_preamble")]
+    no_prompt_preamble: bool,
+
+    /// Append the screen's text from the terminal buffer (cropped like the screenshot) to VLM prompts; false makes hybrid analysis send the image alone
+    #[arg(long, value_name = "BOOL", default_value_t = true, action = clap::ArgAction::Set)]
+    prompt_include_text: bool,
+
+    /// What VLM requests show the model: image (the screenshot), text (the screen's text, for text-only LLMs) or hybrid (both)
+    #[arg(long, value_name = "MODE", default_value = "hybrid")]
+    analysis_mode: AnalysisMode,
+
+    /// Scale screenshots down so their longer side is at most PX pixels before sending them to the VLM (saved screenshots keep their size)
+    #[arg(long, env = "CLI_VISION_VLM_IMAGE_MAX_SIZE", value_name = "PX", value_parser = clap::value_parser!(u32).range(1..))]
+    vlm_image_max_size: Option<u32>,
+
+    /// Format screenshots are sent to the VLM in: png, jpeg or webp (lossless)
+    #[arg(long, env = "CLI_VISION_VLM_IMAGE_FORMAT", value_name = "FORMAT", default_value = "png")]
+    vlm_image_format: ImageEncoding,
+
+    /// JPEG quality of screenshots sent to the VLM
+    #[arg(long, env = "CLI_VISION_VLM_IMAGE_QUALITY", value_name = "1-100", default_value = "85", value_parser = clap::value_parser!(u8).range(1..=100))]
+    vlm_image_quality: u8,
+
+    /// Keep the last N steps' screenshots and descriptions in each --analyze request, so the VLM sees transitions
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    vlm_context: usize,
+
+    /// Always ask the VLM instead of reusing cached answers for identical screenshots and prompts
+    #[arg(long)]
+    no_vlm_cache: bool,
+
+    /// Log every VLM request (images as hashes), its timing, token usage and answer to vlm_log.jsonl in the session directory (kept)
+    #[arg(long)]
+    vlm_log: bool,
+
+    /// Output results as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// Terminal size: compact (80x24), standard (120x40), large (160x50), xl (200x60), or WxH (e.g., 100x30)
+    #[arg(long, short = 's', env = "CLI_VISION_DEFAULT_SIZE", default_value = "standard")]
+    size: String,
+
+    /// Run with all preset sizes and compare results (useful for finding resize bugs)
+    #[arg(long)]
+    multi_size: bool,
+
+    /// Draw the terminal cursor in each screenshot
+    #[arg(long)]
+    show_cursor: bool,
+
+    /// Crop every screenshot to part of the screen: top:N, bottom:N, center[:N], rows:A-B, or a COLSxROWS+X+Y cell rectangle
+    #[arg(long, value_name = "PRESET", value_parser = Crop::parse)]
+    crop: Option<Crop>,
+
+    /// Glyph fallbacks for characters without a built-in glyph, in order: font, hex, tofu, or none
+    #[arg(long, value_name = "CHAIN")]
+    glyph_fallback: Option<String>,
+
+    /// GNU Unifont .hex font used by the `font` glyph fallback (default chain: font)
+    #[arg(long, value_name = "FILE")]
+    glyph_font: Option<PathBuf>,
+
+    /// Color theme: default, vscode-dark, solarized, light, or a theme JSON file
+    #[arg(long, env = "CLI_VISION_THEME", default_value = "default")]
+    theme: String,
+
+    /// Scrub the environment to TERM, LANG and --env vars; requires an absolute binary path
+    #[arg(long)]
+    hermetic: bool,
+
+    /// Environment variable for the application as KEY=VALUE (repeatable)
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    env_vars: Vec<(String, String)>,
+
+    /// Kill the application after this much CPU time (seconds, Linux only)
+    #[arg(long, value_name = "SECS")]
+    cpu_limit: Option<u64>,
+
+    /// Limit the application's address space (MiB, Linux only)
+    #[arg(long, value_name = "MB")]
+    memory_limit: Option<u64>,
+
+    /// Limit the application's open file descriptors (Linux only)
+    #[arg(long, value_name = "N")]
+    fd_limit: Option<u64>,
+
+    /// Also save blink phase A/B screenshots for states with blinking content
+    #[arg(long)]
+    blink_frames: bool,
+
+    /// Validate OSC 8 hyperlink URIs (HTTP HEAD checks need the `link-check` feature)
+    #[arg(long)]
+    check_links: bool,
+
+    /// Spell-check the visible text of each state
+    #[arg(long)]
+    spellcheck: bool,
+
+    /// Dictionary for --spellcheck (one word per line)
+    #[arg(long, default_value = SYSTEM_DICTIONARY)]
+    dictionary: PathBuf,
+
+    /// Extra wordlist of accepted words or `wrong -> preferred` terms (repeatable)
+    #[arg(long)]
+    wordlist: Vec<PathBuf>,
+
+    /// Fail the run if built-in banned content (TODO, panics, debug output, emails) is visible
+    #[arg(long)]
+    ban_defaults: bool,
+
+    /// Fail the run if text matching this regex is visible (repeatable)
+    #[arg(long)]
+    ban: Vec<String>,
+
+    /// File of banned-content rules, one `name: regex` per line
+    #[arg(long)]
+    ban_file: Option<PathBuf>,
+
+    /// Run under each locale (comma-separated, first is the base), e.g. "en_US.UTF-8,de_DE.UTF-8"
+    #[arg(long)]
+    locales: Option<String>,
+
+    /// Flag truncated words, ellipsis overuse and overlapping borders (combine with --multi-size)
+    #[arg(long)]
+    layout_check: bool,
+
+    /// Simulate color blindness (comma-separated: protanopia, deuteranopia, tritanopia, or all), saving filtered screenshots and reporting contrast lost
+    #[arg(long, value_name = "LIST")]
+    color_vision: Option<String>,
+
+    /// Pre-open N PTYs and reuse them across --multi-size/--locales runs
+    #[arg(long, value_name = "N")]
+    pty_pool: Option<usize>,
+
+    /// Stream every captured frame to stdout as raw ppm or farbfeld (e.g. `| ffmpeg -f image2pipe -c:v ppm -i - out.mp4`)
+    #[arg(long, value_name = "FORMAT")]
+    emit_frames: Option<FrameFormat>,
+
+    /// Also write each state's screen text next to its screenshot (state_N_*.txt)
+    #[arg(long)]
+    save_text: bool,
+
+    /// Also write the raw bytes received before each state next to its screenshot (state_N_*.ansi)
+    #[arg(long)]
+    save_ansi: bool,
+
+    /// Format of saved states: png, html (also a standalone styled page per state) or grid-json (also the cell grid as JSON)
+    #[arg(long, value_name = "FORMAT", default_value = "png")]
+    format: StateFormat,
+
+    /// Write the states as an animated GIF, each shown for as long as the run waited before the next input
+    #[arg(long, value_name = "FILE")]
+    export_gif: Option<PathBuf>,
+
+    /// Write the states as a video: .webp, .apng or .mp4 (needs the `video` feature and ffmpeg)
+    #[arg(long, value_name = "FILE")]
+    export_video: Option<PathBuf>,
+
+    /// Frame rate of --export-video
+    #[arg(long, value_name = "FPS", default_value_t = DEFAULT_VIDEO_FPS)]
+    video_fps: u32,
+
+    /// Check each state against goldens in this directory, recording the ones that do not exist yet
+    #[arg(long, value_name = "DIR")]
+    golden_dir: Option<PathBuf>,
+
+    /// Also keep and compare each state's screenshot as a golden PNG
+    #[arg(long, requires = "golden_dir")]
+    golden_images: bool,
+
+    /// Accept states that differ from their goldens as the new goldens (or set CLI_VISION_UPDATE=1)
+    #[arg(long, requires = "golden_dir")]
+    update: bool,
+
+    /// Ask the VLM what changed since the same states in this earlier run's output directory, and whether it is a regression
+    #[arg(long, value_name = "DIR")]
+    compare_baseline: Option<PathBuf>,
+
+    /// Have the VLM locate each finding with a box, and save a copy of the screenshot with the boxes drawn (state_N_*.findings.png); states are analyzed one at a time, without earlier answers as context
+    #[arg(long, requires = "analyze")]
+    annotate_findings: bool,
+
+    /// JSON price table for VLM models in dollars per million tokens: {"gpt-4o": {"prompt": 2.5, "completion": 10.0}}
+    #[arg(long, env = "CLI_VISION_VLM_PRICES", value_name = "FILE")]
+    vlm_prices: Option<PathBuf>,
+}
+
+/// Arguments of the `record` command
+#[derive(clap::Args, Debug)]
+struct RecordArgs {
+    /// Path to the binary to execute
+    #[arg(short, long)]
+    binary: PathBuf,
+
+    /// Arguments to pass to the binary (comma-separated)
+    #[arg(short, long, value_delimiter = ',', allow_hyphen_values = true)]
+    args: Vec<String>,
+
+    /// Scenario file to write
+    #[arg(short, long, default_value = "recording.json")]
+    output: PathBuf,
+
+    /// Terminal size: compact, standard, large, xl, or WxH (default: the size of this terminal)
+    #[arg(long, short = 's')]
+    size: Option<String>,
+
+    /// Key that marks a capture point instead of being sent
+    #[arg(long, value_name = "KEY", default_value = DEFAULT_CAPTURE_KEY)]
+    capture_key: String,
+
+    /// Key that ends the recording instead of being sent
+    #[arg(long, value_name = "KEY", default_value = DEFAULT_STOP_KEY)]
+    stop_key: String,
+
+    /// Delay in milliseconds between inputs on replay
+    #[arg(short, long, default_value = "100")]
+    delay: u64,
+
+    /// Shortest pause (beyond the delay) kept as a sleep: step, in milliseconds
+    #[arg(long, value_name = "MS", default_value = "500")]
+    min_pause: u64,
+
+    /// Environment variable for the application as KEY=VALUE (repeatable)
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    env_vars: Vec<(String, String)>,
+}
+
+/// Arguments of the `soak` command
+#[derive(clap::Args, Debug)]
+struct SoakArgs {
+    /// Path to the binary to execute
+    #[arg(short, long)]
+    binary: PathBuf,
+
+    /// Arguments to pass to the binary (comma-separated)
+    #[arg(short, long, value_delimiter = ',', allow_hyphen_values = true)]
+    args: Vec<String>,
+
+    /// Comma-separated inputs repeated until the duration is reached (e.g., "tab,enter,esc")
+    #[arg(long)]
+    inputs_loop: String,
+
+    /// JSON file of named input sequences, used in inputs as @NAME
+    #[arg(long, value_name = "FILE")]
+    macros: Option<PathBuf>,
+
+    /// Keyboard layout for `keycode:` inputs: en-US, en-GB, de-DE, fr-FR, es-ES
+    #[arg(long, value_name = "LAYOUT", default_value = "en-US")]
+    keyboard_layout: KeyboardLayout,
+
+    /// Key encoding: auto (kitty CSI-u once the app enables it), legacy, or kitty
+    #[arg(long, value_name = "PROTOCOL", default_value = "auto")]
+    key_protocol: KeyProtocol,
+
+    /// Wall time to run: seconds, or with an s/m/h suffix (e.g., "30m")
+    #[arg(long, value_parser = parse_duration, default_value = "5m")]
+    duration: Duration,
+
+    /// Minimum time between screenshot and memory samples (e.g., "1m")
+    #[arg(long, value_parser = parse_duration, default_value = "1m")]
+    sample_interval: Duration,
+
+    /// Delay in milliseconds between inputs
+    #[arg(short, long, env = "CLI_VISION_DEFAULT_DELAY", default_value = "100")]
+    delay: u64,
+
+    /// Terminal size: compact, standard, large, xl, or WxH
+    #[arg(long, short = 's', env = "CLI_VISION_DEFAULT_SIZE", default_value = "standard")]
+    size: String,
+
+    /// Fail if resident memory grows by more than this percentage over the first sample
+    #[arg(long, default_value = "50")]
+    max_memory_growth: f64,
+
+    /// Fail if more than this percentage of cells differ from the first iteration
+    #[arg(long, default_value = "5")]
+    max_drift: f64,
+
+    /// Color theme: default, vscode-dark, solarized, light, or a theme JSON file
+    #[arg(long, env = "CLI_VISION_THEME", default_value = "default")]
+    theme: String,
+
+    /// Environment variable for the application as KEY=VALUE (repeatable)
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    env_vars: Vec<(String, String)>,
+
+    /// Output directory for sampled screenshots and the report (default: auto-generated in session dir)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Arguments of the `fuzz` command
+#[derive(clap::Args, Debug)]
+struct FuzzArgs {
+    /// Path to the binary to execute
+    #[arg(short, long)]
+    binary: PathBuf,
+
+    /// Arguments to pass to the binary (comma-separated)
+    #[arg(short, long, value_delimiter = ',', allow_hyphen_values = true)]
+    args: Vec<String>,
+
+    /// Number of keys to send
+    #[arg(long, default_value = "500")]
+    steps: usize,
+
+    /// Seed of the key stream (default: derived from the current time, and printed)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Comma-separated keys to choose from instead of the default pool
+    #[arg(long, value_delimiter = ',')]
+    keys: Vec<String>,
+
+    /// Comma-separated keys to leave out of the pool (e.g., the quit key "q")
+    #[arg(long, value_delimiter = ',')]
+    exclude: Vec<String>,
+
+    /// Delay in milliseconds between keys
+    #[arg(short, long, default_value = "20")]
+    delay: u64,
+
+    /// Terminal size: compact, standard, large, xl, or WxH
+    #[arg(long, short = 's', env = "CLI_VISION_DEFAULT_SIZE", default_value = "standard")]
+    size: String,
+
+    /// Text distance between consecutive screens (0-1) above which a screenshot is kept
+    #[arg(long, default_value_t = DEFAULT_CHANGE_THRESHOLD)]
+    change_threshold: f64,
+
+    /// Color theme: default, vscode-dark, solarized, light, or a theme JSON file
+    #[arg(long, env = "CLI_VISION_THEME", default_value = "default")]
+    theme: String,
+
+    /// Environment variable for the application as KEY=VALUE (repeatable)
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    env_vars: Vec<(String, String)>,
+
+    /// Output directory for screenshots and the report (default: auto-generated in session dir)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Arguments of the `explore` command
+#[derive(clap::Args, Debug)]
+struct ExploreArgs {
+    /// Path to the binary to execute
+    #[arg(short, long)]
+    binary: PathBuf,
+
+    /// Arguments to pass to the binary (comma-separated)
+    #[arg(short, long, value_delimiter = ',', allow_hyphen_values = true)]
+    args: Vec<String>,
+
+    /// Most keys to press
+    #[arg(long, default_value = "30")]
+    steps: usize,
+
+    /// What to try to reach or do, e.g. "open the settings and change a value"
+    #[arg(long)]
+    goal: Option<String>,
+
+    /// Comma-separated keys the VLM may choose from instead of the default list
+    #[arg(long, value_delimiter = ',')]
+    keys: Vec<String>,
+
+    /// Comma-separated keys to leave out of the list (e.g., the quit key "q")
+    #[arg(long, value_delimiter = ',')]
+    exclude: Vec<String>,
+
+    /// Delay in milliseconds between keys
+    #[arg(short, long, default_value = "100")]
+    delay: u64,
+
+    /// Terminal size: compact, standard, large, xl, or WxH
+    #[arg(long, short = 's', env = "CLI_VISION_DEFAULT_SIZE", default_value = "standard")]
+    size: String,
+
+    /// Color theme: default, vscode-dark, solarized, light, or a theme JSON file
+    #[arg(long, env = "CLI_VISION_THEME", default_value = "default")]
+    theme: String,
+
+    /// Environment variable for the application as KEY=VALUE (repeatable)
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    env_vars: Vec<(String, String)>,
+
+    /// VLM endpoint URL
+    #[arg(long, env = "CLI_VISION_VLM_ENDPOINT", default_value = "http://127.0.0.1:8080/v1/chat/completions")]
+    vlm_endpoint: String,
+
+    /// VLM model name
+    #[arg(long, env = "CLI_VISION_VLM_MODEL", default_value = "qwen3")]
+    vlm_model: String,
+
+    /// Log every VLM request to vlm_log.jsonl in the session directory
+    #[arg(long)]
+    vlm_log: bool,
+
+    /// Output directory for screenshots and the trajectory (default: auto-generated in session dir)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output the trajectory as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Arguments of the `mock` command
+#[derive(clap::Args, Debug)]
+struct MockArgs {
+    /// Width in pixels
+    #[arg(short = 'W', long, default_value = "800")]
+    width: u32,
+
+    /// Height in pixels
+    #[arg(short = 'H', long, default_value = "600")]
+    height: u32,
+
+    /// Output file path
+    #[arg(short, long, default_value = "./mock_screenshot.png")]
+    output: PathBuf,
+
+    /// Fill color as hex (e.g., "ff0000" for red)
+    #[arg(short, long, default_value = "000000")]
+    color: String,
+}
+
+/// Arguments of the `release-diff` command
+#[derive(clap::Args, Debug)]
+struct ReleaseDiffArgs {
+    /// Captures of the previous release (a run session or suite output directory)
+    #[arg(long)]
+    old: PathBuf,
+
+    /// Captures of the new release
+    #[arg(long)]
+    new: PathBuf,
+
+    /// Markdown file to write; thumbnails go to <NAME>_images/ beside it
+    #[arg(long, short = 'o', default_value = "CHANGES.md")]
+    output: PathBuf,
+
+    /// Maximum perceptual distance for two screens to count as the same screen
+    #[arg(long, default_value_t = DEFAULT_MATCH_THRESHOLD)]
+    threshold: f64,
+
+    /// Have the VLM summarize each changed screen
+    #[arg(long)]
+    analyze: bool,
+
+    /// VLM endpoint URL
+    #[arg(long, env = "CLI_VISION_VLM_ENDPOINT", default_value = "http://127.0.0.1:8080/v1/chat/completions")]
+    vlm_endpoint: String,
+
+    /// VLM model name
+    #[arg(long, env = "CLI_VISION_VLM_MODEL", default_value = "qwen3")]
+    vlm_model: String,
+
+    /// Also print the changes as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Arguments of the `diff` command
+#[derive(clap::Args, Debug)]
+struct DiffArgs {
+    /// Baseline screenshot (PNG) or directory
+    old: PathBuf,
+
+    /// Screenshot or directory to compare against the baseline
+    new: PathBuf,
+
+    /// Heatmap PNG to write for two screenshots; for directories, where to write
+    /// annotated diff images and diff_summary.json/.md [default: capture-diff]
+    #[arg(long, short = 'o')]
+    output: Option<PathBuf>,
+
+    /// How much a color channel may differ before a pixel counts as changed (0-255)
+    #[arg(long, default_value_t = 0)]
+    tolerance: u8,
+
+    /// Fail when more than this fraction of pixels changed in a state (0 = any change fails)
+    #[arg(long, default_value_t = 0.0)]
+    threshold: f64,
+
+    /// Print the result as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Arguments of the `assert` command
+#[derive(clap::Args, Debug)]
+struct AssertArgs {
+    /// Capture to check: a .grid.json file, a screen text file, or a screenshot with either beside it
+    capture: PathBuf,
+
+    /// Expectation (repeatable): contains TEXT, not-contains TEXT, regex PATTERN, cell-color ROW,COL #rrggbb, cursor-at ROW,COL
+    #[arg(long = "expect", short = 'e', value_name = "EXPECTATION", required = true, value_parser = Expectation::parse)]
+    expectations: Vec<Expectation>,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Arguments of the `chat` command
+#[derive(clap::Args, Debug)]
+struct ChatArgs {
+    /// Session directory holding the state screenshots
+    session: PathBuf,
+
+    /// State to ask about (0 = initial)
+    #[arg(long, default_value = "0")]
+    step: usize,
+
+    /// VLM endpoint URL
+    #[arg(long, env = "CLI_VISION_VLM_ENDPOINT", default_value = "http://127.0.0.1:8080/v1/chat/completions")]
+    vlm_endpoint: String,
+
+    /// VLM model name
+    #[arg(long, env = "CLI_VISION_VLM_MODEL", default_value = "qwen3")]
+    vlm_model: String,
+}
+
+/// Arguments of the `search` command
+#[derive(clap::Args, Debug)]
+struct SearchArgs {
+    /// Directory holding sessions (e.g. a history of nightly runs)
+    base: PathBuf,
+
+    /// What to look for, e.g. "error dialog"
+    query: String,
+
+    /// Number of states to show
+    #[arg(long, default_value = "10")]
+    limit: usize,
+
+    /// VLM endpoint URL, or `local` for the local CLIP model
+    #[arg(long, env = "CLI_VISION_VLM_ENDPOINT", default_value = "http://127.0.0.1:8080/v1/chat/completions")]
+    vlm_endpoint: String,
+
+    /// VLM model name (also used for embeddings)
+    #[arg(long, env = "CLI_VISION_VLM_MODEL", default_value = "qwen3")]
+    vlm_model: String,
+
+    /// Embeddings endpoint URL (default: /v1/embeddings on the VLM server)
+    #[arg(long)]
+    embedding_endpoint: Option<String>,
+
+    /// Output the matches as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Arguments of the `suite` command
+#[derive(clap::Args, Debug)]
+struct SuiteArgs {
+    /// Suite file (JSON, see `schema --suite`)
+    file: PathBuf,
+
+    /// Verify steps with a `check` using the VLM
+    #[arg(long)]
+    analyze: bool,
+
+    /// VLM endpoint URL
+    #[arg(long, env = "CLI_VISION_VLM_ENDPOINT", default_value = "http://127.0.0.1:8080/v1/chat/completions")]
+    vlm_endpoint: String,
+
+    /// VLM model name
+    #[arg(long, env = "CLI_VISION_VLM_MODEL", default_value = "qwen3")]
+    vlm_model: String,
+
+    /// Ask the VLM this many times per check and take the majority verdict (steps may override with `samples`)
+    #[arg(long, env = "CLI_VISION_VLM_SAMPLES", default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    vlm_samples: u32,
+
+    /// Template for the context preamble before VLM prompts ({context}, {step}, {input}, {inputs}, {cols}, {rows}, {binary}, {scenario})
+    #[arg(long, env = "CLI_VISION_VLM_PREAMBLE", value_name = "TEMPLATE")]
+    prompt_preamble: Option<String>,
+
+    /// Send VLM prompts without the run context preamble
+    #[arg(long, conflicts_with = "prompt_preamble")]
+    no_prompt_preamble: bool,
+
+    /// Append the screen's text from the terminal buffer (cropped like the screenshot) to VLM prompts; false makes hybrid analysis send the image alone
+    #[arg(long, value_name = "BOOL", default_value_t = true, action = clap::ArgAction::Set)]
+    prompt_include_text: bool,
+
+    /// What VLM requests show the model: image (the screenshot), text (the screen's text, for text-only LLMs) or hybrid (both)
+    #[arg(long, value_name = "MODE", default_value = "hybrid")]
+    analysis_mode: AnalysisMode,
+
+    /// Scale screenshots down so their longer side is at most PX pixels before sending them to the VLM (saved screenshots keep their size)
+    #[arg(long, env = "CLI_VISION_VLM_IMAGE_MAX_SIZE", value_name = "PX", value_parser = clap::value_parser!(u32).range(1..))]
+    vlm_image_max_size: Option<u32>,
+
+    /// Format screenshots are sent to the VLM in: png, jpeg or webp (lossless)
+    #[arg(long, env = "CLI_VISION_VLM_IMAGE_FORMAT", value_name = "FORMAT", default_value = "png")]
+    vlm_image_format: ImageEncoding,
+
+    /// JPEG quality of screenshots sent to the VLM
+    #[arg(long, env = "CLI_VISION_VLM_IMAGE_QUALITY", value_name = "1-100", default_value = "85", value_parser = clap::value_parser!(u8).range(1..=100))]
+    vlm_image_quality: u8,
+
+    /// Always ask the VLM instead of reusing cached answers for identical screenshots and prompts
+    #[arg(long)]
+    no_vlm_cache: bool,
+
+    /// Log every VLM request (images as hashes), its timing, token usage and answer to vlm_log.jsonl in the output directory
+    #[arg(long)]
+    vlm_log: bool,
+
+    /// JSON file of named input sequences for every scenario (a scenario's own macros win)
+    #[arg(long, value_name = "FILE")]
+    macros: Option<PathBuf>,
+
+    /// Color theme: default, vscode-dark, solarized, light, or a theme JSON file
+    #[arg(long, env = "CLI_VISION_THEME", default_value = "default")]
+    theme: String,
+
+    /// Output directory for screenshots and suite_report.json (default: auto-generated in session dir)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Arguments of the `schema` command
+#[derive(clap::Args, Debug)]
+struct SchemaArgs {
+    /// Print the suite file schema instead
+    #[arg(long)]
+    suite: bool,
+}
+
+/// Arguments of the `serve` command
+#[derive(clap::Args, Debug)]
+struct ServeArgs {
+    /// JSON-RPC for editor plugins: validate scenarios, run steps, fetch captures, stream events
+    #[arg(long)]
+    editor: bool,
+
+    /// Color theme: default, vscode-dark, solarized, light, or a theme JSON file
+    #[arg(long, env = "CLI_VISION_THEME", default_value = "default")]
+    theme: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum FixturesAction {
+    /// Fetch fixtures (verifying checksums) and print their cached paths
+    Fetch {
+        /// Fixture manifest (JSON)
+        #[arg(short, long, default_value = "fixtures.json")]
+        manifest: PathBuf,
+
+        /// Fixtures to fetch as NAME@VERSION (default: every fixture for this platform)
+        specs: Vec<String>,
     },
+}
+
+#[derive(Subcommand, Debug)]
+enum SnapshotsAction {
+    /// Interactively accept or reject pending snapshots
+    Review {
+        /// Snapshot directory
+        #[arg(short, long, default_value = "snapshots")]
+        dir: PathBuf,
+
+        /// Accept all pending snapshots without prompting
+        #[arg(long, conflicts_with = "reject_all")]
+        accept_all: bool,
+
+        /// Reject all pending snapshots without prompting
+        #[arg(long)]
+        reject_all: bool,
+    },
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+
+    match args.command {
+        Some(Commands::Cli(args)) => cli_command(args),
+        Some(Commands::Run(args)) => run_command(args),
+        Some(Commands::Record(args)) => record_command(args),
+        Some(Commands::Soak(args)) => soak_command(args),
+        Some(Commands::Fuzz(args)) => fuzz_command(args),
+        Some(Commands::Explore(args)) => explore_command(args),
+        Some(Commands::Mock(args)) => mock_command(args),
+        Some(Commands::Fixtures { action }) => fixtures_command(action),
+        Some(Commands::Snapshots { action }) => snapshots_command(action),
+        Some(Commands::Suite(args)) => suite_command(args),
+        Some(Commands::Search(args)) => search_command(args),
+        Some(Commands::ReleaseDiff(args)) => release_diff_command(args),
+        Some(Commands::Diff(args)) if args.old.is_dir() && args.new.is_dir() => diff_dirs_command(args),
+        Some(Commands::Diff(args)) => diff_command(args),
+        Some(Commands::Assert(args)) => assert_command(args),
+        Some(Commands::Chat(args)) => chat_command(args),
+        Some(Commands::Schema(args)) => schema_command(args),
+        Some(Commands::Serve(args)) => serve_command(args),
+        None => {
+            print_commands();
+            Ok(())
+        }
+    }
+}
+
+/// `cli`: capture a CLI application screenshot using PTY emulation
+fn cli_command(args: CliArgs) -> Result<(), Box<dyn Error>> {
+    let CliArgs {
+        binary,
+        output,
+        keep,
+        size,
+        show_cursor,
+        crop,
+        glyph_fallback,
+        glyph_font,
+        theme,
+        hermetic,
+        env_vars,
+        cpu_limit,
+        memory_limit,
+        fd_limit,
+        save_text,
+        args: binary_args,
+    } = args;
+    // Parse terminal size
+    let term_size = TerminalSize::from_str(&size)
+        .ok_or_else(|| format!("Invalid terminal size '{}'. Use: compact, standard, large, xl, or WxH", size))?;
+    let (cols, rows) = term_size.dimensions();
+
+    // Create session - if output specified, use that dir and keep by default
+    let session = if let Some(ref dir) = output {
+        Session::in_dir(dir).keep(keep || output.is_some())
+    } else {
+        let binary_name = binary.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "capture".to_string());
+        Session::with_name(&binary_name).keep(keep)
+    };
+    session.init()?;
+
+    let config = PtyBackendConfig::new(&binary)
+        .args(binary_args)
+        .size(cols, rows)
+        .show_cursor(show_cursor)
+        .glyph_fallback(glyph_fallback_chain(glyph_fallback.as_deref(), glyph_font.as_deref())?)
+        .theme(Theme::resolve(&theme)?)
+        .hermetic(hermetic)
+        .limits(resource_limits(cpu_limit, memory_limit, fd_limit));
+    let config = env_vars.into_iter().fold(config, |config, (key, value)| config.env(key, value));
+    let config = match crop {
+        Some(crop) => config.crop(crop),
+        None => config,
+    };
+    let mut backend = PtyBackend::new(config);
+
+    let result = backend.capture()?;
+    let output_path = session.capture_path("capture");
+    std::fs::write(&output_path, &result.image_data)?;
+
+    println!("Captured CLI screenshot: {}", output_path.display());
+    if save_text && let Some(text) = &result.text {
+        let text_path = output_path.with_extension("txt");
+        std::fs::write(&text_path, text)?;
+        println!("  Text: {}", text_path.display());
+    }
+    println!("  Size: {}x{} (terminal: {}x{})", result.width, result.height, cols, rows);
+
+    // Keep session alive if needed (prevent Drop cleanup)
+    if keep || output.is_some() {
+        std::mem::forget(session);
+    }
+    Ok(())
+}
+
+/// `run`: run a CLI application with inputs, capturing state after each
+fn run_command(args: RunArgs) -> Result<(), Box<dyn Error>> {
+    let RunArgs {
+        binary,
+        args: binary_args,
+        inputs,
+        scenario,
+        replay,
+        replay_speed,
+        replay_idle_limit,
+        capture_at,
+        macros,
+        keyboard_layout,
+        key_protocol,
+        delay,
+        type_delay,
+        wait_timeout,
+        output,
+        keep,
+        analyze,
+        vlm_endpoint,
+        vlm_model,
+        prompt,
+        prompt_file,
+        step_prompts,
+        step_prompts_file,
+        prompt_preamble,
+        no_prompt_preamble,
+        prompt_include_text,
+        analysis_mode,
+        vlm_image_max_size,
+        vlm_image_format,
+        vlm_image_quality,
+        vlm_context,
+        no_vlm_cache,
+        vlm_log,
+        json,
+        size,
+        multi_size,
+        show_cursor,
+        crop,
+        glyph_fallback,
+        glyph_font,
+        theme,
+        hermetic,
+        env_vars,
+        cpu_limit,
+        memory_limit,
+        fd_limit,
+        blink_frames,
+        check_links,
+        spellcheck,
+        dictionary,
+        wordlist,
+        ban_defaults,
+        ban,
+        ban_file,
+        locales,
+        layout_check,
+        color_vision,
+        pty_pool,
+        emit_frames,
+        save_text,
+        save_ansi,
+        format,
+        export_gif,
+        export_video,
+        video_fps,
+        golden_dir,
+        golden_images,
+        update,
+        compare_baseline,
+        annotate_findings,
+        vlm_prices,
+    } = args;
+    // Streamed frames own stdout, so nothing else is printed there
+    let text_output = !json && emit_frames.is_none();
+    let color_vision = color_vision.as_deref().map(ColorDeficiency::parse_list).transpose()?;
+    if let Some(path) = &export_video {
+        if VideoFormat::from_path(path).is_none() {
+            return Err(format!("Unknown video format '{}' (expected .webp, .apng, .mp4)", path.display()).into());
+        }
+        check_video_support()?;
+    }
+    if analysis_mode == AnalysisMode::Text && compare_baseline.is_some() {
+        return Err("--compare-baseline compares screenshots and cannot be used with --analysis-mode text".into());
+    }
+    let prices = match &vlm_prices {
+        Some(path) => PriceTable::from_file(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+        None => PriceTable::default(),
+    };
+
+    // A scenario supplies the application and its inputs, and overrides the size, delay and layout
+    let scenario = scenario.as_deref().map(Scenario::from_file).transpose()?;
+    let (binary, binary_args, size, delay, keyboard_layout) = match &scenario {
+        Some(scenario) => (
+            scenario.binary.clone(),
+            scenario.args.clone(),
+            scenario.size.clone().unwrap_or(size),
+            scenario.delay_ms,
+            match &scenario.layout {
+                Some(name) => scenario.keyboard_layout().ok_or_else(|| format!("Unknown keyboard layout '{}'", name))?,
+                None => keyboard_layout,
+            },
+        ),
+        None => (binary.unwrap_or_default(), binary_args, size, delay, keyboard_layout),
+    };
+
+    // A replayed cast runs at its recorded size, capturing at the planned points
+    let replay = replay.as_deref().map(Cast::from_file).transpose()?;
+    let size = replay.as_ref().map_or(size, |cast| format!("{}x{}", cast.width, cast.height));
+    let replay_plan = replay.as_ref().map(|cast| {
+        let config = ReplayConfig::default().speed(replay_speed).capture(capture_at);
+        let config = match replay_idle_limit {
+            Some(limit) => config.idle_limit(limit),
+            None => config,
+        };
+        replay_plan(cast, &config)
+    });
+    if let Some(cast) = &replay {
+        if !cast.has_input() {
+            eprintln!("Warning: the cast has no input events; record with `asciinema rec --stdin` to replay keystrokes");
+        }
+        if capture_at == ReplayCapture::Markers && !cast.events.iter().any(|e| e.kind == CastEventKind::Marker) {
+            eprintln!("Note: the cast has no markers, so only the final state is captured (see --capture-at)");
+        }
+    }
+
+    // Create session - if output specified, use that dir and keep by default
+    let binary_name = binary.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "run".to_string());
+
+    let session = if let Some(ref dir) = output {
+        Session::in_dir(dir).keep(keep || output.is_some())
+    } else {
+        // The transcript is no use in a session that is deleted afterwards
+        Session::with_name(&format!("{}_run", binary_name)).keep(keep || vlm_log)
+    };
+    session.init()?;
+
+    // Parse inputs, expanding repeats and macros
+    let expanded = match (&scenario, &replay_plan) {
+        (Some(scenario), _) => scenario.expanded_inputs().map_err(|e| format!("Invalid scenario inputs: {}", e))?,
+        // Each replay capture point stands in for an input
+        (None, Some(plan)) => plan
+            .iter()
+            .filter_map(|step| match &step.action {
+                ReplayAction::Capture(label) => Some(label.clone()),
+                _ => None,
+            })
+            .enumerate()
+            .map(|(origin, input)| ExpandedInput { input, source: None, origin })
+            .collect(),
+        (None, None) => {
+            let input_list: Vec<String> = inputs
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+            let macros = macros.as_deref().map(load_macros).transpose()?.unwrap_or_default();
+            expand_inputs(&input_list, &macros).map_err(|e| format!("Invalid inputs: {}", e))?
+        }
+    };
+    let input_list = expanded_inputs(&expanded);
+
+    // Parse step-specific prompts if provided
+    let step_prompt_map = match (&step_prompts, &step_prompts_file) {
+        (_, Some(path)) => load_step_prompts(path)?,
+        (Some(s), None) => parse_step_prompts(s).unwrap_or_default(),
+        (None, None) => Default::default(),
+    };
+    let prompt = match &prompt_file {
+        Some(path) => Some(
+            std::fs::read_to_string(path)
+                .map_err(|e| format!("prompt file {}: {}", path.display(), e))?
+                .trim()
+                .to_string(),
+        ),
+        None => prompt,
+    };
+
+    let spell_checker = if spellcheck {
+        let mut checker = match SpellChecker::new().wordlist_file(&dictionary) {
+            Ok(checker) => checker,
+            Err(e) => {
+                eprintln!("Warning: could not read dictionary {}: {}", dictionary.display(), e);
+                SpellChecker::new()
+            }
+        };
+        for path in &wordlist {
+            checker = checker.wordlist_file(path)
+                .map_err(|e| format!("Failed to read wordlist {}: {}", path.display(), e))?;
+        }
+        Some(checker)
+    } else {
+        None
+    };
+
+    let mut banned_scanner = if ban_defaults {
+        BannedContentScanner::with_defaults()
+    } else {
+        BannedContentScanner::new()
+    };
+    for pattern in &ban {
+        banned_scanner = banned_scanner.pattern(pattern, pattern)
+            .map_err(|e| format!("Invalid --ban pattern '{}': {}", pattern, e))?;
+    }
+    if let Some(path) = &ban_file {
+        banned_scanner = banned_scanner.rules_file(path)
+            .map_err(|e| format!("Failed to load ban rules {}: {}", path.display(), e))?;
+    }
+    let mut banned_states = 0usize;
+    let mut wait_timeouts = Vec::new();
+    let golden = golden_dir
+        .as_ref()
+        .map(|dir| Golden::new(dir).update(update || update_from_env()).images(golden_images));
+    let mut golden_mismatches = 0usize;
+
+    let run_options = RunOptions::default()
+        .theme(Theme::resolve(&theme)?)
+        .layout(keyboard_layout)
+        .key_protocol(key_protocol)
+        .type_delay(Duration::from_millis(type_delay))
+        .wait_timeout(Duration::from_millis(wait_timeout))
+        .hermetic(hermetic)
+        .limits(resource_limits(cpu_limit, memory_limit, fd_limit))
+        .show_cursor(show_cursor)
+        .glyph_fallback(glyph_fallback_chain(glyph_fallback.as_deref(), glyph_font.as_deref())?)
+        .blink_frames(blink_frames)
+        .capture_ansi(save_ansi)
+        .capture_grid(format == StateFormat::GridJson || golden_dir.is_some())
+        .capture_html(format == StateFormat::Html);
+    let run_options = env_vars.into_iter().fold(run_options, |options, (key, value)| options.env(key, value));
+    let run_options = match crop {
+        Some(crop) => run_options.crop(crop),
+        None => run_options,
+    };
+    let run_options = match &scenario {
+        Some(scenario) => {
+            let mut options = run_options;
+            options.env.extend(scenario.env_pairs());
+            options.step_crops.extend(scenario.crops(&expanded));
+            options
+        }
+        None => run_options,
+    };
+    let run_options = match pty_pool {
+        Some(capacity) => run_options.pool(PtyPool::warm(capacity, TerminalSize::default())?),
+        None => run_options,
+    };
+
+    // Determine terminal sizes to test
+    let sizes_to_test: Vec<TerminalSize> = if multi_size {
+        TerminalSize::all_presets()
+    } else {
+        let term_size = TerminalSize::from_str(&size)
+            .ok_or_else(|| format!("Invalid terminal size '{}'. Use: compact, standard, large, xl, or WxH (e.g., 100x30)", size))?;
+        vec![term_size]
+    };
+
+    // Locale matrix (None = inherit the caller's locale)
+    let locale_list: Vec<Option<String>> = match &locales {
+        Some(list) => list
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| Some(s.to_string()))
+            .collect(),
+        None => vec![None],
+    };
+    // Screen text per (size, locale) for the localization report
+    let mut locale_texts: Vec<((u16, u16), String, Vec<String>)> = Vec::new();
+
+    let vlm_config = with_prompt_options(
+        configure_vlm(&vlm_endpoint, &vlm_model, !no_vlm_cache, vlm_log.then_some(&session)),
+        prompt_preamble,
+        no_prompt_preamble,
+        analysis_mode,
+        prompt_include_text,
+    )
+    .image_prep(image_prep(vlm_image_max_size, vlm_image_format, vlm_image_quality));
+    let settings = RunSettings {
+        session: &session,
+        run_options: &run_options,
+        replay_plan: replay_plan.as_deref(),
+        binary: &binary,
+        binary_args: &binary_args,
+        input_list: &input_list,
+        expanded: &expanded,
+        delay,
+        multi_size,
+        text_output,
+        json,
+        emit_frames,
+        save_text,
+        analyze,
+        annotate_findings,
+        compare_baseline: compare_baseline.as_deref(),
+        vlm_endpoint: &vlm_endpoint,
+        vlm_model: &vlm_model,
+        vlm_config: &vlm_config,
+        vlm_context,
+        prompt: prompt.as_deref(),
+        step_prompt_map: &step_prompt_map,
+        prices: &prices,
+        golden: golden.as_ref(),
+        check_links,
+        spell_checker: spell_checker.as_ref(),
+        banned_scanner: &banned_scanner,
+        layout_check,
+        color_vision: color_vision.as_deref(),
+        export_gif: export_gif.as_deref(),
+        export_video: export_video.as_deref(),
+        video_fps,
+    };
+    // Process each size and locale
+    for term_size in &sizes_to_test {
+        for locale in &locale_list {
+            let outcome = run_one_configuration(&settings, *term_size, locale.as_deref())?;
+            banned_states += outcome.banned_states;
+            wait_timeouts.extend(outcome.wait_timeout);
+            golden_mismatches += outcome.golden_mismatches;
+            if let Some(locale) = locale {
+                locale_texts.push((term_size.dimensions(), locale.clone(), outcome.texts));
+            }
+        }
+    }
+
+    // Localization completeness relative to the first (base) locale
+    if locale_list.len() > 1 {
+        let mut report = Vec::new();
+        for (size, base_locale, base_texts) in &locale_texts {
+            if Some(base_locale) != locale_list[0].as_ref() {
+                continue;
+            }
+            for (other_size, locale, texts) in &locale_texts {
+                if other_size == size && locale != base_locale {
+                    report.push(locale_coverage(locale, base_texts, texts));
+                }
+            }
+        }
+        std::fs::write(
+            session.dir.join("localization_report.json"),
+            serde_json::to_string_pretty(&report)?,
+        )?;
+        if text_output {
+            println!("\nLocalization coverage (base: {}):", locale_list[0].as_deref().unwrap_or("default"));
+            for coverage in &report {
+                println!(
+                    "  {}: {:.1}% ({} of {} strings untranslated)",
+                    coverage.locale,
+                    coverage.coverage_percent,
+                    coverage.untranslated.len(),
+                    coverage.total_strings
+                );
+                for s in coverage.untranslated.iter().take(10) {
+                    println!("    step {} at {},{}: '{}'", s.step, s.row, s.col, s.text);
+                }
+            }
+        }
+    }
+
+    // Print session location
+    if text_output {
+        println!("\nSession: {}", session.dir.display());
+    }
+
+    // Keep session alive if needed (prevent Drop cleanup)
+    if keep || output.is_some() {
+        std::mem::forget(session);
+    }
+
+    if let Some(timeout) = wait_timeouts.first() {
+        return Err(timeout.clone().into());
+    }
+    if banned_states > 0 {
+        return Err(format!("Banned content visible in {} state(s)", banned_states).into());
+    }
+    if golden_mismatches > 0 {
+        return Err(format!("{} state(s) differ from their goldens (rerun with --update to accept)", golden_mismatches).into());
+    }
+    Ok(())
+}
+
+/// `record`: drive an application from this terminal and record the keystrokes as a replayable scenario
+fn record_command(args: RecordArgs) -> Result<(), Box<dyn Error>> {
+    let RecordArgs {
+        binary,
+        args: binary_args,
+        output,
+        size,
+        capture_key,
+        stop_key,
+        delay,
+        min_pause,
+        env_vars,
+    } = args;
+    let mut config = RecordConfig::default().capture_key(&capture_key).stop_key(&stop_key);
+    if let Some(size) = size {
+        let term_size = TerminalSize::from_str(&size)
+            .ok_or_else(|| format!("Invalid terminal size '{}'. Use: compact, standard, large, xl, or WxH", size))?;
+        config = config.size(term_size);
+    }
+    let config = env_vars.into_iter().fold(config, |config, (key, value)| config.env(key, value));
+
+    eprintln!(
+        "Recording {}: press {} to mark a capture point, {} to stop",
+        binary.display(),
+        capture_key,
+        stop_key
+    );
+    let recording = record(&binary.to_string_lossy(), &binary_args, &config)?;
+
+    println!();
+    let scenario = Scenario::from_recording(binary, binary_args, &recording, delay, min_pause);
+    std::fs::write(&output, serde_json::to_string_pretty(&scenario)? + "\n")?;
+    let captures = recording.events.iter().filter(|e| matches!(e, RecordedEvent::Capture { .. })).count();
+    println!("Wrote {}: {} steps, {} capture point(s)", output.display(), scenario.steps.len(), captures);
+    if let Some(code) = recording.exit_code {
+        println!("Application exited with code {}", code);
+    }
+    println!("Replay with: cli-vision run --scenario {}", output.display());
+    Ok(())
+}
+
+/// `soak`: loop inputs for a fixed wall time, failing on crash, memory growth or layout drift
+fn soak_command(args: SoakArgs) -> Result<(), Box<dyn Error>> {
+    let SoakArgs {
+        binary,
+        args: binary_args,
+        inputs_loop,
+        macros,
+        keyboard_layout,
+        key_protocol,
+        duration,
+        sample_interval,
+        delay,
+        size,
+        max_memory_growth,
+        max_drift,
+        theme,
+        env_vars,
+        output,
+        json,
+    } = args;
+    let term_size = TerminalSize::from_str(&size)
+        .ok_or_else(|| format!("Invalid terminal size '{}'. Use: compact, standard, large, xl, or WxH", size))?;
+    let inputs: Vec<String> = inputs_loop
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let macros = macros.as_deref().map(load_macros).transpose()?.unwrap_or_default();
+    let inputs = expanded_inputs(&expand_inputs(&inputs, &macros).map_err(|e| format!("Invalid inputs: {}", e))?);
+
+    let session = match &output {
+        Some(dir) => Session::in_dir(dir).keep(true),
+        None => Session::with_name("soak").keep(true),
+    };
+    session.init()?;
+
+    let options = RunOptions::default().theme(Theme::resolve(&theme)?).layout(keyboard_layout).key_protocol(key_protocol);
+    let options = env_vars.into_iter().fold(options, |options, (key, value)| options.env(key, value));
+    let config = SoakConfig::new(inputs, duration)
+        .sample_interval(sample_interval)
+        .input_delay(delay)
+        .size(term_size)
+        .options(options)
+        .max_memory_growth(max_memory_growth)
+        .max_drift(max_drift);
+
+    if !json {
+        println!("Soaking {} for {:?}...", binary.display(), duration);
+    }
+    let report = run_soak(&binary.to_string_lossy(), &binary_args, &config)?;
+
+    for sample in &report.samples {
+        std::fs::write(session.capture_path(&format!("sample_{:05}", sample.iteration)), &sample.image_data)?;
+    }
+    let report_json = serde_json::to_string_pretty(&report)?;
+    std::fs::write(session.dir.join("soak_report.json"), &report_json)?;
+
+    if json {
+        println!("{}", report_json);
+    } else {
+        println!("Iterations: {} in {:.1}s", report.iterations, report.duration_secs);
+        for sample in &report.samples {
+            let rss = sample
+                .rss_bytes
+                .map(|bytes| format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0)))
+                .unwrap_or_else(|| "n/a".to_string());
+            println!(
+                "  iteration {:>6} @ {:>7.1}s  rss {:>10}  drift {:.1}%",
+                sample.iteration, sample.elapsed_secs, rss, sample.drift_percent
+            );
+        }
+        for failure in &report.failures {
+            match failure {
+                SoakFailure::Crashed { iteration, exit_code } => {
+                    println!("FAIL: application exited with code {} during iteration {}", exit_code, iteration)
+                }
+                SoakFailure::MemoryGrowth { baseline_bytes, final_bytes, growth_percent } => println!(
+                    "FAIL: memory grew {:.1}% ({} -> {} bytes)",
+                    growth_percent, baseline_bytes, final_bytes
+                ),
+                SoakFailure::LayoutDrift { iteration, drift_percent } => {
+                    println!("FAIL: layout drifted {:.1}% from the first iteration at iteration {}", drift_percent, iteration)
+                }
+                SoakFailure::WaitTimedOut { iteration, condition } => {
+                    println!("FAIL: timed out waiting for {} during iteration {}", condition, iteration)
+                }
+            }
+        }
+        println!("\nSession: {}", session.dir.display());
+    }
+
+    if !report.passed {
+        return Err(format!("Soak failed with {} failure(s)", report.failures.len()).into());
+    }
+    Ok(())
+}
 
-    /// Run a CLI application with inputs, capturing state after each
-    Run {
-        /// Path to the binary to execute
-        #[arg(short, long)]
-        binary: PathBuf,
+/// `fuzz`: send a seeded random key stream, keeping screenshots of dramatic changes and crashes
+fn fuzz_command(args: FuzzArgs) -> Result<(), Box<dyn Error>> {
+    let FuzzArgs {
+        binary,
+        args: binary_args,
+        steps,
+        seed,
+        keys,
+        exclude,
+        delay,
+        size,
+        change_threshold,
+        theme,
+        env_vars,
+        output,
+        json,
+    } = args;
+    let term_size = TerminalSize::from_str(&size)
+        .ok_or_else(|| format!("Invalid terminal size '{}'. Use: compact, standard, large, xl, or WxH", size))?;
+    let seed = seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default()
+    });
 
-        /// Arguments to pass to the binary (comma-separated, e.g., "--headless,--config,foo.yaml")
-        #[arg(short, long, value_delimiter = ',', allow_hyphen_values = true)]
-        args: Vec<String>,
+    let session = match &output {
+        Some(dir) => Session::in_dir(dir).keep(true),
+        None => Session::with_name("fuzz").keep(true),
+    };
+    session.init()?;
 
-        /// Comma-separated list of inputs (e.g., "down,down,enter,escape")
-        #[arg(short, long)]
-        inputs: String,
+    let options = RunOptions::default().theme(Theme::resolve(&theme)?);
+    let options = env_vars.into_iter().fold(options, |options, (key, value)| options.env(key, value));
+    let mut config = FuzzConfig::new(steps, seed)
+        .input_delay(delay)
+        .size(term_size)
+        .options(options)
+        .change_threshold(change_threshold);
+    if !keys.is_empty() {
+        config = config.keys(keys);
+    }
+    let exclude: Vec<&str> = exclude.iter().map(String::as_str).collect();
+    let config = config.exclude(&exclude);
+    if config.keys.is_empty() {
+        return Err("No keys left to send after --exclude".into());
+    }
 
-        /// Delay in milliseconds between inputs
-        #[arg(short, long, env = "CLI_VISION_DEFAULT_DELAY", default_value = "100")]
-        delay: u64,
+    if !json {
+        println!("Fuzzing {} with {} keys (seed {})...", binary.display(), steps, seed);
+    }
+    let report = run_fuzz(&binary.to_string_lossy(), &binary_args, &config)?;
 
-        /// Output directory for screenshots (default: auto-generated in session dir)
-        #[arg(short, long)]
-        output: Option<PathBuf>,
+    for event in &report.events {
+        std::fs::write(session.capture_path(&format!("step_{:05}", event.step)), &event.image_data)?;
+    }
+    let report_json = serde_json::to_string_pretty(&report)?;
+    std::fs::write(session.dir.join("fuzz_report.json"), &report_json)?;
 
-        /// Keep screenshots after completion (default: cleanup unless --output is specified)
-        #[arg(long, short = 'k')]
-        keep: bool,
+    if json {
+        println!("{}", report_json);
+    } else {
+        println!("Keys sent: {} of {}", report.steps_run, report.inputs.len());
+        for event in &report.events {
+            let input = event.input.as_deref().unwrap_or("initial");
+            match event.kind {
+                FuzzEventKind::DramaticChange { distance } => {
+                    println!("  step {:>5} ({}): screen changed {:.0}%", event.step, input, distance * 100.0)
+                }
+                FuzzEventKind::Died => println!("  step {:>5} ({}): application died", event.step, input),
+            }
+        }
+        if let Some(reason) = &report.died {
+            println!("FAIL: {}", reason);
+            println!("Reproduce with: --seed {} --steps {}", report.seed, report.steps_run);
+        }
+        println!("\nSession: {}", session.dir.display());
+    }
 
-        /// Analyze screenshots with VLM and include descriptions
-        #[arg(long)]
-        analyze: bool,
+    if let Some(reason) = report.died {
+        return Err(format!("Application died after {} key(s): {}", report.steps_run, reason).into());
+    }
+    Ok(())
+}
 
-        /// VLM endpoint URL
-        #[arg(long, env = "CLI_VISION_VLM_ENDPOINT", default_value = "http://127.0.0.1:8080/v1/chat/completions")]
-        vlm_endpoint: String,
+/// `explore`: let the VLM explore an application, choosing each key from a list, to find crashes and dead ends
+fn explore_command(args: ExploreArgs) -> Result<(), Box<dyn Error>> {
+    let ExploreArgs {
+        binary,
+        args: binary_args,
+        steps,
+        goal,
+        keys,
+        exclude,
+        delay,
+        size,
+        theme,
+        env_vars,
+        vlm_endpoint,
+        vlm_model,
+        vlm_log,
+        output,
+        json,
+    } = args;
+    let term_size = TerminalSize::from_str(&size)
+        .ok_or_else(|| format!("Invalid terminal size '{}'. Use: compact, standard, large, xl, or WxH", size))?;
+    if !matches!(check_health(&vlm_endpoint, 5), Ok(true)) {
+        return Err(format!("VLM endpoint not responding at {}; exploring needs it to choose keys", vlm_endpoint).into());
+    }
 
-        /// VLM model name
-        #[arg(long, env = "CLI_VISION_VLM_MODEL", default_value = "qwen3")]
-        vlm_model: String,
+    let session = match &output {
+        Some(dir) => Session::in_dir(dir).keep(true),
+        None => Session::with_name("explore").keep(true),
+    };
+    session.init()?;
 
-        /// Custom analysis prompt (use {input} and {step} as placeholders)
-        #[arg(long)]
-        prompt: Option<String>,
+    let options = RunOptions::default().theme(Theme::resolve(&theme)?);
+    let options = env_vars.into_iter().fold(options, |options, (key, value)| options.env(key, value));
+    let mut config = ExploreConfig::new(steps).input_delay(delay).size(term_size).options(options);
+    if !keys.is_empty() {
+        config = config.keys(keys);
+    }
+    if let Some(goal) = goal {
+        config = config.goal(goal);
+    }
+    let exclude: Vec<&str> = exclude.iter().map(String::as_str).collect();
+    let config = config.exclude(&exclude);
+    if config.keys.is_empty() {
+        return Err("No keys left to choose from after --exclude".into());
+    }
+    let vlm_config = configure_vlm(&vlm_endpoint, &vlm_model, false, vlm_log.then_some(&session));
 
-        /// Per-step prompts as JSON: {"1": "check if button is blue", "3": "verify dialog opened"}
-        #[arg(long)]
-        step_prompts: Option<String>,
+    if !json {
+        println!("Exploring {} for up to {} keys...", binary.display(), steps);
+    }
+    let mut write_error = None;
+    let report = run_explore(&binary.to_string_lossy(), &binary_args, &config, &vlm_config, |step| {
+        // Named like `run`'s states, so `chat` and `diff` work on the session
+        let filename = match &step.input {
+            Some(input) => format!("state_{}_{}.png", step.step, input.replace(['+', ' ', ':'], "_")),
+            None => "state_0_initial.png".to_string(),
+        };
+        if let Err(e) = std::fs::write(session.dir.join(&filename), &step.image_data) {
+            write_error.get_or_insert(e);
+        }
+        if !json {
+            let input = step.input.as_deref().unwrap_or("initial");
+            let observation = match (&step.observation, step.exit_code) {
+                (Some(observation), _) => observation.clone(),
+                (None, Some(code)) => format!("(exited with code {})", code),
+                (None, None) => "(not described)".to_string(),
+            };
+            let dead_end = if step.dead_end { " [dead end]" } else { "" };
+            println!("  step {:>3} ({}): {}{}", step.step, input, observation, dead_end);
+            if let (Some(key), Some(rationale)) = (&step.next_key, &step.rationale) {
+                println!("            -> {}: {}", key, rationale);
+            }
+        }
+    })?;
+    if let Some(e) = write_error {
+        return Err(e.into());
+    }
+    let report_json = serde_json::to_string_pretty(&report)?;
+    std::fs::write(session.dir.join("explore_report.json"), &report_json)?;
 
-        /// Output results as JSON
-        #[arg(long)]
-        json: bool,
+    if json {
+        println!("{}", report_json);
+    } else {
+        println!("Keys pressed: {}, distinct screens: {}", report.inputs().len(), report.screens);
+        for step in report.dead_ends() {
+            println!("Dead end at step {}: {}", step.step, step.observation.as_deref().unwrap_or_default());
+        }
+        if let Some(reason) = &report.stopped {
+            println!("Stopped: {}", reason);
+        }
+        if let Some(reason) = &report.died {
+            println!("FAIL: {}", reason);
+            println!("Reproduce with: run --inputs \"{}\"", report.inputs().join(","));
+        }
+        println!("\nSession: {}", session.dir.display());
+    }
 
-        /// Terminal size: compact (80x24), standard (120x40), large (160x50), xl (200x60), or WxH (e.g., 100x30)
-        #[arg(long, short = 's', env = "CLI_VISION_DEFAULT_SIZE", default_value = "standard")]
-        size: String,
+    if let Some(reason) = &report.died {
+        return Err(format!("Application died after {} key(s): {}", report.inputs().len(), reason).into());
+    }
+    Ok(())
+}
 
-        /// Run with all preset sizes and compare results (useful for finding resize bugs)
-        #[arg(long)]
-        multi_size: bool,
-    },
+/// `mock`: create a mock framebuffer screenshot for testing
+fn mock_command(args: MockArgs) -> Result<(), Box<dyn Error>> {
+    let MockArgs { width, height, output, color } = args;
+    let color_bytes = parse_hex_color(&color)?;
+    let mut fb = MockFramebuffer::with_color(width, height, color_bytes);
 
-    /// Create a mock framebuffer screenshot for testing
-    Mock {
-        /// Width in pixels
-        #[arg(short = 'W', long, default_value = "800")]
-        width: u32,
-
-        /// Height in pixels
-        #[arg(short = 'H', long, default_value = "600")]
-        height: u32,
-
-        /// Output file path
-        #[arg(short, long, default_value = "./mock_screenshot.png")]
-        output: PathBuf,
-
-        /// Fill color as hex (e.g., "ff0000" for red)
-        #[arg(short, long, default_value = "000000")]
-        color: String,
-    },
+    // Draw some sample content
+    fb.draw_text(10, 10, "Mock Framebuffer", [255, 255, 255], color_bytes);
+    fb.draw_rect(10, 30, 100, 50, [128, 128, 128]);
+
+    let result = fb.capture()?;
+    std::fs::write(&output, &result.image_data)?;
+
+    println!("Created mock screenshot: {}", output.display());
+    println!("  Size: {}x{}", result.width, result.height);
+    Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+/// `fixtures`: download pinned fixture binaries into the local cache
+fn fixtures_command(action: FixturesAction) -> Result<(), Box<dyn Error>> {
+    let FixturesAction::Fetch { manifest, specs } = action;
+    let manifest = FixtureManifest::from_file(&manifest)?;
+    let cache = FixtureCache::from_env();
+    let platform = current_platform();
 
-    match args.command {
-        Some(Commands::Cli {
-            binary,
-            output,
-            keep,
-            size,
-            args: binary_args,
-        }) => {
-            // Parse terminal size
-            let term_size = TerminalSize::from_str(&size)
-                .ok_or_else(|| format!("Invalid terminal size '{}'. Use: compact, standard, large, xl, or WxH", size))?;
-            let (cols, rows) = term_size.dimensions();
-
-            // Create session - if output specified, use that dir and keep by default
-            let session = if let Some(ref dir) = output {
-                Session::in_dir(dir).keep(keep || output.is_some())
-            } else {
-                let binary_name = binary.file_stem()
-                    .map(|s| s.to_string_lossy().to_string())
-                    .unwrap_or_else(|| "capture".to_string());
-                Session::with_name(&binary_name).keep(keep)
-            };
-            session.init()?;
+    let targets: Vec<(String, String)> = if specs.is_empty() {
+        manifest
+            .fixtures
+            .iter()
+            .filter(|f| f.platform.as_deref().is_none_or(|p| p == platform))
+            .map(|f| (f.name.clone(), f.version.clone()))
+            .collect()
+    } else {
+        specs
+            .iter()
+            .map(|spec| {
+                spec.split_once('@')
+                    .map(|(name, version)| (name.to_string(), version.to_string()))
+                    .ok_or_else(|| format!("Invalid fixture '{}', expected NAME@VERSION", spec))
+            })
+            .collect::<Result<_, _>>()?
+    };
+
+    for (name, version) in targets {
+        let path = cache.fetch_named(&manifest, &name, &version)?;
+        println!("{}@{}: {}", name, version, path.display());
+    }
+    Ok(())
+}
+
+/// `snapshots`: manage golden text snapshots written by `expect_snapshot!`
+fn snapshots_command(action: SnapshotsAction) -> Result<(), Box<dyn Error>> {
+    let SnapshotsAction::Review { dir, accept_all, reject_all } = action;
+    let pending = pending_snapshots(&dir)?;
+    if pending.is_empty() {
+        println!("No pending snapshots in {}", dir.display());
+        return Ok(());
+    }
 
-            let config = PtyBackendConfig::new(&binary)
-                .args(binary_args)
-                .size(cols, rows);
-            let mut backend = PtyBackend::new(config);
+    let (mut accepted, mut rejected) = (0, 0);
+    for snapshot in &pending {
+        let accept = if accept_all || reject_all {
+            accept_all
+        } else {
+            let new = std::fs::read(&snapshot.pending_path)?;
+            let old = std::fs::read(&snapshot.path).ok();
+            let is_image = snapshot.path.extension().is_some_and(|ext| ext == "png");
+            println!();
+            println!("Snapshot: {} ({})", snapshot.name, if old.is_some() { "changed" } else { "new" });
+            match (old, is_image) {
+                (Some(old), true) => {
+                    print!("{}", image_diff(&old, &new).unwrap_or_default());
+                    println!("old: {}\nnew: {}", snapshot.path.display(), snapshot.pending_path.display());
+                }
+                (None, true) => println!("new: {}", snapshot.pending_path.display()),
+                (Some(old), false) => {
+                    print!("{}", line_diff(&String::from_utf8_lossy(&old), &String::from_utf8_lossy(&new)))
+                }
+                (None, false) => print!("{}", String::from_utf8_lossy(&new)),
+            }
+            match prompt("[a]ccept, [r]eject, [s]kip? ")?.as_str() {
+                "a" | "accept" => true,
+                "r" | "reject" => false,
+                _ => continue,
+            }
+        };
 
-            let result = backend.capture()?;
-            let output_path = session.capture_path("capture");
-            std::fs::write(&output_path, &result.image_data)?;
+        if accept {
+            accept_snapshot(snapshot)?;
+            accepted += 1;
+        } else {
+            reject_snapshot(snapshot)?;
+            rejected += 1;
+        }
+    }
 
-            println!("Captured CLI screenshot: {}", output_path.display());
-            println!("  Size: {}x{} (terminal: {}x{})", result.width, result.height, cols, rows);
+    println!();
+    println!("Accepted: {}  Rejected: {}  Skipped: {}", accepted, rejected, pending.len() - accepted - rejected);
+    Ok(())
+}
 
-            // Keep session alive if needed (prevent Drop cleanup)
-            if keep || output.is_some() {
-                std::mem::forget(session);
+/// `suite`: run every scenario in a suite file and write a report with an issue heatmap
+fn suite_command(args: SuiteArgs) -> Result<(), Box<dyn Error>> {
+    let SuiteArgs {
+        file,
+        analyze,
+        vlm_endpoint,
+        vlm_model,
+        vlm_samples,
+        prompt_preamble,
+        no_prompt_preamble,
+        prompt_include_text,
+        analysis_mode,
+        vlm_image_max_size,
+        vlm_image_format,
+        vlm_image_quality,
+        no_vlm_cache,
+        vlm_log,
+        macros,
+        theme,
+        output,
+        json,
+    } = args;
+    let mut suite: Suite = serde_json::from_str(&std::fs::read_to_string(&file)?)
+        .map_err(|e| format!("{}: {}", file.display(), e))?;
+    for scenario in &mut suite.scenarios {
+        scenario.load_check_files(file.parent().unwrap_or(Path::new("")))?;
+    }
+    if let Some(path) = &macros {
+        let shared = load_macros(path)?;
+        for scenario in &mut suite.scenarios {
+            for (name, inputs) in &shared {
+                scenario.macros.entry(name.clone()).or_insert_with(|| inputs.clone());
             }
         }
+    }
+    let session = match &output {
+        Some(dir) => Session::in_dir(dir).keep(true),
+        None => Session::with_name("suite").keep(true),
+    };
+    session.init()?;
+
+    let vlm_config = with_prompt_options(
+        configure_vlm(&vlm_endpoint, &vlm_model, !no_vlm_cache, vlm_log.then_some(&session)).samples(vlm_samples),
+        prompt_preamble,
+        no_prompt_preamble,
+        analysis_mode,
+        prompt_include_text,
+    )
+    .image_prep(image_prep(vlm_image_max_size, vlm_image_format, vlm_image_quality));
+    let vlm = if analyze && matches!(check_health(&vlm_endpoint, 5), Ok(true)) {
+        Some(&vlm_config)
+    } else {
+        if analyze {
+            eprintln!("Warning: VLM endpoint not responding at {}; skipping checks", vlm_endpoint);
+        }
+        None
+    };
 
-        Some(Commands::Run {
-            binary,
-            args: binary_args,
-            inputs,
-            delay,
-            output,
-            keep,
-            analyze,
-            vlm_endpoint,
-            vlm_model,
-            prompt,
-            step_prompts,
-            json,
-            size,
-            multi_size,
-        }) => {
-            // Create session - if output specified, use that dir and keep by default
-            let binary_name = binary.file_stem()
-                .map(|s| s.to_string_lossy().to_string())
-                .unwrap_or_else(|| "run".to_string());
-
-            let session = if let Some(ref dir) = output {
-                Session::in_dir(dir).keep(keep || output.is_some())
-            } else {
-                Session::with_name(&format!("{}_run", binary_name)).keep(keep)
-            };
-            session.init()?;
+    let options = RunOptions::default().theme(Theme::resolve(&theme)?);
+    let report = run_suite(&suite, &options, &session.dir, vlm)?;
+    let report_path = session.dir.join("suite_report.json");
+    std::fs::write(&report_path, serde_json::to_string_pretty(&report)?)?;
 
-            // Parse inputs
-            let input_list: Vec<String> = inputs
-                .split(',')
-                .map(|s| s.trim().to_string())
-                .filter(|s| !s.is_empty())
-                .collect();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("Suite{}: {} passed, {} failed", suite.name.as_ref().map(|n| format!(" {}", n)).unwrap_or_default(), report.passed, report.failed);
+        for scenario in &report.scenarios {
+            println!("  [{}] {}", if scenario.passed { "PASS" } else { "FAIL" }, scenario.name);
+            if let Some(error) = &scenario.error {
+                println!("    Error: {}", error);
+            }
+            for step in &scenario.steps {
+                if step.expect_text_found == Some(false) {
+                    println!("    Step {}: expected text not found", step.step);
+                }
+                for result in step.expectations.iter().filter(|result| !result.pass) {
+                    println!("    Step {}: expected {}: {}", step.step, result.expectation, result.message);
+                }
+                if let Some(verdict) = step.verdict.as_ref().filter(|v| v.samples > 1) {
+                    println!(
+                        "    Step {}: {} by {:.0}% of {} samples",
+                        step.step,
+                        if verdict.pass { "passed" } else { "failed" },
+                        verdict.agreement * 100.0,
+                        verdict.samples
+                    );
+                }
+                for issue in step.verdict.iter().flat_map(|v| &v.issues) {
+                    println!("    Step {}: {} [{}] {}", step.step, issue.category, issue.area, issue.description);
+                }
+            }
+        }
+        println!();
+        println!("Issues: {}", report.heatmap.summary);
+        if report.vlm_retries > 0 {
+            println!("VLM retries: {}", report.vlm_retries);
+        }
+        println!("Report: {}", report_path.display());
+    }
+    if report.failed > 0 {
+        return Err(format!("{} scenario(s) failed", report.failed).into());
+    }
+    Ok(())
+}
 
-            // Parse step-specific prompts if provided
-            let step_prompt_map: std::collections::HashMap<usize, String> = step_prompts
-                .as_ref()
-                .and_then(|s| serde_json::from_str(s).ok())
-                .unwrap_or_default();
+/// `search`: find the captured states most similar to a description across all sessions
+fn search_command(args: SearchArgs) -> Result<(), Box<dyn Error>> {
+    let SearchArgs {
+        base,
+        query,
+        limit,
+        vlm_endpoint,
+        vlm_model,
+        embedding_endpoint,
+        json,
+    } = args;
+    let source = if is_local_endpoint(&vlm_endpoint) {
+        EmbeddingSource::Local
+    } else {
+        EmbeddingSource::Endpoint {
+            endpoint: embedding_endpoint.unwrap_or_else(|| embeddings_endpoint(&vlm_endpoint)),
+            vlm: Box::new(configure_vlm(&vlm_endpoint, &vlm_model, false, None)),
+        }
+    };
+    let hits = search_sessions(&base, &query, &source, limit)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&hits)?);
+    } else if hits.is_empty() {
+        println!("No captured states found under {}", base.display());
+    } else {
+        for hit in &hits {
+            println!(
+                "{:.3}  {}  {}",
+                hit.score,
+                hit.modified.as_deref().unwrap_or("-"),
+                hit.screenshot.display()
+            );
+        }
+    }
+    Ok(())
+}
 
-            // Determine terminal sizes to test
-            let sizes_to_test: Vec<TerminalSize> = if multi_size {
-                TerminalSize::all_presets()
-            } else {
-                let term_size = TerminalSize::from_str(&size)
-                    .ok_or_else(|| format!("Invalid terminal size '{}'. Use: compact, standard, large, xl, or WxH (e.g., 100x30)", size))?;
-                vec![term_size]
-            };
+/// `release-diff`: write a Markdown changelog of the screens that changed between two releases' captures
+fn release_diff_command(args: ReleaseDiffArgs) -> Result<(), Box<dyn Error>> {
+    let ReleaseDiffArgs {
+        old,
+        new,
+        output,
+        threshold,
+        analyze,
+        vlm_endpoint,
+        vlm_model,
+        json,
+    } = args;
+    let config = configure_vlm(&vlm_endpoint, &vlm_model, false, None);
+    let scenarios = diff_releases(&old, &new, threshold, |old_png, new_png| {
+        if !analyze {
+            return None;
+        }
+        match summarize_change(&config, old_png, new_png) {
+            Ok(summary) => Some(summary),
+            Err(e) => {
+                eprintln!("VLM summary failed: {}", e);
+                None
+            }
+        }
+    })?;
+    write_changelog(&scenarios, &output)?;
 
-            // Process each size
-            for term_size in &sizes_to_test {
-                let (cols, rows) = term_size.dimensions();
-                let size_output = if multi_size {
-                    session.size_subdir(cols, rows)
-                } else {
-                    session.dir.clone()
-                };
-                std::fs::create_dir_all(&size_output)?;
-
-            // Run with inputs and capture each state
-            let captures = run_with_inputs_sized(
-                binary.to_str().unwrap_or(""),
-                &binary_args,
-                &input_list,
-                delay,
-                *term_size,
+    if json {
+        println!("{}", serde_json::to_string_pretty(&scenarios)?);
+    }
+    let changed = scenarios.iter().filter(|scenario| !scenario.changes.is_empty()).count();
+    let screens: usize = scenarios.iter().map(|scenario| scenario.changes.len()).sum();
+    println!(
+        "Wrote {}: {} screens differ in {} of {} scenarios",
+        output.display(),
+        screens,
+        changed,
+        scenarios.len()
+    );
+    Ok(())
+}
+
+/// `diff` of two capture directories: compare every state, writing a summary and diff images
+fn diff_dirs_command(args: DiffArgs) -> Result<(), Box<dyn Error>> {
+    let DiffArgs {
+        old,
+        new,
+        output,
+        tolerance,
+        threshold,
+        json,
+    } = args;
+    let output = output.unwrap_or_else(|| PathBuf::from("capture-diff"));
+    let mut diff = diff_directories(&old, &new, tolerance)?;
+    for state in &mut diff.states {
+        if let Some(pixels) = state.pixels.as_ref().filter(|pixels| !pixels.is_identical()) {
+            let relative = PathBuf::from(format!("{}.diff.png", state.name));
+            let path = output.join(&relative);
+            std::fs::create_dir_all(path.parent().unwrap_or(&output))?;
+            let image = annotated_diff(
+                &std::fs::read(old.join(format!("{}.png", state.baseline_name.as_ref().unwrap_or(&state.name))))?,
+                &std::fs::read(new.join(format!("{}.png", state.name)))?,
+                pixels,
             )?;
+            std::fs::write(path, image)?;
+            state.image = Some(relative);
+        }
+    }
+    std::fs::create_dir_all(&output)?;
+    std::fs::write(output.join("diff_summary.json"), serde_json::to_string_pretty(&diff)?)?;
+    std::fs::write(output.join("diff_summary.md"), diff.to_markdown())?;
 
-            // Check VLM health before starting analysis (if analyze is requested)
-            let vlm_healthy = if analyze {
-                match check_health(&vlm_endpoint, 5) {
-                    Ok(true) => {
-                        if !json {
-                            eprintln!("VLM endpoint responding, starting analysis...");
-                        }
-                        true
-                    }
-                    Ok(false) | Err(_) => {
-                        eprintln!("Warning: VLM endpoint not responding at {}", vlm_endpoint);
-                        eprintln!("Skipping analysis. Screenshots will still be saved.");
-                        false
-                    }
-                }
-            } else {
-                false
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+    } else {
+        for state in diff.states.iter().filter(|state| state.status != StateDiffStatus::Unchanged) {
+            let details = match (&state.cells, &state.pixels) {
+                (Some(cells), _) => cells.summary(),
+                (None, Some(pixels)) => pixels.summary(),
+                (None, None) => String::new(),
             };
+            match &state.baseline_name {
+                Some(baseline) => println!("{:?} {} (baseline {}): {}", state.status, state.name, baseline, details),
+                None => println!("{:?} {}: {}", state.status, state.name, details),
+            }
+        }
+        println!(
+            "{} changed, {} added, {} removed, {} unchanged; summary in {}",
+            diff.count(StateDiffStatus::Changed),
+            diff.count(StateDiffStatus::Added),
+            diff.count(StateDiffStatus::Removed),
+            diff.count(StateDiffStatus::Unchanged),
+            output.join("diff_summary.md").display()
+        );
+    }
+    let failures = diff.failures(threshold);
+    if !failures.is_empty() {
+        return Err(format!("{} state(s) differ beyond the threshold of {:.2}%", failures.len(), threshold * 100.0).into());
+    }
+    Ok(())
+}
 
-            // Build result
-            let mut states: Vec<StateCapture> = Vec::new();
-
-            for capture in &captures {
-                // Save screenshot
-                let filename = if capture.step == 0 {
-                    "state_0_initial.png".to_string()
-                } else {
-                    let input_name = capture
-                        .input
-                        .as_ref()
-                        .map(|s| s.replace('+', "_").replace(' ', "_"))
-                        .unwrap_or_default();
-                    format!("state_{}_{}.png", capture.step, input_name)
-                };
-                let screenshot_path = size_output.join(&filename);
-                std::fs::write(&screenshot_path, &capture.image_data)?;
-
-                // Get VLM description if requested and VLM is healthy
-                let description = if vlm_healthy {
-                    // Check for step-specific prompt first, then custom prompt, then default
-                    let custom_prompt = step_prompt_map
-                        .get(&capture.step)
-                        .map(|s| s.as_str())
-                        .or(prompt.as_deref());
-
-                    let analysis_prompt = build_analysis_prompt(
-                        capture.step,
-                        capture.input.as_deref(),
-                        custom_prompt,
-                    );
+/// `diff` of two screenshots: compare them pixel by pixel, writing a heatmap
+fn diff_command(args: DiffArgs) -> Result<(), Box<dyn Error>> {
+    let DiffArgs {
+        old,
+        new,
+        output,
+        tolerance,
+        threshold,
+        json,
+    } = args;
+    let diff = diff_images(&std::fs::read(&old)?, &std::fs::read(&new)?, tolerance)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+    } else {
+        println!("{}", diff.summary());
+    }
+    if let Some(output) = output {
+        std::fs::write(&output, &diff.overlay)?;
+        println!("Heatmap: {}", output.display());
+    }
+    if diff.ratio > threshold {
+        return Err(format!("{:.2}% of pixels changed (threshold {:.2}%)", diff.ratio * 100.0, threshold * 100.0).into());
+    }
+    Ok(())
+}
+
+/// `assert`: check a saved capture against expectations, e.g. -e "contains Saved" -e "cursor-at 5,0"
+fn assert_command(args: AssertArgs) -> Result<(), Box<dyn Error>> {
+    let AssertArgs { capture, expectations, json } = args;
+    let report = check_all(&expectations, &Screen::load(&capture)?);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for result in &report.results {
+            println!("[{}] {}: {}", if result.pass { "PASS" } else { "FAIL" }, result.expectation, result.message);
+        }
+    }
+    let failed = report.results.iter().filter(|result| !result.pass).count();
+    if failed > 0 {
+        return Err(format!("{} of {} expectation(s) failed", failed, report.results.len()).into());
+    }
+    Ok(())
+}
 
-                    let vlm_config = VlmConfig::new(&vlm_endpoint).model(&vlm_model);
+/// `chat`: ask the VLM follow-up questions about a captured state, saving the conversation in the session
+fn chat_command(args: ChatArgs) -> Result<(), Box<dyn Error>> {
+    let ChatArgs { session, step, vlm_endpoint, vlm_model } = args;
+    use std::io::Write;
 
-                    match analyze_image(&vlm_config, &capture.image_data, &analysis_prompt) {
-                        Ok(desc) => Some(desc),
-                        Err(e) => {
-                            eprintln!("Warning: VLM analysis failed for step {}: {}", capture.step, e);
-                            None
-                        }
-                    }
-                } else {
-                    None
-                };
+    let mut chat = ChatSession::open(&session, step)?;
+    let config = configure_vlm(&vlm_endpoint, &vlm_model, false, None);
+    println!("Chatting about {} (empty line or `exit` to quit)", chat.screenshot.display());
+    for message in &chat.messages {
+        match message.role {
+            ChatRole::User => println!("\n> {}", message.content),
+            ChatRole::Assistant => println!("{}", message.content),
+        }
+    }
 
-                states.push(StateCapture {
-                    step: capture.step,
-                    input: capture.input.clone(),
-                    screenshot_path: screenshot_path.clone(),
-                    description,
-                });
+    loop {
+        print!("\n> ");
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line)? == 0 {
+            break;
+        }
+        let question = line.trim();
+        if question.is_empty() || question == "exit" || question == "quit" {
+            break;
+        }
+        // Print the answer as it streams in
+        let mut shown = 0;
+        let answer = chat.ask(&config, question, |progress| {
+            if let VlmProgress::Receiving(content) = progress {
+                print!("{}", &content[shown..]);
+                let _ = std::io::stdout().flush();
+                shown = content.len();
             }
+        });
+        match answer {
+            Ok(answer) => println!("{}", &answer[shown.min(answer.len())..]),
+            Err(e) => eprintln!("VLM request failed: {}", e),
+        }
+    }
+    println!("Conversation saved to {}", chat.transcript_path.display());
+    Ok(())
+}
 
-            let result = RunResult {
-                success: true,
-                error: None,
-                states,
-            };
+/// `schema`: print the JSON Schema for scenario (or suite) files
+fn schema_command(args: SchemaArgs) -> Result<(), Box<dyn Error>> {
+    let SchemaArgs { suite } = args;
+    let schema = if suite { suite_json_schema() } else { scenario_json_schema() };
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
 
-            if json {
-                println!("{}", serde_json::to_string_pretty(&result)?);
-            } else {
-                if multi_size {
-                    println!("Run completed at {}x{}: {} states captured", cols, rows, result.states.len());
-                } else {
-                    println!("Run completed: {} states captured", result.states.len());
+/// `serve`: serve a protocol over stdio for other tools to drive cli-vision
+fn serve_command(args: ServeArgs) -> Result<(), Box<dyn Error>> {
+    let ServeArgs { editor, theme } = args;
+    if !editor {
+        return Err("serve needs a protocol; use --editor".into());
+    }
+    let options = RunOptions::default().theme(Theme::resolve(&theme)?);
+    EditorServer::new(std::io::stdout())
+        .options(options)
+        .serve(&mut std::io::stdin().lock())?;
+    Ok(())
+}
+
+/// Print the list of commands when none is given
+fn print_commands() {
+    println!("CLI Vision - Terminal UI testing with vision model analysis");
+    println!();
+    println!("Usage: cli-vision <COMMAND>");
+    println!();
+    println!("Commands:");
+    println!("  cli   Capture a CLI application screenshot using PTY emulation");
+    println!("  run   Run a TUI app with inputs, capture & analyze state changes");
+    println!("  record  Drive an app from this terminal and save the keystrokes as a scenario");
+    println!("  soak  Loop inputs for a fixed time, checking for crashes, leaks and drift");
+    println!("  fuzz  Send a seeded random key stream, keeping screenshots of crashes");
+    println!("  mock  Create a mock framebuffer screenshot for testing");
+    println!("  fixtures   Download pinned fixture binaries");
+    println!("  snapshots  Review golden text snapshots");
+    println!("  suite      Run a suite of scenarios and report an issue heatmap");
+    println!("  release-diff  Write a Markdown changelog of UI changes between releases");
+    println!("  chat       Ask follow-up questions about a captured state");
+    println!("  search     Find states similar to a description across sessions");
+    println!("  schema     Print the JSON Schema for scenario files");
+    println!("  serve      Serve the editor JSON-RPC protocol over stdio");
+    println!();
+    println!("Run with --help for more information.");
+}
+
+/// The VLM config for `--vlm-endpoint` and `--vlm-model`.
+///
+/// Answers are cached if `cache` is set (no `--no-vlm-cache`), and requests
+/// are logged to the transcript of `log_to` (`--vlm-log`).
+fn configure_vlm(endpoint: &str, model: &str, cache: bool, log_to: Option<&Session>) -> VlmConfig {
+    let config = VlmConfig::new(endpoint).model(model);
+    let config = if cache { config.cache(VlmCache::from_env()) } else { config };
+    match log_to {
+        Some(session) => config.transcript(VlmTranscript::new(session.vlm_log_path())),
+        None => config,
+    }
+}
+
+/// Apply the `--prompt-preamble` / `--no-prompt-preamble`, `--analysis-mode` and `--prompt-include-text` options
+fn with_prompt_options(
+    config: VlmConfig,
+    preamble: Option<String>,
+    no_preamble: bool,
+    mode: AnalysisMode,
+    include_text: bool,
+) -> VlmConfig {
+    let config = match preamble {
+        _ if no_preamble => config.no_preamble(),
+        Some(template) => config.preamble(template),
+        None => config,
+    };
+    // Without the text, hybrid analysis sends the image alone
+    config.analysis_mode(match mode {
+        AnalysisMode::Hybrid if !include_text => AnalysisMode::Image,
+        mode => mode,
+    })
+}
+
+/// How screenshots are prepared for VLM requests, from `--vlm-image-*`
+fn image_prep(max_size: Option<u32>, encoding: ImageEncoding, quality: u8) -> ImagePrep {
+    let prep = ImagePrep::original().encoding(encoding).quality(quality);
+    match max_size {
+        Some(pixels) => prep.max_size(pixels),
+        None => prep,
+    }
+}
+
+
+/// Everything a `run` applies to each terminal size and locale
+#[derive(Clone, Copy)]
+struct RunSettings<'a> {
+    session: &'a Session,
+    run_options: &'a RunOptions,
+    replay_plan: Option<&'a [ReplayStep]>,
+    binary: &'a Path,
+    binary_args: &'a [String],
+    input_list: &'a [String],
+    expanded: &'a [ExpandedInput],
+    delay: u64,
+    multi_size: bool,
+    text_output: bool,
+    json: bool,
+    emit_frames: Option<FrameFormat>,
+    save_text: bool,
+    analyze: bool,
+    annotate_findings: bool,
+    compare_baseline: Option<&'a Path>,
+    vlm_endpoint: &'a str,
+    vlm_model: &'a str,
+    vlm_config: &'a VlmConfig,
+    vlm_context: usize,
+    prompt: Option<&'a str>,
+    step_prompt_map: &'a BTreeMap<usize, String>,
+    prices: &'a PriceTable,
+    golden: Option<&'a Golden>,
+    check_links: bool,
+    spell_checker: Option<&'a SpellChecker>,
+    banned_scanner: &'a BannedContentScanner,
+    layout_check: bool,
+    color_vision: Option<&'a [ColorDeficiency]>,
+    export_gif: Option<&'a Path>,
+    export_video: Option<&'a Path>,
+    video_fps: u32,
+}
+
+/// What one terminal size and locale of a `run` found
+struct ConfigurationOutcome {
+    /// States with banned content on screen
+    banned_states: usize,
+    /// The first wait that timed out
+    wait_timeout: Option<String>,
+    /// States that differ from their goldens
+    golden_mismatches: usize,
+    /// Screen text of each state, for the localization report
+    texts: Vec<String>,
+}
+
+/// Run the application at one terminal size and locale, saving and reporting its states
+fn run_one_configuration(
+    settings: &RunSettings,
+    term_size: TerminalSize,
+    locale: Option<&str>,
+) -> Result<ConfigurationOutcome, Box<dyn Error>> {
+    let RunSettings {
+        session,
+        run_options,
+        replay_plan,
+        binary,
+        binary_args,
+        input_list,
+        expanded,
+        delay,
+        multi_size,
+        text_output,
+        json,
+        emit_frames,
+        save_text,
+        analyze,
+        annotate_findings,
+        compare_baseline,
+        vlm_endpoint,
+        vlm_model,
+        vlm_config,
+        vlm_context,
+        prompt,
+        step_prompt_map,
+        prices,
+        golden,
+        check_links,
+        spell_checker,
+        banned_scanner,
+        layout_check,
+        color_vision,
+        export_gif,
+        export_video,
+        video_fps,
+    } = *settings;
+    let (cols, rows) = term_size.dimensions();
+    let mut size_output = if multi_size {
+        session.size_subdir(cols, rows)
+    } else {
+        session.dir.clone()
+    };
+    if let Some(locale) = locale {
+        size_output = size_output.join(format!("locale_{}", locale));
+    }
+    std::fs::create_dir_all(&size_output)?;
+
+    let cast = CastRecorder::new();
+    let locale_options = match locale {
+        Some(locale) => run_options.clone().locale(locale),
+        None => run_options.clone(),
+    };
+    let locale_options = locale_options.record_cast(cast.clone());
+
+    // Run with inputs (or replay the cast) and capture each state
+    let captures = match replay_plan {
+        Some(plan) => replay_cast(binary.to_str().unwrap_or(""), binary_args, plan, term_size, &locale_options)?,
+        None => run_with_options(
+            binary.to_str().unwrap_or(""),
+            binary_args,
+            input_list,
+            delay,
+            term_size,
+            &locale_options,
+        )?,
+    };
+
+    // Check VLM health before starting analysis (if analyze or a baseline comparison is requested)
+    let vlm_healthy = if analyze || compare_baseline.is_some() {
+        match check_health(vlm_endpoint, 5) {
+            Ok(true) => {
+                if text_output {
+                    eprintln!("VLM endpoint responding, starting analysis...");
                 }
-                for state in &result.states {
-                    let input_str = state
-                        .input
-                        .as_ref()
-                        .map(|s| format!(" (input: {})", s))
-                        .unwrap_or_default();
-                    println!(
-                        "  Step {}{}: {}",
-                        state.step,
-                        input_str,
-                        state.screenshot_path.display()
-                    );
-                    if let Some(desc) = &state.description {
-                        // Print first 200 chars of description
-                        let preview: String = desc.chars().take(200).collect();
-                        println!("    Description: {}...", preview);
-                    }
+                true
+            }
+            Ok(false) | Err(_) => {
+                eprintln!("Warning: VLM endpoint not responding at {}", vlm_endpoint);
+                eprintln!("Skipping analysis. Screenshots will still be saved.");
+                false
+            }
+        }
+    } else {
+        false
+    };
+
+    // Which server answers, and whether it has the model; servers without a model list only get a note
+    let vlm_server = if vlm_healthy && !is_local_endpoint(vlm_endpoint) {
+        match check_model(&configure_vlm(vlm_endpoint, vlm_model, false, None)) {
+            Ok(info) => {
+                if text_output {
+                    eprintln!("VLM server: {}", info);
                 }
+                if !info.model_found {
+                    eprintln!("Warning: the VLM server does not list model '{}' (listed: {})", vlm_model, info.models.join(", "));
+                }
+                Some(info)
+            }
+            Err(e) => {
+                if text_output {
+                    let error = e.to_string();
+                    eprintln!("Note: could not list the VLM server's models: {}", error.lines().next().unwrap_or_default());
+                }
+                None
             }
-            } // end for term_size loop
+        }
+    } else {
+        None
+    };
 
-            // Print session location
-            if !json {
-                println!("\nSession: {}", session.dir.display());
+    // Build result
+    let mut states: Vec<StateCapture> = Vec::new();
+    let mut conversation = VlmConversation::new(vlm_context);
+    let mut vlm_retries = 0;
+    let mut golden_mismatches = 0;
+    let state_usage = std::cell::Cell::new(None::<VlmUsage>);
+    // Count retries and tokens, and let the user know why a step is taking longer
+    let mut on_progress = |progress: VlmProgress| match progress {
+        VlmProgress::Retrying { attempt, delay, error } => {
+            vlm_retries += 1;
+            eprintln!("Warning: VLM request failed (attempt {}): {}; retrying in {:.1}s", attempt, error, delay.as_secs_f64());
+        }
+        VlmProgress::Usage(usage) => {
+            let mut total = state_usage.get().unwrap_or_default();
+            total += usage;
+            state_usage.set(Some(total));
+        }
+        _ => {}
+    };
+
+    for capture in &captures {
+        // Save screenshot
+        let filename = if capture.step == 0 {
+            "state_0_initial.png".to_string()
+        } else {
+            let input_name = capture
+                .input
+                .as_ref()
+                .map(|s| s.replace(['+', ' ', ':'], "_"))
+                .unwrap_or_default();
+            format!("state_{}_{}.png", capture.step, input_name)
+        };
+        let screenshot_path = size_output.join(&filename);
+        std::fs::write(&screenshot_path, &capture.image_data)?;
+        let text_path = if save_text {
+            let path = screenshot_path.with_extension("txt");
+            std::fs::write(&path, &capture.text)?;
+            Some(path)
+        } else {
+            None
+        };
+        let ansi_path = match &capture.ansi {
+            Some(ansi) => {
+                let path = screenshot_path.with_extension("ansi");
+                std::fs::write(&path, ansi)?;
+                Some(path)
+            }
+            None => None,
+        };
+        let html_path = match &capture.html {
+            Some(html) => {
+                let path = screenshot_path.with_extension("html");
+                let title = match &capture.input {
+                    Some(input) => format!("State {}: {}", capture.step, input),
+                    None => "State 0: initial".to_string(),
+                };
+                std::fs::write(&path, html_page(html, &title))?;
+                Some(path)
+            }
+            None => None,
+        };
+        let grid_path = match &capture.grid {
+            Some(grid) => {
+                let path = screenshot_path.with_extension("grid.json");
+                std::fs::write(&path, serde_json::to_string(grid)?)?;
+                Some(path)
             }
+            None => None,
+        };
 
-            // Keep session alive if needed (prevent Drop cleanup)
-            if keep || output.is_some() {
-                std::mem::forget(session);
+        // Goldens mirror the output layout: <size>/<locale>/state_N_*
+        if let Some(golden) = golden {
+            let relative = screenshot_path.strip_prefix(&session.dir).unwrap_or(&screenshot_path);
+            let name = relative.with_extension("").to_string_lossy().replace('\\', "/");
+            match golden.check(&name, capture)? {
+                GoldenOutcome::Matched => {}
+                GoldenOutcome::Recorded(_) if text_output => println!("  Recorded golden {}", name),
+                GoldenOutcome::Updated(_) if text_output => println!("  Updated golden {}", name),
+                GoldenOutcome::Recorded(_) | GoldenOutcome::Updated(_) => {}
+                GoldenOutcome::Missing(_) => unreachable!("run records missing goldens"),
+                GoldenOutcome::Mismatch(diff) => {
+                    golden_mismatches += 1;
+                    eprintln!("Golden {} does not match:\n{}", name, diff);
+                }
             }
         }
 
-        Some(Commands::Mock {
-            width,
-            height,
-            output,
-            color,
-        }) => {
-            let color_bytes = parse_hex_color(&color)?;
-            let mut fb = MockFramebuffer::with_color(width, height, color_bytes);
+        let link_errors: Vec<String> = if check_links {
+            capture
+                .hyperlinks
+                .iter()
+                .filter_map(|link| {
+                    validate_hyperlink(link, true).map(|e| format!("{}: {}", link.uri, e))
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // Blink phases go in a subdirectory so they are not mistaken for states
+        let blink_frame_paths = match &capture.blink_frames {
+            Some(frames) => {
+                let blink_dir = size_output.join("blink");
+                std::fs::create_dir_all(&blink_dir)?;
+                let stem = filename.trim_end_matches(".png");
+                let phase_a = blink_dir.join(format!("{}_a.png", stem));
+                let phase_b = blink_dir.join(format!("{}_b.png", stem));
+                std::fs::write(&phase_a, &frames.phase_a)?;
+                std::fs::write(&phase_b, &frames.phase_b)?;
+                Some([phase_a, phase_b])
+            }
+            None => None,
+        };
+
+        // Filtered screenshots also go in a subdirectory, one per deficiency
+        let color_vision_issues = match color_vision {
+            Some(deficiencies) => {
+                let image = image::load_from_memory(&capture.image_data)?.to_rgb8();
+                let vision_dir = size_output.join("colorvision");
+                std::fs::create_dir_all(&vision_dir)?;
+                let stem = filename.trim_end_matches(".png");
+                for deficiency in deficiencies {
+                    deficiency
+                        .simulate_image(&image)
+                        .save(vision_dir.join(format!("{}_{}.png", stem, deficiency)))?;
+                }
+                check_color_vision(&image, &capture.text, deficiencies)
+            }
+            None => Vec::new(),
+        };
+
+            let context = PromptContext {
+            step: capture.step,
+            input: capture.input.clone(),
+            prior_inputs: input_list[..capture.step.saturating_sub(1)].to_vec(),
+            terminal_size: Some([cols, rows]),
+            binary: binary.file_name().map(|n| n.to_string_lossy().to_string()),
+            scenario: None,
+            screen_text: Some(locale_options.crop_text(capture.step, &capture.text)),
+        };
+
+        // Get VLM description if requested and VLM is healthy
+        let mut findings = Vec::new();
+        let description = if analyze && vlm_healthy {
+            // Check for step-specific prompt first, then custom prompt, then default
+            let custom_prompt = step_prompt_map
+                .get(&capture.step)
+                .map(|s| s.as_str())
+                .or(prompt);
 
-            // Draw some sample content
-            fb.draw_text(10, 10, "Mock Framebuffer", [255, 255, 255], color_bytes);
-            fb.draw_rect(10, 30, 100, 50, [128, 128, 128]);
+            let analysis_prompt = build_analysis_prompt(
+                capture.step,
+                capture.input.as_deref(),
+                custom_prompt,
+            );
+            let analysis_prompt = vlm_config.contextual_prompt(&analysis_prompt, &context);
+
+            let answer = if annotate_findings {
+                analyze_image_grounded(vlm_config, &capture.image_data, &analysis_prompt).map(|analysis| {
+                    findings = analysis.findings;
+                    analysis.summary
+                })
+            } else {
+                conversation.analyze(vlm_config, &capture.image_data, &analysis_prompt, &mut on_progress)
+            };
+            match answer {
+                Ok(desc) => Some(desc),
+                Err(e) => {
+                    eprintln!("Warning: VLM analysis failed for step {}: {}", capture.step, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        // The annotated copy sits beside the screenshot, like the state's other files
+        let findings_path = if findings.is_empty() {
+            None
+        } else {
+            let path = screenshot_path.with_extension("findings.png");
+            std::fs::write(&path, vlm_grounding::annotate_findings(&capture.image_data, &findings)?)?;
+            Some(path)
+        };
+
+        // The baseline state sits at the same path relative to the baseline run's directory
+        let baseline_comparison = match compare_baseline {
+            Some(baseline_dir) if vlm_healthy => {
+                let relative = screenshot_path.strip_prefix(&session.dir).unwrap_or(&screenshot_path);
+                let baseline_path = baseline_dir.join(relative);
+                match std::fs::read(&baseline_path) {
+                    Ok(baseline) => {
+                        let prompt = build_comparison_prompt(capture.step, capture.input.as_deref());
+                        let prompt = vlm_config.contextual_prompt(&prompt, &context);
+                        match compare_images_with_progress(vlm_config, &baseline, &capture.image_data, &prompt, &mut on_progress) {
+                            Ok(answer) => Some(answer),
+                            Err(e) => {
+                                eprintln!("Warning: VLM comparison failed for step {}: {}", capture.step, e);
+                                None
+                            }
+                        }
+                    }
+                    Err(_) => {
+                        eprintln!("Warning: no baseline for step {} at {}", capture.step, baseline_path.display());
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
 
-            let result = fb.capture()?;
-            std::fs::write(&output, &result.image_data)?;
+        states.push(StateCapture {
+            step: capture.step,
+            input: capture.input.clone(),
+            expanded_from: capture.step.checked_sub(1).and_then(|idx| expanded[idx].source.clone()),
+            screenshot_path: screenshot_path.clone(),
+            html_path,
+            text_path,
+            ansi_path,
+            grid_path,
+            description,
+            findings,
+            findings_path,
+            baseline_comparison,
+            vlm_usage: state_usage.take(),
+            bell_count: capture.bell_count,
+            visual_bell_count: capture.visual_bell_count,
+            cursor: capture.cursor,
+            cursor_changes: capture.cursor_changes.clone(),
+            blink_frame_paths,
+            hyperlinks: capture.hyperlinks.clone(),
+            link_errors,
+            misspellings: spell_checker
+                .as_ref()
+                .map(|checker| checker.check_text(&capture.text))
+                .unwrap_or_default(),
+            banned_content: banned_scanner.scan(&capture.text),
+            layout_issues: if layout_check { check_layout(&capture.text) } else { Vec::new() },
+            color_vision_issues,
+            unrenderable_codepoints: capture.unrenderable.iter().copied().map(codepoint_label).collect(),
+            wait: capture.wait.clone(),
+        });
+    }
 
-            println!("Created mock screenshot: {}", output.display());
-            println!("  Size: {}x{}", result.width, result.height);
+    let size_banned = states.iter().filter(|s| !s.banned_content.is_empty()).count();
+    let timeout = states
+        .iter()
+        .find_map(|s| s.wait.as_ref().filter(|w| w.timed_out).map(|w| format!("step {}: {}", s.step, w)));
+    let outcome = ConfigurationOutcome {
+        banned_states: size_banned,
+        wait_timeout: timeout.clone(),
+        golden_mismatches,
+        texts: captures.iter().map(|c| c.text.clone()).collect(),
+    };
+    let cast_path = match cast.cast() {
+        Some(recording) => {
+            let path = size_output.join("run.cast");
+            std::fs::write(&path, recording.to_v2())?;
+            Some(path)
+        }
+        None => None,
+    };
+    // One animation per size and locale, named after the requested file
+    let animation_path = |path: &Path| match path.file_name() {
+        Some(name) if multi_size || locale.is_some() => size_output.join(name),
+        _ => path.to_path_buf(),
+    };
+    let gif_path = match export_gif {
+        Some(path) => {
+            let path = animation_path(path);
+            std::fs::write(&path, to_gif(&captures, delay)?)?;
+            Some(path)
+        }
+        None => None,
+    };
+    let video_path = match export_video {
+        Some(path) => {
+            let path = animation_path(path);
+            encode_video(&captures, delay, video_fps, &path)?;
+            Some(path)
         }
+        None => None,
+    };
+    let vlm_usage = states.iter().filter_map(|s| s.vlm_usage).reduce(|mut total, usage| {
+        total += usage;
+        total
+    });
+    let result = RunResult {
+        success: size_banned == 0 && timeout.is_none(),
+        error: timeout.or_else(|| (size_banned > 0).then(|| format!("Banned content visible in {} state(s)", size_banned))),
+        states,
+        cast_path,
+        gif_path,
+        video_path,
+        vlm_retries,
+        vlm_usage,
+        vlm_cost: vlm_usage.and_then(|usage| prices.cost(vlm_model, &usage)),
+        vlm_server,
+    };
 
-        None => {
-            println!("CLI Vision - Terminal UI testing with vision model analysis");
-            println!();
-            println!("Usage: cli-vision <COMMAND>");
-            println!();
-            println!("Commands:");
-            println!("  cli   Capture a CLI application screenshot using PTY emulation");
-            println!("  run   Run a TUI app with inputs, capture & analyze state changes");
-            println!("  mock  Create a mock framebuffer screenshot for testing");
-            println!();
-            println!("Run with --help for more information.");
+    if let Some(format) = emit_frames {
+        let mut stdout = std::io::stdout().lock();
+        for capture in &captures {
+            write_png_frame(&mut stdout, format, &capture.image_data)?;
+        }
+        std::io::Write::flush(&mut stdout)?;
+    } else if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        let locale_str = locale.map(|l| format!(" [{}]", l)).unwrap_or_default();
+        if multi_size {
+            println!("Run completed at {}x{}{}: {} states captured", cols, rows, locale_str, result.states.len());
+        } else {
+            println!("Run completed{}: {} states captured", locale_str, result.states.len());
+        }
+        if let Some(path) = &result.cast_path {
+            println!("  Recording: {}", path.display());
+        }
+        for path in result.gif_path.iter().chain(&result.video_path) {
+            println!("  Animation: {}", path.display());
+        }
+        if result.vlm_retries > 0 {
+            println!("  VLM retries: {}", result.vlm_retries);
+        }
+        if let Some(usage) = &result.vlm_usage {
+            let cost = result.vlm_cost.map(|cost| format!(" (${:.4})", cost)).unwrap_or_default();
+            println!("  VLM tokens: {} prompt, {} completion{}", usage.prompt_tokens, usage.completion_tokens, cost);
+        }
+        for state in &result.states {
+            let input_str = match (&state.input, &state.expanded_from) {
+                (Some(input), Some(source)) => format!(" (input: {}, from {})", input, source),
+                (Some(input), None) => format!(" (input: {})", input),
+                (None, _) => String::new(),
+            };
+            println!(
+                "  Step {}{}: {}",
+                state.step,
+                input_str,
+                state.screenshot_path.display()
+            );
+            if state.bell_count > 0 || state.visual_bell_count > 0 {
+                println!(
+                    "    Bells: {} audible, {} visual",
+                    state.bell_count, state.visual_bell_count
+                );
+            }
+            if let Some(wait) = &state.wait {
+                println!("    Wait: {}", wait);
+            }
+            if !state.unrenderable_codepoints.is_empty() {
+                println!("    Unrenderable: {}", state.unrenderable_codepoints.join(", "));
+            }
+            if !state.cursor_changes.is_empty() {
+                println!(
+                    "    Cursor: {} ({:?}{})",
+                    if state.cursor.visible { "shown" } else { "hidden" },
+                    state.cursor.shape,
+                    if state.cursor.blinking { ", blinking" } else { "" }
+                );
+            }
+            for m in &state.misspellings {
+                match &m.suggestion {
+                    Some(preferred) => println!("    Terminology: '{}' at {},{} (use '{}')", m.word, m.row, m.col, preferred),
+                    None => println!("    Misspelled: '{}' at {},{}", m.word, m.row, m.col),
+                }
+            }
+            for m in &state.banned_content {
+                println!("    Banned ({}): '{}' at {},{}", m.rule, m.text, m.row, m.col);
+            }
+            for issue in &state.layout_issues {
+                println!("    Layout ({:?}) at {},{}: '{}'", issue.kind, issue.row, issue.col, issue.context);
+            }
+            for issue in &state.color_vision_issues {
+                let [a, b] = issue.colors.map(|[r, g, b]| format!("#{:02x}{:02x}{:02x}", r, g, b));
+                match issue.kind {
+                    ColorVisionIssueKind::LowContrast => println!(
+                        "    Color vision ({}): contrast {:.1} -> {:.1} for {} on {} at {},{}: '{}'",
+                        issue.deficiency, issue.original, issue.simulated, a, b, issue.row, issue.col, issue.context
+                    ),
+                    ColorVisionIssueKind::Indistinguishable => println!(
+                        "    Color vision ({}): {} and {} look alike (delta E {:.0} -> {:.0}) at {},{}: '{}'",
+                        issue.deficiency, a, b, issue.original, issue.simulated, issue.row, issue.col, issue.context
+                    ),
+                }
+            }
+            for error in &state.link_errors {
+                println!("    Invalid link: {}", error);
+            }
+            if let Some(desc) = &state.description {
+                // Print first 200 chars of description
+                let preview: String = desc.chars().take(200).collect();
+                println!("    Description: {}...", preview);
+            }
+            for (number, finding) in (1..).zip(&state.findings) {
+                let location = &finding.location;
+                println!("    Finding {}: {} ({:?} {},{} {}x{})", number, finding.description, location.unit, location.x, location.y, location.width, location.height);
+            }
+            if let Some(path) = &state.findings_path {
+                println!("    Findings: {}", path.display());
+            }
+            if let Some(comparison) = &state.baseline_comparison {
+                let preview: String = comparison.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(200).collect();
+                println!("    Baseline comparison: {}", preview);
+            }
         }
     }
+    Ok(outcome)
+}
 
-    Ok(())
+/// Print a prompt and read one trimmed, lowercased line from stdin
+fn prompt(message: &str) -> Result<String, Box<dyn Error>> {
+    use std::io::Write;
+    print!("{}", message);
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    Ok(line.trim().to_lowercase())
+}
+
+/// Build resource limits from the optional `--cpu-limit`, `--memory-limit` and `--fd-limit` values
+fn resource_limits(cpu_seconds: Option<u64>, memory_mb: Option<u64>, open_files: Option<u64>) -> ResourceLimits {
+    let mut limits = ResourceLimits::new();
+    if let Some(seconds) = cpu_seconds {
+        limits = limits.cpu_seconds(seconds);
+    }
+    if let Some(mb) = memory_mb {
+        limits = limits.memory_mb(mb);
+    }
+    if let Some(count) = open_files {
+        limits = limits.open_files(count);
+    }
+    limits
+}
+
+/// Build the glyph fallback chain from `--glyph-fallback` and `--glyph-font`
+fn glyph_fallback_chain(spec: Option<&str>, font: Option<&Path>) -> Result<Vec<GlyphFallback>, Box<dyn Error>> {
+    let font = match font {
+        Some(path) => Some(Arc::new(HexFont::from_file(path)?)),
+        None => None,
+    };
+    let spec = spec.unwrap_or(if font.is_some() { "font" } else { "none" });
+    Ok(parse_fallback_chain(spec, font)?)
+}
+
+/// Parse a duration as seconds or with an `s`, `m` or `h` suffix
+fn parse_duration(arg: &str) -> Result<Duration, String> {
+    let arg = arg.trim();
+    let (number, unit) = match arg.char_indices().last() {
+        Some((idx, unit @ ('s' | 'm' | 'h'))) => (&arg[..idx], unit),
+        _ => (arg, 's'),
+    };
+    let value: f64 = number.parse().map_err(|_| format!("invalid duration '{}'", arg))?;
+    let scale = match unit {
+        'h' => 3600.0,
+        'm' => 60.0,
+        _ => 1.0,
+    };
+    Duration::try_from_secs_f64(value * scale).map_err(|_| format!("invalid duration '{}'", arg))
+}
+
+/// Parse a `KEY=VALUE` environment variable argument
+fn parse_env_var(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{}'", arg)),
+    }
 }
 
 fn parse_hex_color(hex: &str) -> Result<[u8; 3], Box<dyn Error>> {
@@ -0,0 +1,155 @@
+//! Banned-content scanning (debug strings, panics, PII) over screen text.
+//!
+//! A [`BannedContentScanner`] holds named regex rules. Any match in a
+//! captured state is reported with its coordinates so runs can be failed
+//! when content that must never ship is visible.
+//!
+//! # Example
+//!
+//! ```rust
+//! use cli_vision::qa::BannedContentScanner;
+//!
+//! let scanner = BannedContentScanner::with_defaults()
+//!     .pattern("internal-host", r"\b[a-z0-9-]+\.corp\.example\b")
+//!     .unwrap();
+//! let found = scanner.scan("Connected to db1.corp.example\nTODO: remove");
+//! assert_eq!(found.len(), 2);
+//! ```
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Rules applied by [`BannedContentScanner::with_defaults`]
+pub const DEFAULT_BANNED_RULES: &[(&str, &str)] = &[
+    ("todo", r"\b(TODO|FIXME|XXX)\b"),
+    ("panic", r"panicked at|stack backtrace:"),
+    ("debug", r"\[DEBUG\]|\bdbg!"),
+    ("email", r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}"),
+];
+
+/// A banned-content match on screen
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BannedMatch {
+    /// Name of the rule that matched
+    pub rule: String,
+    /// The matched text
+    pub text: String,
+    /// Row of the match (0-based)
+    pub row: u32,
+    /// Column of the first matched character (0-based)
+    pub col: u32,
+}
+
+/// A named banned-content rule
+#[derive(Debug, Clone)]
+struct BannedRule {
+    name: String,
+    pattern: Regex,
+}
+
+/// Scanner applying banned-content rules to screen text
+#[derive(Debug, Clone, Default)]
+pub struct BannedContentScanner {
+    rules: Vec<BannedRule>,
+}
+
+impl BannedContentScanner {
+    /// Create a scanner with no rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a scanner with the built-in rules (TODO markers, panics, debug output, emails)
+    pub fn with_defaults() -> Self {
+        let mut scanner = Self::new();
+        for (name, pattern) in DEFAULT_BANNED_RULES {
+            scanner = scanner
+                .pattern(name, pattern)
+                .expect("built-in banned-content patterns are valid");
+        }
+        scanner
+    }
+
+    /// Add a named regex rule
+    pub fn pattern(mut self, name: &str, pattern: &str) -> Result<Self, regex::Error> {
+        self.rules.push(BannedRule {
+            name: name.to_string(),
+            pattern: Regex::new(pattern)?,
+        });
+        Ok(self)
+    }
+
+    /// Add a rule matching literal text
+    pub fn literal(self, name: &str, text: &str) -> Self {
+        self.pattern(name, &regex::escape(text))
+            .expect("escaped literal is a valid pattern")
+    }
+
+    /// Load rules from a file.
+    ///
+    /// One rule per line as `name: regex` (or just `regex`, named after
+    /// itself); `#` at the start of a line begins a comment.
+    pub fn rules_file(mut self, path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, pattern) = match line.split_once(": ") {
+                Some((name, pattern)) => (name.trim(), pattern.trim()),
+                None => (line, line),
+            };
+            self = self
+                .pattern(name, pattern)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("rule '{}': {}", name, e)))?;
+        }
+        Ok(self)
+    }
+
+    /// Whether the scanner has any rules
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Scan screen text, one line per row
+    pub fn scan(&self, text: &str) -> Vec<BannedMatch> {
+        let mut found = Vec::new();
+        for (row, line) in text.lines().enumerate() {
+            for rule in &self.rules {
+                for m in rule.pattern.find_iter(line) {
+                    found.push(BannedMatch {
+                        rule: rule.name.clone(),
+                        text: m.as_str().to_string(),
+                        row: row as u32,
+                        col: line[..m.start()].chars().count() as u32,
+                    });
+                }
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_rules_report_coordinates() {
+        let scanner = BannedContentScanner::with_defaults();
+        let found = scanner.scan("│ ok │\n│ thread 'main' panicked at src/main.rs:3 │\n│ mail: dev@example.com");
+        let summary: Vec<(&str, u32, u32)> = found.iter().map(|m| (m.rule.as_str(), m.row, m.col)).collect();
+        assert_eq!(summary, vec![("panic", 1, 16), ("email", 2, 8)]);
+    }
+
+    #[test]
+    fn test_literal_rules_are_escaped() {
+        let scanner = BannedContentScanner::new().literal("host", "build-01.internal");
+        assert_eq!(scanner.scan("build-01.internal").len(), 1);
+        assert!(scanner.scan("build-01xinternal").is_empty());
+    }
+}
@@ -0,0 +1,383 @@
+//! Color-blindness simulation and the contrast it costs.
+//!
+//! Captures are passed through protanopia, deuteranopia and tritanopia
+//! filters (Machado, Oliveira and Fernandes 2009, full severity). The
+//! foreground and background of each cell are read back from the rendered
+//! image, and the checks report what only fails under simulation: text
+//! whose contrast drops below [`MIN_CONTRAST_RATIO`], and pairs of colors
+//! that told widgets apart but collapse to nearly the same color.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use image::{Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+
+use crate::snapshot::{CELL_HEIGHT, CELL_WIDTH};
+
+/// WCAG AA contrast ratio for normal text
+pub const MIN_CONTRAST_RATIO: f64 = 4.5;
+
+/// CIE76 distance above which two colors are clearly different
+pub const DISTINCT_DELTA_E: f64 = 20.0;
+
+/// CIE76 distance below which two colors are hard to tell apart
+pub const INDISTINCT_DELTA_E: f64 = 10.0;
+
+/// Pixels of a second color a cell needs before it counts as having a foreground
+const MIN_GLYPH_PIXELS: u32 = 4;
+
+/// A simulated color vision deficiency
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorDeficiency {
+    /// No red cones
+    Protanopia,
+    /// No green cones
+    Deuteranopia,
+    /// No blue cones
+    Tritanopia,
+}
+
+impl ColorDeficiency {
+    /// Every supported deficiency
+    pub const ALL: [ColorDeficiency; 3] = [Self::Protanopia, Self::Deuteranopia, Self::Tritanopia];
+
+    /// Lower-case name, as accepted by [`FromStr`]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Protanopia => "protanopia",
+            Self::Deuteranopia => "deuteranopia",
+            Self::Tritanopia => "tritanopia",
+        }
+    }
+
+    /// Parse a comma-separated list of names, or `all`
+    pub fn parse_list(list: &str) -> Result<Vec<Self>, String> {
+        if list.trim().eq_ignore_ascii_case("all") {
+            return Ok(Self::ALL.to_vec());
+        }
+        list.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::parse).collect()
+    }
+
+    /// Linear-RGB transform for this deficiency
+    fn matrix(self) -> [[f64; 3]; 3] {
+        match self {
+            Self::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Self::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Self::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+
+    /// How a color appears with this deficiency
+    pub fn simulate(self, color: [u8; 3]) -> [u8; 3] {
+        let linear = color.map(to_linear);
+        self.matrix().map(|row| from_linear(row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2]))
+    }
+
+    /// Pass a whole image through the filter
+    pub fn simulate_image(self, image: &RgbImage) -> RgbImage {
+        let mut cache: HashMap<[u8; 3], [u8; 3]> = HashMap::new();
+        let mut out = image.clone();
+        for pixel in out.pixels_mut() {
+            pixel.0 = *cache.entry(pixel.0).or_insert_with(|| self.simulate(pixel.0));
+        }
+        out
+    }
+}
+
+impl fmt::Display for ColorDeficiency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ColorDeficiency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "protanopia" | "protan" => Ok(Self::Protanopia),
+            "deuteranopia" | "deutan" => Ok(Self::Deuteranopia),
+            "tritanopia" | "tritan" => Ok(Self::Tritanopia),
+            other => Err(format!(
+                "unknown color deficiency '{}' (expected: protanopia, deuteranopia, tritanopia, all)",
+                other
+            )),
+        }
+    }
+}
+
+/// Kind of problem that appears under simulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorVisionIssueKind {
+    /// Text contrast drops below [`MIN_CONTRAST_RATIO`]
+    LowContrast,
+    /// Two colors used to tell elements apart become nearly the same
+    Indistinguishable,
+}
+
+/// Something that stops being readable or distinguishable with a color vision deficiency
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColorVisionIssue {
+    /// What kind of problem was found
+    pub kind: ColorVisionIssueKind,
+    /// Deficiency under which it appears
+    pub deficiency: ColorDeficiency,
+    /// Row of the region (0-based)
+    pub row: u32,
+    /// Column of the region (0-based)
+    pub col: u32,
+    /// Foreground and background for low contrast, or the two colors that merge
+    pub colors: [[u8; 3]; 2],
+    /// Contrast ratio or delta E with normal vision
+    pub original: f64,
+    /// Contrast ratio or delta E under simulation
+    pub simulated: f64,
+    /// Text of the region
+    pub context: String,
+}
+
+/// Background and, if a glyph is drawn, foreground of one cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CellColors {
+    bg: [u8; 3],
+    fg: Option<[u8; 3]>,
+}
+
+/// Check a rendered capture under each deficiency. `text` is the screen text
+/// (one line per row) used for context.
+pub fn check_color_vision(image: &RgbImage, text: &str, deficiencies: &[ColorDeficiency]) -> Vec<ColorVisionIssue> {
+    let cells = read_cells(image);
+    let lines: Vec<Vec<char>> = text.lines().map(|line| line.chars().collect()).collect();
+    let context = |row: usize, start: usize, end: usize| -> String {
+        lines.get(row).map(|line| line[start.min(line.len())..(end + 1).min(line.len())].iter().collect()).unwrap_or_default()
+    };
+
+    let mut issues = Vec::new();
+    for &deficiency in deficiencies {
+        let mut cache: HashMap<[u8; 3], [u8; 3]> = HashMap::new();
+        let mut sim = |color: [u8; 3]| *cache.entry(color).or_insert_with(|| deficiency.simulate(color));
+
+        // Text that loses contrast, grouped into runs of the same colors
+        for (row, line) in cells.iter().enumerate() {
+            // The issue being built and the last column it covers
+            let mut run: Option<(ColorVisionIssue, usize)> = None;
+            let mut close = |run: &mut Option<(ColorVisionIssue, usize)>| {
+                if let Some((mut issue, end)) = run.take() {
+                    issue.context = context(row, issue.col as usize, end);
+                    issues.push(issue);
+                }
+            };
+            for (col, cell) in line.iter().enumerate() {
+                let lost = cell.fg.and_then(|fg| {
+                    let original = contrast_ratio(fg, cell.bg);
+                    let simulated = contrast_ratio(sim(fg), sim(cell.bg));
+                    (original >= MIN_CONTRAST_RATIO && simulated < MIN_CONTRAST_RATIO)
+                        .then_some((fg, original, simulated))
+                });
+                match (lost, run.as_mut()) {
+                    (Some((fg, ..)), Some((issue, end))) if issue.colors == [fg, cell.bg] => *end = col,
+                    // Blank cells on the same background continue a run
+                    (None, Some((issue, _))) if cell.fg.is_none() && issue.colors[1] == cell.bg => {}
+                    (Some((fg, original, simulated)), _) => {
+                        close(&mut run);
+                        let issue = ColorVisionIssue {
+                            kind: ColorVisionIssueKind::LowContrast,
+                            deficiency,
+                            row: row as u32,
+                            col: col as u32,
+                            colors: [fg, cell.bg],
+                            original,
+                            simulated,
+                            context: String::new(),
+                        };
+                        run = Some((issue, col));
+                    }
+                    (None, _) => close(&mut run),
+                }
+            }
+            close(&mut run);
+        }
+
+        // Colors that tell elements apart: foregrounds sharing a background,
+        // and the backgrounds of neighbouring cells
+        let mut pairs: Vec<([u8; 3], [u8; 3], usize, usize)> = Vec::new();
+        let mut add_pair = |a: [u8; 3], b: [u8; 3], row: usize, col: usize| {
+            let (a, b) = if a <= b { (a, b) } else { (b, a) };
+            if a != b && !pairs.iter().any(|p| p.0 == a && p.1 == b) {
+                pairs.push((a, b, row, col));
+            }
+        };
+        let mut first_fg: Vec<([u8; 3], [u8; 3], usize, usize)> = Vec::new();
+        for (row, line) in cells.iter().enumerate() {
+            for (col, cell) in line.iter().enumerate() {
+                if let Some(fg) = cell.fg
+                    && !first_fg.iter().any(|f| f.0 == cell.bg && f.1 == fg)
+                {
+                    for other in first_fg.iter().filter(|f| f.0 == cell.bg) {
+                        add_pair(other.1, fg, row, col);
+                    }
+                    first_fg.push((cell.bg, fg, row, col));
+                }
+                if col > 0 {
+                    add_pair(line[col - 1].bg, cell.bg, row, col);
+                }
+                if row > 0 {
+                    add_pair(cells[row - 1][col].bg, cell.bg, row, col);
+                }
+            }
+        }
+        for (a, b, row, col) in pairs {
+            let original = delta_e(a, b);
+            let simulated = delta_e(sim(a), sim(b));
+            if original >= DISTINCT_DELTA_E && simulated < INDISTINCT_DELTA_E {
+                issues.push(ColorVisionIssue {
+                    kind: ColorVisionIssueKind::Indistinguishable,
+                    deficiency,
+                    row: row as u32,
+                    col: col as u32,
+                    colors: [a, b],
+                    original,
+                    simulated,
+                    context: context(row, col, col + 15),
+                });
+            }
+        }
+    }
+    issues
+}
+
+/// Read the background (most common color) and foreground (next most common)
+/// of every cell in a rendered screen
+fn read_cells(image: &RgbImage) -> Vec<Vec<CellColors>> {
+    let (cols, rows) = (image.width() / CELL_WIDTH, image.height() / CELL_HEIGHT);
+    (0..rows)
+        .map(|row| {
+            (0..cols)
+                .map(|col| {
+                    let mut counts: Vec<([u8; 3], u32)> = Vec::new();
+                    for y in row * CELL_HEIGHT..(row + 1) * CELL_HEIGHT {
+                        for x in col * CELL_WIDTH..(col + 1) * CELL_WIDTH {
+                            let Rgb(color) = *image.get_pixel(x, y);
+                            match counts.iter_mut().find(|(c, _)| *c == color) {
+                                Some((_, count)) => *count += 1,
+                                None => counts.push((color, 1)),
+                            }
+                        }
+                    }
+                    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+                    CellColors {
+                        bg: counts[0].0,
+                        fg: counts.get(1).filter(|(_, count)| *count >= MIN_GLYPH_PIXELS).map(|(color, _)| *color),
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn to_linear(channel: u8) -> f64 {
+    let c = f64::from(channel) / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn from_linear(value: f64) -> u8 {
+    let v = value.clamp(0.0, 1.0);
+    let c = if v <= 0.0031308 { v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 };
+    (c * 255.0).round() as u8
+}
+
+fn luminance(color: [u8; 3]) -> f64 {
+    let [r, g, b] = color.map(to_linear);
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// WCAG contrast ratio, from 1 (identical) to 21 (black on white)
+pub fn contrast_ratio(a: [u8; 3], b: [u8; 3]) -> f64 {
+    let (la, lb) = (luminance(a), luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+/// CIE76 color difference in CIELAB (D65)
+pub fn delta_e(a: [u8; 3], b: [u8; 3]) -> f64 {
+    let (la, lb) = (lab(a), lab(b));
+    ((la[0] - lb[0]).powi(2) + (la[1] - lb[1]).powi(2) + (la[2] - lb[2]).powi(2)).sqrt()
+}
+
+fn lab(color: [u8; 3]) -> [f64; 3] {
+    let [r, g, b] = color.map(to_linear);
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f64| if t > 216.0 / 24389.0 { t.cbrt() } else { (24389.0 / 27.0 * t + 16.0) / 116.0 };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A screen of `cells` (fg, bg) pairs in one row, glyphs drawn as a block in each cell
+    fn screen(cells: &[([u8; 3], [u8; 3])]) -> RgbImage {
+        let mut image = RgbImage::new(CELL_WIDTH * cells.len() as u32, CELL_HEIGHT);
+        for (col, (fg, bg)) in cells.iter().enumerate() {
+            for y in 0..CELL_HEIGHT {
+                for x in 0..CELL_WIDTH {
+                    let glyph = (4..12).contains(&x) && (8..24).contains(&y);
+                    image.put_pixel(col as u32 * CELL_WIDTH + x, y, Rgb(if glyph { *fg } else { *bg }));
+                }
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn test_simulation_keeps_grays_and_merges_red_green() {
+        for deficiency in ColorDeficiency::ALL {
+            let gray = deficiency.simulate([128, 128, 128]);
+            assert!(gray.iter().all(|&c| c.abs_diff(128) <= 2), "{deficiency}: {gray:?}");
+        }
+        let (red, green) = ([220, 50, 50], [60, 160, 60]);
+        assert!(delta_e(red, green) > DISTINCT_DELTA_E);
+        let sim = ColorDeficiency::Deuteranopia;
+        assert!(delta_e(sim.simulate(red), sim.simulate(green)) < INDISTINCT_DELTA_E);
+        assert_eq!(ColorDeficiency::parse_list("all").unwrap().len(), 3);
+        assert_eq!(ColorDeficiency::parse_list("deutan, tritanopia").unwrap(), vec![ColorDeficiency::Deuteranopia, ColorDeficiency::Tritanopia]);
+        assert!(ColorDeficiency::parse_list("achromat").is_err());
+    }
+
+    #[test]
+    fn test_reports_merged_status_colors_and_lost_contrast() {
+        let black = [0, 0, 0];
+        let (red, green) = ([220, 50, 50], [60, 160, 60]);
+        let image = screen(&[(red, black), (green, black), ([255, 255, 255], black)]);
+        let issues = check_color_vision(&image, "RGW\n", &[ColorDeficiency::Deuteranopia]);
+        let merged: Vec<_> = issues.iter().filter(|i| i.kind == ColorVisionIssueKind::Indistinguishable).collect();
+        assert_eq!(merged.len(), 1);
+        assert_eq!((merged[0].row, merged[0].col, merged[0].colors), (0, 1, [green, red]));
+        assert_eq!(merged[0].context, "GW");
+
+        // Pure red on black passes AA, but protanopes see it far darker
+        let image = screen(&[([255, 0, 0], black), ([255, 0, 0], black), (black, black), ([255, 0, 0], black)]);
+        let lost = check_color_vision(&image, "ab c\n", &[ColorDeficiency::Protanopia]);
+        assert_eq!(lost.len(), 1);
+        assert_eq!((lost[0].kind, lost[0].col, lost[0].context.as_str()), (ColorVisionIssueKind::LowContrast, 0, "ab c"));
+        assert!(lost[0].original >= MIN_CONTRAST_RATIO && lost[0].simulated < MIN_CONTRAST_RATIO);
+    }
+}
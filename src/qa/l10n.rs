@@ -0,0 +1,126 @@
+//! Localization completeness checks across a locale matrix.
+//!
+//! Screens captured under a localized locale are compared with the same
+//! screens under the base locale. Text segments that are identical in both
+//! are likely untranslated; the share of segments that changed gives a
+//! per-locale coverage percentage.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// A text segment that is identical to the base locale
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UntranslatedString {
+    /// Step of the state the segment was found in
+    pub step: usize,
+    /// The segment text
+    pub text: String,
+    /// Row of the segment (0-based)
+    pub row: u32,
+    /// Column of the first character (0-based)
+    pub col: u32,
+}
+
+/// Translation coverage of one locale relative to the base locale
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocaleCoverage {
+    /// Locale name (e.g. `de_DE.UTF-8`)
+    pub locale: String,
+    /// Number of text segments checked
+    pub total_strings: usize,
+    /// Segments identical to the base locale
+    pub untranslated: Vec<UntranslatedString>,
+    /// Percentage of segments that differ from the base locale
+    pub coverage_percent: f64,
+}
+
+/// Split screen text into (row, col, segment) triples.
+///
+/// Segments are separated by runs of two or more spaces or by box-drawing
+/// characters, which is how TUI layouts separate labels. Segments without
+/// a word of at least two letters (numbers, single hotkeys) are skipped.
+pub fn text_segments(text: &str) -> Vec<(u32, u32, String)> {
+    let mut segments = Vec::new();
+    for (row, line) in text.lines().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let mut col = 0;
+        while col < chars.len() {
+            if chars[col] == ' ' || is_border(chars[col]) {
+                col += 1;
+                continue;
+            }
+            let start = col;
+            while col < chars.len()
+                && !is_border(chars[col])
+                && !(chars[col] == ' ' && chars.get(col + 1) == Some(&' '))
+            {
+                col += 1;
+            }
+            let segment: String = chars[start..col].iter().collect::<String>().trim().to_string();
+            if segment.split(|c: char| !c.is_alphabetic()).any(|word| word.chars().count() >= 2) {
+                segments.push((row as u32, start as u32, segment));
+            }
+        }
+    }
+    segments
+}
+
+/// Box-drawing and block element characters
+fn is_border(ch: char) -> bool {
+    ('\u{2500}'..='\u{259F}').contains(&ch)
+}
+
+/// Compute coverage of a localized run against the base locale run.
+///
+/// States are paired by index; a localized segment counts as untranslated
+/// if the same segment appears anywhere in the paired base state.
+pub fn locale_coverage(locale: &str, base_states: &[String], localized_states: &[String]) -> LocaleCoverage {
+    let mut total_strings = 0;
+    let mut untranslated = Vec::new();
+
+    for (step, (base, localized)) in base_states.iter().zip(localized_states).enumerate() {
+        let base_segments: HashSet<String> = text_segments(base).into_iter().map(|(_, _, s)| s).collect();
+        for (row, col, text) in text_segments(localized) {
+            total_strings += 1;
+            if base_segments.contains(&text) {
+                untranslated.push(UntranslatedString { step, text, row, col });
+            }
+        }
+    }
+
+    let coverage_percent = if total_strings == 0 {
+        100.0
+    } else {
+        (total_strings - untranslated.len()) as f64 * 100.0 / total_strings as f64
+    };
+    LocaleCoverage {
+        locale: locale.to_string(),
+        total_strings,
+        untranslated,
+        coverage_percent,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments_split_on_gaps_and_borders() {
+        let segments = text_segments("│ Open file   Quit │ 42\n  [q] x");
+        let texts: Vec<&str> = segments.iter().map(|(_, _, s)| s.as_str()).collect();
+        assert_eq!(texts, vec!["Open file", "Quit"]);
+        assert_eq!((segments[1].0, segments[1].1), (0, 14));
+    }
+
+    #[test]
+    fn test_coverage_flags_identical_strings() {
+        let base = vec!["Open file   Settings   Quit".to_string()];
+        let german = vec!["Datei öffnen   Settings   Beenden".to_string()];
+        let coverage = locale_coverage("de_DE.UTF-8", &base, &german);
+        assert_eq!(coverage.total_strings, 3);
+        assert_eq!(coverage.untranslated.len(), 1);
+        assert_eq!(coverage.untranslated[0].text, "Settings");
+        assert!((coverage.coverage_percent - 66.666).abs() < 0.01);
+    }
+}
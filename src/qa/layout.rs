@@ -0,0 +1,157 @@
+//! Text density and overflow heuristics for layout QA.
+//!
+//! Resize bugs usually leave textual traces: words cut off at the right
+//! margin, labels squeezed into ellipses, or boxes drawn over each other.
+//! These heuristics flag such regions from the screen text alone.
+
+use serde::{Deserialize, Serialize};
+
+/// Number of ellipses on one screen above which each one is reported
+pub const ELLIPSIS_LIMIT: usize = 3;
+
+/// Kind of suspected layout problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutIssueKind {
+    /// A word runs into the last column and is likely cut off
+    Truncated,
+    /// One of many ellipses on the screen (labels squeezed for space)
+    Ellipsis,
+    /// Box borders cross without a junction character (overlapping boxes)
+    BorderOverlap,
+}
+
+/// A suspect region on screen
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayoutIssue {
+    /// What kind of problem was detected
+    pub kind: LayoutIssueKind,
+    /// Row of the region (0-based)
+    pub row: u32,
+    /// Column of the region (0-based)
+    pub col: u32,
+    /// Text around the region
+    pub context: String,
+}
+
+/// Run all layout heuristics over screen text (one full-width line per row)
+pub fn check_layout(text: &str) -> Vec<LayoutIssue> {
+    let grid: Vec<Vec<char>> = text.lines().map(|line| line.chars().collect()).collect();
+    let mut issues = Vec::new();
+    issues.extend(find_truncations(&grid));
+    issues.extend(find_ellipses(&grid));
+    issues.extend(find_border_overlaps(&grid));
+    issues
+}
+
+/// Lines whose last column holds the middle of a word
+fn find_truncations(grid: &[Vec<char>]) -> Vec<LayoutIssue> {
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    let mut issues = Vec::new();
+    for (row, line) in grid.iter().enumerate() {
+        if width < 2 || line.len() != width {
+            continue;
+        }
+        if line[width - 1].is_alphanumeric() && line[width - 2].is_alphanumeric() {
+            let word_start = line.iter().rposition(|c| !c.is_alphanumeric()).map_or(0, |i| i + 1);
+            issues.push(LayoutIssue {
+                kind: LayoutIssueKind::Truncated,
+                row: row as u32,
+                col: (width - 1) as u32,
+                context: line[word_start..].iter().collect(),
+            });
+        }
+    }
+    issues
+}
+
+/// Ellipses (`…` or `...`), reported only when there are more than [`ELLIPSIS_LIMIT`]
+fn find_ellipses(grid: &[Vec<char>]) -> Vec<LayoutIssue> {
+    let mut found = Vec::new();
+    for (row, line) in grid.iter().enumerate() {
+        let mut col = 0;
+        while col < line.len() {
+            let is_dots = line[col..].starts_with(&['.', '.', '.']);
+            if line[col] == '…' || is_dots {
+                let start = line[..col].iter().rposition(|c| c.is_whitespace()).map_or(0, |i| i + 1);
+                let end = col + if is_dots { 3 } else { 1 };
+                found.push(LayoutIssue {
+                    kind: LayoutIssueKind::Ellipsis,
+                    row: row as u32,
+                    col: col as u32,
+                    context: line[start..end].iter().collect(),
+                });
+                col = end;
+            } else {
+                col += 1;
+            }
+        }
+    }
+    if found.len() > ELLIPSIS_LIMIT { found } else { Vec::new() }
+}
+
+fn is_horizontal(ch: char) -> bool {
+    matches!(ch, '─' | '━' | '═')
+}
+
+fn is_vertical(ch: char) -> bool {
+    matches!(ch, '│' | '┃' | '║')
+}
+
+/// Straight border lines crossing each other without a junction (`┼`)
+fn find_border_overlaps(grid: &[Vec<char>]) -> Vec<LayoutIssue> {
+    let at = |row: usize, col: usize| grid.get(row).and_then(|line| line.get(col)).copied().unwrap_or(' ');
+    let mut issues = Vec::new();
+    for (row, line) in grid.iter().enumerate() {
+        for (col, &ch) in line.iter().enumerate() {
+            let crossed = if is_vertical(ch) {
+                col > 0 && is_horizontal(at(row, col - 1)) && is_horizontal(at(row, col + 1))
+            } else if is_horizontal(ch) {
+                row > 0 && is_vertical(at(row - 1, col)) && is_vertical(at(row + 1, col))
+            } else {
+                false
+            };
+            if crossed {
+                issues.push(LayoutIssue {
+                    kind: LayoutIssueKind::BorderOverlap,
+                    row: row as u32,
+                    col: col as u32,
+                    context: ch.to_string(),
+                });
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_truncated_word_at_margin() {
+        let issues = check_layout("Status: connec\nOK            \nDone.        x");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, LayoutIssueKind::Truncated);
+        assert_eq!((issues[0].row, issues[0].col), (0, 13));
+        assert_eq!(issues[0].context, "connec");
+    }
+
+    #[test]
+    fn test_ellipses_reported_only_when_overused() {
+        assert!(check_layout("Fil… Edi… Vie…   ").is_empty());
+        let issues = check_layout("Fil… Edi… Vie… He...  ");
+        assert_eq!(issues.len(), 4);
+        assert!(issues.iter().all(|i| i.kind == LayoutIssueKind::Ellipsis));
+        assert_eq!(issues[3].context, "He...");
+    }
+
+    #[test]
+    fn test_detects_crossing_borders() {
+        let screen = "┌──│──┐\n│  │  │\n└──┴──┘";
+        let issues = check_layout(screen);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, LayoutIssueKind::BorderOverlap);
+        assert_eq!((issues[0].row, issues[0].col), (0, 3));
+    }
+}
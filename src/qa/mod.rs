@@ -0,0 +1,18 @@
+//! Text-based QA checks over captured screen text.
+//!
+//! These checks run on the emulator's text buffer rather than the rendered
+//! image, so they are cheap enough to run on every captured state without a
+//! vision model. The color vision checks read cell colors back from the
+//! rendered image instead.
+
+pub mod banned;
+pub mod color_vision;
+pub mod l10n;
+pub mod layout;
+pub mod spell;
+
+pub use banned::{BannedContentScanner, BannedMatch, DEFAULT_BANNED_RULES};
+pub use color_vision::{check_color_vision, ColorDeficiency, ColorVisionIssue, ColorVisionIssueKind};
+pub use l10n::{locale_coverage, LocaleCoverage, UntranslatedString};
+pub use layout::{check_layout, LayoutIssue, LayoutIssueKind};
+pub use spell::{Misspelling, SpellChecker, SYSTEM_DICTIONARY};
@@ -0,0 +1,229 @@
+//! Dictionary-based spell and terminology checking.
+//!
+//! A [`SpellChecker`] combines a dictionary (e.g. the system word list),
+//! custom wordlists of accepted words, and terminology rules mapping
+//! discouraged spellings to preferred ones (`Github -> GitHub`).
+//!
+//! # Example
+//!
+//! ```rust
+//! use cli_vision::qa::SpellChecker;
+//!
+//! let checker = SpellChecker::new()
+//!     .words(["open", "settings", "quit"])
+//!     .term("Github", "GitHub");
+//! let findings = checker.check_text("Open setings\nQuit Github");
+//! assert_eq!(findings.len(), 2);
+//! assert_eq!((findings[0].row, findings[0].col), (0, 5));
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Location of the system word list on most Unix systems
+pub const SYSTEM_DICTIONARY: &str = "/usr/share/dict/words";
+
+/// A word on screen that is not in the dictionary or violates a terminology rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Misspelling {
+    /// The word as it appears on screen
+    pub word: String,
+    /// Row of the word (0-based)
+    pub row: u32,
+    /// Column of the first character (0-based)
+    pub col: u32,
+    /// Preferred spelling, for terminology violations
+    pub suggestion: Option<String>,
+}
+
+/// Spell checker over screen text
+#[derive(Debug, Clone)]
+pub struct SpellChecker {
+    /// Accepted words (lowercase)
+    words: HashSet<String>,
+    /// Discouraged spelling (lowercase) -> preferred spelling
+    terms: HashMap<String, String>,
+    /// Preferred spellings from terminology rules (lowercase)
+    preferred: HashSet<String>,
+    /// Words shorter than this are ignored (default: 3)
+    pub min_word_len: usize,
+}
+
+impl Default for SpellChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpellChecker {
+    /// Create an empty checker; with no words loaded only terminology rules apply
+    pub fn new() -> Self {
+        Self {
+            words: HashSet::new(),
+            terms: HashMap::new(),
+            preferred: HashSet::new(),
+            min_word_len: 3,
+        }
+    }
+
+    /// Create a checker using the system dictionary
+    pub fn from_system_dictionary() -> io::Result<Self> {
+        Self::new().wordlist_file(SYSTEM_DICTIONARY)
+    }
+
+    /// Load a wordlist file (see [`SpellChecker::wordlist`] for the format)
+    pub fn wordlist_file(self, path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(self.wordlist(&contents))
+    }
+
+    /// Add words from wordlist text.
+    ///
+    /// One word per line; `#` starts a comment. Lines of the form
+    /// `wrong -> preferred` add terminology rules instead.
+    pub fn wordlist(mut self, contents: &str) -> Self {
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            match line.split_once("->") {
+                Some((wrong, preferred)) => self = self.term(wrong.trim(), preferred.trim()),
+                None => {
+                    self.words.insert(line.to_lowercase());
+                }
+            }
+        }
+        self
+    }
+
+    /// Accept additional words
+    pub fn words<I, S>(mut self, words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.words
+            .extend(words.into_iter().map(|w| w.as_ref().to_lowercase()));
+        self
+    }
+
+    /// Add a terminology rule: any casing of `wrong` other than `preferred` is reported
+    pub fn term(mut self, wrong: &str, preferred: &str) -> Self {
+        self.preferred.insert(preferred.to_lowercase());
+        self.terms.insert(wrong.to_lowercase(), preferred.to_string());
+        self
+    }
+
+    /// Check screen text, one line per row
+    pub fn check_text(&self, text: &str) -> Vec<Misspelling> {
+        let mut findings = Vec::new();
+        for (row, line) in text.lines().enumerate() {
+            for (col, word) in words_in_line(line) {
+                if let Some(suggestion) = self.check_word(word) {
+                    findings.push(Misspelling {
+                        word: word.to_string(),
+                        row: row as u32,
+                        col: col as u32,
+                        suggestion,
+                    });
+                }
+            }
+        }
+        findings
+    }
+
+    /// Returns `Some(suggestion)` if the word should be reported
+    fn check_word(&self, word: &str) -> Option<Option<String>> {
+        let lower = word.to_lowercase();
+        if let Some(preferred) = self.terms.get(&lower) {
+            return (word != preferred).then(|| Some(preferred.clone()));
+        }
+
+        let is_acronym = word.chars().all(|c| !c.is_alphabetic() || c.is_uppercase());
+        let is_camel_case = word.chars().skip(1).any(|c| c.is_uppercase()) && !is_acronym;
+        if self.words.is_empty()
+            || word.chars().count() < self.min_word_len
+            || is_acronym
+            || is_camel_case
+        {
+            return None;
+        }
+
+        let stem = lower.strip_suffix("'s").unwrap_or(&lower);
+        if self.words.contains(&lower) || self.words.contains(stem) || self.preferred.contains(&lower) {
+            None
+        } else {
+            Some(None)
+        }
+    }
+}
+
+/// Split a line into (column, word) pairs.
+///
+/// Tokens containing digits or underscores (identifiers, versions) are skipped.
+fn words_in_line(line: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start: Option<(usize, usize)> = None;
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+
+    for (col, &(byte, ch)) in chars.iter().enumerate() {
+        let is_token_char = ch.is_alphanumeric() || ch == '_' || ch == '\'';
+        match (is_token_char, start) {
+            (true, None) => start = Some((col, byte)),
+            (false, Some((start_col, start_byte))) => {
+                push_word(&mut words, start_col, &line[start_byte..byte]);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some((start_col, start_byte)) = start {
+        push_word(&mut words, start_col, &line[start_byte..]);
+    }
+    words
+}
+
+fn push_word<'a>(words: &mut Vec<(usize, &'a str)>, col: usize, token: &'a str) {
+    if token.chars().any(|c| c.is_ascii_digit() || c == '_') {
+        return;
+    }
+    let trimmed = token.trim_start_matches('\'');
+    let offset = token.len() - trimmed.len();
+    let trimmed = trimmed.trim_end_matches('\'');
+    if !trimmed.is_empty() {
+        words.push((col + offset, trimmed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_unknown_words_with_coordinates() {
+        let checker = SpellChecker::new().wordlist("save\nchanges\nuser\n");
+        let findings = checker.check_text("  Save chnages?\n\nuser's  SQL v2beta config_path");
+        assert_eq!(
+            findings,
+            vec![Misspelling {
+                word: "chnages".to_string(),
+                row: 0,
+                col: 7,
+                suggestion: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_terminology_rules_suggest_preferred_spelling() {
+        let checker = SpellChecker::new().wordlist("# terms\nGithub -> GitHub\n");
+        let findings = checker.check_text("Sign in with github or GitHub");
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].col, 13);
+        assert_eq!(findings[0].suggestion.as_deref(), Some("GitHub"));
+    }
+}
@@ -0,0 +1,495 @@
+//! Suite runs and their reports.
+//!
+//! [`run_suite`] runs every scenario of a [`Suite`], saving each state's
+//! screenshot, checking `expect_text` and `expect` entries and asking the VLM to verify steps
+//! with a `check`. Failed verdicts are collected as [`ReportedIssue`]s and
+//! aggregated into an [`IssueHeatmap`] by screen region, category and
+//! scenario tag, with a one-line summary such as "most issues in the
+//! footer area, mostly alignment" to show where fixes pay off first.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::expect::{Expectation, ExpectationResult, Screen};
+use crate::scenario::{Scenario, ScenarioStep, Suite};
+use crate::snapshot::{expanded_inputs, run_with_options, RunOptions, SnapshotError};
+use crate::vlm::{verify_image_in_context, PromptContext, VlmConfig, VlmVerdict};
+
+/// Horizontal band of the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Band {
+    /// Top rows (title bars, tabs, menus)
+    Header,
+    /// Everything between header and footer
+    Body,
+    /// Bottom rows (status lines, key hints)
+    Footer,
+}
+
+/// Vertical third of the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Left,
+    Center,
+    Right,
+}
+
+/// One cell of the 3x3 heatmap grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ScreenRegion {
+    pub band: Band,
+    pub side: Side,
+}
+
+impl ScreenRegion {
+    /// Read a region from a free-form area description such as "footer",
+    /// "bottom-right" or "left sidebar". Unrecognized areas are the body center.
+    pub fn parse(area: &str) -> Self {
+        let area = area.to_lowercase();
+        let has = |words: &[&str]| words.iter().any(|word| area.contains(word));
+        let band = if has(&["header", "top", "title", "menu", "tab"]) {
+            Band::Header
+        } else if has(&["footer", "bottom", "status", "hint"]) {
+            Band::Footer
+        } else {
+            Band::Body
+        };
+        let side = if has(&["left", "sidebar"]) {
+            Side::Left
+        } else if has(&["right"]) {
+            Side::Right
+        } else {
+            Side::Center
+        };
+        Self { band, side }
+    }
+
+    fn band_name(band: Band) -> &'static str {
+        match band {
+            Band::Header => "header",
+            Band::Body => "body",
+            Band::Footer => "footer",
+        }
+    }
+}
+
+impl fmt::Display for ScreenRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let band = Self::band_name(self.band);
+        match self.side {
+            Side::Left => write!(f, "{}-left", band),
+            Side::Center => f.write_str(band),
+            Side::Right => write!(f, "{}-right", band),
+        }
+    }
+}
+
+/// A failed VLM verdict, located on screen
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportedIssue {
+    /// Scenario name
+    pub scenario: String,
+    /// State the issue was seen in (0 = initial)
+    pub step: usize,
+    /// Tags of the scenario
+    pub tags: Vec<String>,
+    /// Where on screen
+    pub region: ScreenRegion,
+    /// Kind of problem ("unspecified" when the model gave none)
+    pub category: String,
+    /// What is wrong
+    pub description: String,
+}
+
+/// Issue counts by screen region, category and scenario tag
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssueHeatmap {
+    /// Number of issues
+    pub total: usize,
+    /// Counts per region: rows are header, body, footer; columns left, center, right
+    pub grid: [[usize; 3]; 3],
+    /// Counts per category
+    pub by_category: BTreeMap<String, usize>,
+    /// Counts per category for each scenario tag
+    pub by_tag: BTreeMap<String, BTreeMap<String, usize>>,
+    /// One-line summary of where issues concentrate
+    pub summary: String,
+}
+
+impl IssueHeatmap {
+    /// Aggregate issues
+    pub fn from_issues(issues: &[ReportedIssue]) -> Self {
+        let mut heatmap = Self {
+            total: issues.len(),
+            ..Self::default()
+        };
+        for issue in issues {
+            heatmap.grid[issue.region.band as usize][issue.region.side as usize] += 1;
+            *heatmap.by_category.entry(issue.category.clone()).or_default() += 1;
+            for tag in &issue.tags {
+                *heatmap.by_tag.entry(tag.clone()).or_default().entry(issue.category.clone()).or_default() += 1;
+            }
+        }
+        heatmap.summary = heatmap.summarize();
+        heatmap
+    }
+
+    /// Issues in one region
+    pub fn count(&self, region: ScreenRegion) -> usize {
+        self.grid[region.band as usize][region.side as usize]
+    }
+
+    fn summarize(&self) -> String {
+        if self.total == 0 {
+            return "no issues reported".to_string();
+        }
+        // Name a single region when it holds most of its band, otherwise the whole band
+        let bands = [Band::Header, Band::Body, Band::Footer];
+        let band_total = |band: Band| self.grid[band as usize].iter().sum::<usize>();
+        let band = bands.into_iter().rev().max_by_key(|&band| band_total(band)).unwrap_or(Band::Body);
+        let side = [Side::Left, Side::Center, Side::Right]
+            .into_iter()
+            .rev()
+            .max_by_key(|&side| self.grid[band as usize][side as usize])
+            .unwrap_or(Side::Center);
+        let region = ScreenRegion { band, side };
+        let area = if self.count(region) * 2 > band_total(band) && side != Side::Center {
+            region.to_string()
+        } else {
+            ScreenRegion::band_name(band).to_string()
+        };
+
+        let mut summary = format!(
+            "{} issue{}, most in the {} area ({})",
+            self.total,
+            if self.total == 1 { "" } else { "s" },
+            area,
+            band_total(band)
+        );
+        if let Some((category, count)) = most_common(&self.by_category) {
+            summary.push_str(&format!(", mostly {} ({})", category, count));
+        }
+        let tag_totals: BTreeMap<String, usize> =
+            self.by_tag.iter().map(|(tag, counts)| (tag.clone(), counts.values().sum())).collect();
+        if let Some((tag, count)) = most_common(&tag_totals) {
+            summary.push_str(&format!("; most affected tag: {} ({})", tag, count));
+        }
+        summary
+    }
+}
+
+/// Key with the highest count, the first in order on ties
+fn most_common(counts: &BTreeMap<String, usize>) -> Option<(&str, usize)> {
+    counts
+        .iter()
+        .rev()
+        .max_by_key(|(_, count)| **count)
+        .map(|(key, count)| (key.as_str(), *count))
+}
+
+/// One captured state of a scenario
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepReport {
+    /// State number (0 = initial)
+    pub step: usize,
+    /// Input that led to this state
+    pub input: Option<String>,
+    /// Repeat or macro token the input was expanded from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expanded_from: Option<String>,
+    /// Step name from the scenario
+    pub name: Option<String>,
+    /// Saved screenshot
+    pub screenshot_path: PathBuf,
+    /// Saved screen as HTML with selectable text
+    #[serde(default)]
+    pub html_path: Option<PathBuf>,
+    /// Whether the step's `expect_text` was on screen
+    pub expect_text_found: Option<bool>,
+    /// Results of the step's `expect` entries
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expectations: Vec<ExpectationResult>,
+    /// VLM verdict for the step's `check`
+    pub verdict: Option<VlmVerdict>,
+}
+
+/// Result of one scenario
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioReport {
+    /// Scenario name
+    pub name: String,
+    /// Scenario tags
+    pub tags: Vec<String>,
+    /// Whether the run succeeded and every expectation and check passed
+    pub passed: bool,
+    /// Why the run itself failed
+    pub error: Option<String>,
+    /// Captured states
+    pub steps: Vec<StepReport>,
+}
+
+/// Result of a suite run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuiteReport {
+    /// Suite name
+    pub name: Option<String>,
+    /// Scenarios that passed
+    pub passed: usize,
+    /// Scenarios that failed
+    pub failed: usize,
+    /// Per-scenario results
+    pub scenarios: Vec<ScenarioReport>,
+    /// Every failed VLM verdict
+    pub issues: Vec<ReportedIssue>,
+    /// Issues aggregated by region, category and tag
+    pub heatmap: IssueHeatmap,
+    /// VLM requests retried after transient failures
+    #[serde(default)]
+    pub vlm_retries: u32,
+}
+
+impl SuiteReport {
+    /// Build a report from scenario results, collecting failed verdicts into the heatmap
+    pub fn from_scenarios(name: Option<String>, scenarios: Vec<ScenarioReport>) -> Self {
+        let mut issues = Vec::new();
+        for scenario in &scenarios {
+            for step in &scenario.steps {
+                let Some(verdict) = step.verdict.as_ref().filter(|v| !v.pass) else { continue };
+                for issue in &verdict.issues {
+                    issues.push(ReportedIssue {
+                        scenario: scenario.name.clone(),
+                        step: step.step,
+                        tags: scenario.tags.clone(),
+                        region: ScreenRegion::parse(&issue.area),
+                        category: if issue.category.is_empty() { "unspecified".to_string() } else { issue.category.clone() },
+                        description: issue.description.clone(),
+                    });
+                }
+            }
+        }
+        let passed = scenarios.iter().filter(|s| s.passed).count();
+        let vlm_retries = scenarios.iter().flat_map(|s| &s.steps).filter_map(|s| s.verdict.as_ref()).map(|v| v.retries).sum();
+        Self {
+            name,
+            passed,
+            failed: scenarios.len() - passed,
+            vlm_retries,
+            heatmap: IssueHeatmap::from_issues(&issues),
+            issues,
+            scenarios,
+        }
+    }
+}
+
+/// Display name of a scenario: its name, or the binary's file name
+fn scenario_name(scenario: &Scenario, index: usize) -> String {
+    scenario.name.clone().unwrap_or_else(|| {
+        let binary = scenario.binary.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        format!("{} #{}", binary, index + 1)
+    })
+}
+
+/// Run every scenario in a suite, saving screenshots under `output_dir`.
+///
+/// Steps with a `check` are verified with the VLM when `vlm` is given and
+/// skipped otherwise.
+pub fn run_suite(suite: &Suite, options: &RunOptions, output_dir: &Path, vlm: Option<&VlmConfig>) -> std::io::Result<SuiteReport> {
+    let mut reports = Vec::new();
+    for (index, scenario) in suite.scenarios.iter().enumerate() {
+        let name = scenario_name(scenario, index);
+        let dir = output_dir.join(format!(
+            "{:02}_{}",
+            index + 1,
+            name.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect::<String>()
+        ));
+        std::fs::create_dir_all(&dir)?;
+
+        // Color and cursor expectations read the cell grid
+        let needs_grid = scenario.steps.iter().any(|step| !step.expect.is_empty());
+        let mut options = options.clone().layout(scenario.keyboard_layout().unwrap_or_default()).capture_html(true);
+        if needs_grid {
+            options = options.capture_grid(true);
+        }
+        options.env.extend(scenario.env_pairs());
+        let expanded = scenario.expanded_inputs();
+        options.step_crops.extend(scenario.crops(expanded.as_deref().unwrap_or_default()));
+        let inputs = expanded.as_deref().map(expanded_inputs).unwrap_or_default();
+        let captures = expanded.clone().map_err(SnapshotError::Capture).and_then(|_| {
+            run_with_options(
+                &scenario.binary.to_string_lossy(),
+                &scenario.args,
+                &inputs,
+                scenario.delay_ms,
+                scenario.terminal_size().unwrap_or_default(),
+                &options,
+            )
+        });
+        let expanded = expanded.unwrap_or_default();
+        let captures = match captures {
+            Ok(captures) => captures,
+            Err(e) => {
+                reports.push(ScenarioReport {
+                    name,
+                    tags: scenario.tags.clone(),
+                    passed: false,
+                    error: Some(e.to_string()),
+                    steps: Vec::new(),
+                });
+                continue;
+            }
+        };
+
+        let mut steps = Vec::new();
+        for capture in &captures {
+            let screenshot_path = dir.join(format!("state_{}.png", capture.step));
+            std::fs::write(&screenshot_path, &capture.image_data)?;
+            let html_path = dir.join(format!("state_{}.html", capture.step));
+            std::fs::write(&html_path, capture.html.as_deref().unwrap_or_default())?;
+            // State N is reached by expanded input N-1; a step's expectations apply to the
+            // state after the last input it expands to
+            let expansion = capture.step.checked_sub(1).and_then(|idx| expanded.get(idx));
+            let is_last = expanded.get(capture.step).is_none_or(|next| Some(next.origin) != expansion.map(|e| e.origin));
+            let step = expansion.filter(|_| is_last).and_then(|e| scenario.steps.get(e.origin));
+            let expect_text_found = step
+                .and_then(|s| s.expect_text.as_ref())
+                .map(|expected| capture.text.contains(expected.as_str()));
+            let mut screen = Screen::from_state(capture);
+            screen.regions = scenario.regions();
+            let expectations = step
+                .into_iter()
+                .flat_map(|s| &s.expect)
+                .map(|spec| match Expectation::parse(spec) {
+                    Ok(expectation) => expectation.check(&screen),
+                    Err(problem) => ExpectationResult {
+                        expectation: spec.clone(),
+                        pass: false,
+                        message: problem,
+                    },
+                })
+                .collect();
+            let verdict = match (step, vlm) {
+                (Some(step @ ScenarioStep { check: Some(check), .. }), Some(config)) => {
+                    let config = match step.samples {
+                        Some(samples) => config.clone().samples(samples),
+                        None => config.clone(),
+                    };
+                    let (cols, rows) = scenario.terminal_size().unwrap_or_default().dimensions();
+                    let context = PromptContext {
+                        step: capture.step,
+                        input: capture.input.clone(),
+                        prior_inputs: inputs[..capture.step - 1].to_vec(),
+                        terminal_size: Some([cols, rows]),
+                        binary: scenario.binary.file_name().map(|n| n.to_string_lossy().to_string()),
+                        scenario: scenario.description.clone().or_else(|| scenario.name.clone()),
+                        screen_text: Some(options.crop_text(capture.step, &capture.text)),
+                    };
+                    Some(verify_image_in_context(&config, &capture.image_data, check, &context).unwrap_or_else(|e| VlmVerdict {
+                        pass: false,
+                        issues: Vec::new(),
+                        response: format!("VLM request failed: {}", e),
+                        samples: 0,
+                        agreement: 0.0,
+                        retries: 0,
+                    }))
+                }
+                _ => None,
+            };
+            steps.push(StepReport {
+                step: capture.step,
+                input: capture.input.clone(),
+                expanded_from: expansion.and_then(|e| e.source.clone()),
+                name: step.and_then(|s| s.name.clone()),
+                screenshot_path,
+                html_path: Some(html_path),
+                expect_text_found,
+                expectations,
+                verdict,
+            });
+        }
+        let passed = steps
+            .iter()
+            .all(|s| s.expect_text_found != Some(false) && s.expectations.iter().all(|e| e.pass) && s.verdict.as_ref().is_none_or(|v| v.pass));
+        reports.push(ScenarioReport {
+            name,
+            tags: scenario.tags.clone(),
+            passed,
+            error: None,
+            steps,
+        });
+    }
+    Ok(SuiteReport::from_scenarios(suite.name.clone(), reports))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vlm::VlmIssue;
+
+    fn failed_step(step: usize, issues: &[(&str, &str)]) -> StepReport {
+        StepReport {
+            step,
+            input: None,
+            expanded_from: None,
+            name: None,
+            screenshot_path: PathBuf::new(),
+            html_path: None,
+            expect_text_found: None,
+            expectations: Vec::new(),
+            verdict: Some(VlmVerdict {
+                pass: false,
+                issues: issues
+                    .iter()
+                    .map(|(category, area)| VlmIssue {
+                        category: category.to_string(),
+                        area: area.to_string(),
+                        description: String::new(),
+                    })
+                    .collect(),
+                response: String::new(),
+                samples: 1,
+                agreement: 1.0,
+                retries: 0,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_regions_parse_from_free_text() {
+        assert_eq!(ScreenRegion::parse("footer").to_string(), "footer");
+        assert_eq!(ScreenRegion::parse("Bottom-Right corner").to_string(), "footer-right");
+        assert_eq!(ScreenRegion::parse("left sidebar").to_string(), "body-left");
+        assert_eq!(ScreenRegion::parse("tab bar").band, Band::Header);
+        assert_eq!(ScreenRegion::parse("").to_string(), "body");
+    }
+
+    #[test]
+    fn test_heatmap_aggregates_by_region_category_and_tag() {
+        let scenario = |name: &str, tags: &[&str], steps| ScenarioReport {
+            name: name.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            passed: false,
+            error: None,
+            steps,
+        };
+        let report = SuiteReport::from_scenarios(
+            Some("nightly".to_string()),
+            vec![
+                scenario("settings", &["settings"], vec![failed_step(1, &[("alignment", "footer"), ("color", "header")])]),
+                scenario("search", &["search"], vec![failed_step(2, &[("alignment", "status bar")]), failed_step(3, &[("", "")])]),
+            ],
+        );
+        assert_eq!(report.failed, 2);
+        let heatmap = &report.heatmap;
+        assert_eq!(heatmap.total, 4);
+        assert_eq!(heatmap.grid, [[0, 1, 0], [0, 1, 0], [0, 2, 0]]);
+        assert_eq!(heatmap.by_category["alignment"], 2);
+        assert_eq!(heatmap.by_category["unspecified"], 1);
+        assert_eq!(heatmap.by_tag["settings"]["color"], 1);
+        assert_eq!(heatmap.summary, "4 issues, most in the footer area (2), mostly alignment (2); most affected tag: search (2)");
+        assert_eq!(IssueHeatmap::from_issues(&[]).summary, "no issues reported");
+    }
+}
@@ -3,6 +3,11 @@
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+use crate::qa::{BannedMatch, ColorVisionIssue, LayoutIssue, Misspelling};
+use crate::snapshot::{CursorChange, CursorState, Hyperlink, WaitOutcome};
+use crate::vlm::{ServerInfo, VlmUsage};
+use crate::vlm_grounding::Finding;
+
 /// Result of a single state capture
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StateCapture {
@@ -12,11 +17,99 @@ pub struct StateCapture {
     /// Input that led to this state (None for initial state)
     pub input: Option<String>,
 
+    /// Repeat or macro token the input was expanded from, e.g. `down*40` or `@login`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expanded_from: Option<String>,
+
     /// Path to the screenshot
     pub screenshot_path: PathBuf,
 
+    /// Path to the standalone HTML page of the screen (if `--format html`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html_path: Option<PathBuf>,
+
+    /// Path to the screen text, one line per row (if `--save-text`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_path: Option<PathBuf>,
+
+    /// Path to the raw bytes received since the previous state (if `--save-ansi`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ansi_path: Option<PathBuf>,
+
+    /// Path to the cell grid as JSON (if `--format grid-json`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grid_path: Option<PathBuf>,
+
     /// VLM-generated description (if analyze=true)
     pub description: Option<String>,
+
+    /// What the VLM found and where, numbered from 1 in the annotated screenshot (if `--annotate-findings`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub findings: Vec<Finding>,
+
+    /// Path to the screenshot with the findings outlined (if `--annotate-findings`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub findings_path: Option<PathBuf>,
+
+    /// VLM answer on what changed since the same state in the baseline run, and whether it is a regression (if `--compare-baseline`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub baseline_comparison: Option<String>,
+
+    /// Tokens the VLM requests for this state used, as reported by the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vlm_usage: Option<VlmUsage>,
+
+    /// Audible bells (BEL) rung while reaching this state
+    #[serde(default)]
+    pub bell_count: u32,
+
+    /// Visual bells (screen flashes) while reaching this state
+    #[serde(default)]
+    pub visual_bell_count: u32,
+
+    /// Cursor visibility and style in this state
+    #[serde(default)]
+    pub cursor: CursorState,
+
+    /// Cursor visibility/style changes while reaching this state
+    #[serde(default)]
+    pub cursor_changes: Vec<CursorChange>,
+
+    /// Blink phase A (shown) and B (hidden) screenshots, if requested
+    #[serde(default)]
+    pub blink_frame_paths: Option<[PathBuf; 2]>,
+
+    /// OSC 8 hyperlinks present in this state
+    #[serde(default)]
+    pub hyperlinks: Vec<Hyperlink>,
+
+    /// Problems found when validating hyperlinks (if --check-links)
+    #[serde(default)]
+    pub link_errors: Vec<String>,
+
+    /// Misspelled words on screen (if --spellcheck)
+    #[serde(default)]
+    pub misspellings: Vec<Misspelling>,
+
+    /// Banned content visible in this state
+    #[serde(default)]
+    pub banned_content: Vec<BannedMatch>,
+
+    /// Suspected layout problems (if --layout-check)
+    #[serde(default)]
+    pub layout_issues: Vec<LayoutIssue>,
+
+    /// Text and colors that become hard to tell apart with color blindness (if --color-vision)
+    #[serde(default)]
+    pub color_vision_issues: Vec<ColorVisionIssue>,
+
+    /// Codepoints on screen without a real glyph, as `U+XXXX`
+    #[serde(default)]
+    pub unrenderable_codepoints: Vec<String>,
+
+    /// How the wait input that led to this state ended: matched text or timeout
+    #[serde(default)]
+    pub wait: Option<WaitOutcome>,
 }
 
 /// Result of a complete test run
@@ -30,4 +123,36 @@ pub struct RunResult {
 
     /// All captured states (N inputs → N+1 states)
     pub states: Vec<StateCapture>,
+
+    /// Asciicast v2 recording of the application's output, with a marker at each state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cast_path: Option<PathBuf>,
+
+    /// Animated GIF of the states, when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gif_path: Option<PathBuf>,
+
+    /// WebP, APNG or MP4 video of the states, when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub video_path: Option<PathBuf>,
+
+    /// VLM requests retried after transient failures
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub vlm_retries: u32,
+
+    /// Tokens used by all VLM requests of the run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vlm_usage: Option<VlmUsage>,
+
+    /// Cost in dollars of `vlm_usage`, if `--vlm-prices` lists the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vlm_cost: Option<f64>,
+
+    /// The VLM server's identity and models, if it lists them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vlm_server: Option<ServerInfo>,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
 }
@@ -0,0 +1,697 @@
+//! Scenario files.
+//!
+//! A scenario is a JSON file describing an application to launch and the
+//! steps to drive it through, so runs can be authored, versioned and
+//! validated without a wrapper program:
+//!
+//! ```json
+//! {
+//!   "name": "open help",
+//!   "tags": ["help"],
+//!   "binary": "/usr/bin/htop",
+//!   "size": "standard",
+//!   "regions": { "statusbar": "0,23,80,1" },
+//!   "steps": [
+//!     { "input": "F1", "expect_text": "Help", "check": "the help screen is readable" },
+//!     { "input": "down", "expect": ["cursor-at 2,0", "cell-color 2,0 #00ff00", "in statusbar not-contains Error"] },
+//!     { "input": "q" }
+//!   ]
+//! }
+//! ```
+//!
+//! A long `check` can live in its own file: `"check_file": "checks/help.txt"`
+//! is read relative to the scenario (or suite) file when it is loaded.
+//!
+//! A suite groups several scenarios in one file. [`validate_scenario`]
+//! reports problems as [`Diagnostic`]s with line and column positions, for
+//! editors to show inline; the JSON Schemas live in [`crate::schema`].
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::expect::Expectation;
+use crate::schema::{scenario_json_schema, validate_json, SourceMap};
+use crate::snapshot::pty::{parse_resize, parse_sleep};
+use crate::snapshot::layout::{KEYCODE_PREFIX, LAYOUT_NAMES};
+use crate::snapshot::{
+    key_sequence, parse_explicit_input, parse_mouse_input, parse_raw_input, parse_signal_input, parse_wait_input, expand_inputs, expanded_inputs, CellRect, Crop, ExpandedInput,
+    ExplicitInput, KeyboardLayout, Macros, RecordedEvent, Recording, TerminalSize,
+};
+
+fn default_delay_ms() -> u64 {
+    100
+}
+
+/// An application and the steps to drive it through
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Human-readable name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// What the scenario tests; given to the VLM as context for its checks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Labels for grouping results in suite reports, e.g. "settings" or "smoke"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Binary to execute
+    pub binary: PathBuf,
+    /// Arguments passed to the binary
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Terminal size: compact, standard, large, xl, or WxH (default: standard)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<String>,
+    /// Delay before each input (milliseconds)
+    #[serde(default = "default_delay_ms")]
+    pub delay_ms: u64,
+    /// Extra environment variables for the application
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Keyboard layout for `keycode:` inputs, e.g. "de-DE" (default: en-US)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    /// Crop preset for every screenshot, e.g. "bottom:3" (default: the whole screen)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crop: Option<String>,
+    /// Named input sequences that steps can use as `@NAME`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub macros: Macros,
+    /// Named rectangles of cells, as `X,Y,COLS,ROWS` or `COLSxROWS+X+Y`, that `expect` entries can check with `in NAME`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub regions: BTreeMap<String, String>,
+    /// Steps in order
+    pub steps: Vec<ScenarioStep>,
+}
+
+/// One input sent to the application
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioStep {
+    /// Input token, as accepted by `run --inputs` (e.g. "down", "ctrl+c", "resize:100x30", "down*40", "@login")
+    pub input: String,
+    /// Optional name for the resulting state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Text expected on screen after the step
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expect_text: Option<String>,
+    /// Deterministic checks after the step, e.g. "not-contains Error" or "cursor-at 5,0" (see [`crate::expect`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expect: Vec<String>,
+    /// Statement for the VLM to verify after the step, e.g. "the footer keys are aligned"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check: Option<String>,
+    /// File holding the `check` statement, relative to the scenario file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check_file: Option<PathBuf>,
+    /// VLM samples to vote over for this step's `check` (overrides the suite setting)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub samples: Option<u32>,
+    /// Crop preset for this step's screenshot (overrides the scenario's `crop`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crop: Option<String>,
+}
+
+/// Several scenarios run together
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Suite {
+    /// Human-readable name
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Scenarios in order
+    pub scenarios: Vec<Scenario>,
+}
+
+impl ScenarioStep {
+    /// A step sending `input`, with no name or checks
+    pub fn new(input: impl Into<String>) -> Self {
+        Self {
+            input: input.into(),
+            name: None,
+            expect_text: None,
+            expect: Vec::new(),
+            check: None,
+            check_file: None,
+            samples: None,
+            crop: None,
+        }
+    }
+}
+
+impl Scenario {
+    /// A scenario replaying a recording at the recorded size.
+    ///
+    /// Each key press is a step. A pause before a key that exceeds the step
+    /// delay by at least `min_pause_ms` is kept as a `sleep:` step, and a capture
+    /// point becomes a `sleep:` step for the time since the previous key,
+    /// named `capture N`.
+    pub fn from_recording(binary: PathBuf, args: Vec<String>, recording: &Recording, delay_ms: u64, min_pause_ms: u64) -> Self {
+        let mut steps = Vec::new();
+        let mut last_ms = 0;
+        let mut captures = 0;
+        for event in &recording.events {
+            match event {
+                RecordedEvent::Key { at_ms, input } => {
+                    let pause = at_ms.saturating_sub(last_ms).saturating_sub(delay_ms);
+                    if pause > 0 && pause >= min_pause_ms {
+                        steps.push(ScenarioStep::new(format!("sleep:{}", pause)));
+                    }
+                    steps.push(ScenarioStep::new(input.clone()));
+                    last_ms = *at_ms;
+                }
+                RecordedEvent::Capture { at_ms } => {
+                    captures += 1;
+                    let mut step = ScenarioStep::new(format!("sleep:{}", at_ms.saturating_sub(last_ms)));
+                    step.name = Some(format!("capture {}", captures));
+                    steps.push(step);
+                    last_ms = *at_ms;
+                }
+            }
+        }
+        let (cols, rows) = recording.size;
+        Scenario {
+            name: None,
+            description: None,
+            tags: Vec::new(),
+            binary,
+            args,
+            size: Some(format!("{}x{}", cols, rows)),
+            delay_ms,
+            env: BTreeMap::new(),
+            layout: None,
+            crop: None,
+            macros: Macros::new(),
+            regions: BTreeMap::new(),
+            steps,
+        }
+    }
+
+    /// Parse a scenario from JSON
+    pub fn parse(source: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(source)
+    }
+
+    /// Load a scenario file, reading its steps' `check_file`s
+    pub fn from_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let mut scenario = Self::parse(&fs::read_to_string(path)?).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+        })?;
+        scenario.load_check_files(path.parent().unwrap_or(Path::new("")))?;
+        Ok(scenario)
+    }
+
+    /// Read each step's `check_file`, relative to `base_dir`, into its `check`
+    pub fn load_check_files(&mut self, base_dir: &Path) -> std::io::Result<()> {
+        for step in &mut self.steps {
+            let Some(file) = &step.check_file else { continue };
+            let path = base_dir.join(file);
+            let check = fs::read_to_string(&path)
+                .map_err(|e| std::io::Error::new(e.kind(), format!("check file {}: {}", path.display(), e)))?;
+            step.check = Some(check.trim().to_string());
+        }
+        Ok(())
+    }
+
+    /// Terminal size, or `None` if the size string is not recognized
+    pub fn terminal_size(&self) -> Option<TerminalSize> {
+        match &self.size {
+            Some(size) => TerminalSize::from_str(size),
+            None => Some(TerminalSize::default()),
+        }
+    }
+
+    /// Keyboard layout, or `None` if the layout name is not recognized
+    pub fn keyboard_layout(&self) -> Option<KeyboardLayout> {
+        match &self.layout {
+            Some(name) => KeyboardLayout::from_name(name),
+            None => Some(KeyboardLayout::default()),
+        }
+    }
+
+    /// Named regions as rectangles; malformed ones are skipped
+    pub fn regions(&self) -> BTreeMap<String, CellRect> {
+        self.regions
+            .iter()
+            .filter_map(|(name, spec)| Some((name.clone(), CellRect::parse(spec).ok()?)))
+            .collect()
+    }
+
+    /// Crop presets as `(state, crop)` pairs for the states of an expansion of the steps:
+    /// state 0 uses the scenario's crop and the others the crop of the step they came from,
+    /// falling back to the scenario's; malformed presets are skipped
+    pub fn crops(&self, expanded: &[ExpandedInput]) -> Vec<(usize, Crop)> {
+        let default = self.crop.as_deref().and_then(|spec| Crop::parse(spec).ok());
+        let step_crop = |input: &ExpandedInput| {
+            let step = self.steps.get(input.origin)?;
+            step.crop.as_deref().and_then(|spec| Crop::parse(spec).ok())
+        };
+        std::iter::once(default)
+            .chain(expanded.iter().map(|input| step_crop(input).or(default)))
+            .enumerate()
+            .filter_map(|(state, crop)| Some((state, crop?)))
+            .collect()
+    }
+
+    /// Inputs of the steps with repeats and macros expanded
+    pub fn expanded_inputs(&self) -> Result<Vec<ExpandedInput>, String> {
+        expand_inputs(&self.inputs(), &self.macros)
+    }
+
+    /// Inputs of the steps in order
+    pub fn inputs(&self) -> Vec<String> {
+        self.steps.iter().map(|step| step.input.clone()).collect()
+    }
+
+    /// Environment variables as `(key, value)` pairs
+    pub fn env_pairs(&self) -> Vec<(String, String)> {
+        self.env.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+}
+
+/// Severity of a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The scenario cannot run as written
+    Error,
+    /// The scenario runs but probably not as intended
+    Warning,
+}
+
+/// A problem found in a scenario file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// Line of the problem (1-based)
+    pub line: usize,
+    /// Column of the problem (1-based)
+    pub column: usize,
+    /// Severity
+    pub severity: Severity,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(source: &str, offset: usize, severity: Severity, message: impl Into<String>) -> Self {
+        let (line, column) = line_column(source, offset);
+        Self {
+            line,
+            column,
+            severity,
+            message: message.into(),
+        }
+    }
+
+    /// Diagnostic for a JSON syntax or type error
+    pub(crate) fn from_json_error(e: &serde_json::Error) -> Self {
+        Self {
+            line: e.line().max(1),
+            column: e.column().max(1),
+            severity: Severity::Error,
+            message: strip_position(&e.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}:{}: {}: {}", self.line, self.column, severity, self.message)
+    }
+}
+
+/// Parse and check a scenario, returning it (if it is valid) and every problem found.
+///
+/// The document is first checked against [`scenario_json_schema`], which
+/// reports every structural problem at once; a scenario that passes is
+/// further checked for unknown sizes and layouts, malformed crops, `resize:`, `sleep:`, `wait-for:`,
+/// `wait-regex:`, `wait-gone:`, `wait-stable:`, `signal:`, `click:`, `scroll:`, `raw:`, `bytes:` and `keycode:` steps, unknown `key:`
+/// names, unknown macros and bad repeat counts, malformed regions, malformed `expect` entries and unknown regions in them,
+/// a missing binary and an empty step list.
+/// Inputs that a repeat or macro expands to are checked like ordinary steps.
+pub fn validate_scenario(source: &str) -> (Option<Scenario>, Vec<Diagnostic>) {
+    let diagnostics = validate_json(source, &scenario_json_schema());
+    if !diagnostics.is_empty() {
+        return (None, diagnostics);
+    }
+    let scenario = match Scenario::parse(source) {
+        Ok(scenario) => scenario,
+        Err(e) => return (None, vec![Diagnostic::from_json_error(&e)]),
+    };
+
+    let map = SourceMap::scan(source);
+    let mut diagnostics = Vec::new();
+    if let Some(size) = &scenario.size
+        && TerminalSize::from_str(size).is_none()
+    {
+        diagnostics.push(Diagnostic::new(
+            source,
+            map.offset("/size", false),
+            Severity::Error,
+            format!("unknown terminal size '{}' (expected: compact, standard, large, xl, or WxH)", size),
+        ));
+    }
+
+    if let Some(name) = &scenario.layout
+        && KeyboardLayout::from_name(name).is_none()
+    {
+        diagnostics.push(Diagnostic::new(
+            source,
+            map.offset("/layout", false),
+            Severity::Error,
+            format!("unknown keyboard layout '{}' (expected: {})", name, LAYOUT_NAMES.join(", ")),
+        ));
+    }
+
+    if let Some(Err(problem)) = scenario.crop.as_deref().map(Crop::parse) {
+        diagnostics.push(Diagnostic::new(source, map.offset("/crop", false), Severity::Error, problem));
+    }
+
+    for (name, spec) in &scenario.regions {
+        if let Err(problem) = CellRect::parse(spec) {
+            diagnostics.push(Diagnostic::new(
+                source,
+                map.offset(&format!("/regions/{}", name), false),
+                Severity::Error,
+                format!("region '{}': {}", name, problem),
+            ));
+        }
+    }
+
+    let binary = &scenario.binary;
+    if binary.components().count() > 1 && !binary.exists() {
+        diagnostics.push(Diagnostic::new(
+            source,
+            map.offset("/binary", false),
+            Severity::Warning,
+            format!("binary '{}' does not exist", binary.display()),
+        ));
+    }
+
+    if scenario.steps.is_empty() {
+        diagnostics.push(Diagnostic::new(
+            source,
+            map.offset("/steps", false),
+            Severity::Warning,
+            "scenario has no steps; only the initial screen is captured",
+        ));
+    }
+    let layout = scenario.keyboard_layout().unwrap_or_default();
+    for (idx, step) in scenario.steps.iter().enumerate() {
+        // Each distinct input a repeat or macro expands to is checked on its own
+        let mut inputs = match expand_inputs(std::slice::from_ref(&step.input), &scenario.macros) {
+            Ok(expanded) => expanded_inputs(&expanded),
+            Err(problem) => {
+                diagnostics.push(Diagnostic::new(
+                    source,
+                    map.offset(&format!("/steps/{}/input", idx), false),
+                    Severity::Error,
+                    format!("step {}: {}", idx + 1, problem),
+                ));
+                Vec::new()
+            }
+        };
+        inputs.sort();
+        inputs.dedup();
+        if let Some(Err(problem)) = step.crop.as_deref().map(Crop::parse) {
+            diagnostics.push(Diagnostic::new(
+                source,
+                map.offset(&format!("/steps/{}/crop", idx), false),
+                Severity::Error,
+                format!("step {}: {}", idx + 1, problem),
+            ));
+        }
+        if step.check.is_some() && step.check_file.is_some() {
+            diagnostics.push(Diagnostic::new(
+                source,
+                map.offset(&format!("/steps/{}/check_file", idx), false),
+                Severity::Error,
+                format!("step {}: give either check or check_file, not both", idx + 1),
+            ));
+        }
+        for (entry, spec) in step.expect.iter().enumerate() {
+            let problem = match Expectation::parse(spec) {
+                Ok(expectation) => expectation
+                    .region()
+                    .filter(|region| !scenario.regions.contains_key(*region) && CellRect::parse(region).is_err())
+                    .map(|region| format!("unknown region '{}'", region)),
+                Err(problem) => Some(problem),
+            };
+            if let Some(problem) = problem {
+                diagnostics.push(Diagnostic::new(
+                    source,
+                    map.offset(&format!("/steps/{}/expect/{}", idx, entry), false),
+                    Severity::Error,
+                    format!("step {}: {}", idx + 1, problem),
+                ));
+            }
+        }
+        for input in &inputs {
+            let is_keycode = input
+                .trim()
+                .get(..KEYCODE_PREFIX.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(KEYCODE_PREFIX));
+            if is_keycode && layout.keycode_bytes(input).is_none() {
+                diagnostics.push(Diagnostic::new(
+                    source,
+                    map.offset(&format!("/steps/{}/input", idx), false),
+                    Severity::Error,
+                    format!("step {}: '{}' is not a key on the {} layout", idx + 1, input, layout.name()),
+                ));
+            }
+            let is_resize = input
+                .trim()
+                .split_once(':')
+                .is_some_and(|(prefix, _)| prefix.eq_ignore_ascii_case("resize"));
+            if is_resize && parse_resize(input).is_none() {
+                diagnostics.push(Diagnostic::new(
+                    source,
+                    map.offset(&format!("/steps/{}/input", idx), false),
+                    Severity::Error,
+                    format!("step {}: malformed resize '{}' (expected resize:COLSxROWS)", idx + 1, input),
+                ));
+            }
+            let is_sleep = input
+                .trim()
+                .split_once(':')
+                .is_some_and(|(prefix, _)| prefix.eq_ignore_ascii_case("sleep"));
+            if is_sleep && parse_sleep(input).is_none() {
+                diagnostics.push(Diagnostic::new(
+                    source,
+                    map.offset(&format!("/steps/{}/input", idx), false),
+                    Severity::Error,
+                    format!("step {}: malformed sleep '{}' (expected sleep:MS or sleep:SECONDSs)", idx + 1, input),
+                ));
+            }
+            let mouse_prefix = input.trim().split_once(':').map(|(prefix, _)| prefix.to_lowercase());
+            if let Some(prefix @ ("click" | "scroll")) = mouse_prefix.as_deref()
+                && parse_mouse_input(input).is_none()
+            {
+                let expected = if prefix == "click" { "click:COL,ROW[,left|middle|right]" } else { "scroll:COL,ROW,up|down" };
+                diagnostics.push(Diagnostic::new(
+                    source,
+                    map.offset(&format!("/steps/{}/input", idx), false),
+                    Severity::Error,
+                    format!("step {}: malformed {} '{}' (expected {})", idx + 1, prefix, input, expected),
+                ));
+            }
+            if let Some(ExplicitInput::Key(name)) = parse_explicit_input(input)
+                && key_sequence(name).is_none()
+                && layout.keycode_bytes(name).is_none()
+            {
+                diagnostics.push(Diagnostic::new(
+                    source,
+                    map.offset(&format!("/steps/{}/input", idx), false),
+                    Severity::Error,
+                    format!("step {}: unknown key '{}' (use text: to send it literally)", idx + 1, name),
+                ));
+            }
+            if let Some(Err(problem)) = parse_signal_input(input) {
+                diagnostics.push(Diagnostic::new(
+                    source,
+                    map.offset(&format!("/steps/{}/input", idx), false),
+                    Severity::Error,
+                    format!("step {}: {}", idx + 1, problem),
+                ));
+            }
+            if let Some(Err(problem)) = parse_wait_input(input) {
+                diagnostics.push(Diagnostic::new(
+                    source,
+                    map.offset(&format!("/steps/{}/input", idx), false),
+                    Severity::Error,
+                    format!("step {}: {}", idx + 1, problem),
+                ));
+            }
+            if let Some(Err(problem)) = parse_raw_input(input) {
+                diagnostics.push(Diagnostic::new(
+                    source,
+                    map.offset(&format!("/steps/{}/input", idx), false),
+                    Severity::Error,
+                    format!("step {}: malformed byte input '{}': {}", idx + 1, input, problem),
+                ));
+            }
+        }
+    }
+
+    (Some(scenario), diagnostics)
+}
+
+/// serde_json appends " at line L column C" to its messages; the position is reported separately
+fn strip_position(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(idx) => message[..idx].to_string(),
+        None => message.to_string(),
+    }
+}
+
+/// 1-based line and column (in characters) of a byte offset
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording_becomes_replayable_steps() {
+        let key = |at_ms, input: &str| RecordedEvent::Key { at_ms, input: input.to_string() };
+        let recording = Recording {
+            size: (100, 30),
+            events: vec![key(50, "down"), key(120, "down"), key(2120, "enter"), RecordedEvent::Capture { at_ms: 2300 }],
+            exit_code: None,
+        };
+        let scenario = Scenario::from_recording(PathBuf::from("/bin/sh"), Vec::new(), &recording, 100, 500);
+        assert_eq!(scenario.inputs(), ["down", "down", "sleep:1900", "enter", "sleep:180"]);
+        assert_eq!(scenario.steps[4].name.as_deref(), Some("capture 1"));
+        assert_eq!(scenario.terminal_size(), Some(TerminalSize::Custom(100, 30)));
+        let (parsed, diagnostics) = validate_scenario(&serde_json::to_string(&scenario).unwrap());
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(parsed, Some(scenario));
+    }
+
+    #[test]
+    fn test_syntax_errors_carry_positions() {
+        let (scenario, diagnostics) = validate_scenario("{\n  \"binary\": \"/bin/sh\",\n  \"steps\": [}\n");
+        assert!(scenario.is_none());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (3, 13));
+        assert!(!diagnostics[0].message.contains("at line"));
+    }
+
+    #[test]
+    fn test_semantic_problems_point_at_their_values() {
+        let source = r#"{
+  "binary": "/bin/sh",
+  "size": "huge",
+  "layout": "de-DE",
+  "steps": [
+    { "input": "down" },
+    { "input": "resize:wide-ish" },
+    { "input": "keycode:Semicolon" },
+    { "input": "keycode:altgr+KeyA" },
+    { "input": "text:enter" },
+    { "input": "key:entr" },
+    { "input": "q", "expect": ["contains Bye", "cursor-at 1"] },
+    { "input": "enter", "expect": ["in footer contains OK"] }
+  ]
+}"#;
+        let (scenario, diagnostics) = validate_scenario(source);
+        assert_eq!(scenario.unwrap().inputs()[..2], ["down", "resize:wide-ish"]);
+        let positions: Vec<_> = diagnostics.iter().map(|d| (d.line, d.column, d.severity)).collect();
+        // KeyA has no AltGr level on de-DE
+        assert_eq!(
+            positions,
+            [
+                (3, 11, Severity::Error),
+                (7, 16, Severity::Error),
+                (9, 16, Severity::Error),
+                (11, 16, Severity::Error),
+                (12, 48, Severity::Error),
+                (13, 36, Severity::Error)
+            ]
+        );
+        assert_eq!(diagnostics[4].message, "step 7: malformed position '1' (expected ROW,COL)");
+        assert_eq!(diagnostics[5].message, "step 8: unknown region 'footer'");
+    }
+
+    #[test]
+    fn test_macros_are_expanded_and_checked() {
+        let source = r#"{
+  "binary": "/bin/sh",
+  "macros": { "open": ["down*2", "resize:big"] },
+  "steps": [
+    { "input": "@open", "crop": "top:1" },
+    { "input": "@close" }
+  ]
+}"#;
+        let (scenario, diagnostics) = validate_scenario(source);
+        let messages: Vec<_> = diagnostics.iter().map(|d| (d.line, d.message.as_str())).collect();
+        assert_eq!(
+            messages,
+            [
+                (5, "step 1: malformed resize 'resize:big' (expected resize:COLSxROWS)"),
+                (6, "step 2: unknown macro '@close'")
+            ]
+        );
+        let scenario = scenario.unwrap();
+        assert_eq!(scenario.expanded_inputs().unwrap_err(), "unknown macro '@close'");
+        let open = expand_inputs(&["@open".to_string()], &scenario.macros).unwrap();
+        assert_eq!(scenario.crops(&open), [(1, Crop::Top(1)), (2, Crop::Top(1)), (3, Crop::Top(1))]);
+    }
+
+    #[test]
+    fn test_step_crops_override_scenario_crop() {
+        let source = r#"{
+  "binary": "/bin/sh",
+  "crop": "bottom:3",
+  "steps": [
+    { "input": "down", "crop": "top:1" },
+    { "input": "up" },
+    { "input": "q", "crop": "left:2" }
+  ]
+}"#;
+        let (scenario, diagnostics) = validate_scenario(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (7, 29));
+        let scenario = scenario.unwrap();
+        assert_eq!(
+            scenario.crops(&scenario.expanded_inputs().unwrap()),
+            [(0, Crop::Bottom(3)), (1, Crop::Top(1)), (2, Crop::Bottom(3)), (3, Crop::Bottom(3))]
+        );
+    }
+
+    #[test]
+    fn test_check_files_are_read_relative_to_the_scenario() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("checks")).unwrap();
+        fs::write(dir.path().join("checks/help.txt"), "the help screen lists every key binding\n").unwrap();
+        let source = r#"{
+  "binary": "/bin/sh",
+  "steps": [
+    { "input": "F1", "check_file": "checks/help.txt" },
+    { "input": "q", "check": "the app exited", "check_file": "checks/help.txt" }
+  ]
+}"#;
+        let (_, diagnostics) = validate_scenario(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "step 2: give either check or check_file, not both");
+
+        fs::write(dir.path().join("scenario.json"), source).unwrap();
+        let scenario = Scenario::from_file(dir.path().join("scenario.json")).unwrap();
+        assert_eq!(scenario.steps[0].check.as_deref(), Some("the help screen lists every key binding"));
+    }
+}
@@ -0,0 +1,346 @@
+//! JSON Schemas for scenario and suite files, and validation against them.
+//!
+//! The schemas are generated from the serde types in [`crate::scenario`], so
+//! they cannot drift from what the loader accepts. Editors can use them for
+//! completion and hover docs (`cli-vision schema > scenario.schema.json`).
+//!
+//! [`validate_json`] checks a document against a schema and reports every
+//! violation at its position in the source: unknown fields at the key,
+//! wrong types at the value, and missing fields at the enclosing object.
+//! Only the keywords the generated schemas use are supported (`type`,
+//! `properties`, `required`, `additionalProperties`, `items`, `$ref`,
+//! `enum`, `const`, `minimum`, `maximum`, `anyOf`, `oneOf`, `allOf`).
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::scenario::{Diagnostic, Scenario, Severity, Suite};
+
+/// JSON Schema for a scenario file
+pub fn scenario_json_schema() -> Value {
+    serde_json::to_value(schemars::schema_for!(Scenario)).expect("schema serializes to JSON")
+}
+
+/// JSON Schema for a suite file
+pub fn suite_json_schema() -> Value {
+    serde_json::to_value(schemars::schema_for!(Suite)).expect("schema serializes to JSON")
+}
+
+/// Check a JSON document against a schema.
+///
+/// Malformed JSON yields a single diagnostic at the syntax error; otherwise
+/// one diagnostic is returned per schema violation, in document order.
+pub fn validate_json(source: &str, schema: &Value) -> Vec<Diagnostic> {
+    let value: Value = match serde_json::from_str(source) {
+        Ok(value) => value,
+        Err(e) => return vec![Diagnostic::from_json_error(&e)],
+    };
+    let map = SourceMap::scan(source);
+    let mut violations = Vec::new();
+    check(&value, schema, schema, "", &mut violations);
+    let mut diagnostics: Vec<(usize, Diagnostic)> = violations
+        .into_iter()
+        .map(|v| {
+            let offset = map.offset(&v.pointer, v.at_key);
+            (offset, Diagnostic::new(source, offset, Severity::Error, v.message))
+        })
+        .collect();
+    diagnostics.sort_by_key(|(offset, _)| *offset);
+    diagnostics.into_iter().map(|(_, d)| d).collect()
+}
+
+/// A schema violation at a JSON pointer
+struct Violation {
+    pointer: String,
+    /// Point at the object key rather than the value (unknown fields)
+    at_key: bool,
+    message: String,
+}
+
+fn check(value: &Value, schema: &Value, root: &Value, pointer: &str, out: &mut Vec<Violation>) {
+    let violation = |message: String| Violation {
+        pointer: pointer.to_string(),
+        at_key: false,
+        message,
+    };
+
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        match reference.strip_prefix('#').and_then(|path| root.pointer(path)) {
+            Some(target) => check(value, target, root, pointer, out),
+            None => out.push(violation(format!("schema reference '{}' not found", reference))),
+        }
+    }
+
+    if let Some(expected) = schema.get("type") {
+        let names: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !names.is_empty() && !names.iter().any(|name| has_type(value, name)) {
+            out.push(violation(format!("expected {}, found {}", names.join(" or "), type_name(value))));
+            // Nested keywords would only repeat the type mismatch
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        let names: Vec<String> = allowed.iter().map(Value::to_string).collect();
+        out.push(violation(format!("expected one of {}, found {}", names.join(", "), value)));
+    }
+    if let Some(constant) = schema.get("const")
+        && constant != value
+    {
+        out.push(violation(format!("expected {}, found {}", constant, value)));
+    }
+
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64)
+            && number < minimum
+        {
+            out.push(violation(format!("{} is less than the minimum {}", value, minimum)));
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64)
+            && number > maximum
+        {
+            out.push(violation(format!("{} is greater than the maximum {}", value, maximum)));
+        }
+    }
+
+    if let Value::Object(object) = value {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    out.push(violation(format!("missing field `{}`", name)));
+                }
+            }
+        }
+        for (key, child) in object {
+            let child_pointer = format!("{}/{}", pointer, escape_pointer(key));
+            match (properties.and_then(|p| p.get(key)), schema.get("additionalProperties")) {
+                (Some(child_schema), _) => check(child, child_schema, root, &child_pointer, out),
+                (None, Some(Value::Bool(false))) => {
+                    let mut known: Vec<&str> = properties.map(|p| p.keys().map(String::as_str).collect()).unwrap_or_default();
+                    known.sort_unstable();
+                    out.push(Violation {
+                        pointer: child_pointer,
+                        at_key: true,
+                        message: format!("unknown field `{}`, expected one of `{}`", key, known.join("`, `")),
+                    });
+                }
+                (None, Some(extra @ Value::Object(_))) => check(child, extra, root, &child_pointer, out),
+                (None, _) => {}
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (idx, item) in items.iter().enumerate() {
+            check(item, item_schema, root, &format!("{}/{}", pointer, idx), out);
+        }
+    }
+
+    if let Some(branches) = schema.get("allOf").and_then(Value::as_array) {
+        for branch in branches {
+            check(value, branch, root, pointer, out);
+        }
+    }
+    for (keyword, exactly_one) in [("anyOf", false), ("oneOf", true)] {
+        let Some(branches) = schema.get(keyword).and_then(Value::as_array) else {
+            continue;
+        };
+        let mut branch_errors: Vec<Vec<Violation>> = branches
+            .iter()
+            .map(|branch| {
+                let mut errors = Vec::new();
+                check(value, branch, root, pointer, &mut errors);
+                errors
+            })
+            .collect();
+        let matching = branch_errors.iter().filter(|errors| errors.is_empty()).count();
+        if matching == 0 {
+            match branch_errors.pop() {
+                Some(errors) if branches.len() == 1 => out.extend(errors),
+                _ => out.push(violation("value does not match any of the allowed shapes".to_string())),
+            }
+        } else if exactly_one && matching > 1 {
+            out.push(violation("value matches more than one of the allowed shapes".to_string()));
+        }
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Escape a key for use in a JSON pointer (RFC 6901)
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Byte offsets of the values and object keys of a JSON document, by JSON pointer
+#[derive(Debug, Default)]
+pub(crate) struct SourceMap {
+    values: HashMap<String, usize>,
+    keys: HashMap<String, usize>,
+}
+
+impl SourceMap {
+    /// Scan a document that is known to be valid JSON
+    pub(crate) fn scan(source: &str) -> Self {
+        let mut map = Self::default();
+        map.scan_value(source.as_bytes(), skip_whitespace(source.as_bytes(), 0), String::new());
+        map
+    }
+
+    /// Offset of the value (or its key) at `pointer`, falling back to the nearest ancestor
+    pub(crate) fn offset(&self, pointer: &str, at_key: bool) -> usize {
+        if at_key && let Some(&offset) = self.keys.get(pointer) {
+            return offset;
+        }
+        let mut pointer = pointer;
+        loop {
+            if let Some(&offset) = self.values.get(pointer) {
+                return offset;
+            }
+            match pointer.rfind('/') {
+                Some(idx) => pointer = &pointer[..idx],
+                None => return 0,
+            }
+        }
+    }
+
+    /// Record the value starting at `pos` and return the offset just past it
+    fn scan_value(&mut self, bytes: &[u8], pos: usize, pointer: String) -> usize {
+        self.values.insert(pointer.clone(), pos);
+        match bytes.get(pos) {
+            Some(b'{') => {
+                let mut pos = skip_whitespace(bytes, pos + 1);
+                while bytes.get(pos) == Some(&b'"') {
+                    let key_end = skip_string(bytes, pos);
+                    let key: String = serde_json::from_slice(&bytes[pos..key_end]).unwrap_or_default();
+                    let child = format!("{}/{}", pointer, escape_pointer(&key));
+                    self.keys.insert(child.clone(), pos);
+                    // Past the ':' separator
+                    let value_start = skip_whitespace(bytes, skip_whitespace(bytes, key_end) + 1);
+                    pos = skip_whitespace(bytes, self.scan_value(bytes, value_start, child));
+                    if bytes.get(pos) == Some(&b',') {
+                        pos = skip_whitespace(bytes, pos + 1);
+                    }
+                }
+                pos + 1
+            }
+            Some(b'[') => {
+                let mut pos = skip_whitespace(bytes, pos + 1);
+                let mut idx = 0;
+                while pos < bytes.len() && bytes[pos] != b']' {
+                    pos = skip_whitespace(bytes, self.scan_value(bytes, pos, format!("{}/{}", pointer, idx)));
+                    if bytes.get(pos) == Some(&b',') {
+                        pos = skip_whitespace(bytes, pos + 1);
+                    }
+                    idx += 1;
+                }
+                pos + 1
+            }
+            Some(b'"') => skip_string(bytes, pos),
+            _ => {
+                let mut pos = pos;
+                while pos < bytes.len() && !matches!(bytes[pos], b',' | b']' | b'}') && !bytes[pos].is_ascii_whitespace() {
+                    pos += 1;
+                }
+                pos
+            }
+        }
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+        pos += 1;
+    }
+    pos
+}
+
+/// Offset just past the string literal starting at `pos`
+fn skip_string(bytes: &[u8], pos: usize) -> usize {
+    let mut pos = pos + 1;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' => pos += 2,
+            b'"' => return pos + 1,
+            _ => pos += 1,
+        }
+    }
+    pos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_is_generated_from_the_serde_types() {
+        let schema = scenario_json_schema();
+        assert_eq!(schema["required"], serde_json::json!(["binary", "steps"]));
+        assert_eq!(schema["additionalProperties"], false);
+        assert!(schema["$defs"]["ScenarioStep"]["properties"]["input"].is_object());
+        assert!(suite_json_schema()["$defs"]["Scenario"].is_object());
+    }
+
+    #[test]
+    fn test_every_violation_is_reported_at_its_position() {
+        let source = r#"{
+  "binary": 42,
+  "steps": [
+    { "input": "down", "wait": 3 },
+    { "name": "no input" }
+  ],
+  "delay_ms": -1
+}"#;
+        let diagnostics = validate_json(source, &scenario_json_schema());
+        let found: Vec<_> = diagnostics.iter().map(|d| (d.line, d.column, d.message.as_str())).collect();
+        assert_eq!(
+            found,
+            [
+                (2, 13, "expected string, found integer"),
+                (4, 24, "unknown field `wait`, expected one of `check`, `check_file`, `crop`, `expect`, `expect_text`, `input`, `name`, `samples`"),
+                (5, 5, "missing field `input`"),
+                (7, 15, "-1 is less than the minimum 0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_source_map_tracks_nested_pointers() {
+        let source = "{\"a\": [1, {\"b~/c\": \"x\\\"y\"}], \"d\": null}";
+        let map = SourceMap::scan(source);
+        assert_eq!(map.offset("/a/1/b~0~1c", false), source.find("\"x").unwrap());
+        assert_eq!(map.offset("/a/1/b~0~1c", true), source.find("\"b~").unwrap());
+        assert_eq!(map.offset("/d", false), source.find("null").unwrap());
+        assert_eq!(map.offset("/a/7", false), source.find('[').unwrap());
+    }
+}
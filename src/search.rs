@@ -0,0 +1,288 @@
+//! Similarity search over captured states.
+//!
+//! Every state screenshot under a session base directory is embedded once
+//! and the vectors are cached in an `embeddings.json` next to the
+//! screenshots, so searching a history of nightly runs for "error dialog"
+//! only embeds new states and the query.
+//!
+//! Embeddings come from the local CLIP model (`local-vision` feature), which
+//! compares the query text with the images directly, or from an
+//! OpenAI-compatible embeddings endpoint, which embeds each state's screen
+//! text (`state_N.txt` when present) or otherwise a VLM description of it.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::search::{search_sessions, EmbeddingSource};
+//!
+//! let hits = search_sessions("/tmp/cli-vision".as_ref(), "error dialog", &EmbeddingSource::Local, 5).unwrap();
+//! for hit in &hits {
+//!     println!("{:.3} {}", hit.score, hit.screenshot.display());
+//! }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::compare::list_state_images;
+use crate::vlm::{analyze_image, embed_texts, VlmConfig, VlmError, VlmResult};
+
+/// File caching state embeddings inside a session directory
+pub const INDEX_FILE: &str = "embeddings.json";
+
+/// How deep below the base directory sessions are looked for
+const MAX_SEARCH_DEPTH: usize = 3;
+
+/// Prompt used to describe a state when embedding it through an endpoint
+const DESCRIBE_PROMPT: &str = "Describe this terminal screen in two sentences: what kind of screen it is \
+     (menu, dialog, list, editor, error, ...) and the most important visible text.";
+
+/// Where embeddings come from
+#[derive(Debug, Clone)]
+pub enum EmbeddingSource {
+    /// The local CLIP model (requires the `local-vision` feature)
+    Local,
+    /// An OpenAI-compatible embeddings endpoint; `vlm` describes states without screen text
+    Endpoint {
+        /// Embeddings endpoint URL
+        endpoint: String,
+        /// Model used for embeddings and descriptions
+        vlm: Box<VlmConfig>,
+    },
+}
+
+impl EmbeddingSource {
+    /// Identifies the embedding space; cached vectors from another source are recomputed
+    pub fn id(&self) -> String {
+        match self {
+            EmbeddingSource::Local => "local-clip".to_string(),
+            EmbeddingSource::Endpoint { endpoint, vlm } => format!("{}#{}", endpoint, vlm.model),
+        }
+    }
+
+    /// Embed a state screenshot
+    pub fn embed_state(&self, screenshot: &Path) -> VlmResult<Vec<f32>> {
+        match self {
+            EmbeddingSource::Local => local_embed_image(&fs::read(screenshot)?),
+            EmbeddingSource::Endpoint { endpoint, vlm } => {
+                let text = match fs::read_to_string(screenshot.with_extension("txt")) {
+                    Ok(text) => text,
+                    Err(_) => analyze_image(vlm, &fs::read(screenshot)?, DESCRIBE_PROMPT)?,
+                };
+                first_embedding(embed_texts(vlm, endpoint, &[text])?)
+            }
+        }
+    }
+
+    /// Embed a search query
+    pub fn embed_query(&self, query: &str) -> VlmResult<Vec<f32>> {
+        match self {
+            EmbeddingSource::Local => local_embed_text(&format!("a terminal screenshot showing {}", query)),
+            EmbeddingSource::Endpoint { endpoint, vlm } => first_embedding(embed_texts(vlm, endpoint, &[query.to_string()])?),
+        }
+    }
+}
+
+fn first_embedding(embeddings: Vec<Vec<f32>>) -> VlmResult<Vec<f32>> {
+    embeddings
+        .into_iter()
+        .next()
+        .ok_or_else(|| VlmError::InvalidResponse("no embedding returned".to_string()))
+}
+
+#[cfg(feature = "local-vision")]
+fn local_embed_image(png: &[u8]) -> VlmResult<Vec<f32>> {
+    crate::local_vision::shared_model()
+        .and_then(|model| model.embed_image(png))
+        .map_err(|e| VlmError::LocalModel(e.to_string()))
+}
+
+#[cfg(feature = "local-vision")]
+fn local_embed_text(text: &str) -> VlmResult<Vec<f32>> {
+    crate::local_vision::shared_model()
+        .and_then(|model| model.embed_texts(&[text.to_string()]))
+        .map_err(|e| VlmError::LocalModel(e.to_string()))
+        .and_then(first_embedding)
+}
+
+#[cfg(not(feature = "local-vision"))]
+fn local_embed_image(_png: &[u8]) -> VlmResult<Vec<f32>> {
+    Err(VlmError::LocalModel("local embeddings need the `local-vision` feature".to_string()))
+}
+
+#[cfg(not(feature = "local-vision"))]
+fn local_embed_text(_text: &str) -> VlmResult<Vec<f32>> {
+    Err(VlmError::LocalModel("local embeddings need the `local-vision` feature".to_string()))
+}
+
+/// Cached embeddings of one session directory's states
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmbeddingIndex {
+    /// [`EmbeddingSource::id`] the vectors were computed with
+    pub source: String,
+    /// Embedding per screenshot file name
+    pub states: BTreeMap<String, Vec<f32>>,
+}
+
+/// A state matching a search query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    /// Session directory holding the state
+    pub session: PathBuf,
+    /// State screenshot
+    pub screenshot: PathBuf,
+    /// Cosine similarity to the query (higher is closer)
+    pub score: f32,
+    /// When the session directory was last modified (RFC 3339)
+    pub modified: Option<String>,
+}
+
+/// Cosine similarity of two vectors (0.0 if either is zero or their lengths differ)
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 { 0.0 } else { dot / denominator }
+}
+
+/// Directories under `base` (including `base`) that contain state screenshots
+pub fn find_session_dirs(base: &Path) -> std::io::Result<Vec<PathBuf>> {
+    fn walk(dir: &Path, depth: usize, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+        if !list_state_images(dir)?.is_empty() {
+            out.push(dir.to_path_buf());
+        }
+        if depth == MAX_SEARCH_DEPTH {
+            return Ok(());
+        }
+        let mut children: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_dir())
+            .collect();
+        children.sort();
+        for child in children {
+            walk(&child, depth + 1, out)?;
+        }
+        Ok(())
+    }
+    let mut dirs = Vec::new();
+    walk(base, 0, &mut dirs)?;
+    Ok(dirs)
+}
+
+/// Embed the states of a session directory, reusing and updating its cached index
+pub fn index_session<F>(dir: &Path, source_id: &str, mut embed: F) -> VlmResult<EmbeddingIndex>
+where
+    F: FnMut(&Path) -> VlmResult<Vec<f32>>,
+{
+    let index_path = dir.join(INDEX_FILE);
+    let mut index = fs::read(&index_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<EmbeddingIndex>(&data).ok())
+        .filter(|index| index.source == source_id)
+        .unwrap_or_else(|| EmbeddingIndex {
+            source: source_id.to_string(),
+            states: BTreeMap::new(),
+        });
+    let mut changed = false;
+    for screenshot in list_state_images(dir)? {
+        let Some(name) = screenshot.file_name().and_then(|n| n.to_str()).map(str::to_string) else {
+            continue;
+        };
+        if let Entry::Vacant(entry) = index.states.entry(name) {
+            entry.insert(embed(&screenshot)?);
+            changed = true;
+        }
+    }
+    if changed {
+        let json = serde_json::to_string(&index).map_err(|e| VlmError::InvalidResponse(e.to_string()))?;
+        fs::write(&index_path, json)?;
+    }
+    Ok(index)
+}
+
+/// Rank the states of every session under `base` by similarity to an embedded query
+pub fn search_with<F>(base: &Path, query: &[f32], source_id: &str, limit: usize, mut embed: F) -> VlmResult<Vec<SearchHit>>
+where
+    F: FnMut(&Path) -> VlmResult<Vec<f32>>,
+{
+    let mut hits = Vec::new();
+    for dir in find_session_dirs(base)? {
+        let index = index_session(&dir, source_id, &mut embed)?;
+        let modified = fs::metadata(&dir)
+            .and_then(|m| m.modified())
+            .ok()
+            .map(|time| chrono::DateTime::<chrono::Local>::from(time).to_rfc3339());
+        for (name, vector) in &index.states {
+            let screenshot = dir.join(name);
+            if screenshot.exists() {
+                hits.push(SearchHit {
+                    session: dir.clone(),
+                    screenshot,
+                    score: cosine_similarity(query, vector),
+                    modified: modified.clone(),
+                });
+            }
+        }
+    }
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+/// Find the `limit` states under `base` most similar to `query`
+pub fn search_sessions(base: &Path, query: &str, source: &EmbeddingSource, limit: usize) -> VlmResult<Vec<SearchHit>> {
+    let query = source.embed_query(query)?;
+    search_with(base, &query, &source.id(), limit, |screenshot| source.embed_state(screenshot))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_search_ranks_states_and_caches_embeddings() {
+        let base = tempfile::tempdir().unwrap();
+        let nightly = |name: &str| {
+            let dir = base.path().join(name);
+            fs::create_dir_all(&dir).unwrap();
+            dir
+        };
+        let (monday, tuesday) = (nightly("monday"), nightly("tuesday"));
+        fs::write(monday.join("state_0_initial.png"), b"menu").unwrap();
+        fs::write(tuesday.join("state_0_initial.png"), b"menu").unwrap();
+        fs::write(tuesday.join("state_1_enter.png"), b"error dialog").unwrap();
+
+        // A fake embedding: the screenshot bytes decide the direction
+        let embedded = std::cell::Cell::new(0);
+        let fake = |path: &Path| -> VlmResult<Vec<f32>> {
+            embedded.set(embedded.get() + 1);
+            Ok(if fs::read(path)? == b"menu" { vec![1.0, 0.0] } else { vec![0.2, 1.0] })
+        };
+        let hits = search_with(base.path(), &[0.0, 1.0], "fake", 2, &fake).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits[0].screenshot.ends_with("tuesday/state_1_enter.png"));
+        assert_eq!(hits[0].session, tuesday);
+        assert!(hits[0].score > hits[1].score);
+        assert_eq!(embedded.get(), 3);
+
+        // The cached index is reused; a new source recomputes it
+        search_with(base.path(), &[0.0, 1.0], "fake", 5, &fake).unwrap();
+        assert_eq!(embedded.get(), 3);
+        search_with(base.path(), &[0.0, 1.0], "other", 5, &fake).unwrap();
+        assert_eq!(embedded.get(), 6);
+    }
+}
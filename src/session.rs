@@ -67,7 +67,7 @@ impl Session {
         let dir = dir.into();
         let id = dir.file_name()
             .map(|s| s.to_string_lossy().to_string())
-            .unwrap_or_else(|| generate_session_id());
+            .unwrap_or_else(generate_session_id);
 
         Self {
             id,
@@ -125,6 +125,11 @@ impl Session {
         self.dir.join(filename)
     }
 
+    /// Path of the VLM transcript (see [`crate::vlm_log`])
+    pub fn vlm_log_path(&self) -> PathBuf {
+        self.dir.join(crate::vlm_log::VLM_LOG_FILE)
+    }
+
     /// Get subdirectory for a specific terminal size
     pub fn size_subdir(&self, cols: u16, rows: u16) -> PathBuf {
         self.dir.join(format!("{}x{}", cols, rows))
@@ -209,18 +214,14 @@ pub fn cleanup_old_sessions(max_age: std::time::Duration) -> std::io::Result<usi
         let entry = entry?;
         let path = entry.path();
 
-        if path.is_dir() {
-            if let Ok(metadata) = entry.metadata() {
-                if let Ok(modified) = metadata.modified() {
-                    if let Ok(age) = now.duration_since(modified) {
-                        if age > max_age {
-                            if fs::remove_dir_all(&path).is_ok() {
-                                cleaned += 1;
-                            }
-                        }
-                    }
-                }
-            }
+        if path.is_dir()
+            && let Ok(metadata) = entry.metadata()
+            && let Ok(modified) = metadata.modified()
+            && let Ok(age) = now.duration_since(modified)
+            && age > max_age
+            && fs::remove_dir_all(&path).is_ok()
+        {
+            cleaned += 1;
         }
     }
 
@@ -6,12 +6,21 @@
 
 use font8x8::{BASIC_FONTS, UnicodeFonts};
 use image::{ImageBuffer, RgbImage};
-use std::io::Cursor;
+use std::io::{Cursor, Write};
 use std::path::PathBuf;
 use std::sync::mpsc;
 use std::time::Duration;
 
+use super::crop::{crop_png_cells, CellRect, Crop};
+use super::glyphs::{codepoint_label, GlyphFallback};
+use super::grid::TerminalGrid;
+use super::keys::kitty_key_sequence;
+use super::pty::{apply_limits, build_command, ingest_chunk, resize_pty, ClosedWriter, RenderOptions, TerminalIdentity};
+use super::signal::send_signal;
+use super::limits::ResourceLimits;
+use super::theme::Theme;
 use super::types::{SnapshotError, SnapshotResult};
+use super::wait::{timeout_message, wait_for_condition, DEFAULT_WAIT_TIMEOUT};
 use crate::harness::types::InputAction;
 
 /// Result of a capture operation
@@ -25,6 +34,24 @@ pub struct CaptureResult {
     pub height: u32,
     /// Optional metadata about the capture
     pub metadata: Option<serde_json::Value>,
+    /// Screen text, one line per row, for backends that emulate a terminal
+    pub text: Option<String>,
+    /// Cell grid, for backends that emulate a terminal
+    pub grid: Option<TerminalGrid>,
+}
+
+impl CaptureResult {
+    /// The same capture with the image cropped to a rectangle of its cells, e.g. a status bar
+    /// (`CellRect { cols: 200, rows: 1, x: 0, y: 59 }`); the text and grid still cover the whole screen
+    pub fn crop_cells(&self, rect: CellRect) -> SnapshotResult<CaptureResult> {
+        let (image_data, width, height) = crop_png_cells(&self.image_data, rect)?;
+        Ok(CaptureResult {
+            image_data,
+            width,
+            height,
+            ..self.clone()
+        })
+    }
 }
 
 /// Trait for capture backends
@@ -214,6 +241,8 @@ impl CaptureBackend for MockFramebuffer {
             metadata: Some(serde_json::json!({
                 "mock": true
             })),
+            text: None,
+            grid: None,
         })
     }
 
@@ -243,6 +272,18 @@ pub struct PtyBackendConfig {
     pub terminal_width: u16,
     /// Terminal height in rows (default: 40)
     pub terminal_height: u16,
+    /// Rendering options for the captured image
+    pub render: RenderOptions,
+    /// Identity reported to device attribute and version queries
+    pub identity: TerminalIdentity,
+    /// Color theme used for rendering
+    pub theme: Theme,
+    /// Extra environment variables for the application
+    pub env: Vec<(String, String)>,
+    /// Run with a scrubbed environment and no PATH lookup
+    pub hermetic: bool,
+    /// CPU, memory and file-descriptor limits for the application
+    pub limits: ResourceLimits,
 }
 
 impl Default for PtyBackendConfig {
@@ -253,6 +294,12 @@ impl Default for PtyBackendConfig {
             inputs: Vec::new(),
             terminal_width: 120,
             terminal_height: 40,
+            render: RenderOptions::default(),
+            identity: TerminalIdentity::default(),
+            theme: Theme::default(),
+            env: Vec::new(),
+            hermetic: false,
+            limits: ResourceLimits::default(),
         }
     }
 }
@@ -296,6 +343,55 @@ impl PtyBackendConfig {
         self.terminal_height = height;
         self
     }
+
+    /// Set whether the cursor is overlaid on the captured image
+    pub fn show_cursor(mut self, show: bool) -> Self {
+        self.render.show_cursor = show;
+        self
+    }
+
+    /// Set the glyph fallback chain for characters the built-in fonts do not cover
+    pub fn glyph_fallback(mut self, chain: Vec<GlyphFallback>) -> Self {
+        self.render.glyph_fallback = chain;
+        self
+    }
+
+    /// Crop the captured image to a band of rows
+    pub fn crop(mut self, crop: Crop) -> Self {
+        self.render.crop = Some(crop);
+        self
+    }
+
+    /// Set the identity reported to DA1/DA2/XTVERSION queries
+    pub fn identity(mut self, identity: TerminalIdentity) -> Self {
+        self.identity = identity;
+        self
+    }
+
+    /// Set the color theme used for rendering
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Set an environment variable for the application
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Set whether the application runs hermetically (scrubbed environment,
+    /// absolute binary path required)
+    pub fn hermetic(mut self, hermetic: bool) -> Self {
+        self.hermetic = hermetic;
+        self
+    }
+
+    /// Set resource limits for the application
+    pub fn limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
 }
 
 /// PTY-based capture backend for CLI applications
@@ -320,9 +416,9 @@ impl PtyBackend {
 
 impl CaptureBackend for PtyBackend {
     fn capture(&mut self) -> SnapshotResult<CaptureResult> {
-        use super::pty::{Vt100Parser, CELL_HEIGHT, CELL_WIDTH};
-        use portable_pty::{native_pty_system, CommandBuilder, PtySize};
-        use std::io::{Read, Write};
+        use super::pty::Vt100Parser;
+        use portable_pty::{native_pty_system, PtySize};
+        use std::io::Read;
         use std::sync::mpsc;
         use std::thread;
         use std::time::Duration;
@@ -330,6 +426,8 @@ impl CaptureBackend for PtyBackend {
         let terminal_width = self.config.terminal_width;
         let terminal_height = self.config.terminal_height;
         let mut parser = Vt100Parser::new(u32::from(terminal_width), u32::from(terminal_height));
+        parser.terminal_mut().identity = self.config.identity.clone();
+        parser.terminal_mut().set_theme(self.config.theme.clone());
 
         let pty_system = native_pty_system();
         let pair = pty_system
@@ -341,19 +439,20 @@ impl CaptureBackend for PtyBackend {
             })
             .map_err(|e| SnapshotError::Capture(format!("Failed to open PTY: {}", e)))?;
 
-        let binary_path = self.config.binary_path.to_string_lossy().to_string();
-        let mut cmd = CommandBuilder::new(&binary_path);
-        cmd.env("TERM", "xterm-256color");
-        cmd.env("COLUMNS", terminal_width.to_string());
-        cmd.env("LINES", terminal_height.to_string());
-        for arg in &self.config.args {
-            cmd.arg(arg);
-        }
+        let (cmd, binary_path) = build_command(
+            &self.config.binary_path.to_string_lossy(),
+            &self.config.args,
+            (terminal_width, terminal_height),
+            &self.config.env,
+            self.config.hermetic,
+            &self.config.limits,
+        )?;
 
         let mut child = pair
             .slave
             .spawn_command(cmd)
             .map_err(|e| SnapshotError::Capture(format!("Failed to spawn '{}': {}", binary_path, e)))?;
+        apply_limits(child.as_mut(), &self.config.limits, &binary_path)?;
         drop(pair.slave);
 
         let _ = pair.master.resize(PtySize {
@@ -395,42 +494,82 @@ impl CaptureBackend for PtyBackend {
         });
 
         // Wait for initial render
-        drain_until_quiet(&rx, &mut parser, Duration::from_millis(180));
+        drain_until_quiet(&rx, &mut parser, &mut writer, Duration::from_millis(180));
 
         // Send inputs
-        for input in &self.config.inputs {
+        for (step, input) in self.config.inputs.iter().enumerate() {
+            let (delay, input) = input.delay_and_action();
+            if !delay.is_zero() {
+                thread::sleep(delay);
+            }
             match input {
                 InputAction::SendString(text) => {
                     let _ = writer.write_all(text.as_bytes());
-                    let _ = writer.write_all(&[b'\r']);
+                    let _ = writer.write_all(b"\r");
                     let _ = writer.flush();
-                    drain_until_quiet(&rx, &mut parser, Duration::from_millis(180));
+                    drain_until_quiet(&rx, &mut parser, &mut writer, Duration::from_millis(180));
                 }
                 InputAction::SendKey(key) => {
-                    let sequence = key_to_sequence(key);
+                    let sequence = key_to_sequence(key, parser.terminal().key_flags());
                     let _ = writer.write_all(&sequence);
                     let _ = writer.flush();
-                    drain_until_quiet(&rx, &mut parser, Duration::from_millis(180));
+                    drain_until_quiet(&rx, &mut parser, &mut writer, Duration::from_millis(180));
+                }
+                InputAction::Raw(bytes) => {
+                    let _ = writer.write_all(bytes);
+                    let _ = writer.flush();
+                    drain_until_quiet(&rx, &mut parser, &mut writer, Duration::from_millis(180));
+                }
+                InputAction::Resize(cols, rows) => {
+                    resize_pty(pair.master.as_ref(), &mut parser, *cols, *rows)?;
+                    drain_until_quiet(&rx, &mut parser, &mut writer, Duration::from_millis(180));
+                }
+                InputAction::MouseClick { .. } | InputAction::MouseScroll { .. } => {
+                    if let Some(mouse) = input.mouse_input() {
+                        let _ = writer.write_all(&parser.terminal().mouse_report(mouse));
+                        let _ = writer.flush();
+                    }
+                    drain_until_quiet(&rx, &mut parser, &mut writer, Duration::from_millis(180));
+                }
+                InputAction::WaitForText(_) | InputAction::WaitStable { .. } => {
+                    let condition = input.wait_condition().expect("wait action");
+                    if wait_for_condition(&rx, &mut parser, &mut writer, &condition, DEFAULT_WAIT_TIMEOUT).timed_out {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return Err(SnapshotError::Capture(timeout_message(step + 1, &condition, DEFAULT_WAIT_TIMEOUT, parser.terminal())));
+                    }
+                    drain_until_quiet(&rx, &mut parser, &mut writer, Duration::from_millis(180));
+                }
+                InputAction::Signal(sig) => {
+                    send_signal(child.process_id(), *sig)
+                        .map_err(|e| SnapshotError::Capture(format!("Failed to send {}: {}", sig, e)))?;
+                    drain_until_quiet(&rx, &mut parser, &mut writer, Duration::from_millis(180));
+                }
+                InputAction::Eof => {
+                    writer = Box::new(ClosedWriter);
+                    drain_until_quiet(&rx, &mut parser, &mut writer, Duration::from_millis(180));
+                }
+                InputAction::Sleep(_) | InputAction::Delayed { .. } => {
+                    drain_until_quiet(&rx, &mut parser, &mut writer, Duration::from_millis(180));
                 }
             }
         }
 
         // Final drain and cleanup
-        drain_until_quiet(&rx, &mut parser, Duration::from_millis(180));
+        drain_until_quiet(&rx, &mut parser, &mut writer, Duration::from_millis(180));
         drop(writer);
 
         // Wait for process with timeout
         let start = std::time::Instant::now();
         let max_wait = Duration::from_secs(3);
+        let closed_writer = &mut std::io::sink();
         while start.elapsed() < max_wait {
             if let Ok(Some(_)) = child.try_wait() {
-                drain_until_quiet(&rx, &mut parser, Duration::from_millis(180));
+                drain_until_quiet(&rx, &mut parser, closed_writer, Duration::from_millis(180));
                 break;
             }
             if let Ok(chunk) = rx.recv_timeout(Duration::from_millis(60)) {
-                for byte in chunk {
-                    parser.process_byte(byte);
-                }
+                ingest_chunk(&chunk, &mut parser, closed_writer);
             }
         }
 
@@ -440,20 +579,32 @@ impl CaptureBackend for PtyBackend {
         }
 
         // Render to image
-        let img = parser.terminal().render_to_image();
+        let img = parser.terminal().render_to_image_with(&self.config.render);
         let mut png_bytes = Vec::new();
         img.write_to(&mut Cursor::new(&mut png_bytes), image::ImageFormat::Png)
             .map_err(|e| SnapshotError::Capture(format!("Failed to encode PNG: {}", e)))?;
 
         Ok(CaptureResult {
             image_data: png_bytes,
-            width: u32::from(terminal_width) * CELL_WIDTH,
-            height: u32::from(terminal_height) * CELL_HEIGHT,
+            width: img.width(),
+            height: img.height(),
             metadata: Some(serde_json::json!({
                 "terminal_width": terminal_width,
                 "terminal_height": terminal_height,
                 "binary": binary_path,
+                "bell_count": parser.terminal().bell_count,
+                "visual_bell_count": parser.terminal().visual_bell_count,
+                "cursor": parser.terminal().cursor_state(),
+                "hyperlinks": parser.terminal().hyperlinks(),
+                "unrenderable_codepoints": parser
+                    .terminal()
+                    .unrenderable_chars(&self.config.render.glyph_fallback)
+                    .into_iter()
+                    .map(codepoint_label)
+                    .collect::<Vec<_>>(),
             })),
+            text: Some(parser.terminal().to_text()),
+            grid: Some(parser.terminal().to_grid()),
         })
     }
 
@@ -476,6 +627,7 @@ impl CaptureBackend for PtyBackend {
 fn drain_until_quiet(
     rx: &mpsc::Receiver<Vec<u8>>,
     parser: &mut super::pty::Vt100Parser,
+    writer: &mut dyn Write,
     quiet_window: Duration,
 ) {
     use std::time::Instant;
@@ -484,9 +636,7 @@ fn drain_until_quiet(
     loop {
         match rx.recv_timeout(Duration::from_millis(50)) {
             Ok(chunk) => {
-                for byte in chunk {
-                    parser.process_byte(byte);
-                }
+                ingest_chunk(&chunk, parser, writer);
                 last_activity = Instant::now();
             }
             Err(mpsc::RecvTimeoutError::Timeout) => {
@@ -499,27 +649,65 @@ fn drain_until_quiet(
     }
     // Final drain of any buffered data
     while let Ok(chunk) = rx.try_recv() {
-        for byte in chunk {
-            parser.process_byte(byte);
-        }
+        ingest_chunk(&chunk, parser, writer);
     }
 }
 
-/// Convert key name to VT100 sequence
-fn key_to_sequence(key: &str) -> Vec<u8> {
-    match key.to_lowercase().as_str() {
-        "up" => b"\x1b[A".to_vec(),
-        "down" => b"\x1b[B".to_vec(),
-        "right" => b"\x1b[C".to_vec(),
-        "left" => b"\x1b[D".to_vec(),
-        "enter" => vec![b'\r'],
-        "space" => vec![b' '],
-        "tab" => vec![b'\t'],
-        "backspace" => vec![0x08],
-        "escape" | "esc" => vec![0x1b],
-        other if other.len() == 1 => other.as_bytes().to_vec(),
-        other => other.as_bytes().to_vec(),
+/// Convert key name to VT100 sequence, CSI-u encoded if the application enabled kitty keyboard flags
+fn key_to_sequence(key: &str, key_flags: u8) -> Vec<u8> {
+    kitty_key_sequence(key, key_flags).unwrap_or_else(|| key.as_bytes().to_vec())
+}
+
+// =============================================================================
+// Capture utilities (merged from capture.rs)
+// =============================================================================
+
+use std::fs;
+use crate::snapshot::utils::{
+    generate_filename, generate_timestamp, write_description, write_manifest, write_screen_text,
+};
+use crate::snapshot::{Snapshot, SnapshotConfig};
+
+/// Capture a snapshot using the provided backend and save it according to config.
+pub fn capture_with_backend(
+    backend: &mut dyn CaptureBackend,
+    config: &SnapshotConfig,
+) -> SnapshotResult<Snapshot> {
+    fs::create_dir_all(&config.output_dir)?;
+
+    let timestamp = generate_timestamp();
+    let filename = generate_filename(backend.source_type(), &timestamp);
+    let image_path = config.output_dir.join(&filename);
+
+    let result = backend.capture()?;
+    fs::write(&image_path, &result.image_data)?;
+
+    let metadata = if config.include_metadata {
+        let mut meta = crate::snapshot::utils::create_base_metadata(
+            result.width,
+            result.height,
+            backend.source_type(),
+            &timestamp,
+        );
+        if let Some(serde_json::Value::Object(extra)) = result.metadata {
+            for (k, v) in extra {
+                meta.insert(k, v);
+            }
+        }
+        Some(serde_json::Value::Object(meta))
+    } else {
+        None
+    };
+
+    let snapshot = Snapshot::new(image_path.clone(), backend.source_type().to_string(), metadata);
+
+    write_manifest(&snapshot, config)?;
+    write_description(&snapshot, config)?;
+    if let Some(text) = &result.text {
+        write_screen_text(&snapshot, config, text)?;
     }
+
+    Ok(snapshot)
 }
 
 #[cfg(test)]
@@ -609,53 +797,34 @@ mod tests {
         assert_eq!(fb2.get_pixel(0, 0), [100, 150, 200]);
         assert_eq!(fb2.get_pixel(10, 10), [255, 0, 0]);
     }
-}
-
-// =============================================================================
-// Capture utilities (merged from capture.rs)
-// =============================================================================
-
-use std::fs;
-use crate::snapshot::utils::{
-    generate_filename, generate_timestamp, write_description, write_manifest,
-};
-use crate::snapshot::{Snapshot, SnapshotConfig};
-
-/// Capture a snapshot using the provided backend and save it according to config.
-pub fn capture_with_backend(
-    backend: &mut dyn CaptureBackend,
-    config: &SnapshotConfig,
-) -> SnapshotResult<Snapshot> {
-    fs::create_dir_all(&config.output_dir)?;
-
-    let timestamp = generate_timestamp();
-    let filename = generate_filename(backend.source_type(), &timestamp);
-    let image_path = config.output_dir.join(&filename);
-
-    let result = backend.capture()?;
-    fs::write(&image_path, &result.image_data)?;
-
-    let metadata = if config.include_metadata {
-        let mut meta = crate::snapshot::utils::create_base_metadata(
-            result.width,
-            result.height,
-            backend.source_type(),
-            &timestamp,
-        );
-        if let Some(serde_json::Value::Object(extra)) = result.metadata {
-            for (k, v) in extra {
-                meta.insert(k, v);
-            }
-        }
-        Some(serde_json::Value::Object(meta))
-    } else {
-        None
-    };
 
-    let snapshot = Snapshot::new(image_path.clone(), backend.source_type().to_string(), metadata);
-
-    write_manifest(&snapshot, config)?;
-    write_description(&snapshot, config)?;
-
-    Ok(snapshot)
+    #[test]
+    fn test_crop_cells_keeps_a_rectangle() {
+        use crate::snapshot::{CELL_HEIGHT, CELL_WIDTH};
+        let mut fb = MockFramebuffer::new(3 * CELL_WIDTH, 2 * CELL_HEIGHT);
+        fb.draw_rect(2 * CELL_WIDTH, CELL_HEIGHT, CELL_WIDTH, CELL_HEIGHT, [255, 0, 0]);
+        let cropped = fb.capture().unwrap().crop_cells(CellRect { cols: 5, rows: 1, x: 1, y: 1 }).unwrap();
+        // Clamped to the two columns left of the offset
+        assert_eq!((cropped.width, cropped.height), (2 * CELL_WIDTH, CELL_HEIGHT));
+        let image = MockFramebuffer::from_png_bytes(&cropped.image_data).unwrap();
+        assert_eq!(image.get_pixel(0, 0), [0, 0, 0]);
+        assert_eq!(image.get_pixel(CELL_WIDTH, 0), [255, 0, 0]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_capture_writes_screen_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SnapshotConfig {
+            output_dir: dir.path().to_path_buf(),
+            include_text: true,
+            ..SnapshotConfig::default()
+        };
+        let mut backend = PtyBackend::new(PtyBackendConfig::new("/bin/echo").arg("hello from the pty"));
+        let snapshot = capture_with_backend(&mut backend, &config).unwrap();
+        let text = fs::read_to_string(snapshot.image_path.with_extension("screen.txt")).unwrap();
+        assert!(text.starts_with("hello from the pty"));
+        // The description keeps its own file
+        assert!(snapshot.image_path.with_extension("txt").exists());
+    }
 }
@@ -0,0 +1,246 @@
+//! Cropping screenshots to a band of rows or a rectangle of cells.
+//!
+//! Status lines and title bars are often the only part of a screen a test
+//! cares about. A [`Crop`] selects part of the cell grid when rendering,
+//! so cropped screenshots are cheaper to store and send to a VLM:
+//!
+//! - `top:N` / `bottom:N`: the first or last `N` rows (`top` and `bottom` alone mean one row)
+//! - `center:N`: the middle `N` rows (`center` alone means the middle third)
+//! - `rows:A-B`: rows `A` to `B`, 0-based and inclusive
+//! - `COLSxROWS+X+Y` or `X,Y,COLS,ROWS`: a [`CellRect`] of `COLS` by `ROWS` cells from column `X`, row `Y`
+//!
+//! A capture that was already taken is cropped with `crop_cells` on
+//! [`CaptureResult`](super::backend::CaptureResult) or
+//! [`StateCaptureResult`](super::pty::StateCaptureResult).
+
+use std::ops::Range;
+
+use super::pty::{CELL_HEIGHT, CELL_WIDTH};
+use super::{SnapshotError, SnapshotResult};
+
+/// A rectangle of cells: `cols` by `rows` cells whose top-left cell is column `x`, row `y` (0-based)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellRect {
+    pub cols: u16,
+    pub rows: u16,
+    pub x: u16,
+    pub y: u16,
+}
+
+impl CellRect {
+    /// Parse `COLSxROWS+X+Y`, e.g. `80x1+0+59`, where the offset may be left
+    /// out (`+0+0`), or `X,Y,COLS,ROWS`, e.g. `0,59,80,1`
+    pub fn parse(spec: &str) -> Result<CellRect, String> {
+        let invalid = || format!("invalid cell rectangle '{}' (expected COLSxROWS+X+Y or X,Y,COLS,ROWS)", spec);
+        let spec = spec.trim();
+        if spec.contains(',') {
+            let numbers: Vec<_> = spec.split(',').map(|n| n.trim().parse::<u16>()).collect();
+            return match numbers[..] {
+                [Ok(x), Ok(y), Ok(cols), Ok(rows)] if cols > 0 && rows > 0 => Ok(CellRect { cols, rows, x, y }),
+                _ => Err(invalid()),
+            };
+        }
+        let (size, offset) = spec.split_once('+').unwrap_or((spec, "0+0"));
+        let (cols, rows) = size.to_lowercase().split_once('x').map(|(c, r)| (c.trim().parse(), r.trim().parse())).ok_or_else(invalid)?;
+        let (x, y) = offset.split_once('+').map(|(x, y)| (x.trim().parse(), y.trim().parse())).ok_or_else(invalid)?;
+        match (cols, rows, x, y) {
+            (Ok(cols), Ok(rows), Ok(x), Ok(y)) if cols > 0 && rows > 0 => Ok(CellRect { cols, rows, x, y }),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Columns of a screen `width` columns wide that are kept; never empty for a non-empty screen
+    pub fn col_range(self, width: u32) -> Range<u32> {
+        clamped_span(self.x, self.cols, width)
+    }
+
+    /// Rows of a screen `height` rows high that are kept; never empty for a non-empty screen
+    pub fn row_range(self, height: u32) -> Range<u32> {
+        clamped_span(self.y, self.rows, height)
+    }
+}
+
+impl std::fmt::Display for CellRect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}+{}+{}", self.cols, self.rows, self.x, self.y)
+    }
+}
+
+/// `len` cells from `start`, moved and shortened to fit in `0..size`
+fn clamped_span(start: u16, len: u16, size: u32) -> Range<u32> {
+    let start = u32::from(start).min(size.saturating_sub(1));
+    start..(start + u32::from(len)).min(size).max(start + 1).min(size.max(1))
+}
+
+/// Crop a PNG screenshot to a rectangle of cells, clamped to the image.
+///
+/// Returns the PNG data and its width and height in pixels.
+pub fn crop_png_cells(png: &[u8], rect: CellRect) -> SnapshotResult<(Vec<u8>, u32, u32)> {
+    let image = image::load_from_memory(png)
+        .map_err(|e| SnapshotError::Capture(format!("Failed to decode capture: {}", e)))?
+        .to_rgb8();
+    let cols = rect.col_range(image.width() / CELL_WIDTH);
+    let rows = rect.row_range(image.height() / CELL_HEIGHT);
+    let (width, height) = (cols.len() as u32 * CELL_WIDTH, rows.len() as u32 * CELL_HEIGHT);
+    let cropped = image::imageops::crop_imm(&image, cols.start * CELL_WIDTH, rows.start * CELL_HEIGHT, width, height).to_image();
+    let mut data = Vec::new();
+    cropped
+        .write_to(&mut std::io::Cursor::new(&mut data), image::ImageFormat::Png)
+        .map_err(|e| SnapshotError::Capture(format!("Failed to encode PNG: {}", e)))?;
+    Ok((data, width, height))
+}
+
+/// A band of rows or a rectangle of cells to keep when rendering
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crop {
+    /// The first rows
+    Top(u16),
+    /// The last rows
+    Bottom(u16),
+    /// Rows around the middle; `None` keeps the middle third
+    Center(Option<u16>),
+    /// Rows `first..=last`
+    Rows { first: u16, last: u16 },
+    /// A rectangle of cells
+    Rect(CellRect),
+}
+
+impl Crop {
+    /// Parse a crop preset such as `bottom:3`, `top`, `center:10` or `rows:2-5`, or a `COLSxROWS+X+Y` rectangle
+    pub fn parse(spec: &str) -> Result<Crop, String> {
+        if spec.trim().starts_with(|c: char| c.is_ascii_digit()) {
+            return CellRect::parse(spec).map(Crop::Rect);
+        }
+        let spec = spec.trim().to_lowercase();
+        let (name, value) = match spec.split_once(':') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (spec.as_str(), None),
+        };
+        let count = |value: Option<&str>| match value {
+            None => Ok(None),
+            Some(n) => n.parse::<u16>().ok().filter(|n| *n > 0).map(Some).ok_or(()),
+        };
+        let crop = match name {
+            "top" => count(value).map(|n| Crop::Top(n.unwrap_or(1))),
+            "bottom" => count(value).map(|n| Crop::Bottom(n.unwrap_or(1))),
+            "center" => count(value).map(Crop::Center),
+            "rows" => value
+                .and_then(|range| range.split_once('-'))
+                .and_then(|(first, last)| Some((first.trim().parse().ok()?, last.trim().parse().ok()?)))
+                .filter(|(first, last)| first <= last)
+                .map(|(first, last)| Crop::Rows { first, last })
+                .ok_or(()),
+            _ => Err(()),
+        };
+        crop.map_err(|_| format!("invalid crop '{}' (expected top:N, bottom:N, center[:N], rows:A-B or COLSxROWS+X+Y)", spec))
+    }
+
+    /// Rows of a screen `height` rows high that are kept; never empty for a non-empty screen
+    pub fn rows(self, height: u32) -> Range<u32> {
+        let clamp = |n: u16| u32::from(n).clamp(1, height.max(1));
+        let range = match self {
+            Crop::Top(n) => 0..clamp(n),
+            Crop::Bottom(n) => height.saturating_sub(clamp(n))..height,
+            Crop::Center(n) => {
+                let n = n.map_or((height / 3).max(1), clamp);
+                let start = (height - n.min(height)) / 2;
+                start..start + n.min(height)
+            }
+            Crop::Rows { first, last } => {
+                let first = u32::from(first).min(height.saturating_sub(1));
+                first..(u32::from(last) + 1).clamp(first + 1, height.max(1))
+            }
+            Crop::Rect(rect) => rect.row_range(height),
+        };
+        range.start..range.end.min(height)
+    }
+
+    /// Columns of a screen `width` columns wide that are kept: all of them unless the crop is a rectangle
+    pub fn cols(self, width: u32) -> Range<u32> {
+        match self {
+            Crop::Rect(rect) => rect.col_range(width),
+            _ => 0..width,
+        }
+    }
+
+    /// The part of a screen's text (one line per row, as from `to_text`) this crop keeps
+    pub fn text(self, text: &str) -> String {
+        let lines: Vec<&str> = text.lines().collect();
+        let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0) as u32;
+        let (rows, cols) = (self.rows(lines.len() as u32), self.cols(width));
+        lines[rows.start as usize..rows.end as usize]
+            .iter()
+            .map(|line| {
+                let kept: String = line.chars().skip(cols.start as usize).take(cols.len()).collect();
+                kept.trim_end().to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl std::fmt::Display for Crop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Crop::Top(n) => write!(f, "top:{}", n),
+            Crop::Bottom(n) => write!(f, "bottom:{}", n),
+            Crop::Center(Some(n)) => write!(f, "center:{}", n),
+            Crop::Center(None) => f.write_str("center"),
+            Crop::Rows { first, last } => write!(f, "rows:{}-{}", first, last),
+            Crop::Rect(rect) => rect.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_presets() {
+        assert_eq!(Crop::parse("bottom:3"), Ok(Crop::Bottom(3)));
+        assert_eq!(Crop::parse("TOP"), Ok(Crop::Top(1)));
+        assert_eq!(Crop::parse("center"), Ok(Crop::Center(None)));
+        assert_eq!(Crop::parse("rows: 2-5"), Ok(Crop::Rows { first: 2, last: 5 }));
+        for bad in ["bottom:0", "left:3", "rows:5-2", "top:x"] {
+            assert!(Crop::parse(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(Crop::parse("rows:2-5").unwrap().to_string(), "rows:2-5");
+    }
+
+    #[test]
+    fn test_rows_are_clamped_to_the_screen() {
+        assert_eq!(Crop::Top(2).rows(24), 0..2);
+        assert_eq!(Crop::Bottom(3).rows(24), 21..24);
+        assert_eq!(Crop::Bottom(30).rows(24), 0..24);
+        assert_eq!(Crop::Center(None).rows(24), 8..16);
+        assert_eq!(Crop::Center(Some(4)).rows(24), 10..14);
+        assert_eq!(Crop::Rows { first: 20, last: 40 }.rows(24), 20..24);
+        assert_eq!(Crop::Rows { first: 30, last: 40 }.rows(24), 23..24);
+    }
+
+    #[test]
+    fn test_cell_rectangles() {
+        let rect = CellRect { cols: 80, rows: 1, x: 0, y: 59 };
+        assert_eq!(Crop::parse("80x1+0+59"), Ok(Crop::Rect(rect)));
+        assert_eq!(Crop::parse("10X2"), Ok(Crop::Rect(CellRect { cols: 10, rows: 2, x: 0, y: 0 })));
+        assert_eq!(CellRect::parse("0, 59, 80, 1"), Ok(rect));
+        for bad in ["0x1+0+0", "80x1+0", "80+0+0", "80x1+a+0", "0,0,80", "0,0,0,1"] {
+            assert!(Crop::parse(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(Crop::Rect(rect).to_string(), "80x1+0+59");
+        // Clamped to a 120x40 screen
+        assert_eq!((Crop::Rect(rect).cols(120), Crop::Rect(rect).rows(40)), (0..80, 39..40));
+        let wide = CellRect { cols: 50, rows: 3, x: 100, y: 0 };
+        assert_eq!(wide.col_range(120), 100..120);
+        assert_eq!(Crop::Bottom(2).cols(120), 0..120);
+    }
+
+    #[test]
+    fn test_crop_text() {
+        let screen = "File  Edit\nhello world\n\n-- INSERT --  1,1";
+        assert_eq!(Crop::Bottom(1).text(screen), "-- INSERT --  1,1");
+        assert_eq!(Crop::Top(2).text(screen), "File  Edit\nhello world");
+        assert_eq!(Crop::Rect(CellRect { cols: 5, rows: 1, x: 6, y: 1 }).text(screen), "world");
+    }
+}
@@ -0,0 +1,756 @@
+//! Pixel and cell differences between two captures.
+//!
+//! [`diff_images`] compares two PNGs pixel by pixel and reports how much
+//! changed, where, and which terminal cells the changes fall in, together
+//! with an overlay image: the new screenshot dimmed to gray with the changed
+//! pixels painted red, more saturated the more they changed. Regressions show
+//! up at a glance without asking a VLM.
+//!
+//! Screenshots of different sizes are compared on a canvas as large as both;
+//! pixels only one of them covers count as changed.
+//!
+//! Pixels are noisy for terminals: one recolored word changes hundreds of
+//! them. [`TerminalGrid::diff`] compares two [`TerminalGrid`]s cell by cell
+//! instead and reports a [`CellDiff`]: which cells gained or lost text and
+//! which changed character or style, with their coordinates.
+//!
+//! [`diff_directories`] runs both over two whole sessions, pairing states by
+//! similarity so that an inserted or removed state does not shift the rest,
+//! and [`DirectoryDiff::to_markdown`] summarizes the result for a pull request.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::snapshot::run_with_inputs;
+//!
+//! let captures = run_with_inputs("./my-tui", &[], &["down".to_string()], 100).unwrap();
+//! let diff = captures[0].pixel_diff(&captures[1]).unwrap();
+//! println!("{:.1}% changed in {} cells", diff.ratio * 100.0, diff.changed_cells.len());
+//! std::fs::write("heatmap.png", &diff.overlay).unwrap();
+//! ```
+
+use image::{GenericImage, Rgb, RgbImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::grid::{GridCell, TerminalGrid};
+use crate::compare::{align_session_dirs, list_state_images, state_step, StateAlignment, DEFAULT_MATCH_THRESHOLD};
+use super::pty::{CELL_HEIGHT, CELL_WIDTH};
+use super::{SnapshotError, SnapshotResult};
+
+/// A rectangle of pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PixelRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// How two screenshots differ
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PixelDiff {
+    /// Width of the compared canvas
+    pub width: u32,
+    /// Height of the compared canvas
+    pub height: u32,
+    /// Pixels whose channels differ by more than the tolerance
+    pub changed_pixels: u64,
+    /// Fraction of the canvas that changed (0 = identical, 1 = every pixel)
+    pub ratio: f64,
+    /// Smallest rectangle holding every changed pixel
+    pub bounds: Option<PixelRect>,
+    /// Terminal cells holding changed pixels, as (row, column)
+    pub changed_cells: Vec<(u32, u32)>,
+    /// PNG of the new screenshot dimmed to gray with changed pixels in red
+    #[serde(skip)]
+    pub overlay: Vec<u8>,
+}
+
+impl PixelDiff {
+    /// Whether no pixel changed
+    pub fn is_identical(&self) -> bool {
+        self.changed_pixels == 0
+    }
+
+    /// One-line summary, e.g. `1520 of 614400 pixels differ (0.25%) in 3 cells, within 8x40 at (16, 120)`
+    pub fn summary(&self) -> String {
+        match self.bounds {
+            None => "screenshots are identical".to_string(),
+            Some(rect) => format!(
+                "{} of {} pixels differ ({:.2}%) in {} cells, within {}x{} at ({}, {})",
+                self.changed_pixels,
+                u64::from(self.width) * u64::from(self.height),
+                self.ratio * 100.0,
+                self.changed_cells.len(),
+                rect.width,
+                rect.height,
+                rect.x,
+                rect.y
+            ),
+        }
+    }
+}
+
+/// Compare two PNG screenshots.
+///
+/// A pixel counts as changed when any channel differs by more than
+/// `tolerance` (0 = exact), which absorbs anti-aliasing from other renderers.
+pub fn diff_images(old_png: &[u8], new_png: &[u8], tolerance: u8) -> SnapshotResult<PixelDiff> {
+    let decode = |png: &[u8], which: &str| {
+        image::load_from_memory(png)
+            .map(|image| image.to_rgba8())
+            .map_err(|e| SnapshotError::Capture(format!("Failed to decode {} screenshot: {}", which, e)))
+    };
+    let (old, new) = (decode(old_png, "old")?, decode(new_png, "new")?);
+    let width = old.width().max(new.width());
+    let height = old.height().max(new.height());
+
+    let mut overlay = RgbImage::new(width, height);
+    let mut changed_pixels = 0u64;
+    let mut changed_cells = BTreeSet::new();
+    let (mut min, mut max) = ((u32::MAX, u32::MAX), (0, 0));
+    for y in 0..height {
+        for x in 0..width {
+            let delta = match (pixel_at(&old, x, y), pixel_at(&new, x, y)) {
+                (Some(a), Some(b)) => a.iter().zip(b).map(|(a, b)| a.abs_diff(b)).max().unwrap_or(0),
+                _ => u8::MAX,
+            };
+            let base = pixel_at(&new, x, y).or_else(|| pixel_at(&old, x, y)).unwrap_or_default();
+            let color = if delta > tolerance {
+                changed_pixels += 1;
+                changed_cells.insert((y / CELL_HEIGHT, x / CELL_WIDTH));
+                min = (min.0.min(x), min.1.min(y));
+                max = (max.0.max(x), max.1.max(y));
+                let fade = (u8::MAX - delta) / 2;
+                [u8::MAX, fade, fade]
+            } else {
+                // Dimmed gray keeps the layout readable under the red
+                let luma = (u32::from(base[0]) * 299 + u32::from(base[1]) * 587 + u32::from(base[2]) * 114) / 1000;
+                let gray = (luma * 2 / 5) as u8;
+                [gray, gray, gray]
+            };
+            overlay.put_pixel(x, y, Rgb(color));
+        }
+    }
+
+    let mut png = Vec::new();
+    overlay
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| SnapshotError::Capture(format!("Failed to encode PNG: {}", e)))?;
+    let total = u64::from(width) * u64::from(height);
+    Ok(PixelDiff {
+        width,
+        height,
+        changed_pixels,
+        ratio: if total == 0 { 0.0 } else { changed_pixels as f64 / total as f64 },
+        bounds: (changed_pixels > 0).then(|| PixelRect {
+            x: min.0,
+            y: min.1,
+            width: max.0 - min.0 + 1,
+            height: max.1 - min.1 + 1,
+        }),
+        changed_cells: changed_cells.into_iter().collect(),
+        overlay: png,
+    })
+}
+
+/// How a cell differs between two grids
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CellChangeKind {
+    /// A blank cell now shows a character
+    Added,
+    /// A character was cleared
+    Removed,
+    /// The character or its style changed
+    Changed,
+}
+
+/// Property of a cell that differs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CellField {
+    Char,
+    Fg,
+    Bg,
+    Attrs,
+    UnderlineColor,
+    Link,
+}
+
+/// One cell that differs between two grids
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CellChange {
+    /// Row (0-based)
+    pub row: u32,
+    /// Column (0-based)
+    pub col: u32,
+    pub kind: CellChangeKind,
+    /// Properties that differ
+    pub fields: Vec<CellField>,
+    /// The cell before, if the old grid covers it
+    pub before: Option<GridCell>,
+    /// The cell after, if the new grid covers it
+    pub after: Option<GridCell>,
+}
+
+/// Differences between two terminal grids, cell by cell
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CellDiff {
+    /// Cells that gained a character
+    pub added: usize,
+    /// Cells whose character was cleared
+    pub removed: usize,
+    /// Cells whose character or style changed
+    pub changed: usize,
+    /// Rows holding at least one change
+    pub rows: Vec<u32>,
+    /// Whether the cursor moved or changed appearance
+    pub cursor_changed: bool,
+    /// Every differing cell, row by row
+    pub changes: Vec<CellChange>,
+}
+
+impl CellDiff {
+    /// Whether the grids show the same thing
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && !self.cursor_changed
+    }
+
+    /// One-line summary, e.g. `3 added, 1 removed, 12 changed cells in 2 rows`
+    pub fn summary(&self) -> String {
+        if self.changes.is_empty() {
+            return if self.cursor_changed { "only the cursor changed" } else { "grids are identical" }.to_string();
+        }
+        format!(
+            "{} added, {} removed, {} changed cells in {} rows",
+            self.added,
+            self.removed,
+            self.changed,
+            self.rows.len()
+        )
+    }
+}
+
+impl TerminalGrid {
+    /// Compare this grid to a later one, cell by cell.
+    ///
+    /// A cell only one grid covers (after a resize) counts as added or
+    /// removed when it holds a character and is skipped when blank.
+    pub fn diff(&self, later: &TerminalGrid) -> CellDiff {
+        let mut diff = CellDiff {
+            cursor_changed: self.cursor != later.cursor,
+            ..CellDiff::default()
+        };
+        let height = self.height.max(later.height);
+        let width = self.width.max(later.width);
+        for row in 0..height {
+            for col in 0..width {
+                let (before, after) = (self.cell(row, col), later.cell(row, col));
+                let fields = match (before, after) {
+                    (Some(a), Some(b)) => changed_fields(a, b),
+                    (Some(cell), None) | (None, Some(cell)) if !is_blank(cell) => vec![CellField::Char],
+                    _ => continue,
+                };
+                if fields.is_empty() {
+                    continue;
+                }
+                let kind = match (before.is_none_or(is_blank), after.is_none_or(is_blank)) {
+                    (true, false) => CellChangeKind::Added,
+                    (false, true) => CellChangeKind::Removed,
+                    _ => CellChangeKind::Changed,
+                };
+                match kind {
+                    CellChangeKind::Added => diff.added += 1,
+                    CellChangeKind::Removed => diff.removed += 1,
+                    CellChangeKind::Changed => diff.changed += 1,
+                }
+                if diff.rows.last() != Some(&row) {
+                    diff.rows.push(row);
+                }
+                diff.changes.push(CellChange {
+                    row,
+                    col,
+                    kind,
+                    fields,
+                    before: before.cloned(),
+                    after: after.cloned(),
+                });
+            }
+        }
+        diff
+    }
+}
+
+/// Whether a cell shows no character
+fn is_blank(cell: &GridCell) -> bool {
+    cell.ch == ' '
+}
+
+/// Properties that differ between two cells, in [`CellField`] order
+fn changed_fields(a: &GridCell, b: &GridCell) -> Vec<CellField> {
+    [
+        (a.ch != b.ch).then_some(CellField::Char),
+        (a.fg != b.fg).then_some(CellField::Fg),
+        (a.bg != b.bg).then_some(CellField::Bg),
+        (a.attrs != b.attrs).then_some(CellField::Attrs),
+        (a.underline_color != b.underline_color).then_some(CellField::UnderlineColor),
+        (a.link != b.link).then_some(CellField::Link),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Whether a state exists on both sides and differs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateDiffStatus {
+    Unchanged,
+    Changed,
+    /// Only in the current directory
+    Added,
+    /// Only in the baseline directory
+    Removed,
+}
+
+/// Comparison of one state between two directories
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateDiff {
+    /// Path relative to the compared directories, without extension (e.g. `120x40/state_1_down`)
+    pub name: String,
+    /// Baseline state compared with this one, when its name differs because
+    /// a state was inserted or removed earlier in the session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub baseline_name: Option<String>,
+    pub status: StateDiffStatus,
+    /// Fraction of pixels that changed, or of cells when there are no screenshots
+    pub ratio: f64,
+    /// Pixel difference, when both sides have a screenshot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pixels: Option<PixelDiff>,
+    /// Cell difference, when both sides have a `.grid.json`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cells: Option<CellDiff>,
+    /// Annotated diff image written for the state, relative to the summary files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<PathBuf>,
+}
+
+/// Comparison of every state in two directories
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryDiff {
+    pub baseline: PathBuf,
+    pub current: PathBuf,
+    /// States by name
+    pub states: Vec<StateDiff>,
+}
+
+impl DirectoryDiff {
+    /// States with a given status
+    pub fn count(&self, status: StateDiffStatus) -> usize {
+        self.states.iter().filter(|state| state.status == status).count()
+    }
+
+    /// States that were added, removed, or changed by more than `threshold`
+    pub fn failures(&self, threshold: f64) -> Vec<&StateDiff> {
+        self.states
+            .iter()
+            .filter(|state| match state.status {
+                StateDiffStatus::Unchanged => false,
+                StateDiffStatus::Changed => state.ratio > threshold,
+                StateDiffStatus::Added | StateDiffStatus::Removed => true,
+            })
+            .collect()
+    }
+
+    /// Markdown summary: counts, then a table of the states that differ
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# Capture diff\n\n");
+        out.push_str(&format!(
+            "`{}` against baseline `{}`: {} changed, {} added, {} removed and {} unchanged states.\n",
+            self.current.display(),
+            self.baseline.display(),
+            self.count(StateDiffStatus::Changed),
+            self.count(StateDiffStatus::Added),
+            self.count(StateDiffStatus::Removed),
+            self.count(StateDiffStatus::Unchanged)
+        ));
+        let differing: Vec<_> = self.states.iter().filter(|state| state.status != StateDiffStatus::Unchanged).collect();
+        if differing.is_empty() {
+            return out;
+        }
+        out.push_str("\n| State | Status | Changed | Details | Diff |\n|-------|--------|---------|---------|------|\n");
+        for state in differing {
+            let status = match state.status {
+                StateDiffStatus::Unchanged => "unchanged",
+                StateDiffStatus::Changed => "changed",
+                StateDiffStatus::Added => "added",
+                StateDiffStatus::Removed => "removed",
+            };
+            let details = state
+                .cells
+                .as_ref()
+                .map(CellDiff::summary)
+                .or_else(|| state.pixels.as_ref().map(PixelDiff::summary))
+                .unwrap_or_default();
+            let image = state
+                .image
+                .as_ref()
+                .map(|path| format!("![{}]({})", state.name, path.display()))
+                .unwrap_or_default();
+            let name = match &state.baseline_name {
+                Some(baseline) => format!("`{}` → `{}`", baseline, state.name),
+                None => format!("`{}`", state.name),
+            };
+            out.push_str(&format!(
+                "| {} | {} | {:.2}% | {} | {} |\n",
+                name,
+                status,
+                state.ratio * 100.0,
+                details,
+                image
+            ));
+        }
+        out
+    }
+}
+
+/// Compare two capture directories (run sessions, suite outputs, golden
+/// directories), pairing `.png` and `.grid.json` files by their path
+/// relative to each directory.
+///
+/// `state_<N>_*` screenshots of a directory both sides have are paired with
+/// [`align_session_dirs`] instead, so a state inserted or removed mid-session
+/// is reported as such and later states are compared with their counterparts.
+///
+/// Pixel overlays are kept in each [`PixelDiff`]; see [`annotated_diff`] for
+/// an image to look at.
+pub fn diff_directories(baseline: &Path, current: &Path, tolerance: u8) -> SnapshotResult<DirectoryDiff> {
+    let (old_files, new_files) = (state_files(baseline)?, state_files(current)?);
+    let mut states = Vec::new();
+    for (old_name, new_name) in pair_states(baseline, current, &old_files, &new_files)? {
+        let old = old_name.as_ref().and_then(|name| old_files.get(name));
+        let new = new_name.as_ref().and_then(|name| new_files.get(name));
+        let status = match (old, new) {
+            (None, _) => StateDiffStatus::Added,
+            (_, None) => StateDiffStatus::Removed,
+            _ => StateDiffStatus::Unchanged,
+        };
+        let name = new_name.clone().or_else(|| old_name.clone()).unwrap_or_default();
+        let mut state = StateDiff {
+            baseline_name: old_name.filter(|old| new_name.as_ref().is_some_and(|new| new != old)),
+            name,
+            status,
+            ratio: if status == StateDiffStatus::Unchanged { 0.0 } else { 1.0 },
+            pixels: None,
+            cells: None,
+            image: None,
+        };
+        if let (Some(old), Some(new)) = (old, new) {
+            if let (Some(old_png), Some(new_png)) = (&old.png, &new.png) {
+                state.pixels = Some(diff_images(&fs::read(old_png)?, &fs::read(new_png)?, tolerance)?);
+            }
+            if let (Some(old_grid), Some(new_grid)) = (&old.grid, &new.grid) {
+                let (old_grid, new_grid) = (read_grid(old_grid)?, read_grid(new_grid)?);
+                let cells = old_grid.diff(&new_grid);
+                let total = old_grid.width.max(new_grid.width) * old_grid.height.max(new_grid.height);
+                if state.pixels.is_none() && total > 0 {
+                    state.ratio = cells.changes.len() as f64 / f64::from(total);
+                }
+                state.cells = Some(cells);
+            }
+            if let Some(pixels) = &state.pixels {
+                state.ratio = pixels.ratio;
+            }
+            let changed = state.pixels.as_ref().is_some_and(|diff| !diff.is_identical())
+                || state.cells.as_ref().is_some_and(|diff| !diff.is_empty());
+            if changed {
+                state.status = StateDiffStatus::Changed;
+            }
+        }
+        states.push(state);
+    }
+    Ok(DirectoryDiff {
+        baseline: baseline.to_path_buf(),
+        current: current.to_path_buf(),
+        states,
+    })
+}
+
+/// Baseline and current names of the states to compare, in name order.
+///
+/// Session directories are aligned by [`align_session_dirs`]; everything
+/// else pairs by name.
+fn pair_states(
+    baseline: &Path,
+    current: &Path,
+    old_files: &BTreeMap<String, StateFiles>,
+    new_files: &BTreeMap<String, StateFiles>,
+) -> SnapshotResult<Vec<(Option<String>, Option<String>)>> {
+    let session_dir = |name: &str| {
+        let (dir, file) = name.rsplit_once('/').unwrap_or(("", name));
+        state_step(Path::new(&format!("{}.png", file))).map(|_| dir.to_string())
+    };
+    let session_dirs: BTreeSet<String> = old_files
+        .iter()
+        .filter(|(_, files)| files.png.is_some())
+        .filter_map(|(name, _)| session_dir(name))
+        .filter(|dir| current.join(dir).is_dir())
+        .collect();
+
+    let mut pairs = Vec::new();
+    let (mut paired_old, mut paired_new) = (BTreeSet::new(), BTreeSet::new());
+    for dir in &session_dirs {
+        let (old_dir, new_dir) = (baseline.join(dir), current.join(dir));
+        let name = |root: &Path, path: &PathBuf| {
+            let relative = path.strip_prefix(root).unwrap_or(path).with_extension("");
+            relative.to_string_lossy().replace('\\', "/")
+        };
+        let old_states: Vec<String> = list_state_images(&old_dir)?.iter().map(|path| name(baseline, path)).collect();
+        let new_states: Vec<String> = list_state_images(&new_dir)?.iter().map(|path| name(current, path)).collect();
+        for entry in align_session_dirs(&old_dir, &new_dir, DEFAULT_MATCH_THRESHOLD)? {
+            let (old, new) = match entry {
+                StateAlignment::Matched { baseline, current, .. } => (Some(baseline), Some(current)),
+                StateAlignment::Inserted { current } => (None, Some(current)),
+                StateAlignment::Removed { baseline } => (Some(baseline), None),
+            };
+            let old = old.map(|idx| old_states[idx].clone());
+            let new = new.map(|idx| new_states[idx].clone());
+            paired_old.extend(old.clone());
+            paired_new.extend(new.clone());
+            pairs.push((old, new));
+        }
+    }
+
+    let names: BTreeSet<&String> = old_files.keys().chain(new_files.keys()).collect();
+    for name in names {
+        let old = (old_files.contains_key(name) && !paired_old.contains(name)).then(|| name.clone());
+        let new = (new_files.contains_key(name) && !paired_new.contains(name)).then(|| name.clone());
+        if old.is_some() || new.is_some() {
+            pairs.push((old, new));
+        }
+    }
+    pairs.sort_by(|a, b| a.1.as_ref().or(a.0.as_ref()).cmp(&b.1.as_ref().or(b.0.as_ref())));
+    Ok(pairs)
+}
+
+/// Baseline, current screenshot and heatmap side by side, with the changed
+/// region outlined in yellow on each
+pub fn annotated_diff(old_png: &[u8], new_png: &[u8], diff: &PixelDiff) -> SnapshotResult<Vec<u8>> {
+    const GAP: u32 = 8;
+    let load = |png: &[u8]| {
+        image::load_from_memory(png)
+            .map(|image| image.to_rgb8())
+            .map_err(|e| SnapshotError::Capture(format!("Failed to load PNG: {}", e)))
+    };
+    let panels = [load(old_png)?, load(new_png)?, load(&diff.overlay)?];
+    let mut combined = RgbImage::from_pixel(3 * diff.width + 2 * GAP, diff.height, Rgb([255, 255, 255]));
+    for (index, panel) in (0u32..).zip(&panels) {
+        let left = index * (diff.width + GAP);
+        combined
+            .copy_from(panel, left, 0)
+            .map_err(|e| SnapshotError::Capture(e.to_string()))?;
+        if let Some(rect) = diff.bounds {
+            outline(&mut combined, left, rect);
+        }
+    }
+    let mut png = Vec::new();
+    combined
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| SnapshotError::Capture(format!("Failed to encode PNG: {}", e)))?;
+    Ok(png)
+}
+
+/// Draw a rectangle just outside `rect`, shifted right by `left`
+fn outline(image: &mut RgbImage, left: u32, rect: PixelRect) {
+    const YELLOW: Rgb<u8> = Rgb([255, 220, 0]);
+    let x0 = i64::from(left + rect.x) - 1;
+    let y0 = i64::from(rect.y) - 1;
+    let (x1, y1) = (x0 + i64::from(rect.width) + 1, y0 + i64::from(rect.height) + 1);
+    let mut put = |x: i64, y: i64| {
+        if let (Ok(x), Ok(y)) = (u32::try_from(x), u32::try_from(y))
+            && x < image.width()
+            && y < image.height()
+        {
+            image.put_pixel(x, y, YELLOW);
+        }
+    };
+    for x in x0..=x1 {
+        put(x, y0);
+        put(x, y1);
+    }
+    for y in y0..=y1 {
+        put(x0, y);
+        put(x1, y);
+    }
+}
+
+/// Screenshot and grid of a state in a directory
+#[derive(Default)]
+struct StateFiles {
+    png: Option<PathBuf>,
+    grid: Option<PathBuf>,
+}
+
+/// State files under `dir` by relative name, skipping diff and findings images written by earlier runs
+fn state_files(dir: &Path) -> SnapshotResult<BTreeMap<String, StateFiles>> {
+    let mut files: BTreeMap<String, StateFiles> = BTreeMap::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let relative = path.strip_prefix(dir).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+            if let Some(name) = relative.strip_suffix(".grid.json") {
+                files.entry(name.to_string()).or_default().grid = Some(path);
+            } else if let Some(name) = relative.strip_suffix(".png")
+                && !name.ends_with(".diff")
+                && !name.ends_with(".findings")
+            {
+                files.entry(name.to_string()).or_default().png = Some(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn read_grid(path: &Path) -> SnapshotResult<TerminalGrid> {
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+/// Color channels of a pixel, if the image covers it
+fn pixel_at(image: &RgbaImage, x: u32, y: u32) -> Option<[u8; 4]> {
+    (x < image.width() && y < image.height()).then(|| image.get_pixel(x, y).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32, paint: impl Fn(u32, u32) -> [u8; 3]) -> Vec<u8> {
+        let image = RgbImage::from_fn(width, height, |x, y| Rgb(paint(x, y)));
+        let mut data = Vec::new();
+        image.write_to(&mut std::io::Cursor::new(&mut data), image::ImageFormat::Png).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_changed_pixels_are_located_and_highlighted() {
+        let (w, h) = (3 * CELL_WIDTH, 2 * CELL_HEIGHT);
+        let old = png(w, h, |_, _| [0, 0, 0]);
+        // One pixel in the cell at row 1, column 2, and a faint one elsewhere
+        let new = png(w, h, |x, y| match (x, y) {
+            _ if x == 2 * CELL_WIDTH + 1 && y == CELL_HEIGHT + 2 => [200, 0, 0],
+            (0, 0) => [3, 3, 3],
+            _ => [0, 0, 0],
+        });
+        let diff = diff_images(&old, &new, 5).unwrap();
+        assert_eq!(diff.changed_pixels, 1);
+        assert_eq!(diff.changed_cells, vec![(1, 2)]);
+        assert_eq!(diff.bounds, Some(PixelRect { x: 2 * CELL_WIDTH + 1, y: CELL_HEIGHT + 2, width: 1, height: 1 }));
+        let overlay = image::load_from_memory(&diff.overlay).unwrap().to_rgb8();
+        assert_eq!(overlay.get_pixel(2 * CELL_WIDTH + 1, CELL_HEIGHT + 2).0[0], 255);
+        assert_eq!(overlay.get_pixel(0, 0).0, [1, 1, 1]);
+
+        assert_eq!(diff_images(&old, &new, 0).unwrap().changed_pixels, 2);
+        assert!(diff_images(&old, &old, 0).unwrap().is_identical());
+    }
+
+    #[test]
+    fn test_size_changes_count_as_changed() {
+        let old = png(2, 2, |_, _| [9, 9, 9]);
+        let new = png(4, 2, |_, _| [9, 9, 9]);
+        let diff = diff_images(&old, &new, 0).unwrap();
+        assert_eq!((diff.width, diff.height, diff.changed_pixels), (4, 2, 4));
+        assert_eq!(diff.ratio, 0.5);
+        assert!(diff.summary().starts_with("4 of 8 pixels differ (50.00%) in 1 cells, within 2x2 at (2, 0)"));
+    }
+
+    #[test]
+    fn test_directories_are_paired_by_state_name() {
+        let (baseline, current) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let black = png(4, 4, |_, _| [0, 0, 0]);
+        let dotted = png(4, 4, |x, y| if (x, y) == (1, 1) { [255, 255, 255] } else { [0, 0, 0] });
+        for (dir, files) in [(&baseline, [("state_0_initial", &black), ("120x40/state_1_down", &black)]), (&current, [("state_0_initial", &black), ("120x40/state_1_down", &dotted)])] {
+            for (name, data) in files {
+                let path = dir.path().join(format!("{}.png", name));
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, data).unwrap();
+            }
+        }
+        fs::write(baseline.path().join("state_2_q.png"), &black).unwrap();
+
+        let diff = diff_directories(baseline.path(), current.path(), 0).unwrap();
+        let statuses: Vec<_> = diff.states.iter().map(|state| (state.name.as_str(), state.status)).collect();
+        assert_eq!(statuses, vec![
+            ("120x40/state_1_down", StateDiffStatus::Changed),
+            ("state_0_initial", StateDiffStatus::Unchanged),
+            ("state_2_q", StateDiffStatus::Removed),
+        ]);
+        assert_eq!(diff.states[0].ratio, 1.0 / 16.0);
+        assert_eq!(diff.failures(0.1).len(), 1);
+        assert_eq!(diff.failures(0.0).len(), 2);
+        assert!(diff.to_markdown().contains("| `120x40/state_1_down` | changed | 6.25% | 1 of 16 pixels differ (6.25%) in 1 cells, within 1x1 at (1, 1) |  |"));
+
+        let annotated = annotated_diff(&black, &dotted, diff.states[0].pixels.as_ref().unwrap()).unwrap();
+        let annotated = image::load_from_memory(&annotated).unwrap().to_rgb8();
+        assert_eq!(annotated.dimensions(), (3 * 4 + 16, 4));
+        assert_eq!(annotated.get_pixel(0, 0).0, [255, 220, 0]);
+    }
+
+    #[test]
+    fn test_session_states_are_aligned_around_an_inserted_state() {
+        let (baseline, current) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let black = png(16, 16, |_, _| [0, 0, 0]);
+        let left = png(16, 16, |x, _| if x < 8 { [255, 255, 255] } else { [0, 0, 0] });
+        let top = png(16, 16, |_, y| if y < 8 { [255, 255, 255] } else { [0, 0, 0] });
+        let dialog = png(16, 16, |x, y| if (x < 8) == (y < 8) { [255, 255, 255] } else { [0, 0, 0] });
+        for (name, data) in [("state_0_initial", &black), ("state_1_enter", &left), ("state_2_q", &top)] {
+            fs::write(baseline.path().join(format!("{}.png", name)), data).unwrap();
+        }
+        for (name, data) in [("state_0_initial", &black), ("state_1_y", &dialog), ("state_2_enter", &left), ("state_3_q", &top)] {
+            fs::write(current.path().join(format!("{}.png", name)), data).unwrap();
+        }
+
+        let diff = diff_directories(baseline.path(), current.path(), 0).unwrap();
+        let states: Vec<_> = diff.states.iter().map(|state| (state.name.as_str(), state.baseline_name.as_deref(), state.status)).collect();
+        assert_eq!(states, vec![
+            ("state_0_initial", None, StateDiffStatus::Unchanged),
+            ("state_1_y", None, StateDiffStatus::Added),
+            ("state_2_enter", Some("state_1_enter"), StateDiffStatus::Unchanged),
+            ("state_3_q", Some("state_2_q"), StateDiffStatus::Unchanged),
+        ]);
+    }
+
+    #[test]
+    fn test_grid_diff_reports_cells() {
+        let grid = |bytes: &[u8]| {
+            let mut parser = crate::snapshot::Vt100Parser::new(10, 3);
+            parser.process(bytes);
+            parser.terminal().to_grid()
+        };
+        let before = grid(b"Save\r\nQuit");
+        let after = grid(b"\x1b[7mSave\x1b[0m\r\nQu\r\nok");
+        let diff = before.diff(&after);
+        assert_eq!((diff.added, diff.removed, diff.changed), (2, 2, 4));
+        assert_eq!(diff.rows, vec![0, 1, 2]);
+        assert!(diff.cursor_changed);
+        let saved = &diff.changes[0];
+        assert_eq!((saved.row, saved.col, saved.kind), (0, 0, CellChangeKind::Changed));
+        assert_eq!(saved.fields, vec![CellField::Attrs]);
+        let cleared = diff.changes.iter().find(|change| change.kind == CellChangeKind::Removed).unwrap();
+        assert_eq!((cleared.row, cleared.col, cleared.after.as_ref().unwrap().ch), (1, 2, ' '));
+        assert_eq!(diff.summary(), "2 added, 2 removed, 4 changed cells in 3 rows");
+
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(json["changes"][0]["fields"], serde_json::json!(["attrs"]));
+        assert!(before.diff(&before).is_empty());
+    }
+}
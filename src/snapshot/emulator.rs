@@ -0,0 +1,98 @@
+//! Building blocks of the terminal emulator for custom renderers and analyzers.
+//!
+//! [`Vt100Parser`] is the usual entry point. Tools that drive their own
+//! `vte` parser, draw cells themselves or map colors like the renderer does
+//! can use the pieces here instead of copying them:
+//!
+//! - [`TerminalPerformer`] applies parsed sequences to a [`Vt100Terminal`];
+//!   [`vte`] is re-exported so the parser version always matches.
+//! - [`DEFAULT_PALETTE`], [`SOLARIZED_PALETTE`], [`LIGHT_PALETTE`] and
+//!   [`xterm_256_to_rgb`] are the renderer's color tables.
+//! - [`glyph`] and [`builtin_glyph`] look up the 8x16 bitmaps cells are drawn
+//!   with.
+//!
+//! ```rust
+//! use cli_vision::snapshot::emulator::{vte, TerminalPerformer, Vt100Terminal};
+//!
+//! let mut terminal = Vt100Terminal::new(20, 2);
+//! let mut parser = vte::Parser::new();
+//! let mut performer = TerminalPerformer::new(&mut terminal);
+//! for byte in b"\x1b[1mhello" {
+//!     parser.advance(&mut performer, *byte);
+//! }
+//! assert!(terminal.to_text().starts_with("hello"));
+//! ```
+
+pub use vte;
+
+pub use super::glyphs::GlyphFallback;
+pub use super::pty::{TerminalPerformer, Vt100Parser, Vt100Terminal};
+pub use super::theme::{xterm_256_to_rgb, Theme, DEFAULT_PALETTE, LIGHT_PALETTE, SOLARIZED_PALETTE};
+
+use super::pty::{builtin_bitmap, get_char_bitmap, FONT_HEIGHT, FONT_WIDTH};
+
+/// Width of a glyph in font pixels (cells are drawn at twice this size)
+pub const GLYPH_WIDTH: u32 = FONT_WIDTH;
+/// Height of a glyph in font pixels
+pub const GLYPH_HEIGHT: u32 = FONT_HEIGHT;
+
+/// An 8x16 glyph bitmap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Glyph {
+    rows: [u8; 16],
+}
+
+impl Glyph {
+    /// Whether the pixel at column `x` (0-7) and row `y` (0-15) is set
+    pub fn pixel(&self, x: u32, y: u32) -> bool {
+        x < GLYPH_WIDTH && y < GLYPH_HEIGHT && (self.rows[y as usize] >> x) & 1 == 1
+    }
+
+    /// Rows from top to bottom; the leftmost pixel is the least significant bit
+    pub fn rows(&self) -> [u8; 16] {
+        self.rows
+    }
+
+    /// Whether no pixel is set
+    pub fn is_blank(&self) -> bool {
+        self.rows.iter().all(|row| *row == 0)
+    }
+}
+
+/// Glyph drawn for a character: the bundled fonts, then `fallback`, else blank
+pub fn glyph(ch: char, fallback: &[GlyphFallback]) -> Glyph {
+    Glyph { rows: get_char_bitmap(ch, fallback) }
+}
+
+/// Glyph from the bundled fonts, if they cover the character
+pub fn builtin_glyph(ch: char) -> Option<Glyph> {
+    builtin_bitmap(ch).map(|rows| Glyph { rows })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xterm_256_colors() {
+        assert_eq!(xterm_256_to_rgb(1), DEFAULT_PALETTE[1]);
+        assert_eq!(xterm_256_to_rgb(16), [0, 0, 0]);
+        assert_eq!(xterm_256_to_rgb(196), [255, 0, 0]);
+        assert_eq!(xterm_256_to_rgb(232), [8, 8, 8]);
+        // Themes only change 0-15
+        let light = Theme::builtin("light").unwrap();
+        assert_eq!(light.color(1), LIGHT_PALETTE[1]);
+        assert_eq!(light.color(100), xterm_256_to_rgb(100));
+    }
+
+    #[test]
+    fn test_glyph_lookup() {
+        let a = builtin_glyph('A').unwrap();
+        assert!(!a.is_blank());
+        assert_eq!(glyph('A', &[]), a);
+        assert!(glyph(' ', &[]).is_blank());
+        assert!(builtin_glyph('\u{E000}').is_none());
+        assert!(!glyph('\u{E000}', &[GlyphFallback::HexBox]).is_blank());
+        assert!(!a.pixel(GLYPH_WIDTH, 0));
+    }
+}
@@ -0,0 +1,586 @@
+//! Exporting terminal screens to other formats.
+//!
+//! [`to_html`] turns the cell grid into a `<pre>` element with one styled
+//! `<span>` per run of cells that look alike, so a state can be embedded in
+//! a web dashboard or report as real, selectable text rather than a PNG.
+//! Colors, bold, dim, italic, underline styles, strikethrough, blink and
+//! inverse video are kept, and OSC 8 hyperlinks become links. [`html_page`]
+//! wraps such an element in a standalone page.
+//!
+//! [`to_gif`] stitches the states of a run into one animated GIF, each state
+//! shown for as long as the run waited before sending the next input.
+//! [`encode_video`] (with the `video` feature) encodes the same timeline as
+//! animated WebP, APNG or MP4 at a fixed frame rate using `ffmpeg`, which the
+//! feature does not bundle: it must be installed and on the `PATH` at run
+//! time. [`check_video_support`] reports a missing `ffmpeg` up front.
+
+use std::fmt::Write as _;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+
+use super::pty::{parse_sleep, StateCaptureResult, UnderlineStyle, Vt100Terminal};
+use super::{SnapshotError, SnapshotResult};
+
+/// CSS class of the `<pre>` element produced by [`to_html`]
+pub const HTML_CLASS: &str = "cli-vision-screen";
+
+/// Fonts of the screen in an [`html_page`]; box drawing lines up only in a monospace font
+const HTML_FONTS: &str = "ui-monospace, 'DejaVu Sans Mono', Menlo, Consolas, monospace";
+
+/// File format of saved states
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StateFormat {
+    /// PNG screenshot
+    #[default]
+    Png,
+    /// Standalone HTML page (see [`html_page`]) next to the PNG screenshot
+    Html,
+    /// [`TerminalGrid`](super::grid::TerminalGrid) as JSON next to the PNG screenshot
+    GridJson,
+}
+
+impl FromStr for StateFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "png" => Ok(StateFormat::Png),
+            "html" => Ok(StateFormat::Html),
+            "grid-json" | "grid" => Ok(StateFormat::GridJson),
+            other => Err(format!("unknown state format '{}' (expected: png, html, grid-json)", other)),
+        }
+    }
+}
+
+/// How long the last state of a GIF is shown before the animation loops
+pub const GIF_FINAL_FRAME: Duration = Duration::from_secs(2);
+
+/// Shortest GIF frame time; viewers slow shorter frames down to 100ms
+pub const GIF_MIN_FRAME: Duration = Duration::from_millis(20);
+
+/// Quantization speed of the GIF encoder (1 = best quality, 30 = fastest)
+const GIF_SPEED: i32 = 10;
+
+/// Default frame rate of exported videos
+pub const DEFAULT_VIDEO_FPS: u32 = 10;
+
+/// Container of an exported video
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+    /// Animated WebP (lossless)
+    WebP,
+    /// Animated PNG
+    Apng,
+    /// H.264 in MP4
+    Mp4,
+}
+
+impl VideoFormat {
+    /// Format for a file extension: `.webp`, `.apng` or `.png`, `.mp4`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "webp" => Some(VideoFormat::WebP),
+            "apng" | "png" => Some(VideoFormat::Apng),
+            "mp4" => Some(VideoFormat::Mp4),
+            _ => None,
+        }
+    }
+
+    /// `ffmpeg` output options; animations loop forever
+    #[cfg_attr(not(feature = "video"), allow(dead_code))]
+    fn ffmpeg_args(self) -> &'static [&'static str] {
+        match self {
+            VideoFormat::WebP => &["-c:v", "libwebp", "-lossless", "1", "-loop", "0"],
+            VideoFormat::Apng => &["-f", "apng", "-plays", "0"],
+            // H.264 needs even dimensions and 4:2:0 chroma for most players
+            VideoFormat::Mp4 => &["-c:v", "libx264", "-pix_fmt", "yuv420p", "-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"],
+        }
+    }
+}
+
+/// How one cell looks, after inverse video and reverse-screen mode are applied
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CellStyle {
+    fg: [u8; 3],
+    bg: [u8; 3],
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: Option<(UnderlineStyle, Option<[u8; 3]>)>,
+    strikethrough: bool,
+    blink: bool,
+}
+
+impl CellStyle {
+    /// Inline CSS for the properties that differ from the screen defaults
+    fn css(&self, default_fg: [u8; 3], default_bg: [u8; 3]) -> String {
+        let mut css = String::new();
+        if self.fg != default_fg {
+            let _ = write!(css, "color:{};", hex(self.fg));
+        }
+        if self.bg != default_bg {
+            let _ = write!(css, "background:{};", hex(self.bg));
+        }
+        if self.bold {
+            css.push_str("font-weight:bold;");
+        }
+        if self.dim {
+            css.push_str("opacity:0.6;");
+        }
+        if self.italic {
+            css.push_str("font-style:italic;");
+        }
+        let mut lines = Vec::new();
+        if self.underline.is_some() {
+            lines.push("underline");
+        }
+        if self.strikethrough {
+            lines.push("line-through");
+        }
+        if self.blink {
+            lines.push("blink");
+        }
+        if !lines.is_empty() {
+            let _ = write!(css, "text-decoration:{};", lines.join(" "));
+        }
+        if let Some((style, color)) = self.underline {
+            let style = match style {
+                UnderlineStyle::Double => Some("double"),
+                UnderlineStyle::Curly => Some("wavy"),
+                UnderlineStyle::Dotted => Some("dotted"),
+                UnderlineStyle::Dashed => Some("dashed"),
+                UnderlineStyle::None | UnderlineStyle::Single => None,
+            };
+            if let Some(style) = style {
+                let _ = write!(css, "text-decoration-style:{};", style);
+            }
+            if let Some(color) = color {
+                let _ = write!(css, "text-decoration-color:{};", hex(color));
+            }
+        }
+        css
+    }
+}
+
+fn hex(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+fn escape_html(text: &str, out: &mut String) {
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(ch),
+        }
+    }
+}
+
+/// Render the screen as an HTML `<pre>` element with styled spans.
+///
+/// Each row is one line; blank cells at the end of a row are left out so
+/// copied text has no trailing spaces. The element carries the
+/// [`HTML_CLASS`] class and the screen's default colors, so a page only
+/// needs to choose the monospace font.
+pub fn to_html(terminal: &Vt100Terminal) -> String {
+    // Reverse-screen mode swaps the default colors, as when rendering
+    let (fg, bg) = terminal.default_colors();
+    let reverse = terminal.reverse_screen();
+    let screen_color = move |color: [u8; 3]| match reverse {
+        true if color == fg => bg,
+        true if color == bg => fg,
+        _ => color,
+    };
+    let (default_fg, default_bg) = (screen_color(fg), screen_color(bg));
+
+    let mut out = format!(
+        "<pre class=\"{}\" style=\"color:{};background:{}\">",
+        HTML_CLASS,
+        hex(default_fg),
+        hex(default_bg)
+    );
+    for row in 0..terminal.height {
+        let width = if terminal.line_size(row).is_double_width() {
+            (terminal.width / 2).max(1)
+        } else {
+            terminal.width
+        } as usize;
+        let r = row as usize;
+        let cells: Vec<(CellStyle, Option<u32>, char)> = (0..width)
+            .map(|col| {
+                let attrs = terminal.attributes[r][col];
+                let (mut fg, mut bg) = (screen_color(terminal.fg_colors[r][col]), screen_color(terminal.bg_colors[r][col]));
+                if attrs.inverse {
+                    std::mem::swap(&mut fg, &mut bg);
+                }
+                let style = CellStyle {
+                    fg,
+                    bg,
+                    bold: attrs.bold,
+                    dim: attrs.dim,
+                    italic: attrs.italic,
+                    underline: attrs.underline.then_some((attrs.underline_style, attrs.underline_color)),
+                    strikethrough: attrs.strikethrough,
+                    blink: attrs.blink,
+                };
+                (style, attrs.hyperlink, terminal.buffer[r][col])
+            })
+            .collect();
+
+        let plain = |(style, link, ch): &(CellStyle, Option<u32>, char)| {
+            *ch == ' ' && link.is_none() && style.bg == default_bg && style.underline.is_none() && !style.strikethrough
+        };
+        let end = cells.iter().rposition(|cell| !plain(cell)).map_or(0, |idx| idx + 1);
+
+        let mut col = 0;
+        while col < end {
+            let (style, link, _) = cells[col];
+            let start = col;
+            while col < end && cells[col].0 == style && cells[col].1 == link {
+                col += 1;
+            }
+            let text: String = cells[start..col].iter().map(|(_, _, ch)| *ch).collect();
+            let uri = link.and_then(|index| terminal.hyperlink_uri(index));
+            if let Some(uri) = uri {
+                out.push_str("<a href=\"");
+                escape_html(uri, &mut out);
+                out.push_str("\">");
+            }
+            let css = style.css(default_fg, default_bg);
+            if css.is_empty() {
+                escape_html(&text, &mut out);
+            } else {
+                let _ = write!(out, "<span style=\"{}\">", css);
+                escape_html(&text, &mut out);
+                out.push_str("</span>");
+            }
+            if uri.is_some() {
+                out.push_str("</a>");
+            }
+        }
+        if row + 1 < terminal.height {
+            out.push('\n');
+        }
+    }
+    out.push_str("</pre>");
+    out
+}
+
+/// Wrap a screen from [`to_html`] in a standalone HTML page with the given title
+pub fn html_page(screen: &str, title: &str) -> String {
+    let mut escaped_title = String::new();
+    escape_html(title, &mut escaped_title);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n\
+         .{} {{ display: inline-block; margin: 0; padding: 0.5em; font-family: {}; line-height: 1.2; }}\n\
+         .{} a {{ color: inherit; }}\n</style>\n</head>\n<body>\n{}\n</body>\n</html>\n",
+        escaped_title, HTML_CLASS, HTML_FONTS, HTML_CLASS, screen
+    )
+}
+
+/// How long each state is shown in an animation: until the next input was sent.
+///
+/// That is the input delay, the pause of a `sleep:` input, or the time a wait
+/// input took. The last state is shown for [`GIF_FINAL_FRAME`].
+pub fn frame_durations(captures: &[StateCaptureResult], input_delay_ms: u64) -> Vec<Duration> {
+    let mut durations: Vec<Duration> = captures
+        .iter()
+        .skip(1)
+        .map(|next| {
+            let input = next.input.as_deref().unwrap_or_default();
+            let duration = match (&next.wait, parse_sleep(input)) {
+                (Some(wait), _) => Duration::from_millis(wait.elapsed_ms),
+                (None, Some(sleep)) => sleep,
+                (None, None) => Duration::from_millis(input_delay_ms),
+            };
+            duration.max(GIF_MIN_FRAME)
+        })
+        .collect();
+    if !captures.is_empty() {
+        durations.push(GIF_FINAL_FRAME);
+    }
+    durations
+}
+
+/// Encode the states of a run as a looping animated GIF.
+///
+/// Frames are timed with [`frame_durations`]. States captured at a smaller
+/// size (after a `resize:` input) are drawn in the top-left corner of a
+/// canvas as large as the largest state.
+pub fn to_gif(captures: &[StateCaptureResult], input_delay_ms: u64) -> SnapshotResult<Vec<u8>> {
+    let gif_error = |e: image::ImageError| SnapshotError::Capture(format!("Failed to encode GIF: {}", e));
+    if captures.is_empty() {
+        return Err(SnapshotError::Capture("No states to animate".to_string()));
+    }
+    let size = canvas_size(captures);
+
+    let mut data = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut data, GIF_SPEED);
+        encoder.set_repeat(Repeat::Infinite).map_err(gif_error)?;
+        for (capture, duration) in captures.iter().zip(frame_durations(captures, input_delay_ms)) {
+            let canvas = frame_canvas(capture, size)?;
+            let delay = Delay::from_numer_denom_ms(duration.as_millis() as u32, 1);
+            encoder.encode_frame(Frame::from_parts(canvas, 0, 0, delay)).map_err(gif_error)?;
+        }
+    }
+    Ok(data)
+}
+
+/// Size of a canvas that holds every state
+fn canvas_size(captures: &[StateCaptureResult]) -> (u32, u32) {
+    let width = captures.iter().map(|c| c.width).max().unwrap_or_default();
+    let height = captures.iter().map(|c| c.height).max().unwrap_or_default();
+    (width, height)
+}
+
+/// Decode a state, drawn in the top-left corner of a canvas of the given size
+fn frame_canvas(capture: &StateCaptureResult, (width, height): (u32, u32)) -> SnapshotResult<RgbaImage> {
+    let image = image::load_from_memory(&capture.image_data)
+        .map_err(|e| SnapshotError::Capture(format!("Failed to decode capture: {}", e)))?
+        .to_rgba8();
+    if image.dimensions() == (width, height) {
+        return Ok(image);
+    }
+    let mut canvas = RgbaImage::from_pixel(width, height, image::Rgba([0, 0, 0, 255]));
+    image::imageops::overlay(&mut canvas, &image, 0, 0);
+    Ok(canvas)
+}
+
+/// Which state each video frame shows at a frame rate, timed with [`frame_durations`].
+///
+/// Frame counts follow the running total of the durations so rounding does
+/// not drift, and every state gets at least one frame.
+pub fn video_frames(durations: &[Duration], fps: u32) -> Vec<usize> {
+    let mut frames = Vec::new();
+    let mut elapsed = Duration::ZERO;
+    for (state, duration) in durations.iter().enumerate() {
+        elapsed += *duration;
+        let end = (elapsed.as_secs_f64() * f64::from(fps.max(1))).round() as usize;
+        let count = end.saturating_sub(frames.len()).max(1);
+        frames.extend(std::iter::repeat_n(state, count));
+    }
+    frames
+}
+
+/// Program [`encode_video`] pipes the frames to
+#[cfg(feature = "video")]
+const FFMPEG: &str = "ffmpeg";
+
+/// Check that videos can be encoded: the `video` feature is built in and
+/// `ffmpeg` runs.
+///
+/// `ffmpeg` is probed once per process, so a run can call this before it
+/// captures anything and fail early.
+pub fn check_video_support() -> SnapshotResult<()> {
+    #[cfg(feature = "video")]
+    {
+        static PROBE: std::sync::OnceLock<Result<(), String>> = std::sync::OnceLock::new();
+        PROBE.get_or_init(|| probe_encoder(FFMPEG)).clone().map_err(SnapshotError::Capture)
+    }
+    #[cfg(not(feature = "video"))]
+    Err(SnapshotError::Capture("Video export needs the `video` feature".to_string()))
+}
+
+/// Run `program -version`, describing why it cannot be used if it fails
+#[cfg(feature = "video")]
+fn probe_encoder(program: &str) -> Result<(), String> {
+    use std::process::{Command, Stdio};
+
+    let status = Command::new(program).arg("-version").stdout(Stdio::null()).stderr(Stdio::null()).status();
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(missing_encoder(program, &format!("`{} -version` failed ({})", program, status))),
+        Err(e) => Err(missing_encoder(program, &e.to_string())),
+    }
+}
+
+/// Error for an encoder that is not installed or does not run
+#[cfg(feature = "video")]
+fn missing_encoder(program: &str, reason: &str) -> String {
+    format!("Video export (the `video` feature) needs `{}` on the PATH: {}", program, reason)
+}
+
+/// Encode the states of a run as a video at `fps` frames per second.
+///
+/// The container follows the extension of `path` (see [`VideoFormat`]).
+/// States are timed like [`to_gif`] and the frames are piped to `ffmpeg`,
+/// which must be on the `PATH` and built with libwebp or libx264 for those
+/// formats. Without the `video` feature, or without `ffmpeg`, this fails
+/// (see [`check_video_support`]).
+pub fn encode_video(captures: &[StateCaptureResult], input_delay_ms: u64, fps: u32, path: &Path) -> SnapshotResult<()> {
+    let format = VideoFormat::from_path(path).ok_or_else(|| {
+        SnapshotError::Capture(format!("Unknown video format '{}' (expected .webp, .apng, .mp4)", path.display()))
+    })?;
+    if captures.is_empty() {
+        return Err(SnapshotError::Capture("No states to animate".to_string()));
+    }
+    #[cfg(feature = "video")]
+    return run_ffmpeg(captures, input_delay_ms, fps, format, path);
+    #[cfg(not(feature = "video"))]
+    {
+        let _ = (input_delay_ms, fps, format);
+        check_video_support()
+    }
+}
+
+/// Pipe PPM frames into `ffmpeg`
+#[cfg(feature = "video")]
+fn run_ffmpeg(
+    captures: &[StateCaptureResult],
+    input_delay_ms: u64,
+    fps: u32,
+    format: VideoFormat,
+    path: &Path,
+) -> SnapshotResult<()> {
+    use std::io::Read;
+    use std::process::{Command, Stdio};
+
+    use super::frames::{write_frame, FrameFormat};
+
+    check_video_support()?;
+    let size = canvas_size(captures);
+    let frames = captures
+        .iter()
+        .map(|capture| frame_canvas(capture, size).map(|canvas| image::DynamicImage::ImageRgba8(canvas).to_rgb8()))
+        .collect::<SnapshotResult<Vec<_>>>()?;
+
+    let fps = fps.max(1);
+    let mut child = Command::new(FFMPEG)
+        .args(["-y", "-loglevel", "error", "-f", "image2pipe", "-c:v", "ppm", "-framerate", &fps.to_string(), "-i", "-"])
+        .args(format.ffmpeg_args())
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| SnapshotError::Capture(missing_encoder(FFMPEG, &e.to_string())))?;
+
+    // A closed pipe means ffmpeg gave up; its stderr says why
+    if let Some(mut stdin) = child.stdin.take() {
+        for state in video_frames(&frame_durations(captures, input_delay_ms), fps) {
+            if write_frame(&mut stdin, FrameFormat::Ppm, &frames[state]).is_err() {
+                break;
+            }
+        }
+    }
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_string(&mut stderr);
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(SnapshotError::Capture(format!("ffmpeg failed ({}): {}", status, stderr.trim())));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::pty::{render_to_png, RenderOptions, CELL_HEIGHT, CELL_WIDTH};
+    use crate::snapshot::Vt100Parser;
+
+    #[test]
+    fn test_styled_runs_become_spans() {
+        let mut parser = Vt100Parser::new(20, 2);
+        parser.process(b"\x1b[1;31mError\x1b[0m: a<b & \x1b[4:3mc\x1b[0m\r\n\x1b[7mX\x1b[0m   ");
+        let html = to_html(parser.terminal());
+        assert!(html.starts_with("<pre class=\"cli-vision-screen\" style=\"color:#ffffff;background:#000000\">"));
+        assert!(html.contains("<span style=\"color:#cd3131;font-weight:bold;\">Error</span>: a&lt;b &amp; "));
+        assert!(html.contains("<span style=\"text-decoration:underline;text-decoration-style:wavy;\">c</span>\n"));
+        // Inverse video swaps the colors; trailing blanks are dropped
+        assert!(html.ends_with("<span style=\"color:#000000;background:#ffffff;\">X</span></pre>"));
+    }
+
+    #[test]
+    fn test_hyperlinks_become_links() {
+        let mut parser = Vt100Parser::new(20, 1);
+        parser.process(b"see \x1b]8;;https://example.com/?a=1&b=2\x07docs\x1b]8;;\x07.");
+        let html = to_html(parser.terminal());
+        assert!(html.contains(">see <a href=\"https://example.com/?a=1&amp;b=2\">docs</a>.</pre>"));
+    }
+
+    #[test]
+    fn test_html_page_is_standalone() {
+        let mut parser = Vt100Parser::new(10, 1);
+        parser.process(b"\x1b[32mok\x1b[0m");
+        let page = html_page(&to_html(parser.terminal()), "state 1: <enter>");
+        assert!(page.starts_with("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">"));
+        assert!(page.contains("<title>state 1: &lt;enter&gt;</title>"));
+        assert!(page.contains(".cli-vision-screen { display: inline-block;"));
+        assert!(page.contains("<body>\n<pre class=\"cli-vision-screen\""));
+        assert_eq!("HTML".parse::<StateFormat>(), Ok(StateFormat::Html));
+    }
+
+    #[test]
+    fn test_states_become_timed_gif_frames() {
+        let capture = |step: usize, input: Option<&str>, text: &str| {
+            let mut parser = Vt100Parser::new(10, 2);
+            parser.process(text.as_bytes());
+            let terminal = parser.terminal();
+            StateCaptureResult {
+                step,
+                input: input.map(str::to_string),
+                image_data: render_to_png(&parser, &RenderOptions::default()),
+                width: terminal.width * CELL_WIDTH,
+                height: terminal.height * CELL_HEIGHT,
+                bell_count: 0,
+                visual_bell_count: 0,
+                cursor: terminal.cursor_state(),
+                cursor_position: (0, 0),
+                cursor_changes: Vec::new(),
+                blink_frames: None,
+                hyperlinks: Vec::new(),
+                text: terminal.to_text(),
+                html: None,
+                unrenderable: Vec::new(),
+                wait: None,
+                exit_code: None,
+                ansi: None,
+                grid: None,
+            }
+        };
+        let captures = vec![capture(0, None, "$"), capture(1, Some("a"), "$ a"), capture(2, Some("sleep:1s"), "$ a!")];
+        let durations = frame_durations(&captures, 5);
+        assert_eq!(durations, vec![GIF_MIN_FRAME, Duration::from_secs(1), GIF_FINAL_FRAME]);
+
+        let gif = to_gif(&captures, 5).unwrap();
+        assert!(gif.starts_with(b"GIF89a"));
+        let decoder = image::codecs::gif::GifDecoder::new(std::io::Cursor::new(gif)).unwrap();
+        let frames = image::AnimationDecoder::into_frames(decoder).collect_frames().unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[1].delay(), Delay::from_numer_denom_ms(1000, 1));
+    }
+
+    #[test]
+    fn test_video_frames_follow_state_durations() {
+        let ms = Duration::from_millis;
+        assert_eq!(video_frames(&[ms(250), ms(20), ms(100)], 10), vec![0, 0, 0, 1, 2]);
+        // Rounding does not drift over many short states
+        assert_eq!(video_frames(&[ms(150); 10], 10).len(), 15);
+        assert_eq!(VideoFormat::from_path(Path::new("out.WebP")), Some(VideoFormat::WebP));
+        assert_eq!(VideoFormat::from_path(Path::new("out.gif")), None);
+    }
+
+    #[cfg(not(feature = "video"))]
+    #[test]
+    fn test_video_export_names_the_missing_feature() {
+        let err = check_video_support().unwrap_err().to_string();
+        assert!(err.contains("`video` feature"), "{}", err);
+    }
+
+    #[cfg(feature = "video")]
+    #[test]
+    fn test_missing_encoder_is_named() {
+        let err = probe_encoder("cli-vision-no-such-encoder").unwrap_err();
+        assert!(err.starts_with("Video export (the `video` feature) needs `cli-vision-no-such-encoder` on the PATH"), "{}", err);
+    }
+}
@@ -0,0 +1,89 @@
+//! Raw frame streams for piping captures into other tools.
+//!
+//! Frames are written back to back with no container, so a stream of PPM
+//! frames can go straight into `ffmpeg -f image2pipe -c:v ppm -i -` and a
+//! farbfeld stream into any tool reading concatenated farbfeld images.
+
+use image::RgbImage;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use super::{SnapshotError, SnapshotResult};
+
+/// Streaming frame format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// Binary PPM (`P6`, 8 bits per channel)
+    Ppm,
+    /// farbfeld (`farbfeld` magic, 16-bit big-endian RGBA)
+    Farbfeld,
+}
+
+impl FromStr for FrameFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ppm" => Ok(FrameFormat::Ppm),
+            "farbfeld" | "ff" => Ok(FrameFormat::Farbfeld),
+            other => Err(format!("unknown frame format '{}' (expected: ppm, farbfeld)", other)),
+        }
+    }
+}
+
+/// Write one frame in the given format
+pub fn write_frame(out: &mut dyn Write, format: FrameFormat, image: &RgbImage) -> io::Result<()> {
+    let (width, height) = image.dimensions();
+    match format {
+        FrameFormat::Ppm => {
+            write!(out, "P6\n{} {}\n255\n", width, height)?;
+            out.write_all(image.as_raw())
+        }
+        FrameFormat::Farbfeld => {
+            out.write_all(b"farbfeld")?;
+            out.write_all(&width.to_be_bytes())?;
+            out.write_all(&height.to_be_bytes())?;
+            let mut pixels = Vec::with_capacity(width as usize * height as usize * 8);
+            for pixel in image.pixels() {
+                for channel in pixel.0 {
+                    pixels.extend_from_slice(&(u16::from(channel) * 257).to_be_bytes());
+                }
+                pixels.extend_from_slice(&u16::MAX.to_be_bytes());
+            }
+            out.write_all(&pixels)
+        }
+    }
+}
+
+/// Decode a PNG capture and write it as one frame
+pub fn write_png_frame(out: &mut dyn Write, format: FrameFormat, png: &[u8]) -> SnapshotResult<()> {
+    let image = image::load_from_memory(png)
+        .map_err(|e| SnapshotError::Capture(format!("Failed to decode capture: {}", e)))?
+        .to_rgb8();
+    write_frame(out, format, &image)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn test_ppm_frame_layout() {
+        let image = RgbImage::from_pixel(2, 1, Rgb([1, 2, 3]));
+        let mut out = Vec::new();
+        write_frame(&mut out, FrameFormat::Ppm, &image).unwrap();
+        assert_eq!(out, b"P6\n2 1\n255\n\x01\x02\x03\x01\x02\x03");
+    }
+
+    #[test]
+    fn test_farbfeld_frame_layout() {
+        let image = RgbImage::from_pixel(1, 1, Rgb([255, 0, 1]));
+        let mut out = Vec::new();
+        write_frame(&mut out, FrameFormat::Farbfeld, &image).unwrap();
+        assert_eq!(&out[..16], b"farbfeld\0\0\0\x01\0\0\0\x01");
+        assert_eq!(&out[16..], [0xff, 0xff, 0, 0, 1, 1, 0xff, 0xff]);
+        assert!("gif".parse::<FrameFormat>().is_err());
+    }
+}
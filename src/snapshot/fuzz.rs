@@ -0,0 +1,255 @@
+//! Smoke testing with seeded random key streams.
+//!
+//! A fuzz run generates a stream of random keys from a seed, sends it to the
+//! application with [`run_with_options`], and keeps the screenshots of the
+//! states where the screen changed dramatically or the application died.
+//! The stream depends only on the seed, step count and key pool, and is
+//! recorded in the [`FuzzReport`], so any failure can be replayed exactly.
+//!
+//! The run ends when the application exits, so keys that quit it on purpose
+//! (`q`, `escape`, ...) should be left out of the pool.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::snapshot::fuzz::{run_fuzz, FuzzConfig};
+//!
+//! let report = run_fuzz("./my-tui", &[], &FuzzConfig::new(500, 42).exclude(&["q"])).unwrap();
+//! assert!(report.died.is_none(), "died after {:?}", report.inputs);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use super::pty::{run_with_options, RunOptions, StateCaptureResult, TerminalSize};
+use super::SnapshotResult;
+use crate::compare::text_distance;
+
+/// Keys sent by default: navigation, editing, function keys, letters, digits and
+/// punctuation, and control chords that do not signal or end input
+pub const DEFAULT_FUZZ_KEYS: &[&str] = &[
+    "up", "down", "left", "right", "home", "end", "pageup", "pagedown", "insert", "delete", "enter", "space", "tab",
+    "shift+tab", "backspace", "escape", "f1", "f2", "f3", "f4", "f5", "f6", "f7", "f8", "f9", "f10", "f11", "f12", "a",
+    "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s", "t", "u", "v", "w", "x",
+    "y", "z", "A", "G", "N", "0", "1", "5", "9", "/", "?", ":", ".", "-", "+", "*", "[", "]", "ctrl+a", "ctrl+e",
+    "ctrl+f", "ctrl+b", "ctrl+n", "ctrl+p", "ctrl+k", "ctrl+l", "ctrl+r", "ctrl+u", "ctrl+w", "alt+enter", "ctrl+right",
+    "ctrl+left",
+];
+
+/// Default minimum text distance between consecutive screens that counts as a dramatic change
+pub const DEFAULT_CHANGE_THRESHOLD: f64 = 0.5;
+
+/// Configuration for a fuzz run
+#[derive(Debug, Clone)]
+pub struct FuzzConfig {
+    /// Number of keys to send
+    pub steps: usize,
+    /// Seed of the key stream
+    pub seed: u64,
+    /// Keys to choose from
+    pub keys: Vec<String>,
+    /// Delay before each key (milliseconds)
+    pub input_delay_ms: u64,
+    /// Terminal size
+    pub size: TerminalSize,
+    /// Capture options (theme, environment, limits, ...)
+    pub options: RunOptions,
+    /// Text distance between consecutive screens above which the new screen is kept
+    pub change_threshold: f64,
+}
+
+impl FuzzConfig {
+    /// Send `steps` keys from [`DEFAULT_FUZZ_KEYS`] chosen with `seed`
+    pub fn new(steps: usize, seed: u64) -> Self {
+        Self {
+            steps,
+            seed,
+            keys: DEFAULT_FUZZ_KEYS.iter().map(|key| key.to_string()).collect(),
+            input_delay_ms: 20,
+            size: TerminalSize::default(),
+            options: RunOptions::default(),
+            change_threshold: DEFAULT_CHANGE_THRESHOLD,
+        }
+    }
+
+    /// Choose from these keys instead of the default pool
+    pub fn keys(mut self, keys: Vec<String>) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    /// Leave keys out of the pool, e.g. the application's quit key
+    pub fn exclude(mut self, keys: &[&str]) -> Self {
+        self.keys.retain(|key| !keys.iter().any(|excluded| excluded.eq_ignore_ascii_case(key)));
+        self
+    }
+
+    /// Set the delay before each key
+    pub fn input_delay(mut self, delay_ms: u64) -> Self {
+        self.input_delay_ms = delay_ms;
+        self
+    }
+
+    /// Set the terminal size
+    pub fn size(mut self, size: TerminalSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Set capture options
+    pub fn options(mut self, options: RunOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set the text distance that counts as a dramatic change
+    pub fn change_threshold(mut self, threshold: f64) -> Self {
+        self.change_threshold = threshold;
+        self
+    }
+}
+
+/// Why a state was kept
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FuzzEventKind {
+    /// The screen changed more than the threshold since the previous state
+    DramaticChange { distance: f64 },
+    /// The application had exited or stopped accepting input in this state
+    Died,
+}
+
+/// A state worth looking at
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuzzEvent {
+    /// State number (0 = initial)
+    pub step: usize,
+    /// Key that led to the state
+    pub input: Option<String>,
+    /// Why the state was kept
+    #[serde(flatten)]
+    pub kind: FuzzEventKind,
+    /// PNG screenshot
+    #[serde(skip)]
+    pub image_data: Vec<u8>,
+}
+
+/// Result of a fuzz run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuzzReport {
+    /// Seed of the key stream
+    pub seed: u64,
+    /// The exact keys generated, in order
+    pub inputs: Vec<String>,
+    /// Keys delivered before the run ended
+    pub steps_run: usize,
+    /// Dramatic changes and the death of the application, in order
+    pub events: Vec<FuzzEvent>,
+    /// Why the application is considered dead, if it died
+    pub died: Option<String>,
+}
+
+/// The key stream for a seed: `steps` keys drawn from `keys`
+pub fn key_stream(seed: u64, steps: usize, keys: &[String]) -> Vec<String> {
+    if keys.is_empty() {
+        return Vec::new();
+    }
+    let mut rng = SplitMix64(seed);
+    (0..steps).map(|_| keys[(rng.next() % keys.len() as u64) as usize].clone()).collect()
+}
+
+/// Send a seeded random key stream and keep the states that need a look
+pub fn run_fuzz(command: &str, args: &[String], config: &FuzzConfig) -> SnapshotResult<FuzzReport> {
+    let inputs = key_stream(config.seed, config.steps, &config.keys);
+
+    // Captures are collected as they happen, so they survive the run failing
+    let captures: Arc<Mutex<Vec<StateCaptureResult>>> = Arc::default();
+    let sink = captures.clone();
+    let options = config
+        .options
+        .clone()
+        .stop_on_exit(true)
+        .on_capture(move |capture| sink.lock().unwrap_or_else(|p| p.into_inner()).push(capture.clone()));
+    let result = run_with_options(command, args, &inputs, config.input_delay_ms, config.size, &options);
+    let captures = std::mem::take(&mut *captures.lock().unwrap_or_else(|p| p.into_inner()));
+
+    let died = match result {
+        Ok(_) => captures.last().and_then(|last| last.exit_code).map(|code| format!("exited with code {}", code)),
+        // Nothing was captured, so the application never ran
+        Err(e) if captures.is_empty() => return Err(e),
+        Err(e) => Some(e.to_string()),
+    };
+
+    let mut events: Vec<FuzzEvent> = captures
+        .windows(2)
+        .filter_map(|pair| {
+            let distance = text_distance(&pair[0].text, &pair[1].text);
+            (distance > config.change_threshold).then(|| FuzzEvent {
+                step: pair[1].step,
+                input: pair[1].input.clone(),
+                kind: FuzzEventKind::DramaticChange { distance },
+                image_data: pair[1].image_data.clone(),
+            })
+        })
+        .collect();
+    if died.is_some()
+        && let Some(last) = captures.last()
+    {
+        events.push(FuzzEvent {
+            step: last.step,
+            input: last.input.clone(),
+            kind: FuzzEventKind::Died,
+            image_data: last.image_data.clone(),
+        });
+    }
+
+    Ok(FuzzReport {
+        seed: config.seed,
+        steps_run: captures.len().saturating_sub(1),
+        inputs,
+        events,
+        died,
+    })
+}
+
+/// Small, fast PRNG whose output is fixed for a seed on every platform
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_stream_is_reproducible() {
+        let keys: Vec<String> = DEFAULT_FUZZ_KEYS.iter().map(|key| key.to_string()).collect();
+        let stream = key_stream(42, 200, &keys);
+        assert_eq!(stream, key_stream(42, 200, &keys));
+        assert_ne!(stream, key_stream(43, 200, &keys));
+        assert!(stream.iter().all(|key| keys.contains(key)));
+        let config = FuzzConfig::new(10, 1).exclude(&["Q", "escape"]);
+        assert!(!config.keys.iter().any(|key| key == "q" || key == "escape"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fuzz_reports_death() {
+        // Exits on the third line of input
+        let script = "read a; read b; read c; exit 1".to_string();
+        let config = FuzzConfig::new(20, 7).keys(vec!["enter".to_string()]).input_delay(0);
+        let report = run_fuzz("/bin/sh", &["-c".to_string(), script], &config).unwrap();
+        assert!(report.died.is_some());
+        assert_eq!(report.inputs.len(), 20);
+        assert!(report.steps_run < 20);
+        assert!(matches!(report.events.last().map(|e| e.kind), Some(FuzzEventKind::Died)));
+    }
+}
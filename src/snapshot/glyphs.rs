@@ -0,0 +1,271 @@
+//! Fallback glyphs for characters the built-in fonts do not cover.
+//!
+//! Icon-heavy UIs (Nerd Font symbols, emoji) use codepoints outside the
+//! bundled font8x8 sets, which otherwise render as blank cells. A fallback
+//! chain is tried in order for such characters:
+//!
+//! - [`GlyphFallback::Font`]: a user-provided font in GNU Unifont `.hex` format
+//! - [`GlyphFallback::HexBox`]: the codepoint's hex digits drawn in the cell
+//! - [`GlyphFallback::Tofu`]: an empty box
+//!
+//! An empty chain keeps unknown characters blank.
+//! [`Vt100Terminal::unrenderable_chars`](super::Vt100Terminal::unrenderable_chars)
+//! reports which characters on screen had no real glyph.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Names accepted by [`parse_fallback_chain`]
+pub const FALLBACK_NAMES: &[&str] = &["font", "hex", "tofu"];
+
+/// 3x5 hex digit glyphs, rows top to bottom (`#` = set pixel)
+const HEX_DIGIT_ART: [[&str; 5]; 16] = [
+    ["###", "#.#", "#.#", "#.#", "###"],
+    [".#.", "##.", ".#.", ".#.", "###"],
+    ["###", "..#", "###", "#..", "###"],
+    ["###", "..#", "###", "..#", "###"],
+    ["#.#", "#.#", "###", "..#", "..#"],
+    ["###", "#..", "###", "..#", "###"],
+    ["###", "#..", "###", "#.#", "###"],
+    ["###", "..#", "..#", ".#.", ".#."],
+    ["###", "#.#", "###", "#.#", "###"],
+    ["###", "#.#", "###", "..#", "###"],
+    [".#.", "#.#", "###", "#.#", "#.#"],
+    ["##.", "#.#", "##.", "#.#", "##."],
+    [".##", "#..", "#..", "#..", ".##"],
+    ["##.", "#.#", "#.#", "#.#", "##."],
+    ["###", "#..", "###", "#..", "###"],
+    ["###", "#..", "##.", "#..", "#.."],
+];
+
+/// Bitmap font loaded from a GNU Unifont `.hex` file.
+///
+/// Each line is `CODEPOINT:BITMAP` with 32 hex digits for an 8x16 glyph or
+/// 64 for a 16x16 glyph; wide glyphs are squeezed into one cell.
+#[derive(Clone, Default)]
+pub struct HexFont {
+    glyphs: HashMap<char, [u8; 16]>,
+}
+
+impl std::fmt::Debug for HexFont {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HexFont").field("glyphs", &self.glyphs.len()).finish()
+    }
+}
+
+impl HexFont {
+    /// Parse `.hex` font data
+    pub fn parse(data: &str) -> Result<Self, String> {
+        let mut glyphs = HashMap::new();
+        for (idx, line) in data.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("line {}: expected CODEPOINT:BITMAP", idx + 1);
+            let (code, bitmap) = line.split_once(':').ok_or_else(invalid)?;
+            let ch = u32::from_str_radix(code, 16).ok().and_then(char::from_u32).ok_or_else(invalid)?;
+            glyphs.insert(ch, parse_hex_bitmap(bitmap).ok_or_else(invalid)?);
+        }
+        Ok(Self { glyphs })
+    }
+
+    /// Load a `.hex` font file
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        Self::parse(&fs::read_to_string(path)?)
+            .map_err(|msg| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), msg)))
+    }
+
+    /// Glyph for a character (leftmost pixel in the least significant bit)
+    pub fn get(&self, ch: char) -> Option<[u8; 16]> {
+        self.glyphs.get(&ch).copied()
+    }
+
+    /// Number of glyphs in the font
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    /// Whether the font has no glyphs
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+}
+
+/// Parse a 32- or 64-digit `.hex` bitmap into 16 rows of 8 pixels
+fn parse_hex_bitmap(hex: &str) -> Option<[u8; 16]> {
+    let row_digits = match hex.len() {
+        32 => 2,
+        64 => 4,
+        _ => return None,
+    };
+    let mut rows = [0u8; 16];
+    for (row, slot) in rows.iter_mut().enumerate() {
+        let bits = u16::from_str_radix(hex.get(row * row_digits..(row + 1) * row_digits)?, 16).ok()?;
+        // `.hex` stores the leftmost pixel in the most significant bit
+        *slot = if row_digits == 2 {
+            (bits as u8).reverse_bits()
+        } else {
+            (0..8).fold(0u8, |acc, px| {
+                let pair = (bits >> (14 - px * 2)) & 0b11;
+                acc | (u8::from(pair != 0) << px)
+            })
+        };
+    }
+    Some(rows)
+}
+
+/// One step of the glyph fallback chain
+#[derive(Debug, Clone)]
+pub enum GlyphFallback {
+    /// Glyphs from a user-provided font
+    Font(Arc<HexFont>),
+    /// A box showing the codepoint in hex
+    HexBox,
+    /// An empty box ("tofu")
+    Tofu,
+}
+
+impl GlyphFallback {
+    /// Glyph for a character, or `None` to try the next fallback
+    pub fn bitmap(&self, ch: char) -> Option<[u8; 16]> {
+        match self {
+            GlyphFallback::Font(font) => font.get(ch),
+            GlyphFallback::HexBox => Some(hex_box_bitmap(ch)),
+            GlyphFallback::Tofu => Some(tofu_bitmap()),
+        }
+    }
+}
+
+/// Glyph from the first fallback in the chain that provides one
+pub fn fallback_bitmap(ch: char, chain: &[GlyphFallback]) -> Option<[u8; 16]> {
+    chain.iter().find_map(|fallback| fallback.bitmap(ch))
+}
+
+/// Whether a user font in the chain has a real glyph for the character
+pub fn font_has_glyph(ch: char, chain: &[GlyphFallback]) -> bool {
+    chain
+        .iter()
+        .any(|fallback| matches!(fallback, GlyphFallback::Font(font) if font.get(ch).is_some()))
+}
+
+/// Parse a comma-separated chain such as `font,hex,tofu` (`none` for an empty chain).
+///
+/// `font` requires a font to be given.
+pub fn parse_fallback_chain(spec: &str, font: Option<Arc<HexFont>>) -> Result<Vec<GlyphFallback>, String> {
+    let mut chain = Vec::new();
+    for name in spec.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        match name.to_lowercase().as_str() {
+            "none" => {}
+            "font" => chain.push(GlyphFallback::Font(
+                font.clone().ok_or("glyph fallback 'font' needs a font file")?,
+            )),
+            "hex" => chain.push(GlyphFallback::HexBox),
+            "tofu" => chain.push(GlyphFallback::Tofu),
+            other => {
+                return Err(format!(
+                    "unknown glyph fallback '{}' (expected: {}, or none)",
+                    other,
+                    FALLBACK_NAMES.join(", ")
+                ))
+            }
+        }
+    }
+    Ok(chain)
+}
+
+/// `U+XXXX` label for a character
+pub fn codepoint_label(ch: char) -> String {
+    format!("U+{:04X}", ch as u32)
+}
+
+/// Draw a 3x5 hex digit at the given column and row
+fn draw_hex_digit(bitmap: &mut [u8; 16], digit: u32, col: u32, row: usize) {
+    for (dy, line) in HEX_DIGIT_ART[digit as usize].iter().enumerate() {
+        for (dx, pixel) in line.bytes().enumerate() {
+            if pixel == b'#' {
+                bitmap[row + dy] |= 1 << (col + dx as u32);
+            }
+        }
+    }
+}
+
+/// Codepoint digits in two-digit lines: a ruled box with two lines for the
+/// BMP, three unruled lines for higher planes
+fn hex_box_bitmap(ch: char) -> [u8; 16] {
+    let code = ch as u32;
+    let mut bitmap = [0u8; 16];
+    let (digits, line_rows): (u32, &[usize]) = if code <= 0xFFFF {
+        bitmap[0] = 0xFF;
+        bitmap[15] = 0xFF;
+        (4, &[2, 9])
+    } else {
+        (6, &[0, 5, 10])
+    };
+    for (line, &row) in line_rows.iter().enumerate() {
+        for (idx, col) in [0, 4].into_iter().enumerate() {
+            let position = digits - 1 - (line * 2 + idx) as u32;
+            draw_hex_digit(&mut bitmap, (code >> (position * 4)) & 0xF, col, row);
+        }
+    }
+    bitmap
+}
+
+/// Hollow rectangle inset by one pixel
+fn tofu_bitmap() -> [u8; 16] {
+    let mut bitmap = [0b0100_0010u8; 16];
+    bitmap[0] = 0;
+    bitmap[15] = 0;
+    bitmap[1] = 0b0111_1110;
+    bitmap[14] = 0b0111_1110;
+    bitmap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_font_parses_narrow_and_wide_glyphs() {
+        let narrow = format!("E0B0:{}", "80".repeat(16));
+        let wide = format!("1F600:{}", "C000".repeat(16));
+        let font = HexFont::parse(&format!("# comment\n{}\n{}\n", narrow, wide)).unwrap();
+        assert_eq!(font.len(), 2);
+        // Leftmost pixel ends up in the least significant bit
+        assert_eq!(font.get('\u{E0B0}').unwrap(), [1; 16]);
+        assert_eq!(font.get('\u{1F600}').unwrap(), [1; 16]);
+        assert!(HexFont::parse("zz:00").is_err());
+    }
+
+    #[test]
+    fn test_fallback_chain_order() {
+        let font = Arc::new(HexFont::parse(&format!("E0B0:{}", "FF".repeat(16))).unwrap());
+        let chain = parse_fallback_chain("font,tofu", Some(font)).unwrap();
+        assert_eq!(fallback_bitmap('\u{E0B0}', &chain), Some([0xFF; 16]));
+        assert_eq!(fallback_bitmap('\u{E0B1}', &chain), Some(tofu_bitmap()));
+        assert!(font_has_glyph('\u{E0B0}', &chain));
+        assert!(!font_has_glyph('\u{E0B1}', &chain));
+
+        assert!(parse_fallback_chain("none", None).unwrap().is_empty());
+        assert!(parse_fallback_chain("font", None).is_err());
+        assert!(parse_fallback_chain("sparkles", None).is_err());
+    }
+
+    #[test]
+    fn test_hex_box_shows_codepoint_digits() {
+        let mut expected = [0u8; 16];
+        expected[0] = 0xFF;
+        expected[15] = 0xFF;
+        // U+E0B1: "E0" on the first line, "B1" on the second
+        draw_hex_digit(&mut expected, 0xE, 0, 2);
+        draw_hex_digit(&mut expected, 0x0, 4, 2);
+        draw_hex_digit(&mut expected, 0xB, 0, 9);
+        draw_hex_digit(&mut expected, 0x1, 4, 9);
+        assert_eq!(hex_box_bitmap('\u{E0B1}'), expected);
+        assert_eq!(codepoint_label('\u{1F600}'), "U+1F600");
+    }
+}
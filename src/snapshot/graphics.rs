@@ -0,0 +1,98 @@
+//! Images placed on the terminal screen by graphics protocols.
+//!
+//! Decoded images are anchored to a cell and scroll with the text. They are
+//! kept at output resolution and blended over the rendered cells.
+
+use image::{RgbImage, RgbaImage};
+
+use super::pty::{CELL_HEIGHT, CELL_WIDTH};
+
+/// An image anchored to a screen cell
+#[derive(Debug, Clone)]
+pub struct InlineImage {
+    /// Column of the top-left corner
+    pub col: u32,
+    /// Row of the top-left corner (negative once scrolled partly off the top)
+    pub row: i64,
+    /// Pixels at output resolution
+    pub image: RgbaImage,
+    /// Image id assigned by the kitty graphics protocol
+    pub id: Option<u32>,
+}
+
+/// Where the cursor goes once an image is placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageCursor {
+    /// To the line below the image, in the same column (sixel, iTerm2)
+    BelowImage,
+    /// To the cell after the image, on its last row (kitty)
+    AfterImage,
+    /// Stays where it is
+    Unmoved,
+}
+
+impl InlineImage {
+    /// Number of text rows the image covers
+    pub fn rows(&self) -> u32 {
+        self.image.height().div_ceil(CELL_HEIGHT)
+    }
+
+    /// Number of text columns the image covers
+    pub fn cols(&self) -> u32 {
+        self.image.width().div_ceil(CELL_WIDTH)
+    }
+
+    /// Whether any part of the image is still below the top of the screen
+    pub(crate) fn is_visible(&self) -> bool {
+        self.row + i64::from(self.rows()) > 0
+    }
+}
+
+/// Alpha-blend images over a rendered screen, in placement order.
+///
+/// `first_row` is the screen row drawn at the top of `target`, for cropped renders.
+pub(crate) fn composite(target: &mut RgbImage, images: &[InlineImage], first_row: i64) {
+    let (width, height) = target.dimensions();
+    for placed in images {
+        let left = i64::from(placed.col) * i64::from(CELL_WIDTH);
+        let top = (placed.row - first_row) * i64::from(CELL_HEIGHT);
+        for (x, y, pixel) in placed.image.enumerate_pixels() {
+            let (tx, ty) = (left + i64::from(x), top + i64::from(y));
+            if tx < 0 || ty < 0 || tx >= i64::from(width) || ty >= i64::from(height) {
+                continue;
+            }
+            let alpha = u16::from(pixel[3]);
+            if alpha == 0 {
+                continue;
+            }
+            let under = target.get_pixel_mut(tx as u32, ty as u32);
+            for channel in 0..3 {
+                let blended = (u16::from(pixel[channel]) * alpha + u16::from(under[channel]) * (255 - alpha)) / 255;
+                under[channel] = blended as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, Rgba};
+
+    #[test]
+    fn test_composite_blends_and_clips() {
+        let mut target = RgbImage::from_pixel(CELL_WIDTH * 2, CELL_HEIGHT * 2, Rgb([0, 0, 0]));
+        let mut image = RgbaImage::from_pixel(CELL_WIDTH, CELL_HEIGHT * 2, Rgba([255, 255, 255, 255]));
+        image.put_pixel(0, CELL_HEIGHT + 1, Rgba([200, 100, 0, 128]));
+        let placed = InlineImage { col: 1, row: -1, image, id: None };
+        assert_eq!((placed.rows(), placed.cols()), (2, 1));
+        assert!(placed.is_visible());
+
+        composite(&mut target, &[placed], 0);
+        assert_eq!(target.get_pixel(0, 0), &Rgb([0, 0, 0]));
+        assert_eq!(target.get_pixel(CELL_WIDTH, 0), &Rgb([255, 255, 255]));
+        assert_eq!(target.get_pixel(CELL_WIDTH, 1), &Rgb([100, 50, 0]));
+        // The top half was scrolled off, so the second row of cells stays untouched
+        assert_eq!(target.get_pixel(CELL_WIDTH, CELL_HEIGHT), &Rgb([0, 0, 0]));
+    }
+}
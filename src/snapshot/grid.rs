@@ -0,0 +1,223 @@
+//! Cell grid snapshots for programmatic assertions.
+//!
+//! A [`TerminalGrid`] is a plain copy of what is on screen: each cell's
+//! character, colors and attributes, plus the cursor. It serializes to JSON
+//! (`run --format grid-json`), so tests in any language can check colors
+//! and positions without reading pixels.
+//!
+//! [`StateCaptureResult::grid`](super::pty::StateCaptureResult::grid) holds
+//! the grid of each state of a run with
+//! [`RunOptions::capture_grid`](super::pty::RunOptions::capture_grid), and
+//! [`CaptureResult::grid`](super::backend::CaptureResult::grid) that of a
+//! [`PtyBackend`](super::backend::PtyBackend) capture.
+//!
+//! Colors are stored as the emulator holds them: an `inverse` cell keeps its
+//! own colors and lists the attribute, and reverse-screen mode is reported
+//! in [`TerminalGrid::reverse_screen`] rather than applied.
+
+use serde::{Deserialize, Serialize};
+
+use super::pty::{CursorState, UnderlineStyle, Vt100Terminal};
+
+/// Text attribute of a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GridAttribute {
+    Bold,
+    Dim,
+    Italic,
+    Underline,
+    DoubleUnderline,
+    CurlyUnderline,
+    DottedUnderline,
+    DashedUnderline,
+    Blink,
+    Inverse,
+    Strikethrough,
+}
+
+/// One character cell
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GridCell {
+    /// Character in the cell (a space when blank)
+    pub ch: char,
+    /// Foreground color
+    pub fg: [u8; 3],
+    /// Background color
+    pub bg: [u8; 3],
+    /// Attributes set on the cell
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attrs: Vec<GridAttribute>,
+    /// Underline color, when set separately from the foreground
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underline_color: Option<[u8; 3]>,
+    /// Target of the OSC 8 hyperlink the cell belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+}
+
+impl GridCell {
+    /// Whether the cell has an attribute
+    pub fn has(&self, attr: GridAttribute) -> bool {
+        self.attrs.contains(&attr)
+    }
+}
+
+/// Cursor position and appearance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GridCursor {
+    /// Row (0-based)
+    pub row: u32,
+    /// Column (0-based)
+    pub col: u32,
+    /// Visibility, shape and blinking
+    #[serde(flatten)]
+    pub state: CursorState,
+}
+
+/// Characters, colors and attributes of every cell on screen, and the cursor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TerminalGrid {
+    /// Width in columns
+    pub width: u32,
+    /// Height in rows
+    pub height: u32,
+    /// Default foreground color
+    pub default_fg: [u8; 3],
+    /// Default background color
+    pub default_bg: [u8; 3],
+    /// Whether reverse-screen mode (DECSCNM) swaps the default colors
+    #[serde(default)]
+    pub reverse_screen: bool,
+    /// Cursor
+    pub cursor: GridCursor,
+    /// Cells, row by row
+    pub rows: Vec<Vec<GridCell>>,
+}
+
+impl TerminalGrid {
+    /// Copy the screen of a terminal
+    pub fn from_terminal(terminal: &Vt100Terminal) -> Self {
+        let (default_fg, default_bg) = terminal.default_colors();
+        let rows = (0..terminal.height as usize)
+            .map(|r| {
+                (0..terminal.width as usize)
+                    .map(|c| {
+                        let attrs = terminal.attributes[r][c];
+                        GridCell {
+                            ch: terminal.buffer[r][c],
+                            fg: terminal.fg_colors[r][c],
+                            bg: terminal.bg_colors[r][c],
+                            attrs: grid_attributes(&attrs),
+                            underline_color: attrs.underline.then_some(attrs.underline_color).flatten(),
+                            link: attrs.hyperlink.and_then(|index| terminal.hyperlink_uri(index)).map(str::to_string),
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Self {
+            width: terminal.width,
+            height: terminal.height,
+            default_fg,
+            default_bg,
+            reverse_screen: terminal.reverse_screen(),
+            cursor: GridCursor {
+                row: terminal.cursor_y,
+                col: terminal.cursor_x,
+                state: terminal.cursor_state(),
+            },
+            rows,
+        }
+    }
+
+    /// The cell at a position, if it is on screen
+    pub fn cell(&self, row: u32, col: u32) -> Option<&GridCell> {
+        self.rows.get(row as usize)?.get(col as usize)
+    }
+
+    /// Text of a row, without trailing blanks
+    pub fn row_text(&self, row: u32) -> String {
+        let text: String = self.rows.get(row as usize).into_iter().flatten().map(|cell| cell.ch).collect();
+        text.trim_end().to_string()
+    }
+
+    /// `len` characters starting at a position, cut off at the end of the row
+    pub fn text_at(&self, row: u32, col: u32, len: usize) -> String {
+        let cells = self.rows.get(row as usize).map(Vec::as_slice).unwrap_or_default();
+        cells.iter().skip(col as usize).take(len).map(|cell| cell.ch).collect()
+    }
+
+    /// Position (row, column) of the first occurrence of `text` within a row
+    pub fn find(&self, text: &str) -> Option<(u32, u32)> {
+        let needle: Vec<char> = text.chars().collect();
+        if needle.is_empty() {
+            return None;
+        }
+        self.rows.iter().enumerate().find_map(|(row, cells)| {
+            cells
+                .windows(needle.len())
+                .position(|window| window.iter().map(|cell| cell.ch).eq(needle.iter().copied()))
+                .map(|col| (row as u32, col as u32))
+        })
+    }
+
+    /// Cells of the first occurrence of `text`, e.g. to check how a label is styled
+    pub fn cells_of(&self, text: &str) -> Option<&[GridCell]> {
+        let (row, col) = self.find(text)?;
+        let start = col as usize;
+        Some(&self.rows[row as usize][start..start + text.chars().count()])
+    }
+}
+
+/// Attribute list of a cell, in [`GridAttribute`] order
+fn grid_attributes(attrs: &super::pty::CellAttributes) -> Vec<GridAttribute> {
+    let underline = match attrs.underline_style {
+        _ if !attrs.underline => None,
+        UnderlineStyle::Double => Some(GridAttribute::DoubleUnderline),
+        UnderlineStyle::Curly => Some(GridAttribute::CurlyUnderline),
+        UnderlineStyle::Dotted => Some(GridAttribute::DottedUnderline),
+        UnderlineStyle::Dashed => Some(GridAttribute::DashedUnderline),
+        UnderlineStyle::None | UnderlineStyle::Single => Some(GridAttribute::Underline),
+    };
+    [
+        attrs.bold.then_some(GridAttribute::Bold),
+        attrs.dim.then_some(GridAttribute::Dim),
+        attrs.italic.then_some(GridAttribute::Italic),
+        underline,
+        attrs.blink.then_some(GridAttribute::Blink),
+        attrs.inverse.then_some(GridAttribute::Inverse),
+        attrs.strikethrough.then_some(GridAttribute::Strikethrough),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::Vt100Parser;
+
+    #[test]
+    fn test_grid_keeps_colors_attributes_and_cursor() {
+        let mut parser = Vt100Parser::new(12, 2);
+        parser.process(b"\x1b[1;31mError\x1b[0m \x1b[4:3;7mx\x1b[0m\r\n\x1b]8;;https://example.com\x07go\x1b]8;;\x07");
+        let grid = parser.terminal().to_grid();
+        assert_eq!(grid.row_text(0), "Error x");
+        let error = grid.cell(0, 0).unwrap();
+        assert_eq!((error.ch, error.fg, error.attrs.as_slice()), ('E', [0xcd, 0x31, 0x31], &[GridAttribute::Bold][..]));
+        assert_eq!(grid.cell(0, 6).unwrap().attrs, vec![GridAttribute::CurlyUnderline, GridAttribute::Inverse]);
+        assert_eq!(grid.cell(1, 1).unwrap().link.as_deref(), Some("https://example.com"));
+        assert_eq!((grid.cursor.row, grid.cursor.col), (1, 2));
+        assert!(grid.cell(2, 0).is_none());
+        assert_eq!(grid.find("x"), Some((0, 6)));
+        assert_eq!(grid.text_at(0, 2, 3), "ror");
+        assert!(grid.cells_of("Error").unwrap().iter().all(|cell| cell.has(GridAttribute::Bold)));
+        assert_eq!(grid.find("missing"), None);
+
+        let json = serde_json::to_string(&grid).unwrap();
+        assert!(json.contains(r#"{"ch":"E","fg":[205,49,49],"bg":[0,0,0],"attrs":["bold"]}"#));
+        assert_eq!(serde_json::from_str::<TerminalGrid>(&json).unwrap(), grid);
+    }
+}
@@ -0,0 +1,103 @@
+//! OSC 8 hyperlinks found in captured terminal states, with optional validation.
+//!
+//! Syntax checks are always available. Reachability checks issue an HTTP
+//! HEAD request and are only compiled with the `link-check` feature.
+
+use serde::{Deserialize, Serialize};
+
+/// A hyperlink (OSC 8) occupying a run of cells on one row
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hyperlink {
+    /// Target URI
+    pub uri: String,
+    /// Visible link text
+    pub text: String,
+    /// Row of the link (0-based)
+    pub row: u32,
+    /// First column covered by the link (inclusive)
+    pub start_col: u32,
+    /// Last column covered by the link (inclusive)
+    pub end_col: u32,
+}
+
+/// Check that a URI is syntactically valid.
+///
+/// Requires an RFC 3986 scheme, no whitespace or control characters, and a
+/// host for network schemes (`http`, `https`, `ftp`).
+pub fn check_uri_syntax(uri: &str) -> Result<(), String> {
+    let (scheme, rest) = uri
+        .split_once(':')
+        .ok_or_else(|| "missing scheme".to_string())?;
+
+    let mut scheme_chars = scheme.chars();
+    let valid_scheme = scheme_chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && scheme_chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    if !valid_scheme {
+        return Err(format!("invalid scheme '{}'", scheme));
+    }
+    if rest.is_empty() {
+        return Err("empty URI after scheme".to_string());
+    }
+    if uri.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("contains whitespace or control characters".to_string());
+    }
+
+    if matches!(scheme.to_ascii_lowercase().as_str(), "http" | "https" | "ftp") {
+        let host = rest
+            .strip_prefix("//")
+            .map(|authority| authority.split(['/', '?', '#']).next().unwrap_or(""))
+            .unwrap_or("");
+        if host.is_empty() {
+            return Err("missing host".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Check that an HTTP(S) URI responds to a HEAD request with a non-error status.
+///
+/// Returns the HTTP status code on success.
+#[cfg(feature = "link-check")]
+pub fn check_uri_reachable(uri: &str, timeout_secs: u64) -> Result<u16, String> {
+    check_uri_syntax(uri)?;
+    let timeout = std::time::Duration::from_secs(timeout_secs);
+    let agent = ureq::AgentBuilder::new().timeout_connect(timeout).timeout(timeout).build();
+    match agent.head(uri).call() {
+        Ok(response) => Ok(response.status()),
+        Err(ureq::Error::Status(code, _)) => Err(format!("HTTP {}", code)),
+        Err(ureq::Error::Transport(transport)) => Err(format!("connection failed: {}", transport)),
+    }
+}
+
+/// Validate a hyperlink, returning a description of the problem if any.
+///
+/// Always checks syntax; with `check_reachable` (requires the `link-check`
+/// feature) also issues a HEAD request for HTTP(S) links.
+pub fn validate_hyperlink(link: &Hyperlink, check_reachable: bool) -> Option<String> {
+    if let Err(e) = check_uri_syntax(&link.uri) {
+        return Some(e);
+    }
+    #[cfg(feature = "link-check")]
+    if check_reachable && (link.uri.starts_with("http://") || link.uri.starts_with("https://")) {
+        return check_uri_reachable(&link.uri, 10).err();
+    }
+    #[cfg(not(feature = "link-check"))]
+    let _ = check_reachable;
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uri_syntax() {
+        assert!(check_uri_syntax("https://example.com/docs").is_ok());
+        assert!(check_uri_syntax("file:///tmp/report.txt").is_ok());
+        assert!(check_uri_syntax("mailto:dev@example.com").is_ok());
+        assert!(check_uri_syntax("example.com").is_err());
+        assert!(check_uri_syntax("https:///path").is_err());
+        assert!(check_uri_syntax("1http://example.com").is_err());
+        assert!(check_uri_syntax("https://example.com/a b").is_err());
+    }
+}
@@ -0,0 +1,455 @@
+//! Kitty graphics protocol and iTerm2 inline images.
+//!
+//! Kitty graphics commands arrive as APC strings
+//! (`ESC _ G key=value,... ; payload ESC \`), which the VT parser discards,
+//! so [`ApcScanner`] picks them out of the byte stream. [`KittyGraphics`]
+//! keeps transmitted images by id and turns each command into a placement
+//! and a reply. iTerm2 images arrive as `OSC 1337 ; File=args : base64` and
+//! are decoded by [`decode_iterm_file`].
+//!
+//! Images are scaled to output resolution, where one cell is
+//! [`CELL_WIDTH`] x [`CELL_HEIGHT`] pixels.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use image::imageops::FilterType;
+use image::RgbaImage;
+
+use super::pty::{CELL_HEIGHT, CELL_WIDTH};
+
+/// Largest APC string or image payload accepted, so corrupt data cannot exhaust memory
+pub const MAX_IMAGE_PAYLOAD: usize = 64 * 1024 * 1024;
+
+/// Largest width or height of a decoded or scaled image
+pub const MAX_IMAGE_DIMENSION: u32 = 4096;
+
+/// Base64 as sent by kitty chunks, where padding may be left off
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum ApcState {
+    #[default]
+    Ground,
+    Escape,
+    Body,
+    BodyEscape,
+}
+
+/// Collects APC strings (`ESC _ ... ESC \`) from a raw byte stream
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ApcScanner {
+    state: ApcState,
+    body: Vec<u8>,
+    overflowed: bool,
+}
+
+impl ApcScanner {
+    /// Feed one byte. Returns the body of an APC string once its terminator arrives.
+    pub(crate) fn advance(&mut self, byte: u8) -> Option<Vec<u8>> {
+        match (self.state, byte) {
+            (ApcState::Ground | ApcState::Escape, 0x1b) => self.state = ApcState::Escape,
+            (ApcState::Escape | ApcState::BodyEscape, b'_') => {
+                self.state = ApcState::Body;
+                self.body.clear();
+                self.overflowed = false;
+            }
+            (ApcState::Body, 0x1b) => self.state = ApcState::BodyEscape,
+            (ApcState::Body, 0x9c) | (ApcState::BodyEscape, b'\\') => {
+                self.state = ApcState::Ground;
+                let body = std::mem::take(&mut self.body);
+                return (!self.overflowed).then_some(body);
+            }
+            // CAN and SUB abort the string
+            (ApcState::Body, 0x18 | 0x1a) => self.state = ApcState::Ground,
+            (ApcState::Body, _) => {
+                if self.body.len() < MAX_IMAGE_PAYLOAD {
+                    self.body.push(byte);
+                } else {
+                    self.overflowed = true;
+                }
+            }
+            (ApcState::BodyEscape, _) => self.state = ApcState::Ground,
+            (ApcState::Ground, _) | (ApcState::Escape, _) => self.state = ApcState::Ground,
+        }
+        None
+    }
+}
+
+/// Control keys of one kitty graphics command
+#[derive(Debug, Clone)]
+struct KittyCommand {
+    /// `a`: t(ransmit), T(ransmit and display), p(ut), d(elete), q(uery)
+    action: u8,
+    /// `f`: 24 (RGB), 32 (RGBA) or 100 (PNG)
+    format: u32,
+    /// `t`: d(irect), f(ile) or t(emporary file)
+    medium: u8,
+    /// `o`: compression of the payload (`z` for zlib)
+    compression: Option<u8>,
+    /// `s`, `v`: pixel size of raw data
+    width: u32,
+    height: u32,
+    /// `i`: image id
+    id: Option<u32>,
+    /// `m=1`: more chunks follow
+    more: bool,
+    /// `c`, `r`: cells to scale the image to
+    cols: u32,
+    rows: u32,
+    /// `C=1`: leave the cursor where it is
+    hold_cursor: bool,
+    /// `q`: 1 suppresses OK replies, 2 suppresses errors too
+    quiet: u32,
+    /// `d`: what to delete
+    delete: u8,
+}
+
+impl KittyCommand {
+    fn parse(control: &[u8]) -> Self {
+        let mut command = Self {
+            action: b't',
+            format: 32,
+            medium: b'd',
+            compression: None,
+            width: 0,
+            height: 0,
+            id: None,
+            more: false,
+            cols: 0,
+            rows: 0,
+            hold_cursor: false,
+            quiet: 0,
+            delete: b'a',
+        };
+        for pair in control.split(|&b| b == b',') {
+            let [key, b'=', value @ ..] = pair else { continue };
+            let number = || std::str::from_utf8(value).ok()?.parse::<u32>().ok();
+            let letter = value.first().copied();
+            match key {
+                b'a' => command.action = letter.unwrap_or(b't'),
+                b'f' => command.format = number().unwrap_or(32),
+                b't' => command.medium = letter.unwrap_or(b'd'),
+                b'o' => command.compression = letter,
+                b's' => command.width = number().unwrap_or(0),
+                b'v' => command.height = number().unwrap_or(0),
+                b'i' => command.id = number().filter(|&id| id != 0),
+                b'm' => command.more = number() == Some(1),
+                b'c' => command.cols = number().unwrap_or(0),
+                b'r' => command.rows = number().unwrap_or(0),
+                b'C' => command.hold_cursor = number() == Some(1),
+                b'q' => command.quiet = number().unwrap_or(0),
+                b'd' => command.delete = letter.unwrap_or(b'a'),
+                _ => {}
+            }
+        }
+        command
+    }
+}
+
+/// An image to place at the cursor in response to a kitty command
+#[derive(Debug, Clone)]
+pub struct KittyPlacement {
+    /// Image id, if the command gave one
+    pub id: Option<u32>,
+    /// Pixels at output resolution
+    pub image: RgbaImage,
+    /// Whether the cursor moves past the image (`C=1` keeps it in place)
+    pub move_cursor: bool,
+}
+
+/// What the terminal should do in response to a kitty graphics command
+#[derive(Debug, Clone, Default)]
+pub struct KittyOutcome {
+    /// Image to place at the cursor
+    pub place: Option<KittyPlacement>,
+    /// Placements to remove: `Some(None)` for all, `Some(Some(id))` for one image
+    pub delete: Option<Option<u32>>,
+    /// Reply to write back to the application
+    pub reply: Option<String>,
+}
+
+/// State of the kitty graphics protocol: transmitted images and chunked uploads
+#[derive(Debug, Clone, Default)]
+pub struct KittyGraphics {
+    images: HashMap<u32, RgbaImage>,
+    /// Chunked transmission in progress: its first command and the payload so far
+    pending: Option<(KittyCommand, Vec<u8>)>,
+}
+
+impl KittyGraphics {
+    /// Handle the body of an APC string. Anything but a graphics command (`G...`) is ignored.
+    pub fn command(&mut self, body: &[u8]) -> KittyOutcome {
+        let Some(body) = body.strip_prefix(b"G") else {
+            return KittyOutcome::default();
+        };
+        let (control, payload) = match body.iter().position(|&b| b == b';') {
+            Some(split) => (&body[..split], &body[split + 1..]),
+            None => (body, &[][..]),
+        };
+        let command = KittyCommand::parse(control);
+
+        // Later chunks carry only `m` (and maybe `q`); the first chunk's keys apply
+        if let Some((first, mut data)) = self.pending.take() {
+            if data.len() + payload.len() <= MAX_IMAGE_PAYLOAD {
+                data.extend_from_slice(payload);
+            }
+            if command.more {
+                self.pending = Some((first, data));
+                return KittyOutcome::default();
+            }
+            return self.execute(first, &data);
+        }
+        if command.more && matches!(command.action, b't' | b'T' | b'q') {
+            self.pending = Some((command, payload.to_vec()));
+            return KittyOutcome::default();
+        }
+        self.execute(command, payload)
+    }
+
+    fn execute(&mut self, command: KittyCommand, payload: &[u8]) -> KittyOutcome {
+        let mut outcome = KittyOutcome::default();
+        let place = |image: &RgbaImage| KittyPlacement {
+            id: command.id,
+            image: scale_image(image.clone(), cell_pixels(command.cols, CELL_WIDTH), cell_pixels(command.rows, CELL_HEIGHT), false),
+            move_cursor: !command.hold_cursor,
+        };
+        let result = match command.action {
+            b't' | b'T' | b'q' => decode_kitty_image(&command, payload).map(|image| {
+                if command.action == b'T' {
+                    outcome.place = Some(place(&image));
+                }
+                if command.action != b'q'
+                    && let Some(id) = command.id
+                {
+                    self.images.insert(id, image);
+                }
+            }),
+            b'p' => match command.id.and_then(|id| self.images.get(&id)) {
+                Some(image) => {
+                    outcome.place = Some(place(image));
+                    Ok(())
+                }
+                None => Err("ENOENT:image not found"),
+            },
+            b'd' => {
+                // Upper-case selectors also free the stored image data
+                let target = match command.delete.to_ascii_lowercase() {
+                    b'i' => command.id,
+                    _ => None,
+                };
+                if command.delete.is_ascii_uppercase() {
+                    match target {
+                        Some(id) => {
+                            self.images.remove(&id);
+                        }
+                        None => self.images.clear(),
+                    }
+                }
+                outcome.delete = Some(target);
+                return outcome;
+            }
+            _ => Err("EINVAL:unsupported action"),
+        };
+
+        // Only commands that name an image get a reply
+        if let Some(id) = command.id {
+            outcome.reply = match result {
+                Ok(()) if command.quiet == 0 => Some(format!("\x1b_Gi={};OK\x1b\\", id)),
+                Err(error) if command.quiet < 2 => Some(format!("\x1b_Gi={};{}\x1b\\", id, error)),
+                _ => None,
+            };
+        }
+        outcome
+    }
+}
+
+fn cell_pixels(cells: u32, cell_size: u32) -> Option<u32> {
+    (cells > 0).then(|| cells.saturating_mul(cell_size))
+}
+
+/// Decode the payload of a transmit or query command
+fn decode_kitty_image(command: &KittyCommand, payload: &[u8]) -> Result<RgbaImage, &'static str> {
+    let payload: Vec<u8> = payload.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+    let data = BASE64.decode(payload).map_err(|_| "EINVAL:invalid base64 payload")?;
+    let data = match command.medium {
+        b'd' => data,
+        b'f' | b't' => {
+            let path = String::from_utf8(data).map_err(|_| "EINVAL:invalid file name")?;
+            std::fs::read(path).map_err(|_| "EBADF:cannot read file")?
+        }
+        _ => return Err("ENOTSUPPORTED:transmission medium not supported"),
+    };
+    let data = match command.compression {
+        None => data,
+        Some(b'z') => {
+            let mut inflated = Vec::new();
+            flate2::read::ZlibDecoder::new(&data[..])
+                .take(MAX_IMAGE_PAYLOAD as u64)
+                .read_to_end(&mut inflated)
+                .map_err(|_| "EINVAL:invalid zlib data")?;
+            inflated
+        }
+        Some(_) => return Err("ENOTSUPPORTED:compression not supported"),
+    };
+
+    let image = match command.format {
+        24 | 32 => {
+            let (width, height) = (command.width, command.height);
+            if width == 0 || height == 0 || width > MAX_IMAGE_DIMENSION || height > MAX_IMAGE_DIMENSION {
+                return Err("EINVAL:invalid image size");
+            }
+            let channels = (command.format / 8) as usize;
+            let pixels = width as usize * height as usize;
+            if data.len() < pixels * channels {
+                return Err("ENODATA:insufficient image data");
+            }
+            let rgba: Vec<u8> = data[..pixels * channels]
+                .chunks_exact(channels)
+                .flat_map(|px| [px[0], px[1], px[2], if channels == 4 { px[3] } else { 255 }])
+                .collect();
+            RgbaImage::from_raw(width, height, rgba).ok_or("EINVAL:invalid image size")?
+        }
+        100 => image::load_from_memory(&data).map_err(|_| "EBADPNG:cannot decode image")?.to_rgba8(),
+        _ => return Err("EINVAL:unknown format"),
+    };
+    if image.width() > MAX_IMAGE_DIMENSION || image.height() > MAX_IMAGE_DIMENSION {
+        return Err("EINVAL:image too large");
+    }
+    Ok(image)
+}
+
+/// Decode an iTerm2 `File=` argument list and payload (`name=...;inline=1:base64`)
+/// and scale it as requested. `screen` is the terminal size in cells, for
+/// percentage sizes.
+///
+/// Returns the image and whether the cursor should move past it, or `None` for
+/// downloads (`inline` not set) and undecodable data.
+pub fn decode_iterm_file(file: &[u8], screen: (u32, u32)) -> Option<(RgbaImage, bool)> {
+    let split = file.iter().position(|&b| b == b':')?;
+    let args = std::str::from_utf8(&file[..split]).ok()?;
+    let arg = |name: &str| {
+        args.split(';')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    };
+    if arg("inline") != Some("1") {
+        return None;
+    }
+
+    let payload: Vec<u8> = file[split + 1..].iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+    let data = BASE64.decode(payload).ok()?;
+    let image = image::load_from_memory(&data).ok()?.to_rgba8();
+    if image.width() > MAX_IMAGE_DIMENSION || image.height() > MAX_IMAGE_DIMENSION {
+        return None;
+    }
+    let width = arg("width").and_then(|v| iterm_dimension(v, CELL_WIDTH, screen.0));
+    let height = arg("height").and_then(|v| iterm_dimension(v, CELL_HEIGHT, screen.1));
+    let preserve_aspect = arg("preserveAspectRatio") != Some("0");
+    let move_cursor = arg("doNotMoveCursor") != Some("1");
+    Some((scale_image(image, width, height, preserve_aspect), move_cursor))
+}
+
+/// Pixel size for an iTerm2 `width`/`height` value: `N` cells, `Npx`, `N%` or `auto`
+fn iterm_dimension(value: &str, cell_size: u32, screen_cells: u32) -> Option<u32> {
+    let pixels = if let Some(px) = value.strip_suffix("px") {
+        px.parse().ok()?
+    } else if let Some(percent) = value.strip_suffix('%') {
+        screen_cells * cell_size * percent.parse::<u32>().ok()?.min(100) / 100
+    } else {
+        value.parse::<u32>().ok()?.saturating_mul(cell_size)
+    };
+    (pixels > 0).then_some(pixels)
+}
+
+/// Scale an image to the requested pixel size. A missing dimension follows
+/// the aspect ratio; with both given and `preserve_aspect`, the image is fit
+/// inside the box.
+fn scale_image(image: RgbaImage, width: Option<u32>, height: Option<u32>, preserve_aspect: bool) -> RgbaImage {
+    let (w, h) = (f64::from(image.width()), f64::from(image.height()));
+    let (target_w, target_h) = match (width, height) {
+        (None, None) => return image,
+        (Some(tw), None) => (f64::from(tw), h * f64::from(tw) / w),
+        (None, Some(th)) => (w * f64::from(th) / h, f64::from(th)),
+        (Some(tw), Some(th)) if preserve_aspect => {
+            let scale = (f64::from(tw) / w).min(f64::from(th) / h);
+            (w * scale, h * scale)
+        }
+        (Some(tw), Some(th)) => (f64::from(tw), f64::from(th)),
+    };
+    let clamp = |v: f64| (v.round() as u32).clamp(1, MAX_IMAGE_DIMENSION);
+    let (target_w, target_h) = (clamp(target_w), clamp(target_h));
+    if (target_w, target_h) == image.dimensions() {
+        return image;
+    }
+    image::imageops::resize(&image, target_w, target_h, FilterType::Triangle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn png_base64(width: u32, height: u32) -> String {
+        let image = RgbaImage::from_pixel(width, height, Rgba([0, 255, 0, 255]));
+        let mut png = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut png, image::ImageOutputFormat::Png).unwrap();
+        base64::engine::general_purpose::STANDARD.encode(png.into_inner())
+    }
+
+    #[test]
+    fn test_apc_scanner_extracts_strings() {
+        let mut scanner = ApcScanner::default();
+        let bodies: Vec<Vec<u8>> = b"a\x1b[1m\x1b_Gi=1\x1b\\b\x1b_Gx\x18\x1b_ok\x1b\\"
+            .iter()
+            .filter_map(|&byte| scanner.advance(byte))
+            .collect();
+        assert_eq!(bodies, vec![b"Gi=1".to_vec(), b"ok".to_vec()]);
+    }
+
+    #[test]
+    fn test_kitty_chunked_transmit_reply_and_put() {
+        let mut kitty = KittyGraphics::default();
+        // A 2x1 RGB image sent in two chunks, then displayed at 2x1 cells
+        let data = BASE64.encode([255, 0, 0, 0, 0, 255]);
+        let first = format!("Ga=t,f=24,s=2,v=1,i=7,m=1;{}", &data[..4]);
+        assert!(kitty.command(first.as_bytes()).reply.is_none());
+        let last = kitty.command(format!("Gm=0;{}", &data[4..]).as_bytes());
+        assert_eq!(last.reply.as_deref(), Some("\x1b_Gi=7;OK\x1b\\"));
+        assert!(last.place.is_none());
+
+        let put = kitty.command(b"Ga=p,i=7,c=2,r=1,C=1");
+        let placement = put.place.unwrap();
+        assert_eq!(placement.image.dimensions(), (CELL_WIDTH * 2, CELL_HEIGHT));
+        assert!(!placement.move_cursor);
+
+        let missing = kitty.command(b"Ga=p,i=8");
+        assert_eq!(missing.reply.as_deref(), Some("\x1b_Gi=8;ENOENT:image not found\x1b\\"));
+        assert_eq!(kitty.command(b"Ga=d,d=I,i=7").delete, Some(Some(7)));
+        assert!(kitty.command(b"Ga=p,i=7,q=2").reply.is_none());
+    }
+
+    #[test]
+    fn test_iterm_file_sizes() {
+        let file = |args: &str| format!("{}:{}", args, png_base64(4, 2));
+        assert!(decode_iterm_file(file("name=eA==;size=10").as_bytes(), (10, 5)).is_none());
+
+        let (image, moves) = decode_iterm_file(file("inline=1").as_bytes(), (10, 5)).unwrap();
+        assert_eq!((image.dimensions(), moves), ((4, 2), true));
+
+        // 2 cells wide keeps the 2:1 aspect ratio
+        let (image, _) = decode_iterm_file(file("inline=1;width=2").as_bytes(), (10, 5)).unwrap();
+        assert_eq!(image.dimensions(), (CELL_WIDTH * 2, CELL_WIDTH));
+
+        let args = "inline=1;width=50%;height=10px;preserveAspectRatio=0;doNotMoveCursor=1";
+        let (image, moves) = decode_iterm_file(file(args).as_bytes(), (10, 5)).unwrap();
+        assert_eq!((image.dimensions(), moves), ((CELL_WIDTH * 5, 10), false));
+    }
+}
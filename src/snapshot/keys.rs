@@ -0,0 +1,608 @@
+//! Named keys and modifier combinations.
+//!
+//! Key inputs are a key name or character, optionally prefixed with
+//! modifiers: `shift+tab`, `ctrl+right`, `alt+enter`, `ctrl+shift+home`.
+//! Modifiers are `shift`, `ctrl` (or `control`, `c-`) and `alt` (or `meta`,
+//! `m-`), joined with `+` or `-`.
+//!
+//! Keys are encoded the way xterm does by default: cursor, editing and
+//! function keys carry the modifiers as a CSI parameter (`1 + shift + 2*alt +
+//! 4*ctrl`, so `ctrl+right` is `CSI 1;5C`), while other keys fold them into
+//! the character: ctrl makes a control code, alt prefixes ESC.
+//!
+//! Applications that enable the kitty keyboard protocol (`CSI > flags u`)
+//! get CSI-u encoded keys instead, e.g. `ctrl+i` as `CSI 105;5u` rather than
+//! a tab; see [`KeyPress::to_kitty_bytes`] and [`KeyProtocol`].
+//!
+//! Exact byte sequences the key names cannot express are written as hex,
+//! `raw:1b5b41`, or with escapes, `bytes:\x1b[200~`; see [`parse_raw_input`].
+//!
+//! A bare word is a key when it names one, so typing the word "enter" into a
+//! search box needs `text:enter`. `key:` insists on a key name and `type:`
+//! sends its text one character at a time; see [`parse_explicit_input`].
+//!
+//! Going the other way, [`split_keys`] and [`input_for_key`] turn bytes read
+//! from a real terminal back into inputs, for recording sessions.
+
+use std::str::FromStr;
+
+/// Kitty keyboard flag: disambiguate escape codes
+pub const KITTY_DISAMBIGUATE: u8 = 1;
+/// Kitty keyboard flag: report event types
+pub const KITTY_REPORT_EVENTS: u8 = 2;
+/// Kitty keyboard flag: report alternate (shifted) keys
+pub const KITTY_REPORT_ALTERNATES: u8 = 4;
+/// Kitty keyboard flag: report all keys as escape codes
+pub const KITTY_REPORT_ALL_KEYS: u8 = 8;
+/// Kitty keyboard flag: report associated text
+pub const KITTY_REPORT_TEXT: u8 = 16;
+
+/// A key on the keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    /// A character key (including space)
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    /// Function key F1 to F12
+    F(u8),
+}
+
+/// Modifier keys held during a key press
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+}
+
+impl Modifiers {
+    /// No modifiers held
+    pub fn is_empty(self) -> bool {
+        !(self.shift || self.alt || self.ctrl)
+    }
+
+    /// The xterm modifier parameter: 1 plus 1 for shift, 2 for alt and 4 for ctrl
+    pub fn xterm_param(self) -> u8 {
+        1 + u8::from(self.shift) + 2 * u8::from(self.alt) + 4 * u8::from(self.ctrl)
+    }
+}
+
+/// A key together with its modifiers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyPress {
+    pub key: Key,
+    pub modifiers: Modifiers,
+}
+
+impl Key {
+    /// Look up a key by name (case-insensitive) or as a single character
+    pub fn from_name(name: &str) -> Option<Self> {
+        let mut chars = name.chars();
+        if let (Some(ch), None) = (chars.next(), chars.next()) {
+            return Some(Key::Char(ch));
+        }
+        let key = match name.to_lowercase().as_str() {
+            "up" => Key::Up,
+            "down" => Key::Down,
+            "right" => Key::Right,
+            "left" => Key::Left,
+            "home" => Key::Home,
+            "end" => Key::End,
+            "pageup" | "page_up" | "pgup" => Key::PageUp,
+            "pagedown" | "page_down" | "pgdn" => Key::PageDown,
+            "insert" | "ins" => Key::Insert,
+            "delete" | "del" => Key::Delete,
+            "enter" | "return" => Key::Enter,
+            "space" => Key::Char(' '),
+            "tab" => Key::Tab,
+            "backspace" | "bs" => Key::Backspace,
+            "escape" | "esc" => Key::Escape,
+            other => {
+                let n: u8 = other.strip_prefix('f')?.parse().ok()?;
+                if !(1..=12).contains(&n) {
+                    return None;
+                }
+                Key::F(n)
+            }
+        };
+        Some(key)
+    }
+}
+
+impl KeyPress {
+    /// Parse a key input such as `up`, `shift+tab` or `ctrl+alt+x`.
+    ///
+    /// Returns `None` for inputs that are not a key, such as literal text.
+    pub fn parse(input: &str) -> Option<Self> {
+        let mut rest = input.trim();
+        let mut modifiers = Modifiers::default();
+        // Every part before the last separator must be a modifier; the key
+        // itself may be a separator character, as in `ctrl+-`
+        loop {
+            let last = rest.char_indices().last().map_or(0, |(idx, _)| idx);
+            let Some(idx) = rest[..last].find(['+', '-']) else {
+                break;
+            };
+            match rest[..idx].to_lowercase().as_str() {
+                "shift" => modifiers.shift = true,
+                "ctrl" | "control" | "c" => modifiers.ctrl = true,
+                "alt" | "meta" | "m" => modifiers.alt = true,
+                _ => return None,
+            }
+            rest = &rest[idx + 1..];
+        }
+        Some(KeyPress {
+            key: Key::from_name(rest)?,
+            modifiers,
+        })
+    }
+
+    /// Bytes xterm sends for this key press
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mods = self.modifiers;
+        let csi = |param: &str, final_byte: char| -> Vec<u8> {
+            if mods.is_empty() {
+                format!("\x1b[{}{}", param, final_byte).into_bytes()
+            } else {
+                format!("\x1b[{};{}{}", if param.is_empty() { "1" } else { param }, mods.xterm_param(), final_byte).into_bytes()
+            }
+        };
+        let alt_prefixed = |bytes: &[u8]| -> Vec<u8> {
+            let mut out = if mods.alt { vec![0x1b] } else { Vec::new() };
+            out.extend_from_slice(bytes);
+            out
+        };
+        match self.key {
+            Key::Up => csi("", 'A'),
+            Key::Down => csi("", 'B'),
+            Key::Right => csi("", 'C'),
+            Key::Left => csi("", 'D'),
+            Key::Home => csi("", 'H'),
+            Key::End => csi("", 'F'),
+            Key::Insert => csi("2", '~'),
+            Key::Delete => csi("3", '~'),
+            Key::PageUp => csi("5", '~'),
+            Key::PageDown => csi("6", '~'),
+            Key::F(n @ 1..=4) => {
+                let final_byte = (b'P' + n - 1) as char;
+                if mods.is_empty() {
+                    format!("\x1bO{}", final_byte).into_bytes()
+                } else {
+                    csi("", final_byte)
+                }
+            }
+            Key::F(n) => {
+                const CODES: [u8; 8] = [15, 17, 18, 19, 20, 21, 23, 24];
+                let code = CODES[usize::from(n.clamp(5, 12) - 5)];
+                csi(&code.to_string(), '~')
+            }
+            Key::Tab if mods.shift => {
+                let shift_only = Modifiers { shift: false, ..mods };
+                if shift_only.is_empty() {
+                    b"\x1b[Z".to_vec()
+                } else {
+                    format!("\x1b[1;{}Z", mods.xterm_param()).into_bytes()
+                }
+            }
+            Key::Tab => alt_prefixed(b"\t"),
+            Key::Enter => alt_prefixed(b"\r"),
+            Key::Escape => alt_prefixed(b"\x1b"),
+            Key::Backspace => alt_prefixed(if mods.ctrl { b"\x08" } else { b"\x7f" }),
+            Key::Char(ch) => {
+                let ch = if mods.shift { ch.to_ascii_uppercase() } else { ch };
+                match control_code(ch).filter(|_| mods.ctrl) {
+                    Some(code) => alt_prefixed(&[code]),
+                    None => alt_prefixed(ch.to_string().as_bytes()),
+                }
+            }
+        }
+    }
+}
+
+impl KeyPress {
+    /// Bytes for this key press under the kitty keyboard protocol with `flags`.
+    ///
+    /// With no flags this is the same as [`KeyPress::to_bytes`]. Presses are
+    /// reported; releases and repeats are never generated.
+    pub fn to_kitty_bytes(&self, flags: u8) -> Vec<u8> {
+        if flags == 0 {
+            return self.to_bytes();
+        }
+        let mods = self.modifiers;
+        let all_keys = flags & KITTY_REPORT_ALL_KEYS != 0;
+        let csi_u = |code: u32, alternate: Option<char>, text: Option<char>| -> Vec<u8> {
+            let mut seq = format!("\x1b[{}", code);
+            if let Some(alt) = alternate.filter(|_| flags & KITTY_REPORT_ALTERNATES != 0) {
+                seq.push_str(&format!(":{}", u32::from(alt)));
+            }
+            let text = text.filter(|_| all_keys && flags & KITTY_REPORT_TEXT != 0);
+            if !mods.is_empty() || text.is_some() {
+                seq.push_str(&format!(";{}", mods.xterm_param()));
+            }
+            if let Some(text) = text {
+                seq.push_str(&format!(";{}", u32::from(text)));
+            }
+            seq.push('u');
+            seq.into_bytes()
+        };
+        match self.key {
+            Key::Char(ch) => {
+                // Text keys stay plain text unless ctrl or alt changes their meaning
+                let text_only = !mods.ctrl && !mods.alt;
+                if text_only && !all_keys {
+                    return self.to_bytes();
+                }
+                let base = ch.to_ascii_lowercase();
+                let shifted = if mods.shift { ch.to_ascii_uppercase() } else { ch };
+                let alternate = (mods.shift && shifted != base).then_some(shifted);
+                csi_u(u32::from(base), alternate, text_only.then_some(shifted))
+            }
+            Key::Escape => csi_u(27, None, None),
+            Key::Enter | Key::Tab | Key::Backspace if mods.is_empty() && !all_keys => self.to_bytes(),
+            Key::Enter => csi_u(13, None, None),
+            Key::Tab => csi_u(9, None, None),
+            Key::Backspace => csi_u(127, None, None),
+            // CSI R would be read as a cursor position report
+            Key::F(3) if !mods.is_empty() => format!("\x1b[13;{}~", mods.xterm_param()).into_bytes(),
+            _ => self.to_bytes(),
+        }
+    }
+}
+
+/// How key inputs are encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyProtocol {
+    /// Legacy encoding until the application enables the kitty protocol
+    #[default]
+    Auto,
+    /// Always legacy xterm encoding
+    Legacy,
+    /// Always CSI-u, with at least the disambiguate flag
+    Kitty,
+}
+
+impl KeyProtocol {
+    /// Kitty flags to encode with, given the flags the application enabled
+    pub fn flags(self, enabled: u8) -> u8 {
+        match self {
+            KeyProtocol::Auto => enabled,
+            KeyProtocol::Legacy => 0,
+            KeyProtocol::Kitty => enabled | KITTY_DISAMBIGUATE,
+        }
+    }
+}
+
+impl FromStr for KeyProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(KeyProtocol::Auto),
+            "legacy" | "xterm" => Ok(KeyProtocol::Legacy),
+            "kitty" | "csi-u" => Ok(KeyProtocol::Kitty),
+            _ => Err(format!("unknown key protocol '{}' (expected: auto, legacy, kitty)", s)),
+        }
+    }
+}
+
+/// The control code ctrl produces with a character, if any
+fn control_code(ch: char) -> Option<u8> {
+    match ch {
+        'a'..='z' => Some(ch as u8 - b'a' + 1),
+        'A'..='Z' | '@' | '[' | '\\' | ']' | '^' | '_' => Some(ch as u8 & 0x1f),
+        ' ' | '2' => Some(0x00),
+        '?' => Some(0x7f),
+        _ => None,
+    }
+}
+
+/// Bytes for a key input, or `None` if it does not name a key
+pub fn key_sequence(input: &str) -> Option<Vec<u8>> {
+    KeyPress::parse(input).map(|press| press.to_bytes())
+}
+
+/// Bytes for a key input under the kitty keyboard protocol with `flags`
+pub fn kitty_key_sequence(input: &str, flags: u8) -> Option<Vec<u8>> {
+    KeyPress::parse(input).map(|press| press.to_kitty_bytes(flags))
+}
+
+/// Split bytes read from a terminal into the bytes of single key presses.
+///
+/// CSI and SS3 sequences, OSC, DCS and APC strings, legacy mouse reports,
+/// ESC-prefixed (alt) keys and UTF-8 characters each stay together.
+pub fn split_keys(bytes: &[u8]) -> Vec<&[u8]> {
+    let mut keys = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let (key, tail) = rest.split_at(key_length(rest));
+        keys.push(key);
+        rest = tail;
+    }
+    keys
+}
+
+/// Length of the key press at the start of `bytes`
+fn key_length(bytes: &[u8]) -> usize {
+    match bytes {
+        // Legacy mouse report: CSI M and three bytes
+        [0x1b, b'[', b'M', ..] => bytes.len().min(6),
+        [0x1b, b'[', rest @ ..] => rest.iter().position(|b| (0x40..=0x7e).contains(b)).map_or(bytes.len(), |end| end + 3),
+        [0x1b, b'O', _, ..] => 3,
+        // String sequences end with BEL or ST
+        [0x1b, b']' | b'P' | b'_', rest @ ..] => rest
+            .iter()
+            .enumerate()
+            .find_map(|(i, b)| match b {
+                0x07 => Some(i + 3),
+                b'\\' if i > 0 && rest[i - 1] == 0x1b => Some(i + 3),
+                _ => None,
+            })
+            .unwrap_or(bytes.len()),
+        [0x1b, 0x1b, ..] | [0x1b] => 1,
+        [0x1b, rest @ ..] => 1 + key_length(rest),
+        [first, ..] => {
+            let len = match first {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            len.min(bytes.len())
+        }
+        [] => 0,
+    }
+}
+
+/// The input that sends the bytes of one key press: a key name where one
+/// produces them (`up`, `ctrl+right`, `alt+x`, `space`), otherwise the
+/// character itself, otherwise `raw:HEX`
+pub fn input_for_key(bytes: &[u8]) -> String {
+    if let Some(name) = key_name(bytes) {
+        return name;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) if text.chars().count() == 1 && !text.chars().any(char::is_control) => text.to_string(),
+        _ => format!("raw:{}", bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+    }
+}
+
+/// Name of the key press xterm encodes as `bytes`, preferring plain keys,
+/// then modified named keys, then control and alt characters
+fn key_name(bytes: &[u8]) -> Option<String> {
+    const NAMED: [&str; 27] = [
+        "up", "down", "right", "left", "home", "end", "insert", "delete", "pageup", "pagedown", "enter", "tab",
+        "backspace", "escape", "space", "f1", "f2", "f3", "f4", "f5", "f6", "f7", "f8", "f9", "f10", "f11", "f12",
+    ];
+    let name = |modifiers: Modifiers, key: &str| {
+        let mut name = String::new();
+        for (held, prefix) in [(modifiers.ctrl, "ctrl+"), (modifiers.alt, "alt+"), (modifiers.shift, "shift+")] {
+            if held {
+                name.push_str(prefix);
+            }
+        }
+        name + key
+    };
+    let combos = (0..8u8).map(|bits| Modifiers {
+        shift: bits & 1 != 0,
+        alt: bits & 2 != 0,
+        ctrl: bits & 4 != 0,
+    });
+    let chars = ('a'..='z').map(|ch| (ch, ch.to_string()));
+    let candidates = combos
+        .flat_map(|modifiers| NAMED.iter().map(move |key| (modifiers, key.to_string())))
+        .chain(chars.clone().map(|(_, key)| (Modifiers { ctrl: true, ..Default::default() }, key)))
+        .chain(chars.map(|(_, key)| (Modifiers { ctrl: true, alt: true, shift: false }, key)));
+    for (modifiers, key) in candidates {
+        let press = KeyPress {
+            key: Key::from_name(&key)?,
+            modifiers,
+        };
+        if press.to_bytes() == bytes {
+            return Some(name(modifiers, &key));
+        }
+    }
+    // alt+ on a character is ESC and the character
+    let text = std::str::from_utf8(bytes.strip_prefix(b"\x1b")?).ok()?;
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) if !ch.is_control() => Some(format!("alt+{}", ch)),
+        _ => None,
+    }
+}
+
+/// An input whose meaning is spelled out by a `text:`, `key:` or `type:` prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplicitInput<'a> {
+    /// Literal text, sent in one write
+    Text(&'a str),
+    /// A key name or modifier combination, never literal text
+    Key(&'a str),
+    /// Literal text, sent one character at a time
+    Type(&'a str),
+}
+
+/// Split off a `text:`, `key:` or `type:` prefix.
+///
+/// Returns `None` for inputs without one. The text after `text:` and
+/// `type:` is kept exactly, including spaces.
+pub fn parse_explicit_input(input: &str) -> Option<ExplicitInput<'_>> {
+    let (prefix, body) = input.trim_start().split_once(':')?;
+    match prefix.to_lowercase().as_str() {
+        "text" => Some(ExplicitInput::Text(body)),
+        "key" => Some(ExplicitInput::Key(body.trim())),
+        "type" => Some(ExplicitInput::Type(body)),
+        _ => None,
+    }
+}
+
+/// Parse a `raw:` (hex) or `bytes:` (escaped) input into the exact bytes to send.
+///
+/// Returns `None` if the input has neither prefix. Hex may contain
+/// whitespace; `bytes:` understands `\xHH`, `\e`, `\r`, `\n`, `\t`, `\0` and
+/// `\\`, and sends everything else as UTF-8.
+pub fn parse_raw_input(input: &str) -> Option<Result<Vec<u8>, String>> {
+    let (prefix, body) = input.trim_start().split_once(':')?;
+    match prefix.to_lowercase().as_str() {
+        "raw" => Some(parse_hex(body)),
+        "bytes" => Some(unescape_bytes(body)),
+        _ => None,
+    }
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<u8> = hex.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return Err(format!("expected an even number of hex digits, got '{}'", hex));
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| format!("'{}' is not a hex byte", String::from_utf8_lossy(pair)))
+        })
+        .collect()
+}
+
+fn unescape_bytes(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            let mut buf = [0; 4];
+            out.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        let byte = match chars.next() {
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&hex, 16).map_err(|_| format!("'\\x{}' is not a hex byte", hex))?
+            }
+            Some('e') => 0x1b,
+            Some('r') => b'\r',
+            Some('n') => b'\n',
+            Some('t') => b'\t',
+            Some('0') => 0x00,
+            Some('\\') => b'\\',
+            Some(other) => return Err(format!("unknown escape '\\{}'", other)),
+            None => return Err("trailing backslash".to_string()),
+        };
+        out.push(byte);
+    }
+    if out.is_empty() {
+        return Err("no bytes given".to_string());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_combinations() {
+        let press = KeyPress::parse("Ctrl+Shift+Home").unwrap();
+        assert_eq!(press.key, Key::Home);
+        assert_eq!(press.modifiers, Modifiers { shift: true, alt: false, ctrl: true });
+        assert_eq!(KeyPress::parse("c-x").unwrap().key, Key::Char('x'));
+        assert_eq!(KeyPress::parse("ctrl+-").unwrap().key, Key::Char('-'));
+        assert_eq!(KeyPress::parse("f13"), None);
+        assert_eq!(KeyPress::parse("hyper+a"), None);
+        assert_eq!(KeyPress::parse("hello"), None);
+    }
+
+    #[test]
+    fn test_xterm_encodings() {
+        let bytes = |input: &str| key_sequence(input).unwrap();
+        assert_eq!(bytes("up"), b"\x1b[A");
+        assert_eq!(bytes("ctrl+right"), b"\x1b[1;5C");
+        assert_eq!(bytes("shift+alt+left"), b"\x1b[1;4D");
+        assert_eq!(bytes("ctrl+home"), b"\x1b[1;5H");
+        assert_eq!(bytes("shift+delete"), b"\x1b[3;2~");
+        assert_eq!(bytes("f1"), b"\x1bOP");
+        assert_eq!(bytes("ctrl+f1"), b"\x1b[1;5P");
+        assert_eq!(bytes("alt+f5"), b"\x1b[15;3~");
+        assert_eq!(bytes("shift+tab"), b"\x1b[Z");
+        assert_eq!(bytes("ctrl+shift+tab"), b"\x1b[1;6Z");
+        assert_eq!(bytes("alt+enter"), b"\x1b\r");
+        assert_eq!(bytes("ctrl+c"), [0x03]);
+        assert_eq!(bytes("ctrl+alt+x"), [0x1b, 0x18]);
+        assert_eq!(bytes("ctrl+space"), [0x00]);
+        assert_eq!(bytes("shift+a"), b"A");
+        assert_eq!(bytes("ctrl+backspace"), [0x08]);
+    }
+
+    #[test]
+    fn test_kitty_encodings() {
+        let bytes = |input: &str, flags: u8| kitty_key_sequence(input, flags).unwrap();
+        let flags = KITTY_DISAMBIGUATE;
+        assert_eq!(bytes("a", flags), b"a");
+        assert_eq!(bytes("shift+a", flags), b"A");
+        assert_eq!(bytes("ctrl+i", flags), b"\x1b[105;5u");
+        assert_eq!(bytes("alt+enter", flags), b"\x1b[13;3u");
+        assert_eq!(bytes("enter", flags), b"\r");
+        assert_eq!(bytes("escape", flags), b"\x1b[27u");
+        assert_eq!(bytes("shift+tab", flags), b"\x1b[9;2u");
+        assert_eq!(bytes("ctrl+up", flags), b"\x1b[1;5A");
+        assert_eq!(bytes("ctrl+f3", flags), b"\x1b[13;5~");
+
+        let all = KITTY_DISAMBIGUATE | KITTY_REPORT_ALL_KEYS;
+        assert_eq!(bytes("enter", all), b"\x1b[13u");
+        assert_eq!(bytes("a", all), b"\x1b[97u");
+        let rich = all | KITTY_REPORT_ALTERNATES | KITTY_REPORT_TEXT;
+        assert_eq!(bytes("shift+a", rich), b"\x1b[97:65;2;65u");
+        assert_eq!(bytes("ctrl+c", 0), [0x03]);
+    }
+
+    #[test]
+    fn test_raw_byte_inputs() {
+        assert_eq!(parse_raw_input("raw:1b5b41"), Some(Ok(b"\x1b[A".to_vec())));
+        assert_eq!(parse_raw_input("RAW: 1b 5b 32 30 30 7e"), Some(Ok(b"\x1b[200~".to_vec())));
+        assert_eq!(parse_raw_input("bytes:\\x1b[200~hi\\e[201~"), Some(Ok(b"\x1b[200~hi\x1b[201~".to_vec())));
+        assert_eq!(parse_raw_input("bytes:a\\\\b\\r"), Some(Ok(b"a\\b\r".to_vec())));
+        assert!(matches!(parse_raw_input("raw:1b5"), Some(Err(_))));
+        assert!(matches!(parse_raw_input("raw:zz"), Some(Err(_))));
+        assert!(matches!(parse_raw_input("bytes:\\q"), Some(Err(_))));
+        assert_eq!(parse_raw_input("resize:80x24"), None);
+        assert_eq!(parse_raw_input("enter"), None);
+    }
+
+    #[test]
+    fn test_terminal_bytes_decode_to_inputs() {
+        let bytes = b"a \x1b[A\x1b[1;5C\r\x7f\x1b\x1bx\x01\x1bO\x51\xc3\xa9\x1b[200~";
+        let inputs: Vec<String> = split_keys(bytes).into_iter().map(input_for_key).collect();
+        assert_eq!(
+            inputs,
+            ["a", "space", "up", "ctrl+right", "enter", "backspace", "escape", "alt+x", "ctrl+a", "f2", "é", "raw:1b5b3230307e"]
+        );
+        // Every named input sends the bytes it was decoded from
+        for key in split_keys(bytes).into_iter().take(10) {
+            assert_eq!(key_sequence(&input_for_key(key)).unwrap(), key);
+        }
+        assert_eq!(split_keys(b"\x1b]11;rgb:0/0/0\x07q"), [&b"\x1b]11;rgb:0/0/0\x07"[..], b"q"]);
+    }
+
+    #[test]
+    fn test_explicit_inputs() {
+        assert_eq!(parse_explicit_input("text:enter"), Some(ExplicitInput::Text("enter")));
+        assert_eq!(parse_explicit_input("TEXT: two words "), Some(ExplicitInput::Text(" two words ")));
+        assert_eq!(parse_explicit_input("key: ctrl+c"), Some(ExplicitInput::Key("ctrl+c")));
+        assert_eq!(parse_explicit_input("type:hello"), Some(ExplicitInput::Type("hello")));
+        assert_eq!(parse_explicit_input("enter"), None);
+        assert_eq!(parse_explicit_input("click:1,2"), None);
+    }
+}
@@ -0,0 +1,337 @@
+//! Keyboard layouts for layout-aware input.
+//!
+//! Characters reach the application as text, so typing `ö` works on any
+//! layout. What differs between layouts is which physical key produces a
+//! character: the key right of `L` types `;` on en-US but `ö` on de-DE.
+//! Scenarios written in terms of physical keys use `keycode:` inputs with
+//! W3C `KeyboardEvent.code` names, optionally with `shift+`, `altgr+`,
+//! `ctrl+` or `alt+` (e.g. `keycode:Semicolon`, `keycode:altgr+KeyQ`), and
+//! [`KeyboardLayout::key_for`] finds the key that produces a character.
+
+use std::str::FromStr;
+
+/// Names accepted by [`KeyboardLayout::from_name`]
+pub const LAYOUT_NAMES: &[&str] = &["en-US", "en-GB", "de-DE", "fr-FR", "es-ES"];
+
+/// Prefix of physical-key inputs
+pub const KEYCODE_PREFIX: &str = "keycode:";
+
+/// en-US: code, unshifted, shifted
+const US_KEYS: &[(&str, char, char)] = &[
+    ("Backquote", '`', '~'),
+    ("Digit1", '1', '!'),
+    ("Digit2", '2', '@'),
+    ("Digit3", '3', '#'),
+    ("Digit4", '4', '$'),
+    ("Digit5", '5', '%'),
+    ("Digit6", '6', '^'),
+    ("Digit7", '7', '&'),
+    ("Digit8", '8', '*'),
+    ("Digit9", '9', '('),
+    ("Digit0", '0', ')'),
+    ("Minus", '-', '_'),
+    ("Equal", '=', '+'),
+    ("BracketLeft", '[', '{'),
+    ("BracketRight", ']', '}'),
+    ("Backslash", '\\', '|'),
+    ("Semicolon", ';', ':'),
+    ("Quote", '\'', '"'),
+    ("Comma", ',', '<'),
+    ("Period", '.', '>'),
+    ("Slash", '/', '?'),
+    ("Space", ' ', ' '),
+];
+
+/// Differences from en-US: code, unshifted, shifted, AltGr
+type Overrides = &'static [(&'static str, char, char, Option<char>)];
+
+const GB_KEYS: Overrides = &[
+    ("Backquote", '`', '¬', Some('¦')),
+    ("Digit2", '2', '"', None),
+    ("Digit3", '3', '£', None),
+    ("Digit4", '4', '$', Some('€')),
+    ("Quote", '\'', '@', None),
+    ("Backslash", '#', '~', None),
+    ("IntlBackslash", '\\', '|', None),
+];
+
+const DE_KEYS: Overrides = &[
+    ("Backquote", '^', '°', None),
+    ("Digit2", '2', '"', Some('²')),
+    ("Digit3", '3', '§', Some('³')),
+    ("Digit6", '6', '&', None),
+    ("Digit7", '7', '/', Some('{')),
+    ("Digit8", '8', '(', Some('[')),
+    ("Digit9", '9', ')', Some(']')),
+    ("Digit0", '0', '=', Some('}')),
+    ("Minus", 'ß', '?', Some('\\')),
+    ("Equal", '´', '`', None),
+    ("KeyQ", 'q', 'Q', Some('@')),
+    ("KeyE", 'e', 'E', Some('€')),
+    ("KeyY", 'z', 'Z', None),
+    ("KeyZ", 'y', 'Y', None),
+    ("KeyM", 'm', 'M', Some('µ')),
+    ("BracketLeft", 'ü', 'Ü', None),
+    ("BracketRight", '+', '*', Some('~')),
+    ("Backslash", '#', '\'', None),
+    ("Semicolon", 'ö', 'Ö', None),
+    ("Quote", 'ä', 'Ä', None),
+    ("Comma", ',', ';', None),
+    ("Period", '.', ':', None),
+    ("Slash", '-', '_', None),
+    ("IntlBackslash", '<', '>', Some('|')),
+];
+
+const FR_KEYS: Overrides = &[
+    ("Backquote", '²', '²', None),
+    ("Digit1", '&', '1', None),
+    ("Digit2", 'é', '2', Some('~')),
+    ("Digit3", '"', '3', Some('#')),
+    ("Digit4", '\'', '4', Some('{')),
+    ("Digit5", '(', '5', Some('[')),
+    ("Digit6", '-', '6', Some('|')),
+    ("Digit7", 'è', '7', Some('`')),
+    ("Digit8", '_', '8', Some('\\')),
+    ("Digit9", 'ç', '9', Some('^')),
+    ("Digit0", 'à', '0', Some('@')),
+    ("Minus", ')', '°', Some(']')),
+    ("Equal", '=', '+', Some('}')),
+    ("KeyQ", 'a', 'A', None),
+    ("KeyW", 'z', 'Z', None),
+    ("KeyE", 'e', 'E', Some('€')),
+    ("KeyA", 'q', 'Q', None),
+    ("KeyZ", 'w', 'W', None),
+    ("KeyM", ',', '?', None),
+    ("BracketLeft", '^', '¨', None),
+    ("BracketRight", '$', '£', Some('¤')),
+    ("Backslash", '*', 'µ', None),
+    ("Semicolon", 'm', 'M', None),
+    ("Quote", 'ù', '%', None),
+    ("Comma", ';', '.', None),
+    ("Period", ':', '/', None),
+    ("Slash", '!', '§', None),
+    ("IntlBackslash", '<', '>', None),
+];
+
+const ES_KEYS: Overrides = &[
+    ("Backquote", 'º', 'ª', Some('\\')),
+    ("Digit1", '1', '!', Some('|')),
+    ("Digit2", '2', '"', Some('@')),
+    ("Digit3", '3', '·', Some('#')),
+    ("Digit4", '4', '$', Some('~')),
+    ("Digit6", '6', '&', Some('¬')),
+    ("Digit7", '7', '/', None),
+    ("Digit8", '8', '(', None),
+    ("Digit9", '9', ')', None),
+    ("Digit0", '0', '=', None),
+    ("Minus", '\'', '?', None),
+    ("Equal", '¡', '¿', None),
+    ("KeyE", 'e', 'E', Some('€')),
+    ("BracketLeft", '`', '^', Some('[')),
+    ("BracketRight", '+', '*', Some(']')),
+    ("Backslash", 'ç', 'Ç', Some('}')),
+    ("Semicolon", 'ñ', 'Ñ', None),
+    ("Quote", '´', '¨', Some('{')),
+    ("Comma", ',', ';', None),
+    ("Period", '.', ':', None),
+    ("Slash", '-', '_', None),
+    ("IntlBackslash", '<', '>', None),
+];
+
+/// Characters produced by one physical key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyChars {
+    /// Without modifiers
+    pub base: char,
+    /// With Shift
+    pub shift: char,
+    /// With AltGr, if the key has a third level
+    pub altgr: Option<char>,
+}
+
+/// A physical key and the modifiers needed to produce a character
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyStroke {
+    /// W3C `KeyboardEvent.code` name (e.g. "Semicolon")
+    pub code: String,
+    pub shift: bool,
+    pub altgr: bool,
+}
+
+impl std::fmt::Display for KeyStroke {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", KEYCODE_PREFIX)?;
+        if self.altgr {
+            write!(f, "altgr+")?;
+        }
+        if self.shift {
+            write!(f, "shift+")?;
+        }
+        write!(f, "{}", self.code)
+    }
+}
+
+/// Mapping from physical keys to the characters they produce
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyboardLayout {
+    name: &'static str,
+    keys: Vec<(&'static str, KeyChars)>,
+}
+
+impl Default for KeyboardLayout {
+    fn default() -> Self {
+        Self::build("en-US", &[])
+    }
+}
+
+impl FromStr for KeyboardLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_name(s).ok_or_else(|| format!("unknown keyboard layout '{}' (expected: {})", s, LAYOUT_NAMES.join(", ")))
+    }
+}
+
+impl KeyboardLayout {
+    /// Built-in layout by name (case-insensitive; `us`, `gb`, `de`, `fr`, `es` also accepted)
+    pub fn from_name(name: &str) -> Option<Self> {
+        let layout = match name.to_lowercase().replace('_', "-").as_str() {
+            "en-us" | "us" => Self::build("en-US", &[]),
+            "en-gb" | "gb" | "uk" => Self::build("en-GB", GB_KEYS),
+            "de-de" | "de" => Self::build("de-DE", DE_KEYS),
+            "fr-fr" | "fr" => Self::build("fr-FR", FR_KEYS),
+            "es-es" | "es" => Self::build("es-ES", ES_KEYS),
+            _ => return None,
+        };
+        Some(layout)
+    }
+
+    fn build(name: &'static str, overrides: Overrides) -> Self {
+        let letters = ('a'..='z').map(|ch| {
+            // Letter codes are named after the en-US letter ("KeyA"); the static table keeps them borrowable
+            const CODES: [&str; 26] = [
+                "KeyA", "KeyB", "KeyC", "KeyD", "KeyE", "KeyF", "KeyG", "KeyH", "KeyI", "KeyJ", "KeyK", "KeyL", "KeyM",
+                "KeyN", "KeyO", "KeyP", "KeyQ", "KeyR", "KeyS", "KeyT", "KeyU", "KeyV", "KeyW", "KeyX", "KeyY", "KeyZ",
+            ];
+            (CODES[(ch as u8 - b'a') as usize], ch, ch.to_ascii_uppercase())
+        });
+        let mut keys: Vec<(&'static str, KeyChars)> = US_KEYS
+            .iter()
+            .copied()
+            .chain(letters)
+            .map(|(code, base, shift)| (code, KeyChars { base, shift, altgr: None }))
+            .collect();
+        for &(code, base, shift, altgr) in overrides {
+            let chars = KeyChars { base, shift, altgr };
+            match keys.iter_mut().find(|(known, _)| *known == code) {
+                Some(entry) => entry.1 = chars,
+                None => keys.push((code, chars)),
+            }
+        }
+        Self { name, keys }
+    }
+
+    /// Layout name (e.g. "de-DE")
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// Characters produced by a physical key (code names are case-insensitive)
+    pub fn key(&self, code: &str) -> Option<KeyChars> {
+        self.keys
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(code))
+            .map(|(_, chars)| *chars)
+    }
+
+    /// Character produced by a physical key with the given modifiers
+    pub fn char_for(&self, code: &str, shift: bool, altgr: bool) -> Option<char> {
+        let chars = self.key(code)?;
+        match (altgr, shift) {
+            (true, _) => chars.altgr,
+            (false, true) => Some(chars.shift),
+            (false, false) => Some(chars.base),
+        }
+    }
+
+    /// The key (and modifiers) producing a character, preferring the fewest modifiers
+    pub fn key_for(&self, ch: char) -> Option<KeyStroke> {
+        let stroke = |code: &str, shift, altgr| KeyStroke {
+            code: code.to_string(),
+            shift,
+            altgr,
+        };
+        let find = |pick: fn(&KeyChars) -> Option<char>| self.keys.iter().find(|(_, chars)| pick(chars) == Some(ch));
+        if let Some((code, _)) = find(|chars| Some(chars.base)) {
+            return Some(stroke(code, false, false));
+        }
+        if let Some((code, _)) = find(|chars| Some(chars.shift)) {
+            return Some(stroke(code, true, false));
+        }
+        find(|chars| chars.altgr).map(|(code, _)| stroke(code, false, true))
+    }
+
+    /// Bytes for a `keycode:` input, or `None` if it is not one or names an unknown key
+    pub fn keycode_bytes(&self, input: &str) -> Option<Vec<u8>> {
+        let spec = input.trim();
+        let spec = spec
+            .get(..KEYCODE_PREFIX.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(KEYCODE_PREFIX))
+            .map(|_| &spec[KEYCODE_PREFIX.len()..])?;
+        let mut parts: Vec<&str> = spec.split('+').collect();
+        let code = parts.pop()?;
+        let (mut shift, mut altgr, mut ctrl, mut alt) = (false, false, false, false);
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "shift" => shift = true,
+                "altgr" => altgr = true,
+                "ctrl" => ctrl = true,
+                "alt" => alt = true,
+                _ => return None,
+            }
+        }
+        let ch = self.char_for(code, shift, altgr)?;
+        let mut bytes = Vec::new();
+        if alt {
+            bytes.push(0x1b);
+        }
+        if ctrl && ch.is_ascii_alphabetic() {
+            bytes.push(ch.to_ascii_lowercase() as u8 - b'a' + 1);
+        } else {
+            let mut buf = [0u8; 4];
+            bytes.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+        }
+        Some(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_physical_keys_follow_the_layout() {
+        let us = KeyboardLayout::default();
+        let de: KeyboardLayout = "de-DE".parse().unwrap();
+        assert_eq!(us.keycode_bytes("keycode:Semicolon"), Some(b";".to_vec()));
+        assert_eq!(de.keycode_bytes("keycode:Semicolon"), Some("ö".as_bytes().to_vec()));
+        assert_eq!(de.keycode_bytes("KEYCODE:shift+semicolon"), Some("Ö".as_bytes().to_vec()));
+        assert_eq!(de.keycode_bytes("keycode:altgr+KeyQ"), Some(b"@".to_vec()));
+        // QWERTZ: the key labelled Y on en-US types z, so ctrl+ gives ^Z
+        assert_eq!(de.keycode_bytes("keycode:ctrl+KeyY"), Some(vec![0x1a]));
+        assert_eq!(de.keycode_bytes("keycode:alt+KeyA"), Some(b"\x1ba".to_vec()));
+        assert_eq!(de.keycode_bytes("keycode:Nope"), None);
+        assert_eq!(de.keycode_bytes("down"), None);
+    }
+
+    #[test]
+    fn test_key_for_finds_the_producing_key() {
+        let de = KeyboardLayout::from_name("de").unwrap();
+        assert_eq!(de.key_for('ö').unwrap().to_string(), "keycode:Semicolon");
+        assert_eq!(de.key_for('@').unwrap().to_string(), "keycode:altgr+KeyQ");
+        let fr = KeyboardLayout::from_name("fr-FR").unwrap();
+        assert_eq!(fr.key_for('A').unwrap().to_string(), "keycode:shift+KeyQ");
+        assert_eq!(fr.key_for('1').unwrap().to_string(), "keycode:shift+Digit1");
+        assert!(KeyboardLayout::from_name("xx-XX").is_none());
+    }
+}
@@ -0,0 +1,126 @@
+//! Resource limits for the application under test.
+//!
+//! Limits keep a runaway target (busy loop, memory leak, descriptor leak)
+//! from taking down the machine running the capture. They are set on the
+//! application's process with `prlimit(2)` as soon as it is spawned, as both
+//! soft and hard limits so it cannot raise them again.
+//!
+//! Limits are supported on Linux only. Windows Job Objects are out of scope;
+//! on other platforms a run with limits fails before the application starts.
+
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// CPU time, memory and file-descriptor limits for the spawned process
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// CPU time in seconds (`RLIMIT_CPU`)
+    pub cpu_seconds: Option<u64>,
+    /// Address space in bytes (`RLIMIT_AS`)
+    pub memory_bytes: Option<u64>,
+    /// Number of open file descriptors (`RLIMIT_NOFILE`)
+    pub open_files: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// No limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit CPU time
+    pub fn cpu_seconds(mut self, seconds: u64) -> Self {
+        self.cpu_seconds = Some(seconds);
+        self
+    }
+
+    /// Limit the address space, in bytes
+    pub fn memory_bytes(mut self, bytes: u64) -> Self {
+        self.memory_bytes = Some(bytes);
+        self
+    }
+
+    /// Limit the address space, in mebibytes
+    pub fn memory_mb(self, mb: u64) -> Self {
+        self.memory_bytes(mb * 1024 * 1024)
+    }
+
+    /// Limit the number of open file descriptors
+    pub fn open_files(mut self, count: u64) -> Self {
+        self.open_files = Some(count);
+        self
+    }
+
+    /// Whether no limit is set
+    pub fn is_empty(&self) -> bool {
+        self.cpu_seconds.is_none() && self.memory_bytes.is_none() && self.open_files.is_none()
+    }
+
+    /// Fail if limits are set but cannot be applied on this platform
+    pub fn check_supported(&self) -> Result<(), String> {
+        if self.is_empty() || cfg!(target_os = "linux") {
+            Ok(())
+        } else {
+            Err("Resource limits are only supported on Linux".to_string())
+        }
+    }
+
+    /// Apply the limits to the running process `pid`
+    #[cfg(target_os = "linux")]
+    pub fn apply(&self, pid: u32) -> io::Result<()> {
+        let limits = [
+            (libc::RLIMIT_CPU, self.cpu_seconds),
+            (libc::RLIMIT_AS, self.memory_bytes),
+            (libc::RLIMIT_NOFILE, self.open_files),
+        ];
+        for (resource, value) in limits {
+            let Some(value) = value else { continue };
+            let limit = libc::rlimit { rlim_cur: value, rlim_max: value };
+            // SAFETY: `limit` outlives the call and the old limit is not asked for
+            if unsafe { libc::prlimit(pid as libc::pid_t, resource, &limit, std::ptr::null_mut()) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the limits to the running process `pid`
+    #[cfg(not(target_os = "linux"))]
+    pub fn apply(&self, _pid: u32) -> io::Result<()> {
+        match self.check_supported() {
+            Ok(()) => Ok(()),
+            Err(message) => Err(io::Error::new(io::ErrorKind::Unsupported, message)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_limits_are_always_supported() {
+        assert!(ResourceLimits::new().check_supported().is_ok());
+        assert!(ResourceLimits::new().apply(std::process::id()).is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_limits_apply_to_the_running_process() {
+        let mut child = std::process::Command::new("sleep").arg("5").spawn().unwrap();
+        let limits = ResourceLimits::new().cpu_seconds(7).memory_mb(256).open_files(32);
+        let applied = limits.apply(child.id());
+        let table = std::fs::read_to_string(format!("/proc/{}/limits", child.id())).unwrap();
+        let _ = child.kill();
+        let _ = child.wait();
+        applied.unwrap();
+        // Soft and hard limits are both set
+        let limit = |name: &str| {
+            let line = table.lines().find(|line| line.starts_with(name)).unwrap();
+            line[name.len()..].split_whitespace().take(2).collect::<Vec<_>>().join(" ")
+        };
+        assert_eq!(limit("Max cpu time"), "7 7");
+        assert_eq!(limit("Max address space"), "268435456 268435456");
+        assert_eq!(limit("Max open files"), "32 32");
+    }
+}
@@ -0,0 +1,159 @@
+//! Repetition and named macros in input sequences.
+//!
+//! Input lists are expanded before they are run:
+//!
+//! - `TOKEN*N` repeats an input `N` times, e.g. `down*40`
+//! - `@NAME` inserts the inputs of a macro, e.g. `@login`; macros may use
+//!   other macros and repeats, and `@NAME*N` repeats a whole macro
+//!
+//! Macros are defined in a scenario's `"macros"` object or a JSON file passed
+//! with `--macros`, mapping names to input lists:
+//!
+//! ```json
+//! { "login": ["type:admin", "tab", "type:secret", "enter"] }
+//! ```
+//!
+//! `text:`, `type:`, `raw:` and `bytes:` inputs are literal and never
+//! expanded, so `text:*2` still types `*2`. Every expanded input remembers
+//! the token it came from, for traceability in run results.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Macro definitions: name (without `@`) to inputs
+pub type Macros = BTreeMap<String, Vec<String>>;
+
+/// Largest count accepted by `TOKEN*N`
+pub const MAX_REPEAT: usize = 1000;
+
+/// Largest number of inputs one token may expand to
+pub const MAX_EXPANSION: usize = 10_000;
+
+/// Prefixes of inputs whose payload is literal
+const LITERAL_PREFIXES: [&str; 4] = ["text:", "type:", "raw:", "bytes:"];
+
+/// One input after expansion
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpandedInput {
+    /// The input to send
+    pub input: String,
+    /// The token as written, if it was a repeat or macro
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Index of that token in the unexpanded list
+    pub origin: usize,
+}
+
+/// Load macro definitions from a JSON file
+pub fn load_macros(path: &Path) -> std::io::Result<Macros> {
+    let data = fs::read_to_string(path)?;
+    serde_json::from_str(&data)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+}
+
+/// Expand repeats and macros in an input list
+pub fn expand_inputs(inputs: &[String], macros: &Macros) -> Result<Vec<ExpandedInput>, String> {
+    let mut expanded = Vec::new();
+    for (origin, token) in inputs.iter().enumerate() {
+        let mut out = Vec::new();
+        expand_token(token.trim(), macros, &mut Vec::new(), &mut out)?;
+        let source = (out.len() != 1 || out[0] != token.trim()).then(|| token.trim().to_string());
+        expanded.extend(out.into_iter().map(|input| ExpandedInput {
+            input,
+            source: source.clone(),
+            origin,
+        }));
+    }
+    Ok(expanded)
+}
+
+/// Inputs of an expansion, without their sources
+pub fn expanded_inputs(expanded: &[ExpandedInput]) -> Vec<String> {
+    expanded.iter().map(|e| e.input.clone()).collect()
+}
+
+fn expand_token(token: &str, macros: &Macros, stack: &mut Vec<String>, out: &mut Vec<String>) -> Result<(), String> {
+    if out.len() > MAX_EXPANSION {
+        return Err(format!("input expands to more than {} inputs", MAX_EXPANSION));
+    }
+    let is_literal = LITERAL_PREFIXES
+        .iter()
+        .any(|prefix| token.get(..prefix.len()).is_some_and(|p| p.eq_ignore_ascii_case(prefix)));
+    if is_literal {
+        out.push(token.to_string());
+        return Ok(());
+    }
+
+    if let Some((base, count)) = parse_repeat(token)? {
+        for _ in 0..count {
+            expand_token(base, macros, stack, out)?;
+        }
+        return Ok(());
+    }
+
+    match token.strip_prefix('@').filter(|name| !name.is_empty()) {
+        Some(name) => {
+            let body = macros.get(name).ok_or_else(|| format!("unknown macro '@{}'", name))?;
+            if stack.iter().any(|open| open == name) {
+                return Err(format!("macro '@{}' uses itself", name));
+            }
+            stack.push(name.to_string());
+            for input in body {
+                expand_token(input.trim(), macros, stack, out)?;
+            }
+            stack.pop();
+        }
+        None => out.push(token.to_string()),
+    }
+    Ok(())
+}
+
+/// Split `TOKEN*N` into the token and count
+fn parse_repeat(token: &str) -> Result<Option<(&str, usize)>, String> {
+    let Some((base, count)) = token.rsplit_once('*') else {
+        return Ok(None);
+    };
+    if base.is_empty() || count.is_empty() || !count.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(None);
+    }
+    match count.parse::<usize>() {
+        Ok(n) if (1..=MAX_REPEAT).contains(&n) => Ok(Some((base, n))),
+        _ => Err(format!("repeat count in '{}' must be between 1 and {}", token, MAX_REPEAT)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_repeats_and_macros_expand_with_sources() {
+        let macros = Macros::from([
+            ("login".to_string(), strings(&["type:admin", "tab", "@submit"])),
+            ("submit".to_string(), strings(&["enter"])),
+        ]);
+        let expanded = expand_inputs(&strings(&["down*3", "@login", "text:*2", "q"]), &macros).unwrap();
+        assert_eq!(expanded_inputs(&expanded), ["down", "down", "down", "type:admin", "tab", "enter", "text:*2", "q"]);
+        assert_eq!(expanded[0].source.as_deref(), Some("down*3"));
+        assert_eq!((expanded[5].source.as_deref(), expanded[5].origin), (Some("@login"), 1));
+        assert_eq!((expanded[7].source.as_deref(), expanded[7].origin), (None, 3));
+        assert_eq!(expand_inputs(&strings(&["@submit*2"]), &macros).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_expansion_errors() {
+        let macros = Macros::from([("loop".to_string(), strings(&["down", "@loop"]))]);
+        assert!(expand_inputs(&strings(&["@loop"]), &macros).unwrap_err().contains("uses itself"));
+        assert!(expand_inputs(&strings(&["@missing"]), &macros).unwrap_err().contains("unknown macro"));
+        assert!(expand_inputs(&strings(&["down*0"]), &macros).is_err());
+        assert!(expand_inputs(&strings(&["down*1000*1000"]), &macros).unwrap_err().contains("more than"));
+        // A lone `*` or `@` is still an ordinary input
+        assert_eq!(expanded_inputs(&expand_inputs(&strings(&["*", "@"]), &macros).unwrap()), ["*", "@"]);
+    }
+}
@@ -1,9 +1,54 @@
 pub mod backend;
+pub mod crop;
+pub mod diff;
+pub mod emulator;
+pub mod export;
+pub mod frames;
+pub mod fuzz;
+pub mod glyphs;
+pub mod grid;
+pub mod graphics;
+pub mod hyperlink;
+pub mod image_protocols;
+pub mod keys;
+pub mod layout;
+pub mod limits;
+pub mod macros;
+pub mod mouse;
+pub mod pool;
 pub mod pty;
+pub mod record;
+pub mod replay;
+pub mod signal;
+pub mod sixel;
+pub mod soak;
+pub mod theme;
 pub mod types;
 pub mod utils;
+pub mod wait;
 
 pub use types::{Snapshot, SnapshotConfig, SnapshotError, SnapshotResult};
+pub use crop::{crop_png_cells, CellRect, Crop};
+pub use diff::{annotated_diff, diff_directories, diff_images, CellChange, CellChangeKind, CellDiff, CellField, DirectoryDiff, PixelDiff, PixelRect, StateDiff, StateDiffStatus};
 pub use backend::{CaptureBackend, CaptureResult, MockFramebuffer, PtyBackend, PtyBackendConfig, capture_with_backend};
-pub use pty::{run_with_inputs, run_with_inputs_sized, StateCaptureResult, TerminalSize, Vt100Parser, Vt100Terminal, CELL_HEIGHT, CELL_WIDTH};
-pub use utils::{create_base_metadata, generate_filename, generate_timestamp, write_description, write_manifest};
+pub use frames::{write_frame, write_png_frame, FrameFormat};
+pub use fuzz::{run_fuzz, FuzzConfig, FuzzReport};
+pub use glyphs::{GlyphFallback, HexFont};
+pub use graphics::{ImageCursor, InlineImage};
+pub use grid::{GridAttribute, GridCell, GridCursor, TerminalGrid};
+pub use image_protocols::{decode_iterm_file, KittyGraphics, KittyOutcome, KittyPlacement};
+pub use hyperlink::{check_uri_syntax, validate_hyperlink, Hyperlink};
+pub use keys::{input_for_key, key_sequence, kitty_key_sequence, parse_explicit_input, parse_raw_input, split_keys, ExplicitInput, Key, KeyPress, KeyProtocol, Modifiers};
+pub use layout::KeyboardLayout;
+pub use limits::ResourceLimits;
+pub use macros::{expand_inputs, expanded_inputs, load_macros, ExpandedInput, Macros};
+pub use mouse::{parse_mouse_input, MouseButton, MouseInput, MouseTracking, ScrollDirection};
+pub use pool::{PoolStats, PtyPool};
+pub use pty::{build_command, is_eof_input, run_driven, run_with_inputs, run_with_inputs_sized, run_with_options, validate_inputs, BlinkFrames, BlinkPhase, CaptureObserver, CellAttributes, CursorChange, CursorShape, CursorState, LineSize, RenderOptions, RunOptions, StateCaptureResult, TerminalIdentity, TerminalSize, UnderlineStyle, Vt100Parser, Vt100Terminal, CELL_HEIGHT, CELL_WIDTH, DEFAULT_TYPE_DELAY, HERMETIC_LANG};
+pub use record::{record, RecordConfig, RecordedEvent, Recording};
+pub use replay::{replay_cast, replay_plan, Cast, CastRecorder, ReplayCapture, ReplayConfig};
+pub use signal::{parse_signal_input, send_signal, Sig};
+pub use soak::{run_soak, SoakConfig, SoakFailure, SoakReport, SoakSample};
+pub use theme::{Theme, BUILTIN_THEMES};
+pub use wait::{parse_wait_input, WaitCondition, WaitOutcome, DEFAULT_WAIT_TIMEOUT};
+pub use utils::{create_base_metadata, generate_filename, generate_timestamp, write_description, write_manifest, write_screen_text};
@@ -0,0 +1,186 @@
+//! Mouse input and xterm mouse reporting.
+//!
+//! Applications ask for mouse reports with DEC private modes: 9 (X10, presses
+//! only), 1000 (presses and releases), 1002 (plus drags) or 1003 (all
+//! motion), and choose SGR encoding with 1006. The emulator tracks these
+//! modes, and [`encode_mouse`] turns a click or wheel step into the bytes the
+//! application expects, or nothing if it has not enabled mouse reporting.
+//!
+//! Inputs are written `click:COL,ROW[,left|middle|right]` and
+//! `scroll:COL,ROW,up|down`, with 0-based cell coordinates.
+
+use serde::{Deserialize, Serialize};
+
+/// Mouse button
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseButton {
+    #[default]
+    Left,
+    Middle,
+    Right,
+}
+
+/// Mouse wheel direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrollDirection {
+    Up,
+    Down,
+}
+
+/// A mouse action at a cell (0-based)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseInput {
+    /// Press and release a button
+    Click { col: u16, row: u16, button: MouseButton },
+    /// One wheel step
+    Scroll { col: u16, row: u16, direction: ScrollDirection },
+}
+
+/// Which mouse events the application asked to receive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MouseTracking {
+    /// No reports
+    #[default]
+    Off,
+    /// Button presses only (mode 9)
+    X10,
+    /// Presses and releases (mode 1000)
+    Normal,
+    /// Presses, releases and drags (mode 1002)
+    ButtonEvent,
+    /// Presses, releases and all motion (mode 1003)
+    AnyEvent,
+}
+
+impl MouseTracking {
+    /// Tracking selected by a DEC private mode number
+    pub fn from_mode(mode: u16) -> Option<Self> {
+        match mode {
+            9 => Some(Self::X10),
+            1000 => Some(Self::Normal),
+            1002 => Some(Self::ButtonEvent),
+            1003 => Some(Self::AnyEvent),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a `click:` or `scroll:` input token
+pub fn parse_mouse_input(input: &str) -> Option<MouseInput> {
+    let (prefix, args) = input.trim().split_once(':')?;
+    let args: Vec<&str> = args.split(',').map(str::trim).collect();
+    let coordinate = |idx: usize| args.get(idx)?.parse::<u16>().ok();
+    let (col, row) = (coordinate(0)?, coordinate(1)?);
+    let extra = args.get(2).map(|s| s.to_lowercase());
+    if args.len() > 3 {
+        return None;
+    }
+    match prefix.trim().to_lowercase().as_str() {
+        "click" => {
+            let button = match extra.as_deref() {
+                None | Some("left") => MouseButton::Left,
+                Some("middle") => MouseButton::Middle,
+                Some("right") => MouseButton::Right,
+                Some(_) => return None,
+            };
+            Some(MouseInput::Click { col, row, button })
+        }
+        "scroll" => {
+            let direction = match extra.as_deref() {
+                Some("up") => ScrollDirection::Up,
+                Some("down") => ScrollDirection::Down,
+                _ => return None,
+            };
+            Some(MouseInput::Scroll { col, row, direction })
+        }
+        _ => None,
+    }
+}
+
+/// Encode a mouse input as the application asked (`sgr` for mode 1006).
+///
+/// Returns no bytes when tracking is off, or when a position cannot be
+/// expressed in the legacy encoding (beyond column or row 223).
+pub fn encode_mouse(input: MouseInput, tracking: MouseTracking, sgr: bool) -> Vec<u8> {
+    if tracking == MouseTracking::Off {
+        return Vec::new();
+    }
+    let (col, row, code, release) = match input {
+        MouseInput::Click { col, row, button } => {
+            let code = match button {
+                MouseButton::Left => 0,
+                MouseButton::Middle => 1,
+                MouseButton::Right => 2,
+            };
+            (col, row, code, tracking != MouseTracking::X10)
+        }
+        MouseInput::Scroll { col, row, direction } => {
+            let code = match direction {
+                ScrollDirection::Up => 64,
+                ScrollDirection::Down => 65,
+            };
+            (col, row, code, false)
+        }
+    };
+    let (x, y) = (u32::from(col) + 1, u32::from(row) + 1);
+
+    if sgr {
+        let mut bytes = format!("\x1b[<{};{};{}M", code, x, y).into_bytes();
+        if release {
+            bytes.extend_from_slice(format!("\x1b[<{};{};{}m", code, x, y).as_bytes());
+        }
+        return bytes;
+    }
+
+    // Legacy encoding: each value offset by 32 in a single byte; releases report button 3
+    if x + 32 > 255 || y + 32 > 255 {
+        return Vec::new();
+    }
+    let event = |code: u32| [0x1b, b'[', b'M', (32 + code) as u8, (32 + x) as u8, (32 + y) as u8];
+    let mut bytes = event(code).to_vec();
+    if release {
+        bytes.extend_from_slice(&event(3));
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mouse_tokens() {
+        assert_eq!(
+            parse_mouse_input("click:10,5"),
+            Some(MouseInput::Click { col: 10, row: 5, button: MouseButton::Left })
+        );
+        assert_eq!(
+            parse_mouse_input("CLICK: 0, 2, right"),
+            Some(MouseInput::Click { col: 0, row: 2, button: MouseButton::Right })
+        );
+        assert_eq!(
+            parse_mouse_input("scroll:3,4,down"),
+            Some(MouseInput::Scroll { col: 3, row: 4, direction: ScrollDirection::Down })
+        );
+        assert_eq!(parse_mouse_input("scroll:3,4"), None);
+        assert_eq!(parse_mouse_input("click:10"), None);
+        assert_eq!(parse_mouse_input("click:1,2,thumb"), None);
+    }
+
+    #[test]
+    fn test_encodings() {
+        let click = MouseInput::Click { col: 9, row: 4, button: MouseButton::Left };
+        assert!(encode_mouse(click, MouseTracking::Off, true).is_empty());
+        assert_eq!(encode_mouse(click, MouseTracking::Normal, true), b"\x1b[<0;10;5M\x1b[<0;10;5m");
+        assert_eq!(encode_mouse(click, MouseTracking::Normal, false), b"\x1b[M *%\x1b[M#*%");
+        assert_eq!(encode_mouse(click, MouseTracking::X10, false), b"\x1b[M *%");
+
+        let scroll = MouseInput::Scroll { col: 0, row: 0, direction: ScrollDirection::Up };
+        assert_eq!(encode_mouse(scroll, MouseTracking::AnyEvent, true), b"\x1b[<64;1;1M");
+        let far = MouseInput::Click { col: 300, row: 0, button: MouseButton::Left };
+        assert!(encode_mouse(far, MouseTracking::Normal, false).is_empty());
+        assert_eq!(encode_mouse(far, MouseTracking::Normal, true), b"\x1b[<0;301;1M\x1b[<0;301;1m");
+    }
+}
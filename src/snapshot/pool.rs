@@ -0,0 +1,258 @@
+//! Warm pool of pre-opened PTYs.
+//!
+//! Opening a PTY pair and starting its reader thread is a fixed cost paid by
+//! every capture run. A [`PtyPool`] pre-opens pairs and hands them out to
+//! runs that set [`RunOptions::pool`](super::RunOptions); when a run ends its
+//! application is killed, leftover output is drained, the terminal modes are
+//! restored, and the pair goes back to the pool with its reader thread still
+//! running.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::snapshot::{run_with_options, PtyPool, RunOptions, TerminalSize};
+//!
+//! let pool = PtyPool::warm(4, TerminalSize::default()).unwrap();
+//! let options = RunOptions::default().pool(pool.clone());
+//! for scenario in [["down"], ["enter"]] {
+//!     let inputs: Vec<String> = scenario.iter().map(|s| s.to_string()).collect();
+//!     run_with_options("./my-tui", &[], &inputs, 100, TerminalSize::default(), &options).unwrap();
+//! }
+//! println!("{:?}", pool.stats());
+//! ```
+
+use std::io::Write;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize, SlavePty};
+
+use super::pty::{spawn_reader, TerminalSize};
+use super::{SnapshotError, SnapshotResult};
+
+/// How long a returned PTY must stay silent before it is considered drained
+const DRAIN_QUIET: Duration = Duration::from_millis(50);
+
+/// Upper bound on draining a returned PTY (a killed app's children may keep writing)
+const MAX_DRAIN: Duration = Duration::from_millis(500);
+
+/// An open PTY pair with its reader thread
+pub(crate) struct PtyHandle {
+    pub(crate) master: Box<dyn MasterPty + Send>,
+    /// Kept open by pooled PTYs so the reader survives the application exiting
+    slave: Option<Box<dyn SlavePty + Send>>,
+    pub(crate) writer: Box<dyn Write + Send>,
+    pub(crate) rx: Receiver<Vec<u8>>,
+    /// Terminal modes at open time, restored before reuse
+    #[cfg(unix)]
+    modes: Option<nix::sys::termios::Termios>,
+}
+
+impl PtyHandle {
+    /// Open a PTY pair and start its reader thread
+    pub(crate) fn open(cols: u16, rows: u16) -> SnapshotResult<Self> {
+        let pair = native_pty_system()
+            .openpty(pty_size(cols, rows))
+            .map_err(|e| SnapshotError::Capture(format!("Failed to open PTY: {}", e)))?;
+        let reader = pair
+            .master
+            .try_clone_reader()
+            .map_err(|e| SnapshotError::Capture(format!("Failed to clone PTY reader: {}", e)))?;
+        let writer = pair
+            .master
+            .take_writer()
+            .map_err(|e| SnapshotError::Capture(format!("Failed to take PTY writer: {}", e)))?;
+
+        #[cfg(unix)]
+        let modes = pair
+            .master
+            .as_raw_fd()
+            .and_then(|fd| nix::sys::termios::tcgetattr(fd).ok());
+
+        Ok(Self {
+            master: pair.master,
+            slave: Some(pair.slave),
+            writer,
+            rx: spawn_reader(reader),
+            #[cfg(unix)]
+            modes,
+        })
+    }
+
+    /// Spawn a command on the slave side
+    pub(crate) fn spawn(&self, cmd: CommandBuilder, program: &str) -> SnapshotResult<Box<dyn Child + Send + Sync>> {
+        let slave = self
+            .slave
+            .as_ref()
+            .ok_or_else(|| SnapshotError::Capture("PTY slave already closed".to_string()))?;
+        slave
+            .spawn_command(cmd)
+            .map_err(|e| SnapshotError::Capture(format!("Failed to spawn '{}': {}", program, e)))
+    }
+
+    /// Close our copy of the slave so the reader sees EOF once the application exits
+    pub(crate) fn close_slave(&mut self) {
+        self.slave = None;
+    }
+
+    /// Resize the PTY
+    fn resize(&self, cols: u16, rows: u16) -> SnapshotResult<()> {
+        self.master
+            .resize(pty_size(cols, rows))
+            .map_err(|e| SnapshotError::Capture(format!("Failed to resize PTY: {}", e)))
+    }
+
+    /// Discard leftover output and restore the terminal modes from open time
+    fn reset(&mut self) {
+        let start = std::time::Instant::now();
+        while start.elapsed() < MAX_DRAIN && self.rx.recv_timeout(DRAIN_QUIET).is_ok() {}
+
+        #[cfg(unix)]
+        if let (Some(fd), Some(modes)) = (self.master.as_raw_fd(), &self.modes) {
+            let _ = nix::sys::termios::tcsetattr(fd, nix::sys::termios::SetArg::TCSANOW, modes);
+        }
+    }
+}
+
+fn pty_size(cols: u16, rows: u16) -> PtySize {
+    PtySize {
+        rows,
+        cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+/// Pool usage counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// PTYs opened by the pool (pre-opened or on a miss)
+    pub opened: usize,
+    /// Checkouts served by an idle PTY
+    pub reused: usize,
+}
+
+struct PoolState {
+    idle: Vec<PtyHandle>,
+    capacity: usize,
+    stats: PoolStats,
+}
+
+/// Shared pool of pre-opened PTYs; clones share the same pool
+#[derive(Clone)]
+pub struct PtyPool {
+    state: Arc<Mutex<PoolState>>,
+}
+
+impl std::fmt::Debug for PtyPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.lock();
+        f.debug_struct("PtyPool")
+            .field("idle", &state.idle.len())
+            .field("capacity", &state.capacity)
+            .field("stats", &state.stats)
+            .finish()
+    }
+}
+
+impl PtyPool {
+    /// Empty pool keeping up to `capacity` idle PTYs; PTYs are opened on demand
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(PoolState {
+                idle: Vec::with_capacity(capacity),
+                capacity,
+                stats: PoolStats::default(),
+            })),
+        }
+    }
+
+    /// Pool with `capacity` PTYs pre-opened at the given size
+    pub fn warm(capacity: usize, size: TerminalSize) -> SnapshotResult<Self> {
+        let pool = Self::new(capacity);
+        let (cols, rows) = size.dimensions();
+        let handles = (0..capacity)
+            .map(|_| PtyHandle::open(cols, rows))
+            .collect::<SnapshotResult<Vec<_>>>()?;
+        let mut state = pool.lock();
+        state.stats.opened = handles.len();
+        state.idle = handles;
+        drop(state);
+        Ok(pool)
+    }
+
+    /// Number of idle PTYs ready for checkout
+    pub fn idle(&self) -> usize {
+        self.lock().idle.len()
+    }
+
+    /// Usage counters
+    pub fn stats(&self) -> PoolStats {
+        self.lock().stats
+    }
+
+    /// Take an idle PTY resized to `cols`x`rows`, or open a new one
+    pub(crate) fn checkout(&self, cols: u16, rows: u16) -> SnapshotResult<PtyHandle> {
+        let idle = self.lock().idle.pop();
+        match idle {
+            Some(handle) => {
+                handle.resize(cols, rows)?;
+                self.lock().stats.reused += 1;
+                Ok(handle)
+            }
+            None => {
+                let handle = PtyHandle::open(cols, rows)?;
+                self.lock().stats.opened += 1;
+                Ok(handle)
+            }
+        }
+    }
+
+    /// Return a PTY whose application has exited; dropped if the pool is full
+    pub(crate) fn checkin(&self, mut handle: PtyHandle) {
+        if handle.slave.is_none() {
+            return;
+        }
+        handle.reset();
+        let mut state = self.lock();
+        if state.idle.len() < state.capacity {
+            state.idle.push(handle);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PoolState> {
+        // A panic while holding the lock cannot leave the idle list inconsistent
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::{run_with_options, RunOptions};
+
+    #[test]
+    fn test_warm_pool_preopens_ptys() {
+        let pool = PtyPool::warm(2, TerminalSize::Compact).unwrap();
+        assert_eq!(pool.idle(), 2);
+        assert_eq!(pool.stats(), PoolStats { opened: 2, reused: 0 });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pooled_runs_reuse_the_pty() {
+        let pool = PtyPool::new(1);
+        let options = RunOptions::default().pool(pool.clone());
+        let args = |text: &str| vec!["-c".to_string(), format!("stty size; echo {}; cat", text)];
+
+        let first = run_with_options("/bin/sh", &args("first"), &[], 0, TerminalSize::Compact, &options).unwrap();
+        let second = run_with_options("/bin/sh", &args("second"), &[], 0, TerminalSize::Standard, &options).unwrap();
+
+        let lines = |text: &str| text.lines().take(2).map(|line| line.trim_end().to_string()).collect::<Vec<_>>();
+        assert_eq!(pool.stats(), PoolStats { opened: 1, reused: 1 });
+        assert_eq!(lines(&first[0].text), ["24 80", "first"]);
+        // Nothing from the first run leaks into the second, and the size follows the run
+        assert_eq!(lines(&second[0].text), ["40 120", "second"]);
+    }
+}
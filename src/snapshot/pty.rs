@@ -1,53 +1,57 @@
 use font8x8::{BASIC_FONTS, BLOCK_FONTS, BOX_FONTS, GREEK_FONTS, HIRAGANA_FONTS, LATIN_FONTS, MISC_FONTS, UnicodeFonts};
-use image::{ImageBuffer, Rgb};
-use portable_pty::{native_pty_system, Child, CommandBuilder, PtySize};
-use std::io::{ErrorKind, Read, Write};
+use image::{ImageBuffer, Rgb, RgbaImage};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+use serde::{Deserialize, Serialize};
+
+use super::crop::{crop_png_cells, CellRect, Crop};
+use super::diff::{diff_images, PixelDiff};
+use super::glyphs::{fallback_bitmap, font_has_glyph, GlyphFallback};
+use super::graphics::{composite, ImageCursor, InlineImage};
+use super::grid::TerminalGrid;
+use super::layout::KeyboardLayout;
+use super::hyperlink::Hyperlink;
+use super::image_protocols::{decode_iterm_file, ApcScanner, KittyGraphics, MAX_IMAGE_PAYLOAD};
+use super::keys::{kitty_key_sequence, parse_explicit_input, parse_raw_input, ExplicitInput, KeyProtocol};
+use super::limits::ResourceLimits;
+use super::mouse::{encode_mouse, parse_mouse_input, MouseInput, MouseTracking};
+use super::pool::{PtyHandle, PtyPool};
+use super::replay::{state_marker, CastRecorder};
+use super::signal::{parse_signal_input, send_signal, Sig};
+use super::sixel::SixelDecoder;
+use super::theme::Theme;
+use super::wait::{parse_wait_input, timeout_message, wait_for_condition, WaitCondition, WaitOutcome, DEFAULT_WAIT_TIMEOUT};
+use std::collections::BTreeMap;
+use std::io::{self, ErrorKind, Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 use vte::{Params, Parser as AnsiParser, Perform};
 
 const DEFAULT_TERMINAL_WIDTH: u16 = 120;
 const DEFAULT_TERMINAL_HEIGHT: u16 = 40;
-const FONT_WIDTH: u32 = 8;
-const FONT_HEIGHT: u32 = 16;
+pub(crate) const FONT_WIDTH: u32 = 8;
+pub(crate) const FONT_HEIGHT: u32 = 16;
 const PIXEL_SCALE: u32 = 2;
 /// Width of a terminal cell in pixels (font width * pixel scale)
 pub const CELL_WIDTH: u32 = FONT_WIDTH * PIXEL_SCALE;
 /// Height of a terminal cell in pixels (font height * pixel scale)
 pub const CELL_HEIGHT: u32 = FONT_HEIGHT * PIXEL_SCALE;
 const QUIET_WINDOW: Duration = Duration::from_millis(180);
+/// Quiet window after a synchronized-output frame ends (DEC mode 2026)
+const FRAME_QUIET_WINDOW: Duration = Duration::from_millis(40);
 /// Maximum time to wait for initial render (for apps that output continuously)
 const MAX_INITIAL_RENDER_WAIT: Duration = Duration::from_secs(3);
 /// Maximum time to wait for render after each input
 const MAX_INPUT_RENDER_WAIT: Duration = Duration::from_secs(2);
-const PROCESS_DRAIN_TIMEOUT: Duration = Duration::from_secs(3);
-
-const ANSI_COLORS: [[u8; 3]; 8] = [
-    [0, 0, 0],
-    [205, 49, 49],
-    [13, 188, 121],
-    [229, 229, 16],
-    [36, 114, 200],
-    [188, 63, 188],
-    [17, 168, 205],
-    [229, 229, 229],
-];
-
-const ANSI_BRIGHT_COLORS: [[u8; 3]; 8] = [
-    [102, 102, 102],
-    [241, 76, 76],
-    [35, 209, 139],
-    [245, 245, 67],
-    [59, 142, 234],
-    [214, 112, 214],
-    [41, 184, 219],
-    [255, 255, 255],
-];
-
-fn clamp_u16_to_u8(value: u16) -> u8 {
-    value.min(255) as u8
+pub(crate) const PROCESS_DRAIN_TIMEOUT: Duration = Duration::from_secs(3);
+/// Entries kept on the kitty keyboard flags stack
+const MAX_KEY_FLAGS_DEPTH: usize = 16;
+
+/// Darken a color for dim (faint) text
+fn dim_color(color: [u8; 3]) -> [u8; 3] {
+    [color[0] / 2, color[1] / 2, color[2] / 2]
 }
 
 /// Brighten a color for bold text
@@ -60,30 +64,15 @@ fn brighten_color(color: [u8; 3]) -> [u8; 3] {
     ]
 }
 
-fn xterm_256_to_rgb(idx: u8) -> [u8; 3] {
-    match idx {
-        0..=7 => ANSI_COLORS[idx as usize],
-        8..=15 => ANSI_BRIGHT_COLORS[(idx - 8) as usize],
-        16..=231 => {
-            let normalized = idx - 16;
-            let r = normalized / 36;
-            let g = (normalized % 36) / 6;
-            let b = normalized % 6;
-            let scale = [0, 95, 135, 175, 215, 255];
-            [scale[r as usize], scale[g as usize], scale[b as usize]]
-        }
-        232..=255 => {
-            let shade = 8 + (idx - 232) * 10;
-            [shade, shade, shade]
-        }
-    }
-}
-
-fn get_char_bitmap(ch: char) -> [u8; 16] {
-    font8x8_bitmap(ch)
+/// Glyph for a character: the built-in fonts, then the fallback chain, else blank
+pub(crate) fn get_char_bitmap(ch: char, fallback: &[GlyphFallback]) -> [u8; 16] {
+    builtin_bitmap(ch)
+        .or_else(|| fallback_bitmap(ch, fallback))
+        .unwrap_or([0; 16])
 }
 
-fn font8x8_bitmap(ch: char) -> [u8; 16] {
+/// Glyph from the bundled fonts, if they cover the character
+pub(crate) fn builtin_bitmap(ch: char) -> Option<[u8; 16]> {
     fn expand(glyph: [u8; 8]) -> [u8; 16] {
         let mut out = [0u8; 16];
         for (idx, row) in glyph.iter().enumerate() {
@@ -95,18 +84,16 @@ fn font8x8_bitmap(ch: char) -> [u8; 16] {
     }
 
     // font8x8 glyph sets
-    if let Some(glyph) = BASIC_FONTS.get(ch) { return expand(glyph); }
-    if let Some(glyph) = BOX_FONTS.get(ch) { return expand(glyph); }
-    if let Some(glyph) = BLOCK_FONTS.get(ch) { return expand(glyph); }
-    if let Some(glyph) = LATIN_FONTS.get(ch) { return expand(glyph); }
-    if let Some(glyph) = GREEK_FONTS.get(ch) { return expand(glyph); }
-    if let Some(glyph) = HIRAGANA_FONTS.get(ch) { return expand(glyph); }
-    if let Some(glyph) = MISC_FONTS.get(ch) { return expand(glyph); }
+    if let Some(glyph) = BASIC_FONTS.get(ch) { return Some(expand(glyph)); }
+    if let Some(glyph) = BOX_FONTS.get(ch) { return Some(expand(glyph)); }
+    if let Some(glyph) = BLOCK_FONTS.get(ch) { return Some(expand(glyph)); }
+    if let Some(glyph) = LATIN_FONTS.get(ch) { return Some(expand(glyph)); }
+    if let Some(glyph) = GREEK_FONTS.get(ch) { return Some(expand(glyph)); }
+    if let Some(glyph) = HIRAGANA_FONTS.get(ch) { return Some(expand(glyph)); }
+    if let Some(glyph) = MISC_FONTS.get(ch) { return Some(expand(glyph)); }
 
     // Braille (U+2800-U+28FF) - used by ratatui Canvas for plotting
-    if let Some(braille) = render_braille(ch) { return braille; }
-
-    [0; 16]
+    render_braille(ch)
 }
 
 /// Render Braille character (U+2800-U+28FF) to 8x16 bitmap.
@@ -137,11 +124,17 @@ fn render_braille(ch: char) -> Option<[u8; 16]> {
     Some(bitmap)
 }
 
-struct TerminalPerformer<'a> {
+/// Applies parsed escape sequences to a [`Vt100Terminal`] (the `vte::Perform` side of [`Vt100Parser`])
+pub struct TerminalPerformer<'a> {
     terminal: &'a mut Vt100Terminal,
 }
 
 impl<'a> TerminalPerformer<'a> {
+    /// Performer updating `terminal`
+    pub fn new(terminal: &'a mut Vt100Terminal) -> Self {
+        Self { terminal }
+    }
+
     fn param_or(params: &Params, index: usize, default: u16) -> u16 {
         params
             .iter()
@@ -158,89 +151,160 @@ impl<'a> TerminalPerformer<'a> {
             return;
         }
 
-        let values: Vec<u16> = params.iter().flat_map(|chunk| chunk.iter().copied()).collect();
-        if values.is_empty() {
+        // Each group is one `;`-separated parameter with its `:`-separated sub-parameters
+        let groups: Vec<&[u16]> = params.iter().filter(|group| !group.is_empty()).collect();
+        if groups.is_empty() {
             self.terminal.reset_attributes();
             return;
         }
 
         let mut i = 0;
-        while i < values.len() {
-            let value = values[i];
+        while i < groups.len() {
+            let group = groups[i];
+            let value = group[0];
+
+            // Colon form (e.g. `4:3`, `58:2::255:0:0`) keeps its arguments inside the group
+            if group.len() > 1 {
+                match value {
+                    4 => self.terminal.set_underline_style(UnderlineStyle::from_sgr(group[1])),
+                    38 | 48 | 58 => {
+                        let sub = &group[1..];
+                        // Direct color may carry a colorspace id: `2:<cs>:r:g:b`
+                        let color = if sub[0] == 2 && sub.len() >= 5 {
+                            parse_extended_color(&[2, sub[2], sub[3], sub[4]], &self.terminal.palette)
+                        } else {
+                            parse_extended_color(sub, &self.terminal.palette)
+                        };
+                        if let Some((color, _)) = color {
+                            self.apply_extended_color(value, color);
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+                continue;
+            }
+
             match value {
                 0 => self.terminal.reset_attributes(),
                 1 => self.terminal.set_bold(true),
+                2 => self.terminal.set_dim(true),
+                3 => self.terminal.set_italic(true),
                 4 => self.terminal.set_underline(true),
+                5 | 6 => self.terminal.set_blink(true), // Slow and rapid blink
                 7 => self.terminal.set_inverse(true),
-                22 => self.terminal.set_bold(false), // Normal intensity (not bold)
+                9 => self.terminal.set_strikethrough(true),
+                21 => self.terminal.set_underline_style(UnderlineStyle::Double),
+                22 => {
+                    // Normal intensity (neither bold nor dim)
+                    self.terminal.set_bold(false);
+                    self.terminal.set_dim(false);
+                }
+                23 => self.terminal.set_italic(false),
                 24 => self.terminal.set_underline(false),
+                25 => self.terminal.set_blink(false),
                 27 => self.terminal.set_inverse(false),
+                29 => self.terminal.set_strikethrough(false),
                 30..=37 => {
                     self.terminal
-                        .set_fg_color(ANSI_COLORS[(value - 30) as usize]);
+                        .set_fg_color(self.terminal.palette_color((value - 30) as u8));
                 }
                 40..=47 => {
                     self.terminal
-                        .set_bg_color(ANSI_COLORS[(value - 40) as usize]);
+                        .set_bg_color(self.terminal.palette_color((value - 40) as u8));
                 }
                 90..=97 => {
                     self.terminal
-                        .set_fg_color(ANSI_BRIGHT_COLORS[(value - 90) as usize]);
+                        .set_fg_color(self.terminal.palette_color((value - 82) as u8));
                 }
                 100..=107 => {
                     self.terminal
-                        .set_bg_color(ANSI_BRIGHT_COLORS[(value - 100) as usize]);
+                        .set_bg_color(self.terminal.palette_color((value - 92) as u8));
                 }
-                38 | 48 => {
-                    let is_fg = value == 38;
-                    if i + 1 >= values.len() {
-                        break;
-                    }
-                    let mode = values[i + 1];
-                    match mode {
-                        2 => {
-                            if i + 4 >= values.len() {
-                                break;
-                            }
-                            let r = clamp_u16_to_u8(values[i + 2]);
-                            let g = clamp_u16_to_u8(values[i + 3]);
-                            let b = clamp_u16_to_u8(values[i + 4]);
-                            let color = [r, g, b];
-                            if is_fg {
-                                self.terminal.set_fg_color(color);
-                            } else {
-                                self.terminal.set_bg_color(color);
-                            }
-                            i += 5;
-                            continue;
-                        }
-                        5 => {
-                            if i + 2 >= values.len() {
-                                break;
+                38 | 48 | 58 => {
+                    // Semicolon form: the color arguments are the following parameters
+                    let rest: Vec<u16> = groups[i + 1..].iter().map(|g| g[0]).collect();
+                    match rest.first() {
+                        Some(2) | Some(5) => match parse_extended_color(&rest, &self.terminal.palette) {
+                            Some((color, consumed)) => {
+                                self.apply_extended_color(value, color);
+                                i += 1 + consumed;
+                                continue;
                             }
-                            let idx = values[i + 2] as u8;
-                            let color = xterm_256_to_rgb(idx);
-                            if is_fg {
-                                self.terminal.set_fg_color(color);
-                            } else {
-                                self.terminal.set_bg_color(color);
-                            }
-                            i += 3;
-                            continue;
-                        }
-                        _ => {
+                            None => break,
+                        },
+                        Some(_) => {
                             i += 2;
                             continue;
                         }
+                        None => break,
                     }
                 }
                 39 => self.terminal.reset_fg(),
                 49 => self.terminal.reset_bg(),
+                59 => self.terminal.set_underline_color(None),
                 _ => {}
             }
             i += 1;
         }
     }
+
+    /// Apply an extended color to the foreground (38), background (48) or underline (58)
+    fn apply_extended_color(&mut self, selector: u16, color: [u8; 3]) {
+        match selector {
+            38 => self.terminal.set_fg_color(color),
+            48 => self.terminal.set_bg_color(color),
+            58 => self.terminal.set_underline_color(Some(color)),
+            _ => {}
+        }
+    }
+}
+
+/// Parse an X11 color spec as used by OSC 4/10/11: `rgb:r/g/b` (1-4 hex
+/// digits per channel) or `#rgb` / `#rrggbb` / `#rrrgggbbb` / `#rrrrggggbbbb`.
+fn parse_color_spec(spec: &[u8]) -> Option<[u8; 3]> {
+    let spec = std::str::from_utf8(spec).ok()?;
+    // Scale a channel of 1-4 hex digits to 8 bits
+    let channel = |hex: &str| -> Option<u8> {
+        if hex.is_empty() || hex.len() > 4 {
+            return None;
+        }
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        let max = (1u32 << (4 * hex.len())) - 1;
+        Some((value * 255 / max) as u8)
+    };
+
+    if let Some(rgb) = spec.strip_prefix("rgb:") {
+        let mut parts = rgb.split('/');
+        let color = [channel(parts.next()?)?, channel(parts.next()?)?, channel(parts.next()?)?];
+        return parts.next().is_none().then_some(color);
+    }
+    let hex = spec.strip_prefix('#')?;
+    if hex.is_empty() || !hex.len().is_multiple_of(3) || hex.len() > 12 || !hex.is_ascii() {
+        return None;
+    }
+    let width = hex.len() / 3;
+    let channel = |i: usize| channel(&hex[i * width..i * width + width]);
+    Some([channel(0)?, channel(1)?, channel(2)?])
+}
+
+/// Parse an extended color (`2;r;g;b` or `5;idx`) following SGR 38/48/58.
+/// Returns the color and the number of values consumed; out-of-range values
+/// make the color invalid, as in xterm.
+fn parse_extended_color(values: &[u16], palette: &[[u8; 3]]) -> Option<([u8; 3], usize)> {
+    match values.first()? {
+        2 => {
+            let rgb = values.get(1..4)?;
+            let color = [
+                u8::try_from(rgb[0]).ok()?,
+                u8::try_from(rgb[1]).ok()?,
+                u8::try_from(rgb[2]).ok()?,
+            ];
+            Some((color, 4))
+        }
+        5 => Some((palette[usize::from(u8::try_from(*values.get(1)?).ok()?)], 2)),
+        _ => None,
+    }
 }
 
 impl<'a> Perform for TerminalPerformer<'a> {
@@ -248,18 +312,103 @@ impl<'a> Perform for TerminalPerformer<'a> {
         self.terminal.write_char(c);
     }
 
+    fn osc_dispatch(&mut self, params: &[&[u8]], bell_terminated: bool) {
+        match params.first().copied() {
+            // OSC 8 ; params ; URI — the URI itself may contain ';'
+            Some(b"8") if params.len() >= 3 => {
+                let uri = params[2..].join(&b';');
+                self.terminal.set_hyperlink(Some(&String::from_utf8_lossy(&uri)));
+            }
+            Some(b"8") => self.terminal.set_hyperlink(None),
+            // OSC 1337 ; File=args : base64 — iTerm2 inline image (base64 has no ';')
+            Some(b"1337") if params.len() >= 2 => {
+                let arg = params[1..].join(&b';');
+                self.terminal.iterm_file(&arg);
+            }
+            // OSC 4 ; index ; spec [; index ; spec ...] — set or query palette entries
+            Some(b"4") => {
+                for pair in params[1..].chunks(2) {
+                    let [idx, spec] = pair else { break };
+                    let Some(idx) = std::str::from_utf8(idx).ok().and_then(|i| i.parse::<u8>().ok()) else {
+                        continue;
+                    };
+                    if *spec == b"?" {
+                        let color = self.terminal.palette_color(idx);
+                        self.terminal.report_color(&format!("4;{}", idx), color, bell_terminated);
+                    } else if let Some(color) = parse_color_spec(spec) {
+                        self.terminal.set_palette_color(idx, color);
+                    }
+                }
+            }
+            // OSC 10 / 11 ; spec — set or query the default foreground / background
+            Some(code @ (b"10" | b"11")) if params.len() >= 2 => {
+                let is_fg = code == b"10";
+                let (fg, bg) = self.terminal.default_colors();
+                if params[1] == b"?" {
+                    let code = if is_fg { "10" } else { "11" };
+                    self.terminal.report_color(code, if is_fg { fg } else { bg }, bell_terminated);
+                } else if let Some(color) = parse_color_spec(params[1]) {
+                    if is_fg {
+                        self.terminal.set_default_fg(color);
+                    } else {
+                        self.terminal.set_default_bg(color);
+                    }
+                }
+            }
+            // OSC 104 [; index ...] — reset palette entries
+            Some(b"104") => {
+                let indices: Vec<u8> = params[1..]
+                    .iter()
+                    .filter_map(|p| std::str::from_utf8(p).ok()?.parse().ok())
+                    .collect();
+                if indices.is_empty() {
+                    self.terminal.reset_palette_color(None);
+                }
+                for idx in indices {
+                    self.terminal.reset_palette_color(Some(idx));
+                }
+            }
+            // OSC 110 / 111 — reset the default foreground / background
+            Some(b"110") => {
+                let color = self.terminal.theme().foreground;
+                self.terminal.set_default_fg(color);
+            }
+            Some(b"111") => {
+                let color = self.terminal.theme().background;
+                self.terminal.set_default_bg(color);
+            }
+            _ => {}
+        }
+    }
+
+    fn hook(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, action: char) {
+        // Sixel: `DCS p1 ; p2 ; p3 q` (p2 = 1 keeps undrawn pixels transparent)
+        if action == 'q' && intermediates.is_empty() {
+            self.terminal.begin_sixel(Self::param_or(params, 1, 0) == 1);
+        }
+    }
+
+    fn put(&mut self, byte: u8) {
+        self.terminal.sixel_data(byte);
+    }
+
+    fn unhook(&mut self) {
+        self.terminal.end_sixel();
+    }
+
     fn execute(&mut self, byte: u8) {
         match byte {
             b'\n' => self.terminal.write_char('\n'),
             b'\r' => self.terminal.write_char('\r'),
             b'\t' => self.terminal.write_char('\t'),
+            0x07 => self.terminal.bell(),
             0x08 => self.terminal.backspace(),
             _ => {}
         }
     }
 
     fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, action: char) {
-        let private_mode = intermediates.iter().any(|b| *b == b'?');
+        let private_mode = intermediates.contains(&b'?');
 
         match action {
             'H' | 'f' => {
@@ -270,19 +419,19 @@ impl<'a> Perform for TerminalPerformer<'a> {
             }
             'A' => {
                 let value = Self::param_or(params, 0, 1) as i32;
-                self.terminal.move_cursor_rel(0, -(value as i32));
+                self.terminal.move_cursor_rel(0, -value);
             }
             'B' => {
                 let value = Self::param_or(params, 0, 1) as i32;
-                self.terminal.move_cursor_rel(0, value as i32);
+                self.terminal.move_cursor_rel(0, value);
             }
             'C' => {
                 let value = Self::param_or(params, 0, 1) as i32;
-                self.terminal.move_cursor_rel(value as i32, 0);
+                self.terminal.move_cursor_rel(value, 0);
             }
             'D' => {
                 let value = Self::param_or(params, 0, 1) as i32;
-                self.terminal.move_cursor_rel(-(value as i32), 0);
+                self.terminal.move_cursor_rel(-value, 0);
             }
             'J' => {
                 let mode = Self::param_or(params, 0, 0);
@@ -295,23 +444,66 @@ impl<'a> Perform for TerminalPerformer<'a> {
             }
             'K' => self.terminal.clear_line_from_cursor(),
             'm' => self.handle_sgr(params),
+            'I' => self.terminal.tab_forward(u32::from(Self::param_or(params, 0, 1))),
+            'Z' => self.terminal.tab_backward(u32::from(Self::param_or(params, 0, 1))),
+            'g' => self.terminal.clear_tab_stops(Self::param_or(params, 0, 0)),
+            'n' => self
+                .terminal
+                .device_status_report(Self::param_or(params, 0, 0), private_mode),
+            'c' if intermediates.is_empty() && Self::param_or(params, 0, 0) == 0 => {
+                self.terminal.primary_device_attributes();
+            }
+            'c' if intermediates == b">" && Self::param_or(params, 0, 0) == 0 => {
+                self.terminal.secondary_device_attributes();
+            }
+            'q' if intermediates == b">" && Self::param_or(params, 0, 0) == 0 => {
+                self.terminal.report_version();
+            }
+            'p' if intermediates == b"?$" => {
+                self.terminal.report_private_mode(Self::param_or(params, 0, 0));
+            }
+            'q' if intermediates == b" " => {
+                self.terminal.set_cursor_style(Self::param_or(params, 0, 0));
+            }
             's' => self.terminal.save_cursor(),
-            'u' => self.terminal.restore_cursor(),
+            'u' if intermediates.is_empty() => self.terminal.restore_cursor(),
+            'u' if intermediates == b">" => {
+                self.terminal.push_key_flags(Self::param_or(params, 0, 0));
+            }
+            'u' if intermediates == b"<" => {
+                self.terminal.pop_key_flags(Self::param_or(params, 0, 1));
+            }
+            'u' if intermediates == b"=" => {
+                self.terminal.set_key_flags(Self::param_or(params, 0, 0), Self::param_or(params, 1, 1));
+            }
+            'u' if private_mode => self.terminal.report_key_flags(),
             'h' if private_mode => {
                 // Handle private mode set
                 let mode = Self::param_or(params, 0, 0);
                 match mode {
+                    5 => self.terminal.set_reverse_screen(true),
+                    7 => self.terminal.set_autowrap(true),
+                    9 | 1000 | 1002 | 1003 => self.terminal.set_mouse_tracking(mode, true),
+                    1006 => self.terminal.set_sgr_mouse(true),
+                    25 => self.terminal.set_cursor_visible(true),
+                    2026 => self.terminal.set_synchronized_output(true),
                     47 | 1047 | 1049 => {
                         // Enter alternate screen buffer
                         self.terminal.enter_alternate_screen();
                     }
-                    _ => {} // Ignore other private modes (cursor visibility, etc.)
+                    _ => {} // Ignore other private modes
                 }
             }
             'l' if private_mode => {
                 // Handle private mode reset
                 let mode = Self::param_or(params, 0, 0);
                 match mode {
+                    5 => self.terminal.set_reverse_screen(false),
+                    7 => self.terminal.set_autowrap(false),
+                    9 | 1000 | 1002 | 1003 => self.terminal.set_mouse_tracking(mode, false),
+                    1006 => self.terminal.set_sgr_mouse(false),
+                    25 => self.terminal.set_cursor_visible(false),
+                    2026 => self.terminal.set_synchronized_output(false),
                     47 | 1047 | 1049 => {
                         // Leave alternate screen buffer
                         self.terminal.leave_alternate_screen();
@@ -323,22 +515,273 @@ impl<'a> Perform for TerminalPerformer<'a> {
         }
     }
 
-    fn esc_dispatch(&mut self, _intermediates: &[u8], _ignore: bool, byte: u8) {
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        if intermediates == b"#" {
+            // DECDHL (3, 4), DECSWL (5) and DECDWL (6)
+            let size = match byte {
+                b'3' => LineSize::DoubleHeightTop,
+                b'4' => LineSize::DoubleHeightBottom,
+                b'5' => LineSize::Single,
+                b'6' => LineSize::DoubleWidth,
+                _ => return,
+            };
+            self.terminal.set_line_size(size);
+            return;
+        }
+        if !intermediates.is_empty() {
+            return;
+        }
         match byte {
             b'7' => self.terminal.save_cursor(),
             b'8' => self.terminal.restore_cursor(),
-            b'c' => self.terminal.clear(),
+            b'H' => self.terminal.set_tab_stop(),
+            b'c' => {
+                self.terminal.clear();
+                self.terminal.set_tab_interval(DEFAULT_TAB_INTERVAL);
+                self.terminal.set_autowrap(true);
+            }
             _ => {}
         }
     }
 }
 
+/// Underline style selected with `SGR 4:x` (or `SGR 21` for double)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnderlineStyle {
+    #[default]
+    None,
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+impl UnderlineStyle {
+    /// Map the `SGR 4:x` sub-parameter to a style
+    pub fn from_sgr(value: u16) -> Self {
+        match value {
+            0 => UnderlineStyle::None,
+            2 => UnderlineStyle::Double,
+            3 => UnderlineStyle::Curly,
+            4 => UnderlineStyle::Dotted,
+            5 => UnderlineStyle::Dashed,
+            _ => UnderlineStyle::Single,
+        }
+    }
+
+    /// Whether a pixel of the cell (in font coordinates) belongs to the underline
+    fn covers(self, px: u32, py: u32) -> bool {
+        let bottom = FONT_HEIGHT - 1;
+        match self {
+            UnderlineStyle::None => false,
+            UnderlineStyle::Single => py >= bottom - 1,
+            UnderlineStyle::Double => py == bottom - 3 || py == bottom,
+            UnderlineStyle::Curly => {
+                const WAVE: [u32; 8] = [0, 0, 1, 1, 2, 2, 1, 1];
+                py == bottom - WAVE[(px % 8) as usize]
+            }
+            UnderlineStyle::Dotted => py >= bottom - 1 && (px / 2).is_multiple_of(2),
+            UnderlineStyle::Dashed => py >= bottom - 1 && px % 8 < 6,
+        }
+    }
+}
+
+/// Size of a line set with DECDHL/DECDWL (`ESC # 3` .. `ESC # 6`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineSize {
+    /// Normal line (DECSWL)
+    #[default]
+    Single,
+    /// Double-width line (DECDWL)
+    DoubleWidth,
+    /// Top half of a double-height, double-width line (DECDHL)
+    DoubleHeightTop,
+    /// Bottom half of a double-height, double-width line (DECDHL)
+    DoubleHeightBottom,
+}
+
+impl LineSize {
+    /// Whether each character takes two columns
+    pub fn is_double_width(self) -> bool {
+        self != LineSize::Single
+    }
+
+    /// Glyph row shown at display row `py` of the cell
+    fn source_row(self, py: u32) -> u32 {
+        match self {
+            LineSize::Single | LineSize::DoubleWidth => py,
+            LineSize::DoubleHeightTop => py / 2,
+            LineSize::DoubleHeightBottom => FONT_HEIGHT / 2 + py / 2,
+        }
+    }
+}
+
+/// Cursor shape used when the cursor is drawn into a capture
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CursorShape {
+    /// Full cell block (drawn by inverting the cell)
+    #[default]
+    Block,
+    /// Line along the bottom of the cell
+    Underline,
+    /// Vertical bar along the left edge of the cell
+    Bar,
+}
+
+/// Cursor visibility and style at a point in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CursorState {
+    /// Whether the cursor is shown (DECTCEM)
+    pub visible: bool,
+    /// Cursor shape (DECSCUSR)
+    pub shape: CursorShape,
+    /// Whether the cursor blinks (DECSCUSR)
+    pub blinking: bool,
+}
+
+impl Default for CursorState {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            shape: CursorShape::Block,
+            blinking: true,
+        }
+    }
+}
+
+/// A cursor visibility or style change requested by the application
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CursorChange {
+    /// Cursor shown (`CSI ?25h`)
+    Shown,
+    /// Cursor hidden (`CSI ?25l`)
+    Hidden,
+    /// Cursor style changed (`CSI Ps SP q`)
+    Style {
+        /// New cursor shape
+        shape: CursorShape,
+        /// Whether the new style blinks
+        blinking: bool,
+    },
+}
+
+/// Phase of blinking content (blink attribute, blinking cursor) in a render
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlinkPhase {
+    /// Phase A: blinking text and cursor are drawn
+    Visible,
+    /// Phase B: blinking text and cursor are blanked out
+    Hidden,
+}
+
+/// Options controlling how a terminal buffer is rendered to an image
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    /// Overlay the cursor at its current position
+    pub show_cursor: bool,
+    /// Render a specific blink phase; `None` highlights blinking cells instead
+    pub blink_phase: Option<BlinkPhase>,
+    /// Fallbacks for characters the built-in fonts do not cover (empty = blank)
+    pub glyph_fallback: Vec<GlyphFallback>,
+    /// Render only a band of rows or a rectangle of cells
+    pub crop: Option<Crop>,
+}
+
+impl RenderOptions {
+    /// Set whether the cursor is overlaid on the rendered image
+    pub fn show_cursor(mut self, show: bool) -> Self {
+        self.show_cursor = show;
+        self
+    }
+
+    /// Render a specific blink phase
+    pub fn blink_phase(mut self, phase: BlinkPhase) -> Self {
+        self.blink_phase = Some(phase);
+        self
+    }
+
+    /// Set the glyph fallback chain for characters the built-in fonts do not cover
+    pub fn glyph_fallback(mut self, chain: Vec<GlyphFallback>) -> Self {
+        self.glyph_fallback = chain;
+        self
+    }
+
+    /// Render only the rows (or cells) selected by a crop
+    pub fn crop(mut self, crop: Option<Crop>) -> Self {
+        self.crop = crop;
+        self
+    }
+
+    /// Height in rows of the image rendered for a screen `height` rows high
+    pub(crate) fn image_rows(&self, height: u32) -> u32 {
+        self.crop.map_or(height, |crop| crop.rows(height).len() as u32)
+    }
+
+    /// Width in columns of the image rendered for a screen `width` columns wide
+    pub(crate) fn image_cols(&self, width: u32) -> u32 {
+        self.crop.map_or(width, |crop| crop.cols(width).len() as u32)
+    }
+}
+
+/// Identity reported in answers to terminal capability queries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalIdentity {
+    /// Primary Device Attributes parameters (`CSI ? <da1> c`)
+    pub da1: String,
+    /// Secondary Device Attributes terminal type (`CSI > <type> ; <version> ; 0 c`)
+    pub da2_type: u16,
+    /// Firmware version reported in DA2 and XTVERSION
+    pub version: u32,
+    /// Terminal name reported by XTVERSION (`DCS > | <name>(<version>) ST`)
+    pub name: String,
+}
+
+impl Default for TerminalIdentity {
+    /// An xterm-compatible VT220 with sixel graphics and ANSI color
+    fn default() -> Self {
+        Self {
+            da1: "62;4;22".to_string(),
+            da2_type: 41,
+            version: 379,
+            name: "XTerm".to_string(),
+        }
+    }
+}
+
+/// Default spacing between tab stops
+pub const DEFAULT_TAB_INTERVAL: u32 = 8;
+
+/// Tab stop table with a stop every `interval` columns (excluding column 0)
+fn default_tab_stops(width: u32, interval: u32) -> Vec<bool> {
+    (0..width)
+        .map(|col| interval > 0 && col > 0 && col.is_multiple_of(interval))
+        .collect()
+}
+
 /// Text attributes for a single cell
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub struct CellAttributes {
     pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
     pub underline: bool,
+    /// Underline style (`None` unless `underline` is set)
+    pub underline_style: UnderlineStyle,
+    /// Underline color set via `SGR 58` (`None` = use the foreground color)
+    pub underline_color: Option<[u8; 3]>,
+    pub blink: bool,
     pub inverse: bool,
+    pub strikethrough: bool,
+    /// Active OSC 8 hyperlink (index into the terminal's hyperlink URIs)
+    pub hyperlink: Option<u32>,
+    /// Foreground set by SGR rather than the default, so reverse-screen mode keeps it
+    pub explicit_fg: bool,
+    /// Background set by SGR rather than the default, so reverse-screen mode keeps it
+    pub explicit_bg: bool,
 }
 
 /// Saved state for alternate screen buffer
@@ -348,10 +791,44 @@ struct SavedScreen {
     fg_colors: Vec<Vec<[u8; 3]>>,
     bg_colors: Vec<Vec<[u8; 3]>>,
     attributes: Vec<Vec<CellAttributes>>,
+    line_sizes: Vec<LineSize>,
+    images: Vec<InlineImage>,
     cursor_x: u32,
     cursor_y: u32,
 }
 
+/// Resize a row-major grid, dropping `drop_top` rows first and filling new cells with `fill`
+fn resize_grid<T: Clone>(grid: &mut Vec<Vec<T>>, width: u32, height: u32, drop_top: usize, fill: T) {
+    grid.drain(..drop_top.min(grid.len()));
+    grid.resize(height as usize, vec![fill.clone(); width as usize]);
+    for row in grid.iter_mut() {
+        row.resize(width as usize, fill.clone());
+    }
+}
+
+/// Move images up by `rows` rows, dropping those scrolled off entirely
+fn shift_images(images: &mut Vec<InlineImage>, rows: usize) {
+    for image in images.iter_mut() {
+        image.row -= rows as i64;
+    }
+    images.retain(InlineImage::is_visible);
+}
+
+/// Replace color `old` with `new` in every cell whose color was not set explicitly
+fn recolor_cells(
+    colors: &mut [Vec<[u8; 3]>],
+    attributes: &[Vec<CellAttributes>],
+    explicit: impl Fn(&CellAttributes) -> bool,
+    old: [u8; 3],
+    new: [u8; 3],
+) {
+    for (cell, attrs) in colors.iter_mut().flatten().zip(attributes.iter().flatten()) {
+        if *cell == old && !explicit(attrs) {
+            *cell = new;
+        }
+    }
+}
+
 /// Represents the state of a VT100 terminal
 #[derive(Debug, Clone)]
 pub struct Vt100Terminal {
@@ -365,11 +842,29 @@ pub struct Vt100Terminal {
     pub fg_colors: Vec<Vec<[u8; 3]>>,
     /// Background color buffer
     pub bg_colors: Vec<Vec<[u8; 3]>>,
-    /// Cell attributes buffer (bold, underline, inverse)
+    /// Cell attributes buffer (bold, dim, italic, underline, blink, inverse, strikethrough)
     pub attributes: Vec<Vec<CellAttributes>>,
+    /// Per-line size (DECDHL/DECDWL), one entry per row
+    line_sizes: Vec<LineSize>,
+    /// Images placed by graphics protocols, in placement order
+    images: Vec<InlineImage>,
+    /// Sixel image being received, with the background for undrawn pixels
+    sixel: Option<(Box<SixelDecoder>, Option<[u8; 3]>)>,
+    /// Images transmitted with the kitty graphics protocol
+    kitty: KittyGraphics,
+    /// iTerm2 multipart file being received: its arguments, `:`, and the payload so far
+    iterm_upload: Option<Vec<u8>>,
     /// Cursor position
     pub cursor_x: u32,
     pub cursor_y: u32,
+    /// Cursor shape (used when the cursor is rendered)
+    pub cursor_shape: CursorShape,
+    /// Whether the cursor blinks (DECSCUSR odd values)
+    pub cursor_blinking: bool,
+    /// Whether the cursor is visible (DECTCEM, `CSI ?25h/l`)
+    pub cursor_visible: bool,
+    /// Cursor visibility/style changes received since they were last taken, in order
+    pub cursor_changes: Vec<CursorChange>,
     /// Current colors
     pub current_fg: [u8; 3],
     pub current_bg: [u8; 3],
@@ -384,6 +879,40 @@ pub struct Vt100Terminal {
     alternate_screen: Option<Box<SavedScreen>>,
     /// Whether we're currently in the alternate screen
     in_alternate_screen: bool,
+    /// Number of audible bells (BEL) received
+    pub bell_count: u32,
+    /// Number of visual bells (reverse-screen flashes) received
+    pub visual_bell_count: u32,
+    /// Reverse-screen mode was set and may be the start of a visual bell flash
+    flash_pending: bool,
+    /// Whole-screen reverse video (DECSCNM, `CSI ?5h`): default colors are swapped when rendering
+    reverse_screen: bool,
+    /// Mouse events the application asked for (modes 9, 1000, 1002, 1003)
+    mouse_tracking: MouseTracking,
+    /// SGR mouse encoding requested (mode 1006)
+    sgr_mouse: bool,
+    /// Kitty keyboard protocol flags pushed by the application (`CSI > flags u`), innermost last
+    key_flags: Vec<u8>,
+    /// Tab stop table, one entry per column
+    tab_stops: Vec<bool>,
+    /// Replies to device queries (DSR, etc.) waiting to be written back to the application
+    pending_replies: Vec<u8>,
+    /// Identity reported to DA1/DA2/XTVERSION queries
+    pub identity: TerminalIdentity,
+    /// Inside a synchronized-output frame (`CSI ?2026h` .. `CSI ?2026l`)
+    synchronized_output: bool,
+    /// Number of synchronized-output frames completed
+    pub completed_frames: u64,
+    /// Wrap at the right margin (DECAWM)
+    autowrap: bool,
+    /// The last column was written and the next printable character wraps first
+    wrap_pending: bool,
+    /// Distinct OSC 8 hyperlink URIs, referenced by `CellAttributes::hyperlink`
+    hyperlink_uris: Vec<String>,
+    /// Theme the palette and default colors were initialized from
+    theme: Theme,
+    /// Live 256-color palette (the theme plus OSC 4 changes)
+    palette: Vec<[u8; 3]>,
 }
 
 impl Vt100Terminal {
@@ -408,8 +937,17 @@ impl Vt100Terminal {
             fg_colors,
             bg_colors,
             attributes,
+            line_sizes: vec![LineSize::Single; height as usize],
+            images: Vec::new(),
+            sixel: None,
+            kitty: KittyGraphics::default(),
+            iterm_upload: None,
             cursor_x: 0,
             cursor_y: 0,
+            cursor_shape: CursorShape::default(),
+            cursor_blinking: true,
+            cursor_visible: true,
+            cursor_changes: Vec::new(),
             current_fg: [255, 255, 255],
             current_bg: [0, 0, 0],
             current_attrs: CellAttributes::default(),
@@ -418,7 +956,88 @@ impl Vt100Terminal {
             saved_cursor: None,
             alternate_screen: None,
             in_alternate_screen: false,
+            bell_count: 0,
+            visual_bell_count: 0,
+            flash_pending: false,
+            reverse_screen: false,
+            mouse_tracking: MouseTracking::Off,
+            sgr_mouse: false,
+            key_flags: Vec::new(),
+            tab_stops: default_tab_stops(width, DEFAULT_TAB_INTERVAL),
+            pending_replies: Vec::new(),
+            identity: TerminalIdentity::default(),
+            synchronized_output: false,
+            completed_frames: 0,
+            autowrap: true,
+            wrap_pending: false,
+            hyperlink_uris: Vec::new(),
+            theme: Theme::default(),
+            palette: (0..=255).map(|idx| Theme::default().color(idx)).collect(),
+        }
+    }
+
+    /// Switch the color theme; cells in the old default colors take the new ones.
+    ///
+    /// Resets any palette and default color changes made via OSC 4/10/11.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.set_default_fg(theme.foreground);
+        self.set_default_bg(theme.background);
+        self.palette = (0..=255).map(|idx| theme.color(idx)).collect();
+        self.theme = theme;
+    }
+
+    /// The current color theme
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Color of a 256-color palette entry, including OSC 4 changes
+    pub fn palette_color(&self, idx: u8) -> [u8; 3] {
+        self.palette[idx as usize]
+    }
+
+    /// Redefine a palette entry (OSC 4); applies to text drawn afterwards
+    pub fn set_palette_color(&mut self, idx: u8, color: [u8; 3]) {
+        self.palette[idx as usize] = color;
+    }
+
+    /// Restore a palette entry, or the whole palette, from the theme (OSC 104)
+    pub fn reset_palette_color(&mut self, idx: Option<u8>) {
+        match idx {
+            Some(idx) => self.palette[idx as usize] = self.theme.color(idx),
+            None => self.palette = (0..=255).map(|idx| self.theme.color(idx)).collect(),
+        }
+    }
+
+    /// Default (foreground, background) colors
+    pub fn default_colors(&self) -> ([u8; 3], [u8; 3]) {
+        (self.default_fg, self.default_bg)
+    }
+
+    /// Change the default foreground (OSC 10); cells in the default color take the new one
+    pub fn set_default_fg(&mut self, color: [u8; 3]) {
+        recolor_cells(&mut self.fg_colors, &self.attributes, |attrs| attrs.explicit_fg, self.default_fg, color);
+        if !self.current_attrs.explicit_fg {
+            self.current_fg = color;
+        }
+        self.default_fg = color;
+    }
+
+    /// Change the default background (OSC 11); cells in the default color take the new one
+    pub fn set_default_bg(&mut self, color: [u8; 3]) {
+        recolor_cells(&mut self.bg_colors, &self.attributes, |attrs| attrs.explicit_bg, self.default_bg, color);
+        if !self.current_attrs.explicit_bg {
+            self.current_bg = color;
         }
+        self.default_bg = color;
+    }
+
+    /// Queue the reply to an OSC color query (`OSC code ; ? ST`)
+    pub fn report_color(&mut self, code: &str, color: [u8; 3], bell_terminated: bool) {
+        let [r, g, b] = color.map(u16::from).map(|c| c * 0x101);
+        let terminator = if bell_terminated { "\x07" } else { "\x1b\\" };
+        let reply = format!("\x1b]{};rgb:{:04x}/{:04x}/{:04x}{}", code, r, g, b, terminator);
+        self.pending_replies.extend_from_slice(reply.as_bytes());
     }
 
     /// Clear the screen
@@ -431,8 +1050,11 @@ impl Vt100Terminal {
                 self.attributes[y as usize][x as usize] = CellAttributes::default();
             }
         }
+        self.line_sizes.fill(LineSize::Single);
+        self.images.clear();
         self.cursor_x = 0;
         self.cursor_y = 0;
+        self.wrap_pending = false;
         self.saved_cursor = None;
         self.reset_attributes();
     }
@@ -445,9 +1067,18 @@ impl Vt100Terminal {
         } else if ch == '\r' {
             self.cursor_x = 0;
         } else if ch == '\t' {
-            self.cursor_x = ((self.cursor_x / 8) + 1) * 8;
+            self.tab_forward(1);
+            return;
         } else {
-            if self.cursor_x < self.width && self.cursor_y < self.height {
+            self.cancel_flash();
+            // A character written after filling the last column wraps first (deferred wrap)
+            if self.wrap_pending {
+                self.cursor_x = 0;
+                self.cursor_y += 1;
+                self.scroll_if_needed();
+            }
+            let line_width = self.line_width(self.cursor_y);
+            if self.cursor_x < line_width && self.cursor_y < self.height {
                 let row = self.cursor_y as usize;
                 let col = self.cursor_x as usize;
                 self.buffer[row][col] = ch;
@@ -455,61 +1086,299 @@ impl Vt100Terminal {
                 self.bg_colors[row][col] = self.current_bg;
                 self.attributes[row][col] = self.current_attrs;
             }
-            self.cursor_x += 1;
+            if self.cursor_x + 1 < line_width {
+                self.cursor_x += 1;
+                self.wrap_pending = false;
+            } else {
+                // At the right margin the cursor stays put; with autowrap off
+                // further characters overwrite the last column
+                self.wrap_pending = self.autowrap;
+            }
+            return;
         }
 
-        // Handle line wrapping
-        if self.cursor_x >= self.width {
-            self.cursor_x = 0;
-            self.cursor_y += 1;
-        }
+        self.wrap_pending = false;
+        self.scroll_if_needed();
+    }
 
-        // Handle scrolling
+    /// Scroll the screen up if the cursor moved below the last row
+    fn scroll_if_needed(&mut self) {
         if self.cursor_y >= self.height {
             // Scroll up
             self.buffer.remove(0);
             self.fg_colors.remove(0);
             self.bg_colors.remove(0);
             self.attributes.remove(0);
+            self.line_sizes.remove(0);
 
             self.buffer.push(vec![' '; self.width as usize]);
-            self.fg_colors.push(vec![[255, 255, 255]; self.width as usize]);
-            self.bg_colors.push(vec![[0, 0, 0]; self.width as usize]);
+            self.fg_colors.push(vec![self.default_fg; self.width as usize]);
+            self.bg_colors.push(vec![self.default_bg; self.width as usize]);
             self.attributes.push(vec![CellAttributes::default(); self.width as usize]);
+            self.line_sizes.push(LineSize::Single);
+
+            for image in &mut self.images {
+                image.row -= 1;
+            }
+            self.images.retain(InlineImage::is_visible);
 
             self.cursor_y = self.height - 1;
         }
     }
 
+    /// Enable or disable autowrap at the right margin (DECAWM, `CSI ?7h/l`)
+    pub fn set_autowrap(&mut self, enabled: bool) {
+        self.autowrap = enabled;
+        if !enabled {
+            self.wrap_pending = false;
+        }
+    }
+
+    /// Begin (`true`) or end (`false`) a synchronized-output frame (DEC mode 2026)
+    pub fn set_synchronized_output(&mut self, enabled: bool) {
+        if self.synchronized_output && !enabled {
+            self.completed_frames += 1;
+        }
+        self.synchronized_output = enabled;
+    }
+
+    /// Whether a synchronized-output frame is in progress
+    pub fn synchronized_output(&self) -> bool {
+        self.synchronized_output
+    }
+
+    /// Answer a DEC private mode query (DECRQM, `CSI ? Ps $ p`) so apps can
+    /// detect support for modes such as synchronized output
+    pub fn report_private_mode(&mut self, mode: u16) {
+        let state = match mode {
+            5 => Some(self.reverse_screen),
+            7 => Some(self.autowrap),
+            9 | 1000 | 1002 | 1003 => Some(MouseTracking::from_mode(mode) == Some(self.mouse_tracking)),
+            1006 => Some(self.sgr_mouse),
+            25 => Some(self.cursor_visible),
+            47 | 1047 | 1049 => Some(self.in_alternate_screen),
+            2026 => Some(self.synchronized_output),
+            _ => None,
+        };
+        // 1 = set, 2 = reset, 0 = not recognized
+        let value = match state {
+            Some(true) => 1,
+            Some(false) => 2,
+            None => 0,
+        };
+        let reply = format!("\x1b[?{};{}$y", mode, value);
+        self.pending_replies.extend_from_slice(reply.as_bytes());
+    }
+
+    /// Start receiving a sixel image (DCS `q`)
+    pub fn begin_sixel(&mut self, transparent: bool) {
+        let background = (!transparent).then_some(self.current_bg);
+        self.sixel = Some((Box::new(SixelDecoder::new()), background));
+    }
+
+    /// Feed a byte of DCS data to the sixel image being received
+    pub fn sixel_data(&mut self, byte: u8) {
+        if let Some((decoder, _)) = self.sixel.as_mut() {
+            decoder.put(byte);
+        }
+    }
+
+    /// Finish the sixel image being received and place it at the cursor
+    pub fn end_sixel(&mut self) {
+        if let Some((decoder, background)) = self.sixel.take()
+            && let Some(image) = decoder.finish(background)
+        {
+            self.place_image(image, None, ImageCursor::BelowImage);
+        }
+    }
+
+    /// Handle a kitty graphics command (the body of an APC string)
+    pub fn kitty_command(&mut self, body: &[u8]) {
+        let outcome = self.kitty.command(body);
+        match outcome.delete {
+            Some(Some(id)) => self.images.retain(|image| image.id != Some(id)),
+            Some(None) => self.images.clear(),
+            None => {}
+        }
+        if let Some(placement) = outcome.place {
+            let cursor = if placement.move_cursor { ImageCursor::AfterImage } else { ImageCursor::Unmoved };
+            self.place_image(placement.image, placement.id, cursor);
+        }
+        if let Some(reply) = outcome.reply {
+            self.pending_replies.extend_from_slice(reply.as_bytes());
+        }
+    }
+
+    /// Handle an iTerm2 `OSC 1337` file transfer, placing inline images at the cursor
+    pub fn iterm_file(&mut self, arg: &[u8]) {
+        let file = if let Some(file) = arg.strip_prefix(b"File=") {
+            file.to_vec()
+        } else if let Some(args) = arg.strip_prefix(b"MultipartFile=") {
+            self.iterm_upload = Some([args, b":"].concat());
+            return;
+        } else if let Some(part) = arg.strip_prefix(b"FilePart=") {
+            if let Some(upload) = self.iterm_upload.as_mut()
+                && upload.len() + part.len() <= MAX_IMAGE_PAYLOAD
+            {
+                upload.extend_from_slice(part);
+            }
+            return;
+        } else if arg == b"FileEnd" {
+            let Some(file) = self.iterm_upload.take() else { return };
+            file
+        } else {
+            return;
+        };
+        if let Some((image, move_cursor)) = decode_iterm_file(&file, (self.width, self.height)) {
+            let cursor = if move_cursor { ImageCursor::BelowImage } else { ImageCursor::Unmoved };
+            self.place_image(image, None, cursor);
+        }
+    }
+
+    /// Place an image with its top-left corner at the cursor.
+    ///
+    /// The cursor then moves as `cursor` says, scrolling the screen if the
+    /// image reaches past the bottom.
+    pub fn place_image(&mut self, image: RgbaImage, id: Option<u32>, cursor: ImageCursor) {
+        let placed = InlineImage {
+            col: self.cursor_x,
+            row: i64::from(self.cursor_y),
+            image,
+            id,
+        };
+        let (rows, end_col) = (placed.rows(), placed.col + placed.cols());
+        self.images.push(placed);
+        match cursor {
+            // As with sixel scrolling in xterm
+            ImageCursor::BelowImage => {
+                self.wrap_pending = false;
+                for _ in 0..rows {
+                    self.cursor_y += 1;
+                    self.scroll_if_needed();
+                }
+            }
+            ImageCursor::AfterImage => {
+                for _ in 1..rows {
+                    self.cursor_y += 1;
+                    self.scroll_if_needed();
+                }
+                self.cursor_x = end_col.min(self.width.saturating_sub(1));
+                self.wrap_pending = self.autowrap && end_col >= self.width;
+            }
+            ImageCursor::Unmoved => {}
+        }
+    }
+
+    /// Images currently on screen, in placement order
+    pub fn images(&self) -> &[InlineImage] {
+        &self.images
+    }
+
+    /// Whether autowrap (DECAWM) is enabled
+    pub fn autowrap(&self) -> bool {
+        self.autowrap
+    }
+
+    /// Move the cursor forward to the `count`th next tab stop (HT, CHT).
+    ///
+    /// Stops at the last column when no further tab stop is set.
+    pub fn tab_forward(&mut self, count: u32) {
+        let last = self.width.saturating_sub(1);
+        self.wrap_pending = false;
+        for _ in 0..count {
+            self.cursor_x = (self.cursor_x + 1..last)
+                .find(|&col| self.tab_stops[col as usize])
+                .unwrap_or(last);
+        }
+    }
+
+    /// Move the cursor back to the `count`th previous tab stop (CBT)
+    pub fn tab_backward(&mut self, count: u32) {
+        self.wrap_pending = false;
+        for _ in 0..count {
+            self.cursor_x = (0..self.cursor_x)
+                .rev()
+                .find(|&col| self.tab_stops[col as usize])
+                .unwrap_or(0);
+        }
+    }
+
+    /// Set a tab stop at the cursor column (HTS, `ESC H`)
+    pub fn set_tab_stop(&mut self) {
+        if let Some(stop) = self.tab_stops.get_mut(self.cursor_x as usize) {
+            *stop = true;
+        }
+    }
+
+    /// Clear tab stops (TBC, `CSI Ps g`): 0 clears the stop at the cursor, 3 clears all
+    pub fn clear_tab_stops(&mut self, mode: u16) {
+        match mode {
+            0 => {
+                if let Some(stop) = self.tab_stops.get_mut(self.cursor_x as usize) {
+                    *stop = false;
+                }
+            }
+            3 => self.tab_stops.fill(false),
+            _ => {}
+        }
+    }
+
+    /// Replace all tab stops with stops every `interval` columns (default 8)
+    pub fn set_tab_interval(&mut self, interval: u32) {
+        self.tab_stops = default_tab_stops(self.width, interval);
+    }
+
+    /// Columns that currently have a tab stop
+    pub fn tab_stops(&self) -> Vec<u32> {
+        (0..self.width).filter(|&col| self.tab_stops[col as usize]).collect()
+    }
+
     /// Move cursor to position
     pub fn move_cursor(&mut self, x: u32, y: u32) {
         self.cursor_x = x.min(self.width.saturating_sub(1));
         self.cursor_y = y.min(self.height.saturating_sub(1));
+        self.wrap_pending = false;
     }
 
     /// Set current foreground color
     pub fn set_fg_color(&mut self, color: [u8; 3]) {
         self.current_fg = color;
+        self.current_attrs.explicit_fg = true;
     }
 
     /// Set current background color
     pub fn set_bg_color(&mut self, color: [u8; 3]) {
         self.current_bg = color;
+        self.current_attrs.explicit_bg = true;
     }
 
     /// Reset current attributes to defaults
     pub fn reset_attributes(&mut self) {
         self.current_fg = self.default_fg;
         self.current_bg = self.default_bg;
-        self.current_attrs = CellAttributes::default();
+        // SGR 0 does not end an OSC 8 hyperlink
+        self.current_attrs = CellAttributes {
+            hyperlink: self.current_attrs.hyperlink,
+            ..CellAttributes::default()
+        };
     }
 
     pub fn reset_fg(&mut self) {
         self.current_fg = self.default_fg;
+        self.current_attrs.explicit_fg = false;
     }
 
     pub fn reset_bg(&mut self) {
         self.current_bg = self.default_bg;
+        self.current_attrs.explicit_bg = false;
+    }
+
+    /// Attributes of a cell erased with the current background
+    fn erased_attrs(&self) -> CellAttributes {
+        CellAttributes {
+            explicit_bg: self.current_attrs.explicit_bg,
+            ..CellAttributes::default()
+        }
     }
 
     /// Set bold attribute
@@ -517,14 +1386,46 @@ impl Vt100Terminal {
         self.current_attrs.bold = enabled;
     }
 
-    /// Set underline attribute
-    pub fn set_underline(&mut self, enabled: bool) {
-        self.current_attrs.underline = enabled;
+    /// Set dim (faint) attribute
+    pub fn set_dim(&mut self, enabled: bool) {
+        self.current_attrs.dim = enabled;
     }
 
-    /// Set inverse (reverse video) attribute
-    pub fn set_inverse(&mut self, enabled: bool) {
-        self.current_attrs.inverse = enabled;
+    /// Set italic attribute
+    pub fn set_italic(&mut self, enabled: bool) {
+        self.current_attrs.italic = enabled;
+    }
+
+    /// Set underline attribute (single style when enabled)
+    pub fn set_underline(&mut self, enabled: bool) {
+        let style = if enabled { UnderlineStyle::Single } else { UnderlineStyle::None };
+        self.set_underline_style(style);
+    }
+
+    /// Set underline style (`UnderlineStyle::None` disables the underline)
+    pub fn set_underline_style(&mut self, style: UnderlineStyle) {
+        self.current_attrs.underline = style != UnderlineStyle::None;
+        self.current_attrs.underline_style = style;
+    }
+
+    /// Set underline color (`None` falls back to the foreground color)
+    pub fn set_underline_color(&mut self, color: Option<[u8; 3]>) {
+        self.current_attrs.underline_color = color;
+    }
+
+    /// Set blink attribute
+    pub fn set_blink(&mut self, enabled: bool) {
+        self.current_attrs.blink = enabled;
+    }
+
+    /// Set inverse (reverse video) attribute
+    pub fn set_inverse(&mut self, enabled: bool) {
+        self.current_attrs.inverse = enabled;
+    }
+
+    /// Set strikethrough (crossed-out) attribute
+    pub fn set_strikethrough(&mut self, enabled: bool) {
+        self.current_attrs.strikethrough = enabled;
     }
 
     /// Enter alternate screen buffer (used by vim, less, htop, etc.)
@@ -539,6 +1440,8 @@ impl Vt100Terminal {
             fg_colors: self.fg_colors.clone(),
             bg_colors: self.bg_colors.clone(),
             attributes: self.attributes.clone(),
+            line_sizes: self.line_sizes.clone(),
+            images: std::mem::take(&mut self.images),
             cursor_x: self.cursor_x,
             cursor_y: self.cursor_y,
         };
@@ -560,9 +1463,12 @@ impl Vt100Terminal {
             self.fg_colors = saved.fg_colors;
             self.bg_colors = saved.bg_colors;
             self.attributes = saved.attributes;
+            self.line_sizes = saved.line_sizes;
+            self.images = saved.images;
             self.cursor_x = saved.cursor_x;
             self.cursor_y = saved.cursor_y;
         }
+        self.wrap_pending = false;
         self.in_alternate_screen = false;
     }
 
@@ -571,6 +1477,55 @@ impl Vt100Terminal {
         self.in_alternate_screen
     }
 
+    /// Resize the screen, clipping content (no reflow).
+    ///
+    /// Columns are cut or padded on the right. When rows shrink, rows are
+    /// dropped from the top if needed to keep the cursor on screen (as xterm
+    /// does), otherwise from the bottom. A saved main screen is resized the
+    /// same way.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let width = width.max(1);
+        let height = height.max(1);
+        let drop_top = (self.cursor_y + 1).saturating_sub(height) as usize;
+        let (fg, bg) = (self.default_fg, self.default_bg);
+
+        resize_grid(&mut self.buffer, width, height, drop_top, ' ');
+        resize_grid(&mut self.fg_colors, width, height, drop_top, fg);
+        resize_grid(&mut self.bg_colors, width, height, drop_top, bg);
+        resize_grid(&mut self.attributes, width, height, drop_top, CellAttributes::default());
+        self.line_sizes.drain(..drop_top.min(self.line_sizes.len()));
+        self.line_sizes.resize(height as usize, LineSize::Single);
+        shift_images(&mut self.images, drop_top);
+        self.cursor_y -= drop_top as u32;
+
+        if let Some(saved) = self.alternate_screen.as_mut() {
+            let drop_top = (saved.cursor_y + 1).saturating_sub(height) as usize;
+            resize_grid(&mut saved.buffer, width, height, drop_top, ' ');
+            resize_grid(&mut saved.fg_colors, width, height, drop_top, fg);
+            resize_grid(&mut saved.bg_colors, width, height, drop_top, bg);
+            resize_grid(&mut saved.attributes, width, height, drop_top, CellAttributes::default());
+            saved.line_sizes.drain(..drop_top.min(saved.line_sizes.len()));
+            saved.line_sizes.resize(height as usize, LineSize::Single);
+            shift_images(&mut saved.images, drop_top);
+            saved.cursor_x = saved.cursor_x.min(width - 1);
+            saved.cursor_y -= drop_top as u32;
+        }
+
+        // Keep existing stops and extend with the default interval
+        let mut tab_stops = default_tab_stops(width, DEFAULT_TAB_INTERVAL);
+        let kept = self.tab_stops.len().min(width as usize);
+        tab_stops[..kept].copy_from_slice(&self.tab_stops[..kept]);
+        self.tab_stops = tab_stops;
+
+        self.width = width;
+        self.height = height;
+        self.cursor_x = self.cursor_x.min(width - 1);
+        self.wrap_pending = false;
+        if let Some((x, y)) = self.saved_cursor {
+            self.saved_cursor = Some((x.min(width - 1), y.min(height - 1)));
+        }
+    }
+
     /// Clear from cursor to end of line
     pub fn clear_line_from_cursor(&mut self) {
         if self.cursor_y >= self.height {
@@ -582,7 +1537,7 @@ impl Vt100Terminal {
             self.buffer[row][idx] = ' ';
             self.fg_colors[row][idx] = self.current_fg;
             self.bg_colors[row][idx] = self.current_bg;
-            self.attributes[row][idx] = CellAttributes::default();
+            self.attributes[row][idx] = self.erased_attrs();
         }
     }
 
@@ -597,17 +1552,44 @@ impl Vt100Terminal {
                 self.buffer[row][col] = ' ';
                 self.fg_colors[row][col] = self.current_fg;
                 self.bg_colors[row][col] = self.current_bg;
-                self.attributes[row][col] = CellAttributes::default();
+                self.attributes[row][col] = self.erased_attrs();
+            }
+            if start_col == 0 {
+                self.line_sizes[y as usize] = LineSize::Single;
             }
         }
     }
 
+    /// Size of a line (DECDHL/DECDWL)
+    pub fn line_size(&self, row: u32) -> LineSize {
+        self.line_sizes.get(row as usize).copied().unwrap_or_default()
+    }
+
+    /// Set the size of the cursor line; double-size lines hold half as many columns
+    pub fn set_line_size(&mut self, size: LineSize) {
+        if let Some(line) = self.line_sizes.get_mut(self.cursor_y as usize) {
+            *line = size;
+            self.cursor_x = self.cursor_x.min(self.line_width(self.cursor_y) - 1);
+            self.wrap_pending = false;
+        }
+    }
+
+    /// Number of columns a line can hold
+    fn line_width(&self, row: u32) -> u32 {
+        if self.line_size(row).is_double_width() {
+            (self.width / 2).max(1)
+        } else {
+            self.width
+        }
+    }
+
     /// Move cursor relative
     pub fn move_cursor_rel(&mut self, dx: i32, dy: i32) {
         let new_x = (self.cursor_x as i32 + dx).clamp(0, self.width.saturating_sub(1) as i32);
         let new_y = (self.cursor_y as i32 + dy).clamp(0, self.height.saturating_sub(1) as i32);
         self.cursor_x = new_x as u32;
         self.cursor_y = new_y as u32;
+        self.wrap_pending = false;
     }
 
     /// Save cursor position
@@ -621,28 +1603,299 @@ impl Vt100Terminal {
             self.cursor_x = x.min(self.width.saturating_sub(1));
             self.cursor_y = y.min(self.height.saturating_sub(1));
         }
+        self.wrap_pending = false;
+    }
+
+    /// Answer a Device Status Report (`CSI 5n`, `CSI 6n`, `CSI ?6n`)
+    pub fn device_status_report(&mut self, mode: u16, private: bool) {
+        let reply = match (mode, private) {
+            (5, false) => "\x1b[0n".to_string(),
+            (6, false) => format!("\x1b[{};{}R", self.cursor_y + 1, self.cursor_x + 1),
+            (6, true) => format!("\x1b[?{};{}R", self.cursor_y + 1, self.cursor_x + 1),
+            _ => return,
+        };
+        self.pending_replies.extend_from_slice(reply.as_bytes());
+    }
+
+    /// Answer Primary Device Attributes (`CSI c`)
+    pub fn primary_device_attributes(&mut self) {
+        let reply = format!("\x1b[?{}c", self.identity.da1);
+        self.pending_replies.extend_from_slice(reply.as_bytes());
+    }
+
+    /// Answer Secondary Device Attributes (`CSI > c`)
+    pub fn secondary_device_attributes(&mut self) {
+        let reply = format!("\x1b[>{};{};0c", self.identity.da2_type, self.identity.version);
+        self.pending_replies.extend_from_slice(reply.as_bytes());
+    }
+
+    /// Answer a terminal name and version query (XTVERSION, `CSI > q`)
+    pub fn report_version(&mut self) {
+        let reply = format!("\x1bP>|{}({})\x1b\\", self.identity.name, self.identity.version);
+        self.pending_replies.extend_from_slice(reply.as_bytes());
+    }
+
+    /// Take the replies queued for the application (to be written to the PTY)
+    pub fn take_replies(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.pending_replies)
+    }
+
+    /// Start (`Some(uri)`) or end (`None` / empty URI) an OSC 8 hyperlink
+    pub fn set_hyperlink(&mut self, uri: Option<&str>) {
+        self.current_attrs.hyperlink = match uri {
+            Some(uri) if !uri.is_empty() => {
+                let index = match self.hyperlink_uris.iter().position(|u| u == uri) {
+                    Some(index) => index,
+                    None => {
+                        self.hyperlink_uris.push(uri.to_string());
+                        self.hyperlink_uris.len() - 1
+                    }
+                };
+                Some(index as u32)
+            }
+            _ => None,
+        };
+    }
+
+    /// URI of a hyperlink index stored in [`CellAttributes::hyperlink`]
+    pub fn hyperlink_uri(&self, index: u32) -> Option<&str> {
+        self.hyperlink_uris.get(index as usize).map(String::as_str)
+    }
+
+    /// Hyperlinks currently on screen, one entry per contiguous run of cells
+    pub fn hyperlinks(&self) -> Vec<Hyperlink> {
+        let mut links = Vec::new();
+        for (row, attrs) in self.attributes.iter().enumerate() {
+            let mut col = 0;
+            while col < attrs.len() {
+                let Some(index) = attrs[col].hyperlink else {
+                    col += 1;
+                    continue;
+                };
+                let start = col;
+                while col < attrs.len() && attrs[col].hyperlink == Some(index) {
+                    col += 1;
+                }
+                links.push(Hyperlink {
+                    uri: self.hyperlink_uris[index as usize].clone(),
+                    text: self.buffer[row][start..col].iter().collect::<String>().trim_end().to_string(),
+                    row: row as u32,
+                    start_col: start as u32,
+                    end_col: (col - 1) as u32,
+                });
+            }
+        }
+        links
+    }
+
+    /// Show or hide the cursor (DECTCEM)
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        if self.cursor_visible != visible {
+            self.cursor_changes.push(if visible { CursorChange::Shown } else { CursorChange::Hidden });
+        }
+        self.cursor_visible = visible;
+    }
+
+    /// Set the cursor style from a DECSCUSR (`CSI Ps SP q`) parameter.
+    ///
+    /// 0/1 blinking block, 2 steady block, 3 blinking underline,
+    /// 4 steady underline, 5 blinking bar, 6 steady bar.
+    pub fn set_cursor_style(&mut self, style: u16) {
+        let shape = match style {
+            0..=2 => CursorShape::Block,
+            3 | 4 => CursorShape::Underline,
+            5 | 6 => CursorShape::Bar,
+            _ => return,
+        };
+        let blinking = style == 0 || style % 2 == 1;
+        if (shape, blinking) != (self.cursor_shape, self.cursor_blinking) {
+            self.cursor_changes.push(CursorChange::Style { shape, blinking });
+        }
+        self.cursor_shape = shape;
+        self.cursor_blinking = blinking;
+    }
+
+    /// Current cursor visibility and style
+    pub fn cursor_state(&self) -> CursorState {
+        CursorState {
+            visible: self.cursor_visible,
+            shape: self.cursor_shape,
+            blinking: self.cursor_blinking,
+        }
+    }
+
+    /// Take the cursor changes received since the last call
+    pub fn take_cursor_changes(&mut self) -> Vec<CursorChange> {
+        std::mem::take(&mut self.cursor_changes)
+    }
+
+    /// Ring the audible bell (BEL)
+    pub fn bell(&mut self) {
+        self.bell_count += 1;
+    }
+
+    /// Set or reset reverse-screen mode (DECSCNM, `CSI ?5h` / `CSI ?5l`).
+    ///
+    /// Setting and then resetting it with nothing drawn or captured in between
+    /// is counted as a visual bell flash; reverse video in real use is not.
+    pub fn set_reverse_screen(&mut self, enabled: bool) {
+        self.reverse_screen = enabled;
+        if enabled {
+            self.start_flash();
+        } else {
+            self.end_flash();
+        }
+    }
+
+    /// Enable or disable a mouse tracking mode (9, 1000, 1002 or 1003).
+    ///
+    /// Enabling one replaces any other; disabling the active one turns reporting off.
+    pub fn set_mouse_tracking(&mut self, mode: u16, enabled: bool) {
+        let Some(tracking) = MouseTracking::from_mode(mode) else { return };
+        if enabled {
+            self.mouse_tracking = tracking;
+        } else if self.mouse_tracking == tracking {
+            self.mouse_tracking = MouseTracking::Off;
+        }
+    }
+
+    /// Select SGR mouse encoding (mode 1006)
+    pub fn set_sgr_mouse(&mut self, enabled: bool) {
+        self.sgr_mouse = enabled;
+    }
+
+    /// Mouse events the application asked for
+    pub fn mouse_tracking(&self) -> MouseTracking {
+        self.mouse_tracking
+    }
+
+    /// Bytes reporting a mouse input to the application, empty if it has not enabled mouse reporting
+    pub fn mouse_report(&self, input: MouseInput) -> Vec<u8> {
+        encode_mouse(input, self.mouse_tracking, self.sgr_mouse)
+    }
+
+    /// Push kitty keyboard protocol flags (`CSI > flags u`)
+    pub fn push_key_flags(&mut self, flags: u16) {
+        // Bound the stack as kitty does, dropping the oldest entry
+        if self.key_flags.len() >= MAX_KEY_FLAGS_DEPTH {
+            self.key_flags.remove(0);
+        }
+        self.key_flags.push((flags & 0x1f) as u8);
+    }
+
+    /// Pop `count` entries of kitty keyboard flags (`CSI < count u`)
+    pub fn pop_key_flags(&mut self, count: u16) {
+        let keep = self.key_flags.len().saturating_sub(usize::from(count.max(1)));
+        self.key_flags.truncate(keep);
+    }
+
+    /// Change the current kitty keyboard flags (`CSI = flags ; mode u`):
+    /// mode 1 replaces them, 2 sets the given bits and 3 clears them
+    pub fn set_key_flags(&mut self, flags: u16, mode: u16) {
+        let flags = (flags & 0x1f) as u8;
+        let current = self.key_flags();
+        let updated = match mode {
+            1 => flags,
+            2 => current | flags,
+            3 => current & !flags,
+            _ => return,
+        };
+        match self.key_flags.last_mut() {
+            Some(top) => *top = updated,
+            None => self.key_flags.push(updated),
+        }
+    }
+
+    /// Answer a kitty keyboard flags query (`CSI ? u`)
+    pub fn report_key_flags(&mut self) {
+        let reply = format!("\x1b[?{}u", self.key_flags());
+        self.pending_replies.extend_from_slice(reply.as_bytes());
+    }
+
+    /// Kitty keyboard protocol flags currently enabled by the application (0 for legacy keys)
+    pub fn key_flags(&self) -> u8 {
+        self.key_flags.last().copied().unwrap_or(0)
+    }
+
+    /// Whether reverse-screen mode (DECSCNM) is set
+    pub fn reverse_screen(&self) -> bool {
+        self.reverse_screen
+    }
+
+    /// Reverse-screen mode set (`CSI ?5h`), the first half of a visual bell flash
+    pub fn start_flash(&mut self) {
+        self.flash_pending = true;
+    }
+
+    /// Reverse-screen mode reset (`CSI ?5l`); completes a visual bell flash
+    pub fn end_flash(&mut self) {
+        if self.flash_pending {
+            self.flash_pending = false;
+            self.visual_bell_count += 1;
+        }
+    }
+
+    /// Reverse-screen mode outlasted a draw or capture, so its reset is not a flash
+    pub fn cancel_flash(&mut self) {
+        self.flash_pending = false;
     }
 
     /// Handle backspace
     pub fn backspace(&mut self) {
+        self.wrap_pending = false;
         if self.cursor_x > 0 {
             self.cursor_x -= 1;
         }
     }
 
+    /// Whether any content would differ between blink phases
+    pub fn has_blinking_content(&self, show_cursor: bool) -> bool {
+        (show_cursor && self.cursor_visible && self.cursor_blinking)
+            || self.attributes.iter().flatten().any(|attrs| attrs.blink)
+    }
+
     /// Render the terminal to an image buffer
     pub fn render_to_image(&self) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        self.render_to_image_with(&RenderOptions::default())
+    }
+
+    /// Render the terminal to an image buffer with the given options
+    pub fn render_to_image_with(&self, options: &RenderOptions) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        // A crop renders only its rows, shifted to the top of the image
+        let rows = options.crop.map_or(0..self.height, |crop| crop.rows(self.height));
         let img_width = self.width * FONT_WIDTH * PIXEL_SCALE;
-        let img_height = self.height * FONT_HEIGHT * PIXEL_SCALE;
+        let img_height = rows.len() as u32 * FONT_HEIGHT * PIXEL_SCALE;
 
         let mut img = ImageBuffer::new(img_width, img_height);
 
-        for y in 0..self.height {
-            for x in 0..self.width {
+        // Reverse-screen mode swaps the default colors; explicit colors are kept
+        let (default_fg, default_bg) = (self.default_fg, self.default_bg);
+        let screen_color = |color: [u8; 3], explicit: bool| match self.reverse_screen && !explicit {
+            true if color == default_fg => default_bg,
+            true if color == default_bg => default_fg,
+            _ => color,
+        };
+
+        for y in rows.clone() {
+            let img_row = y - rows.start;
+            let line_size = self.line_size(y);
+            // Double-size lines draw each cell across two columns
+            let cell_scale = if line_size.is_double_width() { 2 } else { 1 };
+            let line_width = self.line_width(y);
+            if line_width * cell_scale < self.width {
+                // Odd width: the column left over after the doubled cells stays blank
+                for img_y in img_row * CELL_HEIGHT..(img_row + 1) * CELL_HEIGHT {
+                    for img_x in line_width * cell_scale * CELL_WIDTH..img_width {
+                        img.put_pixel(img_x, img_y, Rgb(screen_color(self.default_bg, false)));
+                    }
+                }
+            }
+
+            for x in 0..line_width {
                 let ch = self.buffer[y as usize][x as usize];
-                let mut fg = self.fg_colors[y as usize][x as usize];
-                let mut bg = self.bg_colors[y as usize][x as usize];
                 let attrs = self.attributes[y as usize][x as usize];
+                let mut fg = screen_color(self.fg_colors[y as usize][x as usize], attrs.explicit_fg);
+                let mut bg = screen_color(self.bg_colors[y as usize][x as usize], attrs.explicit_bg);
 
                 // Handle inverse (reverse video)
                 if attrs.inverse {
@@ -654,26 +1907,71 @@ impl Vt100Terminal {
                     fg = brighten_color(fg);
                 }
 
-                let bitmap = get_char_bitmap(ch);
+                // Handle dim (faint) by darkening the foreground color
+                if attrs.dim {
+                    fg = dim_color(fg);
+                }
+
+                // Without an explicit phase a still image cannot show blinking,
+                // so highlight blinking cells with a brightened background instead
+                if attrs.blink && options.blink_phase.is_none() {
+                    bg = brighten_color(bg);
+                }
+                let blink_hidden = options.blink_phase == Some(BlinkPhase::Hidden);
+
+                let cursor_shape = (options.show_cursor
+                    && self.cursor_visible
+                    && !(self.cursor_blinking && blink_hidden)
+                    && x == self.cursor_x
+                    && y == self.cursor_y)
+                    .then_some(self.cursor_shape);
+                if cursor_shape == Some(CursorShape::Block) {
+                    std::mem::swap(&mut fg, &mut bg);
+                }
+
+                let bitmap = get_char_bitmap(ch, &options.glyph_fallback);
 
                 for py in 0..FONT_HEIGHT {
-                    let row = bitmap[py as usize];
-                    for px in 0..FONT_WIDTH {
+                    let src_py = line_size.source_row(py);
+                    let mut row = bitmap[src_py as usize];
+                    // Slant italic glyphs by shifting upper rows to the right
+                    if attrs.italic {
+                        row <<= (FONT_HEIGHT - 1 - src_py) / 6;
+                    }
+                    for px in 0..FONT_WIDTH * cell_scale {
+                        let src_px = px / cell_scale;
                         // font8x8 stores the leftmost pixel in the least significant bit
-                        let bit = (row >> px) & 1;
+                        let bit = (row >> src_px) & 1;
                         let mut color = if bit == 1 { fg } else { bg };
 
-                        // Draw underline on the last row of the character cell
-                        if attrs.underline && py >= FONT_HEIGHT - 2 {
+                        // Draw underline near the bottom of the character cell
+                        if attrs.underline && attrs.underline_style.covers(src_px, src_py) {
+                            color = attrs.underline_color.unwrap_or(fg);
+                        }
+
+                        // Draw strikethrough across the middle of the cell
+                        if attrs.strikethrough && (src_py == FONT_HEIGHT / 2 - 1 || src_py == FONT_HEIGHT / 2) {
                             color = fg;
                         }
 
+                        // Blank out blinking text in the hidden phase
+                        if attrs.blink && blink_hidden {
+                            color = bg;
+                        }
+
+                        // Draw thin cursor shapes over the glyph
+                        match cursor_shape {
+                            Some(CursorShape::Underline) if py >= FONT_HEIGHT - 2 => color = fg,
+                            Some(CursorShape::Bar) if px == 0 => color = fg,
+                            _ => {}
+                        }
+
                         for sy in 0..PIXEL_SCALE {
                             for sx in 0..PIXEL_SCALE {
                                 let img_x =
-                                    x * FONT_WIDTH * PIXEL_SCALE + px * PIXEL_SCALE + sx;
+                                    x * cell_scale * FONT_WIDTH * PIXEL_SCALE + px * PIXEL_SCALE + sx;
                                 let img_y =
-                                    y * FONT_HEIGHT * PIXEL_SCALE + py * PIXEL_SCALE + sy;
+                                    img_row * FONT_HEIGHT * PIXEL_SCALE + py * PIXEL_SCALE + sy;
                                 if img_x < img_width && img_y < img_height {
                                     img.put_pixel(img_x, img_y, Rgb(color));
                                 }
@@ -684,9 +1982,41 @@ impl Vt100Terminal {
             }
         }
 
+        composite(&mut img, &self.images, i64::from(rows.start));
+
+        // A rectangle also drops the columns outside it
+        let cols = options.crop.map_or(0..self.width, |crop| crop.cols(self.width));
+        if cols != (0..self.width) {
+            let width = cols.len() as u32 * CELL_WIDTH;
+            return image::imageops::crop_imm(&img, cols.start * CELL_WIDTH, 0, width, img_height).to_image();
+        }
         img
     }
 
+    /// Characters on screen with no glyph in the built-in fonts or a fallback font, sorted
+    pub fn unrenderable_chars(&self, fallback: &[GlyphFallback]) -> Vec<char> {
+        let mut chars: Vec<char> = self
+            .buffer
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|&ch| builtin_bitmap(ch).is_none() && !font_has_glyph(ch, fallback))
+            .collect();
+        chars.sort_unstable();
+        chars.dedup();
+        chars
+    }
+
+    /// The screen as an HTML `<pre>` element with styled spans (see [`super::export::to_html`])
+    pub fn to_html(&self) -> String {
+        super::export::to_html(self)
+    }
+
+    /// Copy of every cell and the cursor, for assertions and JSON export
+    pub fn to_grid(&self) -> TerminalGrid {
+        TerminalGrid::from_terminal(self)
+    }
+
     /// Dump the buffer as visible text (for debugging)
     pub fn to_text(&self) -> String {
         let mut out = String::with_capacity((self.width as usize + 1) * self.height as usize);
@@ -704,6 +2034,12 @@ impl Vt100Terminal {
 pub struct Vt100Parser {
     terminal: Vt100Terminal,
     parser: AnsiParser,
+    /// APC strings are dropped by the VT parser, so kitty graphics are found here
+    apc: ApcScanner,
+    /// Receives everything processed, plus markers and resizes
+    cast: Option<CastRecorder>,
+    /// Everything processed, when kept for [`StateCaptureResult::ansi`]
+    raw: Option<Vec<u8>>,
 }
 
 impl Vt100Parser {
@@ -711,15 +2047,50 @@ impl Vt100Parser {
         Self {
             terminal: Vt100Terminal::new(width, height),
             parser: AnsiParser::new(),
+            apc: ApcScanner::default(),
+            cast: None,
+            raw: None,
         }
     }
 
+    /// Keep the bytes processed from now on (see [`Vt100Parser::raw_output`])
+    pub fn keep_raw_output(&mut self) {
+        self.raw.get_or_insert_with(Vec::new);
+    }
+
+    /// Bytes processed since [`Vt100Parser::keep_raw_output`], if it was called
+    pub fn raw_output(&self) -> Option<&[u8]> {
+        self.raw.as_deref()
+    }
+
+    /// Start recording processed output to `recorder`, at the current size
+    pub fn record_cast(&mut self, recorder: CastRecorder) {
+        recorder.start(self.terminal.width as u16, self.terminal.height as u16);
+        self.cast = Some(recorder);
+    }
+
     /// Process a byte of input
     pub fn process_byte(&mut self, byte: u8) {
         let mut performer = TerminalPerformer {
             terminal: &mut self.terminal,
         };
         self.parser.advance(&mut performer, byte);
+        if let Some(body) = self.apc.advance(byte) {
+            self.terminal.kitty_command(&body);
+        }
+    }
+
+    /// Process a chunk of input
+    pub fn process(&mut self, bytes: &[u8]) {
+        if let Some(cast) = &self.cast {
+            cast.output(bytes);
+        }
+        if let Some(raw) = &mut self.raw {
+            raw.extend_from_slice(bytes);
+        }
+        for &byte in bytes {
+            self.process_byte(byte);
+        }
     }
 
     /// Get the current terminal state
@@ -742,7 +2113,7 @@ pub fn capture_cli_screenshot_pty(
 ) -> super::SnapshotResult<super::Snapshot> {
     use super::utils::{
         create_base_metadata, generate_filename, generate_timestamp, write_description,
-        write_manifest,
+        write_manifest, write_screen_text,
     };
     use super::{Snapshot, SnapshotError};
 
@@ -804,32 +2175,61 @@ pub fn capture_cli_screenshot_pty(
 
     let rx = spawn_reader(reader);
 
-    wait_for_initial_render(&rx, &mut parser);
+    wait_for_initial_render(&rx, &mut parser, &mut writer);
 
-    for input in inputs {
-        match input {
-            crate::harness::types::InputAction::SendString(text) => {
-                writer.write_all(text.as_bytes()).map_err(|e| {
-                    SnapshotError::Capture(format!("Failed to send text '{}': {}", text, e))
-                })?;
-                writer
-                    .write_all(&[b'\r'])
-                    .map_err(|e| SnapshotError::Capture(format!("Failed to send enter: {}", e)))?;
-                writer.flush().map_err(SnapshotError::Io)?;
-                wait_for_input_render(&rx, &mut parser);
+    let options = RunOptions::default();
+    for (step, input) in inputs.iter().enumerate() {
+        use crate::harness::types::InputAction;
+
+        let (delay, input) = input.delay_and_action();
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
+        let action = match input {
+            InputAction::SendString(text) => PtyInput::Bytes(format!("{}\r", text).into_bytes()),
+            InputAction::SendKey(key) => PtyInput::Bytes(key_to_sequence(key, parser.terminal().key_flags())),
+            InputAction::Raw(bytes) => PtyInput::Bytes(bytes.clone()),
+            InputAction::Resize(cols, rows) => PtyInput::Resize(*cols, *rows),
+            InputAction::MouseClick { col, row, button } => {
+                PtyInput::Mouse(MouseInput::Click { col: *col, row: *row, button: *button })
+            }
+            InputAction::MouseScroll { col, row, direction } => {
+                PtyInput::Mouse(MouseInput::Scroll { col: *col, row: *row, direction: *direction })
             }
-            crate::harness::types::InputAction::SendKey(key) => {
-                let sequence = key_to_sequence(key);
-                writer.write_all(&sequence).map_err(|e| {
-                    SnapshotError::Capture(format!("Failed to send key '{}': {}", key, e))
-                })?;
-                writer.flush().map_err(SnapshotError::Io)?;
-                wait_for_input_render(&rx, &mut parser);
+            InputAction::WaitForText(text) => PtyInput::Wait(WaitCondition::Text(text.clone())),
+            InputAction::WaitStable { window_ms, max_wait_ms } => PtyInput::Wait(WaitCondition::Stable {
+                window: Duration::from_millis(*window_ms),
+                max_wait: max_wait_ms.map(Duration::from_millis),
+            }),
+            InputAction::Signal(sig) => PtyInput::Signal(*sig),
+            InputAction::Eof => PtyInput::Eof,
+            // The pause is part of `delay`
+            InputAction::Sleep(_) | InputAction::Delayed { .. } => PtyInput::Sleep(Duration::ZERO),
+        };
+        let target = InputTarget {
+            master: pair.master.as_ref(),
+            writer: &mut writer,
+            rx: &rx,
+            pid: child.process_id(),
+        };
+        let outcome = match send_input(target, &mut parser, &action, &options) {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(err);
             }
+        };
+        if let (PtyInput::Wait(condition), Some(outcome)) = (&action, outcome)
+            && outcome.timed_out
+        {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(SnapshotError::Capture(timeout_message(step + 1, condition, DEFAULT_WAIT_TIMEOUT, parser.terminal())));
         }
     }
 
-    wait_for_input_render(&rx, &mut parser);
+    wait_for_input_render(&rx, &mut parser, &mut writer);
     drop(writer);
     wait_for_process_exit(child.as_mut(), &rx, &mut parser, PROCESS_DRAIN_TIMEOUT);
 
@@ -853,8 +2253,8 @@ pub fn capture_cli_screenshot_pty(
 
     let metadata = if config.include_metadata {
         let meta = create_base_metadata(
-            u32::from(terminal_width) * CELL_WIDTH,
-            u32::from(terminal_height) * CELL_HEIGHT,
+            img.width(),
+            img.height(),
             "cli_pty",
             &timestamp,
         );
@@ -866,6 +2266,7 @@ pub fn capture_cli_screenshot_pty(
     let snapshot = Snapshot::new(image_path.clone(), "cli_pty".to_string(), metadata);
     write_manifest(&snapshot, config)?;
     write_description(&snapshot, config)?;
+    write_screen_text(&snapshot, config, &parser.terminal().to_text())?;
 
     Ok(snapshot)
 }
@@ -883,14 +2284,77 @@ pub struct StateCaptureResult {
     pub width: u32,
     /// Image height
     pub height: u32,
+    /// Audible bells (BEL) received since the previous state
+    pub bell_count: u32,
+    /// Visual bells (reverse-screen flashes) received since the previous state
+    pub visual_bell_count: u32,
+    /// Cursor visibility and style when the state was captured
+    pub cursor: CursorState,
+    /// Cursor position when the state was captured, as (row, column), 0-based
+    pub cursor_position: (u32, u32),
+    /// Cursor visibility/style changes received since the previous state
+    pub cursor_changes: Vec<CursorChange>,
+    /// Both blink phases, when requested and the state has blinking content
+    pub blink_frames: Option<BlinkFrames>,
+    /// OSC 8 hyperlinks on screen
+    pub hyperlinks: Vec<Hyperlink>,
+    /// Screen text, one line per row (see [`Vt100Terminal::to_text`])
+    pub text: String,
+    /// Screen as styled HTML (see [`Vt100Terminal::to_html`]), if [`RunOptions::capture_html`] is set
+    pub html: Option<String>,
+    /// Characters on screen without a real glyph (see [`Vt100Terminal::unrenderable_chars`])
+    pub unrenderable: Vec<char>,
+    /// How the wait input that led to this state ended, if it was one
+    pub wait: Option<WaitOutcome>,
+    /// Exit code, if the application had exited when the state was captured
+    pub exit_code: Option<u32>,
+    /// Bytes received from the application since the previous state, if
+    /// [`RunOptions::capture_ansi`] is set; `cat` them in a terminal to replay the change
+    pub ansi: Option<Vec<u8>>,
+    /// Characters, colors and attributes of every cell, if [`RunOptions::capture_grid`] is set;
+    /// assert on these instead of reading the PNG
+    pub grid: Option<TerminalGrid>,
+}
+
+impl StateCaptureResult {
+    /// The same state with the screenshot cropped to a rectangle of its cells.
+    ///
+    /// Cells count from the top-left of the screenshot, which is already
+    /// cropped if the run had a crop. Text, HTML, the grid and other
+    /// per-state data still cover the whole screen.
+    pub fn crop_cells(&self, rect: CellRect) -> super::SnapshotResult<StateCaptureResult> {
+        let (image_data, width, height) = crop_png_cells(&self.image_data, rect)?;
+        Ok(StateCaptureResult {
+            image_data,
+            width,
+            height,
+            blink_frames: None,
+            ..self.clone()
+        })
+    }
+
+    /// How the screenshot of a later state differs from this one (see [`diff_images`](super::diff::diff_images))
+    pub fn pixel_diff(&self, later: &StateCaptureResult) -> super::SnapshotResult<PixelDiff> {
+        diff_images(&self.image_data, &later.image_data, 0)
+    }
+}
+
+/// Paired renders of a state's blink phases
+#[derive(Debug, Clone)]
+pub struct BlinkFrames {
+    /// PNG data with blinking content drawn
+    pub phase_a: Vec<u8>,
+    /// PNG data with blinking content blanked out
+    pub phase_b: Vec<u8>,
 }
 
 /// Terminal size preset for common configurations
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum TerminalSize {
     /// 80x24 - Classic VT100/minimal terminal
     Compact,
     /// 120x40 - Default, typical modern terminal
+    #[default]
     Standard,
     /// 160x50 - Large widescreen terminal
     Large,
@@ -913,6 +2377,7 @@ impl TerminalSize {
     }
 
     /// Parse from string (e.g., "80x24", "compact", "standard")
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "compact" | "small" | "minimal" => Some(TerminalSize::Compact),
@@ -944,200 +2409,707 @@ impl TerminalSize {
     }
 }
 
-impl Default for TerminalSize {
-    fn default() -> Self {
-        TerminalSize::Standard
+/// Parse a `resize:COLSxROWS` input token (a size preset name is also accepted).
+pub fn parse_resize(input: &str) -> Option<TerminalSize> {
+    let (prefix, size) = input.trim().split_once(':')?;
+    if !prefix.eq_ignore_ascii_case("resize") {
+        return None;
     }
+    TerminalSize::from_str(size.trim()).filter(|size| {
+        let (cols, rows) = size.dimensions();
+        cols > 0 && rows > 0
+    })
 }
 
-/// Parse an input string into bytes to send to the PTY.
-fn parse_input(input: &str) -> Vec<u8> {
-    let input_lower = input.to_lowercase();
-    let input_lower = input_lower.trim();
-
-    match input_lower {
-        // Arrow keys
-        "up" => b"\x1b[A".to_vec(),
-        "down" => b"\x1b[B".to_vec(),
-        "right" => b"\x1b[C".to_vec(),
-        "left" => b"\x1b[D".to_vec(),
-        // Navigation keys
-        "home" => b"\x1b[H".to_vec(),
-        "end" => b"\x1b[F".to_vec(),
-        "pageup" | "page_up" | "pgup" => b"\x1b[5~".to_vec(),
-        "pagedown" | "page_down" | "pgdn" => b"\x1b[6~".to_vec(),
-        "insert" | "ins" => b"\x1b[2~".to_vec(),
-        "delete" | "del" => b"\x1b[3~".to_vec(),
-        // Common keys
-        "enter" | "return" => vec![b'\r'],
-        "space" => vec![b' '],
-        "tab" => vec![b'\t'],
-        "backspace" | "bs" => vec![0x7f],
-        "escape" | "esc" => vec![0x1b],
-        // Function keys
-        "f1" => b"\x1bOP".to_vec(),
-        "f2" => b"\x1bOQ".to_vec(),
-        "f3" => b"\x1bOR".to_vec(),
-        "f4" => b"\x1bOS".to_vec(),
-        "f5" => b"\x1b[15~".to_vec(),
-        "f6" => b"\x1b[17~".to_vec(),
-        "f7" => b"\x1b[18~".to_vec(),
-        "f8" => b"\x1b[19~".to_vec(),
-        "f9" => b"\x1b[20~".to_vec(),
-        "f10" => b"\x1b[21~".to_vec(),
-        "f11" => b"\x1b[23~".to_vec(),
-        "f12" => b"\x1b[24~".to_vec(),
-        // Ctrl combinations
-        s if s.starts_with("ctrl+") || s.starts_with("ctrl-") || s.starts_with("c-") => {
-            let key = s.split(&['+', '-'][..]).last().unwrap_or("");
-            if key.len() == 1 {
-                let ch = key.chars().next().unwrap().to_ascii_lowercase();
-                if ch.is_ascii_lowercase() {
-                    vec![(ch as u8) - b'a' + 1]
-                } else {
-                    input.as_bytes().to_vec()
-                }
-            } else if key == "space" {
-                vec![0x00]
-            } else {
-                input.as_bytes().to_vec()
-            }
-        }
-        // Alt combinations (send ESC prefix)
-        s if s.starts_with("alt+") || s.starts_with("alt-") || s.starts_with("m-") => {
-            let key = s.split(&['+', '-'][..]).last().unwrap_or("");
-            let mut result = vec![0x1b];
-            result.extend(key.as_bytes());
-            result
-        }
-        // Single character or literal text
-        _ => input.as_bytes().to_vec(),
+/// Parse a `sleep:` input: a pause in milliseconds (`sleep:1500`, `sleep:1500ms`) or seconds (`sleep:2s`)
+pub fn parse_sleep(input: &str) -> Option<Duration> {
+    let (prefix, value) = input.trim().split_once(':')?;
+    if !prefix.eq_ignore_ascii_case("sleep") {
+        return None;
+    }
+    let value = value.trim().to_lowercase();
+    if let Some(ms) = value.strip_suffix("ms") {
+        ms.trim().parse().ok().map(Duration::from_millis)
+    } else if let Some(secs) = value.strip_suffix('s') {
+        secs.trim().parse::<f64>().ok().filter(|s| s.is_finite() && *s >= 0.0).map(Duration::from_secs_f64)
+    } else {
+        value.parse().ok().map(Duration::from_millis)
     }
 }
 
-/// Run a CLI application with a sequence of inputs, capturing state after each.
-///
-/// Returns N+1 captures for N inputs (initial state + state after each input).
-pub fn run_with_inputs(
-    command: &str,
-    args: &[String],
-    inputs: &[String],
-    input_delay_ms: u64,
-) -> super::SnapshotResult<Vec<StateCaptureResult>> {
-    run_with_inputs_sized(command, args, inputs, input_delay_ms, TerminalSize::default())
+/// Whether an input is the `eof` token, which closes the application's stdin
+pub fn is_eof_input(input: &str) -> bool {
+    input.trim().eq_ignore_ascii_case("eof")
 }
 
-/// Run a CLI application with a sequence of inputs at a specific terminal size.
+/// Stands in for the PTY writer after an `eof` input; every write fails.
 ///
-/// Returns N+1 captures for N inputs (initial state + state after each input).
-pub fn run_with_inputs_sized(
-    command: &str,
-    args: &[String],
-    inputs: &[String],
-    input_delay_ms: u64,
-    size: TerminalSize,
-) -> super::SnapshotResult<Vec<StateCaptureResult>> {
-    use super::SnapshotError;
+/// Dropping the real writer sends the terminal's EOF character, so the
+/// application reads end of input while its output is still captured.
+pub(crate) struct ClosedWriter;
 
-    let (terminal_width, terminal_height) = size.dimensions();
-    let mut parser = Vt100Parser::new(u32::from(terminal_width), u32::from(terminal_height));
+impl Write for ClosedWriter {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(ErrorKind::BrokenPipe, "stdin was closed by an eof input"))
+    }
 
-    let pty_system = native_pty_system();
-    let pair = pty_system
-        .openpty(PtySize {
-            rows: terminal_height,
-            cols: terminal_width,
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Resize the PTY (the kernel delivers SIGWINCH to the application) and the emulated screen
+pub(crate) fn resize_pty(
+    master: &dyn MasterPty,
+    parser: &mut Vt100Parser,
+    cols: u16,
+    rows: u16,
+) -> super::SnapshotResult<()> {
+    master
+        .resize(PtySize {
+            rows,
+            cols,
             pixel_width: 0,
             pixel_height: 0,
         })
-        .map_err(|e| SnapshotError::Capture(format!("Failed to open PTY: {}", e)))?;
-
-    let resolved_command = resolve_binary_path(command);
-    let program = resolved_command
-        .as_ref()
-        .map(|p| p.to_string_lossy().into_owned())
-        .unwrap_or_else(|| command.to_string());
-
-    let mut cmd = CommandBuilder::new(program.clone());
-    cmd.env("TERM", "xterm-256color");
-    cmd.env("COLUMNS", terminal_width.to_string());
-    cmd.env("LINES", terminal_height.to_string());
-    for arg in args {
-        cmd.arg(arg);
+        .map_err(|e| super::SnapshotError::Capture(format!("Failed to resize PTY to {}x{}: {}", cols, rows, e)))?;
+    parser.terminal_mut().resize(u32::from(cols), u32::from(rows));
+    if let Some(cast) = &parser.cast {
+        cast.resize(cols, rows);
     }
+    Ok(())
+}
 
-    let mut child = pair
-        .slave
-        .spawn_command(cmd)
-        .map_err(|e| SnapshotError::Capture(format!("Failed to spawn '{}': {}", program, e)))?;
-    drop(pair.slave);
+/// Parse an input string into bytes to send to the PTY.
+///
+/// `keycode:` inputs name physical keys and are resolved with `layout`;
+/// named keys are encoded for the kitty keyboard `key_flags` (0 for legacy).
+pub(crate) fn parse_input(input: &str, layout: &KeyboardLayout, key_flags: u8) -> Vec<u8> {
+    match parse_explicit_input(input) {
+        Some(ExplicitInput::Text(text) | ExplicitInput::Type(text)) => return text.as_bytes().to_vec(),
+        // An unknown key name sends nothing rather than its spelling
+        Some(ExplicitInput::Key(name)) => {
+            return layout
+                .keycode_bytes(name)
+                .or_else(|| kitty_key_sequence(name, key_flags))
+                .unwrap_or_default();
+        }
+        None => {}
+    }
+    if let Some(Ok(bytes)) = parse_raw_input(input) {
+        return bytes;
+    }
+    if let Some(bytes) = layout.keycode_bytes(input) {
+        return bytes;
+    }
+    // Named keys and modifier combinations, otherwise literal text
+    kitty_key_sequence(input, key_flags).unwrap_or_else(|| input.as_bytes().to_vec())
+}
 
-    if let Err(err) = pair.master.resize(PtySize {
-        rows: terminal_height,
-        cols: terminal_width,
-        pixel_width: 0,
-        pixel_height: 0,
-    }) {
-        eprintln!(
-            "Warning: unable to resize PTY to {}x{}: {}",
-            terminal_width, terminal_height, err
-        );
+/// Write an input to the application.
+///
+/// `type:` inputs are written one character at a time with `type_delay`
+/// between characters, like a person typing; everything else is written at
+/// once as [`parse_input`] encodes it.
+pub(crate) fn write_input<W: Write + ?Sized>(
+    writer: &mut W,
+    input: &str,
+    options: &RunOptions,
+    key_flags: u8,
+) -> std::io::Result<()> {
+    if let Some(ExplicitInput::Type(text)) = parse_explicit_input(input) {
+        let delay = options.type_delay.unwrap_or(DEFAULT_TYPE_DELAY);
+        for (idx, ch) in text.chars().enumerate() {
+            if idx > 0 {
+                thread::sleep(delay);
+            }
+            writer.write_all(ch.encode_utf8(&mut [0; 4]).as_bytes())?;
+            writer.flush()?;
+        }
+        return Ok(());
     }
+    writer.write_all(&parse_input(input, &options.layout, key_flags))?;
+    writer.flush()
+}
 
-    let reader = pair
-        .master
-        .try_clone_reader()
-        .map_err(|e| SnapshotError::Capture(format!("Failed to clone PTY reader: {}", e)))?;
-    let mut writer = pair
-        .master
-        .take_writer()
-        .map_err(|e| SnapshotError::Capture(format!("Failed to take PTY writer: {}", e)))?;
+/// An input as the capture loops act on it
+#[derive(Debug, Clone)]
+pub(crate) enum PtyInput {
+    /// Pause for this long, sending nothing
+    Sleep(Duration),
+    /// Wait until the screen meets a condition
+    Wait(WaitCondition),
+    /// Deliver a signal to the application
+    Signal(Sig),
+    /// Close the application's stdin
+    Eof,
+    /// Resize the PTY to (cols, rows)
+    Resize(u16, u16),
+    /// Report a mouse action, if the application asked for them
+    Mouse(MouseInput),
+    /// Keys and text, encoded by [`write_input`]
+    Keys(String),
+    /// Bytes sent as they are
+    Bytes(Vec<u8>),
+}
 
-    let rx = spawn_reader(reader);
+impl PtyInput {
+    /// Classify an input string.
+    ///
+    /// A malformed `wait-*:` or `signal:` token is an error rather than text
+    /// to type into the application.
+    pub(crate) fn parse(input: &str) -> super::SnapshotResult<Self> {
+        let invalid = |problem: String| super::SnapshotError::Capture(format!("Invalid input '{}': {}", input, problem));
+        if let Some(pause) = parse_sleep(input) {
+            return Ok(Self::Sleep(pause));
+        }
+        if let Some(wait) = parse_wait_input(input) {
+            return wait.map(Self::Wait).map_err(invalid);
+        }
+        if let Some(sig) = parse_signal_input(input) {
+            return sig.map(Self::Signal).map_err(invalid);
+        }
+        if is_eof_input(input) {
+            return Ok(Self::Eof);
+        }
+        if let Some(size) = parse_resize(input) {
+            let (cols, rows) = size.dimensions();
+            return Ok(Self::Resize(cols, rows));
+        }
+        if let Some(mouse) = parse_mouse_input(input) {
+            return Ok(Self::Mouse(mouse));
+        }
+        Ok(Self::Keys(input.to_string()))
+    }
+
+    /// The pause before sending: a sleep replaces `input_delay` and waits need none
+    pub(crate) fn delay(&self, input_delay: Duration) -> Duration {
+        match self {
+            Self::Sleep(pause) => *pause,
+            Self::Wait(_) => Duration::ZERO,
+            _ => input_delay,
+        }
+    }
+}
+
+/// Check every input before the application is started
+pub fn validate_inputs(inputs: &[String]) -> super::SnapshotResult<()> {
+    for input in inputs {
+        PtyInput::parse(input)?;
+    }
+    Ok(())
+}
+
+/// The PTY of a running application that inputs are sent to
+pub(crate) struct InputTarget<'a> {
+    pub(crate) master: &'a dyn MasterPty,
+    pub(crate) writer: &'a mut Box<dyn Write + Send>,
+    pub(crate) rx: &'a Receiver<Vec<u8>>,
+    pub(crate) pid: Option<u32>,
+}
+
+/// Send one input to the application and wait for the screen to settle.
+///
+/// The pause before the input is left to the caller. Returns the outcome of
+/// a wait input; the caller decides what a timeout means for its run.
+pub(crate) fn send_input(
+    target: InputTarget<'_>,
+    parser: &mut Vt100Parser,
+    input: &PtyInput,
+    options: &RunOptions,
+) -> super::SnapshotResult<Option<WaitOutcome>> {
+    use super::SnapshotError;
+
+    let InputTarget { master, writer, rx, pid } = target;
+    let mut outcome = None;
+    match input {
+        // Nothing to send; the state after the pause is captured
+        PtyInput::Sleep(_) => {}
+        PtyInput::Wait(condition) => {
+            let timeout = options.wait_timeout.unwrap_or(DEFAULT_WAIT_TIMEOUT);
+            outcome = Some(wait_for_condition(rx, parser, writer, condition, timeout));
+        }
+        PtyInput::Signal(sig) => {
+            send_signal(pid, *sig).map_err(|e| SnapshotError::Capture(format!("Failed to send {}: {}", sig, e)))?;
+        }
+        PtyInput::Eof => *writer = Box::new(ClosedWriter),
+        PtyInput::Resize(cols, rows) => resize_pty(master, parser, *cols, *rows)?,
+        PtyInput::Mouse(mouse) => {
+            let report = parser.terminal().mouse_report(*mouse);
+            writer
+                .write_all(&report)
+                .and_then(|()| writer.flush())
+                .map_err(|e| SnapshotError::Capture(format!("Failed to send mouse input: {}", e)))?;
+        }
+        PtyInput::Keys(keys) => {
+            let key_flags = options.key_protocol.flags(parser.terminal().key_flags());
+            write_input(writer, keys, options, key_flags)
+                .map_err(|e| SnapshotError::Capture(format!("Failed to send input '{}': {}", keys, e)))?;
+        }
+        PtyInput::Bytes(bytes) => {
+            writer
+                .write_all(bytes)
+                .and_then(|()| writer.flush())
+                .map_err(|e| SnapshotError::Capture(format!("Failed to send input: {}", e)))?;
+        }
+    }
+    wait_for_input_render(rx, parser, writer);
+    Ok(outcome)
+}
+
+/// Pause between characters of a `type:` input unless [`RunOptions::type_delay`] is set
+pub const DEFAULT_TYPE_DELAY: Duration = Duration::from_millis(30);
+
+/// Options for a multi-input capture run
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Rendering options applied to every captured state
+    pub render: RenderOptions,
+    /// Also render both blink phases for states with blinking content
+    pub blink_frames: bool,
+    /// Extra environment variables for the application
+    pub env: Vec<(String, String)>,
+    /// Identity reported to device attribute and version queries
+    pub identity: TerminalIdentity,
+    /// Color theme used for rendering
+    pub theme: Theme,
+    /// Run with a scrubbed environment and no PATH lookup (see [`build_command`])
+    pub hermetic: bool,
+    /// CPU, memory and file-descriptor limits for the application
+    pub limits: ResourceLimits,
+    /// Take the PTY from a warm pool and return it afterwards (see [`PtyPool`])
+    pub pool: Option<PtyPool>,
+    /// Called with each state as soon as it is captured
+    pub on_capture: Option<CaptureObserver>,
+    /// Keyboard layout resolving `keycode:` inputs
+    pub layout: KeyboardLayout,
+    /// Key encoding: follow the application's kitty keyboard requests, or force one
+    pub key_protocol: KeyProtocol,
+    /// Pause between characters of `type:` inputs (default [`DEFAULT_TYPE_DELAY`])
+    pub type_delay: Option<Duration>,
+    /// How long wait inputs wait before timing out (default [`DEFAULT_WAIT_TIMEOUT`])
+    pub wait_timeout: Option<Duration>,
+    /// Crops for individual states, overriding `render.crop` (keyed by state number)
+    pub step_crops: BTreeMap<usize, Crop>,
+    /// End the run at the first state captured after the application exited
+    pub stop_on_exit: bool,
+    /// Record the application's output as an asciicast
+    pub cast: Option<CastRecorder>,
+    /// Keep the raw bytes received between states in [`StateCaptureResult::ansi`]
+    pub capture_ansi: bool,
+    /// Copy the cell grid of each state into [`StateCaptureResult::grid`]
+    pub capture_grid: bool,
+    /// Export each state as HTML into [`StateCaptureResult::html`]
+    pub capture_html: bool,
+}
+
+/// Callback receiving captured states while a run is still in progress
+#[derive(Clone)]
+pub struct CaptureObserver(Arc<dyn Fn(&StateCaptureResult) + Send + Sync>);
+
+impl std::fmt::Debug for CaptureObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CaptureObserver")
+    }
+}
+
+impl RunOptions {
+    /// Set whether the cursor is overlaid on captured screenshots
+    pub fn show_cursor(mut self, show: bool) -> Self {
+        self.render.show_cursor = show;
+        self
+    }
+
+    /// Set the glyph fallback chain for characters the built-in fonts do not cover
+    pub fn glyph_fallback(mut self, chain: Vec<GlyphFallback>) -> Self {
+        self.render.glyph_fallback = chain;
+        self
+    }
+
+    /// Crop every captured screenshot to a band of rows
+    pub fn crop(mut self, crop: Crop) -> Self {
+        self.render.crop = Some(crop);
+        self
+    }
+
+    /// Crop the screenshot of state `step` (0 is the initial state) to a band of rows
+    pub fn crop_step(mut self, step: usize, crop: Crop) -> Self {
+        self.step_crops.insert(step, crop);
+        self
+    }
+
+    /// The crop applied to the screenshot of state `step`, if any
+    pub fn crop_for(&self, step: usize) -> Option<Crop> {
+        self.step_crops.get(&step).copied().or(self.render.crop)
+    }
+
+    /// The part of state `step`'s screen text its screenshot shows
+    pub fn crop_text(&self, step: usize, text: &str) -> String {
+        match self.crop_for(step) {
+            Some(crop) => crop.text(text),
+            None => text.to_string(),
+        }
+    }
+
+    /// Rendering options for state `step`, with its crop applied
+    fn render_for(&self, step: usize) -> RenderOptions {
+        self.render.clone().crop(self.crop_for(step))
+    }
+
+    /// Set whether blink phase A/B renders are produced
+    pub fn blink_frames(mut self, enabled: bool) -> Self {
+        self.blink_frames = enabled;
+        self
+    }
+
+    /// Set the identity reported to DA1/DA2/XTVERSION queries
+    pub fn identity(mut self, identity: TerminalIdentity) -> Self {
+        self.identity = identity;
+        self
+    }
+
+    /// Set the color theme used for rendering
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Set whether the application runs hermetically
+    pub fn hermetic(mut self, hermetic: bool) -> Self {
+        self.hermetic = hermetic;
+        self
+    }
+
+    /// Set an environment variable for the application
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Set resource limits for the application
+    pub fn limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set the keyboard layout used to resolve `keycode:` inputs
+    pub fn layout(mut self, layout: KeyboardLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Set how key inputs are encoded
+    pub fn key_protocol(mut self, protocol: KeyProtocol) -> Self {
+        self.key_protocol = protocol;
+        self
+    }
+
+    /// Set the pause between characters of `type:` inputs
+    pub fn type_delay(mut self, delay: Duration) -> Self {
+        self.type_delay = Some(delay);
+        self
+    }
+
+    /// Set how long wait inputs wait before timing out and ending the run
+    pub fn wait_timeout(mut self, timeout: Duration) -> Self {
+        self.wait_timeout = Some(timeout);
+        self
+    }
+
+    /// Set whether the run ends once the application has exited, skipping the remaining inputs
+    pub fn stop_on_exit(mut self, stop: bool) -> Self {
+        self.stop_on_exit = stop;
+        self
+    }
+
+    /// Reuse PTYs from a warm pool instead of opening one per run
+    pub fn pool(mut self, pool: PtyPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Record the application's output to `recorder`, with a marker at each captured state
+    pub fn record_cast(mut self, recorder: CastRecorder) -> Self {
+        self.cast = Some(recorder);
+        self
+    }
+
+    /// Keep the raw bytes received between states in each [`StateCaptureResult`]
+    pub fn capture_ansi(mut self, capture: bool) -> Self {
+        self.capture_ansi = capture;
+        self
+    }
+
+    /// Copy the cell grid of each state into its [`StateCaptureResult`]
+    pub fn capture_grid(mut self, capture: bool) -> Self {
+        self.capture_grid = capture;
+        self
+    }
+
+    /// Export each state as HTML into its [`StateCaptureResult`]
+    pub fn capture_html(mut self, capture: bool) -> Self {
+        self.capture_html = capture;
+        self
+    }
+
+    /// Call `observer` with each state as soon as it is captured
+    pub fn on_capture(mut self, observer: impl Fn(&StateCaptureResult) + Send + Sync + 'static) -> Self {
+        self.on_capture = Some(CaptureObserver(Arc::new(observer)));
+        self
+    }
+
+    /// Run the application under a locale (sets `LANG`, `LC_ALL` and `LANGUAGE`)
+    pub fn locale(self, locale: &str) -> Self {
+        let language = locale.split('.').next().unwrap_or(locale).to_string();
+        self.env("LANG", locale).env("LC_ALL", locale).env("LANGUAGE", language)
+    }
+}
+
+/// `LANG` given to hermetic runs unless passed explicitly
+pub const HERMETIC_LANG: &str = "C.UTF-8";
+
+/// Build the command for an application running in the PTY.
+///
+/// Normally the application inherits the environment, and commands without
+/// a path are looked up in `PATH`. In hermetic mode the environment is
+/// reduced to `TERM`, `LANG` (default [`HERMETIC_LANG`]), `COLUMNS`, `LINES`
+/// and the explicitly passed variables, and the command must be an absolute
+/// path, so captures do not depend on the machine they run on.
+///
+/// Non-empty `limits` are checked here, so an unsupported platform fails
+/// before anything is spawned; [`apply_limits`] sets them once it is.
+///
+/// Returns the command and the program name used in error messages.
+pub fn build_command(
+    command: &str,
+    args: &[String],
+    (cols, rows): (u16, u16),
+    env: &[(String, String)],
+    hermetic: bool,
+    limits: &ResourceLimits,
+) -> super::SnapshotResult<(CommandBuilder, String)> {
+    let program = if hermetic {
+        if !Path::new(command).is_absolute() {
+            return Err(super::SnapshotError::Capture(format!(
+                "Hermetic mode requires an absolute binary path, got '{}'",
+                command
+            )));
+        }
+        command.to_string()
+    } else {
+        resolve_binary_path(command)
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_else(|| command.to_string())
+    };
+
+    limits.check_supported().map_err(super::SnapshotError::Capture)?;
+
+    let mut cmd = CommandBuilder::new(&program);
+    if hermetic {
+        cmd.env_clear();
+        cmd.env("LANG", HERMETIC_LANG);
+    }
+    cmd.env("TERM", "xterm-256color");
+    cmd.env("COLUMNS", cols.to_string());
+    cmd.env("LINES", rows.to_string());
+    for (key, value) in env {
+        cmd.env(key, value);
+    }
+    cmd.args(args);
+    Ok((cmd, program))
+}
+
+/// Apply `limits` to a freshly spawned application, stopping it if they cannot be set
+pub(crate) fn apply_limits(child: &mut dyn Child, limits: &ResourceLimits, program: &str) -> super::SnapshotResult<()> {
+    if limits.is_empty() {
+        return Ok(());
+    }
+    let applied = child
+        .process_id()
+        .ok_or_else(|| io::Error::other("the application has no process id"))
+        .and_then(|pid| limits.apply(pid));
+    applied.map_err(|e| {
+        let _ = child.kill();
+        let _ = child.wait();
+        super::SnapshotError::Capture(format!("Failed to apply resource limits to '{}': {}", program, e))
+    })
+}
+
+/// Run a CLI application with a sequence of inputs, capturing state after each.
+///
+/// Returns N+1 captures for N inputs (initial state + state after each input).
+pub fn run_with_inputs(
+    command: &str,
+    args: &[String],
+    inputs: &[String],
+    input_delay_ms: u64,
+) -> super::SnapshotResult<Vec<StateCaptureResult>> {
+    run_with_inputs_sized(command, args, inputs, input_delay_ms, TerminalSize::default())
+}
+
+/// Run a CLI application with a sequence of inputs at a specific terminal size.
+///
+/// Returns N+1 captures for N inputs (initial state + state after each input).
+pub fn run_with_inputs_sized(
+    command: &str,
+    args: &[String],
+    inputs: &[String],
+    input_delay_ms: u64,
+    size: TerminalSize,
+) -> super::SnapshotResult<Vec<StateCaptureResult>> {
+    run_with_options(command, args, inputs, input_delay_ms, size, &RunOptions::default())
+}
+
+/// Run a CLI application with a sequence of inputs at a specific terminal size,
+/// with additional capture options.
+///
+/// Returns N+1 captures for N inputs (initial state + state after each input).
+pub fn run_with_options(
+    command: &str,
+    args: &[String],
+    inputs: &[String],
+    input_delay_ms: u64,
+    size: TerminalSize,
+    options: &RunOptions,
+) -> super::SnapshotResult<Vec<StateCaptureResult>> {
+    validate_inputs(inputs)?;
+    let mut inputs = inputs.iter();
+    run_driven(command, args, input_delay_ms, size, options, |_| inputs.next().cloned())
+}
+
+/// Run a CLI application, choosing each input after seeing the state before it.
+///
+/// `next_input` is called with the captures so far (the last one is the
+/// current state) and returns the input to send, or `None` to end the run.
+/// With [`RunOptions::stop_on_exit`] it is not called once the application
+/// has exited. Inputs are interpreted as in [`run_with_options`].
+pub fn run_driven(
+    command: &str,
+    args: &[String],
+    input_delay_ms: u64,
+    size: TerminalSize,
+    options: &RunOptions,
+    mut next_input: impl FnMut(&[StateCaptureResult]) -> Option<String>,
+) -> super::SnapshotResult<Vec<StateCaptureResult>> {
+    use super::SnapshotError;
+
+    let (terminal_width, terminal_height) = size.dimensions();
+    let mut parser = Vt100Parser::new(u32::from(terminal_width), u32::from(terminal_height));
+    parser.terminal_mut().identity = options.identity.clone();
+    parser.terminal_mut().set_theme(options.theme.clone());
+    if let Some(cast) = &options.cast {
+        parser.record_cast(cast.clone());
+    }
+    if options.capture_ansi {
+        parser.keep_raw_output();
+    }
 
-    let mut captures = Vec::with_capacity(inputs.len() + 1);
+    let mut pty = match &options.pool {
+        Some(pool) => pool.checkout(terminal_width, terminal_height)?,
+        None => PtyHandle::open(terminal_width, terminal_height)?,
+    };
 
-    let img_width = u32::from(terminal_width) * CELL_WIDTH;
-    let img_height = u32::from(terminal_height) * CELL_HEIGHT;
+    let (cmd, program) = build_command(
+        command,
+        args,
+        (terminal_width, terminal_height),
+        &options.env,
+        options.hermetic,
+        &options.limits,
+    )?;
+
+    let mut child = pty.spawn(cmd, &program)?;
+    apply_limits(child.as_mut(), &options.limits, &program)?;
+    if options.pool.is_none() {
+        pty.close_slave();
+    }
+
+    if let Err(err) = pty.master.resize(PtySize {
+        rows: terminal_height,
+        cols: terminal_width,
+        pixel_width: 0,
+        pixel_height: 0,
+    }) {
+        eprintln!(
+            "Warning: unable to resize PTY to {}x{}: {}",
+            terminal_width, terminal_height, err
+        );
+    }
+
+    let mut captures = Vec::new();
+
+    let mut counters = CaptureCounters::default();
+    let mut stdin_closed = false;
 
     // Wait for initial render and capture state 0
-    wait_for_initial_render(&rx, &mut parser);
-    captures.push(StateCaptureResult {
-        step: 0,
-        input: None,
-        image_data: render_to_png(&parser),
-        width: img_width,
-        height: img_height,
-    });
+    wait_for_initial_render(&pty.rx, &mut parser, &mut pty.writer);
+    captures.push(counters.capture(&mut parser, options, 0, None, None, child.as_mut()));
+    notify_capture(options, &captures);
 
     // Process each input
-    for (i, input) in inputs.iter().enumerate() {
-        // Apply delay before sending input
-        if input_delay_ms > 0 {
-            thread::sleep(Duration::from_millis(input_delay_ms));
+    loop {
+        if options.stop_on_exit && captures.last().is_some_and(|capture| capture.exit_code.is_some()) {
+            break;
         }
+        let Some(input) = next_input(&captures) else {
+            break;
+        };
+        let action = match PtyInput::parse(&input) {
+            Ok(action) => action,
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(err);
+            }
+        };
 
-        // Parse and send the input
-        let sequence = parse_input(input);
-        writer.write_all(&sequence).map_err(|e| {
-            SnapshotError::Capture(format!("Failed to send input '{}': {}", input, e))
-        })?;
-        writer.flush().map_err(SnapshotError::Io)?;
+        // Apply delay before sending input
+        let delay = action.delay(Duration::from_millis(input_delay_ms));
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
 
-        // Wait for render to settle (shorter timeout per-input)
-        wait_for_input_render(&rx, &mut parser);
+        stdin_closed |= matches!(action, PtyInput::Eof);
+        let target = InputTarget {
+            master: pty.master.as_ref(),
+            writer: &mut pty.writer,
+            rx: &pty.rx,
+            pid: child.process_id(),
+        };
+        let wait_outcome = match send_input(target, &mut parser, &action, options) {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(err);
+            }
+        };
 
         // Capture this state
-        captures.push(StateCaptureResult {
-            step: i + 1,
-            input: Some(input.clone()),
-            image_data: render_to_png(&parser),
-            width: img_width,
-            height: img_height,
-        });
+        let step = captures.len();
+        captures.push(counters.capture(&mut parser, options, step, Some(input), wait_outcome.clone(), child.as_mut()));
+        notify_capture(options, &captures);
+
+        // A wait that timed out ends the run; its state records the timeout
+        if wait_outcome.is_some_and(|outcome| outcome.timed_out) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(captures);
+        }
+    }
+
+    // Clean up: pooled PTYs stay open, so the application is stopped rather than sent EOF.
+    // A PTY whose writer an `eof` input closed cannot be reused.
+    if let Some(pool) = options.pool.as_ref().filter(|_| !stdin_closed) {
+        if child.try_wait().ok().flatten().is_none() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        pool.checkin(pty);
+        return Ok(captures);
     }
 
-    // Clean up
+    let PtyHandle { master: _master, writer, rx, .. } = pty;
     drop(writer);
     wait_for_process_exit(child.as_mut(), &rx, &mut parser, PROCESS_DRAIN_TIMEOUT);
 
@@ -1153,9 +3125,93 @@ pub fn run_with_inputs_sized(
     Ok(captures)
 }
 
+/// Bells and output already reported, so each capture reports those since the previous one
+#[derive(Debug, Default)]
+pub(crate) struct CaptureCounters {
+    bells: (u32, u32),
+    raw_bytes: usize,
+}
+
+impl CaptureCounters {
+    /// Capture the terminal as state `step`, rendered with the options for that state
+    pub(crate) fn capture(
+        &mut self,
+        parser: &mut Vt100Parser,
+        options: &RunOptions,
+        step: usize,
+        input: Option<String>,
+        wait: Option<WaitOutcome>,
+        child: &mut dyn Child,
+    ) -> StateCaptureResult {
+        if let Some(cast) = &parser.cast {
+            cast.marker(state_marker(step, input.as_deref()));
+        }
+        let (bell_count, visual_bell_count) = take_bell_counts(parser, &mut self.bells);
+        parser.terminal_mut().cancel_flash();
+        let cursor_changes = parser.terminal_mut().take_cursor_changes();
+        let parser = &*parser;
+        let render = options.render_for(step);
+        StateCaptureResult {
+            step,
+            input,
+            image_data: render_to_png(parser, &render),
+            width: render.image_cols(parser.terminal().width) * CELL_WIDTH,
+            height: render.image_rows(parser.terminal().height) * CELL_HEIGHT,
+            bell_count,
+            visual_bell_count,
+            cursor: parser.terminal().cursor_state(),
+            cursor_position: (parser.terminal().cursor_y, parser.terminal().cursor_x),
+            cursor_changes,
+            blink_frames: render_blink_frames(parser, options.blink_frames, &render),
+            hyperlinks: parser.terminal().hyperlinks(),
+            text: parser.terminal().to_text(),
+            html: options.capture_html.then(|| parser.terminal().to_html()),
+            unrenderable: parser.terminal().unrenderable_chars(&options.render.glyph_fallback),
+            wait,
+            exit_code: child.try_wait().ok().flatten().map(|status| status.exit_code()),
+            ansi: parser.raw_output().map(|raw| {
+                let since = raw[self.raw_bytes.min(raw.len())..].to_vec();
+                self.raw_bytes = raw.len();
+                since
+            }),
+            grid: options.capture_grid.then(|| parser.terminal().to_grid()),
+        }
+    }
+}
+
+/// Pass the latest capture to the run's observer, if any
+pub(crate) fn notify_capture(options: &RunOptions, captures: &[StateCaptureResult]) {
+    if let (Some(CaptureObserver(observer)), Some(capture)) = (&options.on_capture, captures.last()) {
+        observer(capture);
+    }
+}
+
+/// Return the (audible, visual) bells received since the last call and update the totals seen
+fn take_bell_counts(parser: &Vt100Parser, seen: &mut (u32, u32)) -> (u32, u32) {
+    let terminal = parser.terminal();
+    let counts = (
+        terminal.bell_count - seen.0,
+        terminal.visual_bell_count - seen.1,
+    );
+    *seen = (terminal.bell_count, terminal.visual_bell_count);
+    counts
+}
+
+/// Render both blink phases if requested and the terminal has blinking content
+fn render_blink_frames(parser: &Vt100Parser, enabled: bool, render: &RenderOptions) -> Option<BlinkFrames> {
+    if !enabled || !parser.terminal().has_blinking_content(render.show_cursor) {
+        return None;
+    }
+    let phase = |phase| render_to_png(parser, &render.clone().blink_phase(phase));
+    Some(BlinkFrames {
+        phase_a: phase(BlinkPhase::Visible),
+        phase_b: phase(BlinkPhase::Hidden),
+    })
+}
+
 /// Render the current terminal state to PNG bytes
-fn render_to_png(parser: &Vt100Parser) -> Vec<u8> {
-    let img = parser.terminal().render_to_image();
+pub(crate) fn render_to_png(parser: &Vt100Parser, options: &RenderOptions) -> Vec<u8> {
+    let img = parser.terminal().render_to_image_with(options);
     let mut png_data = Vec::new();
     let mut cursor = std::io::Cursor::new(&mut png_data);
     img.write_to(&mut cursor, image::ImageFormat::Png)
@@ -1163,7 +3219,7 @@ fn render_to_png(parser: &Vt100Parser) -> Vec<u8> {
     png_data
 }
 
-fn spawn_reader(mut reader: Box<dyn Read + Send>) -> Receiver<Vec<u8>> {
+pub(crate) fn spawn_reader(mut reader: Box<dyn Read + Send>) -> Receiver<Vec<u8>> {
     let (tx, rx) = mpsc::channel();
     thread::spawn(move || {
         let mut buffer = [0u8; 4096];
@@ -1188,26 +3244,29 @@ fn spawn_reader(mut reader: Box<dyn Read + Send>) -> Receiver<Vec<u8>> {
     rx
 }
 
-fn wait_for_initial_render(rx: &Receiver<Vec<u8>>, parser: &mut Vt100Parser) {
-    drain_until_quiet_with_max(rx, parser, QUIET_WINDOW, MAX_INITIAL_RENDER_WAIT);
+pub(crate) fn wait_for_initial_render(rx: &Receiver<Vec<u8>>, parser: &mut Vt100Parser, writer: &mut dyn Write) {
+    drain_until_quiet_with_max(rx, parser, writer, QUIET_WINDOW, MAX_INITIAL_RENDER_WAIT);
 }
 
-fn wait_for_input_render(rx: &Receiver<Vec<u8>>, parser: &mut Vt100Parser) {
-    drain_until_quiet_with_max(rx, parser, QUIET_WINDOW, MAX_INPUT_RENDER_WAIT);
+pub(crate) fn wait_for_input_render(rx: &Receiver<Vec<u8>>, parser: &mut Vt100Parser, writer: &mut dyn Write) {
+    drain_until_quiet_with_max(rx, parser, writer, QUIET_WINDOW, MAX_INPUT_RENDER_WAIT);
 }
 
-fn wait_for_process_exit(
+pub(crate) fn wait_for_process_exit(
     child: &mut dyn Child,
     rx: &Receiver<Vec<u8>>,
     parser: &mut Vt100Parser,
     max_wait: Duration,
 ) {
+    // The PTY writer is already closed at this point, so replies are discarded
+    let writer = &mut std::io::sink();
+
     let start = Instant::now();
 
     loop {
         match child.try_wait() {
             Ok(Some(_)) => {
-                drain_until_quiet(rx, parser, QUIET_WINDOW);
+                drain_until_quiet(rx, parser, writer, QUIET_WINDOW);
                 return;
             }
             Ok(None) => {}
@@ -1222,7 +3281,7 @@ fn wait_for_process_exit(
         }
 
         match rx.recv_timeout(Duration::from_millis(60)) {
-            Ok(chunk) => ingest_chunk(&chunk, parser),
+            Ok(chunk) => ingest_chunk(&chunk, parser, writer),
             Err(RecvTimeoutError::Timeout) => {}
             Err(RecvTimeoutError::Disconnected) => break,
         }
@@ -1232,21 +3291,28 @@ fn wait_for_process_exit(
 fn drain_until_quiet(
     rx: &Receiver<Vec<u8>>,
     parser: &mut Vt100Parser,
+    writer: &mut dyn Write,
     quiet_window: Duration,
 ) {
-    drain_until_quiet_with_max(rx, parser, quiet_window, MAX_INPUT_RENDER_WAIT);
+    drain_until_quiet_with_max(rx, parser, writer, quiet_window, MAX_INPUT_RENDER_WAIT);
 }
 
 /// Drain output until quiet or max time reached.
 /// This handles apps that continuously output (like animations).
+///
+/// Apps using synchronized output (DEC mode 2026) mark their frame
+/// boundaries, so once a frame completes and no new frame has started the
+/// much shorter [`FRAME_QUIET_WINDOW`] applies instead of `quiet_window`.
 fn drain_until_quiet_with_max(
     rx: &Receiver<Vec<u8>>,
     parser: &mut Vt100Parser,
+    writer: &mut dyn Write,
     quiet_window: Duration,
     max_wait: Duration,
 ) {
     let start = Instant::now();
     let mut last_activity = Instant::now();
+    let frames_at_start = parser.terminal().completed_frames;
 
     loop {
         // Check if we've exceeded max wait time
@@ -1256,10 +3322,18 @@ fn drain_until_quiet_with_max(
 
         match rx.recv_timeout(Duration::from_millis(50)) {
             Ok(chunk) => {
-                ingest_chunk(&chunk, parser);
+                ingest_chunk(&chunk, parser, writer);
                 last_activity = Instant::now();
             }
             Err(RecvTimeoutError::Timeout) => {
+                let terminal = parser.terminal();
+                let at_frame_end = terminal.completed_frames > frames_at_start
+                    && !terminal.synchronized_output();
+                if at_frame_end && last_activity.elapsed() >= FRAME_QUIET_WINDOW {
+                    // Leave any later output for the next wait so the capture
+                    // shows exactly the completed frame
+                    return;
+                }
                 if last_activity.elapsed() >= quiet_window {
                     break;
                 }
@@ -1270,13 +3344,18 @@ fn drain_until_quiet_with_max(
 
     // Final drain of any remaining data
     while let Ok(chunk) = rx.try_recv() {
-        ingest_chunk(&chunk, parser);
+        ingest_chunk(&chunk, parser, writer);
     }
 }
 
-fn ingest_chunk(chunk: &[u8], parser: &mut Vt100Parser) {
-    for &byte in chunk {
-        parser.process_byte(byte);
+/// Feed PTY output to the parser and write any query replies back to the application
+pub(crate) fn ingest_chunk(chunk: &[u8], parser: &mut Vt100Parser, writer: &mut dyn Write) {
+    parser.process(chunk);
+    let replies = parser.terminal_mut().take_replies();
+    if !replies.is_empty() {
+        // Best effort: the application may already have exited
+        let _ = writer.write_all(&replies);
+        let _ = writer.flush();
     }
 }
 
@@ -1300,19 +3379,8 @@ fn resolve_binary_path(command: &str) -> Option<PathBuf> {
 }
 
 /// Translate a logical key label into the VT100 control sequence used by the demo
-fn key_to_sequence(key: &str) -> Vec<u8> {
-    match key.to_lowercase().as_str() {
-        "up" => b"\x1b[A".to_vec(),
-        "down" => b"\x1b[B".to_vec(),
-        "right" => b"\x1b[C".to_vec(),
-        "left" => b"\x1b[D".to_vec(),
-        "enter" => vec![b'\r'],
-        "space" => vec![b' '],
-        "tab" => vec![b'\t'],
-        "backspace" => vec![0x08],
-        other if other.len() == 1 => other.as_bytes().to_vec(),
-        other => other.as_bytes().to_vec(),
-    }
+fn key_to_sequence(key: &str, key_flags: u8) -> Vec<u8> {
+    kitty_key_sequence(key, key_flags).unwrap_or_else(|| key.as_bytes().to_vec())
 }
 
 #[cfg(test)]
@@ -1321,7 +3389,7 @@ mod tests {
 
     #[test]
     fn font8x8_bitmaps_are_scaled_consistently() {
-        let bitmap = get_char_bitmap('A');
+        let bitmap = get_char_bitmap('A', &[]);
         assert!(
             bitmap.iter().any(|row| *row != 0),
             "bitmap should contain lit pixels"
@@ -1334,6 +3402,636 @@ mod tests {
         }
     }
 
+    #[test]
+    fn double_size_lines_render_at_twice_the_size() {
+        let mut parser = Vt100Parser::new(5, 4);
+        for byte in b"\x1b#6ABC\r\n\x1b#3A\r\n\x1b#4A" {
+            parser.process_byte(*byte);
+        }
+        let terminal = parser.terminal();
+        assert_eq!(terminal.line_size(0), LineSize::DoubleWidth);
+        assert_eq!(terminal.line_size(3), LineSize::DoubleHeightBottom);
+        // Double-width lines hold half the columns, so "C" wrapped
+        assert!(terminal.to_text().starts_with("AB   \nC"));
+
+        let image = terminal.render_to_image();
+        let mut reference = Vt100Parser::new(1, 1);
+        reference.process_byte(b'A');
+        let reference = reference.terminal().render_to_image();
+        let pixel = |image: &ImageBuffer<Rgb<u8>, Vec<u8>>, px: u32, py: u32| {
+            image.get_pixel(px * PIXEL_SCALE, py * PIXEL_SCALE).0
+        };
+        for py in 0..FONT_HEIGHT {
+            for px in 0..FONT_WIDTH {
+                let glyph = pixel(&reference, px, py);
+                assert_eq!(pixel(&image, px * 2, py), glyph);
+                assert_eq!(pixel(&image, px * 2 + 1, py), glyph);
+                // The top half of the glyph fills row 2, the bottom half row 3
+                let stretched = 2 * FONT_HEIGHT + 2 * py;
+                assert_eq!(pixel(&image, px * 2, stretched), glyph);
+                assert_eq!(pixel(&image, px * 2, stretched + 1), glyph);
+            }
+        }
+    }
+
+    #[test]
+    fn sixel_images_are_composited_at_the_cursor_and_scroll() {
+        let mut parser = Vt100Parser::new(4, 3);
+        // A 2x12 red image with a transparent background, placed at column 1
+        parser.process(b" \x1bP0;1;0q#1;2;100;0;0!2~-!2~\x1b\\X");
+        let terminal = parser.terminal();
+        assert_eq!(terminal.images().len(), 1);
+        assert_eq!((terminal.images()[0].col, terminal.images()[0].row), (1, 0));
+        // The cursor moved to the line below the image
+        assert_eq!(terminal.to_text(), "    \n X  \n    \n");
+
+        let image = terminal.render_to_image();
+        assert_eq!(image.get_pixel(CELL_WIDTH, 11).0, [255, 0, 0]);
+        assert_eq!(image.get_pixel(CELL_WIDTH + 2, 0).0, [0, 0, 0]);
+
+        parser.process(b"\r\n\r\n\r\n");
+        assert!(parser.terminal().images().is_empty());
+    }
+
+    #[test]
+    fn kitty_and_iterm_images_are_placed_at_the_cursor() {
+        use base64::Engine;
+        let mut parser = Vt100Parser::new(6, 3);
+        // A 1x1 blue RGBA pixel stretched to 2x1 cells; the cursor ends after it
+        parser.process(b"\x1b_Ga=T,f=32,s=1,v=1,i=3,c=2,r=1;AAD//w==\x1b\\X");
+        let terminal = parser.terminal();
+        assert_eq!(terminal.images().len(), 1);
+        assert_eq!(terminal.images()[0].id, Some(3));
+        assert_eq!(terminal.to_text(), "  X   \n      \n      \n");
+        assert_eq!(parser.terminal_mut().take_replies(), b"\x1b_Gi=3;OK\x1b\\");
+        assert_eq!(parser.terminal().render_to_image().get_pixel(CELL_WIDTH * 2 - 1, 0).0, [0, 0, 255]);
+
+        // iTerm2 images are sized in cells and move the cursor below them
+        let mut png = std::io::Cursor::new(Vec::new());
+        RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255]))
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .unwrap();
+        let payload = base64::engine::general_purpose::STANDARD.encode(png.into_inner());
+        let osc = format!("\r\n\x1b]1337;File=inline=1;width=1;height=1:{}\x07Y", payload);
+        parser.process(osc.as_bytes());
+        let terminal = parser.terminal();
+        assert_eq!(terminal.images().len(), 2);
+        assert_eq!((terminal.images()[1].col, terminal.images()[1].row), (0, 1));
+        assert_eq!(terminal.to_text(), "  X   \n      \nY     \n");
+        assert_eq!(terminal.render_to_image().get_pixel(0, CELL_HEIGHT + 8).0, [255, 0, 0]);
+
+        parser.process(b"\x1b_Ga=d,d=i,i=3\x1b\\");
+        assert_eq!(parser.terminal().images().len(), 1);
+    }
+
+    #[test]
+    fn crop_renders_only_the_selected_rows() {
+        let mut parser = Vt100Parser::new(4, 5);
+        parser.process(b"\x1b[5;1H\x1b[41m \x1b[0m");
+        let terminal = parser.terminal();
+        let options = RenderOptions::default().crop(Some(Crop::Bottom(2)));
+        let image = terminal.render_to_image_with(&options);
+        assert_eq!(image.dimensions(), (4 * CELL_WIDTH, 2 * CELL_HEIGHT));
+        assert_eq!(image.get_pixel(0, CELL_HEIGHT).0, terminal.palette_color(1));
+        assert_eq!(options.image_rows(5), 2);
+
+        let rect = Crop::Rect(CellRect { cols: 1, rows: 1, x: 0, y: 4 });
+        let options = RenderOptions::default().crop(Some(rect));
+        let image = terminal.render_to_image_with(&options);
+        assert_eq!(image.dimensions(), (CELL_WIDTH, CELL_HEIGHT));
+        assert_eq!(image.get_pixel(0, 0).0, terminal.palette_color(1));
+        assert_eq!((options.image_cols(4), options.image_rows(5)), (1, 1));
+    }
+
+    #[test]
+    fn reverse_screen_mode_swaps_default_colors() {
+        let mut parser = Vt100Parser::new(3, 1);
+        parser.process(b"\x1b[?5h \x1b[41m \x1b[7m \x1b[?5$p");
+        let terminal = parser.terminal();
+        assert!(terminal.reverse_screen());
+        let (fg, bg) = terminal.default_colors();
+        let red = terminal.palette_color(1);
+        let image = terminal.render_to_image();
+        assert_eq!(image.get_pixel(0, 0).0, fg);
+        assert_eq!(image.get_pixel(CELL_WIDTH, 0).0, red);
+        // Inverse video applies on top of the swapped defaults
+        assert_eq!(image.get_pixel(CELL_WIDTH * 2, 0).0, bg);
+        assert_eq!(parser.terminal_mut().take_replies(), b"\x1b[?5;1$y");
+
+        parser.process(b"\x1b[?5l");
+        assert_eq!(parser.terminal().render_to_image().get_pixel(0, 0).0, bg);
+        assert_eq!(parser.terminal().visual_bell_count, 0, "reverse video drawn in is not a visual bell");
+    }
+
+    #[test]
+    fn reverse_screen_mode_keeps_explicit_colors_matching_the_defaults() {
+        let mut terminal = Vt100Terminal::new(2, 1);
+        let (fg, bg) = terminal.default_colors();
+        terminal.set_bg_color(bg);
+        terminal.write_char(' ');
+        terminal.reset_bg();
+        terminal.write_char(' ');
+        terminal.set_reverse_screen(true);
+        let image = terminal.render_to_image();
+        assert_eq!(image.get_pixel(0, 0).0, bg);
+        assert_eq!(image.get_pixel(CELL_WIDTH, 0).0, fg);
+    }
+
+    #[test]
+    fn kitty_keyboard_flags_are_pushed_queried_and_popped() {
+        let mut parser = Vt100Parser::new(10, 2);
+        parser.process(b"\x1b7X\x1b[>1u\x1b[?u");
+        assert_eq!(parser.terminal().key_flags(), 1);
+        assert_eq!(parser.terminal_mut().take_replies(), b"\x1b[?1u");
+        assert_eq!(parse_input("ctrl+i", &KeyboardLayout::default(), parser.terminal().key_flags()), b"\x1b[105;5u");
+
+        parser.process(b"\x1b[>8u\x1b[=1;2u");
+        assert_eq!(parser.terminal().key_flags(), 9);
+        parser.process(b"\x1b[<u");
+        assert_eq!(parser.terminal().key_flags(), 1);
+        parser.process(b"\x1b[<5u");
+        assert_eq!(parser.terminal().key_flags(), 0);
+        assert_eq!(parse_input("ctrl+i", &KeyboardLayout::default(), 0), b"\t");
+        assert_eq!(parse_input("key:ctrl+i", &KeyboardLayout::default(), 1), b"\x1b[105;5u");
+        assert_eq!(parse_input("text:ctrl+i", &KeyboardLayout::default(), 1), b"ctrl+i");
+        assert!(parse_input("key:nonsense", &KeyboardLayout::default(), 0).is_empty());
+        // Plain CSI u still restores the cursor
+        parser.process(b"\x1b[u");
+        assert_eq!(parser.terminal().cursor_x, 0);
+    }
+
+    #[test]
+    fn mouse_reports_follow_the_requested_modes() {
+        use super::super::mouse::MouseButton;
+        let click = MouseInput::Click { col: 2, row: 1, button: MouseButton::Left };
+        let mut parser = Vt100Parser::new(10, 3);
+        assert!(parser.terminal().mouse_report(click).is_empty());
+
+        parser.process(b"\x1b[?1002h\x1b[?1006h\x1b[?1002$p");
+        assert_eq!(parser.terminal().mouse_tracking(), MouseTracking::ButtonEvent);
+        assert_eq!(parser.terminal().mouse_report(click), b"\x1b[<0;3;2M\x1b[<0;3;2m");
+        assert_eq!(parser.terminal_mut().take_replies(), b"\x1b[?1002;1$y");
+
+        // Disabling a mode that is not active leaves tracking on
+        parser.process(b"\x1b[?1000l\x1b[?1006l");
+        assert_eq!(parser.terminal().mouse_report(click), b"\x1b[M #\"\x1b[M##\"");
+        parser.process(b"\x1b[?1002l");
+        assert!(parser.terminal().mouse_report(click).is_empty());
+    }
+
+    #[test]
+    fn unknown_glyphs_use_the_fallback_chain() {
+        let mut parser = Vt100Parser::new(3, 1);
+        for byte in "A\u{E0B0}\u{1F600}".bytes() {
+            parser.process_byte(byte);
+        }
+        let terminal = parser.terminal();
+        assert_eq!(terminal.unrenderable_chars(&[]), ['\u{E0B0}', '\u{1F600}']);
+
+        let lit = |image: &ImageBuffer<Rgb<u8>, Vec<u8>>| {
+            (0..CELL_HEIGHT).any(|y| (CELL_WIDTH..2 * CELL_WIDTH).any(|x| image.get_pixel(x, y).0 != [0, 0, 0]))
+        };
+        assert!(!lit(&terminal.render_to_image()));
+        let options = RenderOptions::default().glyph_fallback(vec![GlyphFallback::Tofu]);
+        assert!(lit(&terminal.render_to_image_with(&options)));
+    }
+
+    #[test]
+    fn sgr_sets_and_clears_extended_attributes() {
+        let mut parser = Vt100Parser::new(4, 1);
+        for byte in b"\x1b[2;3;5;9mA\x1b[22;23;25;29mB" {
+            parser.process_byte(*byte);
+        }
+        let attrs = parser.terminal().attributes[0][0];
+        assert!(attrs.dim && attrs.italic && attrs.blink && attrs.strikethrough);
+        assert_eq!(parser.terminal().attributes[0][1], CellAttributes::default());
+    }
+
+    #[test]
+    fn sgr_parses_underline_style_and_color() {
+        let mut parser = Vt100Parser::new(4, 1);
+        for byte in b"\x1b[4:3;58:2::255:0:0mA\x1b[21;58;5;21mB\x1b[24;59mC" {
+            parser.process_byte(*byte);
+        }
+        let attrs = &parser.terminal().attributes[0];
+        assert_eq!(attrs[0].underline_style, UnderlineStyle::Curly);
+        assert_eq!(attrs[0].underline_color, Some([255, 0, 0]));
+        assert!(!attrs[0].italic, "4:3 must not be read as SGR 3");
+        assert_eq!(attrs[1].underline_style, UnderlineStyle::Double);
+        assert_eq!(attrs[1].underline_color, Some(Theme::default().color(21)));
+        assert!(!attrs[2].underline);
+        assert_eq!(attrs[2].underline_color, None);
+    }
+
+    #[test]
+    fn bells_and_visual_bells_are_counted() {
+        let mut parser = Vt100Parser::new(10, 2);
+        for byte in b"\x07oops\x07\x1b[?5h\x1b[?5l\x1b]0;title\x07" {
+            parser.process_byte(*byte);
+        }
+        assert_eq!(parser.terminal().bell_count, 2, "OSC terminator is not a bell");
+        assert_eq!(parser.terminal().visual_bell_count, 1);
+
+        // Reverse video that is drawn in or captured is not a flash
+        parser.process(b"\x1b[?5hmenu\x1b[?5l\x1b[?5h");
+        parser.terminal_mut().cancel_flash();
+        parser.process(b"\x1b[?5l");
+        assert_eq!(parser.terminal().visual_bell_count, 1);
+
+        let mut seen = (0, 0);
+        assert_eq!(take_bell_counts(&parser, &mut seen), (2, 1));
+        assert_eq!(take_bell_counts(&parser, &mut seen), (0, 0));
+    }
+
+    #[test]
+    fn cursor_is_rendered_only_when_requested() {
+        let mut terminal = Vt100Terminal::new(2, 2);
+        terminal.move_cursor(1, 0);
+        let cursor_px = CELL_WIDTH;
+
+        let plain = terminal.render_to_image();
+        assert_eq!(plain.get_pixel(cursor_px, 0).0, [0, 0, 0]);
+
+        let options = RenderOptions::default().show_cursor(true);
+        let block = terminal.render_to_image_with(&options);
+        assert_eq!(block.get_pixel(cursor_px, 0).0, [255, 255, 255]);
+
+        terminal.cursor_shape = CursorShape::Bar;
+        let bar = terminal.render_to_image_with(&options);
+        assert_eq!(bar.get_pixel(cursor_px, 0).0, [255, 255, 255]);
+        assert_eq!(bar.get_pixel(cursor_px + PIXEL_SCALE, 0).0, [0, 0, 0]);
+    }
+
+    #[test]
+    fn cursor_visibility_and_shape_follow_escape_sequences() {
+        let mut parser = Vt100Parser::new(4, 2);
+        parser.process(b"\x1b[?25l\x1b[6 q");
+        assert!(!parser.terminal().cursor_visible);
+        assert_eq!(parser.terminal().cursor_shape, CursorShape::Bar);
+        assert!(!parser.terminal().cursor_blinking);
+
+        let options = RenderOptions::default().show_cursor(true);
+        let hidden = parser.terminal().render_to_image_with(&options);
+        assert_eq!(hidden.get_pixel(0, 0).0, [0, 0, 0]);
+
+        parser.process(b"\x1b[?25h\x1b[3 q");
+        assert!(parser.terminal().cursor_visible);
+        assert_eq!(parser.terminal().cursor_shape, CursorShape::Underline);
+        assert!(parser.terminal().cursor_blinking);
+
+        parser.process(b"\x1b[ q");
+        assert_eq!(parser.terminal().cursor_shape, CursorShape::Block);
+    }
+
+    #[test]
+    fn cursor_changes_are_reported_per_step() {
+        let mut parser = Vt100Parser::new(4, 2);
+
+        // Menu mode hides the cursor; repeated hides are not new changes
+        parser.process(b"\x1b[?25l\x1b[?25l");
+        assert_eq!(parser.terminal_mut().take_cursor_changes(), vec![CursorChange::Hidden]);
+        assert!(!parser.terminal().cursor_state().visible);
+
+        // Input field shows a steady bar cursor
+        parser.process(b"\x1b[?25h\x1b[6 q");
+        assert_eq!(
+            parser.terminal_mut().take_cursor_changes(),
+            vec![
+                CursorChange::Shown,
+                CursorChange::Style { shape: CursorShape::Bar, blinking: false },
+            ]
+        );
+        assert!(parser.terminal_mut().take_cursor_changes().is_empty());
+    }
+
+    #[test]
+    fn blink_phases_show_and_hide_blinking_text() {
+        let mut parser = Vt100Parser::new(2, 1);
+        parser.process(b"\x1b[5m#");
+        let terminal = parser.terminal();
+        assert!(terminal.has_blinking_content(false));
+
+        let lit = |img: &ImageBuffer<Rgb<u8>, Vec<u8>>| img.pixels().filter(|p| p.0 == [255, 255, 255]).count();
+        let visible = terminal.render_to_image_with(&RenderOptions::default().blink_phase(BlinkPhase::Visible));
+        let hidden = terminal.render_to_image_with(&RenderOptions::default().blink_phase(BlinkPhase::Hidden));
+        assert!(lit(&visible) > 0);
+        assert_eq!(lit(&hidden), 0);
+
+        let plain = Vt100Terminal::new(2, 1);
+        assert!(!plain.has_blinking_content(false));
+        assert!(plain.has_blinking_content(true), "default cursor blinks");
+    }
+
+    #[test]
+    fn tab_stops_can_be_set_and_cleared() {
+        let mut parser = Vt100Parser::new(20, 2);
+        parser.process(b"\tA");
+        assert_eq!(parser.terminal().buffer[0][8], 'A');
+
+        // Clear all stops, set one at column 3 with HTS, then tab from column 0
+        parser.process(b"\x1b[3g\r\x1b[3C\x1bH\r\tB");
+        assert_eq!(parser.terminal().tab_stops(), vec![3]);
+        assert_eq!(parser.terminal().buffer[0][3], 'B');
+
+        // With no further stops, tab stops at the last column
+        parser.process(b"\tC");
+        assert_eq!(parser.terminal().buffer[0][19], 'C');
+
+        parser.terminal_mut().set_tab_interval(4);
+        assert_eq!(parser.terminal().tab_stops(), vec![4, 8, 12, 16]);
+    }
+
+    #[test]
+    fn osc8_hyperlinks_are_collected_with_cell_ranges() {
+        let mut parser = Vt100Parser::new(20, 2);
+        parser.process(b"See \x1b]8;;https://example.com/a;b\x1b\\\x1b[1mdocs\x1b[0m\x1b]8;;\x1b\\ now");
+        assert_eq!(
+            parser.terminal().hyperlinks(),
+            vec![Hyperlink {
+                uri: "https://example.com/a;b".to_string(),
+                text: "docs".to_string(),
+                row: 0,
+                start_col: 4,
+                end_col: 7,
+            }]
+        );
+        assert_eq!(parser.terminal().attributes[0][8].hyperlink, None);
+    }
+
+    #[test]
+    fn writing_the_last_column_defers_the_wrap() {
+        let mut parser = Vt100Parser::new(4, 2);
+        parser.process(b"abcd");
+        assert_eq!((parser.terminal().cursor_x, parser.terminal().cursor_y), (3, 0));
+
+        // CR+LF after a full line must not produce a blank row
+        parser.process(b"\r\nX");
+        assert_eq!(parser.terminal().buffer[1][0], 'X');
+
+        parser.process(b"\x1b[Habcde");
+        assert_eq!(parser.terminal().buffer[0].iter().collect::<String>(), "abcd");
+        assert_eq!(parser.terminal().buffer[1][0], 'e');
+    }
+
+    #[test]
+    fn disabling_autowrap_clips_at_the_right_margin() {
+        let mut parser = Vt100Parser::new(4, 2);
+        parser.process(b"\x1b[?7labcdef");
+        assert!(!parser.terminal().autowrap());
+        assert_eq!(parser.terminal().buffer[0].iter().collect::<String>(), "abcf");
+        assert_eq!(parser.terminal().buffer[1].iter().collect::<String>(), "    ");
+    }
+
+    #[test]
+    fn theme_sets_palette_and_default_colors() {
+        let mut parser = Vt100Parser::new(4, 1);
+        parser.terminal_mut().set_theme(Theme::light());
+        parser.process(b"a\x1b[31mb\x1b[39mc");
+
+        let terminal = parser.terminal();
+        assert_eq!(terminal.bg_colors[0][3], [255, 255, 255]);
+        assert_eq!(terminal.fg_colors[0][0], Theme::light().foreground);
+        assert_eq!(terminal.fg_colors[0][1], Theme::light().palette[1]);
+        assert_eq!(terminal.fg_colors[0][2], Theme::light().foreground);
+        assert_eq!(terminal.render_to_image().get_pixel(CELL_WIDTH * 3, 0).0, [255, 255, 255]);
+    }
+
+    #[test]
+    fn osc_color_sequences_update_the_live_palette() {
+        let mut parser = Vt100Parser::new(4, 1);
+        parser.process(b"\x1b]4;1;rgb:12/34/56\x07\x1b]11;#202020\x1b\\\x1b[31mr\x1b[38;5;1mR\x1b[0m ");
+        let terminal = parser.terminal();
+        assert_eq!(terminal.fg_colors[0][0], [0x12, 0x34, 0x56]);
+        assert_eq!(terminal.fg_colors[0][1], [0x12, 0x34, 0x56]);
+        assert_eq!(terminal.bg_colors[0][3], [0x20, 0x20, 0x20]);
+
+        let mut replies = Vec::new();
+        ingest_chunk(b"\x1b]4;1;?\x07\x1b]11;?\x1b\\\x1b]104\x07\x1b]111\x07", &mut parser, &mut replies);
+        assert_eq!(replies, b"\x1b]4;1;rgb:1212/3434/5656\x07\x1b]11;rgb:2020/2020/2020\x1b\\");
+        assert_eq!(parser.terminal().palette_color(1), Theme::default().palette[1]);
+        assert_eq!(parser.terminal().bg_colors[0][3], [0, 0, 0]);
+    }
+
+    #[test]
+    fn out_of_range_extended_colors_are_ignored() {
+        let mut parser = Vt100Parser::new(3, 1);
+        parser.process(b"\x1b[38;5;300ma\x1b[38;2;256;0;0mb\x1b[38:5:300mc");
+        let terminal = parser.terminal();
+        let default_fg = terminal.default_colors().0;
+        assert_eq!(terminal.fg_colors[0], [default_fg; 3]);
+        assert!(!terminal.attributes[0].iter().any(|attrs| attrs.explicit_fg));
+    }
+
+    #[test]
+    fn osc_default_colors_leave_explicit_colors_alone() {
+        let mut parser = Vt100Parser::new(4, 1);
+        let [r, g, b] = parser.terminal().default_colors().0;
+        parser.process(format!("\x1b[38;2;{};{};{}mx\x1b[0my\x1b]10;#102030\x07", r, g, b).as_bytes());
+        let terminal = parser.terminal();
+        assert_eq!(terminal.fg_colors[0][0], [r, g, b]);
+        assert_eq!(terminal.fg_colors[0][1], [0x10, 0x20, 0x30]);
+    }
+
+    #[test]
+    fn color_specs_are_scaled_to_eight_bits() {
+        assert_eq!(parse_color_spec(b"rgb:f/8/0"), Some([255, 136, 0]));
+        assert_eq!(parse_color_spec(b"rgb:ffff/0000/8080"), Some([255, 0, 128]));
+        assert_eq!(parse_color_spec(b"#ff8000"), Some([255, 128, 0]));
+        assert_eq!(parse_color_spec(b"#fff"), Some([255, 255, 255]));
+        assert_eq!(parse_color_spec(b"red"), None);
+        assert_eq!(parse_color_spec(b"rgb:1/2"), None);
+    }
+
+    #[test]
+    fn resizing_clips_content_and_keeps_the_cursor_on_screen() {
+        let mut parser = Vt100Parser::new(6, 4);
+        parser.process(b"row0\r\nrow1\r\nrow2\r\nrow3!!");
+        parser.terminal_mut().resize(3, 2);
+        let terminal = parser.terminal();
+        assert_eq!((terminal.width, terminal.height), (3, 2));
+        assert_eq!(terminal.to_text(), "row\nrow\n");
+        assert_eq!((terminal.cursor_x, terminal.cursor_y), (2, 1));
+
+        parser.terminal_mut().resize(20, 3);
+        parser.process(b"\x1b[3;5HZ");
+        assert_eq!(parser.terminal().buffer[2][4], 'Z');
+        assert_eq!(parser.terminal().tab_stops(), vec![8, 16]);
+    }
+
+    #[test]
+    fn hermetic_commands_get_a_minimal_environment() {
+        let env = vec![("APP_MODE".to_string(), "test".to_string())];
+        assert!(build_command("sh", &[], (80, 24), &env, true, &ResourceLimits::new()).is_err());
+
+        let (cmd, program) = build_command("/bin/sh", &[], (80, 24), &env, true, &ResourceLimits::new()).unwrap();
+        assert_eq!(program, "/bin/sh");
+        let mut keys: Vec<&str> = cmd.iter_full_env_as_str().map(|(key, _)| key).collect();
+        keys.sort();
+        assert_eq!(keys, vec!["APP_MODE", "COLUMNS", "LANG", "LINES", "TERM"]);
+        assert_eq!(cmd.get_env("LANG").unwrap(), HERMETIC_LANG);
+    }
+
+    #[test]
+    fn resize_tokens_are_parsed() {
+        assert_eq!(parse_resize("resize:100x30"), Some(TerminalSize::Custom(100, 30)));
+        assert_eq!(parse_resize("RESIZE:compact"), Some(TerminalSize::Compact));
+        assert_eq!(parse_resize("resize:0x30"), None);
+        assert_eq!(parse_resize("r"), None);
+    }
+
+    #[test]
+    fn sleep_tokens_are_parsed() {
+        assert_eq!(parse_sleep("sleep:1500"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_sleep("SLEEP: 250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_sleep("sleep:1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_sleep("sleep:soon"), None);
+        assert_eq!(parse_sleep("sleep:-1s"), None);
+        assert_eq!(parse_sleep("enter"), None);
+    }
+
+    #[test]
+    fn eof_closes_stdin_mid_run() {
+        let args = vec!["-c".to_string(), "cat >/dev/null; echo done; sleep 5".to_string()];
+        let inputs = ["hello".to_string(), "eof".to_string(), "wait-for:done".to_string()];
+        let options = RunOptions::default().wait_timeout(Duration::from_secs(5));
+        let captures = run_with_options("/bin/sh", &args, &inputs, 0, TerminalSize::Compact, &options).unwrap();
+        assert_eq!(captures.len(), 4);
+        assert!(!captures[3].wait.as_ref().unwrap().timed_out);
+        assert!(is_eof_input(" EOF "));
+        assert!(!is_eof_input("text:eof"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn resource_limits_apply_to_the_application() {
+        let args = vec!["-c".to_string(), "read line; echo fds=$(ulimit -n); sleep 5".to_string()];
+        let inputs = ["enter".to_string(), "wait-for:fds=".to_string()];
+        let options = RunOptions::default().limits(ResourceLimits::new().open_files(32)).wait_timeout(Duration::from_secs(5));
+        let captures = run_with_options("/bin/sh", &args, &inputs, 0, TerminalSize::Compact, &options).unwrap();
+        assert!(captures[2].text.contains("fds=32"), "{}", captures[2].text);
+    }
+
+    #[test]
+    fn failed_send_stops_the_application() {
+        // After `eof` the key cannot be written, so the run fails while the shell is still running
+        let args = vec!["-c".to_string(), "echo pid=$$; cat; sleep 30".to_string()];
+        let mut inputs = ["eof", "hello"].into_iter();
+        let mut pid = None;
+        let result = run_driven("/bin/sh", &args, 0, TerminalSize::Compact, &RunOptions::default(), |captures| {
+            pid = captures[0].text.split("pid=").nth(1).and_then(|s| s.split_whitespace().next()?.parse::<u32>().ok());
+            inputs.next().map(str::to_string)
+        });
+        assert!(result.is_err());
+        let pid = pid.expect("the shell prints its pid");
+        assert!(!Path::new(&format!("/proc/{}", pid)).exists(), "cat was left running or unreaped");
+    }
+
+    #[test]
+    fn malformed_wait_and_signal_inputs_are_rejected() {
+        for input in ["wait-regex:(unclosed", "wait-stable:soon", "signal:STOPP", "signal:99999"] {
+            let inputs = ["hello".to_string(), input.to_string()];
+            let err = run_with_options("/bin/sh", &[], &inputs, 0, TerminalSize::Compact, &RunOptions::default()).unwrap_err();
+            assert!(err.to_string().contains(input), "{}", err);
+        }
+        // Driven runs check each input as it is chosen
+        let result = run_driven("/bin/sh", &[], 0, TerminalSize::Compact, &RunOptions::default(), |_| Some("wait-for:".to_string()));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn raw_output_is_split_between_states() {
+        let args = vec!["-c".to_string(), "printf '\\033[1mready\\033[0m'; read a; printf 'got %s' \"$a\"; sleep 1".to_string()];
+        let inputs = ["x".to_string(), "enter".to_string(), "wait-for:got x".to_string()];
+        let options = RunOptions::default().capture_ansi(true).wait_timeout(Duration::from_secs(5));
+        let captures = run_with_options("/bin/sh", &args, &inputs, 0, TerminalSize::Compact, &options).unwrap();
+        let ansi: Vec<String> = captures.iter().map(|c| String::from_utf8_lossy(c.ansi.as_deref().unwrap()).to_string()).collect();
+        assert_eq!(ansi[0], "\x1b[1mready\x1b[0m");
+        assert_eq!(ansi[1], "x");
+        assert_eq!(ansi.concat(), "\x1b[1mready\x1b[0mx\r\ngot x");
+        assert!(run_with_options("/bin/sh", &args[..1], &[], 0, TerminalSize::Compact, &RunOptions::default()).is_ok_and(|c| c[0].ansi.is_none()));
+    }
+
+    #[test]
+    fn states_carry_the_cell_grid() {
+        let args = vec!["-c".to_string(), "printf 'name: \\033[32mok\\033[0m'; read a".to_string()];
+        let options = RunOptions::default().capture_grid(true);
+        let captures = run_with_options("/bin/sh", &args, &["ab".to_string()], 0, TerminalSize::Compact, &options).unwrap();
+        let grid = captures[1].grid.as_ref().unwrap();
+        assert_eq!(grid.row_text(0), "name: okab");
+        assert!(grid.cells_of("ok").unwrap().iter().all(|cell| cell.fg == grid.cell(0, 6).unwrap().fg && cell.fg != grid.default_fg));
+        assert_eq!(captures[1].cursor_position, (0, 10));
+        assert_eq!((grid.cursor.row, grid.cursor.col), captures[1].cursor_position);
+        assert!(run_with_options("/bin/sh", &args, &[], 0, TerminalSize::Compact, &RunOptions::default()).is_ok_and(|c| c[0].grid.is_none()));
+    }
+
+    #[test]
+    fn html_is_exported_only_when_requested() {
+        let args = vec!["-c".to_string(), "printf 'name: \\033[32mok\\033[0m'; sleep 1".to_string()];
+        let captures = run_with_options("/bin/sh", &args, &[], 0, TerminalSize::Compact, &RunOptions::default()).unwrap();
+        assert!(captures[0].html.is_none());
+        let options = RunOptions::default().capture_html(true);
+        let captures = run_with_options("/bin/sh", &args, &[], 0, TerminalSize::Compact, &options).unwrap();
+        assert!(captures[0].html.as_deref().is_some_and(|html| html.contains("ok")));
+    }
+
+    #[test]
+    fn cursor_position_reports_are_answered() {
+        let mut parser = Vt100Parser::new(10, 5);
+        let mut replies = Vec::new();
+        ingest_chunk(b"\x1b[3;4H\x1b[6n\x1b[5n", &mut parser, &mut replies);
+        assert_eq!(replies, b"\x1b[3;4R\x1b[0n");
+
+        ingest_chunk(b"\x1b[?6n", &mut parser, &mut replies);
+        assert_eq!(&replies[10..], b"\x1b[?3;4R");
+        assert!(parser.terminal_mut().take_replies().is_empty());
+    }
+
+    #[test]
+    fn device_attribute_queries_use_the_configured_identity() {
+        let mut parser = Vt100Parser::new(10, 2);
+        let mut replies = Vec::new();
+        ingest_chunk(b"\x1b[c\x1b[>c\x1b[>0q", &mut parser, &mut replies);
+        assert_eq!(replies, b"\x1b[?62;4;22c\x1b[>41;379;0c\x1bP>|XTerm(379)\x1b\\");
+
+        parser.terminal_mut().identity.name = "cli-vision".to_string();
+        replies.clear();
+        ingest_chunk(b"\x1b[>q", &mut parser, &mut replies);
+        assert_eq!(replies, b"\x1bP>|cli-vision(379)\x1b\\");
+    }
+
+    #[test]
+    fn synchronized_frames_end_the_wait_early() {
+        let (tx, rx) = mpsc::channel();
+        let mut parser = Vt100Parser::new(10, 2);
+        tx.send(b"\x1b[?2026hframe\x1b[?2026l".to_vec()).unwrap();
+
+        let start = Instant::now();
+        drain_until_quiet_with_max(&rx, &mut parser, &mut Vec::new(), Duration::from_secs(5), Duration::from_secs(5));
+        assert!(start.elapsed() < Duration::from_secs(1), "frame end should not wait for the quiet window");
+        assert_eq!(parser.terminal().completed_frames, 1);
+        assert!(!parser.terminal().synchronized_output());
+        assert_eq!(parser.terminal().buffer[0][0], 'f');
+        drop(tx);
+
+        let mut replies = Vec::new();
+        ingest_chunk(b"\x1b[?2026$p\x1b[?9999$p", &mut parser, &mut replies);
+        assert_eq!(replies, b"\x1b[?2026;2$y\x1b[?9999;0$y");
+    }
+
+    #[test]
+    fn strikethrough_and_dim_are_rendered() {
+        let mut terminal = Vt100Terminal::new(1, 2);
+        terminal.set_fg_color([200, 200, 200]);
+        terminal.set_dim(true);
+        terminal.set_strikethrough(true);
+        terminal.write_char(' ');
+
+        let image = terminal.render_to_image();
+        let mid_y = (FONT_HEIGHT / 2) * PIXEL_SCALE;
+        assert_eq!(image.get_pixel(0, mid_y).0, [100, 100, 100]);
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0]);
+    }
+
     #[test]
     fn rendered_pixels_follow_font_bitmaps() {
         let mut terminal = Vt100Terminal::new(1, 2);
@@ -1345,7 +4043,7 @@ mod tests {
         assert_eq!(terminal.fg_colors[0][0], fg);
         assert_eq!(terminal.bg_colors[0][0], bg);
 
-        let bitmap = get_char_bitmap('R');
+        let bitmap = get_char_bitmap('R', &[]);
         let image = terminal.render_to_image();
 
         for (py, row) in bitmap.iter().enumerate() {
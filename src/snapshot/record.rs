@@ -0,0 +1,250 @@
+//! Recording input sequences by driving an application interactively.
+//!
+//! [`record`] connects the controlling terminal to an application in a PTY:
+//! keystrokes are forwarded to the application and its output is written to
+//! the terminal, so the application is driven as usual. Every key press is
+//! recorded with its time as an input token ([`input_for_key`]), and the
+//! capture key marks the states worth looking at. The stop key, stdin
+//! closing or the application exiting ends the recording.
+//!
+//! Replies the terminal sends to the application's queries (cursor position,
+//! device attributes, colors, focus events) are forwarded but not recorded:
+//! on replay the emulator answers those queries itself.
+//!
+//! [`Scenario::from_recording`](crate::scenario::Scenario::from_recording)
+//! turns a [`Recording`] into a scenario file that `run --scenario` and
+//! `suite` replay.
+
+use serde::{Deserialize, Serialize};
+use std::io::{IsTerminal, Write};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+
+use super::keys::{input_for_key, split_keys, KeyPress};
+use super::limits::ResourceLimits;
+use super::pool::PtyHandle;
+use super::pty::{build_command, spawn_reader, TerminalSize};
+use super::{SnapshotError, SnapshotResult};
+
+/// Default key that marks a capture point
+pub const DEFAULT_CAPTURE_KEY: &str = "ctrl+]";
+
+/// Default key that ends the recording
+pub const DEFAULT_STOP_KEY: &str = "ctrl+\\";
+
+/// Turns off modes an application may leave on when it is stopped:
+/// alternate screen, hidden cursor, mouse tracking, bracketed paste, kitty keys and attributes
+const RESTORE_TERMINAL: &[u8] =
+    b"\x1b[?1049l\x1b[?25h\x1b[?1000l\x1b[?1002l\x1b[?1003l\x1b[?1006l\x1b[?2004l\x1b[<u\x1b[0m";
+
+/// How often the session checks whether the application exited
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Configuration for a recording session
+#[derive(Debug, Clone)]
+pub struct RecordConfig {
+    /// Terminal size; `None` uses the size of the controlling terminal
+    pub size: Option<TerminalSize>,
+    /// Key that marks a capture point instead of being sent
+    pub capture_key: String,
+    /// Key that ends the recording instead of being sent
+    pub stop_key: String,
+    /// Extra environment variables for the application
+    pub env: Vec<(String, String)>,
+}
+
+impl Default for RecordConfig {
+    fn default() -> Self {
+        Self {
+            size: None,
+            capture_key: DEFAULT_CAPTURE_KEY.to_string(),
+            stop_key: DEFAULT_STOP_KEY.to_string(),
+            env: Vec::new(),
+        }
+    }
+}
+
+impl RecordConfig {
+    /// Set the terminal size instead of using the controlling terminal's
+    pub fn size(mut self, size: TerminalSize) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Set the key that marks a capture point
+    pub fn capture_key(mut self, key: impl Into<String>) -> Self {
+        self.capture_key = key.into();
+        self
+    }
+
+    /// Set the key that ends the recording
+    pub fn stop_key(mut self, key: impl Into<String>) -> Self {
+        self.stop_key = key.into();
+        self
+    }
+
+    /// Add an environment variable for the application
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+}
+
+/// Something that happened during a recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedEvent {
+    /// A key press, as an input token
+    Key { at_ms: u64, input: String },
+    /// The capture key was pressed
+    Capture { at_ms: u64 },
+}
+
+/// Result of a recording session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recording {
+    /// Terminal size the application ran at
+    pub size: (u16, u16),
+    /// Key presses and capture points, in order
+    pub events: Vec<RecordedEvent>,
+    /// Exit code, if the application exited on its own
+    pub exit_code: Option<u32>,
+}
+
+/// Restores the terminal mode when dropped
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> SnapshotResult<Self> {
+        crossterm::terminal::enable_raw_mode()
+            .map_err(|e| SnapshotError::Capture(format!("Failed to enable raw mode: {}", e)))?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+}
+
+/// Run an application connected to the controlling terminal and record its input
+pub fn record(command: &str, args: &[String], config: &RecordConfig) -> SnapshotResult<Recording> {
+    if !std::io::stdin().is_terminal() {
+        return Err(SnapshotError::Capture("Recording needs an interactive terminal on stdin".to_string()));
+    }
+    let key_bytes = |key: &str| {
+        KeyPress::parse(key)
+            .map(|press| press.to_bytes())
+            .ok_or_else(|| SnapshotError::Capture(format!("'{}' is not a key", key)))
+    };
+    let capture_key = key_bytes(&config.capture_key)?;
+    let stop_key = key_bytes(&config.stop_key)?;
+
+    let (cols, rows) = match config.size {
+        Some(size) => size.dimensions(),
+        None => crossterm::terminal::size().unwrap_or(TerminalSize::default().dimensions()),
+    };
+    let mut pty = PtyHandle::open(cols, rows)?;
+    let (cmd, program) = build_command(command, args, (cols, rows), &config.env, false, &ResourceLimits::default())?;
+    let mut child = pty.spawn(cmd, &program)?;
+    pty.close_slave();
+
+    let raw_mode = RawMode::enable()?;
+    let stdin = spawn_reader(Box::new(std::io::stdin()));
+    let mut stdout = std::io::stdout();
+    let start = Instant::now();
+    let mut events = Vec::new();
+    let mut stopped = false;
+
+    while !stopped {
+        while let Ok(output) = pty.rx.try_recv() {
+            stdout.write_all(&output)?;
+        }
+        stdout.flush()?;
+        if child.try_wait().ok().flatten().is_some() {
+            break;
+        }
+
+        let bytes = match stdin.recv_timeout(POLL_INTERVAL) {
+            Ok(bytes) => bytes,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let at_ms = start.elapsed().as_millis() as u64;
+        for key in split_keys(&bytes) {
+            if key == stop_key.as_slice() {
+                stopped = true;
+                break;
+            }
+            if key == capture_key.as_slice() {
+                events.push(RecordedEvent::Capture { at_ms });
+                continue;
+            }
+            pty.writer.write_all(key)?;
+            if !is_terminal_reply(key) {
+                events.push(RecordedEvent::Key {
+                    at_ms,
+                    input: input_for_key(key),
+                });
+            }
+        }
+        pty.writer.flush()?;
+    }
+
+    // Output written while exiting, e.g. leaving the alternate screen
+    while let Ok(output) = pty.rx.recv_timeout(Duration::from_millis(50)) {
+        stdout.write_all(&output)?;
+    }
+    let exit_code = child.try_wait().ok().flatten().map(|status| status.exit_code());
+    if exit_code.is_none() {
+        let _ = child.kill();
+        let _ = child.wait();
+        stdout.write_all(RESTORE_TERMINAL)?;
+    }
+    stdout.flush()?;
+    drop(raw_mode);
+
+    Ok(Recording {
+        size: (cols, rows),
+        events,
+        exit_code,
+    })
+}
+
+/// Whether bytes from the terminal answer a query rather than being typed:
+/// OSC, DCS and APC strings, cursor position and device attribute reports,
+/// mode and kitty flag reports, and focus events
+fn is_terminal_reply(key: &[u8]) -> bool {
+    match key {
+        [0x1b, b']' | b'P' | b'_', ..] => true,
+        [0x1b, b'[', b'I' | b'O'] => true,
+        [0x1b, b'[', params @ .., last] => {
+            let named = !input_for_key(key).starts_with("raw:");
+            match last {
+                b'R' => !named,
+                b'c' | b'n' | b't' => true,
+                b'y' => params.ends_with(b"$"),
+                b'u' => params.first() == Some(&b'?'),
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terminal_replies_are_not_recorded() {
+        for reply in [&b"\x1b[12;40R"[..], b"\x1b[?62;22c", b"\x1b]11;rgb:0/0/0\x07", b"\x1b[?1;2$y", b"\x1b[?1u", b"\x1b[I"] {
+            assert!(is_terminal_reply(reply), "{:?}", String::from_utf8_lossy(reply));
+        }
+        // Modified F3 has the same form as a cursor position report
+        for key in [&b"\x1b[1;5R"[..], b"\x1b[A", b"\x1b[97;5u", b"q"] {
+            assert!(!is_terminal_reply(key), "{:?}", String::from_utf8_lossy(key));
+        }
+    }
+}
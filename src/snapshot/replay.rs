@@ -0,0 +1,547 @@
+//! Reading, writing and replaying asciinema recordings.
+//!
+//! An asciicast (`.cast`, versions 2 and 3) records a terminal session as
+//! timed events: output (`o`), input (`i`, when recorded with `--stdin`),
+//! markers (`m`) and resizes (`r`). A replay starts the application in a PTY
+//! and sends the recorded input at the recorded times, optionally sped up or
+//! with long pauses cut short, and captures the screen at the markers, after
+//! every input, or at a fixed interval. The recorded output is ignored: the
+//! screens come from the application as it runs now.
+//!
+//! Going the other way, a [`CastRecorder`] set with
+//! [`RunOptions::record_cast`] records a run's output as a cast, with a marker
+//! at every captured state, so a failing run can be watched in a player.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::snapshot::replay::{replay_cast, replay_plan, Cast, ReplayCapture, ReplayConfig};
+//! use cli_vision::snapshot::{RunOptions, TerminalSize};
+//!
+//! let cast = Cast::from_file("bug.cast").unwrap();
+//! let plan = replay_plan(&cast, &ReplayConfig::default().speed(2.0).capture(ReplayCapture::Inputs));
+//! let size = TerminalSize::Custom(cast.width, cast.height);
+//! let captures = replay_cast("./my-tui", &[], &plan, size, &RunOptions::default()).unwrap();
+//! ```
+
+use serde_json::Value;
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::keys::{input_for_key, split_keys};
+use super::pool::PtyHandle;
+use super::pty::{
+    apply_limits, build_command, ingest_chunk, notify_capture, parse_sleep, resize_pty, wait_for_initial_render, wait_for_input_render,
+    wait_for_process_exit, CaptureCounters, RunOptions, StateCaptureResult, TerminalSize, Vt100Parser,
+    PROCESS_DRAIN_TIMEOUT,
+};
+use super::{SnapshotError, SnapshotResult};
+
+/// Kind of a recorded event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CastEventKind {
+    /// Data written by the application
+    Output,
+    /// Data typed by the user
+    Input,
+    /// A marker, with an optional label as data
+    Marker,
+    /// A terminal resize, with `COLSxROWS` as data
+    Resize,
+    /// Any other event code
+    Other(String),
+}
+
+/// One recorded event
+#[derive(Debug, Clone, PartialEq)]
+pub struct CastEvent {
+    /// Seconds since the start of the recording
+    pub time: f64,
+    /// Kind of event
+    pub kind: CastEventKind,
+    /// Event data
+    pub data: String,
+}
+
+/// A parsed asciicast recording
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cast {
+    /// Terminal width at the start
+    pub width: u16,
+    /// Terminal height at the start
+    pub height: u16,
+    /// Events in order
+    pub events: Vec<CastEvent>,
+}
+
+impl Cast {
+    /// Parse an asciicast v2 or v3 recording
+    pub fn parse(source: &str) -> Result<Cast, String> {
+        let mut lines = source
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+        let (_, header) = lines.next().ok_or("empty cast file")?;
+        let header: Value = serde_json::from_str(header).map_err(|e| format!("invalid header: {}", e))?;
+        let version = header["version"].as_u64();
+        let (width, height) = match version {
+            Some(2) => (&header["width"], &header["height"]),
+            Some(3) => (&header["term"]["cols"], &header["term"]["rows"]),
+            _ => return Err(format!("unsupported asciicast version {}", header["version"])),
+        };
+        let dimension = |value: &Value| value.as_u64().and_then(|n| u16::try_from(n).ok()).filter(|n| *n > 0);
+        let (Some(width), Some(height)) = (dimension(width), dimension(height)) else {
+            return Err("header has no terminal size".to_string());
+        };
+
+        let mut events = Vec::new();
+        let mut time = 0.0;
+        for (index, line) in lines {
+            let invalid = || format!("line {}: expected [time, code, data]", index + 1);
+            let event: Value = serde_json::from_str(line).map_err(|_| invalid())?;
+            let (Some(stamp), Some(code), Some(data)) = (event[0].as_f64(), event[1].as_str(), event[2].as_str()) else {
+                return Err(invalid());
+            };
+            // v3 stores the interval since the previous event
+            time = if version == Some(3) { time + stamp } else { stamp };
+            let kind = match code {
+                "o" => CastEventKind::Output,
+                "i" => CastEventKind::Input,
+                "m" => CastEventKind::Marker,
+                "r" => CastEventKind::Resize,
+                other => CastEventKind::Other(other.to_string()),
+            };
+            events.push(CastEvent {
+                time,
+                kind,
+                data: data.to_string(),
+            });
+        }
+        Ok(Cast { width, height, events })
+    }
+
+    /// Load a cast file
+    pub fn from_file(path: impl AsRef<Path>) -> std::io::Result<Cast> {
+        let path = path.as_ref();
+        Cast::parse(&std::fs::read_to_string(path)?).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+        })
+    }
+
+    /// Whether the cast recorded any input
+    pub fn has_input(&self) -> bool {
+        self.events.iter().any(|event| event.kind == CastEventKind::Input)
+    }
+
+    /// The cast in asciicast v2 format
+    pub fn to_v2(&self) -> String {
+        let header = serde_json::json!({
+            "version": 2,
+            "width": self.width,
+            "height": self.height,
+            "env": { "TERM": "xterm-256color" },
+        });
+        let mut out = header.to_string();
+        out.push('\n');
+        for event in &self.events {
+            let code = match &event.kind {
+                CastEventKind::Output => "o",
+                CastEventKind::Input => "i",
+                CastEventKind::Marker => "m",
+                CastEventKind::Resize => "r",
+                CastEventKind::Other(code) => code,
+            };
+            out.push_str(&serde_json::json!([(event.time * 1e6).round() / 1e6, code, event.data]).to_string());
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Records a run as a cast; clones share the recording
+#[derive(Debug, Clone, Default)]
+pub struct CastRecorder(Arc<Mutex<CastRecording>>);
+
+#[derive(Debug, Default)]
+struct CastRecording {
+    start: Option<Instant>,
+    cast: Option<Cast>,
+    /// Trailing bytes of an incomplete UTF-8 character, held until the rest arrives
+    pending: Vec<u8>,
+}
+
+impl CastRecorder {
+    /// An empty recorder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The recording so far, or `None` if no run has started
+    pub fn cast(&self) -> Option<Cast> {
+        self.lock().cast.clone()
+    }
+
+    /// Start a new recording of a terminal `cols` by `rows`
+    pub(crate) fn start(&self, cols: u16, rows: u16) {
+        *self.lock() = CastRecording {
+            start: Some(Instant::now()),
+            cast: Some(Cast {
+                width: cols,
+                height: rows,
+                events: Vec::new(),
+            }),
+            pending: Vec::new(),
+        };
+    }
+
+    /// Record application output
+    pub(crate) fn output(&self, bytes: &[u8]) {
+        let mut recording = self.lock();
+        recording.pending.extend_from_slice(bytes);
+        let pending = std::mem::take(&mut recording.pending);
+        let complete = match std::str::from_utf8(&pending) {
+            Ok(_) => pending.len(),
+            // An error without a length is a character cut off at the end
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => pending.len(),
+        };
+        recording.pending = pending[complete..].to_vec();
+        if complete > 0 {
+            recording.push(CastEventKind::Output, String::from_utf8_lossy(&pending[..complete]).into_owned());
+        }
+    }
+
+    /// Record a marker
+    pub(crate) fn marker(&self, label: impl Into<String>) {
+        self.lock().push(CastEventKind::Marker, label.into());
+    }
+
+    /// Record a terminal resize
+    pub(crate) fn resize(&self, cols: u16, rows: u16) {
+        self.lock().push(CastEventKind::Resize, format!("{}x{}", cols, rows));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CastRecording> {
+        self.0.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+impl CastRecording {
+    fn push(&mut self, kind: CastEventKind, data: String) {
+        let time = self.start.map_or(0.0, |start| start.elapsed().as_secs_f64());
+        if let Some(cast) = &mut self.cast {
+            cast.events.push(CastEvent { time, kind, data });
+        }
+    }
+}
+
+/// Marker label for a captured state
+pub(crate) fn state_marker(step: usize, input: Option<&str>) -> String {
+    match input {
+        Some(input) => format!("state {}: {}", step, input),
+        None => format!("state {}", step),
+    }
+}
+
+/// When a replay captures the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplayCapture {
+    /// At every marker
+    #[default]
+    Markers,
+    /// After every input event
+    Inputs,
+    /// At a fixed interval
+    Every(Duration),
+}
+
+impl ReplayCapture {
+    /// Parse `markers`, `inputs`, or an interval such as `500ms` or `2s`
+    pub fn parse(spec: &str) -> Result<ReplayCapture, String> {
+        match spec.trim().to_lowercase().as_str() {
+            "markers" => Ok(ReplayCapture::Markers),
+            "inputs" => Ok(ReplayCapture::Inputs),
+            // Intervals are written like sleep: durations
+            interval => parse_sleep(&format!("sleep:{}", interval))
+                .filter(|interval| !interval.is_zero())
+                .map(ReplayCapture::Every)
+                .ok_or_else(|| format!("invalid capture point '{}' (expected markers, inputs, or an interval like 500ms)", spec)),
+        }
+    }
+}
+
+/// How a cast is replayed
+#[derive(Debug, Clone)]
+pub struct ReplayConfig {
+    /// Playback speed; 2.0 replays twice as fast
+    pub speed: f64,
+    /// Longest pause between events, before `speed` is applied
+    pub idle_limit: Option<Duration>,
+    /// When to capture the screen
+    pub capture: ReplayCapture,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            idle_limit: None,
+            capture: ReplayCapture::default(),
+        }
+    }
+}
+
+impl ReplayConfig {
+    /// Set the playback speed
+    pub fn speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Shorten pauses between events to at most `limit`
+    pub fn idle_limit(mut self, limit: Duration) -> Self {
+        self.idle_limit = Some(limit);
+        self
+    }
+
+    /// Set when to capture the screen
+    pub fn capture(mut self, capture: ReplayCapture) -> Self {
+        self.capture = capture;
+        self
+    }
+}
+
+/// What a replay does at a point in time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayAction {
+    /// Send recorded input
+    Send(Vec<u8>),
+    /// Resize the terminal to (cols, rows)
+    Resize(u16, u16),
+    /// Capture the screen, labelled
+    Capture(String),
+}
+
+/// A replay action and when it happens, from the start of the replay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayStep {
+    pub at: Duration,
+    pub action: ReplayAction,
+}
+
+/// The timed actions replaying `cast`; always ends with a capture
+pub fn replay_plan(cast: &Cast, config: &ReplayConfig) -> Vec<ReplayStep> {
+    let speed = if config.speed.is_finite() && config.speed > 0.0 { config.speed } else { 1.0 };
+    let mut steps = Vec::new();
+    let mut at = Duration::ZERO;
+    let mut previous = 0.0;
+    let mut markers = 0;
+    for event in &cast.events {
+        let mut pause = Duration::from_secs_f64((event.time - previous).max(0.0));
+        previous = event.time;
+        if let Some(limit) = config.idle_limit {
+            pause = pause.min(limit);
+        }
+        at += pause.div_f64(speed);
+
+        let mut push = |action| steps.push(ReplayStep { at, action });
+        match &event.kind {
+            CastEventKind::Input => {
+                push(ReplayAction::Send(event.data.as_bytes().to_vec()));
+                if config.capture == ReplayCapture::Inputs {
+                    let keys: Vec<String> = split_keys(event.data.as_bytes()).into_iter().map(input_for_key).collect();
+                    // Typed or pasted text reads better as one input than as its characters
+                    let label = if keys.len() > 1 && !event.data.chars().any(char::is_control) {
+                        format!("text:{}", event.data)
+                    } else {
+                        keys.join(" ")
+                    };
+                    push(ReplayAction::Capture(label));
+                }
+            }
+            CastEventKind::Marker if config.capture == ReplayCapture::Markers => {
+                markers += 1;
+                let label = if event.data.is_empty() { markers.to_string() } else { event.data.clone() };
+                push(ReplayAction::Capture(format!("marker:{}", label)));
+            }
+            CastEventKind::Resize => {
+                if let Some(TerminalSize::Custom(cols, rows)) = TerminalSize::from_str(&event.data) {
+                    push(ReplayAction::Resize(cols, rows));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let ReplayCapture::Every(interval) = config.capture {
+        let mut tick = interval;
+        while tick <= at {
+            // After any action at the same time, so the capture sees it
+            let index = steps.partition_point(|step| step.at <= tick);
+            let label = format!("at:{:.2}s", tick.as_secs_f64());
+            steps.insert(index, ReplayStep { at: tick, action: ReplayAction::Capture(label) });
+            tick += interval;
+        }
+    }
+    if !matches!(steps.last(), Some(ReplayStep { action: ReplayAction::Capture(_), .. })) {
+        steps.push(ReplayStep {
+            at,
+            action: ReplayAction::Capture("end".to_string()),
+        });
+    }
+    steps
+}
+
+/// Run an application and replay a plan against it.
+///
+/// Returns the initial state and one state per capture in the plan.
+pub fn replay_cast(
+    command: &str,
+    args: &[String],
+    plan: &[ReplayStep],
+    size: TerminalSize,
+    options: &RunOptions,
+) -> SnapshotResult<Vec<StateCaptureResult>> {
+    let (cols, rows) = size.dimensions();
+    let mut parser = Vt100Parser::new(u32::from(cols), u32::from(rows));
+    parser.terminal_mut().identity = options.identity.clone();
+    parser.terminal_mut().set_theme(options.theme.clone());
+    if let Some(cast) = &options.cast {
+        parser.record_cast(cast.clone());
+    }
+    if options.capture_ansi {
+        parser.keep_raw_output();
+    }
+
+    let mut pty = PtyHandle::open(cols, rows)?;
+    let (cmd, program) = build_command(command, args, (cols, rows), &options.env, options.hermetic, &options.limits)?;
+    let mut child = pty.spawn(cmd, &program)?;
+    apply_limits(child.as_mut(), &options.limits, &program)?;
+    pty.close_slave();
+
+    let mut counters = CaptureCounters::default();
+    let mut captures = Vec::new();
+    wait_for_initial_render(&pty.rx, &mut parser, &mut pty.writer);
+    captures.push(counters.capture(&mut parser, options, 0, None, None, child.as_mut()));
+    notify_capture(options, &captures);
+
+    let start = Instant::now();
+    for step in plan {
+        // Keep the screen current until the step is due
+        while let Some(remaining) = step.at.checked_sub(start.elapsed()).filter(|d| !d.is_zero()) {
+            match pty.rx.recv_timeout(remaining) {
+                Ok(chunk) => ingest_chunk(&chunk, &mut parser, &mut pty.writer),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    std::thread::sleep(remaining);
+                    break;
+                }
+            }
+        }
+
+        match &step.action {
+            ReplayAction::Send(bytes) => {
+                pty.writer
+                    .write_all(bytes)
+                    .and_then(|_| pty.writer.flush())
+                    .map_err(|e| SnapshotError::Capture(format!("Failed to send recorded input: {}", e)))?;
+            }
+            ReplayAction::Resize(cols, rows) => resize_pty(pty.master.as_ref(), &mut parser, *cols, *rows)?,
+            ReplayAction::Capture(label) => {
+                wait_for_input_render(&pty.rx, &mut parser, &mut pty.writer);
+                let step = captures.len();
+                captures.push(counters.capture(&mut parser, options, step, Some(label.clone()), None, child.as_mut()));
+                notify_capture(options, &captures);
+            }
+        }
+    }
+
+    let PtyHandle { master: _master, writer, rx, .. } = pty;
+    drop(writer);
+    wait_for_process_exit(child.as_mut(), &rx, &mut parser, PROCESS_DRAIN_TIMEOUT);
+    if child.try_wait().ok().flatten().is_none() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    Ok(captures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAST: &str = r#"{"version": 2, "width": 80, "height": 24}
+[0.5, "o", "$ "]
+[1.0, "i", "jj"]
+[1.2, "m", "list open"]
+[31.0, "i", "\u001b[A"]
+[31.5, "r", "100x30"]
+[32.0, "m", ""]
+"#;
+
+    #[test]
+    fn test_cast_parses_v2_and_v3() {
+        let cast = Cast::parse(CAST).unwrap();
+        assert_eq!((cast.width, cast.height, cast.events.len()), (80, 24, 6));
+        assert_eq!(cast.events[3].kind, CastEventKind::Input);
+        assert_eq!(cast.events[3].data, "\x1b[A");
+
+        let v3 = "{\"version\": 3, \"term\": {\"cols\": 90, \"rows\": 20}}\n# comment\n[0.5, \"i\", \"q\"]\n[0.25, \"m\", \"\"]\n";
+        let cast = Cast::parse(v3).unwrap();
+        assert_eq!((cast.width, cast.height), (90, 20));
+        assert_eq!(cast.events[1].time, 0.75);
+        assert!(Cast::parse("{\"version\": 1}").is_err());
+    }
+
+    #[test]
+    fn test_recorder_writes_v2_casts() {
+        let recorder = CastRecorder::new();
+        let mut parser = Vt100Parser::new(20, 5);
+        parser.record_cast(recorder.clone());
+        // "é" split across two chunks is recorded whole
+        parser.process(b"caf\xc3");
+        parser.process(b"\xa9\r\n");
+        recorder.marker(state_marker(1, Some("enter")));
+        recorder.resize(30, 6);
+
+        let cast = Cast::parse(&recorder.cast().unwrap().to_v2()).unwrap();
+        assert_eq!((cast.width, cast.height), (20, 5));
+        let events: Vec<(&CastEventKind, &str)> = cast.events.iter().map(|e| (&e.kind, e.data.as_str())).collect();
+        assert_eq!(
+            events,
+            [
+                (&CastEventKind::Output, "caf"),
+                (&CastEventKind::Output, "é\r\n"),
+                (&CastEventKind::Marker, "state 1: enter"),
+                (&CastEventKind::Resize, "30x6"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_scales_timing_and_places_captures() {
+        let cast = Cast::parse(CAST).unwrap();
+        let config = ReplayConfig::default().speed(2.0).idle_limit(Duration::from_secs(2));
+        let plan = replay_plan(&cast, &config);
+        let summary: Vec<(u128, &ReplayAction)> = plan.iter().map(|s| (s.at.as_millis(), &s.action)).collect();
+        assert_eq!(
+            summary,
+            [
+                (500, &ReplayAction::Send(b"jj".to_vec())),
+                (600, &ReplayAction::Capture("marker:list open".to_string())),
+                (1600, &ReplayAction::Send(b"\x1b[A".to_vec())),
+                (1850, &ReplayAction::Resize(100, 30)),
+                (2100, &ReplayAction::Capture("marker:2".to_string())),
+            ]
+        );
+
+        let inputs = replay_plan(&cast, &ReplayConfig::default().capture(ReplayCapture::Inputs));
+        let labels: Vec<&ReplayAction> = inputs.iter().map(|s| &s.action).filter(|a| matches!(a, ReplayAction::Capture(_))).collect();
+        assert_eq!(labels, [&ReplayAction::Capture("text:jj".to_string()), &ReplayAction::Capture("up".to_string()), &ReplayAction::Capture("end".to_string())]);
+        let every = replay_plan(&cast, &ReplayConfig::default().capture(ReplayCapture::parse("10s").unwrap()));
+        assert_eq!(every.iter().filter(|s| matches!(s.action, ReplayAction::Capture(_))).count(), 4);
+    }
+}
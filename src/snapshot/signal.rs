@@ -0,0 +1,158 @@
+//! Sending Unix signals to the application under test.
+//!
+//! A `signal:NAME` input (e.g. `signal:int`, `signal:SIGTSTP`) delivers the
+//! signal to the application's process group, as the terminal's line
+//! discipline would for Ctrl+C or Ctrl+Z, and the resulting screen is
+//! captured. This tests graceful shutdown screens and job control even when
+//! the application has turned off `ISIG` and reads Ctrl+C as a key.
+
+use serde::{Deserialize, Serialize};
+
+/// A signal that can be sent as an input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sig {
+    /// SIGINT (Ctrl+C)
+    Int,
+    /// SIGTERM
+    Term,
+    /// SIGTSTP (Ctrl+Z)
+    Tstp,
+    /// SIGCONT
+    Cont,
+    /// SIGHUP (terminal closed)
+    Hup,
+    /// SIGQUIT (Ctrl+\)
+    Quit,
+    /// SIGUSR1
+    Usr1,
+    /// SIGUSR2
+    Usr2,
+    /// SIGWINCH (normally sent by `resize:`)
+    Winch,
+    /// SIGKILL
+    Kill,
+}
+
+impl Sig {
+    const ALL: [Sig; 10] = [
+        Sig::Int,
+        Sig::Term,
+        Sig::Tstp,
+        Sig::Cont,
+        Sig::Hup,
+        Sig::Quit,
+        Sig::Usr1,
+        Sig::Usr2,
+        Sig::Winch,
+        Sig::Kill,
+    ];
+
+    /// Conventional name, e.g. `SIGINT`
+    pub fn name(self) -> &'static str {
+        match self {
+            Sig::Int => "SIGINT",
+            Sig::Term => "SIGTERM",
+            Sig::Tstp => "SIGTSTP",
+            Sig::Cont => "SIGCONT",
+            Sig::Hup => "SIGHUP",
+            Sig::Quit => "SIGQUIT",
+            Sig::Usr1 => "SIGUSR1",
+            Sig::Usr2 => "SIGUSR2",
+            Sig::Winch => "SIGWINCH",
+            Sig::Kill => "SIGKILL",
+        }
+    }
+
+    /// Look up a signal by name, with or without the `SIG` prefix, in any case
+    pub fn from_name(name: &str) -> Option<Sig> {
+        let name = name.trim().to_uppercase();
+        let name = name.strip_prefix("SIG").unwrap_or(&name);
+        Sig::ALL.into_iter().find(|sig| &sig.name()[3..] == name)
+    }
+
+    #[cfg(unix)]
+    fn to_nix(self) -> nix::sys::signal::Signal {
+        use nix::sys::signal::Signal;
+        match self {
+            Sig::Int => Signal::SIGINT,
+            Sig::Term => Signal::SIGTERM,
+            Sig::Tstp => Signal::SIGTSTP,
+            Sig::Cont => Signal::SIGCONT,
+            Sig::Hup => Signal::SIGHUP,
+            Sig::Quit => Signal::SIGQUIT,
+            Sig::Usr1 => Signal::SIGUSR1,
+            Sig::Usr2 => Signal::SIGUSR2,
+            Sig::Winch => Signal::SIGWINCH,
+            Sig::Kill => Signal::SIGKILL,
+        }
+    }
+}
+
+impl std::fmt::Display for Sig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Parse a `signal:NAME` input.
+///
+/// Returns `None` for other inputs and `Some(Err(..))` for an unknown signal.
+pub fn parse_signal_input(input: &str) -> Option<Result<Sig, String>> {
+    let (prefix, name) = input.trim().split_once(':')?;
+    if !prefix.eq_ignore_ascii_case("signal") {
+        return None;
+    }
+    Some(Sig::from_name(name).ok_or_else(|| {
+        format!("unknown signal '{}' (expected int, term, tstp, cont, hup, quit, usr1, usr2, winch or kill)", name.trim())
+    }))
+}
+
+/// Send a signal to the process group of the process `pid`
+#[cfg(unix)]
+pub fn send_signal(pid: Option<u32>, sig: Sig) -> std::io::Result<()> {
+    use nix::unistd::Pid;
+
+    let pid = pid.ok_or_else(|| std::io::Error::other("the application has no process id"))?;
+    let pgid = nix::unistd::getpgid(Some(Pid::from_raw(pid as i32))).map_err(std::io::Error::from)?;
+    nix::sys::signal::killpg(pgid, sig.to_nix()).map_err(std::io::Error::from)
+}
+
+/// Send a signal to the process group of the process `pid`
+#[cfg(not(unix))]
+pub fn send_signal(_pid: Option<u32>, sig: Sig) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("{} cannot be sent on this platform", sig),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signal_tokens() {
+        assert_eq!(parse_signal_input("signal:int"), Some(Ok(Sig::Int)));
+        assert_eq!(parse_signal_input("SIGNAL:SIGTSTP"), Some(Ok(Sig::Tstp)));
+        assert_eq!(parse_signal_input("signal: usr1 "), Some(Ok(Sig::Usr1)));
+        assert!(matches!(parse_signal_input("signal:stop"), Some(Err(_))));
+        assert_eq!(parse_signal_input("ctrl+c"), None);
+        assert_eq!(Sig::Term.to_string(), "SIGTERM");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signal_reaches_process_group() {
+        use std::os::unix::process::{CommandExt, ExitStatusExt};
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        send_signal(Some(child.id()), Sig::Term).unwrap();
+        let status = child.wait().unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+    }
+}
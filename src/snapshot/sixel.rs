@@ -0,0 +1,277 @@
+//! Sixel graphics decoding.
+//!
+//! Sixel data arrives as a DCS string (`ESC P p1 ; p2 ; p3 q <data> ESC \`).
+//! Each data character from `?` to `~` encodes a column of six vertical
+//! pixels in the current color; `#` selects or defines colors, `!` repeats
+//! the next character, `$` returns to the start of the band and `-` moves
+//! to the next band. [`SixelDecoder`] is fed the data bytes one at a time
+//! and produces an RGBA image, with pixels never drawn left transparent.
+
+use image::{Rgba, RgbaImage};
+
+/// Largest width or height accepted, so corrupt data cannot exhaust memory
+pub const MAX_SIXEL_DIMENSION: u32 = 4096;
+
+/// Color registers available to an image
+const PALETTE_SIZE: usize = 256;
+
+/// VT340 default color registers 0-15, in percent
+const VT340_PALETTE: [[u32; 3]; 16] = [
+    [0, 0, 0],
+    [20, 20, 80],
+    [80, 13, 13],
+    [20, 80, 20],
+    [80, 20, 80],
+    [20, 80, 80],
+    [80, 80, 20],
+    [53, 53, 53],
+    [26, 26, 26],
+    [33, 33, 60],
+    [60, 26, 26],
+    [33, 60, 33],
+    [60, 33, 60],
+    [33, 60, 60],
+    [60, 60, 33],
+    [80, 80, 80],
+];
+
+/// Command whose numeric parameters are being collected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    /// `"Pan;Pad;Ph;Pv` raster attributes
+    Raster,
+    /// `#Pc` select or `#Pc;Pu;Px;Py;Pz` define a color
+    Color,
+    /// `!Pn` repeat introducer
+    Repeat,
+}
+
+/// Incremental sixel decoder
+#[derive(Debug, Clone)]
+pub struct SixelDecoder {
+    palette: Vec<[u8; 3]>,
+    color: usize,
+    /// Pixels drawn so far, row-major, `None` where nothing was drawn
+    rows: Vec<Vec<Option<[u8; 3]>>>,
+    /// Size declared by raster attributes
+    declared: (u32, u32),
+    x: u32,
+    band_top: u32,
+    repeat: u32,
+    command: Option<Command>,
+    params: Vec<u32>,
+}
+
+impl Default for SixelDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SixelDecoder {
+    /// Decoder with the VT340 default palette
+    pub fn new() -> Self {
+        let mut palette = vec![[0u8; 3]; PALETTE_SIZE];
+        for (slot, rgb) in palette.iter_mut().zip(VT340_PALETTE) {
+            *slot = rgb.map(percent_to_u8);
+        }
+        Self {
+            palette,
+            color: 0,
+            rows: Vec::new(),
+            declared: (0, 0),
+            x: 0,
+            band_top: 0,
+            repeat: 1,
+            command: None,
+            params: Vec::new(),
+        }
+    }
+
+    /// Feed one byte of sixel data (everything after the `q`)
+    pub fn put(&mut self, byte: u8) {
+        if self.command.is_some() {
+            match byte {
+                b'0'..=b'9' => {
+                    if self.params.is_empty() {
+                        self.params.push(0);
+                    }
+                    let last = self.params.last_mut().expect("params is not empty");
+                    *last = last.saturating_mul(10).saturating_add(u32::from(byte - b'0'));
+                    return;
+                }
+                b';' => {
+                    if self.params.is_empty() {
+                        self.params.push(0);
+                    }
+                    self.params.push(0);
+                    return;
+                }
+                _ => self.finish_command(),
+            }
+        }
+
+        match byte {
+            b'"' => self.command = Some(Command::Raster),
+            b'#' => self.command = Some(Command::Color),
+            b'!' => self.command = Some(Command::Repeat),
+            b'$' => self.x = 0,
+            b'-' => {
+                self.x = 0;
+                self.band_top = self.band_top.saturating_add(6);
+            }
+            b'?'..=b'~' => {
+                let bits = byte - b'?';
+                let count = std::mem::replace(&mut self.repeat, 1);
+                self.draw(bits, count);
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply the command whose parameters were being collected
+    fn finish_command(&mut self) {
+        let params = std::mem::take(&mut self.params);
+        let param = |idx: usize| params.get(idx).copied().unwrap_or(0);
+        match self.command.take() {
+            Some(Command::Raster) => {
+                self.declared = (param(2).min(MAX_SIXEL_DIMENSION), param(3).min(MAX_SIXEL_DIMENSION));
+            }
+            Some(Command::Color) => {
+                let register = param(0) as usize % PALETTE_SIZE;
+                if params.len() >= 5 {
+                    let (x, y, z) = (param(2), param(3), param(4));
+                    match param(1) {
+                        1 => self.palette[register] = hls_to_rgb(x, y, z),
+                        2 => self.palette[register] = [x, y, z].map(percent_to_u8),
+                        _ => {}
+                    }
+                }
+                self.color = register;
+            }
+            Some(Command::Repeat) => self.repeat = param(0).max(1),
+            None => {}
+        }
+    }
+
+    /// Draw `count` columns of the six-pixel pattern `bits` at the current position
+    fn draw(&mut self, bits: u8, count: u32) {
+        let color = self.palette[self.color];
+        let end = self.x.saturating_add(count).min(MAX_SIXEL_DIMENSION);
+        for dy in 0..6 {
+            let y = self.band_top + dy;
+            if bits & (1 << dy) == 0 || y >= MAX_SIXEL_DIMENSION {
+                continue;
+            }
+            if self.rows.len() <= y as usize {
+                self.rows.resize(y as usize + 1, Vec::new());
+            }
+            let row = &mut self.rows[y as usize];
+            if row.len() < end as usize {
+                row.resize(end as usize, None);
+            }
+            for pixel in &mut row[self.x as usize..end as usize] {
+                *pixel = Some(color);
+            }
+        }
+        self.x = end;
+    }
+
+    /// Finish decoding. Pixels never drawn are filled with `background`,
+    /// or left transparent when it is `None` (`p2 = 1`).
+    ///
+    /// Returns `None` for an empty image.
+    pub fn finish(mut self, background: Option<[u8; 3]>) -> Option<RgbaImage> {
+        self.finish_command();
+        let drawn_width = self.rows.iter().map(Vec::len).max().unwrap_or(0) as u32;
+        let width = drawn_width.max(self.declared.0);
+        let height = (self.rows.len() as u32).max(self.declared.1);
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let blank = match background {
+            Some([r, g, b]) => Rgba([r, g, b, 255]),
+            None => Rgba([0, 0, 0, 0]),
+        };
+        let mut image = RgbaImage::from_pixel(width, height, blank);
+        for (y, row) in self.rows.iter().enumerate() {
+            for (x, pixel) in row.iter().enumerate() {
+                if let Some([r, g, b]) = *pixel {
+                    image.put_pixel(x as u32, y as u32, Rgba([r, g, b, 255]));
+                }
+            }
+        }
+        Some(image)
+    }
+}
+
+fn percent_to_u8(percent: u32) -> u8 {
+    (percent.min(100) * 255 / 100) as u8
+}
+
+/// Convert DEC HLS (hue 0 = blue, lightness and saturation in percent) to RGB
+fn hls_to_rgb(hue: u32, lightness: u32, saturation: u32) -> [u8; 3] {
+    // DEC hues are rotated: 0 is blue, 120 red, 240 green
+    let h = ((hue + 240) % 360) as f64 / 360.0;
+    let l = f64::from(lightness.min(100)) / 100.0;
+    let s = f64::from(saturation.min(100)) / 100.0;
+    if s == 0.0 {
+        let v = (l * 255.0).round() as u8;
+        return [v, v, v];
+    }
+    let q = if l < 0.5 { l * (1.0 + s) } else { l + s - l * s };
+    let p = 2.0 * l - q;
+    let channel = |t: f64| {
+        let t = t.rem_euclid(1.0);
+        let v = if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        } else if t < 0.5 {
+            q
+        } else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        } else {
+            p
+        };
+        (v * 255.0).round() as u8
+    };
+    [channel(h + 1.0 / 3.0), channel(h), channel(h - 1.0 / 3.0)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(data: &[u8], background: Option<[u8; 3]>) -> Option<RgbaImage> {
+        let mut decoder = SixelDecoder::new();
+        for &byte in data {
+            decoder.put(byte);
+        }
+        decoder.finish(background)
+    }
+
+    #[test]
+    fn test_decodes_bands_colors_and_repeats() {
+        // Register 1 redefined as pure red; 3 full columns, then one pixel in the second band
+        let image = decode(b"#1;2;100;0;0!3~-@", None).unwrap();
+        assert_eq!(image.dimensions(), (3, 7));
+        assert_eq!(image.get_pixel(2, 5), &Rgba([255, 0, 0, 255]));
+        assert_eq!(image.get_pixel(0, 6), &Rgba([255, 0, 0, 255]));
+        assert_eq!(image.get_pixel(1, 6)[3], 0);
+    }
+
+    #[test]
+    fn test_raster_attributes_and_background() {
+        let image = decode(b"\"1;1;4;2#0@", Some([9, 9, 9])).unwrap();
+        assert_eq!(image.dimensions(), (4, 2));
+        assert_eq!(image.get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+        assert_eq!(image.get_pixel(3, 1), &Rgba([9, 9, 9, 255]));
+        assert!(decode(b"#1", None).is_none());
+    }
+
+    #[test]
+    fn test_hls_hues_are_rotated() {
+        assert_eq!(hls_to_rgb(0, 50, 100), [0, 0, 255]);
+        assert_eq!(hls_to_rgb(120, 50, 100), [255, 0, 0]);
+        assert_eq!(hls_to_rgb(240, 50, 100), [0, 255, 0]);
+    }
+}
@@ -0,0 +1,376 @@
+//! Long-running soak tests.
+//!
+//! A soak run keeps one application instance alive and loops a sequence of
+//! inputs for a fixed wall time. Screenshots and memory usage are sampled
+//! periodically, and the run fails if the application exits, its memory
+//! grows beyond a threshold, or the screen after an iteration drifts from
+//! the screen after the first iteration.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use cli_vision::snapshot::soak::{run_soak, SoakConfig};
+//!
+//! let inputs = vec!["tab".to_string(), "enter".to_string(), "esc".to_string()];
+//! let config = SoakConfig::new(inputs, Duration::from_secs(30 * 60));
+//! let report = run_soak("./my-tui", &[], &config).unwrap();
+//! assert!(report.passed, "{:?}", report.failures);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use portable_pty::{native_pty_system, PtySize};
+
+use super::pty::{
+    apply_limits, build_command, render_to_png, send_input, spawn_reader, wait_for_initial_render, wait_for_input_render, InputTarget,
+    PtyInput, RunOptions, TerminalSize, Vt100Parser,
+};
+use super::{SnapshotError, SnapshotResult};
+
+/// Configuration for a soak run
+#[derive(Debug, Clone)]
+pub struct SoakConfig {
+    /// Inputs sent in order, repeated until the duration is reached
+    pub inputs: Vec<String>,
+    /// Wall time to keep looping
+    pub duration: Duration,
+    /// Minimum time between samples (the first and last iteration are always sampled)
+    pub sample_interval: Duration,
+    /// Delay before each input (milliseconds)
+    pub input_delay_ms: u64,
+    /// Terminal size
+    pub size: TerminalSize,
+    /// Capture options (theme, environment, limits, ...)
+    pub options: RunOptions,
+    /// Fail if resident memory grows by more than this percentage over the first sample
+    pub max_memory_growth_percent: f64,
+    /// Fail if more than this percentage of cells differ from the first iteration
+    pub max_drift_percent: f64,
+}
+
+impl SoakConfig {
+    /// Loop `inputs` for `duration` with default sampling and thresholds
+    pub fn new(inputs: Vec<String>, duration: Duration) -> Self {
+        Self {
+            inputs,
+            duration,
+            sample_interval: Duration::from_secs(60),
+            input_delay_ms: 100,
+            size: TerminalSize::default(),
+            options: RunOptions::default(),
+            max_memory_growth_percent: 50.0,
+            max_drift_percent: 5.0,
+        }
+    }
+
+    /// Set the minimum time between samples
+    pub fn sample_interval(mut self, interval: Duration) -> Self {
+        self.sample_interval = interval;
+        self
+    }
+
+    /// Set the delay before each input
+    pub fn input_delay(mut self, delay_ms: u64) -> Self {
+        self.input_delay_ms = delay_ms;
+        self
+    }
+
+    /// Set the terminal size
+    pub fn size(mut self, size: TerminalSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Set capture options
+    pub fn options(mut self, options: RunOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set the allowed memory growth in percent
+    pub fn max_memory_growth(mut self, percent: f64) -> Self {
+        self.max_memory_growth_percent = percent;
+        self
+    }
+
+    /// Set the allowed layout drift in percent of cells
+    pub fn max_drift(mut self, percent: f64) -> Self {
+        self.max_drift_percent = percent;
+        self
+    }
+}
+
+/// A periodic sample taken at the end of an iteration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SoakSample {
+    /// Iteration the sample was taken after (1-based)
+    pub iteration: usize,
+    /// Seconds since the application started
+    pub elapsed_secs: f64,
+    /// Resident memory of the application, where the platform reports it
+    pub rss_bytes: Option<u64>,
+    /// Percentage of cells differing from the first iteration
+    pub drift_percent: f64,
+    /// PNG screenshot
+    #[serde(skip)]
+    pub image_data: Vec<u8>,
+}
+
+/// Why a soak run failed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SoakFailure {
+    /// The application exited during the run
+    Crashed {
+        iteration: usize,
+        exit_code: u32,
+    },
+    /// Resident memory grew beyond the threshold
+    MemoryGrowth {
+        baseline_bytes: u64,
+        final_bytes: u64,
+        growth_percent: f64,
+    },
+    /// The screen after an iteration differs from the first iteration
+    LayoutDrift {
+        iteration: usize,
+        drift_percent: f64,
+    },
+    /// A wait input timed out
+    WaitTimedOut {
+        iteration: usize,
+        condition: String,
+    },
+}
+
+/// Result of a soak run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SoakReport {
+    /// Completed iterations of the input loop
+    pub iterations: usize,
+    /// Wall time of the run in seconds
+    pub duration_secs: f64,
+    /// Periodic samples
+    pub samples: Vec<SoakSample>,
+    /// Failures detected (empty if the run passed)
+    pub failures: Vec<SoakFailure>,
+    /// Whether the run passed
+    pub passed: bool,
+}
+
+/// Loop inputs against one application instance for the configured duration.
+///
+/// Stops early on a crash or layout drift; memory growth is checked once
+/// the loop ends.
+pub fn run_soak(command: &str, args: &[String], config: &SoakConfig) -> SnapshotResult<SoakReport> {
+    if config.inputs.is_empty() {
+        return Err(SnapshotError::Capture("Soak run needs at least one input".to_string()));
+    }
+    let actions = config.inputs.iter().map(|input| PtyInput::parse(input)).collect::<SnapshotResult<Vec<_>>>()?;
+
+    let (cols, rows) = config.size.dimensions();
+    let options = &config.options;
+    let mut parser = Vt100Parser::new(u32::from(cols), u32::from(rows));
+    parser.terminal_mut().identity = options.identity.clone();
+    parser.terminal_mut().set_theme(options.theme.clone());
+
+    let pair = native_pty_system()
+        .openpty(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+        .map_err(|e| SnapshotError::Capture(format!("Failed to open PTY: {}", e)))?;
+    let (cmd, program) = build_command(command, args, (cols, rows), &options.env, options.hermetic, &options.limits)?;
+    let mut child = pair
+        .slave
+        .spawn_command(cmd)
+        .map_err(|e| SnapshotError::Capture(format!("Failed to spawn '{}': {}", program, e)))?;
+    apply_limits(child.as_mut(), &options.limits, &program)?;
+    drop(pair.slave);
+    let pid = child.process_id();
+
+    let reader = pair
+        .master
+        .try_clone_reader()
+        .map_err(|e| SnapshotError::Capture(format!("Failed to clone PTY reader: {}", e)))?;
+    let mut writer = pair
+        .master
+        .take_writer()
+        .map_err(|e| SnapshotError::Capture(format!("Failed to take PTY writer: {}", e)))?;
+    let rx = spawn_reader(reader);
+
+    let start = Instant::now();
+    wait_for_initial_render(&rx, &mut parser, &mut writer);
+
+    let mut samples: Vec<SoakSample> = Vec::new();
+    let mut failures = Vec::new();
+    let mut first_screen: Option<Vec<Vec<char>>> = None;
+    let mut next_sample = start;
+    let mut iterations = 0;
+
+    'soak: while start.elapsed() < config.duration {
+        for action in &actions {
+            let delay = action.delay(Duration::from_millis(config.input_delay_ms));
+            if !delay.is_zero() {
+                thread::sleep(delay);
+            }
+            let target = InputTarget {
+                master: pair.master.as_ref(),
+                writer: &mut writer,
+                rx: &rx,
+                pid,
+            };
+            // A send error usually means the application is gone, which the exit check reports
+            let sent = send_input(target, &mut parser, action, options);
+            if sent.is_err() {
+                wait_for_input_render(&rx, &mut parser, &mut writer);
+            }
+
+            if let Ok(Some(status)) = child.try_wait() {
+                failures.push(SoakFailure::Crashed {
+                    iteration: iterations + 1,
+                    exit_code: status.exit_code(),
+                });
+                break 'soak;
+            }
+            match sent {
+                Ok(Some(outcome)) if outcome.timed_out => {
+                    failures.push(SoakFailure::WaitTimedOut {
+                        iteration: iterations + 1,
+                        condition: outcome.condition,
+                    });
+                    break 'soak;
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(err);
+                }
+            }
+        }
+        iterations += 1;
+
+        let screen = &parser.terminal().buffer;
+        let drift_percent = first_screen.as_ref().map_or(0.0, |first| screen_drift_percent(first, screen));
+        if first_screen.is_none() {
+            first_screen = Some(screen.clone());
+        }
+
+        let drifted = drift_percent > config.max_drift_percent;
+        let out_of_time = start.elapsed() >= config.duration;
+        if Instant::now() >= next_sample || drifted || out_of_time {
+            samples.push(SoakSample {
+                iteration: iterations,
+                elapsed_secs: start.elapsed().as_secs_f64(),
+                rss_bytes: pid.and_then(process_rss_bytes),
+                drift_percent,
+                image_data: render_to_png(&parser, &options.render),
+            });
+            next_sample = Instant::now() + config.sample_interval;
+        }
+        if drifted {
+            failures.push(SoakFailure::LayoutDrift {
+                iteration: iterations,
+                drift_percent,
+            });
+            break;
+        }
+    }
+
+    let mut memory = samples.iter().filter_map(|s| s.rss_bytes);
+    if let (Some(baseline), Some(last)) = (memory.clone().next(), memory.next_back())
+        && baseline > 0
+    {
+        let growth_percent = (last as f64 - baseline as f64) * 100.0 / baseline as f64;
+        if growth_percent > config.max_memory_growth_percent {
+            failures.push(SoakFailure::MemoryGrowth {
+                baseline_bytes: baseline,
+                final_bytes: last,
+                growth_percent,
+            });
+        }
+    }
+
+    drop(writer);
+    if child.try_wait().ok().flatten().is_none() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    Ok(SoakReport {
+        iterations,
+        duration_secs: start.elapsed().as_secs_f64(),
+        samples,
+        passed: failures.is_empty(),
+        failures,
+    })
+}
+
+/// Percentage of cells that differ between two screens (100 if the sizes differ)
+pub fn screen_drift_percent(first: &[Vec<char>], current: &[Vec<char>]) -> f64 {
+    let cells: usize = first.iter().map(Vec::len).sum();
+    let same_size = first.len() == current.len() && first.iter().zip(current).all(|(a, b)| a.len() == b.len());
+    if !same_size {
+        return 100.0;
+    }
+    if cells == 0 {
+        return 0.0;
+    }
+    let differing = first
+        .iter()
+        .zip(current)
+        .flat_map(|(a, b)| a.iter().zip(b))
+        .filter(|(a, b)| a != b)
+        .count();
+    differing as f64 * 100.0 / cells as f64
+}
+
+/// Resident set size of a process, where the platform reports it (Linux `/proc`)
+pub fn process_rss_bytes(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_screen_drift_counts_changed_cells() {
+        let first = vec![vec!['a', 'b'], vec!['c', 'd']];
+        assert_eq!(screen_drift_percent(&first, &first), 0.0);
+        assert_eq!(screen_drift_percent(&first, &[vec!['a', 'x'], vec!['c', 'd']]), 25.0);
+        assert_eq!(screen_drift_percent(&first, &[vec!['a', 'b']]), 100.0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_rss_is_read_for_the_current_process() {
+        assert!(process_rss_bytes(std::process::id()).unwrap() > 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exiting_application_fails_the_soak() {
+        let config = SoakConfig::new(vec!["enter".to_string()], Duration::from_secs(10)).input_delay(0);
+        let report = run_soak("/bin/sh", &["-c".to_string(), "read line; exit 3".to_string()], &config).unwrap();
+        assert!(!report.passed);
+        assert!(matches!(report.failures[0], SoakFailure::Crashed { .. }));
+    }
+
+    #[test]
+    fn test_malformed_inputs_are_rejected_before_the_run() {
+        for input in ["wait-regex:(unclosed", "signal:STOPP"] {
+            let config = SoakConfig::new(vec![input.to_string()], Duration::from_secs(10));
+            assert!(run_soak("/bin/sh", &[], &config).is_err());
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Color themes for the terminal renderer.
+//!
+//! A [`Theme`] holds the 16-color ANSI palette and the default foreground
+//! and background. Built-in themes can be selected by name; custom themes
+//! are loaded from JSON files with hex colors:
+//!
+//! ```json
+//! {
+//!   "name": "my-theme",
+//!   "foreground": "#d4d4d4",
+//!   "background": "#1e1e1e",
+//!   "palette": ["#000000", "#cd3131", "#0dbc79", "#e5e510", "#2472c8", "#bc3fbc", "#11a8cd", "#e5e5e5",
+//!               "#666666", "#f14c4c", "#23d18b", "#f5f543", "#3b8eea", "#d670d6", "#29b8db", "#ffffff"]
+//! }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Names accepted by [`Theme::builtin`]
+pub const BUILTIN_THEMES: &[&str] = &["default", "vscode-dark", "solarized", "light"];
+
+/// Standard ANSI palette used by the default and vscode-dark themes
+pub const DEFAULT_PALETTE: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 49, 49],
+    [13, 188, 121],
+    [229, 229, 16],
+    [36, 114, 200],
+    [188, 63, 188],
+    [17, 168, 205],
+    [229, 229, 229],
+    [102, 102, 102],
+    [241, 76, 76],
+    [35, 209, 139],
+    [245, 245, 67],
+    [59, 142, 234],
+    [214, 112, 214],
+    [41, 184, 219],
+    [255, 255, 255],
+];
+
+/// ANSI palette of the solarized theme
+pub const SOLARIZED_PALETTE: [[u8; 3]; 16] = [
+    [7, 54, 66],
+    [220, 50, 47],
+    [133, 153, 0],
+    [181, 137, 0],
+    [38, 139, 210],
+    [211, 54, 130],
+    [42, 161, 152],
+    [238, 232, 213],
+    [0, 43, 54],
+    [203, 75, 22],
+    [88, 110, 117],
+    [101, 123, 131],
+    [131, 148, 150],
+    [108, 113, 196],
+    [147, 161, 161],
+    [253, 246, 227],
+];
+
+/// ANSI palette of the light theme
+pub const LIGHT_PALETTE: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 49, 49],
+    [0, 188, 0],
+    [148, 152, 0],
+    [4, 81, 165],
+    [188, 5, 188],
+    [5, 152, 188],
+    [85, 85, 85],
+    [102, 102, 102],
+    [205, 49, 49],
+    [20, 206, 20],
+    [181, 186, 0],
+    [4, 81, 165],
+    [188, 5, 188],
+    [5, 152, 188],
+    [165, 165, 165],
+];
+
+/// Renderer color theme
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    /// Theme name
+    pub name: String,
+    /// ANSI colors 0-15 (normal then bright)
+    pub palette: [[u8; 3]; 16],
+    /// Default text color
+    pub foreground: [u8; 3],
+    /// Default background color
+    pub background: [u8; 3],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            palette: DEFAULT_PALETTE,
+            foreground: [255, 255, 255],
+            background: [0, 0, 0],
+        }
+    }
+}
+
+/// On-disk theme format with `#rrggbb` colors
+#[derive(Debug, Serialize, Deserialize)]
+struct ThemeFile {
+    #[serde(default)]
+    name: Option<String>,
+    foreground: String,
+    background: String,
+    palette: Vec<String>,
+}
+
+impl Theme {
+    /// VS Code's default dark terminal colors
+    pub fn vscode_dark() -> Self {
+        Self {
+            name: "vscode-dark".to_string(),
+            palette: DEFAULT_PALETTE,
+            foreground: [204, 204, 204],
+            background: [30, 30, 30],
+        }
+    }
+
+    /// Solarized dark
+    pub fn solarized() -> Self {
+        Self {
+            name: "solarized".to_string(),
+            palette: SOLARIZED_PALETTE,
+            foreground: [131, 148, 150],
+            background: [0, 43, 54],
+        }
+    }
+
+    /// Dark text on a white background
+    pub fn light() -> Self {
+        Self {
+            name: "light".to_string(),
+            palette: LIGHT_PALETTE,
+            foreground: [51, 51, 51],
+            background: [255, 255, 255],
+        }
+    }
+
+    /// Look up a built-in theme by name (see [`BUILTIN_THEMES`])
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "default" => Some(Self::default()),
+            "vscode-dark" | "vscode" => Some(Self::vscode_dark()),
+            "solarized" | "solarized-dark" => Some(Self::solarized()),
+            "light" => Some(Self::light()),
+            _ => None,
+        }
+    }
+
+    /// Load a theme from a JSON file
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), msg));
+        let file: ThemeFile = serde_json::from_str(&fs::read_to_string(path)?).map_err(|e| invalid(e.to_string()))?;
+
+        if file.palette.len() != 16 {
+            return Err(invalid(format!("palette needs 16 colors, found {}", file.palette.len())));
+        }
+        let color = |hex: &str| parse_hex_color(hex).ok_or_else(|| invalid(format!("invalid color '{}'", hex)));
+        let mut palette = [[0u8; 3]; 16];
+        for (slot, hex) in palette.iter_mut().zip(&file.palette) {
+            *slot = color(hex)?;
+        }
+
+        Ok(Self {
+            name: file
+                .name
+                .unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().into_owned()),
+            palette,
+            foreground: color(&file.foreground)?,
+            background: color(&file.background)?,
+        })
+    }
+
+    /// Resolve a built-in theme name or a path to a theme file
+    pub fn resolve(name_or_path: &str) -> io::Result<Self> {
+        match Self::builtin(name_or_path) {
+            Some(theme) => Ok(theme),
+            None if Path::new(name_or_path).exists() => Self::from_file(name_or_path),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "unknown theme '{}' (built-in: {})",
+                    name_or_path,
+                    BUILTIN_THEMES.join(", ")
+                ),
+            )),
+        }
+    }
+
+    /// Color for an xterm 256-color index; 0-15 come from the palette
+    pub fn color(&self, idx: u8) -> [u8; 3] {
+        match idx {
+            0..=15 => self.palette[idx as usize],
+            _ => xterm_256_to_rgb(idx),
+        }
+    }
+}
+
+/// Color of an xterm 256-color index with the standard palette for 0-15.
+///
+/// 16-231 are the 6x6x6 color cube and 232-255 the gray ramp; these are the
+/// same in every theme.
+pub fn xterm_256_to_rgb(idx: u8) -> [u8; 3] {
+    match idx {
+        0..=15 => DEFAULT_PALETTE[idx as usize],
+        16..=231 => {
+            let normalized = idx - 16;
+            let r = normalized / 36;
+            let g = (normalized % 36) / 6;
+            let b = normalized % 6;
+            let scale = [0, 95, 135, 175, 215, 255];
+            [scale[r as usize], scale[g as usize], scale[b as usize]]
+        }
+        232..=255 => {
+            let shade = 8 + (idx - 232) * 10;
+            [shade, shade, shade]
+        }
+    }
+}
+
+/// Parse `#rrggbb` (the `#` is optional)
+fn parse_hex_color(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_themes_resolve() {
+        for name in BUILTIN_THEMES {
+            assert_eq!(Theme::builtin(name).unwrap().name, *name);
+        }
+        assert!(Theme::resolve("no-such-theme").is_err());
+        assert_eq!(Theme::default().color(9), [241, 76, 76]);
+        assert_eq!(Theme::default().color(196), [255, 0, 0]);
+    }
+
+    #[test]
+    fn test_theme_file_uses_hex_colors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mono.json");
+        let palette: Vec<String> = (0..16).map(|i| format!("\"#{:02x}{:02x}{:02x}\"", i, i, i)).collect();
+        fs::write(
+            &path,
+            format!(r##"{{"foreground": "#eeeeee", "background": "101010", "palette": [{}]}}"##, palette.join(",")),
+        )
+        .unwrap();
+
+        let theme = Theme::resolve(path.to_str().unwrap()).unwrap();
+        assert_eq!(theme.name, "mono");
+        assert_eq!(theme.foreground, [0xee, 0xee, 0xee]);
+        assert_eq!(theme.background, [0x10, 0x10, 0x10]);
+        assert_eq!(theme.palette[15], [15, 15, 15]);
+    }
+}
@@ -19,6 +19,9 @@ pub struct SnapshotConfig {
 
     /// Whether to allow mock captures when real display is not available (for testing only)
     pub allow_mock_captures: bool,
+
+    /// Whether to write the screen text next to the image, for backends that have one
+    pub include_text: bool,
 }
 
 impl Default for SnapshotConfig {
@@ -28,6 +31,7 @@ impl Default for SnapshotConfig {
             include_metadata: true,
             include_manifest: true,
             allow_mock_captures: false, // Default to production mode - no mocks
+            include_text: false,
         }
     }
 }
@@ -38,6 +38,16 @@ pub fn create_base_metadata(
     meta
 }
 
+/// Write the screen text of a snapshot if configured, as `<name>.screen.txt`
+///
+/// The `.txt` extension alone is taken by the description file.
+pub fn write_screen_text(snapshot: &Snapshot, config: &SnapshotConfig, text: &str) -> SnapshotResult<()> {
+    if config.include_text {
+        fs::write(snapshot.image_path.with_extension("screen.txt"), text)?;
+    }
+    Ok(())
+}
+
 /// Write the JSON manifest for a snapshot if configured
 pub fn write_manifest(snapshot: &Snapshot, config: &SnapshotConfig) -> SnapshotResult<()> {
     if config.include_manifest {
@@ -0,0 +1,319 @@
+//! Waiting for screen conditions instead of fixed delays.
+//!
+//! A wait input sends nothing; the run keeps reading the application's
+//! output until the screen satisfies the condition, then captures the state:
+//!
+//! - `wait-for:TEXT` waits until `TEXT` is on screen
+//! - `wait-regex:PATTERN` waits until a regular expression matches; `^` and
+//!   `$` anchor at row boundaries, so `wait-regex:^Loading (\d+)%$` matches a
+//!   whole row
+//! - `wait-gone:TEXT` waits until `TEXT` is no longer on screen, e.g. a spinner
+//! - `wait-stable:MS` waits until the screen text has not changed for `MS`
+//!   milliseconds, for applications that keep redrawing after an input;
+//!   `wait-stable:MS,MAX` also sets the longest wait in milliseconds
+//!
+//! The timeout is [`DEFAULT_WAIT_TIMEOUT`] unless
+//! [`RunOptions::wait_timeout`](super::RunOptions::wait_timeout) is set. Each
+//! waited-for state carries a [`WaitOutcome`] with the matched text; a run
+//! whose wait timed out stops there, with the last state marked as timed out.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use super::pty::{ingest_chunk, Vt100Parser, Vt100Terminal};
+
+/// How long a wait step waits before failing
+pub const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A screen condition a step waits for
+#[derive(Debug, Clone)]
+pub enum WaitCondition {
+    /// The text appears anywhere on screen (within a row)
+    Text(String),
+    /// The pattern matches the screen, with rows as lines
+    Regex(Regex),
+    /// The text is no longer anywhere on screen
+    Gone(String),
+    /// The screen text stays unchanged for `window`; `max_wait` overrides the run's timeout
+    Stable {
+        /// How long the screen must not change
+        window: Duration,
+        /// Longest wait for this step
+        max_wait: Option<Duration>,
+    },
+}
+
+impl WaitCondition {
+    /// Whether the screen satisfies the condition.
+    ///
+    /// A `Stable` condition depends on how the screen changed over time, so
+    /// it is never met by a single screen; the waiting loop tracks it.
+    pub fn is_met(&self, terminal: &Vt100Terminal) -> bool {
+        match self {
+            WaitCondition::Gone(text) => !terminal.to_text().contains(text.as_str()),
+            WaitCondition::Stable { .. } => false,
+            _ => self.matched_text(terminal).is_some(),
+        }
+    }
+
+    /// The on-screen text satisfying a `Text` or `Regex` condition
+    pub fn matched_text(&self, terminal: &Vt100Terminal) -> Option<String> {
+        match self {
+            WaitCondition::Text(text) => terminal.to_text().contains(text.as_str()).then(|| text.clone()),
+            WaitCondition::Regex(regex) => {
+                let screen: Vec<String> = terminal.to_text().lines().map(|line| line.trim_end().to_string()).collect();
+                regex.find(&screen.join("\n")).map(|found| found.as_str().to_string())
+            }
+            WaitCondition::Gone(_) | WaitCondition::Stable { .. } => None,
+        }
+    }
+}
+
+impl PartialEq for WaitCondition {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (WaitCondition::Text(a), WaitCondition::Text(b)) | (WaitCondition::Gone(a), WaitCondition::Gone(b)) => a == b,
+            (WaitCondition::Regex(a), WaitCondition::Regex(b)) => a.as_str() == b.as_str(),
+            (
+                WaitCondition::Stable { window, max_wait },
+                WaitCondition::Stable { window: other_window, max_wait: other_max },
+            ) => window == other_window && max_wait == other_max,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for WaitCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WaitCondition::Text(text) => write!(f, "text '{}'", text),
+            WaitCondition::Regex(regex) => write!(f, "regex '{}'", regex.as_str()),
+            WaitCondition::Gone(text) => write!(f, "text '{}' to disappear", text),
+            WaitCondition::Stable { window, .. } => write!(f, "screen to stay unchanged for {}ms", window.as_millis()),
+        }
+    }
+}
+
+/// How a wait step ended
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WaitOutcome {
+    /// The condition waited for (see [`WaitCondition`]'s `Display`)
+    pub condition: String,
+    /// Text that satisfied a `wait-for:` or `wait-regex:` condition
+    pub matched: Option<String>,
+    /// How long the step waited, in milliseconds
+    pub elapsed_ms: u64,
+    /// Whether the timeout ran out first
+    pub timed_out: bool,
+}
+
+impl std::fmt::Display for WaitOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let elapsed = self.elapsed_ms as f64 / 1000.0;
+        if self.timed_out {
+            return write!(f, "timed out after {:.1}s waiting for {}", elapsed, self.condition);
+        }
+        write!(f, "waited {:.1}s for {}", elapsed, self.condition)?;
+        match &self.matched {
+            Some(matched) => write!(f, " (matched '{}')", matched),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parse a `wait-for:TEXT`, `wait-regex:PATTERN`, `wait-gone:TEXT` or
+/// `wait-stable:MS[,MAX]` input.
+///
+/// Returns `None` for other inputs and `Some(Err(..))` for a wait with
+/// nothing to wait for, an invalid pattern or malformed durations. Texts are
+/// kept exactly, including spaces.
+pub fn parse_wait_input(input: &str) -> Option<Result<WaitCondition, String>> {
+    let (prefix, body) = input.trim_start().split_once(':')?;
+    let prefix = prefix.to_lowercase();
+    if !matches!(prefix.as_str(), "wait-for" | "wait-regex" | "wait-gone" | "wait-stable") {
+        return None;
+    }
+    if prefix == "wait-stable" {
+        return Some(parse_stable(body));
+    }
+    if body.trim().is_empty() {
+        return Some(Err(format!("{} needs something to wait for", prefix)));
+    }
+    Some(match prefix.as_str() {
+        "wait-regex" => regex::RegexBuilder::new(body)
+            .multi_line(true)
+            .build()
+            .map(WaitCondition::Regex)
+            .map_err(|e| format!("invalid wait-regex pattern: {}", e)),
+        "wait-gone" => Ok(WaitCondition::Gone(body.to_string())),
+        _ => Ok(WaitCondition::Text(body.to_string())),
+    })
+}
+
+fn parse_stable(body: &str) -> Result<WaitCondition, String> {
+    let millis = |value: &str| value.trim().parse::<u64>().ok().map(Duration::from_millis);
+    let (window, max_wait) = match body.split_once(',') {
+        Some((window, max)) => (millis(window), millis(max).map(Some)),
+        None => (millis(body), Some(None)),
+    };
+    match (window, max_wait) {
+        (Some(window), Some(max_wait)) if !window.is_zero() => Ok(WaitCondition::Stable { window, max_wait }),
+        _ => Err("wait-stable needs a window in milliseconds, e.g. wait-stable:500 or wait-stable:500,5000".to_string()),
+    }
+}
+
+/// Read output until `condition` holds or `timeout` runs out.
+///
+/// The screen is checked before reading anything, after every chunk, and
+/// once more if the application closes the PTY. For a `Stable` condition the
+/// screen text is compared at every poll (50ms) and must stay the same for
+/// the whole window; its `max_wait` replaces `timeout`.
+pub(crate) fn wait_for_condition(
+    rx: &Receiver<Vec<u8>>,
+    parser: &mut Vt100Parser,
+    writer: &mut dyn Write,
+    condition: &WaitCondition,
+    timeout: Duration,
+) -> WaitOutcome {
+    let start = Instant::now();
+    let timeout = match condition {
+        WaitCondition::Stable { max_wait: Some(max_wait), .. } => *max_wait,
+        _ => timeout,
+    };
+    let mut last_change = (parser.terminal().to_text(), Instant::now());
+    let mut is_met = |parser: &Vt100Parser| match condition {
+        WaitCondition::Stable { window, .. } => {
+            let text = parser.terminal().to_text();
+            if text != last_change.0 {
+                last_change = (text, Instant::now());
+            }
+            last_change.1.elapsed() >= *window
+        }
+        _ => condition.is_met(parser.terminal()),
+    };
+    let outcome = |parser: &Vt100Parser, timed_out: bool| WaitOutcome {
+        condition: condition.to_string(),
+        matched: condition.matched_text(parser.terminal()),
+        elapsed_ms: start.elapsed().as_millis() as u64,
+        timed_out,
+    };
+    loop {
+        if is_met(parser) {
+            return outcome(parser, false);
+        }
+        let Some(remaining) = timeout.checked_sub(start.elapsed()) else {
+            return outcome(parser, true);
+        };
+        match rx.recv_timeout(remaining.min(Duration::from_millis(50))) {
+            Ok(chunk) => ingest_chunk(&chunk, parser, writer),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                // Nothing can change any more, so a stable wait is over too
+                let met = matches!(condition, WaitCondition::Stable { .. }) || is_met(parser);
+                return outcome(parser, !met);
+            }
+        }
+    }
+}
+
+/// Error message for a wait that timed out, ending with the last screen
+pub(crate) fn timeout_message(step: usize, condition: &WaitCondition, timeout: Duration, terminal: &Vt100Terminal) -> String {
+    let text = terminal.to_text();
+    let screen: Vec<&str> = text.lines().map(str::trim_end).collect();
+    let last = screen.iter().rposition(|line| !line.is_empty()).map_or(0, |idx| idx + 1);
+    format!(
+        "step {}: timed out after {:.1}s waiting for {}; screen was:\n{}",
+        step,
+        timeout.as_secs_f64(),
+        condition,
+        screen[..last].join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_parse_wait_tokens() {
+        assert_eq!(parse_wait_input("wait-for:Ready"), Some(Ok(WaitCondition::Text("Ready".to_string()))));
+        assert_eq!(parse_wait_input("WAIT-FOR:Loaded 3 items"), Some(Ok(WaitCondition::Text("Loaded 3 items".to_string()))));
+        assert!(matches!(parse_wait_input("wait-for: "), Some(Err(_))));
+        assert_eq!(parse_wait_input("wait-gone:Spinner"), Some(Ok(WaitCondition::Gone("Spinner".to_string()))));
+        assert!(matches!(parse_wait_input("wait-regex:^Loading (\\d+)%$"), Some(Ok(WaitCondition::Regex(_)))));
+        assert!(matches!(parse_wait_input("wait-regex:(unclosed"), Some(Err(_))));
+        let stable = |window, max_wait: Option<u64>| WaitCondition::Stable {
+            window: Duration::from_millis(window),
+            max_wait: max_wait.map(Duration::from_millis),
+        };
+        assert_eq!(parse_wait_input("wait-stable:500"), Some(Ok(stable(500, None))));
+        assert_eq!(parse_wait_input("wait-stable:500, 3000"), Some(Ok(stable(500, Some(3000)))));
+        assert!(matches!(parse_wait_input("wait-stable:0"), Some(Err(_))));
+        assert!(matches!(parse_wait_input("wait-stable:soon"), Some(Err(_))));
+        assert_eq!(parse_wait_input("text:wait-for:x"), None);
+        assert_eq!(parse_wait_input("enter"), None);
+    }
+
+    #[test]
+    fn test_wait_reads_until_text_appears() {
+        let (tx, rx) = mpsc::channel();
+        let mut parser = Vt100Parser::new(20, 3);
+        let condition = WaitCondition::Text("Ready".to_string());
+        tx.send(b"Loading...\r\n".to_vec()).unwrap();
+        tx.send(b"Ready".to_vec()).unwrap();
+        let outcome = wait_for_condition(&rx, &mut parser, &mut std::io::sink(), &condition, Duration::from_secs(1));
+        assert!(!outcome.timed_out);
+        assert_eq!(outcome.matched.as_deref(), Some("Ready"));
+
+        let missing = WaitCondition::Text("Done".to_string());
+        assert!(wait_for_condition(&rx, &mut parser, &mut std::io::sink(), &missing, Duration::from_millis(100)).timed_out);
+        let message = timeout_message(2, &missing, Duration::from_millis(100), parser.terminal());
+        assert_eq!(message, "step 2: timed out after 0.1s waiting for text 'Done'; screen was:\nLoading...\nReady");
+    }
+
+    #[test]
+    fn test_regex_and_gone_conditions() {
+        let (tx, rx) = mpsc::channel();
+        let mut parser = Vt100Parser::new(20, 3);
+        tx.send(b"Loading 40%\r\n|".to_vec()).unwrap();
+        tx.send(b"\x1b[2J\x1b[HLoading 100%".to_vec()).unwrap();
+
+        let done = parse_wait_input("wait-regex:^Loading 100%$").unwrap().unwrap();
+        let outcome = wait_for_condition(&rx, &mut parser, &mut std::io::sink(), &done, Duration::from_secs(1));
+        assert_eq!(outcome.matched.as_deref(), Some("Loading 100%"));
+        assert!(outcome.to_string().ends_with("for regex '^Loading 100%$' (matched 'Loading 100%')"));
+
+        let progress = parse_wait_input("wait-regex:^Loading (\\d+)%$").unwrap().unwrap();
+        assert_eq!(progress.matched_text(parser.terminal()).as_deref(), Some("Loading 100%"));
+        let spinner = WaitCondition::Gone("|".to_string());
+        assert!(spinner.is_met(parser.terminal()));
+        assert!(!WaitCondition::Gone("Loading".to_string()).is_met(parser.terminal()));
+    }
+
+    #[test]
+    fn test_stable_waits_for_redraws_to_stop() {
+        let (tx, rx) = mpsc::channel();
+        let mut parser = Vt100Parser::new(20, 1);
+        let redraws = std::thread::spawn(move || {
+            for frame in 0..4 {
+                tx.send(format!("\rframe {}", frame).into_bytes()).unwrap();
+                std::thread::sleep(Duration::from_millis(60));
+            }
+            // Keep the PTY open while the screen settles
+            std::thread::sleep(Duration::from_millis(800));
+        });
+        let condition = parse_wait_input("wait-stable:150").unwrap().unwrap();
+        let outcome = wait_for_condition(&rx, &mut parser, &mut std::io::sink(), &condition, Duration::from_secs(2));
+        assert!(!outcome.timed_out);
+        assert!(outcome.elapsed_ms >= 300);
+        assert!(parser.terminal().to_text().starts_with("frame 3"));
+
+        let never = WaitCondition::Stable { window: Duration::from_secs(5), max_wait: Some(Duration::from_millis(100)) };
+        assert!(wait_for_condition(&rx, &mut parser, &mut std::io::sink(), &never, Duration::from_secs(10)).timed_out);
+        redraws.join().unwrap();
+    }
+}
@@ -0,0 +1,482 @@
+//! Snapshot testing helpers for use inside `cargo test`.
+//!
+//! Text snapshots work like `insta`: the first run of
+//! [`expect_snapshot!`](crate::expect_snapshot) writes a pending
+//! `snapshots/<name>.txt.new` file and fails; `cli-vision snapshots review`
+//! accepts or rejects pending files. Later runs compare the screen text
+//! against the accepted `snapshots/<name>.txt` and fail on mismatch.
+//!
+//! [`assert_image_snapshot`] does the same for screenshots, stored as
+//! `snapshots/<name>.png` and compared pixel by pixel.
+//!
+//! Set `CLI_VISION_SNAPSHOT_DIR` to store snapshots somewhere other than
+//! `<crate>/snapshots`. What happens to new and changed snapshots follows the
+//! environment (see [`UpdateMode::from_env`]), including the variables
+//! `insta` and `expect-test` users already set: `CLI_VISION_UPDATE_SNAPSHOTS=1`,
+//! `INSTA_UPDATE=always` or `UPDATE_EXPECT=1` accept them directly, and on CI
+//! (`CI` set) or with `INSTA_UPDATE=no` they only fail, writing nothing.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::expect_snapshot;
+//! use cli_vision::snapshot::run_with_inputs;
+//! use cli_vision::testing::assert_image_snapshot;
+//!
+//! let captures = run_with_inputs("./my-tui", &[], &["enter".to_string()], 100).unwrap();
+//! expect_snapshot!("state_after_enter", &captures[1]);
+//! assert_image_snapshot("state_after_enter", &captures[1]);
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::snapshot::{CaptureResult, StateCaptureResult, Vt100Terminal};
+
+/// Environment variable selecting the snapshot directory
+pub const SNAPSHOT_DIR_ENV: &str = "CLI_VISION_SNAPSHOT_DIR";
+
+/// Environment variable that accepts new/changed snapshots when set to `1`
+pub const UPDATE_SNAPSHOTS_ENV: &str = "CLI_VISION_UPDATE_SNAPSHOTS";
+
+/// How assertions treat new and changed snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpdateMode {
+    /// Write pending `.new` files for `cli-vision snapshots review`
+    #[default]
+    Pending,
+    /// Overwrite the stored snapshots
+    Always,
+    /// Only compare; nothing is written
+    No,
+}
+
+impl UpdateMode {
+    /// Mode selected by the environment.
+    ///
+    /// `CLI_VISION_UPDATE_SNAPSHOTS` (`1`/`always`, `new`, `no`) wins, then
+    /// insta's `INSTA_UPDATE` (`always`/`unseen`, `new`, `no`, `auto`) and
+    /// expect-test's `UPDATE_EXPECT=1`. Otherwise snapshots go to review
+    /// locally and are only compared on CI (`CI` set and not `false`).
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().map(|v| v.trim().to_lowercase()).filter(|v| !v.is_empty());
+        let on_ci = var("CI").is_some_and(|v| v != "false" && v != "0");
+        let parse = |value: &str| match value {
+            "1" | "always" | "unseen" => Some(UpdateMode::Always),
+            "new" => Some(UpdateMode::Pending),
+            "0" | "no" => Some(UpdateMode::No),
+            _ => None,
+        };
+        var(UPDATE_SNAPSHOTS_ENV)
+            .and_then(|v| parse(&v))
+            .or_else(|| var("INSTA_UPDATE").and_then(|v| parse(&v)))
+            .or_else(|| var("UPDATE_EXPECT").filter(|v| v == "1").map(|_| UpdateMode::Always))
+            .unwrap_or(if on_ci { UpdateMode::No } else { UpdateMode::Pending })
+    }
+}
+
+/// Suffix of snapshot files awaiting review
+const PENDING_SUFFIX: &str = ".new";
+
+/// Values whose screen text can be snapshotted
+pub trait SnapshotText {
+    /// Screen text, one line per row
+    fn snapshot_text(&self) -> String;
+}
+
+impl SnapshotText for str {
+    fn snapshot_text(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl SnapshotText for String {
+    fn snapshot_text(&self) -> String {
+        self.clone()
+    }
+}
+
+impl SnapshotText for StateCaptureResult {
+    fn snapshot_text(&self) -> String {
+        self.text.clone()
+    }
+}
+
+impl SnapshotText for Vt100Terminal {
+    fn snapshot_text(&self) -> String {
+        self.to_text()
+    }
+}
+
+impl<T: SnapshotText + ?Sized> SnapshotText for &T {
+    fn snapshot_text(&self) -> String {
+        (**self).snapshot_text()
+    }
+}
+
+/// Values with a PNG screenshot that can be snapshotted
+pub trait SnapshotImage {
+    /// PNG-encoded screenshot
+    fn snapshot_png(&self) -> &[u8];
+}
+
+impl SnapshotImage for [u8] {
+    fn snapshot_png(&self) -> &[u8] {
+        self
+    }
+}
+
+impl SnapshotImage for Vec<u8> {
+    fn snapshot_png(&self) -> &[u8] {
+        self
+    }
+}
+
+impl SnapshotImage for StateCaptureResult {
+    fn snapshot_png(&self) -> &[u8] {
+        &self.image_data
+    }
+}
+
+impl SnapshotImage for CaptureResult {
+    fn snapshot_png(&self) -> &[u8] {
+        &self.image_data
+    }
+}
+
+/// Assert that screen text matches the stored snapshot `name`.
+///
+/// Records the location of the call in failure messages.
+#[macro_export]
+macro_rules! expect_snapshot {
+    ($name:expr, $value:expr) => {
+        $crate::testing::assert_text_snapshot(
+            $name,
+            &$crate::testing::SnapshotText::snapshot_text(&$value),
+            concat!(file!(), ":", line!()),
+        )
+    };
+}
+
+/// Outcome of comparing text against a stored snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotOutcome {
+    /// The text matches the accepted snapshot
+    Matched,
+    /// The snapshot was written (new or changed) because updates are enabled
+    Updated,
+    /// No accepted snapshot exists
+    New {
+        /// Path of the pending snapshot, unless updates are off ([`UpdateMode::No`])
+        pending: Option<PathBuf>,
+    },
+    /// The value differs from the accepted snapshot
+    Changed {
+        /// Path of the pending snapshot, unless updates are off ([`UpdateMode::No`])
+        pending: Option<PathBuf>,
+        /// Line diff of texts, or a summary of differing pixels for images
+        diff: String,
+    },
+}
+
+/// A snapshot awaiting review
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingSnapshot {
+    /// Snapshot name
+    pub name: String,
+    /// Accepted snapshot path (may not exist yet)
+    pub path: PathBuf,
+    /// Pending snapshot path
+    pub pending_path: PathBuf,
+}
+
+/// Directory snapshots are stored in
+pub fn snapshot_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var(SNAPSHOT_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
+    let base = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    Path::new(&base).join("snapshots")
+}
+
+/// Normalize screen text for storage: trailing spaces and blank trailing rows are dropped
+pub fn normalize_text(text: &str) -> String {
+    let mut lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    while lines.last() == Some(&"") {
+        lines.pop();
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// Simple line diff: `-` lines from the accepted snapshot, `+` lines from the new text
+pub fn line_diff(old: &str, new: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let mut out = String::new();
+    for row in 0..old_lines.len().max(new_lines.len()) {
+        let a = old_lines.get(row);
+        let b = new_lines.get(row);
+        if a == b {
+            continue;
+        }
+        out.push_str(&format!("@@ row {} @@\n", row));
+        if let Some(a) = a {
+            out.push_str(&format!("-{}\n", a));
+        }
+        if let Some(b) = b {
+            out.push_str(&format!("+{}\n", b));
+        }
+    }
+    out
+}
+
+/// Compare text against snapshot `name` in `dir`, writing pending or updated files
+pub fn check_text_snapshot(dir: &Path, name: &str, text: &str, mode: UpdateMode) -> io::Result<SnapshotOutcome> {
+    let path = dir.join(format!("{}.txt", name));
+    let text = normalize_text(text);
+    let accepted = read_if_exists(&path)?.map(|data| String::from_utf8_lossy(&data).into_owned());
+    let matched = accepted.as_deref() == Some(text.as_str());
+    record_outcome(&path, text.as_bytes(), matched, accepted.is_some(), mode, || {
+        line_diff(accepted.as_deref().unwrap_or_default(), &text)
+    })
+}
+
+/// Compare a PNG against image snapshot `name` in `dir`, writing pending or updated files.
+///
+/// Images match when their pixels are equal, however they were encoded.
+pub fn check_image_snapshot(dir: &Path, name: &str, png: &[u8], mode: UpdateMode) -> io::Result<SnapshotOutcome> {
+    let path = dir.join(format!("{}.png", name));
+    image::load_from_memory(png).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let accepted = read_if_exists(&path)?;
+    let diff = accepted.as_deref().map(|old| image_diff(old, png));
+    let matched = matches!(diff, Some(None));
+    record_outcome(&path, png, matched, accepted.is_some(), mode, || diff.flatten().unwrap_or_default())
+}
+
+/// Summary of how two PNG screenshots differ, or `None` if their pixels are equal
+pub fn image_diff(old_png: &[u8], new_png: &[u8]) -> Option<String> {
+    let Ok(old) = image::load_from_memory(old_png).map(|image| image.to_rgba8()) else {
+        return Some("stored snapshot is not a readable PNG\n".to_string());
+    };
+    let Ok(new) = image::load_from_memory(new_png).map(|image| image.to_rgba8()) else {
+        return Some("new screenshot is not a readable PNG\n".to_string());
+    };
+    if old.dimensions() != new.dimensions() {
+        let ((ow, oh), (nw, nh)) = (old.dimensions(), new.dimensions());
+        return Some(format!("size changed from {}x{} to {}x{}\n", ow, oh, nw, nh));
+    }
+    let mut changed = 0usize;
+    let (mut min, mut max) = ((u32::MAX, u32::MAX), (0, 0));
+    for (x, y, pixel) in new.enumerate_pixels() {
+        if old.get_pixel(x, y) != pixel {
+            changed += 1;
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+    }
+    (changed > 0).then(|| {
+        format!(
+            "{} of {} pixels differ, within ({}, {})-({}, {})\n",
+            changed,
+            new.width() * new.height(),
+            min.0,
+            min.1,
+            max.0,
+            max.1
+        )
+    })
+}
+
+/// Store a compared value the way `mode` asks and describe the outcome
+fn record_outcome(
+    path: &Path,
+    contents: &[u8],
+    matched: bool,
+    exists: bool,
+    mode: UpdateMode,
+    diff: impl FnOnce() -> String,
+) -> io::Result<SnapshotOutcome> {
+    let pending = pending_path(path);
+    if matched {
+        remove_if_exists(&pending)?;
+        return Ok(SnapshotOutcome::Matched);
+    }
+    if let Some(dir) = path.parent()
+        && mode != UpdateMode::No
+    {
+        fs::create_dir_all(dir)?;
+    }
+    let pending = match mode {
+        UpdateMode::Always => {
+            fs::write(path, contents)?;
+            remove_if_exists(&pending)?;
+            return Ok(SnapshotOutcome::Updated);
+        }
+        UpdateMode::Pending => {
+            fs::write(&pending, contents)?;
+            Some(pending)
+        }
+        UpdateMode::No => None,
+    };
+    Ok(if exists {
+        SnapshotOutcome::Changed { pending, diff: diff() }
+    } else {
+        SnapshotOutcome::New { pending }
+    })
+}
+
+/// Assert that text matches snapshot `name`, panicking with a diff otherwise.
+///
+/// Usually called through [`expect_snapshot!`](crate::expect_snapshot).
+pub fn assert_text_snapshot(name: &str, text: &str, location: &str) {
+    let outcome = check_text_snapshot(&snapshot_dir(), name, text, UpdateMode::from_env())
+        .unwrap_or_else(|e| panic!("snapshot '{}' ({}): failed to access snapshot files: {}", name, location, e));
+    fail_on_mismatch(name, location, outcome);
+}
+
+/// Assert that a screenshot matches image snapshot `name` pixel for pixel.
+///
+/// The first run stores `snapshots/<name>.png.new` for review and fails,
+/// like [`expect_snapshot!`](crate::expect_snapshot) does for text.
+#[track_caller]
+pub fn assert_image_snapshot(name: &str, capture: &(impl SnapshotImage + ?Sized)) {
+    let location = std::panic::Location::caller().to_string();
+    let outcome = check_image_snapshot(&snapshot_dir(), name, capture.snapshot_png(), UpdateMode::from_env())
+        .unwrap_or_else(|e| panic!("snapshot '{}' ({}): failed to access snapshot files: {}", name, location, e));
+    fail_on_mismatch(name, &location, outcome);
+}
+
+fn fail_on_mismatch(name: &str, location: &str, outcome: SnapshotOutcome) {
+    let written = |pending: &Option<PathBuf>| match pending {
+        Some(path) => format!("written to {}\nreview with `cli-vision snapshots review`", path.display()),
+        None => "not written (snapshot updates are off, e.g. on CI)".to_string(),
+    };
+    match outcome {
+        SnapshotOutcome::Matched | SnapshotOutcome::Updated => {}
+        SnapshotOutcome::New { pending } => {
+            panic!("new snapshot '{}' ({}) {}", name, location, written(&pending))
+        }
+        SnapshotOutcome::Changed { pending, diff } => panic!(
+            "snapshot '{}' ({}) does not match:\n{}new version {}",
+            name,
+            location,
+            diff,
+            written(&pending)
+        ),
+    }
+}
+
+/// List snapshots awaiting review in `dir`, sorted by name
+pub fn pending_snapshots(dir: &Path) -> io::Result<Vec<PendingSnapshot>> {
+    let mut pending = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let pending_path = entry?.path();
+        let Some(file_name) = pending_path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if let Some(accepted) = file_name.strip_suffix(PENDING_SUFFIX)
+            && let Some(name) = accepted.strip_suffix(".txt").or_else(|| accepted.strip_suffix(".png"))
+        {
+            pending.push(PendingSnapshot {
+                name: name.to_string(),
+                path: dir.join(accepted),
+                pending_path: pending_path.clone(),
+            });
+        }
+    }
+    pending.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(pending)
+}
+
+/// Accept a pending snapshot, replacing the stored one
+pub fn accept_snapshot(snapshot: &PendingSnapshot) -> io::Result<()> {
+    fs::rename(&snapshot.pending_path, &snapshot.path)
+}
+
+/// Reject a pending snapshot, keeping the stored one
+pub fn reject_snapshot(snapshot: &PendingSnapshot) -> io::Result<()> {
+    fs::remove_file(&snapshot.pending_path)
+}
+
+fn pending_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(PENDING_SUFFIX);
+    PathBuf::from(name)
+}
+
+pub(crate) fn read_if_exists(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_lifecycle() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let outcome = check_text_snapshot(dir.path(), "menu", "Open   \nQuit\n\n", UpdateMode::Pending).unwrap();
+        assert!(matches!(outcome, SnapshotOutcome::New { .. }));
+        let pending = pending_snapshots(dir.path()).unwrap();
+        assert_eq!(pending.len(), 1);
+        accept_snapshot(&pending[0]).unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("menu.txt")).unwrap(), "Open\nQuit\n");
+
+        assert_eq!(check_text_snapshot(dir.path(), "menu", "Open\nQuit", UpdateMode::Pending).unwrap(), SnapshotOutcome::Matched);
+
+        match check_text_snapshot(dir.path(), "menu", "Open\nExit", UpdateMode::Pending).unwrap() {
+            SnapshotOutcome::Changed { diff, .. } => assert_eq!(diff, "@@ row 1 @@\n-Quit\n+Exit\n"),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+        reject_snapshot(&pending_snapshots(dir.path()).unwrap()[0]).unwrap();
+        assert!(pending_snapshots(dir.path()).unwrap().is_empty());
+
+        assert_eq!(check_text_snapshot(dir.path(), "menu", "Exit", UpdateMode::Always).unwrap(), SnapshotOutcome::Updated);
+    }
+
+    #[test]
+    fn test_image_snapshots_compare_pixels() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = |pixels: &[[u8; 3]]| {
+            let image = image::RgbImage::from_fn(pixels.len() as u32, 1, |x, _| image::Rgb(pixels[x as usize]));
+            let mut data = std::io::Cursor::new(Vec::new());
+            image.write_to(&mut data, image::ImageOutputFormat::Png).unwrap();
+            data.into_inner()
+        };
+        let menu = png(&[[0, 0, 0], [255, 255, 255]]);
+
+        // Updates off (CI): a new snapshot fails without writing anything
+        let outcome = check_image_snapshot(dir.path(), "menu", &menu, UpdateMode::No).unwrap();
+        assert_eq!(outcome, SnapshotOutcome::New { pending: None });
+        assert!(pending_snapshots(dir.path()).unwrap().is_empty());
+
+        check_image_snapshot(dir.path(), "menu", &menu, UpdateMode::Pending).unwrap();
+        accept_snapshot(&pending_snapshots(dir.path()).unwrap()[0]).unwrap();
+        assert_eq!(check_image_snapshot(dir.path(), "menu", &menu, UpdateMode::No).unwrap(), SnapshotOutcome::Matched);
+
+        let changed = png(&[[0, 0, 0], [255, 0, 0]]);
+        match check_image_snapshot(dir.path(), "menu", &changed, UpdateMode::Pending).unwrap() {
+            SnapshotOutcome::Changed { diff, pending } => {
+                assert_eq!(diff, "1 of 2 pixels differ, within (1, 0)-(1, 0)\n");
+                assert!(pending.unwrap().ends_with("menu.png.new"));
+            }
+            other => panic!("unexpected outcome {:?}", other),
+        }
+        assert_eq!(image_diff(&menu, &png(&[[0, 0, 0]])).unwrap(), "size changed from 2x1 to 1x1\n");
+    }
+}
@@ -10,51 +10,166 @@
 //! VLM settings can be configured via environment variables:
 //! - `CLI_VISION_VLM_ENDPOINT`: API endpoint URL
 //! - `CLI_VISION_VLM_MODEL`: Model name
+//! - `CLI_VISION_VLM_PROVIDER`: API format (see [`crate::vlm_provider`])
+//! - `CLI_VISION_VLM_API_KEY`: API key for hosted endpoints
+//! - `CLI_VISION_VLM_HEADERS`: Extra headers, `Name: value` separated by `;`
 //! - `CLI_VISION_VLM_MAX_TOKENS`: Max tokens in response
 //! - `CLI_VISION_VLM_TIMEOUT`: Activity timeout (seconds)
 //! - `CLI_VISION_VLM_CONNECT_TIMEOUT`: Connection timeout (seconds)
+//! - `CLI_VISION_VLM_SAMPLES`: Samples per verification (majority vote)
+//! - `CLI_VISION_VLM_MAX_ATTEMPTS`: Tries per request (see [`RetryPolicy`])
+//! - `CLI_VISION_VLM_IMAGE_MAX_SIZE`, `CLI_VISION_VLM_IMAGE_FORMAT`,
+//!   `CLI_VISION_VLM_IMAGE_QUALITY`: How screenshots are sent (see [`ImagePrep`])
+//!
+//! Prompts about a run start with a preamble giving the model machine-readable
+//! context: step, terminal size, earlier inputs, binary and scenario (see
+//! [`PromptContext`]). [`VlmConfig::preamble`] replaces it and
+//! [`VlmConfig::no_preamble`] turns it off. When the context carries the
+//! screen's text, it is appended after the prompt so the model need not OCR
+//! small glyphs. [`VlmConfig::analysis_mode`] chooses between the screenshot,
+//! its text, or both (see [`AnalysisMode`]); text alone suits cheap text-only
+//! LLMs, and [`complete_text`] asks one without an image.
+//!
+//! A [`VlmConversation`] analyzes successive screenshots with the last few
+//! steps' images and answers kept in the request, so the model can reason
+//! about transitions rather than each screen in isolation.
+//!
+//! [`analyze_image_structured`] asks for JSON matching a schema instead of
+//! free text, for answers a CI job can gate on.
+//!
+//! Screenshots can be scaled down and sent as JPEG or WebP to save tokens;
+//! see [`VlmConfig::image_prep`] and [`crate::vlm_image`].
+//!
+//! [`VlmConfig::cache`] answers repeated requests from disk rather than the
+//! endpoint; see [`crate::vlm_cache`]. [`VlmConfig::transcript`] logs every
+//! request and answer; see [`crate::vlm_log`].
+//!
+//! Setting the endpoint to `local` (or leaving it empty) answers requests with
+//! the offline CLIP model from `CLI_VISION_LOCAL_MODEL` instead; this needs
+//! the `local-vision` feature. See [`crate::local_vision`].
 
 use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::hash::{BuildHasher, Hasher};
 use std::io::{BufRead, BufReader};
-use std::process::{Command, Stdio};
-use std::sync::mpsc;
+use std::sync::Arc;
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-use crate::config;
+use crate::config::{self, Secret};
+use crate::schema::validate_json;
+use crate::vlm_cache::VlmCache;
+use crate::vlm_image::ImagePrep;
+use crate::vlm_log::{TranscriptEntry, VlmTranscript, redact_images};
+use crate::vlm_provider::{OpenAiProvider, StreamEvent, VlmProvider, provider_named};
+use crate::vlm_rate::RateLimiter;
 
 /// Result type for VLM operations
 pub type VlmResult<T> = Result<T, VlmError>;
 
 /// Errors that can occur during VLM operations
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum VlmError {
     /// Failed to connect to the VLM endpoint
+    #[error("Connection failed: {0}")]
     ConnectionFailed(String),
     /// No activity for too long during streaming
+    #[error("No response for {0:?}")]
     ActivityTimeout(Duration),
+    /// The endpoint rejected the credentials (HTTP 401 or 403)
+    #[error("Authentication failed (HTTP {status}): {body}; check CLI_VISION_VLM_API_KEY")]
+    Auth { status: u16, body: String },
+    /// The endpoint is rate limiting requests (HTTP 429)
+    #[error("Rate limited{}: {body}", .retry_after.map(|d| format!(" (retry after {}s)", d.as_secs())).unwrap_or_default())]
+    RateLimited {
+        /// How long the `Retry-After` header asks to wait
+        retry_after: Option<Duration>,
+        body: String,
+    },
+    /// The endpoint answered with another HTTP error status
+    #[error("HTTP {0}: {1}")]
+    Http(u16, String),
+    /// The endpoint sent JSON that does not parse
+    #[error("Malformed JSON ({message}): {excerpt}")]
+    MalformedJson {
+        message: String,
+        /// The start of the body, for telling an HTML error page from truncated JSON
+        excerpt: String,
+    },
     /// Invalid response from the VLM
+    #[error("Invalid response: {0}")]
     InvalidResponse(String),
+    /// The local vision model is unavailable or failed
+    #[error("Local model unavailable: {0}")]
+    LocalModel(String),
     /// IO error
-    Io(std::io::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
 }
 
-impl std::fmt::Display for VlmError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// Characters of a body kept in errors
+const EXCERPT_CHARS: usize = 200;
+
+impl VlmError {
+    /// The error for an HTTP error `status` with response `body`
+    pub fn from_status(status: u16, retry_after: Option<Duration>, body: &str) -> Self {
+        let body = excerpt(body);
+        match status {
+            401 | 403 => VlmError::Auth { status, body },
+            429 => VlmError::RateLimited { retry_after, body },
+            _ => VlmError::Http(status, body),
+        }
+    }
+
+    /// The error for a body that is not the JSON expected
+    pub fn malformed_json(error: &serde_json::Error, body: &str) -> Self {
+        VlmError::MalformedJson {
+            message: error.to_string(),
+            excerpt: excerpt(body),
+        }
+    }
+
+    /// Whether the error is likely transient, so the request is worth retrying:
+    /// rate limiting, server errors (500, 502, 503, 504), request timeouts
+    /// (408), and connections that failed or were cut off. Activity timeouts
+    /// are not retried; the request already waited that long. Neither are
+    /// rejected credentials or malformed answers, which a retry would repeat.
+    pub fn is_retryable(&self) -> bool {
         match self {
-            VlmError::ConnectionFailed(msg) => write!(f, "Connection failed: {}", msg),
-            VlmError::ActivityTimeout(d) => write!(f, "No response for {:?}", d),
-            VlmError::InvalidResponse(msg) => write!(f, "Invalid response: {}", msg),
-            VlmError::Io(e) => write!(f, "IO error: {}", e),
+            VlmError::RateLimited { .. } => true,
+            VlmError::Http(status, _) => matches!(status, 408 | 500 | 502 | 503 | 504),
+            VlmError::ConnectionFailed(_) => true,
+            VlmError::Io(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            ),
+            VlmError::ActivityTimeout(_)
+            | VlmError::Auth { .. }
+            | VlmError::MalformedJson { .. }
+            | VlmError::InvalidResponse(_)
+            | VlmError::LocalModel(_) => false,
         }
     }
-}
 
-impl std::error::Error for VlmError {}
+    /// How long the server asked to wait before retrying, if it said
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            VlmError::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
 
-impl From<std::io::Error> for VlmError {
-    fn from(e: std::io::Error) -> Self {
-        VlmError::Io(e)
+/// `body` trimmed and cut to [`EXCERPT_CHARS`] characters
+fn excerpt(body: &str) -> String {
+    let body = body.trim();
+    match body.char_indices().nth(EXCERPT_CHARS) {
+        Some((end, _)) => format!("{}...", &body[..end]),
+        None => body.to_string(),
     }
 }
 
@@ -65,23 +180,171 @@ pub struct VlmConfig {
     pub endpoint: String,
     /// Model name to use
     pub model: String,
+    /// API format requests are written in
+    pub provider: Arc<dyn VlmProvider>,
+    /// API key, sent the way the provider expects (see [`VlmProvider::auth_headers`])
+    pub api_key: Option<Secret>,
+    /// Extra headers sent with every request
+    pub extra_headers: Vec<(String, Secret)>,
     /// Maximum tokens in response
     pub max_tokens: u32,
     /// Timeout for initial connection (seconds)
     pub connection_timeout: u64,
     /// Timeout for inactivity during streaming (seconds)
     pub activity_timeout: u64,
+    /// Times to ask the VLM per verification; the majority verdict wins
+    pub samples: u32,
+    /// Sampling temperature (server default if unset, see [`VlmConfig::sampling_temperature`])
+    pub temperature: Option<f32>,
+    /// Template put before prompts about a run (see [`render_prompt`]); `None` disables it
+    pub preamble: Option<String>,
+    /// Whether requests carry the screenshot, the screen's text, or both
+    pub analysis_mode: AnalysisMode,
+    /// How screenshots are resized and encoded for requests
+    pub image_prep: ImagePrep,
+    /// When failed requests are retried
+    pub retry: RetryPolicy,
+    /// How fast requests may be sent; `None` sends them right away
+    pub rate_limit: Option<RateLimiter>,
+    /// Where answers are cached; `None` always asks the endpoint
+    pub cache: Option<VlmCache>,
+    /// Where requests and answers are logged; `None` logs nothing
+    pub transcript: Option<VlmTranscript>,
+}
+
+/// When and how often failed VLM requests are retried.
+///
+/// Only errors for which [`VlmError::is_retryable`] holds are retried. The
+/// wait before retry `n` is `initial_backoff * 2^(n-1)`, capped at
+/// `max_backoff`, with up to `jitter` of it randomized so parallel runs do
+/// not retry in lockstep.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Tries per request, including the first; 1 disables retries
+    pub max_attempts: u32,
+    /// Wait before the first retry
+    pub initial_backoff: Duration,
+    /// Longest wait between tries
+    pub max_backoff: Duration,
+    /// Fraction of each wait that is random, from 0.0 to 1.0
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: config::get().vlm.max_attempts,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            jitter: 0.5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// The wait before retry `retry` (1-based) without jitter
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+
+    /// The wait before retry `retry` (1-based), with jitter taken off the backoff
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.backoff(retry);
+        // Each RandomState is seeded differently, which is random enough to spread retries
+        let random = std::collections::hash_map::RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        backoff.mul_f64(1.0 - self.jitter.clamp(0.0, 1.0) * random)
+    }
+}
+
+/// What a request about a screen shows the model
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AnalysisMode {
+    /// The screenshot alone
+    Image,
+    /// The screen's text alone, for text-only LLMs
+    Text,
+    /// The screenshot, with the screen's text after the prompt
+    #[default]
+    Hybrid,
+}
+
+impl AnalysisMode {
+    /// Whether requests carry the screenshot
+    pub fn sends_image(self) -> bool {
+        self != AnalysisMode::Text
+    }
+
+    /// Whether prompts carry the screen's text
+    pub fn sends_text(self) -> bool {
+        self != AnalysisMode::Image
+    }
+}
+
+impl std::str::FromStr for AnalysisMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "image" => Ok(AnalysisMode::Image),
+            "text" => Ok(AnalysisMode::Text),
+            "hybrid" => Ok(AnalysisMode::Hybrid),
+            other => Err(format!("unknown analysis mode '{}' (expected: text, image, hybrid)", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for AnalysisMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AnalysisMode::Image => "image",
+            AnalysisMode::Text => "text",
+            AnalysisMode::Hybrid => "hybrid",
+        })
+    }
 }
 
+/// Endpoint value selecting the offline local vision model
+pub const LOCAL_ENDPOINT: &str = "local";
+
+/// Temperature used when voting over several samples without an explicit one
+pub const DEFAULT_VOTING_TEMPERATURE: f32 = 0.7;
+
+/// Requests [`analyze_image_structured`] makes before giving up on malformed answers
+pub const STRUCTURED_ATTEMPTS: u32 = 3;
+
+/// Preamble template giving the model the run context as JSON
+pub const DEFAULT_PREAMBLE: &str = "Run context (JSON, not part of the screen): {context}\n\n";
+
 impl Default for VlmConfig {
     fn default() -> Self {
         let cfg = config::get();
         Self {
             endpoint: cfg.vlm.endpoint.clone(),
             model: cfg.vlm.model.clone(),
+            provider: provider_named(&cfg.vlm.provider).unwrap_or_else(|| Arc::new(OpenAiProvider)),
+            api_key: cfg.vlm.api_key.clone(),
+            extra_headers: cfg.vlm.extra_headers.clone(),
             max_tokens: cfg.vlm.max_tokens,
             connection_timeout: cfg.vlm.connect_timeout,
             activity_timeout: cfg.vlm.activity_timeout,
+            samples: cfg.vlm.samples,
+            temperature: None,
+            preamble: Some(DEFAULT_PREAMBLE.to_string()),
+            analysis_mode: AnalysisMode::default(),
+            image_prep: ImagePrep::default(),
+            retry: RetryPolicy::default(),
+            rate_limit: RateLimiter::from_env(),
+            cache: None,
+            transcript: None,
         }
     }
 }
@@ -99,6 +362,34 @@ impl VlmConfig {
         self
     }
 
+    /// Write requests for another API (see [`crate::vlm_provider`])
+    pub fn provider(mut self, provider: impl VlmProvider + 'static) -> Self {
+        self.provider = Arc::new(provider);
+        self
+    }
+
+    /// Authenticate with `key`
+    pub fn api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(Secret::new(key));
+        self
+    }
+
+    /// Send another header with every request
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.extra_headers.push((name.into(), Secret::new(value)));
+        self
+    }
+
+    /// Headers for a request: the provider's, the API key's, then the extra headers
+    pub fn request_headers(&self) -> Vec<(String, String)> {
+        let mut headers = self.provider.headers(self);
+        if let Some(key) = &self.api_key {
+            headers.extend(self.provider.auth_headers(key.expose()));
+        }
+        headers.extend(self.extra_headers.iter().map(|(name, value)| (name.clone(), value.expose().to_string())));
+        headers
+    }
+
     pub fn max_tokens(mut self, max_tokens: u32) -> Self {
         self.max_tokens = max_tokens;
         self
@@ -108,6 +399,94 @@ impl VlmConfig {
         self.activity_timeout = seconds;
         self
     }
+
+    pub fn samples(mut self, samples: u32) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Use another preamble template (see [`render_prompt`] for placeholders)
+    pub fn preamble(mut self, template: impl Into<String>) -> Self {
+        self.preamble = Some(template.into());
+        self
+    }
+
+    /// Send prompts without a context preamble
+    pub fn no_preamble(mut self) -> Self {
+        self.preamble = None;
+        self
+    }
+
+    /// Send the screenshot, the screen's text, or both
+    pub fn analysis_mode(mut self, mode: AnalysisMode) -> Self {
+        self.analysis_mode = mode;
+        self
+    }
+
+    /// Resize and re-encode screenshots before sending them (see [`crate::vlm_image`])
+    pub fn image_prep(mut self, prep: ImagePrep) -> Self {
+        self.image_prep = prep;
+        self
+    }
+
+    /// Retry failed requests according to `policy`
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Pace requests with `limiter`; clones of one limiter share its budget
+    pub fn rate_limit(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limit = Some(limiter);
+        self
+    }
+
+    /// Answer repeated requests from `cache`
+    pub fn cache(mut self, cache: VlmCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Log every request and its answer to `transcript`
+    pub fn transcript(mut self, transcript: VlmTranscript) -> Self {
+        self.transcript = Some(transcript);
+        self
+    }
+
+    /// A prompt with the rendered preamble for `context` in front and, unless
+    /// only the screenshot is sent, the screen's text after it
+    pub fn contextual_prompt(&self, prompt: &str, context: &PromptContext) -> String {
+        let prompt = match &self.preamble {
+            Some(template) => format!("{}{}", render_prompt(template, context), prompt),
+            None => prompt.to_string(),
+        };
+        match &context.screen_text {
+            Some(text) if self.analysis_mode.sends_text() => with_screen_text(&prompt, text),
+            _ => prompt,
+        }
+    }
+
+    /// Whether requests go to the local vision model rather than an HTTP endpoint
+    pub fn is_local(&self) -> bool {
+        is_local_endpoint(&self.endpoint)
+    }
+
+    /// Temperature to send with requests.
+    ///
+    /// Identical samples make a vote pointless, so when several are taken
+    /// without an explicit temperature, [`DEFAULT_VOTING_TEMPERATURE`] is used.
+    pub fn sampling_temperature(&self) -> Option<f32> {
+        match self.temperature {
+            Some(t) => Some(t),
+            None if self.samples > 1 => Some(DEFAULT_VOTING_TEMPERATURE),
+            None => None,
+        }
+    }
 }
 
 /// Progress update during VLM analysis
@@ -121,35 +500,205 @@ pub enum VlmProgress {
     Complete(String),
     /// Error occurred
     Error(String),
+    /// A request failed and is tried again after `delay`; `attempt` is the try that failed
+    Retrying { attempt: u32, delay: Duration, error: String },
+    /// Tokens the request used, as reported by the server (sent before `Complete`)
+    Usage(VlmUsage),
+    /// The request was held back this long by [`VlmConfig::rate_limit`] before it was sent
+    Paced(Duration),
+}
+
+/// Tokens used by VLM requests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VlmUsage {
+    /// Tokens in the prompt, images included
+    pub prompt_tokens: u64,
+    /// Tokens in the answer
+    pub completion_tokens: u64,
+}
+
+impl VlmUsage {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    /// Combine counts reported in parts of one response; servers that
+    /// report running totals repeat earlier counts, so the larger one wins
+    pub fn merge(&mut self, other: VlmUsage) {
+        self.prompt_tokens = self.prompt_tokens.max(other.prompt_tokens);
+        self.completion_tokens = self.completion_tokens.max(other.completion_tokens);
+    }
+}
+
+impl std::ops::AddAssign for VlmUsage {
+    fn add_assign(&mut self, other: VlmUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+/// Price of a model's tokens, in dollars per million
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    /// Price of a million prompt tokens
+    pub prompt: f64,
+    /// Price of a million completion tokens
+    pub completion: f64,
+}
+
+/// Token prices by model name, e.g. `{"gpt-4o": {"prompt": 2.5, "completion": 10.0}}`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PriceTable(pub BTreeMap<String, ModelPrice>);
+
+impl PriceTable {
+    /// Load a price table from a JSON file
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Cost in dollars of `usage` with `model`, if the table has its price
+    pub fn cost(&self, model: &str, usage: &VlmUsage) -> Option<f64> {
+        let price = self.0.get(model)?;
+        Some((usage.prompt_tokens as f64 * price.prompt + usage.completion_tokens as f64 * price.completion) / 1_000_000.0)
+    }
+}
+
+/// Whether an endpoint value selects the local vision model (`local` or empty)
+pub fn is_local_endpoint(endpoint: &str) -> bool {
+    let endpoint = endpoint.trim();
+    endpoint.is_empty() || endpoint.eq_ignore_ascii_case(LOCAL_ENDPOINT)
+}
+
+/// Describe a screenshot with the local vision model
+fn local_describe(image_data: &[u8]) -> VlmResult<String> {
+    #[cfg(feature = "local-vision")]
+    return crate::local_vision::shared_model()
+        .and_then(|model| model.describe(image_data))
+        .map_err(|e| VlmError::LocalModel(e.to_string()));
+    #[cfg(not(feature = "local-vision"))]
+    {
+        let _ = image_data;
+        Err(local_vision_missing())
+    }
+}
+
+/// Verify a check with the local vision model
+fn local_check(image_data: &[u8], check: &str) -> VlmResult<VlmVerdict> {
+    #[cfg(feature = "local-vision")]
+    return crate::local_vision::shared_model()
+        .and_then(|model| model.check(image_data, check))
+        .map_err(|e| VlmError::LocalModel(e.to_string()));
+    #[cfg(not(feature = "local-vision"))]
+    {
+        let _ = (image_data, check);
+        Err(local_vision_missing())
+    }
+}
+
+/// The local model only sees screenshots, so it cannot work from text alone
+fn local_text_mode() -> VlmError {
+    VlmError::LocalModel("the local model needs screenshots; use an LLM endpoint for text analysis".to_string())
 }
 
-/// Check if a VLM endpoint is reachable (connection-only check).
+#[cfg(not(feature = "local-vision"))]
+fn local_vision_missing() -> VlmError {
+    VlmError::LocalModel("no VLM endpoint configured and this build lacks the `local-vision` feature".to_string())
+}
+
+/// Check if a VLM endpoint is reachable.
 ///
-/// This only verifies the server accepts TCP connections - it doesn't wait
-/// for a full response since VLM requests can take 30+ seconds for large images.
+/// This only sends a HEAD request to the server root - it doesn't make a
+/// chat request since VLM requests can take 30+ seconds for large images.
+/// For the local model it checks that one is configured.
 pub fn check_health(endpoint: &str, timeout_secs: u64) -> VlmResult<bool> {
-    // Extract host:port from endpoint URL for connection test
-    let url = endpoint.trim_start_matches("http://").trim_start_matches("https://");
-    let host_port = url.split('/').next().unwrap_or("127.0.0.1:8080");
-
-    // Use curl to just test if we can connect (not wait for response)
-    let output = Command::new("curl")
-        .args([
-            "-s",
-            "-o", "/dev/null",
-            "-w", "%{http_code}",
-            "--connect-timeout", &timeout_secs.to_string(),
-            "--max-time", &timeout_secs.to_string(),
-            "-I", // HEAD request - just check if server responds to connection
-            &format!("http://{}", host_port),
-        ])
-        .output()?;
-
-    let status = String::from_utf8_lossy(&output.stdout);
-    // Any response (even 4xx/5xx) means server is reachable
-    // 000 means connection failed entirely
-    let code: u16 = status.trim().parse().unwrap_or(0);
-    Ok(code > 0)
+    if is_local_endpoint(endpoint) {
+        #[cfg(feature = "local-vision")]
+        return Ok(crate::local_vision::model_dir().is_some_and(|dir| dir.join("model.safetensors").is_file()));
+        #[cfg(not(feature = "local-vision"))]
+        return Ok(false);
+    }
+    // Only the server root is asked, with a HEAD request; any HTTP answer
+    // (even 4xx/5xx) means it is reachable
+    let (scheme, rest) = endpoint.split_once("://").unwrap_or(("http", endpoint));
+    let host_port = rest.split('/').next().unwrap_or("127.0.0.1:8080");
+    let timeout = Duration::from_secs(timeout_secs);
+    let agent = ureq::AgentBuilder::new().timeout_connect(timeout).timeout(timeout).build();
+    match agent.head(&format!("{}://{}", scheme, host_port)).call() {
+        Ok(_) | Err(ureq::Error::Status(..)) => Ok(true),
+        Err(ureq::Error::Transport(_)) => Ok(false),
+    }
+}
+
+/// What a VLM server says about itself and its models (see [`check_model`])
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    /// The server's `Server` header, e.g. `llama.cpp`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    /// The server's version, if the API reports one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The configured model
+    pub model: String,
+    /// Whether the server lists the configured model
+    pub model_found: bool,
+    /// Models the server lists
+    pub models: Vec<String>,
+}
+
+impl std::fmt::Display for ServerInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.server, &self.version) {
+            (Some(server), Some(version)) => write!(f, "{} {}", server, version)?,
+            (Some(name), None) | (None, Some(name)) => f.write_str(name)?,
+            (None, None) => f.write_str("unknown server")?,
+        }
+        if self.model_found {
+            write!(f, ", model '{}' available", self.model)
+        } else {
+            write!(f, ", model '{}' not among {} listed", self.model, self.models.len())
+        }
+    }
+}
+
+/// Ask the server which models it offers and whether the configured one is among them.
+///
+/// Unlike [`check_health`], this needs the API's model listing
+/// (`/v1/models`, or the provider's equivalent, see
+/// [`VlmProvider::models_url`]) and sends the API key. The server's
+/// version is read where the API reports one; failing to read it is not
+/// an error. The local model has no listing.
+pub fn check_model(config: &VlmConfig) -> VlmResult<ServerInfo> {
+    if config.is_local() {
+        return Err(VlmError::LocalModel("the local model has no model list".to_string()));
+    }
+    let provider = &config.provider;
+    let (server, body) = get_text(config, &provider.models_url(config))?;
+    let models = provider.parse_models(&body)?;
+    let version = provider.version_url(config).and_then(|url| {
+        let (_, body) = get_text(config, &url).ok()?;
+        let json: serde_json::Value = serde_json::from_str(&body).ok()?;
+        json["version"].as_str().map(str::to_string)
+    });
+    Ok(ServerInfo {
+        server,
+        version,
+        model: config.model.clone(),
+        model_found: model_listed(&config.model, &models),
+        models,
+    })
+}
+
+/// Whether `model` is in a server's model list, allowing for Ollama's
+/// implied `:latest` tag and for servers listing model files by path
+pub fn model_listed(model: &str, models: &[String]) -> bool {
+    models.iter().any(|listed| {
+        listed == model
+            || listed.strip_suffix(":latest") == Some(model)
+            || listed.rsplit('/').next() == Some(model)
+    })
 }
 
 /// Analyze an image with the VLM using streaming to avoid timeouts
@@ -166,216 +715,620 @@ pub fn analyze_image_with_progress<F>(
     config: &VlmConfig,
     image_data: &[u8],
     prompt: &str,
-    mut on_progress: F,
+    on_progress: F,
 ) -> VlmResult<String>
 where
     F: FnMut(VlmProgress),
 {
-    let img_base64 = base64::engine::general_purpose::STANDARD.encode(image_data);
+    analyze_after(config, &[], image_data, prompt, on_progress)
+}
 
-    let request = serde_json::json!({
-        "model": config.model,
-        "messages": [{
-            "role": "user",
-            "content": [
-                {
-                    "type": "image_url",
-                    "image_url": {
-                        "url": format!("data:image/png;base64,{}", img_base64)
-                    }
-                },
-                {
-                    "type": "text",
-                    "text": prompt
-                }
-            ]
-        }],
-        "max_tokens": config.max_tokens,
-        "stream": true
+/// Analyze an image with earlier steps of a conversation in front of it
+fn analyze_after<F>(config: &VlmConfig, history: &[ConversationTurn], image_data: &[u8], prompt: &str, mut on_progress: F) -> VlmResult<String>
+where
+    F: FnMut(VlmProgress),
+{
+    if config.is_local() {
+        if !config.analysis_mode.sends_image() {
+            return Err(local_text_mode());
+        }
+        // The local model describes one screenshot at a time
+        let description = local_describe(image_data)?;
+        on_progress(VlmProgress::Complete(description.clone()));
+        return Ok(description);
+    }
+    complete_chat(config, conversation_messages(config, history, image_data, prompt), None, on_progress)
+}
+
+/// Ask a text model about `prompt` alone, with no image.
+///
+/// This is the request [`AnalysisMode::Text`] analyses make, for prompts
+/// that carry the screen's text themselves (see [`with_screen_text`]).
+pub fn complete_text<F>(config: &VlmConfig, prompt: &str, on_progress: F) -> VlmResult<String>
+where
+    F: FnMut(VlmProgress),
+{
+    if config.is_local() {
+        return Err(local_text_mode());
+    }
+    complete_chat(config, serde_json::json!([{ "role": "user", "content": prompt }]), None, on_progress)
+}
+
+/// A screenshot analyzed earlier in a [`VlmConversation`]
+#[derive(Debug, Clone)]
+struct ConversationTurn {
+    image_data: Vec<u8>,
+    prompt: String,
+    answer: String,
+}
+
+/// Analyses of successive screenshots that keep the last `window` steps in view.
+///
+/// Each request carries the earlier screenshots with their prompts and the
+/// model's answers, oldest first, so the model can say what a step changed.
+/// The window bounds the images (and tokens) sent per request; a window of
+/// 0 analyzes every screenshot on its own like [`analyze_image_with_progress`].
+#[derive(Debug, Clone, Default)]
+pub struct VlmConversation {
+    window: usize,
+    turns: VecDeque<ConversationTurn>,
+}
+
+impl VlmConversation {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            turns: VecDeque::new(),
+        }
+    }
+
+    /// Earlier steps currently kept in view
+    pub fn len(&self) -> usize {
+        self.turns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.turns.is_empty()
+    }
+
+    /// Analyze the next screenshot with the earlier ones in view, then keep it for later steps
+    pub fn analyze<F>(&mut self, config: &VlmConfig, image_data: &[u8], prompt: &str, on_progress: F) -> VlmResult<String>
+    where
+        F: FnMut(VlmProgress),
+    {
+        let answer = analyze_after(config, self.turns.make_contiguous(), image_data, prompt, on_progress)?;
+        self.remember(ConversationTurn {
+            image_data: image_data.to_vec(),
+            prompt: prompt.to_string(),
+            answer: answer.clone(),
+        });
+        Ok(answer)
+    }
+
+    fn remember(&mut self, turn: ConversationTurn) {
+        if self.window == 0 {
+            return;
+        }
+        self.turns.push_back(turn);
+        while self.turns.len() > self.window {
+            self.turns.pop_front();
+        }
+    }
+}
+
+/// Analyze an image and get JSON matching `schema` back.
+///
+/// The schema is sent both as the request's `response_format` (for servers
+/// with a JSON mode) and in the prompt. An answer that is not JSON or does
+/// not validate is sent back with the problems found, up to
+/// [`STRUCTURED_ATTEMPTS`] requests in all. Validation supports the keywords
+/// listed in [`crate::schema`]. The local model cannot answer in JSON.
+pub fn analyze_image_structured(config: &VlmConfig, image_data: &[u8], prompt: &str, schema: &serde_json::Value) -> VlmResult<serde_json::Value> {
+    if config.is_local() {
+        return Err(VlmError::LocalModel("the local model cannot answer in JSON; use a VLM endpoint".to_string()));
+    }
+    let response_format = serde_json::json!({
+        "type": "json_schema",
+        "json_schema": { "name": "analysis", "schema": schema }
     });
+    let mut turns = vec![ChatMessage::user(build_structured_prompt(prompt, schema))];
+    let mut problems = Vec::new();
+    for _ in 0..STRUCTURED_ATTEMPTS {
+        let response = complete_chat(config, image_messages(config, image_data, &turns), Some(&response_format), |_| {})?;
+        match parse_structured(&response, schema) {
+            Ok(value) => return Ok(value),
+            Err(found) => problems = found,
+        }
+        turns.push(ChatMessage::assistant(response));
+        turns.push(ChatMessage::user(format!(
+            "That answer does not match the schema: {}. Answer again with only the corrected JSON.",
+            problems.join("; ")
+        )));
+    }
+    Err(VlmError::InvalidResponse(format!(
+        "no answer matched the schema in {} attempts: {}",
+        STRUCTURED_ATTEMPTS,
+        problems.join("; ")
+    )))
+}
 
-    let request_json = serde_json::to_string(&request)
-        .map_err(|e| VlmError::InvalidResponse(e.to_string()))?;
+/// A prompt asking for a JSON answer matching `schema`
+pub fn build_structured_prompt(prompt: &str, schema: &serde_json::Value) -> String {
+    format!(
+        "{}\n\nAnswer with a single JSON value that matches this JSON Schema, and nothing else:\n{}",
+        prompt, schema
+    )
+}
 
-    // Spawn curl with streaming
-    let mut child = Command::new("curl")
-        .args([
-            "-s",
-            "-N", // Disable buffering for streaming
-            "-X", "POST",
-            &config.endpoint,
-            "-H", "Content-Type: application/json",
-            "-d", &request_json,
-            "--connect-timeout", &config.connection_timeout.to_string(),
-        ])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
-
-    let stdout = child.stdout.take()
-        .ok_or_else(|| VlmError::Io(std::io::Error::other("Failed to capture stdout")))?;
-
-    // Read streaming response with activity timeout
-    let (tx, rx) = mpsc::channel();
-    let activity_timeout = Duration::from_secs(config.activity_timeout);
+/// Parse and validate a JSON answer, which may be wrapped in prose or a code
+/// fence; the error lists every problem found
+pub fn parse_structured(response: &str, schema: &serde_json::Value) -> Result<serde_json::Value, Vec<String>> {
+    let trimmed = response.trim();
+    let value = serde_json::from_str(trimmed)
+        .ok()
+        .or_else(|| json_object(trimmed))
+        .ok_or_else(|| vec!["the answer is not JSON".to_string()])?;
+    let problems: Vec<String> = validate_json(&value.to_string(), schema).into_iter().map(|d| d.message).collect();
+    if problems.is_empty() { Ok(value) } else { Err(problems) }
+}
 
-    // Spawn reader thread
-    thread::spawn(move || {
-        let reader = BufReader::new(stdout);
-        for line in reader.lines() {
-            match line {
-                Ok(line) => {
-                    if tx.send(Ok(line)).is_err() {
-                        break;
-                    }
-                }
-                Err(e) => {
-                    let _ = tx.send(Err(e));
-                    break;
-                }
-            }
+/// Ask about the differences between a baseline screenshot and a current one, sent together in one request.
+///
+/// The model sees the images in that order, each introduced by a label, with
+/// `prompt` after them (see [`build_comparison_prompt`]). The images are
+/// sent whatever the [`AnalysisMode`]. The local model cannot compare images.
+pub fn compare_images(config: &VlmConfig, baseline: &[u8], current: &[u8], prompt: &str) -> VlmResult<String> {
+    compare_images_with_progress(config, baseline, current, prompt, |_| {})
+}
+
+/// Compare two screenshots with progress callbacks
+pub fn compare_images_with_progress<F>(config: &VlmConfig, baseline: &[u8], current: &[u8], prompt: &str, on_progress: F) -> VlmResult<String>
+where
+    F: FnMut(VlmProgress),
+{
+    if config.is_local() {
+        return Err(VlmError::LocalModel("the local model cannot compare screenshots; use a VLM endpoint".to_string()));
+    }
+    let messages = serde_json::json!([{
+        "role": "user",
+        "content": [
+            { "type": "text", "text": "Baseline screenshot (before):" },
+            image_content(&config.image_prep, baseline),
+            { "type": "text", "text": "Current screenshot (after):" },
+            image_content(&config.image_prep, current),
+            { "type": "text", "text": prompt }
+        ]
+    }]);
+    complete_chat(config, messages, None, on_progress)
+}
+
+/// Build a prompt asking what changed between a baseline and a current screenshot, and whether it is a regression
+pub fn build_comparison_prompt(step: usize, input: Option<&str>) -> String {
+    let state = match input {
+        Some(input) => format!("step {}, after pressing '{}'", step, input),
+        None => "the initial state".to_string(),
+    };
+    format!(
+        "Both screenshots show {} of the same terminal application; the first is a known-good baseline. \
+         Describe what changed between them: text, layout, colors, highlighted elements. \
+         Then say whether the change is a regression (something broken, missing, misaligned or unreadable) \
+         or an intended-looking update, ending with a line that reads either \"Regression: yes\" or \"Regression: no\".",
+        state
+    )
+}
+
+/// Who said something in a conversation about a screenshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    User,
+    Assistant,
+}
+
+/// One turn of a conversation about a screenshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+impl ChatMessage {
+    /// A question or instruction from the user
+    pub fn user(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::User, content: content.into() }
+    }
+
+    /// An answer from the model
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::Assistant, content: content.into() }
+    }
+}
+
+/// Continue a conversation about a screenshot; `turns` must end with the user's question.
+///
+/// The image is attached to the first user turn, so follow-up questions are
+/// answered with the whole conversation in view. The local model cannot
+/// hold a conversation.
+pub fn chat_about_image<F>(config: &VlmConfig, image_data: &[u8], turns: &[ChatMessage], on_progress: F) -> VlmResult<String>
+where
+    F: FnMut(VlmProgress),
+{
+    if config.is_local() {
+        return Err(VlmError::LocalModel("the local model cannot answer follow-up questions; use a VLM endpoint".to_string()));
+    }
+    complete_chat(config, image_messages(config, image_data, turns), None, on_progress)
+}
+
+/// Chat messages for `turns`, with the image attached to the first user turn unless the analysis mode leaves it out
+fn image_messages(config: &VlmConfig, image_data: &[u8], turns: &[ChatMessage]) -> serde_json::Value {
+    let mut image = config.analysis_mode.sends_image().then(|| image_content(&config.image_prep, image_data));
+    let messages: Vec<serde_json::Value> = turns
+        .iter()
+        .map(|turn| match (turn.role, image.take_if(|_| turn.role == ChatRole::User)) {
+            (ChatRole::User, Some(image)) => serde_json::json!({
+                "role": "user",
+                "content": [image, { "type": "text", "text": turn.content }]
+            }),
+            (role, _) => serde_json::json!({ "role": role, "content": turn.content }),
+        })
+        .collect();
+    serde_json::Value::Array(messages)
+}
+
+/// Chat messages for the earlier steps of a conversation, each a screenshot
+/// with its prompt and the model's answer, followed by the new screenshot;
+/// in [`AnalysisMode::Text`] only the prompts and answers are sent
+fn conversation_messages(config: &VlmConfig, history: &[ConversationTurn], image_data: &[u8], prompt: &str) -> serde_json::Value {
+    let user = |image_data: &[u8], prompt: &str| match config.analysis_mode.sends_image() {
+        true => serde_json::json!({
+            "role": "user",
+            "content": [image_content(&config.image_prep, image_data), { "type": "text", "text": prompt }]
+        }),
+        false => serde_json::json!({ "role": "user", "content": prompt }),
+    };
+    let mut messages = Vec::new();
+    for turn in history {
+        messages.push(user(&turn.image_data, &turn.prompt));
+        messages.push(serde_json::json!({ "role": "assistant", "content": turn.answer }));
+    }
+    messages.push(user(image_data, prompt));
+    serde_json::Value::Array(messages)
+}
+
+/// Message content part carrying a PNG screenshot, resized and re-encoded by `prep`
+fn image_content(prep: &ImagePrep, image_data: &[u8]) -> serde_json::Value {
+    let (data, mime_type) = prep.apply(image_data);
+    let img_base64 = base64::engine::general_purpose::STANDARD.encode(data);
+    serde_json::json!({
+        "type": "image_url",
+        "image_url": {
+            "url": format!("data:{};base64,{}", mime_type, img_base64)
         }
+    })
+}
+
+/// Send chat messages, retrying transient failures (see [`RetryPolicy`]).
+///
+/// With a [`VlmCache`] the answer is looked up first and stored afterwards.
+/// Majority votes need independent answers, so several samples bypass it.
+fn complete_chat<F>(config: &VlmConfig, messages: serde_json::Value, response_format: Option<&serde_json::Value>, mut on_progress: F) -> VlmResult<String>
+where
+    F: FnMut(VlmProgress),
+{
+    let cache = config.cache.as_ref().filter(|_| config.samples <= 1).map(|cache| {
+        let request = serde_json::json!({ "provider": config.provider.name(), "messages": messages, "response_format": response_format });
+        (cache, VlmCache::key(&config.model, &request))
     });
+    let started = (chrono::Utc::now(), std::time::Instant::now());
+    if let Some((cache, key)) = &cache
+        && let Some(answer) = cache.get(key)
+    {
+        on_progress(VlmProgress::Complete(answer.clone()));
+        let entry = transcript_entry(config, &messages, response_format, started, Ok(&answer));
+        log_request(config, TranscriptEntry { cached: true, ..entry });
+        return Ok(answer);
+    }
+    let (mut usage, mut retries) = (None, 0);
+    let result = with_retries(&config.retry, &mut |progress: VlmProgress| {
+        match &progress {
+            VlmProgress::Usage(counts) => usage = Some(*counts),
+            VlmProgress::Retrying { .. } => retries += 1,
+            _ => {}
+        }
+        on_progress(progress)
+    }, |on_progress| complete_chat_once(config, &messages, response_format, on_progress));
+    let entry = transcript_entry(config, &messages, response_format, started, result.as_deref().map_err(|e| e.to_string()));
+    log_request(config, TranscriptEntry { usage, retries, ..entry });
+    let answer = result?;
+    if let Some((cache, key)) = &cache {
+        // A cache that cannot be written only costs the next run some time
+        let _ = cache.put(key, &config.model, &answer);
+    }
+    Ok(answer)
+}
 
-    on_progress(VlmProgress::Connected);
+/// A transcript entry for a request started at `started` that ended with `outcome`
+fn transcript_entry(
+    config: &VlmConfig,
+    messages: &serde_json::Value,
+    response_format: Option<&serde_json::Value>,
+    started: (chrono::DateTime<chrono::Utc>, std::time::Instant),
+    outcome: Result<&str, String>,
+) -> TranscriptEntry {
+    TranscriptEntry {
+        timestamp: started.0.to_rfc3339(),
+        provider: config.provider.name().to_string(),
+        endpoint: config.provider.url(config),
+        model: config.model.clone(),
+        messages: redact_images(messages),
+        response_format: response_format.cloned(),
+        duration_ms: started.1.elapsed().as_millis() as u64,
+        retries: 0,
+        cached: false,
+        usage: None,
+        response: outcome.as_ref().ok().map(|answer| answer.to_string()),
+        error: outcome.err(),
+    }
+}
 
-    let mut full_content = String::new();
-    let mut last_activity = Instant::now();
+/// Append `entry` to the configured transcript, if any
+fn log_request(config: &VlmConfig, entry: TranscriptEntry) {
+    if let Some(transcript) = &config.transcript
+        && let Err(e) = transcript.record(&entry)
+    {
+        eprintln!("Warning: could not write VLM transcript {}: {}", transcript.path.display(), e);
+    }
+}
 
+/// Run `request` until it succeeds, fails for good, or runs out of tries
+fn with_retries<T, F>(policy: &RetryPolicy, on_progress: &mut F, mut request: impl FnMut(&mut F) -> VlmResult<T>) -> VlmResult<T>
+where
+    F: FnMut(VlmProgress),
+{
+    let mut attempt = 1;
     loop {
-        match rx.recv_timeout(Duration::from_millis(100)) {
-            Ok(Ok(line)) => {
-                last_activity = Instant::now();
-
-                // Parse SSE data
-                if let Some(data) = line.strip_prefix("data: ") {
-                    if data == "[DONE]" {
-                        break;
-                    }
-
-                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
-                        // Extract delta content
-                        if let Some(content) = json["choices"][0]["delta"]["content"].as_str() {
-                            full_content.push_str(content);
-                            on_progress(VlmProgress::Receiving(full_content.clone()));
-                        }
-                        // Also check for reasoning_content (thinking models)
-                        if let Some(content) = json["choices"][0]["delta"]["reasoning_content"].as_str() {
-                            full_content.push_str(content);
-                            on_progress(VlmProgress::Receiving(full_content.clone()));
-                        }
-                    }
-                }
-            }
-            Ok(Err(e)) => {
-                return Err(VlmError::Io(e));
-            }
-            Err(mpsc::RecvTimeoutError::Timeout) => {
-                if last_activity.elapsed() > activity_timeout {
-                    let _ = child.kill();
-                    return Err(VlmError::ActivityTimeout(activity_timeout));
-                }
-            }
-            Err(mpsc::RecvTimeoutError::Disconnected) => {
-                break;
+        match request(on_progress) {
+            Err(e) if e.is_retryable() && attempt < policy.max_attempts => {
+                // A rate limit says how long to wait better than the backoff can guess
+                let delay = e.retry_after().unwrap_or_else(|| policy.delay(attempt));
+                on_progress(VlmProgress::Retrying { attempt, delay, error: e.to_string() });
+                thread::sleep(delay);
+                attempt += 1;
             }
+            result => return result,
         }
     }
+}
 
-    // Wait for process to finish
-    let status = child.wait()?;
+/// Send chat messages once, streaming the answer; servers that do not stream may answer in one piece
+fn complete_chat_once<F>(config: &VlmConfig, messages: &serde_json::Value, response_format: Option<&serde_json::Value>, mut on_progress: F) -> VlmResult<String>
+where
+    F: FnMut(VlmProgress),
+{
+    let provider = &config.provider;
+    let request = provider.build_request(config, messages, response_format);
+    if let Some(limiter) = &config.rate_limit {
+        let waited = limiter.acquire();
+        if !waited.is_zero() {
+            on_progress(VlmProgress::Paced(waited));
+        }
+    }
+    let response = post_json(config, &provider.url(config), &config.request_headers(), &request)?;
+    on_progress(VlmProgress::Connected);
 
-    if !status.success() && full_content.is_empty() {
-        return Err(VlmError::ConnectionFailed("curl process failed".to_string()));
+    let activity_timeout = Duration::from_secs(config.activity_timeout);
+    let mut full_content = String::new();
+    // Servers that ignore `stream` answer with one JSON document instead of events
+    let mut plain_body = String::new();
+    let mut usage = None::<VlmUsage>;
+    for line in BufReader::new(response.into_reader()).lines() {
+        let line = line.map_err(|e| read_error(e, activity_timeout))?;
+        if let Some(counts) = provider.parse_usage(&line) {
+            usage.get_or_insert_default().merge(counts);
+        }
+        match provider.parse_stream_line(&line) {
+            Some(StreamEvent::Text(text)) => {
+                full_content.push_str(&text);
+                on_progress(VlmProgress::Receiving(full_content.clone()));
+            }
+            Some(StreamEvent::Done) => break,
+            Some(StreamEvent::Ignore) => {}
+            None => {
+                plain_body.push_str(&line);
+                plain_body.push('\n');
+            }
+        }
     }
 
-    // If streaming didn't work, try parsing as non-streaming response
     if full_content.is_empty() {
-        // Fall back to non-streaming request
-        return analyze_image_non_streaming(config, image_data, prompt);
+        if plain_body.trim().is_empty() {
+            return Err(VlmError::InvalidResponse("the VLM returned an empty answer".to_string()));
+        }
+        full_content = provider.parse_response(&plain_body)?;
+        if let Some(counts) = provider.parse_usage(&plain_body) {
+            usage.get_or_insert_default().merge(counts);
+        }
     }
 
+    if let Some(usage) = usage {
+        on_progress(VlmProgress::Usage(usage));
+    }
     on_progress(VlmProgress::Complete(full_content.clone()));
     Ok(full_content)
 }
 
-/// Fallback non-streaming analysis (for APIs that don't support streaming)
-fn analyze_image_non_streaming(
-    config: &VlmConfig,
-    image_data: &[u8],
-    prompt: &str,
-) -> VlmResult<String> {
-    let img_base64 = base64::engine::general_purpose::STANDARD.encode(image_data);
+/// POST a JSON request.
+///
+/// The connection timeout applies to connecting, and the activity timeout to
+/// every read after that, so an answer that keeps streaming never times out
+/// while a server that stops sending does.
+fn post_json(config: &VlmConfig, endpoint: &str, headers: &[(String, String)], request: &serde_json::Value) -> VlmResult<ureq::Response> {
+    let activity_timeout = Duration::from_secs(config.activity_timeout);
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(config.connection_timeout))
+        .timeout_read(activity_timeout)
+        .build();
+    let mut post = agent.post(endpoint).set("Content-Type", "application/json");
+    for (name, value) in headers {
+        post = post.set(name, value);
+    }
+    post.send_string(&request.to_string()).map_err(|e| request_error(e, activity_timeout))
+}
+
+/// GET `url` with the configured headers, returning the `Server` header and the body
+fn get_text(config: &VlmConfig, url: &str) -> VlmResult<(Option<String>, String)> {
+    let timeout = Duration::from_secs(config.connection_timeout);
+    let agent = ureq::AgentBuilder::new().timeout_connect(timeout).timeout_read(timeout).build();
+    let mut get = agent.get(url);
+    for (name, value) in config.request_headers() {
+        get = get.set(&name, &value);
+    }
+    let response = get.call().map_err(|e| request_error(e, timeout))?;
+    let server = response.header("Server").map(str::to_string);
+    let body = response.into_string().map_err(|e| read_error(e, timeout))?;
+    Ok((server, body))
+}
+
+/// The [`VlmError`] for a failed HTTP request
+fn request_error(error: ureq::Error, activity_timeout: Duration) -> VlmError {
+    match error {
+        ureq::Error::Status(status, response) => {
+            // Retry-After may also be an HTTP date, which is rare enough to ignore
+            let retry_after = response.header("Retry-After").and_then(|s| s.trim().parse().ok()).map(Duration::from_secs);
+            VlmError::from_status(status, retry_after, &response.into_string().unwrap_or_default())
+        }
+        ureq::Error::Transport(transport) => match transport.kind() {
+            ureq::ErrorKind::Io => match std::error::Error::source(&transport).and_then(|e| e.downcast_ref::<std::io::Error>()) {
+                Some(e) if is_timeout(e) => VlmError::ActivityTimeout(activity_timeout),
+                _ => VlmError::ConnectionFailed(transport.to_string()),
+            },
+            _ => VlmError::ConnectionFailed(transport.to_string()),
+        },
+    }
+}
+
+/// A read of the response body failed; read timeouts are activity timeouts
+fn read_error(error: std::io::Error, activity_timeout: Duration) -> VlmError {
+    if is_timeout(&error) { VlmError::ActivityTimeout(activity_timeout) } else { VlmError::Io(error) }
+}
+
+fn is_timeout(error: &std::io::Error) -> bool {
+    matches!(error.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock)
+}
 
+/// Embeddings endpoint on the same server as a chat completions endpoint
+pub fn embeddings_endpoint(chat_endpoint: &str) -> String {
+    match chat_endpoint.strip_suffix("/chat/completions") {
+        Some(base) => format!("{}/embeddings", base),
+        None => chat_endpoint.to_string(),
+    }
+}
+
+/// Embed texts with an OpenAI-compatible `/v1/embeddings` endpoint, using the configured model
+pub fn embed_texts(config: &VlmConfig, endpoint: &str, texts: &[String]) -> VlmResult<Vec<Vec<f32>>> {
     let request = serde_json::json!({
         "model": config.model,
-        "messages": [{
-            "role": "user",
-            "content": [
-                {
-                    "type": "image_url",
-                    "image_url": {
-                        "url": format!("data:image/png;base64,{}", img_base64)
-                    }
-                },
-                {
-                    "type": "text",
-                    "text": prompt
-                }
-            ]
-        }],
-        "max_tokens": config.max_tokens
+        "input": texts,
     });
-
-    let request_json = serde_json::to_string(&request)
-        .map_err(|e| VlmError::InvalidResponse(e.to_string()))?;
-
-    // Use a very long timeout for non-streaming (since we can't detect activity)
-    let output = Command::new("curl")
-        .args([
-            "-s",
-            "-X", "POST",
-            &config.endpoint,
-            "-H", "Content-Type: application/json",
-            "-d", &request_json,
-            "--connect-timeout", &config.connection_timeout.to_string(),
-            // No --max-time for non-streaming - let it run
-        ])
-        .output()?;
-
-    if !output.status.success() {
-        return Err(VlmError::ConnectionFailed(
-            String::from_utf8_lossy(&output.stderr).to_string()
-        ));
-    }
-
-    let response: serde_json::Value = serde_json::from_slice(&output.stdout)
+    let activity_timeout = Duration::from_secs(config.activity_timeout);
+    let body = with_retries(&config.retry, &mut |_| {}, |_| {
+        if let Some(limiter) = &config.rate_limit {
+            limiter.acquire();
+        }
+        post_json(config, endpoint, &config.request_headers(), &request)?.into_string().map_err(|e| read_error(e, activity_timeout))
+    })?;
+    let response: serde_json::Value = serde_json::from_str(&body)
         .map_err(|e| VlmError::InvalidResponse(e.to_string()))?;
+    let data = response["data"]
+        .as_array()
+        .ok_or_else(|| VlmError::InvalidResponse(format!("no embeddings in response: {}", response)))?;
+    let embeddings: Vec<Vec<f32>> = data
+        .iter()
+        .map(|item| {
+            item["embedding"]
+                .as_array()
+                .map(|values| values.iter().filter_map(|v| v.as_f64()).map(|v| v as f32).collect())
+                .unwrap_or_default()
+        })
+        .collect();
+    if embeddings.len() != texts.len() || embeddings.iter().any(Vec::is_empty) {
+        return Err(VlmError::InvalidResponse(format!(
+            "expected {} embeddings, got {}",
+            texts.len(),
+            embeddings.len()
+        )));
+    }
+    Ok(embeddings)
+}
 
-    // Extract content
-    let content = response["choices"][0]["message"]["content"]
-        .as_str()
-        .unwrap_or("");
+/// What a prompt is about: the run and the state being shown
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PromptContext {
+    /// State number (0 = initial)
+    pub step: usize,
+    /// Input that led to the state
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+    /// Inputs sent before that one, in order
+    pub prior_inputs: Vec<String>,
+    /// Terminal size as `[cols, rows]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terminal_size: Option<[u16; 2]>,
+    /// Name of the application under test
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary: Option<String>,
+    /// What the scenario tests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scenario: Option<String>,
+    /// Text on the screen (or its cropped part), from the terminal buffer
+    #[serde(skip)]
+    pub screen_text: Option<String>,
+}
 
-    // Try reasoning_content for thinking models
-    let result = if content.is_empty() {
-        response["choices"][0]["message"]["reasoning_content"]
-            .as_str()
-            .unwrap_or("No description available")
-    } else {
-        content
-    };
+/// `prompt` followed by the screen's text in a fenced block.
+///
+/// The image stays the source of truth for colors and layout; the text gives
+/// the model the exact characters, which low-resolution glyphs often blur.
+pub fn with_screen_text(prompt: &str, text: &str) -> String {
+    let text = text.trim_end();
+    if text.trim().is_empty() {
+        return prompt.to_string();
+    }
+    format!(
+        "{}\n\nText on the screen, read from the terminal buffer (exact characters, without colors):\n```\n{}\n```",
+        prompt, text
+    )
+}
 
-    Ok(result.to_string())
+/// Fill a prompt template's placeholders from `context`.
+///
+/// Placeholders are `{step}`, `{input}`, `{inputs}` (earlier inputs, comma
+/// separated), `{cols}`, `{rows}`, `{binary}`, `{scenario}` and `{context}`
+/// (all of it as a JSON object); missing values read `none`.
+pub fn render_prompt(template: &str, context: &PromptContext) -> String {
+    let or_none = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+    let size = |idx: usize| or_none(context.terminal_size.map(|size| size[idx].to_string()));
+    let json = serde_json::to_string(context).unwrap_or_default();
+    template
+        .replace("{step}", &context.step.to_string())
+        .replace("{input}", &or_none(context.input.clone()))
+        .replace("{inputs}", &or_none((!context.prior_inputs.is_empty()).then(|| context.prior_inputs.join(", "))))
+        .replace("{cols}", &size(0))
+        .replace("{rows}", &size(1))
+        .replace("{binary}", &or_none(context.binary.clone()))
+        .replace("{scenario}", &or_none(context.scenario.clone()))
+        .replace("{context}", &json)
 }
 
 /// Build a prompt for analyzing a TUI screenshot
 pub fn build_analysis_prompt(step: usize, input: Option<&str>, custom_prompt: Option<&str>) -> String {
     if let Some(custom) = custom_prompt {
-        let input_str = input.unwrap_or("none");
-        custom
-            .replace("{step}", &step.to_string())
-            .replace("{input}", input_str)
+        let context = PromptContext {
+            step,
+            input: input.map(str::to_string),
+            ..Default::default()
+        };
+        render_prompt(custom, &context)
     } else if step == 0 {
         "Describe the initial state of this terminal application. What UI elements are visible? What is selected or highlighted?".to_string()
     } else {
@@ -387,6 +1340,266 @@ pub fn build_analysis_prompt(step: usize, input: Option<&str>, custom_prompt: Op
     }
 }
 
+/// Parse per-step prompts from a JSON object keyed by state number.
+///
+/// Only JSON is accepted; a multi-line prompt uses `\n` escapes:
+///
+/// ```json
+/// {"1": "check if the button is blue", "3": "Verify that the dialog opened\nand that its title is readable."}
+/// ```
+pub fn parse_step_prompts(source: &str) -> Result<BTreeMap<usize, String>, String> {
+    let prompts: BTreeMap<String, String> =
+        serde_json::from_str(source).map_err(|e| format!("expected a JSON object of step prompts: {}", e))?;
+    prompts
+        .into_iter()
+        .map(|(key, prompt)| Ok((step_key(&key)?, prompt)))
+        .collect()
+}
+
+/// Read per-step prompts from a JSON file (see [`parse_step_prompts`])
+pub fn load_step_prompts(path: impl AsRef<std::path::Path>) -> std::io::Result<BTreeMap<usize, String>> {
+    let path = path.as_ref();
+    parse_step_prompts(&std::fs::read_to_string(path)?)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+}
+
+fn step_key(key: &str) -> Result<usize, String> {
+    key.trim().parse().map_err(|_| format!("'{}' is not a step number", key))
+}
+
+/// A problem the VLM reported while verifying a screenshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VlmIssue {
+    /// Kind of problem, e.g. "alignment", "truncation", "color"
+    pub category: String,
+    /// Where on screen, e.g. "footer", "top-right", "sidebar"
+    pub area: String,
+    /// What is wrong
+    pub description: String,
+}
+
+/// Outcome of asking the VLM whether a screenshot satisfies a check
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VlmVerdict {
+    /// Whether the check passed
+    pub pass: bool,
+    /// Problems found (empty when passing)
+    pub issues: Vec<VlmIssue>,
+    /// The model's full answer
+    pub response: String,
+    /// Number of answers voted on
+    #[serde(default = "one_sample")]
+    pub samples: u32,
+    /// Fraction of those answers that agree with `pass` (1.0 for a single sample)
+    #[serde(default = "full_agreement")]
+    pub agreement: f64,
+    /// Requests retried after transient failures while reaching the verdict
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub retries: u32,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+fn one_sample() -> u32 {
+    1
+}
+
+fn full_agreement() -> f64 {
+    1.0
+}
+
+/// Build a prompt asking the VLM to verify a check and locate any problems
+pub fn build_check_prompt(check: &str) -> String {
+    format!(
+        "Verify this terminal screenshot against the following check: {}\n\n\
+         Answer with a single JSON object and nothing else: \
+         {{\"pass\": true or false, \"issues\": [{{\"category\": \"...\", \"area\": \"...\", \"description\": \"...\"}}]}}. \
+         For each problem, category is one word such as alignment, truncation, overlap, color, text or missing; \
+         area is where it is on screen: header, body or footer, optionally with left, center or right (e.g. \"footer-left\"). \
+         Leave issues empty when the check passes.",
+        check
+    )
+}
+
+/// Parse the VLM's answer to a [`build_check_prompt`] prompt.
+///
+/// Models do not always answer in JSON; an answer without a JSON object
+/// passes only if it starts with "pass" or "yes", and a failure without
+/// listed issues is recorded as one unspecified issue.
+pub fn parse_verdict(response: &str) -> VlmVerdict {
+    let (pass, mut issues) = match json_object(response) {
+        Some(value) => {
+            let pass = value["pass"].as_bool().unwrap_or(false);
+            let field = |issue: &serde_json::Value, key: &str| issue[key].as_str().unwrap_or("").trim().to_string();
+            let issues = value["issues"]
+                .as_array()
+                .map(|items| {
+                    items
+                        .iter()
+                        .map(|issue| VlmIssue {
+                            category: field(issue, "category").to_lowercase(),
+                            area: field(issue, "area").to_lowercase(),
+                            description: field(issue, "description"),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            (pass, issues)
+        }
+        None => {
+            let answer = response.trim_start().to_lowercase();
+            (answer.starts_with("pass") || answer.starts_with("yes"), Vec::new())
+        }
+    };
+    if pass {
+        issues.clear();
+    } else if issues.is_empty() {
+        issues.push(VlmIssue {
+            category: String::new(),
+            area: String::new(),
+            description: response.trim().to_string(),
+        });
+    }
+    VlmVerdict {
+        pass,
+        issues,
+        response: response.to_string(),
+        samples: 1,
+        agreement: 1.0,
+        retries: 0,
+    }
+}
+
+/// The outermost JSON object in a model's answer, if it has one
+fn json_object(response: &str) -> Option<serde_json::Value> {
+    match (response.find('{'), response.rfind('}')) {
+        (Some(start), Some(end)) if start < end => serde_json::from_str(&response[start..=end]).ok(),
+        _ => None,
+    }
+}
+
+/// Outcome of asking the VLM whether a screenshot shows an expected state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JudgeVerdict {
+    /// Whether the screenshot matches the expectation
+    pub pass: bool,
+    /// Why, in the model's words
+    pub reasoning: String,
+    /// The model's full answer
+    pub response: String,
+}
+
+/// Build a prompt asking the VLM to judge a screenshot against a description of the expected state
+pub fn build_judge_prompt(expected: &str) -> String {
+    format!(
+        "You are judging a test of a terminal application. The expected state is: {}\n\n\
+         Compare this screenshot with that description. Answer with a single JSON object and nothing else: \
+         {{\"pass\": true or false, \"reasoning\": \"...\"}}. \
+         Pass only if everything the description mentions is visible; the reasoning is one or two sentences \
+         and names what is missing or different when failing.",
+        expected
+    )
+}
+
+/// Parse the VLM's answer to a [`build_judge_prompt`] prompt.
+///
+/// As with [`parse_verdict`], an answer without a JSON object passes only if
+/// it starts with "pass" or "yes"; the whole answer is then the reasoning.
+pub fn parse_judge_verdict(response: &str) -> JudgeVerdict {
+    let (pass, reasoning) = match json_object(response) {
+        Some(value) => (
+            value["pass"].as_bool().unwrap_or(false),
+            value["reasoning"].as_str().unwrap_or("").trim().to_string(),
+        ),
+        None => {
+            let answer = response.trim_start().to_lowercase();
+            (answer.starts_with("pass") || answer.starts_with("yes"), String::new())
+        }
+    };
+    JudgeVerdict {
+        pass,
+        reasoning: if reasoning.is_empty() { response.trim().to_string() } else { reasoning },
+        response: response.to_string(),
+    }
+}
+
+/// Ask the VLM whether a screenshot shows the state `expected` describes
+pub fn judge_image(config: &VlmConfig, image_data: &[u8], expected: &str) -> VlmResult<JudgeVerdict> {
+    if config.is_local() {
+        if !config.analysis_mode.sends_image() {
+            return Err(local_text_mode());
+        }
+        let verdict = local_check(image_data, expected)?;
+        let reasoning = verdict.issues.iter().map(|issue| issue.description.as_str()).collect::<Vec<_>>().join("; ");
+        return Ok(JudgeVerdict {
+            pass: verdict.pass,
+            reasoning: if reasoning.is_empty() { verdict.response.clone() } else { reasoning },
+            response: verdict.response,
+        });
+    }
+    analyze_image(config, image_data, &build_judge_prompt(expected)).map(|response| parse_judge_verdict(&response))
+}
+
+/// Combine several verdicts for the same check by majority vote.
+///
+/// A tie fails. The result carries the issues and answer of the first
+/// verdict on the winning side, along with how many samples agreed.
+pub fn majority_verdict(verdicts: Vec<VlmVerdict>) -> Option<VlmVerdict> {
+    let total = verdicts.len();
+    let passes = verdicts.iter().filter(|v| v.pass).count();
+    let pass = passes * 2 > total;
+    let agreeing = if pass { passes } else { total - passes };
+    let mut verdict = verdicts.into_iter().find(|v| v.pass == pass)?;
+    verdict.samples = total as u32;
+    verdict.agreement = agreeing as f64 / total as f64;
+    Some(verdict)
+}
+
+/// Ask the VLM whether a screenshot satisfies a check.
+///
+/// With [`VlmConfig::samples`] above one the VLM is asked that many times
+/// and the majority verdict is returned; samples whose request fails are
+/// left out of the vote, and the error is returned only if all of them fail.
+pub fn verify_image(config: &VlmConfig, image_data: &[u8], check: &str) -> VlmResult<VlmVerdict> {
+    verify_with_prompt(config, image_data, check, build_check_prompt(check))
+}
+
+/// [`verify_image`] with the run context preamble in front of the prompt
+pub fn verify_image_in_context(config: &VlmConfig, image_data: &[u8], check: &str, context: &PromptContext) -> VlmResult<VlmVerdict> {
+    verify_with_prompt(config, image_data, check, config.contextual_prompt(&build_check_prompt(check), context))
+}
+
+fn verify_with_prompt(config: &VlmConfig, image_data: &[u8], check: &str, prompt: String) -> VlmResult<VlmVerdict> {
+    if config.is_local() {
+        if !config.analysis_mode.sends_image() {
+            return Err(local_text_mode());
+        }
+        // The local model is deterministic, so there is nothing to vote over
+        return local_check(image_data, check);
+    }
+    let mut verdicts = Vec::new();
+    let mut last_error = None;
+    let mut retries = 0;
+    for _ in 0..config.samples.max(1) {
+        let count_retries = |progress: VlmProgress| {
+            if let VlmProgress::Retrying { .. } = progress {
+                retries += 1;
+            }
+        };
+        match analyze_image_with_progress(config, image_data, &prompt, count_retries) {
+            Ok(response) => verdicts.push(parse_verdict(&response)),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    match (majority_verdict(verdicts), last_error) {
+        (Some(verdict), _) => Ok(VlmVerdict { retries, ..verdict }),
+        (None, Some(e)) => Err(e),
+        (None, None) => Err(VlmError::InvalidResponse("no samples taken".to_string())),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -409,16 +1622,380 @@ mod tests {
         assert_eq!(prompt, "Step 2: Did pressing enter work?");
     }
 
+    #[test]
+    fn test_parse_step_prompts() {
+        let json = parse_step_prompts("{\"1\": \"check if button is blue\", \"3\": \"verify dialog opened\"}").unwrap();
+        assert_eq!(json[&3], "verify dialog opened");
+
+        // Colons, '#' and newlines are part of the prompt
+        let prompts = parse_step_prompts(r#"{"2": "Menu: is it open? # note", " 4 ": "Verify the dialog\nand its title."}"#).unwrap();
+        assert_eq!(prompts[&2], "Menu: is it open? # note");
+        assert_eq!(prompts[&4], "Verify the dialog\nand its title.");
+        assert_eq!(parse_step_prompts(r#"{"first": "describe"}"#).unwrap_err(), "'first' is not a step number");
+
+        // YAML is rejected rather than misread: comments, quoted colons, block scalars
+        for yaml in ["3: \"Is the menu open?\" # note\n", "\"3\": \"a: b\"\n", "3: |\n  Verify that the dialog opened\n"] {
+            let err = parse_step_prompts(yaml).unwrap_err();
+            assert!(err.starts_with("expected a JSON object of step prompts"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_context_preamble() {
+        let context = PromptContext {
+            step: 2,
+            input: Some("enter".to_string()),
+            prior_inputs: vec!["down".to_string()],
+            terminal_size: Some([80, 24]),
+            binary: Some("htop".to_string()),
+            scenario: None,
+            screen_text: None,
+        };
+        assert_eq!(render_prompt("{binary} {cols}x{rows} after {inputs}, {input}; {scenario}", &context), "htop 80x24 after down, enter; none");
+
+        let config = VlmConfig::new("http://localhost:8080");
+        assert_eq!(
+            config.contextual_prompt("Is it open?", &context),
+            "Run context (JSON, not part of the screen): {\"step\":2,\"input\":\"enter\",\"prior_inputs\":[\"down\"],\"terminal_size\":[80,24],\"binary\":\"htop\"}\n\nIs it open?"
+        );
+        assert_eq!(config.clone().preamble("[{binary} step {step}] ").contextual_prompt("Q", &context), "[htop step 2] Q");
+        assert_eq!(config.no_preamble().contextual_prompt("Q", &context), "Q");
+    }
+
+    #[test]
+    fn test_screen_text_follows_prompt() {
+        let context = PromptContext {
+            screen_text: Some("  CPU [|||   12%]\n\n".to_string()),
+            ..Default::default()
+        };
+        let config = VlmConfig::new("http://localhost:8080").no_preamble();
+        let prompt = config.contextual_prompt("Is the meter shown?", &context);
+        assert!(prompt.starts_with("Is the meter shown?\n\nText on the screen"));
+        assert!(prompt.ends_with("```\n  CPU [|||   12%]\n```"));
+        assert_eq!(config.analysis_mode(AnalysisMode::Image).contextual_prompt("Q", &context), "Q");
+        assert_eq!(with_screen_text("Q", " \n \n"), "Q");
+    }
+
+    #[test]
+    fn test_image_goes_with_first_question() {
+        let turns = [
+            ChatMessage::user("What is selected?"),
+            ChatMessage::assistant("The second row."),
+            ChatMessage::user("Is it highlighted?"),
+        ];
+        let messages = image_messages(&VlmConfig::new("http://localhost:8080"), b"png", &turns);
+        assert_eq!(messages[0]["content"][0]["type"], "image_url");
+        assert_eq!(messages[0]["content"][1]["text"], "What is selected?");
+        assert_eq!(messages[1], serde_json::json!({ "role": "assistant", "content": "The second row." }));
+        assert_eq!(messages[2], serde_json::json!({ "role": "user", "content": "Is it highlighted?" }));
+    }
+
+    #[test]
+    fn test_parse_verdict() {
+        let verdict = parse_verdict(
+            "Sure: {\"pass\": false, \"issues\": [{\"category\": \"Alignment\", \"area\": \"footer\", \"description\": \"keys drift\"}]}",
+        );
+        assert!(!verdict.pass);
+        assert_eq!(verdict.issues[0].category, "alignment");
+        assert_eq!(verdict.issues[0].area, "footer");
+
+        assert!(parse_verdict("PASS - the dialog is open").pass);
+        let fail = parse_verdict("No, the dialog is missing");
+        assert!(!fail.pass);
+        assert_eq!(fail.issues[0].description, "No, the dialog is missing");
+    }
+
+    /// Answer one HTTP request on a local port with `status` and `body`, returning the endpoint
+    fn serve_once(status: &'static str, body: &'static str) -> String {
+        serve(vec![(status, body)])
+    }
+
+    /// Answer HTTP requests on a local port with each `(status, body)` in turn, returning the endpoint
+    fn serve(responses: Vec<(&'static str, &'static str)>) -> String {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1/chat/completions", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    line.clear();
+                }
+                reader.read_exact(&mut vec![0; length]).unwrap();
+                let response = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body);
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+        });
+        endpoint
+    }
+
+    #[test]
+    fn test_check_model() {
+        let models = "{\"object\":\"list\",\"data\":[{\"id\":\"/models/qwen3\"},{\"id\":\"llava\"}]}";
+        let endpoint = serve(vec![("200 OK", models), ("200 OK", "{\"version\":\"0.8.1\"}")]);
+        let info = check_model(&VlmConfig::new(endpoint).model("qwen3")).unwrap();
+        assert_eq!(info.models, ["/models/qwen3", "llava"]);
+        assert_eq!(info.version.as_deref(), Some("0.8.1"));
+        assert!(info.model_found);
+        assert_eq!(info.to_string(), "0.8.1, model 'qwen3' available");
+
+        assert!(model_listed("llava", &["llava:latest".to_string()]));
+        assert!(!model_listed("llava", &["llava:13b".to_string()]));
+        let endpoint = serve(vec![("404 Not Found", "no such route")]);
+        assert!(matches!(check_model(&VlmConfig::new(endpoint)), Err(VlmError::Http(404, _))));
+    }
+
+    #[test]
+    fn test_text_mode_leaves_images_out() {
+        assert_eq!("TEXT".parse(), Ok(AnalysisMode::Text));
+        assert!("ocr".parse::<AnalysisMode>().is_err());
+        assert_eq!(AnalysisMode::default().to_string(), "hybrid");
+
+        let text_only = VlmConfig::new("http://localhost:8080").analysis_mode(AnalysisMode::Text);
+        let history = [ConversationTurn { image_data: vec![1], prompt: "step 1".to_string(), answer: "A menu".to_string() }];
+        let messages = conversation_messages(&text_only, &history, &[2], "step 2");
+        assert_eq!(messages[0], serde_json::json!({ "role": "user", "content": "step 1" }));
+        assert_eq!(messages[2], serde_json::json!({ "role": "user", "content": "step 2" }));
+        let messages = image_messages(&text_only, b"png", &[ChatMessage::user("What is selected?")]);
+        assert_eq!(messages[0]["content"], "What is selected?");
+
+        let config = VlmConfig::new(serve_once("200 OK", "{\"choices\":[{\"message\":{\"content\":\"A menu\"}}]}")).analysis_mode(AnalysisMode::Text);
+        assert_eq!(complete_text(&config, "Describe:\n```\nFile Edit\n```", |_| {}).unwrap(), "A menu");
+        assert!(matches!(complete_text(&VlmConfig::new("local"), "Describe", |_| {}), Err(VlmError::LocalModel(_))));
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+            jitter: 0.5,
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(300));
+        let delay = policy.delay(2);
+        assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(200));
+
+        assert!(VlmError::from_status(429, None, "").is_retryable());
+        assert!(VlmError::Http(503, String::new()).is_retryable());
+        assert!(!VlmError::Http(400, String::new()).is_retryable());
+        assert!(!VlmError::ActivityTimeout(Duration::from_secs(60)).is_retryable());
+
+        let policy = RetryPolicy { initial_backoff: Duration::from_millis(1), ..policy };
+        let endpoint = serve(vec![("503 Service Unavailable", "loading model"), ("200 OK", "{\"choices\":[{\"message\":{\"content\":\"Ready\"}}]}")]);
+        let mut retries = Vec::new();
+        let answer = analyze_image_with_progress(&VlmConfig::new(endpoint).retry(policy), b"png", "Describe", |update| {
+            if let VlmProgress::Retrying { attempt, error, .. } = update {
+                retries.push((attempt, error));
+            }
+        });
+        assert_eq!(answer.unwrap(), "Ready");
+        assert_eq!(retries, [(1, "HTTP 503: loading model".to_string())]);
+    }
+
+    #[test]
+    fn test_requests_share_the_rate_limit() {
+        let answer = "{\"choices\":[{\"message\":{\"content\":\"Ready\"}}]}";
+        let config = VlmConfig::new(serve(vec![("200 OK", answer), ("200 OK", answer)])).rate_limit(RateLimiter::per_minute(120));
+        let mut paced = Vec::new();
+        for config in [config.clone(), config] {
+            analyze_image_with_progress(&config, b"png", "Describe", |update| {
+                if let VlmProgress::Paced(waited) = update {
+                    paced.push(waited);
+                }
+            })
+            .unwrap();
+        }
+        assert_eq!(paced.len(), 1);
+        assert!(paced[0] <= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_errors_are_classified() {
+        let error = VlmError::from_status(429, Some(Duration::from_secs(7)), "slow down");
+        assert!(matches!(&error, VlmError::RateLimited { body, .. } if body == "slow down"));
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(7)));
+        assert_eq!(error.to_string(), "Rate limited (retry after 7s): slow down");
+        assert!(matches!(VlmError::from_status(401, None, "bad key"), VlmError::Auth { status: 401, .. }));
+        assert!(!VlmError::from_status(403, None, "").is_retryable());
+        assert!(matches!(VlmError::from_status(500, None, " oops\n"), VlmError::Http(500, body) if body == "oops"));
+
+        let page = format!("<html>{}</html>", "x".repeat(500));
+        let endpoint = serve_once("200 OK", Box::leak(page.into_boxed_str()));
+        match analyze_image(&VlmConfig::new(endpoint), b"png", "Describe") {
+            Err(VlmError::MalformedJson { excerpt, .. }) => {
+                assert!(excerpt.starts_with("<html>xxx"));
+                assert_eq!(excerpt.chars().count(), EXCERPT_CHARS + 3);
+            }
+            other => panic!("expected malformed JSON, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_chat_over_http() {
+        let stream = "data: {\"choices\":[{\"delta\":{\"content\":\"Menu \"}}]}\n\ndata: {\"choices\":[{\"delta\":{\"content\":\"open\"}}]}\n\ndata: {\"choices\":[],\"usage\":{\"prompt_tokens\":812,\"completion_tokens\":2}}\n\ndata: [DONE]\n\n";
+        let config = VlmConfig::new(serve_once("200 OK", stream));
+        let mut progress = Vec::new();
+        let answer = analyze_image_with_progress(&config, b"png", "Describe", |update| progress.push(update));
+        assert_eq!(answer.unwrap(), "Menu open");
+        assert!(matches!(progress.last(), Some(VlmProgress::Complete(text)) if text == "Menu open"));
+        assert!(progress.iter().any(|update| matches!(update, VlmProgress::Usage(usage) if usage.prompt_tokens == 812)));
+
+        let plain = "{\"choices\":[{\"message\":{\"content\":\"A dialog\"}}]}";
+        assert_eq!(analyze_image(&VlmConfig::new(serve_once("200 OK", plain)), b"png", "Describe").unwrap(), "A dialog");
+
+        let ndjson = "{\"message\":{\"content\":\"Menu \"},\"done\":false}\n{\"message\":{\"content\":\"open\"},\"done\":false}\n{\"done\":true}\n";
+        let config = VlmConfig::new(serve_once("200 OK", ndjson)).provider(crate::vlm_provider::OllamaProvider);
+        assert_eq!(analyze_image(&config, b"png", "Describe").unwrap(), "Menu open");
+
+        let config = VlmConfig::new(serve_once("503 Service Unavailable", "loading model")).retry(RetryPolicy::none());
+        let error = analyze_image(&config, b"png", "Describe");
+        assert!(matches!(error, Err(VlmError::Http(503, body)) if body == "loading model"));
+    }
+
+    #[test]
+    fn test_cached_answers_skip_the_endpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let plain = "{\"choices\":[{\"message\":{\"content\":\"A dialog\"}}]}";
+        // The server answers once; the repeated request must come from the cache
+        let config = VlmConfig::new(serve_once("200 OK", plain)).retry(RetryPolicy::none()).cache(VlmCache::new(dir.path()));
+        assert_eq!(analyze_image(&config, b"png", "Describe").unwrap(), "A dialog");
+        assert_eq!(analyze_image(&config, b"png", "Describe").unwrap(), "A dialog");
+        assert!(analyze_image(&config, b"other png", "Describe").is_err());
+    }
+
+    #[test]
+    fn test_conversation_keeps_a_window_of_steps() {
+        let mut conversation = VlmConversation::new(2);
+        for step in 0..3u8 {
+            conversation.remember(ConversationTurn {
+                image_data: vec![step],
+                prompt: format!("step {}", step),
+                answer: format!("answer {}", step),
+            });
+        }
+        assert_eq!(conversation.len(), 2);
+        let messages = conversation_messages(&VlmConfig::new("http://localhost:8080"), conversation.turns.make_contiguous(), &[3], "step 3");
+        let roles: Vec<_> = messages.as_array().unwrap().iter().map(|m| m["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, ["user", "assistant", "user", "assistant", "user"]);
+        assert_eq!(messages[0]["content"][1]["text"], "step 1");
+        assert_eq!(messages[3]["content"], "answer 2");
+
+        let mut isolated = VlmConversation::new(0);
+        isolated.remember(ConversationTurn { image_data: vec![0], prompt: String::new(), answer: String::new() });
+        assert!(isolated.is_empty());
+    }
+
+    #[test]
+    fn test_build_comparison_prompt() {
+        assert!(build_comparison_prompt(0, None).contains("the initial state"));
+        let prompt = build_comparison_prompt(2, Some("enter"));
+        assert!(prompt.contains("step 2, after pressing 'enter'"));
+        assert!(prompt.ends_with("\"Regression: yes\" or \"Regression: no\"."));
+    }
+
+    #[test]
+    fn test_parse_judge_verdict() {
+        let verdict = parse_judge_verdict("```json\n{\"pass\": false, \"reasoning\": \" The progress bar is at 40%, not 0%. \"}\n```");
+        assert!(!verdict.pass);
+        assert_eq!(verdict.reasoning, "The progress bar is at 40%, not 0%.");
+
+        let bare = parse_judge_verdict("Yes, the Increment button is selected.");
+        assert!(bare.pass);
+        assert_eq!(bare.reasoning, "Yes, the Increment button is selected.");
+        assert!(!parse_judge_verdict("{\"reasoning\": \"unclear\"}").pass);
+    }
+
+    #[test]
+    fn test_parse_structured() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": { "selected": { "type": "string" }, "progress": { "type": "integer", "maximum": 100 } },
+            "required": ["selected", "progress"]
+        });
+        let value = parse_structured("Here it is:\n```json\n{\"selected\": \"Increment\", \"progress\": 0}\n```", &schema).unwrap();
+        assert_eq!(value["selected"], "Increment");
+
+        let problems = parse_structured("{\"progress\": 140}", &schema).unwrap_err();
+        assert_eq!(problems, ["missing field `selected`", "140 is greater than the maximum 100"]);
+        assert_eq!(parse_structured("The button is selected.", &schema).unwrap_err(), ["the answer is not JSON"]);
+    }
+
+    #[test]
+    fn test_majority_verdict() {
+        let votes = vec![
+            parse_verdict("No, the footer is cut off"),
+            parse_verdict("{\"pass\": true}"),
+            parse_verdict("PASS"),
+        ];
+        let verdict = majority_verdict(votes).unwrap();
+        assert!(verdict.pass);
+        assert_eq!(verdict.samples, 3);
+        assert!((verdict.agreement - 2.0 / 3.0).abs() < 1e-9);
+
+        let tie = majority_verdict(vec![parse_verdict("yes"), parse_verdict("no")]).unwrap();
+        assert!(!tie.pass);
+        assert_eq!(tie.agreement, 0.5);
+        assert!(majority_verdict(Vec::new()).is_none());
+    }
+
     #[test]
     fn test_vlm_config_builder() {
         let config = VlmConfig::new("http://localhost:8080")
             .model("llava")
             .max_tokens(200)
-            .activity_timeout(30);
+            .activity_timeout(30)
+            .samples(0);
 
         assert_eq!(config.endpoint, "http://localhost:8080");
         assert_eq!(config.model, "llava");
         assert_eq!(config.max_tokens, 200);
         assert_eq!(config.activity_timeout, 30);
+        assert_eq!(config.samples, 1);
+        assert_eq!(config.sampling_temperature(), None);
+        assert!(!config.is_local());
+        assert_eq!(config.samples(5).sampling_temperature(), Some(DEFAULT_VOTING_TEMPERATURE));
+        assert!(VlmConfig::new("Local").is_local());
+        assert!(is_local_endpoint(" "));
+        assert_eq!(
+            embeddings_endpoint("http://127.0.0.1:8080/v1/chat/completions"),
+            "http://127.0.0.1:8080/v1/embeddings"
+        );
+    }
+
+    #[test]
+    fn test_usage_and_cost() {
+        let mut usage = VlmUsage { prompt_tokens: 812, completion_tokens: 1 };
+        usage.merge(VlmUsage { prompt_tokens: 0, completion_tokens: 40 });
+        assert_eq!(usage, VlmUsage { prompt_tokens: 812, completion_tokens: 40 });
+        usage += VlmUsage { prompt_tokens: 188, completion_tokens: 60 };
+        assert_eq!(usage.total_tokens(), 1100);
+
+        let prices: PriceTable = serde_json::from_str("{\"gpt-4o\": {\"prompt\": 2.5, \"completion\": 10.0}}").unwrap();
+        let cost = prices.cost("gpt-4o", &usage).unwrap();
+        assert!((cost - 0.0035).abs() < 1e-9);
+        assert_eq!(prices.cost("qwen3", &usage), None);
+    }
+
+    #[test]
+    fn test_api_key_is_sent_but_not_logged() {
+        let config = VlmConfig::new("https://openrouter.ai/api/v1/chat/completions")
+            .api_key("sk-secret")
+            .header("X-Title", "nightly");
+        let headers = config.request_headers();
+        assert!(headers.contains(&("Authorization".to_string(), "Bearer sk-secret".to_string())));
+        assert!(headers.contains(&("X-Title".to_string(), "nightly".to_string())));
+        assert!(!format!("{:?}", config).contains("sk-secret"));
+
+        let anthropic = config.provider(crate::vlm_provider::AnthropicProvider).request_headers();
+        assert!(anthropic.contains(&("x-api-key".to_string(), "sk-secret".to_string())));
     }
 }
@@ -0,0 +1,140 @@
+//! On-disk cache of VLM answers.
+//!
+//! Re-running a suite captures the same screenshots and asks the same
+//! questions. A [`VlmCache`] stores each answer under the SHA-256 of the
+//! model and the request (prompts and images), so an identical request is
+//! answered from disk instead of the endpoint. Entries older than the TTL
+//! are ignored and replaced by the next answer.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::vlm::{VlmConfig, analyze_image};
+//! use cli_vision::vlm_cache::VlmCache;
+//!
+//! let config = VlmConfig::default().cache(VlmCache::from_env());
+//! let png = std::fs::read("state_1.png").unwrap();
+//! // The second call is answered from the cache
+//! let first = analyze_image(&config, &png, "Describe the screen").unwrap();
+//! let second = analyze_image(&config, &png, "Describe the screen").unwrap();
+//! assert_eq!(first, second);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::{self, DEFAULT_VLM_CACHE_TTL, ENV_VLM_CACHE_DIR, ENV_VLM_CACHE_TTL};
+
+/// A cached answer, as stored on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedAnswer {
+    model: String,
+    answer: String,
+}
+
+/// Directory of VLM answers keyed by request
+#[derive(Debug, Clone, PartialEq)]
+pub struct VlmCache {
+    /// Cache directory; answers live at `<dir>/<key[..2]>/<key>.json`
+    pub dir: PathBuf,
+    /// How long an answer stays valid; `None` keeps answers forever
+    pub ttl: Option<Duration>,
+}
+
+impl VlmCache {
+    /// Create a cache in the given directory whose answers never expire
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into(), ttl: None }
+    }
+
+    /// Create a cache in `CLI_VISION_VLM_CACHE_DIR`, or `~/.cache/cli-vision/vlm`,
+    /// with the TTL from `CLI_VISION_VLM_CACHE_TTL` (seconds, 0 for no expiry)
+    pub fn from_env() -> Self {
+        let dir = std::env::var_os(ENV_VLM_CACHE_DIR)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| config::cache_base_dir().join("vlm"));
+        let ttl = std::env::var(ENV_VLM_CACHE_TTL)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_VLM_CACHE_TTL);
+        Self {
+            dir,
+            ttl: (ttl > 0).then(|| Duration::from_secs(ttl)),
+        }
+    }
+
+    /// Expire answers after `ttl`
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Hex-encoded SHA-256 of a model and request body
+    pub fn key(model: &str, request: &serde_json::Value) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(model.as_bytes());
+        hasher.update([0]);
+        hasher.update(request.to_string().as_bytes());
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Path an answer is cached at
+    pub fn path_for(&self, key: &str) -> PathBuf {
+        self.dir.join(&key[..2.min(key.len())]).join(format!("{}.json", key))
+    }
+
+    /// The cached answer for `key`, unless it is missing, unreadable or expired
+    pub fn get(&self, key: &str) -> Option<String> {
+        let path = self.path_for(key);
+        if let Some(ttl) = self.ttl {
+            let age = fs::metadata(&path).ok()?.modified().ok()?.elapsed().unwrap_or_default();
+            if age > ttl {
+                return None;
+            }
+        }
+        let cached: CachedAnswer = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+        Some(cached.answer)
+    }
+
+    /// Store `answer` for `key`
+    pub fn put(&self, key: &str, model: &str, answer: &str) -> io::Result<()> {
+        let path = self.path_for(key);
+        fs::create_dir_all(path.parent().expect("cache path has a parent"))?;
+        let cached = CachedAnswer {
+            model: model.to_string(),
+            answer: answer.to_string(),
+        };
+        // Write then rename so a parallel run never reads half an entry
+        let partial = path.with_extension("part");
+        fs::write(&partial, serde_json::to_string(&cached)?)?;
+        fs::rename(partial, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_answers_are_keyed_by_model_and_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = VlmCache::new(dir.path());
+        let request = serde_json::json!({ "messages": [{ "role": "user", "content": "Describe" }] });
+        let key = VlmCache::key("qwen3", &request);
+        assert_eq!(key.len(), 64);
+        assert_ne!(key, VlmCache::key("llava", &request));
+        assert_eq!(cache.get(&key), None);
+
+        cache.put(&key, "qwen3", "A menu").unwrap();
+        assert_eq!(cache.get(&key).as_deref(), Some("A menu"));
+        assert!(cache.path_for(&key).starts_with(dir.path().join(&key[..2])));
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(cache.clone().ttl(Duration::from_millis(10)).get(&key), None);
+        assert_eq!(cache.ttl(Duration::from_secs(60)).get(&key).as_deref(), Some("A menu"));
+    }
+}
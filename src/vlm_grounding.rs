@@ -0,0 +1,288 @@
+//! Grounded VLM analysis: findings with their location on screen.
+//!
+//! "The footer is cut off" still leaves a reviewer to find the footer.
+//! [`analyze_image_grounded`] asks the model for each finding together with
+//! a box around it, in terminal cells or in pixels of the screenshot, and
+//! [`annotate_findings`] outlines those boxes on a copy of the screenshot,
+//! each in its own color and tagged with the finding's number.
+//!
+//! Boxes are returned in the screenshot's own coordinates: pixel boxes the
+//! model gave for a downscaled image (see [`crate::vlm_image`]) are scaled
+//! back up.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::vlm::VlmConfig;
+//! use cli_vision::vlm_grounding::{analyze_image_grounded, annotate_findings};
+//!
+//! let png = std::fs::read("state_1.png").unwrap();
+//! let analysis = analyze_image_grounded(&VlmConfig::default(), &png, "Find layout problems").unwrap();
+//! for (number, finding) in (1..).zip(&analysis.findings) {
+//!     println!("{}. {}", number, finding.description);
+//! }
+//! std::fs::write("state_1.findings.png", annotate_findings(&png, &analysis.findings).unwrap()).unwrap();
+//! ```
+
+use font8x8::{BASIC_FONTS, UnicodeFonts};
+use image::{Rgb, RgbImage};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::snapshot::{CELL_HEIGHT, CELL_WIDTH, PixelRect};
+use crate::vlm::{VlmConfig, VlmError, VlmResult, analyze_image_structured};
+
+/// Colors boxes are drawn in, one per finding in turn
+const COLORS: [Rgb<u8>; 6] = [
+    Rgb([255, 40, 40]),
+    Rgb([255, 200, 0]),
+    Rgb([0, 200, 255]),
+    Rgb([255, 0, 220]),
+    Rgb([60, 230, 60]),
+    Rgb([255, 130, 0]),
+];
+
+/// Width of box outlines in pixels
+const LINE_WIDTH: u32 = 2;
+
+/// What a finding's box is measured in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BoxUnit {
+    /// Terminal cells: columns and rows from the top left, starting at 0
+    #[default]
+    Cell,
+    /// Pixels of the screenshot, from the top left
+    Pixel,
+}
+
+/// A rectangle around a finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FindingBox {
+    /// Whether the box is in terminal cells or pixels
+    #[serde(default)]
+    pub unit: BoxUnit,
+    /// Left edge (column or pixel)
+    pub x: u32,
+    /// Top edge (row or pixel)
+    pub y: u32,
+    /// Width (columns or pixels)
+    pub width: u32,
+    /// Height (rows or pixels)
+    pub height: u32,
+}
+
+impl FindingBox {
+    /// The box in pixels of the screenshot
+    pub fn to_pixels(&self) -> PixelRect {
+        let (scale_x, scale_y) = match self.unit {
+            BoxUnit::Cell => (CELL_WIDTH, CELL_HEIGHT),
+            BoxUnit::Pixel => (1, 1),
+        };
+        PixelRect {
+            x: self.x.saturating_mul(scale_x),
+            y: self.y.saturating_mul(scale_y),
+            width: self.width.max(1).saturating_mul(scale_x),
+            height: self.height.max(1).saturating_mul(scale_y),
+        }
+    }
+
+    /// The box with pixel coordinates multiplied by `scale_x` and `scale_y`
+    fn scaled(self, scale_x: f64, scale_y: f64) -> Self {
+        if self.unit == BoxUnit::Cell {
+            return self;
+        }
+        let scale = |value: u32, factor: f64| (f64::from(value) * factor).round() as u32;
+        Self {
+            x: scale(self.x, scale_x),
+            y: scale(self.y, scale_y),
+            width: scale(self.width, scale_x),
+            height: scale(self.height, scale_y),
+            ..self
+        }
+    }
+}
+
+/// Something the model found, and where
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Finding {
+    /// What was found
+    pub description: String,
+    /// Where it is on screen
+    #[serde(rename = "box")]
+    pub location: FindingBox,
+}
+
+/// A grounded answer: a summary and the findings it is based on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct GroundedAnalysis {
+    /// The answer to the prompt, in a few sentences
+    pub summary: String,
+    /// Each finding with a box around it; empty if there is nothing to point at
+    pub findings: Vec<Finding>,
+}
+
+/// Ask about a screenshot and have each finding located with a box.
+///
+/// The answer is requested as JSON (see
+/// [`analyze_image_structured`](crate::vlm::analyze_image_structured)), so the
+/// local model cannot answer.
+pub fn analyze_image_grounded(config: &VlmConfig, image_data: &[u8], prompt: &str) -> VlmResult<GroundedAnalysis> {
+    let size = image::load_from_memory(image_data).ok().map(|image| (image.width(), image.height()));
+    // Pixel boxes refer to the image the model saw, which may be smaller
+    let sent = size.map(|(width, height)| match config.image_prep.max_size {
+        Some(max) if width.max(height) > max => {
+            let scale = f64::from(max) / f64::from(width.max(height));
+            ((f64::from(width) * scale).round().max(1.0) as u32, (f64::from(height) * scale).round().max(1.0) as u32)
+        }
+        _ => (width, height),
+    });
+    let pixels = sent.filter(|_| config.analysis_mode.sends_image());
+    let grid = size.map(|(width, height)| (width / CELL_WIDTH, height / CELL_HEIGHT));
+    let schema = serde_json::to_value(schemars::schema_for!(GroundedAnalysis)).expect("schema serializes to JSON");
+    let answer = analyze_image_structured(config, image_data, &build_grounded_prompt(prompt, grid, pixels), &schema)?;
+    let mut analysis: GroundedAnalysis =
+        serde_json::from_value(answer).map_err(|e| VlmError::InvalidResponse(format!("grounded answer: {}", e)))?;
+    if let (Some((width, height)), Some((sent_width, sent_height))) = (size, sent) {
+        let (scale_x, scale_y) = (f64::from(width) / f64::from(sent_width), f64::from(height) / f64::from(sent_height));
+        for finding in &mut analysis.findings {
+            finding.location = finding.location.scaled(scale_x, scale_y);
+        }
+    }
+    Ok(analysis)
+}
+
+/// `prompt` with instructions to put a box around every finding, for a
+/// screen of `grid` columns and rows whose screenshot the model sees at `pixels`
+pub fn build_grounded_prompt(prompt: &str, grid: Option<(u32, u32)>, pixels: Option<(u32, u32)>) -> String {
+    let mut text = format!(
+        "{}\n\nList each problem or element your answer relies on as a finding, with a box around where it is on screen. \
+         Give boxes in terminal cells (\"unit\": \"cell\"), counting columns and rows from 0 at the top left",
+        prompt
+    );
+    if let Some((cols, rows)) = grid {
+        text.push_str(&format!("; the terminal is {} columns by {} rows", cols, rows));
+    }
+    text.push('.');
+    if let Some((width, height)) = pixels {
+        text.push_str(&format!(
+            " Use pixels (\"unit\": \"pixel\") only for things that do not line up with cells; the screenshot is {}x{} pixels.",
+            width, height
+        ));
+    }
+    text
+}
+
+/// The screenshot with each finding's box outlined and numbered from 1, as PNG
+pub fn annotate_findings(png: &[u8], findings: &[Finding]) -> image::ImageResult<Vec<u8>> {
+    let mut image = image::load_from_memory(png)?.to_rgb8();
+    for (number, (finding, color)) in (1..).zip(findings.iter().zip(COLORS.iter().cycle())) {
+        let rect = finding.location.to_pixels();
+        for inset in 0..LINE_WIDTH {
+            outline(&mut image, rect, inset, *color);
+        }
+        label(&mut image, rect, number, *color);
+    }
+    let mut annotated = Vec::new();
+    image.write_to(&mut std::io::Cursor::new(&mut annotated), image::ImageOutputFormat::Png)?;
+    Ok(annotated)
+}
+
+/// Set a pixel if it is on the image
+fn put(image: &mut RgbImage, x: i64, y: i64, color: Rgb<u8>) {
+    if let (Ok(x), Ok(y)) = (u32::try_from(x), u32::try_from(y))
+        && x < image.width()
+        && y < image.height()
+    {
+        image.put_pixel(x, y, color);
+    }
+}
+
+/// Draw the border of `rect` shrunk by `inset` pixels on each side
+fn outline(image: &mut RgbImage, rect: PixelRect, inset: u32, color: Rgb<u8>) {
+    let (x0, y0) = (i64::from(rect.x) + i64::from(inset), i64::from(rect.y) + i64::from(inset));
+    let x1 = i64::from(rect.x) + i64::from(rect.width) - 1 - i64::from(inset);
+    let y1 = i64::from(rect.y) + i64::from(rect.height) - 1 - i64::from(inset);
+    for x in x0..=x1 {
+        put(image, x, y0, color);
+        put(image, x, y1, color);
+    }
+    for y in y0..=y1 {
+        put(image, x0, y, color);
+        put(image, x1, y, color);
+    }
+}
+
+/// Write `number` in black on a tag of `color` at the top left of `rect`,
+/// above the box when there is room and inside it otherwise
+fn label(image: &mut RgbImage, rect: PixelRect, number: usize, color: Rgb<u8>) {
+    const SCALE: u32 = 2;
+    const GLYPH: u32 = 8 * SCALE;
+    let digits = number.to_string();
+    let (width, height) = (digits.len() as u32 * GLYPH + 2, GLYPH + 2);
+    let left = i64::from(rect.x);
+    let top = if rect.y >= height { i64::from(rect.y - height) } else { i64::from(rect.y) };
+    for y in 0..height {
+        for x in 0..width {
+            put(image, left + i64::from(x), top + i64::from(y), color);
+        }
+    }
+    for (index, digit) in (0u32..).zip(digits.chars()) {
+        let Some(bitmap) = BASIC_FONTS.get(digit) else { continue };
+        for (row, bits) in (0u32..).zip(bitmap) {
+            for col in 0..8 {
+                // font8x8 stores the leftmost pixel in the least significant bit
+                if bits & (1 << col) != 0 {
+                    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let x = left + 1 + i64::from(index * GLYPH + col * SCALE + dx);
+                        put(image, x, top + 1 + i64::from(row * SCALE + dy), Rgb([0, 0, 0]));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screenshot(width: u32, height: u32) -> Vec<u8> {
+        let mut png = Vec::new();
+        RgbImage::from_pixel(width, height, Rgb([0, 0, 0]))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageOutputFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn test_boxes_are_converted_to_screenshot_pixels() {
+        let cell = FindingBox { unit: BoxUnit::Cell, x: 2, y: 1, width: 3, height: 0 };
+        assert_eq!(cell.to_pixels(), PixelRect { x: 2 * CELL_WIDTH, y: CELL_HEIGHT, width: 3 * CELL_WIDTH, height: CELL_HEIGHT });
+        assert_eq!(cell.scaled(2.0, 2.0), cell);
+        let pixel = FindingBox { unit: BoxUnit::Pixel, x: 10, y: 20, width: 30, height: 5 };
+        assert_eq!(pixel.scaled(2.0, 1.5), FindingBox { x: 20, y: 30, width: 60, height: 8, ..pixel });
+
+        let answer = r#"{"summary":"Footer cut off","findings":[{"description":"Footer","box":{"unit":"cell","x":0,"y":23,"width":80,"height":1}}]}"#;
+        let analysis: GroundedAnalysis = serde_json::from_str(answer).unwrap();
+        assert_eq!(analysis.findings[0].location.y, 23);
+        let prompt = build_grounded_prompt("Find problems", Some((80, 24)), Some((640, 384)));
+        assert!(prompt.contains("80 columns by 24 rows") && prompt.contains("640x384 pixels"));
+        assert!(!build_grounded_prompt("Find problems", Some((80, 24)), None).contains("pixel"));
+    }
+
+    #[test]
+    fn test_findings_are_outlined_and_numbered() {
+        let findings = [
+            Finding { description: "Title".to_string(), location: FindingBox { unit: BoxUnit::Cell, x: 1, y: 2, width: 2, height: 1 } },
+            Finding { description: "Off screen".to_string(), location: FindingBox { unit: BoxUnit::Pixel, x: 500, y: 500, width: 9, height: 9 } },
+        ];
+        let annotated = image::load_from_memory(&annotate_findings(&screenshot(160, 160), &findings).unwrap()).unwrap().to_rgb8();
+        let rect = findings[0].location.to_pixels();
+        assert_eq!(annotated.get_pixel(rect.x, rect.y + 5), &COLORS[0]);
+        assert_eq!(annotated.get_pixel(rect.x + rect.width - 1, rect.y + 5), &COLORS[0]);
+        assert_eq!(annotated.get_pixel(rect.x + 5, rect.y + 5), &Rgb([0, 0, 0]));
+        // The tag with the number sits above the box
+        assert_eq!(annotated.get_pixel(rect.x, rect.y - 1), &COLORS[0]);
+    }
+}
@@ -0,0 +1,195 @@
+//! Preparing screenshots before they are sent to a VLM.
+//!
+//! A full-size PNG of a 200x60 terminal is over a megapixel, and its base64
+//! text alone can exceed a model's context. [`ImagePrep`] downscales
+//! screenshots so their longer side fits a limit and re-encodes them as
+//! JPEG or WebP. Only the request is affected: the screenshots written to
+//! disk keep their original size and format.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::vlm::{VlmConfig, analyze_image};
+//! use cli_vision::vlm_image::{ImageEncoding, ImagePrep};
+//!
+//! let prep = ImagePrep::default().max_size(1024).encoding(ImageEncoding::Jpeg).quality(80);
+//! let config = VlmConfig::default().image_prep(prep);
+//! let png = std::fs::read("state_1.png").unwrap();
+//! let description = analyze_image(&config, &png, "Describe the screen").unwrap();
+//! ```
+
+use image::imageops::FilterType;
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use crate::config;
+
+/// Format screenshots are sent in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImageEncoding {
+    /// PNG, as captured (lossless)
+    #[default]
+    Png,
+    /// JPEG at [`ImagePrep::quality`]
+    Jpeg,
+    /// Lossless WebP, usually smaller than PNG for terminal screenshots
+    WebP,
+}
+
+impl ImageEncoding {
+    /// MIME type of the encoded image
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ImageEncoding::Png => "image/png",
+            ImageEncoding::Jpeg => "image/jpeg",
+            ImageEncoding::WebP => "image/webp",
+        }
+    }
+}
+
+impl FromStr for ImageEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "png" => Ok(ImageEncoding::Png),
+            "jpeg" | "jpg" => Ok(ImageEncoding::Jpeg),
+            "webp" => Ok(ImageEncoding::WebP),
+            other => Err(format!("unknown image format '{}' (expected: png, jpeg, webp)", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for ImageEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ImageEncoding::Png => "png",
+            ImageEncoding::Jpeg => "jpeg",
+            ImageEncoding::WebP => "webp",
+        })
+    }
+}
+
+/// How screenshots are resized and encoded before they are sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImagePrep {
+    /// Longest side in pixels; larger screenshots are scaled down to fit
+    pub max_size: Option<u32>,
+    /// Format sent to the model
+    pub encoding: ImageEncoding,
+    /// JPEG quality, from 1 to 100
+    pub quality: u8,
+}
+
+impl Default for ImagePrep {
+    fn default() -> Self {
+        let cfg = config::get();
+        Self {
+            max_size: cfg.vlm.image_max_size,
+            encoding: cfg.vlm.image_format.parse().unwrap_or_default(),
+            quality: cfg.vlm.image_quality,
+        }
+    }
+}
+
+impl ImagePrep {
+    /// Send screenshots as captured
+    pub fn original() -> Self {
+        Self {
+            max_size: None,
+            encoding: ImageEncoding::Png,
+            quality: config::DEFAULT_VLM_IMAGE_QUALITY,
+        }
+    }
+
+    /// Scale screenshots down so their longer side is at most `pixels`
+    pub fn max_size(mut self, pixels: u32) -> Self {
+        self.max_size = Some(pixels.max(1));
+        self
+    }
+
+    pub fn encoding(mut self, encoding: ImageEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn quality(mut self, quality: u8) -> Self {
+        self.quality = quality.clamp(1, 100);
+        self
+    }
+
+    /// The screenshot to send and its MIME type.
+    ///
+    /// PNG data that needs no resizing is passed through untouched, as is
+    /// anything that cannot be decoded or re-encoded.
+    pub fn apply<'a>(&self, png: &'a [u8]) -> (Cow<'a, [u8]>, &'static str) {
+        let original = (Cow::Borrowed(png), ImageEncoding::Png.mime_type());
+        if self.max_size.is_none() && self.encoding == ImageEncoding::Png {
+            return original;
+        }
+        let Ok(image) = image::load_from_memory(png) else {
+            return original;
+        };
+        let image = match self.max_size {
+            Some(max) if image.width().max(image.height()) > max => image.resize(max, max, FilterType::Triangle),
+            _ if self.encoding == ImageEncoding::Png => return original,
+            _ => image,
+        };
+        match self.encode(&image.to_rgb8()) {
+            Ok(data) => (Cow::Owned(data), self.encoding.mime_type()),
+            Err(_) => original,
+        }
+    }
+
+    fn encode(&self, image: &image::RgbImage) -> image::ImageResult<Vec<u8>> {
+        let mut data = Vec::new();
+        match self.encoding {
+            ImageEncoding::Png => image.write_to(&mut std::io::Cursor::new(&mut data), image::ImageOutputFormat::Png)?,
+            ImageEncoding::Jpeg => image::codecs::jpeg::JpegEncoder::new_with_quality(&mut data, self.quality).encode_image(image)?,
+            ImageEncoding::WebP => image::codecs::webp::WebPEncoder::new_lossless(&mut data).encode(
+                image.as_raw(),
+                image.width(),
+                image.height(),
+                image::ColorType::Rgb8,
+            )?,
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+
+    fn screenshot(width: u32, height: u32) -> Vec<u8> {
+        let image = image::RgbImage::from_fn(width, height, |x, y| image::Rgb([(x % 256) as u8, (y % 256) as u8, 40]));
+        let mut png = Vec::new();
+        image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageOutputFormat::Png).unwrap();
+        png
+    }
+
+    #[test]
+    fn test_screenshots_are_downscaled_and_reencoded() {
+        let png = screenshot(400, 200);
+        let (data, mime) = ImagePrep::original().apply(&png);
+        assert!(matches!(data, Cow::Borrowed(_)));
+        assert_eq!(mime, "image/png");
+
+        let (data, mime) = ImagePrep::original().max_size(100).apply(&png);
+        assert_eq!(mime, "image/png");
+        assert_eq!(image::load_from_memory(&data).unwrap().dimensions(), (100, 50));
+
+        let (data, mime) = ImagePrep::original().max_size(1000).encoding(ImageEncoding::Jpeg).quality(60).apply(&png);
+        assert_eq!(mime, "image/jpeg");
+        assert_eq!(image::load_from_memory(&data).unwrap().dimensions(), (400, 200));
+
+        let (data, mime) = ImagePrep::original().encoding(ImageEncoding::WebP).apply(&png);
+        assert_eq!(mime, "image/webp");
+        assert_eq!(image::guess_format(&data).unwrap(), image::ImageFormat::WebP);
+
+        // Data that is not an image is sent as it is
+        assert_eq!(ImagePrep::original().max_size(10).apply(b"png").0.as_ref(), b"png");
+        assert_eq!("JPG".parse(), Ok(ImageEncoding::Jpeg));
+    }
+}
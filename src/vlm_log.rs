@@ -0,0 +1,171 @@
+//! Transcripts of VLM requests and answers.
+//!
+//! A flaky analysis is hard to debug from its answer alone. A
+//! [`VlmTranscript`] appends one JSON line per request to a file (usually
+//! [`Session::vlm_log_path`](crate::session::Session::vlm_log_path)): the
+//! prompts sent, the model, how long it took, the tokens used and the full
+//! answer or error. Screenshots are replaced by their SHA-256 and size, so
+//! the log stays small and can be matched against the saved PNGs. Headers
+//! and API keys are never written.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::session::Session;
+//! use cli_vision::vlm::{VlmConfig, analyze_image};
+//! use cli_vision::vlm_log::VlmTranscript;
+//!
+//! let session = Session::new();
+//! let config = VlmConfig::default().transcript(VlmTranscript::new(session.vlm_log_path()));
+//! let png = std::fs::read("state_1.png").unwrap();
+//! analyze_image(&config, &png, "Describe the screen").unwrap();
+//! // session/vlm_log.jsonl now holds the request and its answer
+//! ```
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::vlm::VlmUsage;
+
+/// File name of the transcript in a session directory
+pub const VLM_LOG_FILE: &str = "vlm_log.jsonl";
+
+/// One request and its outcome
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    /// When the request was made (RFC 3339)
+    pub timestamp: String,
+    /// API format of the request
+    pub provider: String,
+    pub endpoint: String,
+    pub model: String,
+    /// Chat messages as sent, with images replaced by [`image_placeholder`]s
+    pub messages: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<serde_json::Value>,
+    /// Time until the answer was complete, retries included
+    pub duration_ms: u64,
+    /// Failed tries before the last one
+    #[serde(default)]
+    pub retries: u32,
+    /// Whether the answer came from the cache rather than the endpoint
+    #[serde(default)]
+    pub cached: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<VlmUsage>,
+    /// The full answer, if the request succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    /// Why the request failed, if it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A JSON Lines file VLM requests are appended to
+#[derive(Debug, Clone, PartialEq)]
+pub struct VlmTranscript {
+    pub path: PathBuf,
+}
+
+impl VlmTranscript {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Append an entry as one line
+    pub fn record(&self, entry: &TranscriptEntry) -> io::Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        // One write per line keeps lines whole when parallel runs share a file
+        OpenOptions::new().create(true).append(true).open(&self.path)?.write_all(line.as_bytes())
+    }
+}
+
+/// Read the entries of a transcript, skipping lines that do not parse
+pub fn read_transcript(path: impl AsRef<Path>) -> io::Result<Vec<TranscriptEntry>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// What an image in a data URL is logged as: its type, size in bytes and SHA-256.
+///
+/// The hash is of the decoded image, so an unmodified screenshot's hash
+/// matches `sha256sum` of its PNG.
+pub fn image_placeholder(data_url: &str) -> String {
+    let (media_type, encoded) = data_url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"))
+        .unwrap_or(("unknown", data_url));
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .unwrap_or_else(|_| encoded.as_bytes().to_vec());
+    let hash: String = Sha256::digest(&data).iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{} {} bytes sha256:{}", media_type, data.len(), hash)
+}
+
+/// `messages` with every image data URL replaced by its [`image_placeholder`]
+pub fn redact_images(messages: &serde_json::Value) -> serde_json::Value {
+    match messages {
+        serde_json::Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| match (key.as_str(), value.as_str()) {
+                ("url", Some(url)) if url.starts_with("data:") => (key.clone(), serde_json::Value::String(image_placeholder(url))),
+                _ => (key.clone(), redact_images(value)),
+            })
+            .collect(),
+        serde_json::Value::Array(items) => items.iter().map(redact_images).collect(),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript_lines_hide_images() {
+        let messages = serde_json::json!([{
+            "role": "user",
+            "content": [
+                { "type": "image_url", "image_url": { "url": "data:image/png;base64,UE5H" } },
+                { "type": "text", "text": "Describe" }
+            ]
+        }]);
+        let redacted = redact_images(&messages);
+        // UE5H is "PNG" in base64
+        assert_eq!(
+            redacted[0]["content"][0]["image_url"]["url"],
+            "image/png 3 bytes sha256:796120837694d3f3f29259cfeb25091698c2a0aa87873658d840b4993ee889b3"
+        );
+        assert_eq!(redacted[0]["content"][1], messages[0]["content"][1]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let transcript = VlmTranscript::new(dir.path().join(VLM_LOG_FILE));
+        let entry = TranscriptEntry {
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            provider: "openai".to_string(),
+            endpoint: "http://localhost:8080/v1/chat/completions".to_string(),
+            model: "qwen3".to_string(),
+            messages: redacted,
+            response_format: None,
+            duration_ms: 1200,
+            retries: 0,
+            cached: false,
+            usage: Some(VlmUsage { prompt_tokens: 900, completion_tokens: 40 }),
+            response: Some("A menu".to_string()),
+            error: None,
+        };
+        transcript.record(&entry).unwrap();
+        transcript.record(&TranscriptEntry { response: None, error: Some("HTTP 500".to_string()), ..entry.clone() }).unwrap();
+        let entries = read_transcript(&transcript.path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], entry);
+        assert_eq!(entries[1].error.as_deref(), Some("HTTP 500"));
+    }
+}
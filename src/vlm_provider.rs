@@ -0,0 +1,634 @@
+//! Wire formats of vision model APIs.
+//!
+//! The VLM client builds every request as OpenAI-style chat `messages`: a
+//! `role` and either a string or a list of `text` and `image_url` (PNG data
+//! URL) parts. A [`VlmProvider`] turns those into its API's request body,
+//! and reads the answer back from a streamed or complete response. It also
+//! knows where the API lists its models, for
+//! [`check_model`](crate::vlm::check_model).
+//!
+//! | Provider | `CLI_VISION_VLM_PROVIDER` | Endpoint |
+//! |----------|---------------------------|----------|
+//! | [`OpenAiProvider`] | `openai` (default) | `.../v1/chat/completions` (llama.cpp, vLLM, LM Studio, OpenAI) |
+//! | [`OllamaProvider`] | `ollama` | `http://localhost:11434/api/chat` |
+//! | [`AnthropicProvider`] | `anthropic` | `https://api.anthropic.com/v1/messages` |
+//! | [`GeminiProvider`] | `gemini` | `https://generativelanguage.googleapis.com/v1beta` (the model is added to the URL) |
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::vlm::{VlmConfig, analyze_image};
+//! use cli_vision::vlm_provider::OllamaProvider;
+//!
+//! let config = VlmConfig::new("http://localhost:11434/api/chat").model("llava").provider(OllamaProvider);
+//! let png = std::fs::read("state_1.png").unwrap();
+//! println!("{}", analyze_image(&config, &png, "Describe the screen").unwrap());
+//! ```
+
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+use crate::vlm::{VlmConfig, VlmError, VlmResult, VlmUsage};
+
+/// What a line of a streamed response means
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// More of the answer
+    Text(String),
+    /// The answer is complete
+    Done,
+    /// Part of the stream without answer text (event names, usage, keep-alives)
+    Ignore,
+}
+
+/// A vision model API
+pub trait VlmProvider: std::fmt::Debug + Send + Sync {
+    /// Name used in `CLI_VISION_VLM_PROVIDER`
+    fn name(&self) -> &'static str;
+
+    /// URL requests are sent to
+    fn url(&self, config: &VlmConfig) -> String {
+        config.endpoint.clone()
+    }
+
+    /// HTTP headers sent with every request, besides `Content-Type`
+    fn headers(&self, _config: &VlmConfig) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// Headers carrying an API key; a bearer token unless the API wants otherwise
+    fn auth_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        vec![("Authorization".to_string(), format!("Bearer {}", api_key))]
+    }
+
+    /// Streaming request body for OpenAI-style `messages`; `response_format`
+    /// is an OpenAI `json_schema` response format
+    fn build_request(&self, config: &VlmConfig, messages: &Value, response_format: Option<&Value>) -> Value;
+
+    /// Interpret one line of a streamed response; `None` if the line is not
+    /// part of a stream, so the body is read with [`VlmProvider::parse_response`]
+    fn parse_stream_line(&self, line: &str) -> Option<StreamEvent>;
+
+    /// The answer in a complete, non-streaming response body
+    fn parse_response(&self, body: &str) -> VlmResult<String>;
+
+    /// Token counts in a streamed line or a complete response body, if it
+    /// reports any; counts from several lines are merged with [`VlmUsage::merge`]
+    fn parse_usage(&self, _line: &str) -> Option<VlmUsage> {
+        None
+    }
+
+    /// URL listing the models the server offers
+    fn models_url(&self, config: &VlmConfig) -> String {
+        replace_suffix(&config.endpoint, "/chat/completions", "/models")
+            .unwrap_or_else(|| format!("{}/v1/models", origin(&config.endpoint)))
+    }
+
+    /// Model names in a response from [`VlmProvider::models_url`]
+    fn parse_models(&self, body: &str) -> VlmResult<Vec<String>> {
+        names_in(body, "data", "id")
+    }
+
+    /// URL reporting the server's version as `{"version": ...}`, if the API has one
+    fn version_url(&self, _config: &VlmConfig) -> Option<String> {
+        None
+    }
+}
+
+/// `endpoint` with `suffix` at its end replaced, if it ends with it
+fn replace_suffix(endpoint: &str, suffix: &str, replacement: &str) -> Option<String> {
+    endpoint.trim_end_matches('/').strip_suffix(suffix).map(|base| format!("{}{}", base, replacement))
+}
+
+/// The scheme, host and port of a URL
+fn origin(endpoint: &str) -> &str {
+    let path_start = endpoint.find("://").map_or(0, |i| i + 3);
+    match endpoint[path_start..].find('/') {
+        Some(i) => &endpoint[..path_start + i],
+        None => endpoint.trim_end_matches('/'),
+    }
+}
+
+/// The `field` of every object in the `list` array of a JSON body
+fn names_in(body: &str, list: &str, field: &str) -> VlmResult<Vec<String>> {
+    let response: Value = serde_json::from_str(body).map_err(|e| VlmError::malformed_json(&e, body))?;
+    let Some(items) = response[list].as_array() else {
+        return Err(VlmError::InvalidResponse(format!("no model list in response: {}", body.trim())));
+    };
+    Ok(items.iter().filter_map(|item| item[field].as_str()).map(str::to_string).collect())
+}
+
+/// The provider called `name`, if there is one
+pub fn provider_named(name: &str) -> Option<Arc<dyn VlmProvider>> {
+    match name.trim().to_lowercase().as_str() {
+        "openai" | "" => Some(Arc::new(OpenAiProvider)),
+        "ollama" => Some(Arc::new(OllamaProvider)),
+        "anthropic" => Some(Arc::new(AnthropicProvider)),
+        "gemini" => Some(Arc::new(GeminiProvider)),
+        _ => None,
+    }
+}
+
+/// A part of an OpenAI-style message
+enum Part<'a> {
+    Text(&'a str),
+    Image { media_type: &'a str, data: &'a str },
+}
+
+/// The parts of a message's `content`, which is a string or a list of parts
+fn parts(content: &Value) -> Vec<Part<'_>> {
+    if let Some(text) = content.as_str() {
+        return vec![Part::Text(text)];
+    }
+    content
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|part| match part["type"].as_str() {
+            Some("text") => part["text"].as_str().map(Part::Text),
+            Some("image_url") => {
+                let url = part["image_url"]["url"].as_str()?;
+                let (media_type, data) = url.strip_prefix("data:")?.split_once(";base64,")?;
+                Some(Part::Image { media_type, data })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The text of a message, with its parts joined
+fn text_of(content: &Value) -> String {
+    parts(content)
+        .into_iter()
+        .filter_map(|part| match part {
+            Part::Text(text) => Some(text),
+            Part::Image { .. } => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The schema in an OpenAI `json_schema` response format
+fn response_schema(response_format: &Value) -> Option<&Value> {
+    response_format["json_schema"].get("schema")
+}
+
+/// The JSON after `data:` in a server-sent event line
+fn sse_data(line: &str) -> Option<&str> {
+    line.strip_prefix("data:").map(str::trim_start)
+}
+
+/// A streamed line or a complete body as JSON
+fn line_json(line: &str) -> Option<Value> {
+    serde_json::from_str(sse_data(line).unwrap_or(line)).ok()
+}
+
+/// Token counts stored under `prompt` and `completion` in `value`, if either is there
+fn usage_at(value: &Value, prompt: &str, completion: &str) -> Option<VlmUsage> {
+    let (prompt_tokens, completion_tokens) = (value[prompt].as_u64(), value[completion].as_u64());
+    (prompt_tokens.is_some() || completion_tokens.is_some()).then(|| VlmUsage {
+        prompt_tokens: prompt_tokens.unwrap_or(0),
+        completion_tokens: completion_tokens.unwrap_or(0),
+    })
+}
+
+/// OpenAI-compatible `/v1/chat/completions` (llama.cpp, vLLM, LM Studio, OpenAI)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenAiProvider;
+
+impl VlmProvider for OpenAiProvider {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn version_url(&self, config: &VlmConfig) -> Option<String> {
+        // vLLM answers here; other servers may not
+        Some(format!("{}/version", origin(&config.endpoint)))
+    }
+
+    fn build_request(&self, config: &VlmConfig, messages: &Value, response_format: Option<&Value>) -> Value {
+        let mut request = json!({
+            "model": config.model,
+            "messages": messages,
+            "max_tokens": config.max_tokens,
+            "stream": true,
+            "stream_options": { "include_usage": true }
+        });
+        if let Some(temperature) = config.sampling_temperature() {
+            request["temperature"] = json!(temperature);
+        }
+        if let Some(format) = response_format {
+            request["response_format"] = format.clone();
+        }
+        request
+    }
+
+    fn parse_usage(&self, line: &str) -> Option<VlmUsage> {
+        usage_at(&line_json(line)?["usage"], "prompt_tokens", "completion_tokens")
+    }
+
+    fn parse_stream_line(&self, line: &str) -> Option<StreamEvent> {
+        let data = sse_data(line)?;
+        if data == "[DONE]" {
+            return Some(StreamEvent::Done);
+        }
+        let Ok(json) = serde_json::from_str::<Value>(data) else {
+            return Some(StreamEvent::Ignore);
+        };
+        // Thinking models stream reasoning_content as well as content
+        let delta = &json["choices"][0]["delta"];
+        let text: String = [&delta["content"], &delta["reasoning_content"]].iter().filter_map(|v| v.as_str()).collect();
+        Some(if text.is_empty() { StreamEvent::Ignore } else { StreamEvent::Text(text) })
+    }
+
+    fn parse_response(&self, body: &str) -> VlmResult<String> {
+        let response: Value = serde_json::from_str(body).map_err(|e| VlmError::malformed_json(&e, body))?;
+        let message = &response["choices"][0]["message"];
+        // Try reasoning_content for thinking models
+        match message["content"].as_str().filter(|content| !content.is_empty()).or(message["reasoning_content"].as_str()) {
+            Some(content) => Ok(content.to_string()),
+            None => Err(VlmError::InvalidResponse(format!("no message in response: {}", body.trim()))),
+        }
+    }
+}
+
+/// Ollama's native `/api/chat`, which streams one JSON object per line
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OllamaProvider;
+
+impl VlmProvider for OllamaProvider {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn models_url(&self, config: &VlmConfig) -> String {
+        replace_suffix(&config.endpoint, "/chat", "/tags").unwrap_or_else(|| format!("{}/api/tags", origin(&config.endpoint)))
+    }
+
+    fn parse_models(&self, body: &str) -> VlmResult<Vec<String>> {
+        names_in(body, "models", "name")
+    }
+
+    fn version_url(&self, config: &VlmConfig) -> Option<String> {
+        Some(format!("{}/api/version", origin(&config.endpoint)))
+    }
+
+    fn build_request(&self, config: &VlmConfig, messages: &Value, response_format: Option<&Value>) -> Value {
+        let messages: Vec<Value> = messages
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|message| {
+                let images: Vec<&str> = parts(&message["content"])
+                    .into_iter()
+                    .filter_map(|part| match part {
+                        Part::Image { data, .. } => Some(data),
+                        Part::Text(_) => None,
+                    })
+                    .collect();
+                let mut converted = json!({ "role": message["role"], "content": text_of(&message["content"]) });
+                if !images.is_empty() {
+                    converted["images"] = json!(images);
+                }
+                converted
+            })
+            .collect();
+        let mut options = json!({ "num_predict": config.max_tokens });
+        if let Some(temperature) = config.sampling_temperature() {
+            options["temperature"] = json!(temperature);
+        }
+        let mut request = json!({ "model": config.model, "messages": messages, "stream": true, "options": options });
+        if let Some(format) = response_format {
+            request["format"] = response_schema(format).cloned().unwrap_or_else(|| json!("json"));
+        }
+        request
+    }
+
+    fn parse_stream_line(&self, line: &str) -> Option<StreamEvent> {
+        let json: Value = serde_json::from_str(line).ok()?;
+        if json["done"].as_bool() == Some(true) {
+            // The last object may still carry text when the server did not stream
+            return Some(match json["message"]["content"].as_str() {
+                Some(text) if !text.is_empty() => StreamEvent::Text(text.to_string()),
+                _ => StreamEvent::Done,
+            });
+        }
+        Some(match json["message"]["content"].as_str() {
+            Some(text) if !text.is_empty() => StreamEvent::Text(text.to_string()),
+            _ => StreamEvent::Ignore,
+        })
+    }
+
+    fn parse_usage(&self, line: &str) -> Option<VlmUsage> {
+        usage_at(&line_json(line)?, "prompt_eval_count", "eval_count")
+    }
+
+    fn parse_response(&self, body: &str) -> VlmResult<String> {
+        let response: Value = serde_json::from_str(body).map_err(|e| VlmError::malformed_json(&e, body))?;
+        match response["message"]["content"].as_str() {
+            Some(content) => Ok(content.to_string()),
+            None => Err(VlmError::InvalidResponse(format!("no message in response: {}", body.trim()))),
+        }
+    }
+}
+
+/// Anthropic's Messages API
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnthropicProvider;
+
+/// Version of the Messages API requests are written for
+pub const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+impl VlmProvider for AnthropicProvider {
+    fn name(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn headers(&self, _config: &VlmConfig) -> Vec<(String, String)> {
+        vec![("anthropic-version".to_string(), ANTHROPIC_VERSION.to_string())]
+    }
+
+    fn auth_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        vec![("x-api-key".to_string(), api_key.to_string())]
+    }
+
+    fn models_url(&self, config: &VlmConfig) -> String {
+        replace_suffix(&config.endpoint, "/messages", "/models").unwrap_or_else(|| format!("{}/v1/models", origin(&config.endpoint)))
+    }
+
+    fn build_request(&self, config: &VlmConfig, messages: &Value, _response_format: Option<&Value>) -> Value {
+        // System prompts are a top-level field rather than a message
+        let mut system = Vec::new();
+        let mut converted = Vec::new();
+        for message in messages.as_array().into_iter().flatten() {
+            if message["role"] == "system" {
+                system.push(text_of(&message["content"]));
+                continue;
+            }
+            let content: Vec<Value> = parts(&message["content"])
+                .into_iter()
+                .map(|part| match part {
+                    Part::Text(text) => json!({ "type": "text", "text": text }),
+                    Part::Image { media_type, data } => json!({
+                        "type": "image",
+                        "source": { "type": "base64", "media_type": media_type, "data": data }
+                    }),
+                })
+                .collect();
+            converted.push(json!({ "role": message["role"], "content": content }));
+        }
+        let mut request = json!({
+            "model": config.model,
+            "messages": converted,
+            "max_tokens": config.max_tokens,
+            "stream": true
+        });
+        if !system.is_empty() {
+            request["system"] = json!(system.join("\n\n"));
+        }
+        if let Some(temperature) = config.sampling_temperature() {
+            request["temperature"] = json!(temperature);
+        }
+        request
+    }
+
+    fn parse_stream_line(&self, line: &str) -> Option<StreamEvent> {
+        if line.starts_with("event:") {
+            return Some(StreamEvent::Ignore);
+        }
+        let json: Value = serde_json::from_str(sse_data(line)?).ok()?;
+        Some(match json["type"].as_str() {
+            Some("content_block_delta") => match json["delta"]["text"].as_str() {
+                Some(text) => StreamEvent::Text(text.to_string()),
+                None => StreamEvent::Ignore,
+            },
+            Some("message_stop") => StreamEvent::Done,
+            _ => StreamEvent::Ignore,
+        })
+    }
+
+    fn parse_usage(&self, line: &str) -> Option<VlmUsage> {
+        // Input tokens come with message_start, output tokens with message_delta
+        let json = line_json(line)?;
+        let usage = if json["type"] == "message_start" { &json["message"]["usage"] } else { &json["usage"] };
+        usage_at(usage, "input_tokens", "output_tokens")
+    }
+
+    fn parse_response(&self, body: &str) -> VlmResult<String> {
+        let response: Value = serde_json::from_str(body).map_err(|e| VlmError::malformed_json(&e, body))?;
+        let Some(content) = response["content"].as_array() else {
+            return Err(VlmError::InvalidResponse(format!("no message in response: {}", body.trim())));
+        };
+        Ok(content.iter().filter_map(|block| block["text"].as_str()).collect())
+    }
+}
+
+/// Google's Gemini API; the endpoint is the API base, e.g.
+/// `https://generativelanguage.googleapis.com/v1beta`, or a full
+/// `...:streamGenerateContent` URL
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GeminiProvider;
+
+impl VlmProvider for GeminiProvider {
+    fn name(&self) -> &'static str {
+        "gemini"
+    }
+
+    fn url(&self, config: &VlmConfig) -> String {
+        if config.endpoint.contains(":streamGenerateContent") || config.endpoint.contains(":generateContent") {
+            return config.endpoint.clone();
+        }
+        format!("{}/models/{}:streamGenerateContent?alt=sse", config.endpoint.trim_end_matches('/'), config.model)
+    }
+
+    fn auth_headers(&self, api_key: &str) -> Vec<(String, String)> {
+        vec![("x-goog-api-key".to_string(), api_key.to_string())]
+    }
+
+    fn models_url(&self, config: &VlmConfig) -> String {
+        let base = config.endpoint.split("/models/").next().unwrap_or_default();
+        format!("{}/models", base.trim_end_matches('/'))
+    }
+
+    fn parse_models(&self, body: &str) -> VlmResult<Vec<String>> {
+        // Names read `models/gemini-2.0-flash`; requests use the part after the slash
+        let names = names_in(body, "models", "name")?;
+        Ok(names.into_iter().map(|name| name.strip_prefix("models/").map(str::to_string).unwrap_or(name)).collect())
+    }
+
+    fn build_request(&self, config: &VlmConfig, messages: &Value, response_format: Option<&Value>) -> Value {
+        let mut system = Vec::new();
+        let mut contents = Vec::new();
+        for message in messages.as_array().into_iter().flatten() {
+            if message["role"] == "system" {
+                system.push(json!({ "text": text_of(&message["content"]) }));
+                continue;
+            }
+            let parts: Vec<Value> = parts(&message["content"])
+                .into_iter()
+                .map(|part| match part {
+                    Part::Text(text) => json!({ "text": text }),
+                    Part::Image { media_type, data } => json!({ "inline_data": { "mime_type": media_type, "data": data } }),
+                })
+                .collect();
+            let role = if message["role"] == "assistant" { "model" } else { "user" };
+            contents.push(json!({ "role": role, "parts": parts }));
+        }
+        let mut generation = json!({ "maxOutputTokens": config.max_tokens });
+        if let Some(temperature) = config.sampling_temperature() {
+            generation["temperature"] = json!(temperature);
+        }
+        // Gemini accepts only a subset of JSON Schema, so the schema stays in the prompt
+        if response_format.is_some() {
+            generation["responseMimeType"] = json!("application/json");
+        }
+        let mut request = json!({ "contents": contents, "generationConfig": generation });
+        if !system.is_empty() {
+            request["systemInstruction"] = json!({ "parts": system });
+        }
+        request
+    }
+
+    fn parse_stream_line(&self, line: &str) -> Option<StreamEvent> {
+        let json: Value = serde_json::from_str(sse_data(line)?).ok()?;
+        let text = candidate_text(&json);
+        Some(if text.is_empty() { StreamEvent::Ignore } else { StreamEvent::Text(text) })
+    }
+
+    fn parse_response(&self, body: &str) -> VlmResult<String> {
+        let response: Value = serde_json::from_str(body).map_err(|e| VlmError::malformed_json(&e, body))?;
+        // Without `alt=sse` the streaming endpoint answers with an array of chunks
+        let chunks = match &response {
+            Value::Array(chunks) => chunks.iter().collect(),
+            chunk => vec![chunk],
+        };
+        if chunks.iter().all(|chunk| chunk.get("candidates").is_none()) {
+            return Err(VlmError::InvalidResponse(format!("no candidates in response: {}", body.trim())));
+        }
+        Ok(chunks.into_iter().map(candidate_text).collect())
+    }
+
+    fn parse_usage(&self, line: &str) -> Option<VlmUsage> {
+        // Every chunk repeats the counts so far; a complete body may be an array of chunks
+        let json = line_json(line)?;
+        let last = match &json {
+            Value::Array(chunks) => chunks.last()?,
+            chunk => chunk,
+        };
+        usage_at(&last["usageMetadata"], "promptTokenCount", "candidatesTokenCount")
+    }
+}
+
+/// The text of the first candidate in a Gemini response chunk
+fn candidate_text(chunk: &Value) -> String {
+    chunk["candidates"][0]["content"]["parts"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|part| part["text"].as_str())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screenshot_messages() -> Value {
+        json!([
+            { "role": "user", "content": [
+                { "type": "image_url", "image_url": { "url": "data:image/png;base64,UE5H" } },
+                { "type": "text", "text": "Describe" }
+            ] },
+            { "role": "assistant", "content": "A menu" },
+            { "role": "user", "content": "Which item is selected?" }
+        ])
+    }
+
+    #[test]
+    fn test_requests_carry_images_in_each_format() {
+        let config = VlmConfig::new("http://127.0.0.1:11434/api/chat").model("llava");
+        let messages = screenshot_messages();
+
+        let ollama = OllamaProvider.build_request(&config, &messages, None);
+        assert_eq!(ollama["messages"][0]["images"], json!(["UE5H"]));
+        assert_eq!(ollama["messages"][0]["content"], "Describe");
+        assert_eq!(ollama["messages"][1]["content"], "A menu");
+
+        let anthropic = AnthropicProvider.build_request(&config, &messages, None);
+        assert_eq!(anthropic["messages"][0]["content"][0]["source"]["data"], "UE5H");
+        assert_eq!(anthropic["messages"][2]["content"][0]["text"], "Which item is selected?");
+
+        let config = VlmConfig::new("https://generativelanguage.googleapis.com/v1beta/").model("gemini-2.0-flash");
+        let gemini = GeminiProvider.build_request(&config, &messages, Some(&json!({ "type": "json_schema" })));
+        assert_eq!(gemini["contents"][0]["parts"][0]["inline_data"]["mime_type"], "image/png");
+        assert_eq!(gemini["contents"][1]["role"], "model");
+        assert_eq!(gemini["generationConfig"]["responseMimeType"], "application/json");
+        assert_eq!(
+            GeminiProvider.url(&config),
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash:streamGenerateContent?alt=sse"
+        );
+    }
+
+    #[test]
+    fn test_answers_are_read_from_each_format() {
+        let text = |event| match event {
+            Some(StreamEvent::Text(text)) => text,
+            other => panic!("expected text, got {:?}", other),
+        };
+        assert_eq!(text(OpenAiProvider.parse_stream_line("data: {\"choices\":[{\"delta\":{\"content\":\"Menu\"}}]}")), "Menu");
+        assert_eq!(OpenAiProvider.parse_stream_line("data: [DONE]"), Some(StreamEvent::Done));
+        assert_eq!(text(OllamaProvider.parse_stream_line("{\"message\":{\"content\":\"Menu\"},\"done\":false}")), "Menu");
+        assert_eq!(OllamaProvider.parse_stream_line("{\"message\":{\"content\":\"\"},\"done\":true}"), Some(StreamEvent::Done));
+        assert_eq!(AnthropicProvider.parse_stream_line("event: content_block_delta"), Some(StreamEvent::Ignore));
+        assert_eq!(
+            text(AnthropicProvider.parse_stream_line("data: {\"type\":\"content_block_delta\",\"delta\":{\"type\":\"text_delta\",\"text\":\"Menu\"}}")),
+            "Menu"
+        );
+        assert_eq!(text(GeminiProvider.parse_stream_line("data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Menu\"}]}}]}")), "Menu");
+
+        assert_eq!(AnthropicProvider.parse_response("{\"content\":[{\"type\":\"text\",\"text\":\"A dialog\"}]}").unwrap(), "A dialog");
+        assert_eq!(
+            GeminiProvider.parse_response("[{\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"A \"}]}}]},{\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"dialog\"}]}}]}]").unwrap(),
+            "A dialog"
+        );
+        assert!(OllamaProvider.parse_response("{\"error\":\"model not found\"}").is_err());
+        assert!(provider_named("Gemini").is_some_and(|p| p.name() == "gemini"));
+        assert!(provider_named("bedrock").is_none());
+    }
+
+    #[test]
+    fn test_usage_is_read_from_each_format() {
+        let usage = |prompt_tokens, completion_tokens| Some(VlmUsage { prompt_tokens, completion_tokens });
+        assert_eq!(OpenAiProvider.parse_usage("data: {\"choices\":[],\"usage\":{\"prompt_tokens\":812,\"completion_tokens\":40}}"), usage(812, 40));
+        assert_eq!(OpenAiProvider.parse_usage("data: {\"choices\":[{\"delta\":{\"content\":\"Menu\"}}],\"usage\":null}"), None);
+        assert_eq!(OllamaProvider.parse_usage("{\"done\":true,\"prompt_eval_count\":812,\"eval_count\":40}"), usage(812, 40));
+        assert_eq!(
+            AnthropicProvider.parse_usage("data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":812,\"output_tokens\":1}}}"),
+            usage(812, 1)
+        );
+        assert_eq!(AnthropicProvider.parse_usage("data: {\"type\":\"message_delta\",\"usage\":{\"output_tokens\":40}}"), usage(0, 40));
+        assert_eq!(
+            GeminiProvider.parse_usage("[{\"usageMetadata\":{\"promptTokenCount\":812}},{\"usageMetadata\":{\"promptTokenCount\":812,\"candidatesTokenCount\":40}}]"),
+            usage(812, 40)
+        );
+    }
+
+    #[test]
+    fn test_models_are_listed_in_each_format() {
+        let config = |endpoint: &str| VlmConfig::new(endpoint);
+        assert_eq!(OpenAiProvider.models_url(&config("http://localhost:8080/v1/chat/completions")), "http://localhost:8080/v1/models");
+        assert_eq!(OpenAiProvider.models_url(&config("http://localhost:8080")), "http://localhost:8080/v1/models");
+        assert_eq!(OllamaProvider.models_url(&config("http://localhost:11434/api/chat")), "http://localhost:11434/api/tags");
+        assert_eq!(AnthropicProvider.models_url(&config("https://api.anthropic.com/v1/messages")), "https://api.anthropic.com/v1/models");
+        assert_eq!(
+            GeminiProvider.models_url(&config("https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash:streamGenerateContent")),
+            "https://generativelanguage.googleapis.com/v1beta/models"
+        );
+        assert_eq!(OllamaProvider.version_url(&config("http://localhost:11434/api/chat")).unwrap(), "http://localhost:11434/api/version");
+
+        assert_eq!(OpenAiProvider.parse_models("{\"object\":\"list\",\"data\":[{\"id\":\"qwen3\"},{\"id\":\"llava\"}]}").unwrap(), ["qwen3", "llava"]);
+        assert_eq!(OllamaProvider.parse_models("{\"models\":[{\"name\":\"llava:latest\"}]}").unwrap(), ["llava:latest"]);
+        assert_eq!(GeminiProvider.parse_models("{\"models\":[{\"name\":\"models/gemini-2.0-flash\"}]}").unwrap(), ["gemini-2.0-flash"]);
+        assert!(OpenAiProvider.parse_models("{\"error\":\"not found\"}").is_err());
+    }
+}
@@ -0,0 +1,125 @@
+//! Pacing of VLM requests.
+//!
+//! Shared and hosted endpoints cap requests per minute and answer 429 past
+//! the cap. A [`RateLimiter`] is a token bucket that holds requests back
+//! until the endpoint's budget allows them: with `burst` 1 (the default)
+//! they are spaced evenly, one every `60s / per_minute`. Clones share one
+//! bucket, so every analysis holding a clone counts against the same limit,
+//! whichever thread it runs on.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use cli_vision::vlm::{VlmConfig, analyze_image};
+//! use cli_vision::vlm_rate::RateLimiter;
+//!
+//! let config = VlmConfig::default().rate_limit(RateLimiter::per_minute(20));
+//! let png = std::fs::read("state_1.png").unwrap();
+//! // At most 20 requests a minute, however many threads share `config`
+//! analyze_image(&config, &png, "Describe the screen").unwrap();
+//! ```
+
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config;
+
+/// Token bucket shared by every clone
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    /// Requests allowed per minute
+    pub per_minute: u32,
+    /// Requests that may be sent at once after a quiet spell
+    pub burst: u32,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Requests that can be sent now; negative when callers are waiting
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Allow `requests` a minute, spaced evenly
+    pub fn per_minute(requests: u32) -> Self {
+        Self::with_burst(requests.max(1), 1)
+    }
+
+    /// The process-wide limiter for `CLI_VISION_VLM_RPM`, if it is set.
+    ///
+    /// Every call returns a clone of the same limiter, so configs created
+    /// separately in one run still share the budget.
+    pub fn from_env() -> Option<Self> {
+        static SHARED: OnceLock<Option<RateLimiter>> = OnceLock::new();
+        SHARED.get_or_init(|| config::get().vlm.requests_per_minute.map(Self::per_minute)).clone()
+    }
+
+    /// Let up to `requests` go out at once before pacing starts
+    pub fn burst(self, requests: u32) -> Self {
+        Self::with_burst(self.per_minute, requests.max(1))
+    }
+
+    fn with_burst(per_minute: u32, burst: u32) -> Self {
+        Self {
+            per_minute,
+            burst,
+            bucket: Arc::new(Mutex::new(Bucket { tokens: burst as f64, updated: Instant::now() })),
+        }
+    }
+
+    /// Time it takes to earn one request
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(60) / self.per_minute
+    }
+
+    /// Take a request from the bucket and return how long to wait before sending it.
+    ///
+    /// The bucket may go into debt, so callers queue up in the order they
+    /// asked without holding the lock while they wait.
+    pub fn reserve(&self) -> Duration {
+        let interval = self.interval().as_secs_f64();
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let earned = now.duration_since(bucket.updated).as_secs_f64() / interval;
+        bucket.tokens = (bucket.tokens + earned).min(self.burst as f64) - 1.0;
+        bucket.updated = now;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens * interval)
+        }
+    }
+
+    /// Block until a request may be sent, returning how long that took
+    pub fn acquire(&self) -> Duration {
+        let wait = self.reserve();
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+        wait
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_are_paced_across_clones() {
+        let limiter = RateLimiter::per_minute(600);
+        assert_eq!(limiter.interval(), Duration::from_millis(100));
+        assert_eq!(limiter.reserve(), Duration::ZERO);
+        // A clone shares the bucket, so it queues behind the first request
+        let second = limiter.clone().reserve();
+        assert!(second > Duration::from_millis(90) && second <= Duration::from_millis(100), "{:?}", second);
+        let third = limiter.reserve();
+        assert!(third > Duration::from_millis(190) && third <= Duration::from_millis(200), "{:?}", third);
+
+        let bursty = RateLimiter::per_minute(60).burst(3);
+        assert_eq!([bursty.reserve(), bursty.reserve(), bursty.reserve()], [Duration::ZERO; 3]);
+        assert!(bursty.reserve() > Duration::from_millis(900));
+    }
+}
@@ -10,9 +10,11 @@ fn test_mock_capture_process() {
     let screenshots_dir = PathBuf::from("./test_screenshots");
     fs::create_dir_all(&screenshots_dir).expect("Failed to create screenshots dir");
 
-    let mut snapshot_config = SnapshotConfig::default();
-    snapshot_config.output_dir = screenshots_dir.clone();
-    snapshot_config.include_metadata = true;
+    let snapshot_config = SnapshotConfig {
+        output_dir: screenshots_dir.clone(),
+        include_metadata: true,
+        ..SnapshotConfig::default()
+    };
 
     let mut backend = MockFramebuffer::with_color(800, 600, [50, 50, 50]);
     backend.draw_rect(10, 10, 100, 30, [255, 255, 255]);
@@ -53,10 +55,12 @@ fn test_capture_creates_metadata() {
     let screenshots_dir = PathBuf::from("./test_screenshots_meta");
     fs::create_dir_all(&screenshots_dir).expect("Failed to create screenshots dir");
 
-    let mut snapshot_config = SnapshotConfig::default();
-    snapshot_config.output_dir = screenshots_dir.clone();
-    snapshot_config.include_metadata = true;
-    snapshot_config.include_manifest = true;
+    let snapshot_config = SnapshotConfig {
+        output_dir: screenshots_dir.clone(),
+        include_metadata: true,
+        include_manifest: true,
+        ..SnapshotConfig::default()
+    };
 
     let mut backend = MockFramebuffer::new(640, 480);
     let result = capture_with_backend(&mut backend, &snapshot_config);
@@ -0,0 +1,61 @@
+//! TUI Harness - the `cli_vision` library under its repository name.
+//!
+//! The library is published as `cli_vision` but lives in the `tui_harness`
+//! repository and will eventually be renamed. This crate re-exports all of
+//! it, so code written against `tui_harness::...` keeps compiling across
+//! the rename:
+//!
+//! ```rust,no_run
+//! use tui_harness::scenario::Scenario;
+//! use tui_harness::snapshot::{run_with_options, RunOptions};
+//!
+//! let scenario = Scenario::from_file("menu.json").unwrap();
+//! let states = run_with_options(
+//!     &scenario.binary.to_string_lossy(),
+//!     &scenario.args,
+//!     &scenario.inputs(),
+//!     100,
+//!     scenario.terminal_size().unwrap_or_default(),
+//!     &RunOptions::default(),
+//! )
+//! .unwrap();
+//! ```
+//!
+//! Modules keep their `cli_vision` names, except that session comparison is
+//! [`diff`]; `tui_harness::compare` remains as a deprecated alias.
+//! Everything in [`prelude`] is what most harness code needs.
+
+pub use cli_vision::*;
+
+/// Comparison of captured states and sessions (`cli_vision::compare`)
+pub mod diff {
+    pub use cli_vision::compare::*;
+}
+
+/// Old name of [`diff`]
+#[deprecated(since = "0.1.0", note = "renamed to `tui_harness::diff`")]
+pub mod compare {
+    pub use cli_vision::compare::*;
+}
+
+/// Types and functions most harness code needs
+pub mod prelude {
+    pub use cli_vision::harness::{run_harness, HarnessConfig, InputAction, StateConfig, StateResult};
+    pub use cli_vision::scenario::{validate_scenario, Scenario};
+    pub use cli_vision::snapshot::{
+        run_with_options, CaptureBackend, PtyBackend, PtyBackendConfig, RunOptions, StateCaptureResult, TerminalSize,
+    };
+    pub use cli_vision::vlm::{analyze_image, VlmConfig, VlmVerdict};
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_items_are_the_cli_vision_items() {
+        let config: cli_vision::VlmConfig = crate::prelude::VlmConfig::default();
+        assert_eq!(config.model, crate::vlm::VlmConfig::default().model);
+        let _: fn(&str) -> _ = crate::scenario::validate_scenario;
+        let aligned: Vec<cli_vision::compare::StateAlignment> = crate::diff::align_states::<u8, _>(&[], &[], 0.5, |_, _| 0.0);
+        assert!(aligned.is_empty());
+    }
+}